@@ -0,0 +1,31 @@
+// Feeds arbitrary bytes to the Redis reply parsers: as text for CLUSTER NODES, CLUSTER INFO and
+// INFO, and as a RESP reply for CLUSTER SLOTS.
+//
+//   cargo +nightly fuzz run redis_parse
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/redis_parse.rs"]
+mod redis_parse;
+#[allow(dead_code)]
+#[path = "../../src/topology.rs"]
+mod topology;
+
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    for node in redis_parse::parse_cluster_nodes(&text) {
+        node.address();
+        node.serves(0);
+    }
+    redis_parse::parse_cluster_info(&text);
+    redis_parse::parse_info(&text);
+
+    if let Ok(reply) = redis::parse_redis_value(data) {
+        for assignment in redis_parse::parse_cluster_slots(&reply) {
+            assert!(assignment.start_slot <= assignment.end_slot);
+        }
+    }
+});
@@ -0,0 +1,77 @@
+// Bearer-token check for admin operations
+//
+// The token comes from ADMIN_TOKEN. When it isn't set, protected endpoints are refused
+// outright rather than left open. Everything under /admin goes through require_admin; routes
+// elsewhere that change state call authorize themselves.
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header;
+use actix_web::middleware::Next;
+use actix_web::{HttpRequest, HttpResponse};
+
+use crate::get_env_or;
+
+// Overrides ADMIN_TOKEN when registered as app data, so an app (or a test) can be given its
+// token without touching the process environment
+#[derive(Clone)]
+pub struct AdminToken(pub String);
+
+fn expected_token(req: &HttpRequest) -> String {
+    match req.app_data::<AdminToken>() {
+        Some(AdminToken(token)) => token.clone(),
+        None => get_env_or("ADMIN_TOKEN", ""),
+    }
+}
+
+// Compares without returning early, so response timing doesn't leak the matching prefix
+pub fn tokens_match(expected: &str, provided: &str) -> bool {
+    expected.len() == provided.len()
+        && expected.bytes().zip(provided.bytes()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+// Accepts `Authorization: Bearer <token>` or `X-Admin-Token: <token>`
+pub fn provided_token(req: &HttpRequest) -> Option<&str> {
+    let headers = req.headers();
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| headers.get("x-admin-token").and_then(|v| v.to_str().ok()))
+        .map(str::trim)
+}
+
+// The refusal is boxed to keep the Ok path small
+pub fn authorize(req: &HttpRequest) -> Result<(), Box<HttpResponse>> {
+    let expected = expected_token(req);
+    if expected.is_empty() {
+        let response = HttpResponse::Forbidden().json(serde_json::json!({
+            "status": "error",
+            "error": "Admin operations are disabled; set ADMIN_TOKEN to enable them"
+        }));
+        return Err(Box::new(response));
+    }
+    match provided_token(req) {
+        Some(token) if tokens_match(&expected, token) => Ok(()),
+        _ => {
+            let response = HttpResponse::Unauthorized()
+                .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+                .json(serde_json::json!({
+                    "status": "error",
+                    "error": "Missing or invalid admin token"
+                }));
+            Err(Box::new(response))
+        }
+    }
+}
+
+// Middleware for a whole scope: nothing inside it runs without the admin token
+pub async fn require_admin(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    if let Err(response) = authorize(req.request()) {
+        return Ok(req.into_response(*response));
+    }
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
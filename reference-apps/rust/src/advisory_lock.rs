@@ -0,0 +1,281 @@
+// Postgres advisory locks
+//
+// Session-scoped locks belong to the connection that took them, so each lock acquired through
+// these endpoints keeps its own connection open in HELD_LOCKS until it is released. Transaction-
+// scoped locks are taken inside a transaction and released by Postgres when it commits.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::{web, HttpResponse, Responder};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use tokio_postgres::error::SqlState;
+
+use crate::postgres_client;
+
+const DEFAULT_TIMEOUT_MS: u64 = 5_000;
+const MAX_HOLD_MS: u64 = 30_000;
+
+struct HeldLock {
+    client: tokio_postgres::Client,
+    acquired_at: chrono::DateTime<chrono::Utc>,
+}
+
+lazy_static! {
+    static ref HELD_LOCKS: Mutex<HashMap<i64, HeldLock>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LockScope {
+    #[default]
+    Session,
+    Transaction,
+}
+
+#[derive(Deserialize)]
+pub struct LockQuery {
+    #[serde(default)]
+    scope: LockScope,
+    // How long to wait for the lock before giving up (acquire only)
+    timeout_ms: Option<u64>,
+    // How long a transaction-scoped lock is held before the transaction commits
+    hold_ms: Option<u64>,
+}
+
+fn error_response(status: actix_web::http::StatusCode, key: i64, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "key": key, "error": error }))
+}
+
+fn is_held_here(key: i64) -> bool {
+    HELD_LOCKS.lock().map(|locks| locks.contains_key(&key)).unwrap_or(false)
+}
+
+fn hold_session_lock(key: i64, client: tokio_postgres::Client) {
+    if let Ok(mut locks) = HELD_LOCKS.lock() {
+        locks.insert(key, HeldLock { client, acquired_at: chrono::Utc::now() });
+    }
+}
+
+// Blocks until the lock is free or timeout_ms passes (lock_timeout applies to advisory locks too)
+pub async fn acquire_lock(path: web::Path<i64>, query: web::Query<LockQuery>) -> impl Responder {
+    let key = path.into_inner();
+    let timeout_ms = query.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS);
+    let hold_ms = query.hold_ms.unwrap_or(0);
+    if hold_ms > MAX_HOLD_MS {
+        return error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            key,
+            format!("hold_ms must be at most {}", MAX_HOLD_MS),
+        );
+    }
+    if query.scope == LockScope::Session && is_held_here(key) {
+        return error_response(actix_web::http::StatusCode::CONFLICT, key, "Lock is already held by this API".to_string());
+    }
+
+    let mut client = match postgres_client().await {
+        Ok(client) => client,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, key, e),
+    };
+
+    let started = Instant::now();
+    let result = match query.scope {
+        LockScope::Session => {
+            match client.batch_execute(&format!("SET lock_timeout = {}", timeout_ms)).await {
+                Ok(()) => client.execute("SELECT pg_advisory_lock($1)", &[&key]).await.map(|_| ()),
+                Err(e) => Err(e),
+            }
+        }
+        LockScope::Transaction => match client.transaction().await {
+            Ok(transaction) => {
+                let locked = async {
+                    transaction.batch_execute(&format!("SET LOCAL lock_timeout = {}", timeout_ms)).await?;
+                    transaction.execute("SELECT pg_advisory_xact_lock($1)", &[&key]).await?;
+                    Ok::<_, tokio_postgres::Error>(())
+                }
+                .await;
+                match locked {
+                    Ok(()) => {
+                        let waited = started.elapsed();
+                        tokio::time::sleep(Duration::from_millis(hold_ms)).await;
+                        // Committing ends the transaction and releases the lock
+                        return match transaction.commit().await {
+                            Ok(()) => HttpResponse::Ok().json(serde_json::json!({
+                                "status": "released",
+                                "key": key,
+                                "scope": "transaction",
+                                "waited_ms": waited.as_millis() as u64,
+                                "held_ms": hold_ms
+                            })),
+                            Err(e) => error_response(
+                                actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+                                key,
+                                format!("Commit failed: {}", e),
+                            ),
+                        };
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            Err(e) => Err(e),
+        },
+    };
+
+    match result {
+        Ok(()) => {
+            let waited_ms = started.elapsed().as_millis() as u64;
+            hold_session_lock(key, client);
+            HttpResponse::Ok().json(serde_json::json!({
+                "status": "acquired",
+                "key": key,
+                "scope": "session",
+                "waited_ms": waited_ms
+            }))
+        }
+        Err(e) if e.code() == Some(&SqlState::LOCK_NOT_AVAILABLE) => error_response(
+            actix_web::http::StatusCode::CONFLICT,
+            key,
+            format!("Lock not acquired within {}ms", timeout_ms),
+        ),
+        Err(e) => error_response(
+            actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+            key,
+            format!("Lock failed: {}", e),
+        ),
+    }
+}
+
+// Never waits; reports whether the lock was free
+pub async fn try_lock(path: web::Path<i64>, query: web::Query<LockQuery>) -> impl Responder {
+    let key = path.into_inner();
+    if query.scope == LockScope::Session && is_held_here(key) {
+        return error_response(actix_web::http::StatusCode::CONFLICT, key, "Lock is already held by this API".to_string());
+    }
+
+    let mut client = match postgres_client().await {
+        Ok(client) => client,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, key, e),
+    };
+
+    let acquired = match query.scope {
+        LockScope::Session => client
+            .query_one("SELECT pg_try_advisory_lock($1)", &[&key])
+            .await
+            .map(|row| row.get::<_, bool>(0)),
+        // The lock is released again as soon as the transaction commits
+        LockScope::Transaction => match client.transaction().await {
+            Ok(transaction) => {
+                let acquired = transaction
+                    .query_one("SELECT pg_try_advisory_xact_lock($1)", &[&key])
+                    .await
+                    .map(|row| row.get::<_, bool>(0));
+                match transaction.commit().await {
+                    Ok(()) => acquired,
+                    Err(e) => Err(e),
+                }
+            }
+            Err(e) => Err(e),
+        },
+    };
+
+    match acquired {
+        Ok(acquired) => {
+            let scope = match query.scope {
+                LockScope::Session => {
+                    if acquired {
+                        hold_session_lock(key, client);
+                    }
+                    "session"
+                }
+                LockScope::Transaction => "transaction",
+            };
+            HttpResponse::Ok().json(serde_json::json!({
+                "status": if acquired { "acquired" } else { "locked" },
+                "key": key,
+                "scope": scope,
+                "acquired": acquired
+            }))
+        }
+        Err(e) => error_response(
+            actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+            key,
+            format!("Lock failed: {}", e),
+        ),
+    }
+}
+
+// Releases a session-scoped lock taken by acquire or try
+pub async fn release_lock(path: web::Path<i64>) -> impl Responder {
+    let key = path.into_inner();
+    let held = HELD_LOCKS.lock().ok().and_then(|mut locks| locks.remove(&key));
+    let held = match held {
+        Some(held) => held,
+        None => {
+            return error_response(
+                actix_web::http::StatusCode::NOT_FOUND,
+                key,
+                "No session lock held by this API for this key".to_string(),
+            )
+        }
+    };
+
+    let held_ms = (chrono::Utc::now() - held.acquired_at).num_milliseconds();
+    // Dropping the connection would release the lock too; unlocking explicitly shows the API
+    match held.client.query_one("SELECT pg_advisory_unlock($1)", &[&key]).await {
+        Ok(row) => HttpResponse::Ok().json(serde_json::json!({
+            "status": "released",
+            "key": key,
+            "released": row.get::<_, bool>(0),
+            "held_ms": held_ms
+        })),
+        Err(e) => error_response(
+            actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+            key,
+            format!("Unlock failed (connection closed, lock released): {}", e),
+        ),
+    }
+}
+
+// Advisory locks currently held on the server, by any session
+pub async fn list_locks() -> impl Responder {
+    let held_here: Vec<serde_json::Value> = HELD_LOCKS
+        .lock()
+        .map(|locks| {
+            locks
+                .iter()
+                .map(|(key, held)| serde_json::json!({ "key": key, "acquired_at": held.acquired_at.to_rfc3339() }))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let client = match postgres_client().await {
+        Ok(client) => client,
+        Err(e) => {
+            return HttpResponse::ServiceUnavailable().json(serde_json::json!({ "status": "error", "error": e }))
+        }
+    };
+
+    // Single bigint keys are split into classid (high 32 bits) and objid (low 32 bits)
+    let sql = "SELECT ((classid::bigint << 32) | objid::bigint) AS key, pid, mode, granted \
+               FROM pg_locks WHERE locktype = 'advisory' AND objsubid = 1 ORDER BY key, granted DESC";
+    match client.query(sql, &[]).await {
+        Ok(rows) => HttpResponse::Ok().json(serde_json::json!({
+            "held_by_api": held_here,
+            "server_locks": rows
+                .iter()
+                .map(|row| serde_json::json!({
+                    "key": row.get::<_, i64>("key"),
+                    "pid": row.get::<_, i32>("pid"),
+                    "mode": row.get::<_, String>("mode"),
+                    "granted": row.get::<_, bool>("granted")
+                }))
+                .collect::<Vec<_>>()
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "status": "error",
+            "error": format!("Query failed: {}", e)
+        })),
+    }
+}
@@ -0,0 +1,243 @@
+// Sampled request/response audit log with redaction of sensitive fields
+//
+// Only JSON bodies up to AUDIT_MAX_BODY_BYTES are captured, so uploads and streamed
+// exports pass through untouched. Recent entries are kept in memory for /admin/requests.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use actix_web::body::{self, BodySize, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderMap};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpResponse, Responder};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::get_env_or;
+use crate::redact;
+
+const REDACTED: &str = "[REDACTED]";
+const SENSITIVE_KEYS: &[&str] = &["password", "token", "secret"];
+
+// Paths that are never audited (the audit log itself and scrape endpoints)
+const EXCLUDED_PREFIXES: &[&str] = &["/admin/requests", "/metrics", "/health"];
+
+#[derive(Serialize, Clone)]
+pub struct AuditEntry {
+    pub id: u64,
+    pub timestamp: String,
+    pub method: String,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    pub status: u16,
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_body: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_body: Option<Value>,
+}
+
+lazy_static! {
+    static ref AUDIT_LOG: Mutex<VecDeque<AuditEntry>> = Mutex::new(VecDeque::new());
+    static ref NEXT_ID: AtomicU64 = AtomicU64::new(1);
+}
+
+pub fn is_sensitive_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SENSITIVE_KEYS.iter().any(|s| key.contains(s))
+}
+
+// Recursively replaces the values of sensitive object keys
+pub fn redact_value(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                if is_sensitive_key(key) {
+                    *v = Value::String(REDACTED.to_string());
+                } else {
+                    redact_value(v);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_value),
+        _ => {}
+    }
+}
+
+// Masks known secrets inside string values, e.g. a Vault password returned under a "value" key
+fn scrub_strings(value: &mut Value) {
+    match value {
+        Value::String(s) => *s = redact::scrub(s),
+        Value::Object(map) => map.values_mut().for_each(scrub_strings),
+        Value::Array(items) => items.iter_mut().for_each(scrub_strings),
+        _ => {}
+    }
+}
+
+pub fn redact_query(query: &str) -> String {
+    query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((key, _)) if is_sensitive_key(key) => format!("{}={}", key, REDACTED),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.starts_with("application/json"))
+        .unwrap_or(false)
+}
+
+fn content_length(headers: &HeaderMap) -> Option<usize> {
+    headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+}
+
+// Bodies over the size limit are noted but not stored, since a truncated JSON document can't be redacted
+fn capture_body(bytes: &[u8], max_bytes: usize) -> Option<Value> {
+    if bytes.is_empty() {
+        return None;
+    }
+    if bytes.len() > max_bytes {
+        return Some(serde_json::json!({ "omitted": "body exceeds AUDIT_MAX_BODY_BYTES", "size": bytes.len() }));
+    }
+    let mut value: Value = serde_json::from_slice(bytes).ok()?;
+    redact_value(&mut value);
+    scrub_strings(&mut value);
+    Some(value)
+}
+
+fn record(entry: AuditEntry) {
+    let capacity: usize = get_env_or("AUDIT_BUFFER_SIZE", "200").parse().unwrap_or(200);
+    if let Ok(mut log) = AUDIT_LOG.lock() {
+        log.push_back(entry);
+        while log.len() > capacity {
+            log.pop_front();
+        }
+    }
+}
+
+pub fn recent_entries() -> Vec<AuditEntry> {
+    AUDIT_LOG
+        .lock()
+        .map(|log| log.iter().rev().cloned().collect())
+        .unwrap_or_default()
+}
+
+// Puts an already-read body back so downstream extractors can consume it
+pub fn restore_payload(req: &mut ServiceRequest, bytes: web::Bytes) {
+    let (_, mut payload) = actix_http::h1::Payload::create(true);
+    payload.unread_data(bytes);
+    req.set_payload(payload.into());
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AuditConfig {
+    pub sample_rate: f64,
+    pub max_body_bytes: usize,
+}
+
+impl AuditConfig {
+    pub fn from_env() -> Self {
+        AuditConfig {
+            sample_rate: get_env_or("AUDIT_SAMPLE_RATE", "0.1").parse().unwrap_or(0.1),
+            max_body_bytes: get_env_or("AUDIT_MAX_BODY_BYTES", "4096").parse().unwrap_or(4096),
+        }
+    }
+}
+
+pub async fn audit_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    audit_with(AuditConfig::from_env(), req, next).await
+}
+
+pub async fn audit_with(
+    config: AuditConfig,
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let sample_rate = config.sample_rate;
+    let excluded = EXCLUDED_PREFIXES.iter().any(|p| req.path().starts_with(p));
+    if excluded || sample_rate <= 0.0 || rand::random::<f64>() >= sample_rate {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    let max_bytes = config.max_body_bytes;
+    let started = Instant::now();
+    let method = req.method().to_string();
+    let path = req.path().to_string();
+    let query = Some(req.query_string()).filter(|q| !q.is_empty()).map(redact_query);
+
+    // Read the request body only when it is JSON and small enough to keep
+    let request_body = if is_json(req.headers()) && content_length(req.headers()).is_some_and(|len| len <= max_bytes) {
+        let bytes = req.extract::<web::Bytes>().await?;
+        let captured = capture_body(&bytes, max_bytes);
+        restore_payload(&mut req, bytes);
+        captured
+    } else {
+        None
+    };
+
+    let res = next.call(req).await?;
+    let status = res.status().as_u16();
+
+    // Only a response whose size is known and within the limit is buffered; a larger or streamed
+    // body passes through untouched rather than being read into memory
+    let small = matches!(res.response().body().size(), BodySize::Sized(len) if len <= max_bytes as u64);
+    let (res, response_body) = if is_json(res.headers()) && small {
+        let (http_req, res) = res.into_parts();
+        let (res, res_body) = res.into_parts();
+        let bytes = body::to_bytes(res_body).await.map_err(|e| {
+            let e: Box<dyn std::error::Error> = e.into();
+            actix_web::error::ErrorInternalServerError(e.to_string())
+        })?;
+        let captured = capture_body(&bytes, max_bytes);
+        (ServiceResponse::new(http_req, res.set_body(BoxBody::new(bytes))), captured)
+    } else {
+        (res.map_into_boxed_body(), None)
+    };
+
+    record(AuditEntry {
+        id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        method,
+        path,
+        query,
+        status,
+        duration_ms: started.elapsed().as_millis() as u64,
+        request_body,
+        response_body,
+    });
+
+    Ok(res)
+}
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    limit: Option<usize>,
+}
+
+pub async fn list_requests(query: web::Query<AuditQuery>) -> impl Responder {
+    let mut entries = recent_entries();
+    if let Some(limit) = query.limit {
+        entries.truncate(limit);
+    }
+    HttpResponse::Ok().json(serde_json::json!({
+        "count": entries.len(),
+        "requests": entries
+    }))
+}
@@ -0,0 +1,315 @@
+// Driver configuration benchmarks: the numbers behind the patterns this app teaches
+//
+// POST /examples/bench/matrix runs each scenario against the live services, baseline first:
+//   postgres  unpooled (connect per query) vs pooled (checkout from the shared pool)
+//   redis     sequential SETs (one round trip each) vs one pipeline
+//   rabbitmq  confirmed publishes (wait for each broker ack) vs unconfirmed
+// and reports per-operation latency, throughput and the candidate's speedup, as JSON or with
+// ?format=text as a plain table. Numbers from a laptop stack are only comparable to each other.
+// The criterion suite in benches/ measures the same pairs outside the server.
+
+use std::time::{Duration, Instant};
+
+use actix_web::{web, HttpResponse, Responder};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::{pool, postgres_client, redis_connection, services};
+#[cfg(feature = "rabbitmq")]
+use crate::amqp_connection;
+
+const DEFAULT_ITERATIONS: usize = 100;
+const MAX_ITERATIONS: usize = 10_000;
+pub const SCENARIOS: &[&str] = &["postgres", "redis", "rabbitmq"];
+
+#[derive(Deserialize)]
+pub struct MatrixQuery {
+    pub iterations: Option<usize>,
+    // Comma-separated subset of SCENARIOS
+    pub scenarios: Option<String>,
+    pub format: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Measurement {
+    pub scenario: &'static str,
+    pub variant: &'static str,
+    pub iterations: usize,
+    pub total_ms: f64,
+    pub per_op_us: f64,
+    pub ops_per_sec: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Measurement {
+    pub fn new(scenario: &'static str, variant: &'static str, iterations: usize, elapsed: Duration) -> Self {
+        let secs = elapsed.as_secs_f64();
+        Measurement {
+            scenario,
+            variant,
+            iterations,
+            total_ms: secs * 1000.0,
+            per_op_us: if iterations == 0 { 0.0 } else { secs * 1_000_000.0 / iterations as f64 },
+            ops_per_sec: if secs > 0.0 { iterations as f64 / secs } else { 0.0 },
+            error: None,
+        }
+    }
+
+    fn failed(scenario: &'static str, variant: &'static str, error: String) -> Self {
+        Measurement { error: Some(error), ..Measurement::new(scenario, variant, 0, Duration::ZERO) }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Comparison {
+    pub scenario: &'static str,
+    pub baseline: &'static str,
+    pub candidate: &'static str,
+    // Baseline time per op over candidate time per op; > 1 means the candidate is faster
+    pub speedup: Option<f64>,
+}
+
+// Rows come in (baseline, candidate) pairs per scenario
+pub fn compare(rows: &[Measurement]) -> Vec<Comparison> {
+    rows.chunks(2)
+        .filter_map(|pair| match pair {
+            [baseline, candidate] if baseline.scenario == candidate.scenario => Some(Comparison {
+                scenario: baseline.scenario,
+                baseline: baseline.variant,
+                candidate: candidate.variant,
+                speedup: (baseline.error.is_none() && candidate.error.is_none() && candidate.per_op_us > 0.0)
+                    .then(|| baseline.per_op_us / candidate.per_op_us),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn render_table(rows: &[Measurement], comparisons: &[Comparison]) -> String {
+    let mut table = format!(
+        "{:<10} {:<12} {:>10} {:>12} {:>12} {:>12}\n",
+        "scenario", "variant", "iterations", "total_ms", "per_op_us", "ops_per_sec"
+    );
+    for row in rows {
+        match &row.error {
+            Some(e) => table.push_str(&format!("{:<10} {:<12} error: {}\n", row.scenario, row.variant, e)),
+            None => table.push_str(&format!(
+                "{:<10} {:<12} {:>10} {:>12.2} {:>12.2} {:>12.0}\n",
+                row.scenario, row.variant, row.iterations, row.total_ms, row.per_op_us, row.ops_per_sec
+            )),
+        }
+    }
+    table.push('\n');
+    for comparison in comparisons {
+        let speedup = comparison.speedup.map_or("n/a".to_string(), |s| format!("{:.1}x", s));
+        table.push_str(&format!(
+            "{:<10} {} vs {}: {}\n",
+            comparison.scenario, comparison.candidate, comparison.baseline, speedup
+        ));
+    }
+    table
+}
+
+// (baseline, candidate) variants of a scenario
+pub fn variants(scenario: &str) -> (&'static str, &'static str) {
+    match scenario {
+        "postgres" => ("unpooled", "pooled"),
+        "redis" => ("sequential", "pipelined"),
+        _ => ("confirmed", "unconfirmed"),
+    }
+}
+
+fn both_failed(scenario: &'static str, error: String) -> Vec<Measurement> {
+    let (baseline, candidate) = variants(scenario);
+    vec![Measurement::failed(scenario, baseline, error.clone()), Measurement::failed(scenario, candidate, error)]
+}
+
+pub fn parse_scenarios(value: Option<&str>) -> Result<Vec<&'static str>, String> {
+    let Some(value) = value else {
+        return Ok(SCENARIOS.to_vec());
+    };
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            SCENARIOS
+                .iter()
+                .find(|s| **s == name)
+                .copied()
+                .ok_or_else(|| format!("Unknown scenario '{}', expected one of {}", name, SCENARIOS.join(", ")))
+        })
+        .collect()
+}
+
+// Time `iterations` runs of `op` after one untimed warm-up run
+async fn time<F, Fut>(scenario: &'static str, variant: &'static str, iterations: usize, mut op: F) -> Measurement
+where
+    F: FnMut(usize) -> Fut,
+    Fut: std::future::Future<Output = Result<(), String>>,
+{
+    if let Err(e) = op(0).await {
+        return Measurement::failed(scenario, variant, e);
+    }
+    let started = Instant::now();
+    for i in 0..iterations {
+        if let Err(e) = op(i).await {
+            return Measurement::failed(scenario, variant, e);
+        }
+    }
+    Measurement::new(scenario, variant, iterations, started.elapsed())
+}
+
+async fn bench_postgres(iterations: usize) -> Vec<Measurement> {
+    let unpooled = time("postgres", "unpooled", iterations, |_| async {
+        let client = postgres_client().await?;
+        client.query_one("SELECT 1", &[]).await.map(|_| ()).map_err(|e| format!("Query failed: {}", e))
+    })
+    .await;
+    let pooled = time("postgres", "pooled", iterations, |_| async {
+        let client = pool::postgres().await?;
+        client.query_one("SELECT 1", &[]).await.map(|_| ()).map_err(|e| format!("Query failed: {}", e))
+    })
+    .await;
+    vec![unpooled, pooled]
+}
+
+async fn bench_redis(iterations: usize) -> Vec<Measurement> {
+    let mut conn = match redis_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return both_failed("redis", e),
+    };
+    let run = uuid::Uuid::new_v4().simple().to_string();
+    let key = |i: usize| format!("bench:{}:{}", run, i);
+
+    let started = Instant::now();
+    let mut sequential = Measurement::new("redis", "sequential", iterations, Duration::ZERO);
+    for i in 0..iterations {
+        if let Err(e) = conn.set_ex::<_, _, ()>(key(i), i, 60).await {
+            sequential = Measurement::failed("redis", "sequential", e.to_string());
+            break;
+        }
+    }
+    if sequential.error.is_none() {
+        sequential = Measurement::new("redis", "sequential", iterations, started.elapsed());
+    }
+
+    let mut pipe = redis::pipe();
+    for i in 0..iterations {
+        pipe.set_ex(key(i), i, 60).ignore();
+    }
+    let started = Instant::now();
+    let pipelined = match pipe.query_async::<()>(&mut conn).await {
+        Ok(()) => Measurement::new("redis", "pipelined", iterations, started.elapsed()),
+        Err(e) => Measurement::failed("redis", "pipelined", e.to_string()),
+    };
+    vec![sequential, pipelined]
+}
+
+#[cfg(feature = "rabbitmq")]
+async fn publish_all(channel: &lapin::Channel, queue: &str, iterations: usize) -> Result<(), String> {
+    for i in 0..iterations {
+        // Without confirm.select the returned confirmation resolves at once as NotRequested
+        channel
+            .basic_publish(
+                "",
+                queue,
+                lapin::options::BasicPublishOptions::default(),
+                i.to_string().as_bytes(),
+                lapin::BasicProperties::default(),
+            )
+            .await
+            .map_err(|e| format!("Publish failed: {}", e))?
+            .await
+            .map_err(|e| format!("Publish confirm failed: {}", e))?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "rabbitmq")]
+async fn bench_rabbitmq(iterations: usize) -> Vec<Measurement> {
+    let conn = match amqp_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return both_failed("rabbitmq", e),
+    };
+    let queue = format!("bench-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+
+    let mut rows = Vec::new();
+    for (variant, confirm) in [("confirmed", true), ("unconfirmed", false)] {
+        let measured = async {
+            let channel = conn.create_channel().await.map_err(|e| format!("Channel creation failed: {}", e))?;
+            if confirm {
+                channel
+                    .confirm_select(lapin::options::ConfirmSelectOptions::default())
+                    .await
+                    .map_err(|e| format!("confirm.select failed: {}", e))?;
+            }
+            // Exclusive, so the broker drops it with the connection
+            let declare = lapin::options::QueueDeclareOptions { exclusive: true, ..Default::default() };
+            channel
+                .queue_declare(&queue, declare, lapin::types::FieldTable::default())
+                .await
+                .map_err(|e| format!("Queue declare failed: {}", e))?;
+            publish_all(&channel, &queue, 1).await?;
+            let started = Instant::now();
+            publish_all(&channel, &queue, iterations).await?;
+            Ok::<_, String>(started.elapsed())
+        }
+        .await;
+        rows.push(match measured {
+            Ok(elapsed) => Measurement::new("rabbitmq", variant, iterations, elapsed),
+            Err(e) => Measurement::failed("rabbitmq", variant, e),
+        });
+    }
+    let _ = conn.close(0, "Done").await;
+    rows
+}
+
+// bench_matrix skips disabled backends, so this only keeps the scenario table uniform
+#[cfg(not(feature = "rabbitmq"))]
+async fn bench_rabbitmq(_iterations: usize) -> Vec<Measurement> {
+    both_failed("rabbitmq", "RabbitMQ support is not compiled in".to_string())
+}
+
+pub async fn bench_matrix(query: web::Query<MatrixQuery>) -> impl Responder {
+    let iterations = query.iterations.unwrap_or(DEFAULT_ITERATIONS);
+    if iterations == 0 || iterations > MAX_ITERATIONS {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "status": "error",
+            "error": format!("iterations must be between 1 and {}", MAX_ITERATIONS)
+        }));
+    }
+    let scenarios = match parse_scenarios(query.scenarios.as_deref()) {
+        Ok(scenarios) => scenarios,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "status": "error", "error": e })),
+    };
+
+    // One scenario at a time so they don't compete for the host
+    let mut rows = Vec::new();
+    for scenario in scenarios {
+        if !services::is_compiled(scenario) {
+            rows.extend(both_failed(scenario, format!("{} is not compiled into this build", scenario)));
+            continue;
+        }
+        if !services::is_enabled(scenario) {
+            rows.extend(both_failed(scenario, format!("{} is disabled (ENABLED_SERVICES)", scenario)));
+            continue;
+        }
+        rows.extend(match scenario {
+            "postgres" => bench_postgres(iterations).await,
+            "redis" => bench_redis(iterations).await,
+            _ => bench_rabbitmq(iterations).await,
+        });
+    }
+    let comparisons = compare(&rows);
+
+    if query.format.as_deref() == Some("text") {
+        return HttpResponse::Ok().content_type("text/plain; charset=utf-8").body(render_table(&rows, &comparisons));
+    }
+    HttpResponse::Ok().json(serde_json::json!({
+        "iterations": iterations,
+        "results": rows,
+        "comparisons": comparisons
+    }))
+}
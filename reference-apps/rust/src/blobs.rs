@@ -0,0 +1,279 @@
+// Content-addressable blob store with tiered storage (Redis for small blobs, MinIO for large)
+//
+// A blob is addressed by the SHA-256 of its bytes, so storing the same content twice keeps one
+// copy and only bumps its reference count. Where the bytes live is decided per blob in
+// application code: up to BLOB_INLINE_MAX_BYTES (default 64 KiB) they sit in Redis next to the
+// metadata, where reads are one round trip; anything larger spills to MinIO, which is cheaper
+// per byte and streams. The Redis hash blob:{<hash>} is the index for both tiers.
+
+use std::collections::HashMap;
+
+use actix_web::http::{header, StatusCode};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use futures_util::StreamExt;
+use redis::AsyncCommands;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::storage::{self, ObjectStore};
+use crate::{etag, get_env_or, redis_connection};
+
+const STATS_KEY: &str = "blob:stats";
+pub const TIER_HEADER: &str = "X-Blob-Tier";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tier {
+    Redis,
+    Minio,
+}
+
+impl Tier {
+    pub fn for_size(size: usize, inline_max: usize) -> Self {
+        if size <= inline_max {
+            Tier::Redis
+        } else {
+            Tier::Minio
+        }
+    }
+
+    pub fn from_name(name: &str) -> Self {
+        if name == "minio" {
+            Tier::Minio
+        } else {
+            Tier::Redis
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Tier::Redis => "redis",
+            Tier::Minio => "minio",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BlobResponse {
+    pub status: String,
+    pub hash: String,
+    pub size: usize,
+    pub tier: Tier,
+    pub deduplicated: bool,
+    pub references: u64,
+}
+
+fn inline_max_bytes() -> usize {
+    get_env_or("BLOB_INLINE_MAX_BYTES", "65536").parse().unwrap_or(65_536)
+}
+
+fn max_bytes() -> usize {
+    get_env_or("BLOB_MAX_BYTES", "10485760").parse().unwrap_or(10_485_760)
+}
+
+pub fn is_blob_hash(value: &str) -> bool {
+    value.len() == 64 && value.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn meta_key(hash: &str) -> String {
+    format!("blob:{{{}}}", hash)
+}
+
+// Same hash slot as the metadata, so under Redis Cluster a blob's keys live on one node
+fn data_key(hash: &str) -> String {
+    format!("blob:{{{}}}:data", hash)
+}
+
+// Fanned out by prefix so no single listing grows without bound
+pub fn object_key(hash: &str) -> String {
+    format!("blobs/sha256/{}/{}", &hash[..2], hash)
+}
+
+fn error_response(status: StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+async fn read_body(mut payload: web::Payload, limit: usize) -> Result<Vec<u8>, HttpResponse> {
+    let mut body = Vec::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|e| error_response(StatusCode::BAD_REQUEST, format!("Failed to read body: {}", e)))?;
+        if body.len() + chunk.len() > limit {
+            return Err(error_response(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("Blob exceeds maximum size of {} bytes", limit),
+            ));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+// PUT /examples/blobs - store the request body; 201 for new content, 200 when it was already there
+pub async fn put_blob(req: HttpRequest, payload: web::Payload) -> impl Responder {
+    let body = match read_body(payload, max_bytes()).await {
+        Ok(body) => body,
+        Err(res) => return res,
+    };
+    let hash = hex::encode(Sha256::digest(&body));
+    let size = body.len();
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+
+    let mut conn = match redis_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return error_response(StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+    let known: Option<String> = match conn.hget(meta_key(&hash), "tier").await {
+        Ok(known) => known,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, format!("Index lookup failed: {}", e)),
+    };
+    // Content already stored stays where it is, even if the inline limit has changed since
+    let tier = known.as_deref().map_or_else(|| Tier::for_size(size, inline_max_bytes()), Tier::from_name);
+
+    // Writing the same bytes twice is harmless, so two racing uploads of new content may both
+    // get here; the reference count below still comes out right
+    if known.is_none() {
+        let stored = match tier {
+            Tier::Redis => conn.set::<_, _, ()>(data_key(&hash), body).await.map_err(|e| e.to_string()),
+            Tier::Minio => match ObjectStore::from_vault().await {
+                Ok(store) => match store.ensure_bucket().await {
+                    Ok(()) => store.put_object(&object_key(&hash), body, &content_type).await.map(|_| ()),
+                    Err(e) => Err(e),
+                },
+                Err(e) => return error_response(StatusCode::SERVICE_UNAVAILABLE, e),
+            },
+        };
+        if let Err(e) = stored {
+            return error_response(StatusCode::BAD_GATEWAY, format!("Storing blob in {} failed: {}", tier.as_str(), e));
+        }
+    }
+
+    let (references,): (u64,) = match redis::pipe()
+        .atomic()
+        .hset_nx(meta_key(&hash), "content_type", &content_type)
+        .ignore()
+        .hset_nx(meta_key(&hash), "created_at", chrono::Utc::now().to_rfc3339())
+        .ignore()
+        .hset_nx(meta_key(&hash), "size", size)
+        .ignore()
+        .hset_nx(meta_key(&hash), "tier", tier.as_str())
+        .ignore()
+        .hincr(meta_key(&hash), "refs", 1)
+        .query_async(&mut conn)
+        .await
+    {
+        Ok(result) => result,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, format!("Index update failed: {}", e)),
+    };
+    let deduplicated = references > 1;
+
+    let mut stats = redis::pipe();
+    if deduplicated {
+        stats.hincr(STATS_KEY, "dedup_hits", 1).ignore().hincr(STATS_KEY, "dedup_bytes_saved", size).ignore();
+    } else {
+        stats
+            .hincr(STATS_KEY, format!("{}_blobs", tier.as_str()), 1)
+            .ignore()
+            .hincr(STATS_KEY, format!("{}_bytes", tier.as_str()), size)
+            .ignore();
+    }
+    if let Err(e) = stats.query_async::<()>(&mut conn).await {
+        log::warn!("Blob stats update failed: {}", e);
+    }
+
+    let response = BlobResponse {
+        status: if deduplicated { "deduplicated" } else { "stored" }.to_string(),
+        hash,
+        size,
+        tier,
+        deduplicated,
+        references,
+    };
+    if deduplicated {
+        HttpResponse::Ok().json(response)
+    } else {
+        HttpResponse::Created().json(response)
+    }
+}
+
+// GET /examples/blobs/{hash} - the blob's bytes, from whichever tier holds them
+pub async fn get_blob(req: HttpRequest, path: web::Path<String>) -> impl Responder {
+    let hash = path.into_inner().to_ascii_lowercase();
+    if !is_blob_hash(&hash) {
+        return error_response(StatusCode::BAD_REQUEST, "Blob address must be a hex SHA-256".to_string());
+    }
+    // The address is the content, so it is a perfect strong validator
+    let tag = format!("\"{}\"", hash);
+    if etag::is_not_modified(&req, &tag) {
+        return etag::not_modified(&tag);
+    }
+
+    let mut conn = match redis_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return error_response(StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+    let meta: HashMap<String, String> = match conn.hgetall(meta_key(&hash)).await {
+        Ok(meta) => meta,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, format!("Index lookup failed: {}", e)),
+    };
+    let Some(tier) = meta.get("tier") else {
+        return error_response(StatusCode::NOT_FOUND, format!("Blob {} not found", hash));
+    };
+    let content_type = meta.get("content_type").map_or("application/octet-stream", String::as_str);
+
+    let mut builder = HttpResponse::Ok();
+    builder
+        .insert_header((header::CONTENT_TYPE, content_type))
+        .insert_header((header::ETAG, tag.clone()))
+        .insert_header((header::CACHE_CONTROL, "public, max-age=31536000, immutable"))
+        .insert_header((TIER_HEADER, tier.as_str()));
+
+    if tier == Tier::Redis.as_str() {
+        let data: Option<Vec<u8>> = match conn.get(data_key(&hash)).await {
+            Ok(data) => data,
+            Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, format!("Blob read failed: {}", e)),
+        };
+        return match data {
+            // Cheap to check while the bytes are in hand
+            Some(data) if hex::encode(Sha256::digest(&data)) == hash => builder.body(data),
+            Some(_) => error_response(StatusCode::INTERNAL_SERVER_ERROR, format!("Blob {} is corrupt", hash)),
+            None => error_response(StatusCode::NOT_FOUND, format!("Blob {} is indexed but its data is gone", hash)),
+        };
+    }
+
+    let store = match ObjectStore::from_vault().await {
+        Ok(store) => store,
+        Err(e) => return error_response(StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+    match store.get_object(&object_key(&hash), None).await {
+        Ok(response) => builder.streaming(storage::body_stream(response)),
+        Err(e) => error_response(StatusCode::BAD_GATEWAY, e),
+    }
+}
+
+// GET /examples/blobs - how much each tier holds and what deduplication saved
+pub async fn blob_stats() -> impl Responder {
+    let mut conn = match redis_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return error_response(StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+    let stats: HashMap<String, u64> = match conn.hgetall(STATS_KEY).await {
+        Ok(stats) => stats,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, format!("Stats read failed: {}", e)),
+    };
+    let stat = |name: &str| stats.get(name).copied().unwrap_or(0);
+    HttpResponse::Ok().json(serde_json::json!({
+        "inline_max_bytes": inline_max_bytes(),
+        "max_bytes": max_bytes(),
+        "tiers": {
+            "redis": { "blobs": stat("redis_blobs"), "bytes": stat("redis_bytes") },
+            "minio": { "blobs": stat("minio_blobs"), "bytes": stat("minio_bytes") }
+        },
+        "dedup_hits": stat("dedup_hits"),
+        "dedup_bytes_saved": stat("dedup_bytes_saved")
+    }))
+}
@@ -0,0 +1,149 @@
+// Startup configuration from Vault (twelve-factor bootstrap)
+//
+// With CONFIG_VAULT_PATH set (e.g. "rust-api/config"), the KV v2 secret at
+// secret/data/<path> is read once at startup, before anything else looks at its settings. Each
+// key becomes an environment variable (http_port -> HTTP_PORT) unless that variable is already
+// set: the process environment always wins, so a single setting can still be overridden per
+// container. Vault is retried for CONFIG_VAULT_WAIT_SECONDS (default 30); with
+// CONFIG_VAULT_REQUIRED=true a failed read stops startup, otherwise the app runs on env alone.
+//
+// The settings needed to reach Vault in the first place can't come from it and are ignored.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+
+use crate::{get_env_or, get_vault_secret};
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(2);
+const RESERVED: &[&str] =
+    &["VAULT_ADDR", "VAULT_TOKEN", "CONFIG_VAULT_PATH", "CONFIG_VAULT_REQUIRED", "CONFIG_VAULT_WAIT_SECONDS"];
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct BootstrapPlan {
+    // (variable, value) pairs to set; values are never reported
+    #[serde(skip)]
+    pub apply: Vec<(String, String)>,
+    pub applied: Vec<String>,
+    // Present in Vault but already set in the environment
+    pub overridden: Vec<String>,
+    // Reserved names, keys that aren't valid variable names, and nested values
+    pub ignored: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BootstrapReport {
+    pub path: String,
+    #[serde(flatten)]
+    pub plan: BootstrapPlan,
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+lazy_static! {
+    static ref REPORT: Mutex<Option<BootstrapReport>> = Mutex::new(None);
+}
+
+// `http-port` and `http.port` both map to HTTP_PORT
+pub fn env_name(key: &str) -> Option<String> {
+    let name: String =
+        key.trim().chars().map(|c| if c == '-' || c == '.' { '_' } else { c.to_ascii_uppercase() }).collect();
+    let valid = !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+    valid.then_some(name)
+}
+
+// Scalars only: strings as-is, numbers and booleans in their JSON form
+pub fn env_value(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        serde_json::Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+pub fn plan_env(data: &serde_json::Map<String, serde_json::Value>, is_set: impl Fn(&str) -> bool) -> BootstrapPlan {
+    let mut plan = BootstrapPlan::default();
+    for (key, value) in data {
+        let (Some(name), Some(value)) = (env_name(key), env_value(value)) else {
+            plan.ignored.push(key.clone());
+            continue;
+        };
+        if RESERVED.contains(&name.as_str()) {
+            plan.ignored.push(key.clone());
+        } else if is_set(&name) {
+            plan.overridden.push(name);
+        } else {
+            plan.applied.push(name.clone());
+            plan.apply.push((name, value));
+        }
+    }
+    plan
+}
+
+async fn read_config(path: &str, wait: Duration) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+    let started = Instant::now();
+    loop {
+        match get_vault_secret(path).await {
+            Ok(serde_json::Value::Object(data)) => return Ok(data),
+            Ok(_) => return Err(format!("secret/data/{} holds no key/value data", path)),
+            Err(e) if started.elapsed() + RETRY_INTERVAL > wait => return Err(e),
+            Err(e) => {
+                log::info!("Waiting for Vault config at secret/data/{}: {}", path, e);
+                tokio::time::sleep(RETRY_INTERVAL).await;
+            }
+        }
+    }
+}
+
+// Runs before the server is configured; Err only when CONFIG_VAULT_REQUIRED=true
+pub async fn load_from_vault() -> Result<(), String> {
+    let path = get_env_or("CONFIG_VAULT_PATH", "");
+    if path.is_empty() {
+        return Ok(());
+    }
+    let required = get_env_or("CONFIG_VAULT_REQUIRED", "false").parse().unwrap_or(false);
+    let wait = Duration::from_secs(get_env_or("CONFIG_VAULT_WAIT_SECONDS", "30").parse().unwrap_or(30));
+    let started = Instant::now();
+
+    let (plan, error) = match read_config(&path, wait).await {
+        Ok(data) => (plan_env(&data, |name| std::env::var_os(name).is_some()), None),
+        Err(e) => (BootstrapPlan::default(), Some(e)),
+    };
+    // Still single-threaded as far as configuration is concerned: nothing has read these yet
+    for (name, value) in &plan.apply {
+        std::env::set_var(name, value);
+    }
+
+    match &error {
+        Some(e) => log::warn!("Config from Vault (secret/data/{}) not loaded: {}", path, e),
+        None => log::info!(
+            "Config from Vault (secret/data/{}): {} applied, {} overridden by env, {} ignored",
+            path,
+            plan.applied.len(),
+            plan.overridden.len(),
+            plan.ignored.len()
+        ),
+    }
+    let report = BootstrapReport {
+        path: format!("secret/data/{}", path),
+        plan,
+        duration_ms: started.elapsed().as_millis() as u64,
+        error: error.clone(),
+    };
+    *REPORT.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(report);
+
+    match error {
+        Some(e) if required => Err(format!("CONFIG_VAULT_REQUIRED is set and the config could not be read: {}", e)),
+        _ => Ok(()),
+    }
+}
+
+pub fn report() -> Option<BootstrapReport> {
+    REPORT.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+}
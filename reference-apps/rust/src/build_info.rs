@@ -0,0 +1,61 @@
+// Build metadata embedded by build.rs, served at /info/build and in the root response
+
+use actix_web::{HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+
+// Owned fields so ApiInfo, which embeds it, stays deserializable
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BuildInfo {
+    pub version: String,
+    pub git_commit: String,
+    pub git_branch: String,
+    // RFC 3339, or None when the build script didn't record it
+    pub build_timestamp: Option<String>,
+    pub features: Vec<String>,
+    pub target: String,
+    pub profile: String,
+    pub rustc: String,
+}
+
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        git_commit: option_env!("BUILD_GIT_COMMIT").unwrap_or("unknown").to_string(),
+        git_branch: option_env!("BUILD_GIT_BRANCH").unwrap_or("unknown").to_string(),
+        build_timestamp: option_env!("BUILD_TIMESTAMP")
+            .and_then(|secs| secs.parse::<i64>().ok())
+            .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+            .map(|t| t.to_rfc3339()),
+        features: option_env!("BUILD_FEATURES")
+            .unwrap_or("")
+            .split(',')
+            .filter(|f| !f.is_empty())
+            .map(str::to_string)
+            .collect(),
+        target: option_env!("BUILD_TARGET").unwrap_or("unknown").to_string(),
+        profile: option_env!("BUILD_PROFILE").unwrap_or("unknown").to_string(),
+        rustc: option_env!("BUILD_RUSTC_VERSION").unwrap_or("unknown").to_string(),
+    }
+}
+
+pub fn short_commit(commit: &str) -> &str {
+    commit.get(..12).unwrap_or(commit)
+}
+
+pub fn log_banner() {
+    let info = build_info();
+    log::info!(
+        "DevStack Core Rust API {} ({}@{}, built {}, {} {}, features: [{}])",
+        info.version,
+        info.git_branch,
+        short_commit(&info.git_commit),
+        info.build_timestamp.as_deref().unwrap_or("unknown"),
+        info.target,
+        info.profile,
+        info.features.join(", ")
+    );
+}
+
+pub async fn build_info_handler() -> impl Responder {
+    HttpResponse::Ok().json(build_info())
+}
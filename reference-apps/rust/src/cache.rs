@@ -0,0 +1,440 @@
+// Cache example extensions built on top of the basic GET/SET/DELETE handlers
+
+use actix_web::{web, HttpResponse, Responder};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+use tokio::sync::watch;
+
+use crate::hedging;
+use crate::pagination::{Page, PageQuery, PageRequest};
+use crate::sql_timing::timed_query;
+use crate::{
+    get_env_or, postgres_client, redis_connection, redis_master_addresses, redis_node_connection,
+    CACHE_EARLY_REFRESH_TOTAL, CACHE_SINGLEFLIGHT_TOTAL,
+};
+
+#[derive(Deserialize)]
+pub struct BulkDeleteQuery {
+    pattern: Option<String>,
+    #[serde(default)]
+    confirm: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BulkDeleteResponse {
+    pub status: String,
+    pub pattern: String,
+    pub deleted: u64,
+    pub nodes_scanned: usize,
+    pub elapsed_ms: u64,
+    // Set when CACHE_BULK_DELETE_MAX_KEYS stopped the delete before every match was gone
+    #[serde(default)]
+    pub truncated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// What one node's delete got through: keys unlinked, and whether the budget ran out first
+struct NodeDelete {
+    deleted: u64,
+    truncated: bool,
+}
+
+// SCAN one node for keys matching the pattern and UNLINK them in pipelined batches, never more
+// than `max_keys` in total. An error still reports the keys already unlinked.
+async fn delete_matching_on_node(
+    address: &str,
+    pattern: &str,
+    batch_size: usize,
+    max_keys: u64,
+) -> Result<NodeDelete, (u64, String)> {
+    let mut conn = redis_node_connection(address).await.map_err(|e| (0, e))?;
+    let mut cursor: u64 = 0;
+    let mut deleted: u64 = 0;
+
+    loop {
+        let (next_cursor, mut keys): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(pattern)
+            .arg("COUNT")
+            .arg(batch_size)
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| (deleted, format!("SCAN failed on {}: {}", address, e)))?;
+
+        let remaining = max_keys.saturating_sub(deleted);
+        let truncated = keys.len() as u64 > remaining;
+        keys.truncate(remaining as usize);
+        if !keys.is_empty() {
+            // Keys may hash to different slots, so unlink individually within one pipeline
+            let mut pipe = redis::pipe();
+            for key in &keys {
+                pipe.cmd("UNLINK").arg(key);
+            }
+            let results: Vec<i64> = pipe
+                .query_async(&mut conn)
+                .await
+                .map_err(|e| (deleted, format!("UNLINK failed on {}: {}", address, e)))?;
+            deleted += results.iter().sum::<i64>() as u64;
+        }
+
+        cursor = next_cursor;
+        if truncated {
+            return Ok(NodeDelete { deleted, truncated: true });
+        }
+        if cursor == 0 {
+            break;
+        }
+    }
+
+    Ok(NodeDelete { deleted, truncated: false })
+}
+
+pub async fn bulk_delete_cache(query: web::Query<BulkDeleteQuery>) -> impl Responder {
+    let start = Instant::now();
+    let pattern = query.pattern.clone().unwrap_or_default();
+
+    if pattern.trim().is_empty() {
+        return HttpResponse::BadRequest().json(BulkDeleteResponse {
+            status: "error".to_string(),
+            pattern,
+            deleted: 0,
+            nodes_scanned: 0,
+            elapsed_ms: start.elapsed().as_millis() as u64,
+            truncated: false,
+            error: Some("Query parameter 'pattern' is required".to_string()),
+        });
+    }
+
+    // Safety guard: destructive cluster-wide deletes must be explicitly confirmed
+    if !query.confirm {
+        return HttpResponse::BadRequest().json(BulkDeleteResponse {
+            status: "confirmation_required".to_string(),
+            pattern,
+            deleted: 0,
+            nodes_scanned: 0,
+            elapsed_ms: start.elapsed().as_millis() as u64,
+            truncated: false,
+            error: Some("Bulk delete requires confirm=true".to_string()),
+        });
+    }
+
+    let batch_size: usize = get_env_or("CACHE_BULK_DELETE_BATCH", "500").parse().unwrap_or(500);
+    let max_keys: u64 = get_env_or("CACHE_BULK_DELETE_MAX_KEYS", "100000").parse().unwrap_or(100_000);
+
+    let masters = match redis_master_addresses().await {
+        Ok(masters) => masters,
+        Err(e) => {
+            return HttpResponse::ServiceUnavailable().json(BulkDeleteResponse {
+                status: "error".to_string(),
+                pattern,
+                deleted: 0,
+                nodes_scanned: 0,
+                elapsed_ms: start.elapsed().as_millis() as u64,
+                truncated: false,
+                error: Some(e),
+            })
+        }
+    };
+
+    let mut deleted = 0;
+    for (index, address) in masters.iter().enumerate() {
+        match delete_matching_on_node(address, &pattern, batch_size, max_keys.saturating_sub(deleted)).await {
+            Ok(NodeDelete { deleted: count, truncated: false }) => deleted += count,
+            Ok(NodeDelete { deleted: count, truncated: true }) => {
+                deleted += count;
+                return HttpResponse::Ok().json(BulkDeleteResponse {
+                    status: "truncated".to_string(),
+                    pattern,
+                    deleted,
+                    nodes_scanned: index + 1,
+                    elapsed_ms: start.elapsed().as_millis() as u64,
+                    truncated: true,
+                    error: Some(format!("Stopped after deleting {} keys (limit {})", deleted, max_keys)),
+                });
+            }
+            Err((count, e)) => {
+                return HttpResponse::InternalServerError().json(BulkDeleteResponse {
+                    status: "error".to_string(),
+                    pattern,
+                    deleted: deleted + count,
+                    nodes_scanned: index + 1,
+                    elapsed_ms: start.elapsed().as_millis() as u64,
+                    truncated: false,
+                    error: Some(e),
+                })
+            }
+        }
+    }
+
+    HttpResponse::Ok().json(BulkDeleteResponse {
+        status: "deleted".to_string(),
+        pattern,
+        deleted,
+        nodes_scanned: masters.len(),
+        elapsed_ms: start.elapsed().as_millis() as u64,
+        truncated: false,
+        error: None,
+    })
+}
+
+// ============================================================================
+// Key listing with cursor pagination across cluster masters
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct KeyListQuery {
+    pattern: Option<String>,
+}
+
+// Where the next page resumes: a master node and its SCAN cursor
+#[derive(Serialize, Deserialize)]
+pub struct ScanPosition {
+    node: String,
+    scan: u64,
+}
+
+// SCAN masters in address order until at least `limit` keys are collected.
+// SCAN may return a few more keys than requested; they are kept so none are skipped.
+async fn scan_keys(
+    masters: &[String],
+    pattern: &str,
+    limit: u64,
+    start: (usize, u64),
+) -> Result<(Vec<String>, Option<ScanPosition>), String> {
+    let (mut index, mut cursor) = start;
+    let mut keys: Vec<String> = Vec::new();
+
+    while index < masters.len() {
+        let mut conn = redis_node_connection(&masters[index]).await?;
+        loop {
+            let (next_cursor, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(pattern)
+                .arg("COUNT")
+                .arg(limit)
+                .query_async(&mut conn)
+                .await
+                .map_err(|e| format!("SCAN failed on {}: {}", masters[index], e))?;
+            keys.extend(batch);
+            cursor = next_cursor;
+
+            if cursor == 0 {
+                break;
+            }
+            if keys.len() as u64 >= limit {
+                return Ok((keys, Some(ScanPosition { node: masters[index].clone(), scan: cursor })));
+            }
+        }
+
+        index += 1;
+        if keys.len() as u64 >= limit {
+            return Ok((keys, masters.get(index).map(|node| ScanPosition { node: node.clone(), scan: 0 })));
+        }
+    }
+
+    Ok((keys, None))
+}
+
+pub async fn list_keys(query: web::Query<KeyListQuery>, page: web::Query<PageQuery>) -> impl Responder {
+    let pattern = query.pattern.clone().unwrap_or_else(|| "*".to_string());
+
+    let (limit, after) = match page.resolve::<ScanPosition>() {
+        Ok(PageRequest::Cursor { limit, after }) => (limit, after),
+        Ok(PageRequest::Offset { .. }) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "status": "error",
+                "error": "Key listing supports cursor pagination only"
+            }))
+        }
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "status": "error", "error": e })),
+    };
+
+    let mut masters = match redis_master_addresses().await {
+        Ok(masters) => masters,
+        Err(e) => return HttpResponse::ServiceUnavailable().json(serde_json::json!({ "status": "error", "error": e })),
+    };
+    // A stable order lets a cursor name the node it stopped on
+    masters.sort();
+
+    let start = match after {
+        None => (0, 0),
+        Some(position) => match masters.iter().position(|node| *node == position.node) {
+            Some(index) => (index, position.scan),
+            None => {
+                return HttpResponse::BadRequest().json(serde_json::json!({
+                    "status": "error",
+                    "error": format!("Cursor refers to unknown node {}", position.node)
+                }))
+            }
+        },
+    };
+
+    match scan_keys(&masters, &pattern, limit, start).await {
+        Ok((keys, next)) => HttpResponse::Ok().json(Page::with_cursor(keys, limit, next)),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({ "status": "error", "error": e })),
+    }
+}
+
+// ============================================================================
+// Cache-aside with single-flight loading and probabilistic early expiration
+// ============================================================================
+
+// Value stored in Redis alongside the metadata needed for early recomputation (XFetch)
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CachedEntry {
+    pub value: serde_json::Value,
+    pub delta_ms: u64,
+    pub expires_at_ms: i64,
+}
+
+type LoadResult = Result<CachedEntry, String>;
+
+lazy_static! {
+    // In-flight loads keyed by cache key; followers wait on the leader's result
+    static ref INFLIGHT: Mutex<HashMap<String, watch::Receiver<Option<LoadResult>>>> = Mutex::new(HashMap::new());
+}
+
+// Removes the in-flight entry when the leader finishes (or is cancelled)
+struct InflightGuard {
+    key: String,
+}
+
+impl Drop for InflightGuard {
+    fn drop(&mut self) {
+        let mut map = INFLIGHT.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        map.remove(&self.key);
+    }
+}
+
+enum Flight {
+    Leader(watch::Sender<Option<LoadResult>>, InflightGuard),
+    Follower(watch::Receiver<Option<LoadResult>>),
+}
+
+fn join_flight(key: &str) -> Flight {
+    let mut map = INFLIGHT.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(rx) = map.get(key) {
+        return Flight::Follower(rx.clone());
+    }
+    let (tx, rx) = watch::channel(None);
+    map.insert(key.to_string(), rx);
+    Flight::Leader(tx, InflightGuard { key: key.to_string() })
+}
+
+// Simulated expensive load from Postgres, written back to Redis with a TTL
+async fn load_and_store(redis_key: &str, key: &str) -> LoadResult {
+    let ttl: u64 = get_env_or("CACHE_ASIDE_TTL", "60").parse().unwrap_or(60);
+    let delay_ms: u64 = get_env_or("CACHE_ASIDE_LOAD_DELAY_MS", "200").parse().unwrap_or(200);
+    let start = Instant::now();
+
+    let client = postgres_client().await?;
+    let sql = "SELECT $1::text, NOW()::text FROM pg_sleep($2)";
+    let delay_secs = delay_ms as f64 / 1000.0;
+    let row = timed_query("postgres", "cache_aside_load", sql, 2, client.query_one(sql, &[&key, &delay_secs]))
+        .await
+        .map_err(|e| format!("Query failed: {}", e))?;
+    let loaded_key: String = row.get(0);
+    let loaded_at: String = row.get(1);
+
+    let entry = CachedEntry {
+        value: serde_json::json!({ "key": loaded_key, "loaded_at": loaded_at }),
+        delta_ms: start.elapsed().as_millis() as u64,
+        expires_at_ms: chrono::Utc::now().timestamp_millis() + (ttl as i64 * 1000),
+    };
+    let raw = serde_json::to_string(&entry).map_err(|e| format!("Serialization failed: {}", e))?;
+
+    let mut conn = redis_connection().await?;
+    redis::cmd("SET")
+        .arg(redis_key)
+        .arg(raw)
+        .arg("EX")
+        .arg(ttl)
+        .query_async::<()>(&mut conn)
+        .await
+        .map_err(|e| format!("SET failed: {}", e))?;
+
+    Ok(entry)
+}
+
+// Returns the load result and whether it was shared from another request's load
+async fn single_flight_load(redis_key: &str, key: &str) -> (LoadResult, bool) {
+    match join_flight(redis_key) {
+        Flight::Leader(tx, _guard) => {
+            CACHE_SINGLEFLIGHT_TOTAL.with_label_values(&["leader"]).inc();
+            let result = load_and_store(redis_key, key).await;
+            let _ = tx.send(Some(result.clone()));
+            (result, false)
+        }
+        Flight::Follower(mut rx) => {
+            CACHE_SINGLEFLIGHT_TOTAL.with_label_values(&["coalesced"]).inc();
+            let shared = rx.wait_for(|v| v.is_some()).await.ok().and_then(|v| (*v).clone());
+            match shared {
+                Some(result) => (result, true),
+                // Leader was cancelled before publishing; load independently
+                None => (load_and_store(redis_key, key).await, false),
+            }
+        }
+    }
+}
+
+// XFetch: recompute early with probability rising as expiry approaches, scaled by load cost
+pub fn should_refresh_early(entry: &CachedEntry, beta: f64, now_ms: i64) -> bool {
+    let random: f64 = 1.0 - rand::random::<f64>(); // (0, 1]
+    let early_by = entry.delta_ms as f64 * beta * -random.ln();
+    now_ms as f64 + early_by >= entry.expires_at_ms as f64
+}
+
+pub async fn cache_aside_get(path: web::Path<String>) -> impl Responder {
+    let key = path.into_inner();
+    let redis_key = format!("cache-aside:{}", key);
+    let beta: f64 = get_env_or("CACHE_XFETCH_BETA", "1.0").parse().unwrap_or(1.0);
+    let start = Instant::now();
+
+    let mut conn = match redis_connection().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                "status": "error",
+                "key": key,
+                "error": e
+            }))
+        }
+    };
+
+    let cached = hedging::redis_get(&mut conn, &redis_key).await.unwrap_or(None);
+
+    let mut status = "miss";
+    if let Some(entry) = cached.and_then(|raw| serde_json::from_str::<CachedEntry>(&raw).ok()) {
+        if !should_refresh_early(&entry, beta, chrono::Utc::now().timestamp_millis()) {
+            return HttpResponse::Ok().json(serde_json::json!({
+                "status": "hit",
+                "key": key,
+                "value": entry.value,
+                "elapsed_ms": start.elapsed().as_millis() as u64
+            }));
+        }
+        CACHE_EARLY_REFRESH_TOTAL.inc();
+        status = "early_refresh";
+    }
+
+    match single_flight_load(&redis_key, &key).await {
+        (Ok(entry), coalesced) => HttpResponse::Ok().json(serde_json::json!({
+            "status": if coalesced { "coalesced" } else { status },
+            "key": key,
+            "value": entry.value,
+            "load_ms": entry.delta_ms,
+            "elapsed_ms": start.elapsed().as_millis() as u64
+        })),
+        (Err(e), _) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "status": "error",
+            "key": key,
+            "error": e
+        })),
+    }
+}
@@ -0,0 +1,73 @@
+// Binary-safe request and response bodies for the cache endpoints
+//
+// Values are stored in Redis as raw bytes. Sets accept either the JSON body
+// `{"value": "...", "ttl": 60}` or any payload sent as `application/octet-stream` (TTL in the
+// query string); gets pick the representation with `?format=json|base64|raw`.
+
+use actix_web::http::header;
+use actix_web::HttpRequest;
+use base64::Engine;
+use serde::Deserialize;
+
+use crate::CacheSetRequest;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CacheFormat {
+    // Value as a JSON string; only for UTF-8 values
+    Json,
+    // Value as a base64 JSON string
+    Base64,
+    // Value as the response body, `application/octet-stream`
+    Raw,
+}
+
+impl CacheFormat {
+    pub fn parse(format: Option<&str>) -> Result<Self, String> {
+        match format.unwrap_or("json") {
+            "json" => Ok(CacheFormat::Json),
+            "base64" => Ok(CacheFormat::Base64),
+            "raw" => Ok(CacheFormat::Raw),
+            other => Err(format!("Unknown format '{}'. Must be one of: json, base64, raw", other)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CacheGetQuery {
+    pub format: Option<String>,
+    // Decrypt a value stored with ?encrypted=true
+    pub encrypted: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct CacheSetQuery {
+    // TTL for `application/octet-stream` bodies, which have no JSON envelope
+    pub ttl: Option<u64>,
+    // Envelope-encrypt the value with a Vault Transit data key
+    pub encrypted: Option<bool>,
+}
+
+pub struct CacheSetBody {
+    pub value: Vec<u8>,
+    pub ttl: Option<u64>,
+}
+
+pub fn is_octet_stream(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/octet-stream"))
+}
+
+pub fn parse_set_body(binary: bool, body: &[u8], query_ttl: Option<u64>) -> Result<CacheSetBody, String> {
+    if binary {
+        return Ok(CacheSetBody { value: body.to_vec(), ttl: query_ttl });
+    }
+    let request: CacheSetRequest = serde_json::from_slice(body).map_err(|e| format!("Invalid JSON body: {}", e))?;
+    Ok(CacheSetBody { value: request.value.into_bytes(), ttl: request.ttl.or(query_ttl) })
+}
+
+pub fn to_base64(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
@@ -0,0 +1,563 @@
+// Cache strategy comparison: cache-aside, read-through, write-through and write-behind
+// PostgreSQL is the system of record; Redis is the cache; timings are reported per request
+
+use actix_web::{web, HttpResponse, Responder};
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::sql_timing::timed_query;
+use crate::{get_env_or, postgres_client, redis_connection};
+
+// The hash tag keeps the queue, the entries a flush has claimed and the flush lock in one
+// cluster slot, and that slot (1645) is served by redis-1, which redis_connection() talks to
+pub(crate) const WRITE_BEHIND_QUEUE: &str = "{cache-strategy}:write-behind";
+// Entries move here while a flush writes them and are removed only once PostgreSQL has committed
+pub(crate) const WRITE_BEHIND_PROCESSING: &str = "{cache-strategy}:write-behind:processing";
+// Held by whichever instance is flushing, so two flushes never trim each other's claimed entries
+pub(crate) const WRITE_BEHIND_LOCK: &str = "{cache-strategy}:flush-lock";
+
+const TABLE_DDL: &str = "CREATE TABLE IF NOT EXISTS cache_strategy_items (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+)";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CacheStrategy {
+    CacheAside,
+    ReadThrough,
+    WriteThrough,
+    WriteBehind,
+}
+
+impl CacheStrategy {
+    pub const ALL: [CacheStrategy; 4] = [
+        CacheStrategy::CacheAside,
+        CacheStrategy::ReadThrough,
+        CacheStrategy::WriteThrough,
+        CacheStrategy::WriteBehind,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "cache-aside" | "aside" => Some(CacheStrategy::CacheAside),
+            "read-through" => Some(CacheStrategy::ReadThrough),
+            "write-through" => Some(CacheStrategy::WriteThrough),
+            "write-behind" | "write-back" => Some(CacheStrategy::WriteBehind),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheStrategy::CacheAside => "cache-aside",
+            CacheStrategy::ReadThrough => "read-through",
+            CacheStrategy::WriteThrough => "write-through",
+            CacheStrategy::WriteBehind => "write-behind",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            CacheStrategy::CacheAside => "Application reads cache, loads from database on miss, invalidates on write",
+            CacheStrategy::ReadThrough => {
+                "Cache layer owns loading and shares one database load among concurrent misses; \
+                 callers only talk to the cache"
+            }
+            CacheStrategy::WriteThrough => "Writes go to database and cache synchronously",
+            CacheStrategy::WriteBehind => "Writes go to cache immediately and are flushed to the database asynchronously",
+        }
+    }
+
+    fn cache_key(&self, key: &str) -> String {
+        format!("strategy:{}:{}", self.as_str(), key)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct StrategyWriteRequest {
+    value: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StrategyResponse {
+    pub strategy: String,
+    pub operation: String,
+    pub key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    pub cache_ms: f64,
+    pub db_ms: f64,
+    pub total_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct QueuedWrite {
+    key: String,
+    value: String,
+}
+
+fn ms(d: Duration) -> f64 {
+    (d.as_secs_f64() * 1000.0 * 100.0).round() / 100.0
+}
+
+fn cache_ttl() -> u64 {
+    get_env_or("CACHE_STRATEGY_TTL", "300").parse().unwrap_or(300)
+}
+
+// Accumulates time spent in each tier for one request
+#[derive(Default)]
+struct Timings {
+    cache: Duration,
+    db: Duration,
+}
+
+impl Timings {
+    async fn cache<T, F: Future<Output = T>>(&mut self, f: F) -> T {
+        let start = Instant::now();
+        let out = f.await;
+        self.cache += start.elapsed();
+        out
+    }
+
+    async fn db<T, F: Future<Output = T>>(&mut self, f: F) -> T {
+        let start = Instant::now();
+        let out = f.await;
+        self.db += start.elapsed();
+        out
+    }
+}
+
+static TABLE_READY: tokio::sync::OnceCell<()> = tokio::sync::OnceCell::const_new();
+
+// The table is created by the first request that needs it, not on every one
+async fn db_client() -> Result<tokio_postgres::Client, String> {
+    let client = postgres_client().await?;
+    TABLE_READY
+        .get_or_try_init(|| async {
+            client.batch_execute(TABLE_DDL).await.map_err(|e| format!("Table setup failed: {}", e))
+        })
+        .await?;
+    Ok(client)
+}
+
+async fn db_read(client: &tokio_postgres::Client, key: &str) -> Result<Option<String>, String> {
+    let sql = "SELECT value FROM cache_strategy_items WHERE key = $1";
+    timed_query("postgres", "strategy_read", sql, 1, client.query_opt(sql, &[&key]))
+        .await
+        .map(|row| row.map(|r| r.get(0)))
+        .map_err(|e| format!("Query failed: {}", e))
+}
+
+async fn db_write(client: &tokio_postgres::Client, key: &str, value: &str) -> Result<(), String> {
+    let sql = "INSERT INTO cache_strategy_items (key, value, updated_at) VALUES ($1, $2, NOW())
+               ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value, updated_at = NOW()";
+    timed_query("postgres", "strategy_write", sql, 2, client.execute(sql, &[&key, &value]))
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("Upsert failed: {}", e))
+}
+
+async fn cache_get(conn: &mut redis::aio::MultiplexedConnection, key: &str) -> Result<Option<String>, String> {
+    redis::cmd("GET")
+        .arg(key)
+        .query_async(conn)
+        .await
+        .map_err(|e| format!("GET failed: {}", e))
+}
+
+async fn cache_set(conn: &mut redis::aio::MultiplexedConnection, key: &str, value: &str) -> Result<(), String> {
+    redis::cmd("SET")
+        .arg(key)
+        .arg(value)
+        .arg("EX")
+        .arg(cache_ttl())
+        .query_async::<()>(conn)
+        .await
+        .map_err(|e| format!("SET failed: {}", e))
+}
+
+async fn cache_invalidate(conn: &mut redis::aio::MultiplexedConnection, key: &str) -> Result<(), String> {
+    redis::cmd("DEL")
+        .arg(key)
+        .query_async::<()>(conn)
+        .await
+        .map_err(|e| format!("DEL failed: {}", e))
+}
+
+type Load = Shared<BoxFuture<'static, Result<Option<String>, String>>>;
+
+lazy_static! {
+    // Read-through loads in progress, by cache key
+    static ref READ_THROUGH_LOADS: Mutex<HashMap<String, Load>> = Mutex::new(HashMap::new());
+}
+
+// Read-through: the cache is built with its loader, so callers only ever ask it for keys. It goes
+// to the database itself, only on a miss, and concurrent misses for one key share a single load.
+struct ReadThroughCache<L> {
+    loader: L,
+}
+
+impl<L> ReadThroughCache<L>
+where
+    L: Fn(String) -> BoxFuture<'static, Result<Option<String>, String>>,
+{
+    async fn get(
+        &self,
+        conn: &mut redis::aio::MultiplexedConnection,
+        key: &str,
+        cache_key: &str,
+        timings: &mut Timings,
+    ) -> Result<(Option<String>, &'static str), String> {
+        if let Some(value) = timings.cache(cache_get(conn, cache_key)).await? {
+            return Ok((Some(value), "cache"));
+        }
+        let load = READ_THROUGH_LOADS
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry(cache_key.to_string())
+            .or_insert_with(|| (self.loader)(key.to_string()).shared())
+            .clone();
+        let loaded = timings.db(load.clone()).await;
+        {
+            let mut loads = READ_THROUGH_LOADS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            if loads.get(cache_key).is_some_and(|current| current.ptr_eq(&load)) {
+                loads.remove(cache_key);
+            }
+        }
+        if let Some(value) = &loaded? {
+            timings.cache(cache_set(conn, cache_key, value)).await?;
+            return Ok((Some(value.clone()), "database"));
+        }
+        Ok((None, "database"))
+    }
+}
+
+fn read_through_cache() -> ReadThroughCache<impl Fn(String) -> BoxFuture<'static, Result<Option<String>, String>>> {
+    ReadThroughCache {
+        loader: |key: String| {
+            async move {
+                let client = db_client().await?;
+                db_read(&client, &key).await
+            }
+            .boxed()
+        },
+    }
+}
+
+async fn strategy_read(strategy: CacheStrategy, key: &str, timings: &mut Timings) -> Result<(Option<String>, &'static str), String> {
+    let mut conn = redis_connection().await?;
+    let cache_key = strategy.cache_key(key);
+
+    match strategy {
+        CacheStrategy::ReadThrough => read_through_cache().get(&mut conn, key, &cache_key, timings).await,
+        // The application checks the cache, loads from the database itself on a miss and fills the cache
+        CacheStrategy::CacheAside => {
+            if let Some(value) = timings.cache(cache_get(&mut conn, &cache_key)).await? {
+                return Ok((Some(value), "cache"));
+            }
+            let client = db_client().await?;
+            let loaded = timings.db(db_read(&client, key)).await?;
+            if let Some(value) = &loaded {
+                timings.cache(cache_set(&mut conn, &cache_key, value)).await?;
+            }
+            Ok((loaded, "database"))
+        }
+        // Write-through and write-behind keep the cache populated on write; misses fall back to the database
+        CacheStrategy::WriteThrough | CacheStrategy::WriteBehind => {
+            if let Some(value) = timings.cache(cache_get(&mut conn, &cache_key)).await? {
+                return Ok((Some(value), "cache"));
+            }
+            let client = db_client().await?;
+            Ok((timings.db(db_read(&client, key)).await?, "database"))
+        }
+    }
+}
+
+async fn strategy_write(strategy: CacheStrategy, key: &str, value: &str, timings: &mut Timings) -> Result<&'static str, String> {
+    let mut conn = redis_connection().await?;
+    let cache_key = strategy.cache_key(key);
+
+    match strategy {
+        CacheStrategy::CacheAside | CacheStrategy::ReadThrough => {
+            let client = db_client().await?;
+            timings.db(db_write(&client, key, value)).await?;
+            timings.cache(cache_invalidate(&mut conn, &cache_key)).await?;
+            Ok("database (cache invalidated)")
+        }
+        CacheStrategy::WriteThrough => {
+            let client = db_client().await?;
+            timings.db(db_write(&client, key, value)).await?;
+            timings.cache(cache_set(&mut conn, &cache_key, value)).await?;
+            Ok("database + cache")
+        }
+        CacheStrategy::WriteBehind => {
+            timings.cache(cache_set(&mut conn, &cache_key, value)).await?;
+            let queued = serde_json::to_string(&QueuedWrite { key: key.to_string(), value: value.to_string() })
+                .map_err(|e| format!("Serialization failed: {}", e))?;
+            timings
+                .cache(async {
+                    redis::cmd("RPUSH")
+                        .arg(WRITE_BEHIND_QUEUE)
+                        .arg(queued)
+                        .query_async::<()>(&mut conn)
+                        .await
+                        .map_err(|e| format!("RPUSH failed: {}", e))
+                })
+                .await?;
+            Ok("cache (database write queued)")
+        }
+    }
+}
+
+// Outlives any sane flush, so a crashed instance can't hold the lock for long
+const FLUSH_LOCK_TTL_MS: u64 = 30_000;
+
+// Release the lock only if it is still ours; it may have expired and been taken by another flush
+const FLUSH_UNLOCK_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+// Drain up to `batch` queued writes into PostgreSQL, returning how many were flushed.
+// Entries are claimed by moving them to the processing list and written in one transaction;
+// they leave Redis only after the commit, so a failed flush (or a crash) leaves them to be
+// retried, ahead of newer entries, by the next one. Only one instance flushes at a time;
+// the others return 0 until the lock is free.
+pub async fn flush_write_behind(batch: usize) -> Result<usize, String> {
+    let batch = batch.max(1);
+    let mut conn = redis_connection().await?;
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    let acquired: Option<String> = redis::cmd("SET")
+        .arg(WRITE_BEHIND_LOCK)
+        .arg(&token)
+        .arg("NX")
+        .arg("PX")
+        .arg(FLUSH_LOCK_TTL_MS)
+        .query_async(&mut conn)
+        .await
+        .map_err(|e| format!("SET NX failed: {}", e))?;
+    if acquired.is_none() {
+        return Ok(0);
+    }
+
+    let result = flush_claimed(&mut conn, batch).await;
+    let _: redis::RedisResult<i64> =
+        redis::Script::new(FLUSH_UNLOCK_SCRIPT).key(WRITE_BEHIND_LOCK).arg(&token).invoke_async(&mut conn).await;
+    result
+}
+
+async fn flush_claimed(conn: &mut redis::aio::MultiplexedConnection, batch: usize) -> Result<usize, String> {
+    // Nothing is claimed while PostgreSQL is unreachable
+    let client = db_client().await?;
+
+    let mut items: Vec<String> = redis::cmd("LRANGE")
+        .arg(WRITE_BEHIND_PROCESSING)
+        .arg(0)
+        .arg(batch as i64 - 1)
+        .query_async(conn)
+        .await
+        .map_err(|e| format!("LRANGE failed: {}", e))?;
+    if items.len() < batch {
+        let mut pipe = redis::pipe();
+        for _ in items.len()..batch {
+            pipe.cmd("LMOVE").arg(WRITE_BEHIND_QUEUE).arg(WRITE_BEHIND_PROCESSING).arg("LEFT").arg("RIGHT");
+        }
+        let moved: Vec<Option<String>> =
+            pipe.query_async(conn).await.map_err(|e| format!("LMOVE failed: {}", e))?;
+        items.extend(moved.into_iter().flatten());
+    }
+
+    if items.is_empty() {
+        return Ok(0);
+    }
+
+    client.batch_execute("BEGIN").await.map_err(|e| format!("BEGIN failed: {}", e))?;
+    let mut flushed = 0;
+    for raw in &items {
+        match serde_json::from_str::<QueuedWrite>(raw) {
+            Ok(write) => {
+                if let Err(e) = db_write(&client, &write.key, &write.value).await {
+                    let _ = client.batch_execute("ROLLBACK").await;
+                    return Err(e);
+                }
+                flushed += 1;
+            }
+            Err(e) => log::warn!("Dropping malformed write-behind entry: {}", e),
+        }
+    }
+    client.batch_execute("COMMIT").await.map_err(|e| format!("COMMIT failed: {}", e))?;
+
+    // Should this fail, the entries are written again next time; the upserts make that harmless
+    redis::cmd("LTRIM")
+        .arg(WRITE_BEHIND_PROCESSING)
+        .arg(items.len())
+        .arg(-1)
+        .query_async::<()>(conn)
+        .await
+        .map_err(|e| format!("LTRIM failed: {}", e))?;
+    Ok(flushed)
+}
+
+// Background flusher for the write-behind queue (disabled with CACHE_WRITE_BEHIND_FLUSH_MS=0)
+pub fn spawn_write_behind_flusher() {
+    let interval_ms: u64 = get_env_or("CACHE_WRITE_BEHIND_FLUSH_MS", "5000").parse().unwrap_or(5000);
+    let batch: usize = get_env_or("CACHE_WRITE_BEHIND_BATCH", "100").parse().unwrap_or(100);
+    if interval_ms == 0 {
+        log::info!("Write-behind flusher disabled; use POST /examples/cache/strategies/flush");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_millis(interval_ms));
+        loop {
+            ticker.tick().await;
+            match flush_write_behind(batch).await {
+                Ok(0) => {}
+                Ok(n) => log::info!("Write-behind flushed {} entries", n),
+                Err(e) => log::debug!("Write-behind flush skipped: {}", e),
+            }
+        }
+    });
+}
+
+fn unknown_strategy(name: &str, key: &str, operation: &str) -> HttpResponse {
+    let valid: Vec<&str> = CacheStrategy::ALL.iter().map(|s| s.as_str()).collect();
+    HttpResponse::BadRequest().json(StrategyResponse {
+        strategy: name.to_string(),
+        operation: operation.to_string(),
+        key: key.to_string(),
+        value: None,
+        source: None,
+        cache_ms: 0.0,
+        db_ms: 0.0,
+        total_ms: 0.0,
+        error: Some(format!("Unknown strategy. Must be one of: {}", valid.join(", "))),
+    })
+}
+
+pub async fn list_strategies() -> impl Responder {
+    // Queued entries plus those a flush has claimed but not yet committed
+    let pending: Option<i64> = match redis_connection().await {
+        Ok(mut conn) => redis::pipe()
+            .cmd("LLEN")
+            .arg(WRITE_BEHIND_QUEUE)
+            .cmd("LLEN")
+            .arg(WRITE_BEHIND_PROCESSING)
+            .query_async::<(i64, i64)>(&mut conn)
+            .await
+            .ok()
+            .map(|(queued, claimed)| queued + claimed),
+        Err(_) => None,
+    };
+
+    let strategies: Vec<serde_json::Value> = CacheStrategy::ALL
+        .iter()
+        .map(|s| serde_json::json!({ "name": s.as_str(), "description": s.description() }))
+        .collect();
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "strategies": strategies,
+        "write_behind_pending": pending
+    }))
+}
+
+pub async fn strategy_get(path: web::Path<(String, String)>) -> impl Responder {
+    let (name, key) = path.into_inner();
+    let strategy = match CacheStrategy::parse(&name) {
+        Some(s) => s,
+        None => return unknown_strategy(&name, &key, "read"),
+    };
+
+    let start = Instant::now();
+    let mut timings = Timings::default();
+    let result = strategy_read(strategy, &key, &mut timings).await;
+    let mut response = StrategyResponse {
+        strategy: strategy.as_str().to_string(),
+        operation: "read".to_string(),
+        key,
+        value: None,
+        source: None,
+        cache_ms: ms(timings.cache),
+        db_ms: ms(timings.db),
+        total_ms: ms(start.elapsed()),
+        error: None,
+    };
+
+    match result {
+        Ok((Some(value), source)) => {
+            response.value = Some(value);
+            response.source = Some(source.to_string());
+            HttpResponse::Ok().json(response)
+        }
+        Ok((None, source)) => {
+            response.source = Some(source.to_string());
+            HttpResponse::NotFound().json(response)
+        }
+        Err(e) => {
+            response.error = Some(e);
+            HttpResponse::InternalServerError().json(response)
+        }
+    }
+}
+
+pub async fn strategy_put(path: web::Path<(String, String)>, body: web::Json<StrategyWriteRequest>) -> impl Responder {
+    let (name, key) = path.into_inner();
+    let strategy = match CacheStrategy::parse(&name) {
+        Some(s) => s,
+        None => return unknown_strategy(&name, &key, "write"),
+    };
+
+    let start = Instant::now();
+    let mut timings = Timings::default();
+    let result = strategy_write(strategy, &key, &body.value, &mut timings).await;
+    let mut response = StrategyResponse {
+        strategy: strategy.as_str().to_string(),
+        operation: "write".to_string(),
+        key,
+        value: Some(body.value.clone()),
+        source: None,
+        cache_ms: ms(timings.cache),
+        db_ms: ms(timings.db),
+        total_ms: ms(start.elapsed()),
+        error: None,
+    };
+
+    match result {
+        Ok(target) => {
+            response.source = Some(target.to_string());
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => {
+            response.value = None;
+            response.error = Some(e);
+            HttpResponse::InternalServerError().json(response)
+        }
+    }
+}
+
+pub async fn strategy_flush() -> impl Responder {
+    let batch: usize = get_env_or("CACHE_WRITE_BEHIND_BATCH", "100").parse().unwrap_or(100);
+    match flush_write_behind(batch).await {
+        Ok(flushed) => HttpResponse::Ok().json(serde_json::json!({
+            "status": "flushed",
+            "flushed": flushed
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "status": "error",
+            "error": e
+        })),
+    }
+}
@@ -0,0 +1,86 @@
+// CloudEvents 1.0 envelopes (structured JSON mode) for published messages
+//
+// A publish request with a `cloudevent` object is sent as `application/cloudevents+json`: the
+// message becomes `data` (parsed as JSON when possible) next to the required context attributes.
+// Consumers recognise the envelope by content type or by its `specversion` attribute.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::get_env_or;
+
+pub const CONTENT_TYPE: &str = "application/cloudevents+json";
+pub const SPEC_VERSION: &str = "1.0";
+const DEFAULT_TYPE: &str = "com.devstack.message.published";
+
+// Publish-time options; anything left out gets a default
+#[derive(Deserialize, Default, Clone)]
+pub struct CloudEventOptions {
+    #[serde(rename = "type")]
+    pub event_type: Option<String>,
+    pub source: Option<String>,
+    pub subject: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CloudEvent {
+    pub specversion: String,
+    pub id: String,
+    pub source: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub datacontenttype: Option<String>,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub data: Value,
+}
+
+impl CloudEvent {
+    pub fn wrap(message: &str, options: &CloudEventOptions) -> Self {
+        // JSON messages are embedded as JSON, anything else as a string
+        let (data, datacontenttype) = match serde_json::from_str::<Value>(message) {
+            Ok(json) => (json, "application/json"),
+            Err(_) => (Value::String(message.to_string()), "text/plain"),
+        };
+        CloudEvent {
+            specversion: SPEC_VERSION.to_string(),
+            id: uuid::Uuid::new_v4().to_string(),
+            source: options
+                .source
+                .clone()
+                .unwrap_or_else(|| get_env_or("CLOUDEVENTS_SOURCE", "/devstack-core/rust-api")),
+            event_type: options.event_type.clone().unwrap_or_else(|| DEFAULT_TYPE.to_string()),
+            subject: options.subject.clone(),
+            time: Some(chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)),
+            datacontenttype: Some(datacontenttype.to_string()),
+            data,
+        }
+    }
+
+    // Some when `value` is a CloudEvent: specversion 1.0 with non-empty id, source and type
+    pub fn parse(value: &Value) -> Option<Self> {
+        let event: CloudEvent = serde_json::from_value(value.clone()).ok()?;
+        let complete = event.specversion == SPEC_VERSION
+            && !event.id.is_empty()
+            && !event.source.is_empty()
+            && !event.event_type.is_empty();
+        complete.then_some(event)
+    }
+
+    // Context attributes without the data, as surfaced by the consume endpoint
+    pub fn attributes(&self) -> Value {
+        serde_json::json!({
+            "specversion": self.specversion,
+            "id": self.id,
+            "source": self.source,
+            "type": self.event_type,
+            "subject": self.subject,
+            "time": self.time,
+            "datacontenttype": self.datacontenttype
+        })
+    }
+}
@@ -0,0 +1,117 @@
+// Transparent gzip compression for large cache values
+//
+// Values of at least CACHE_COMPRESSION_THRESHOLD_BYTES are gzip-compressed before SET. Every
+// value this API writes, compressed or not, starts with VALUE_HEADER and a tag byte naming its
+// encoding, so the payload itself is never inspected: a binary value that happens to begin with
+// gzip bytes still reads back as written. Values without the header were written by other
+// clients and are returned as-is. Compression is skipped when it doesn't make the value smaller.
+
+use std::io::{Read, Write};
+use std::time::Instant;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::{get_env_or, CACHE_COMPRESSION_BYTES_TOTAL, CACHE_COMPRESSION_DURATION, CACHE_COMPRESSION_RATIO};
+
+// Followed by the encoding tag byte. 0xFF never occurs in UTF-8, so no string written by another
+// client can be mistaken for a tagged value.
+pub const VALUE_HEADER: &[u8] = b"\xffcache\x00";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Identity,
+    Gzip,
+}
+
+impl Encoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Identity => "identity",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    pub fn tag(&self) -> u8 {
+        match self {
+            Encoding::Identity => b'i',
+            Encoding::Gzip => b'g',
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            b'i' => Some(Encoding::Identity),
+            b'g' => Some(Encoding::Gzip),
+            _ => None,
+        }
+    }
+}
+
+fn tagged(encoding: Encoding, body: &[u8]) -> Vec<u8> {
+    let mut stored = Vec::with_capacity(VALUE_HEADER.len() + 1 + body.len());
+    stored.extend_from_slice(VALUE_HEADER);
+    stored.push(encoding.tag());
+    stored.extend_from_slice(body);
+    stored
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompressionConfig {
+    // 0 disables compression
+    pub threshold_bytes: usize,
+    pub level: u32,
+}
+
+impl CompressionConfig {
+    pub fn from_env() -> Self {
+        CompressionConfig {
+            threshold_bytes: get_env_or("CACHE_COMPRESSION_THRESHOLD_BYTES", "1024").parse().unwrap_or(1024),
+            level: get_env_or("CACHE_COMPRESSION_LEVEL", "6").parse::<u32>().unwrap_or(6).min(9),
+        }
+    }
+}
+
+// Bytes to store for `value`, and how they are encoded
+pub fn encode(value: &[u8], config: &CompressionConfig) -> (Vec<u8>, Encoding) {
+    if config.threshold_bytes == 0 || value.len() < config.threshold_bytes {
+        return (tagged(Encoding::Identity, value), Encoding::Identity);
+    }
+
+    let started = Instant::now();
+    let mut encoder = GzEncoder::new(tagged(Encoding::Gzip, &[]), Compression::new(config.level));
+    let compressed = encoder.write_all(value).and_then(|_| encoder.finish());
+    CACHE_COMPRESSION_DURATION.with_label_values(&["compress"]).observe(started.elapsed().as_secs_f64());
+
+    match compressed {
+        Ok(stored) if stored.len() < value.len() => {
+            CACHE_COMPRESSION_RATIO.observe(value.len() as f64 / stored.len() as f64);
+            CACHE_COMPRESSION_BYTES_TOTAL.with_label_values(&["original"]).inc_by(value.len() as f64);
+            CACHE_COMPRESSION_BYTES_TOTAL.with_label_values(&["stored"]).inc_by(stored.len() as f64);
+            (stored, Encoding::Gzip)
+        }
+        // Incompressible (already compressed, random) data is stored as-is
+        _ => (tagged(Encoding::Identity, value), Encoding::Identity),
+    }
+}
+
+// Original bytes of a stored value
+pub fn decode(stored: &[u8]) -> Result<(Vec<u8>, Encoding), String> {
+    let Some(tagged) = stored.strip_prefix(VALUE_HEADER) else {
+        return Ok((stored.to_vec(), Encoding::Identity));
+    };
+    let (tag, body) = tagged.split_first().ok_or_else(|| "Cached value has no encoding tag".to_string())?;
+    let compressed = match Encoding::from_tag(*tag) {
+        Some(Encoding::Identity) => return Ok((body.to_vec(), Encoding::Identity)),
+        Some(Encoding::Gzip) => body,
+        None => return Err(format!("Unknown cache value encoding tag 0x{:02x}", tag)),
+    };
+
+    let started = Instant::now();
+    let mut value = Vec::new();
+    let result = GzDecoder::new(compressed).read_to_end(&mut value);
+    CACHE_COMPRESSION_DURATION.with_label_values(&["decompress"]).observe(started.elapsed().as_secs_f64());
+    result.map_err(|e| format!("Failed to decompress cached value: {}", e))?;
+    Ok((value, Encoding::Gzip))
+}
@@ -0,0 +1,378 @@
+// Per-backend concurrency limits
+//
+// Each backend gets a limit, starting at CONCURRENCY_LIMIT_<BACKEND> (default 32). Requests
+// routed to a saturated backend are rejected immediately with 503 and Retry-After instead of
+// queueing.
+//
+// CONCURRENCY_ALGORITHM picks how the limit moves as requests complete:
+// - fixed (default): it doesn't
+// - aimd: +1 per request that succeeds while at least half the limit is in use, x0.9 per request
+//   that fails (5xx) or takes longer than CONCURRENCY_TIMEOUT_MS (default 1000)
+// - vegas: compares each request's latency with the lowest seen, which stands for the backend
+//   without a queue. The estimated queue, limit * (1 - min / latency), grows the limit while it
+//   is below 3*log10(limit) and shrinks it above 6*log10(limit); failures shrink it too. The
+//   lowest latency is forgotten every PROBE_INTERVAL samples, so it follows a backend that moved.
+// Adaptive limits stay within CONCURRENCY_MIN_LIMIT (default 1) and CONCURRENCY_MAX_LIMIT
+// (default 256). backend_concurrency_limit{backend} exports them, GET /admin/concurrency shows
+// the limiter state and POST /admin/concurrency/reset puts limits back where they started.
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+
+use actix_web::body::{BodySize, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header;
+use actix_web::middleware::Next;
+use actix_web::web::Bytes;
+use actix_web::{web, HttpResponse, Responder};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::services::{backend_for_path, BACKENDS};
+use crate::{get_env_or, BACKEND_CONCURRENCY_LIMIT, BACKEND_INFLIGHT, BACKEND_REJECTED_TOTAL};
+
+// Vegas samples between resets of the lowest latency
+const PROBE_INTERVAL: u64 = 1_000;
+// AIMD multiplier applied on a failure
+const BACKOFF_RATIO: f64 = 0.9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Algorithm {
+    Fixed,
+    Aimd,
+    Vegas,
+}
+
+impl Algorithm {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "fixed" => Ok(Algorithm::Fixed),
+            "aimd" => Ok(Algorithm::Aimd),
+            "vegas" => Ok(Algorithm::Vegas),
+            other => Err(format!("Unknown concurrency algorithm {:?}: expected fixed, aimd or vegas", other)),
+        }
+    }
+}
+
+fn algorithm() -> Algorithm {
+    Algorithm::parse(&get_env_or("CONCURRENCY_ALGORITHM", "fixed")).unwrap_or_else(|e| {
+        log::warn!("{}; using fixed limits", e);
+        Algorithm::Fixed
+    })
+}
+
+fn backend_limit(backend: &str) -> usize {
+    let key = format!("CONCURRENCY_LIMIT_{}", backend.to_ascii_uppercase());
+    get_env_or(&key, "32").parse().unwrap_or(32).max(1)
+}
+
+// ============================================================================
+// Limit algorithms
+// ============================================================================
+
+#[derive(Debug, Clone)]
+pub struct AdaptiveLimit {
+    pub algorithm: Algorithm,
+    pub initial: usize,
+    pub min: usize,
+    pub max: usize,
+    // Requests slower than this count as failures for AIMD
+    pub timeout: Duration,
+    limit: f64,
+    min_latency: Option<Duration>,
+    samples: u64,
+}
+
+impl AdaptiveLimit {
+    pub fn new(algorithm: Algorithm, initial: usize, min: usize, max: usize, timeout: Duration) -> Self {
+        let min = min.max(1);
+        let max = max.max(min);
+        let initial = if algorithm == Algorithm::Fixed { initial } else { initial.clamp(min, max) };
+        AdaptiveLimit {
+            algorithm,
+            initial,
+            min,
+            max,
+            timeout,
+            limit: initial as f64,
+            min_latency: None,
+            samples: 0,
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        (self.limit as usize).max(1)
+    }
+
+    pub fn min_latency(&self) -> Option<Duration> {
+        self.min_latency
+    }
+
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    pub fn reset(&mut self) {
+        self.limit = self.initial as f64;
+        self.min_latency = None;
+        self.samples = 0;
+    }
+
+    // One completed request: how long it took, how many requests were in flight when it
+    // started (itself included), and whether it failed
+    pub fn on_sample(&mut self, latency: Duration, inflight: usize, failed: bool) {
+        self.samples += 1;
+        // A limit that isn't being used says nothing about whether more would fit
+        let saturated = inflight * 2 >= self.limit();
+        let limit = match self.algorithm {
+            Algorithm::Fixed => return,
+            Algorithm::Aimd => {
+                if failed || latency > self.timeout {
+                    self.limit * BACKOFF_RATIO
+                } else if saturated {
+                    self.limit + 1.0
+                } else {
+                    self.limit
+                }
+            }
+            Algorithm::Vegas => {
+                if self.samples.is_multiple_of(PROBE_INTERVAL) {
+                    self.min_latency = None;
+                }
+                let min_latency = self.min_latency.map_or(latency, |min| min.min(latency));
+                self.min_latency = Some(min_latency);
+                let step = self.limit.log10().max(1.0);
+                let queue = self.limit * (1.0 - min_latency.as_secs_f64() / latency.as_secs_f64().max(1e-9));
+                if failed || queue > 6.0 * step {
+                    self.limit - step
+                } else if queue < 3.0 * step && saturated {
+                    self.limit + step
+                } else {
+                    self.limit
+                }
+            }
+        };
+        self.limit = limit.clamp(self.min as f64, self.max as f64);
+    }
+}
+
+// ============================================================================
+// Limiters
+// ============================================================================
+
+struct LimiterState {
+    limit: AdaptiveLimit,
+    inflight: usize,
+}
+
+pub struct Limiter {
+    backend: &'static str,
+    state: Mutex<LimiterState>,
+}
+
+impl Limiter {
+    fn new(backend: &'static str) -> Self {
+        let limit = AdaptiveLimit::new(
+            algorithm(),
+            backend_limit(backend),
+            get_env_or("CONCURRENCY_MIN_LIMIT", "1").parse().unwrap_or(1),
+            get_env_or("CONCURRENCY_MAX_LIMIT", "256").parse().unwrap_or(256),
+            Duration::from_millis(get_env_or("CONCURRENCY_TIMEOUT_MS", "1000").parse().unwrap_or(1000)),
+        );
+        BACKEND_CONCURRENCY_LIMIT.with_label_values(&[backend]).set(limit.limit() as i64);
+        Limiter { backend, state: Mutex::new(LimiterState { limit, inflight: 0 }) }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, LimiterState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn info(&self) -> serde_json::Value {
+        let state = self.state();
+        serde_json::json!({
+            "algorithm": state.limit.algorithm,
+            "limit": state.limit.limit(),
+            "initial_limit": state.limit.initial,
+            "min_limit": state.limit.min,
+            "max_limit": state.limit.max,
+            "inflight": state.inflight,
+            "samples": state.limit.samples(),
+            "min_latency_ms": state.limit.min_latency().map(|latency| latency.as_secs_f64() * 1000.0)
+        })
+    }
+
+    fn reset(&self) {
+        let mut state = self.state();
+        state.limit.reset();
+        BACKEND_CONCURRENCY_LIMIT.with_label_values(&[self.backend]).set(state.limit.limit() as i64);
+    }
+}
+
+lazy_static! {
+    static ref LIMITERS: HashMap<&'static str, Arc<Limiter>> =
+        BACKENDS.iter().map(|backend| (*backend, Arc::new(Limiter::new(backend)))).collect();
+}
+
+// Holds a backend permit and keeps the in-flight gauge in step with it
+pub struct BackendPermit {
+    limiter: Arc<Limiter>,
+    inflight: usize,
+    acquired: Instant,
+}
+
+impl BackendPermit {
+    // Feeds the request's latency and outcome to the limit, then releases the permit
+    pub fn complete(self, failed: bool) {
+        let limit = {
+            let mut state = self.limiter.state();
+            state.limit.on_sample(self.acquired.elapsed(), self.inflight, failed);
+            state.limit.limit()
+        };
+        BACKEND_CONCURRENCY_LIMIT.with_label_values(&[self.limiter.backend]).set(limit as i64);
+    }
+}
+
+impl Drop for BackendPermit {
+    fn drop(&mut self) {
+        self.limiter.state().inflight -= 1;
+        BACKEND_INFLIGHT.with_label_values(&[self.limiter.backend]).dec();
+    }
+}
+
+// None when the backend is saturated
+pub fn try_acquire(backend: &'static str) -> Option<BackendPermit> {
+    let limiter = LIMITERS.get(backend)?;
+    let inflight = {
+        let mut state = limiter.state();
+        if state.inflight >= state.limit.limit() {
+            return None;
+        }
+        state.inflight += 1;
+        state.inflight
+    };
+    BACKEND_INFLIGHT.with_label_values(&[backend]).inc();
+    Some(BackendPermit { limiter: limiter.clone(), inflight, acquired: Instant::now() })
+}
+
+// Holds the permit until the response body has been sent: streamed responses (exports,
+// cursors) keep the backend busy long after the handler returns. A body dropped part way,
+// such as on a client disconnect, releases the permit without feeding the limit a sample.
+struct PermitBody {
+    body: BoxBody,
+    permit: Option<BackendPermit>,
+    failed: bool,
+}
+
+impl MessageBody for PermitBody {
+    type Error = Box<dyn std::error::Error>;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let this = self.get_mut();
+        let chunk = ready!(Pin::new(&mut this.body).poll_next(cx));
+        let failed = match &chunk {
+            Some(Ok(_)) => return Poll::Ready(chunk),
+            Some(Err(_)) => true,
+            None => this.failed,
+        };
+        if let Some(permit) = this.permit.take() {
+            permit.complete(failed);
+        }
+        Poll::Ready(chunk)
+    }
+}
+
+pub async fn concurrency_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let backend = match backend_for_path(req.path()) {
+        Some(backend) => backend,
+        None => return Ok(next.call(req).await?.map_into_boxed_body()),
+    };
+
+    match try_acquire(backend) {
+        Some(permit) => {
+            let res = match next.call(req).await {
+                Ok(res) => res,
+                Err(e) => {
+                    permit.complete(true);
+                    return Err(e);
+                }
+            };
+            let failed = res.status().is_server_error();
+            Ok(res.map_body(|_, body| {
+                let body = body.boxed();
+                // Empty bodies are never polled, so there is nothing to wait for
+                if matches!(body.size(), BodySize::None | BodySize::Sized(0)) {
+                    permit.complete(failed);
+                    body
+                } else {
+                    PermitBody { body, permit: Some(permit), failed }.boxed()
+                }
+            }))
+        }
+        None => {
+            BACKEND_REJECTED_TOTAL.with_label_values(&[backend]).inc();
+            let retry_after = get_env_or("CONCURRENCY_RETRY_AFTER_SECONDS", "1");
+            let response = HttpResponse::ServiceUnavailable()
+                .insert_header((header::RETRY_AFTER, retry_after))
+                .json(serde_json::json!({
+                    "status": "error",
+                    "backend": backend,
+                    "error": format!("Too many concurrent {} requests, retry later", backend)
+                }));
+            Ok(req.into_response(response))
+        }
+    }
+}
+
+// ============================================================================
+// Admin handlers
+// ============================================================================
+
+// GET /admin/concurrency
+pub async fn concurrency_limits() -> impl Responder {
+    let limiters: serde_json::Map<String, serde_json::Value> =
+        BACKENDS.iter().filter_map(|backend| Some((backend.to_string(), LIMITERS.get(backend)?.info()))).collect();
+    HttpResponse::Ok().json(serde_json::json!({ "algorithm": algorithm(), "backends": limiters }))
+}
+
+#[derive(Deserialize)]
+pub struct ResetQuery {
+    // Resets every backend when left out
+    backend: Option<String>,
+}
+
+// POST /admin/concurrency/reset
+pub async fn reset_limits(query: web::Query<ResetQuery>) -> impl Responder {
+    let reset: Vec<&str> = match query.backend.as_deref() {
+        Some(backend) => match LIMITERS.get_key_value(backend) {
+            Some((name, limiter)) => {
+                limiter.reset();
+                vec![*name]
+            }
+            None => {
+                return HttpResponse::NotFound().json(serde_json::json!({
+                    "status": "error",
+                    "error": format!("Unknown backend: {}", backend)
+                }))
+            }
+        },
+        None => BACKENDS
+            .iter()
+            .filter_map(|backend| {
+                LIMITERS.get(backend)?.reset();
+                Some(*backend)
+            })
+            .collect(),
+    };
+    HttpResponse::Ok().json(serde_json::json!({ "status": "reset", "backends": reset }))
+}
@@ -0,0 +1,272 @@
+// Cross-database consistency checks for the seeded dataset
+//
+// Each database's rows are rendered to the same canonical text form, hashed, and summed
+// per shard (id modulo shard count), so a divergent shard pinpoints where copies differ.
+
+use actix_web::{web, HttpResponse, Responder};
+use futures_util::TryStreamExt;
+use mongodb::bson::{doc, Document};
+use mysql_async::prelude::Queryable;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::seed::{MONGODB_DATABASE, ORDERS_TABLE, USERS_TABLE};
+use crate::services;
+use crate::{mongodb_client, mysql_connection, postgres_client};
+
+const DEFAULT_SHARDS: u64 = 16;
+const MAX_SHARDS: u64 = 1024;
+const DATABASES: [&str; 3] = ["postgres", "mysql", "mongodb"];
+
+#[derive(Deserialize)]
+pub struct ConsistencyQuery {
+    shards: Option<u64>,
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct ShardDigest {
+    pub rows: u64,
+    pub checksum: String,
+}
+
+// Per-table shard digests for one database: [users, orders]
+type DatabaseDigest = [Vec<ShardDigest>; 2];
+
+fn row_hash(canonical: &str) -> u64 {
+    let digest = Sha256::digest(canonical.as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(bytes)
+}
+
+// Order-independent: rows are combined with a wrapping sum
+pub fn digest_rows<I>(rows: I, shards: u64) -> Vec<ShardDigest>
+where
+    I: IntoIterator<Item = (i64, String)>,
+{
+    let mut sums = vec![(0u64, 0u64); shards as usize];
+    for (id, canonical) in rows {
+        let shard = id.rem_euclid(shards as i64) as usize;
+        sums[shard].0 += 1;
+        sums[shard].1 = sums[shard].1.wrapping_add(row_hash(&canonical));
+    }
+    sums.into_iter()
+        .map(|(rows, checksum)| ShardDigest { rows, checksum: format!("{:016x}", checksum) })
+        .collect()
+}
+
+fn user_row(id: i64, name: &str, email: &str, created_epoch: i64) -> (i64, String) {
+    (id, format!("{}|{}|{}|{}", id, name, email, created_epoch))
+}
+
+fn order_row(id: i64, user_id: i64, amount_cents: i64, status: &str, created_epoch: i64) -> (i64, String) {
+    (id, format!("{}|{}|{}|{}|{}", id, user_id, amount_cents, status, created_epoch))
+}
+
+async fn postgres_digest(shards: u64) -> Result<DatabaseDigest, String> {
+    let client = postgres_client().await?;
+
+    let users = client
+        .query(
+            format!("SELECT id, name, email, EXTRACT(EPOCH FROM created_at)::bigint FROM {}", USERS_TABLE).as_str(),
+            &[],
+        )
+        .await
+        .map_err(|e| format!("Query {} failed: {}", USERS_TABLE, e))?;
+    let orders = client
+        .query(
+            format!(
+                "SELECT id, user_id, amount_cents, status, EXTRACT(EPOCH FROM created_at)::bigint FROM {}",
+                ORDERS_TABLE
+            )
+            .as_str(),
+            &[],
+        )
+        .await
+        .map_err(|e| format!("Query {} failed: {}", ORDERS_TABLE, e))?;
+
+    Ok([
+        digest_rows(
+            users.iter().map(|r| user_row(r.get(0), r.get(1), r.get(2), r.get(3))),
+            shards,
+        ),
+        digest_rows(
+            orders.iter().map(|r| order_row(r.get(0), r.get(1), r.get(2), r.get(3), r.get(4))),
+            shards,
+        ),
+    ])
+}
+
+async fn mysql_digest(shards: u64) -> Result<DatabaseDigest, String> {
+    let mut conn = mysql_connection().await?;
+
+    // DATETIME columns hold UTC wall-clock values; read them back as UTC epochs
+    conn.query_drop("SET time_zone = '+00:00'")
+        .await
+        .map_err(|e| format!("Session setup failed: {}", e))?;
+
+    let users: Vec<(i64, String, String, i64)> = conn
+        .query(format!("SELECT id, name, email, UNIX_TIMESTAMP(created_at) FROM {}", USERS_TABLE))
+        .await
+        .map_err(|e| format!("Query {} failed: {}", USERS_TABLE, e))?;
+    let orders: Vec<(i64, i64, i64, String, i64)> = conn
+        .query(format!(
+            "SELECT id, user_id, amount_cents, status, UNIX_TIMESTAMP(created_at) FROM {}",
+            ORDERS_TABLE
+        ))
+        .await
+        .map_err(|e| format!("Query {} failed: {}", ORDERS_TABLE, e))?;
+    let _ = conn.disconnect().await;
+
+    Ok([
+        digest_rows(users.iter().map(|(id, name, email, ts)| user_row(*id, name, email, *ts)), shards),
+        digest_rows(
+            orders.iter().map(|(id, user_id, amount, status, ts)| order_row(*id, *user_id, *amount, status, *ts)),
+            shards,
+        ),
+    ])
+}
+
+async fn mongodb_collection_rows<F>(collection: &str, to_row: F) -> Result<Vec<(i64, String)>, String>
+where
+    F: Fn(&Document) -> Option<(i64, String)>,
+{
+    let client = mongodb_client().await?;
+    let cursor = client
+        .database(MONGODB_DATABASE)
+        .collection::<Document>(collection)
+        .find(doc! {})
+        .await
+        .map_err(|e| format!("Query {} failed: {}", collection, e))?;
+    let docs: Vec<Document> = cursor
+        .try_collect()
+        .await
+        .map_err(|e| format!("Query {} failed: {}", collection, e))?;
+
+    docs.iter()
+        .map(|d| to_row(d).ok_or_else(|| format!("Malformed document in {}", collection)))
+        .collect()
+}
+
+async fn mongodb_digest(shards: u64) -> Result<DatabaseDigest, String> {
+    let users = mongodb_collection_rows(USERS_TABLE, |d| {
+        Some(user_row(
+            d.get_i64("_id").ok()?,
+            d.get_str("name").ok()?,
+            d.get_str("email").ok()?,
+            d.get_datetime("created_at").ok()?.timestamp_millis() / 1000,
+        ))
+    })
+    .await?;
+    let orders = mongodb_collection_rows(ORDERS_TABLE, |d| {
+        Some(order_row(
+            d.get_i64("_id").ok()?,
+            d.get_i64("user_id").ok()?,
+            d.get_i64("amount_cents").ok()?,
+            d.get_str("status").ok()?,
+            d.get_datetime("created_at").ok()?.timestamp_millis() / 1000,
+        ))
+    })
+    .await?;
+
+    Ok([digest_rows(users, shards), digest_rows(orders, shards)])
+}
+
+// Shards whose digest differs between any of the reachable databases
+pub fn find_divergences(table: &str, digests: &[(&str, &Vec<ShardDigest>)]) -> Vec<serde_json::Value> {
+    let shard_count = digests.iter().map(|(_, d)| d.len()).max().unwrap_or(0);
+    (0..shard_count)
+        .filter(|&shard| {
+            let mut values = digests.iter().map(|(_, d)| d.get(shard));
+            match values.next() {
+                Some(first) => values.any(|v| v != first),
+                None => false,
+            }
+        })
+        .map(|shard| {
+            let per_db: serde_json::Map<String, serde_json::Value> = digests
+                .iter()
+                .map(|(db, d)| (db.to_string(), serde_json::json!(d.get(shard))))
+                .collect();
+            serde_json::json!({ "table": table, "shard": shard, "databases": per_db })
+        })
+        .collect()
+}
+
+// Disabled databases are left out of the comparison rather than reported as errors
+async fn if_enabled<F>(database: &str, fut: F) -> Option<Result<DatabaseDigest, String>>
+where
+    F: std::future::Future<Output = Result<DatabaseDigest, String>>,
+{
+    if services::is_enabled(database) {
+        Some(fut.await)
+    } else {
+        None
+    }
+}
+
+pub async fn check_consistency(query: web::Query<ConsistencyQuery>) -> impl Responder {
+    let shards = query.shards.unwrap_or(DEFAULT_SHARDS);
+    if shards == 0 || shards > MAX_SHARDS {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "status": "error",
+            "error": format!("shards must be between 1 and {}", MAX_SHARDS)
+        }));
+    }
+
+    let (postgres, mysql, mongodb) = tokio::join!(
+        if_enabled("postgres", postgres_digest(shards)),
+        if_enabled("mysql", mysql_digest(shards)),
+        if_enabled("mongodb", mongodb_digest(shards)),
+    );
+
+    let mut reachable: Vec<(&str, DatabaseDigest)> = Vec::new();
+    let mut errors = serde_json::Map::new();
+    for (name, result) in DATABASES.iter().zip([postgres, mysql, mongodb]) {
+        match result {
+            Some(Ok(digest)) => reachable.push((*name, digest)),
+            Some(Err(e)) => {
+                errors.insert(name.to_string(), serde_json::json!(e));
+            }
+            None => {}
+        }
+    }
+
+    if reachable.len() < 2 {
+        return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "status": "error",
+            "error": "At least two databases must be reachable to compare",
+            "errors": errors
+        }));
+    }
+
+    let mut divergences = Vec::new();
+    let mut totals = serde_json::Map::new();
+    for (index, table) in [USERS_TABLE, ORDERS_TABLE].iter().enumerate() {
+        let digests: Vec<(&str, &Vec<ShardDigest>)> =
+            reachable.iter().map(|(db, digest)| (*db, &digest[index])).collect();
+        divergences.extend(find_divergences(table, &digests));
+
+        let counts: serde_json::Map<String, serde_json::Value> = digests
+            .iter()
+            .map(|(db, d)| (db.to_string(), serde_json::json!(d.iter().map(|s| s.rows).sum::<u64>())))
+            .collect();
+        totals.insert(table.to_string(), serde_json::Value::Object(counts));
+    }
+
+    let status = if !divergences.is_empty() {
+        "divergent"
+    } else if !errors.is_empty() {
+        "incomplete"
+    } else {
+        "consistent"
+    };
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": status,
+        "shards": shards,
+        "row_counts": totals,
+        "divergences": divergences,
+        "errors": errors
+    }))
+}
@@ -0,0 +1,61 @@
+// Optional tokio-console support for debugging stalls
+//
+// Built with `--features tokio-console` and RUSTFLAGS="--cfg tokio_unstable", and switched on
+// at runtime with TOKIO_CONSOLE=true, the app serves console-subscriber's gRPC endpoint on
+// TOKIO_CONSOLE_BIND (default 0.0.0.0:6669) for `tokio-console http://<host>:6669`. Every task
+// spawned after startup shows up there with its poll times and wakers: connection drivers and
+// pool checkouts, the scheduler, consumers and listeners, and streaming response bodies.
+// Instrumentation costs memory and CPU per task, so it stays off unless asked for.
+
+use serde::Serialize;
+
+use crate::get_env_or;
+
+#[cfg(all(feature = "tokio-console", not(tokio_unstable)))]
+compile_error!("the tokio-console feature needs RUSTFLAGS=\"--cfg tokio_unstable\"");
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ConsoleStatus {
+    // Built with the tokio-console feature
+    pub compiled: bool,
+    // TOKIO_CONSOLE=true
+    pub requested: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bind: Option<String>,
+}
+
+fn requested() -> bool {
+    get_env_or("TOKIO_CONSOLE", "false").parse().unwrap_or(false)
+}
+
+fn bind_address() -> String {
+    get_env_or("TOKIO_CONSOLE_BIND", "0.0.0.0:6669")
+}
+
+pub fn status() -> ConsoleStatus {
+    let compiled = cfg!(feature = "tokio-console");
+    let requested = requested();
+    ConsoleStatus { compiled, requested, bind: (compiled && requested).then(bind_address) }
+}
+
+// Call once, early in main, so the tasks spawned during startup are instrumented too
+#[cfg(feature = "tokio-console")]
+pub fn init() -> Result<(), String> {
+    if !requested() {
+        return Ok(());
+    }
+    let bind = bind_address();
+    let address: std::net::SocketAddr =
+        bind.parse().map_err(|e| format!("Invalid TOKIO_CONSOLE_BIND '{}': {}", bind, e))?;
+    console_subscriber::ConsoleLayer::builder().with_default_env().server_addr(address).init();
+    log::info!("tokio-console listening on {}", address);
+    Ok(())
+}
+
+#[cfg(not(feature = "tokio-console"))]
+pub fn init() -> Result<(), String> {
+    if requested() {
+        log::warn!("TOKIO_CONSOLE=true but this build lacks the tokio-console feature; ignoring");
+    }
+    Ok(())
+}
@@ -0,0 +1,267 @@
+// Push-based RabbitMQ consumers with prefetch (basic.qos) and manual acks
+//
+// The prefetch count caps how many unacknowledged deliveries the broker pushes to a consumer.
+// With prefetch=1 every message waits for the previous ack to reach the broker; a larger window
+// keeps deliveries in flight while earlier ones are processed.
+
+use std::time::{Duration, Instant};
+
+use actix_web::{web, HttpResponse, Responder};
+use futures_util::StreamExt;
+use serde::Deserialize;
+
+use crate::amqp_connection;
+use crate::message_codec;
+use crate::stream_buffer;
+
+const DEFAULT_PREFETCH: u16 = 10;
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 10_000;
+const DEFAULT_IDLE_MS: u64 = 2_000;
+const MAX_DEMO_MESSAGES: usize = 20_000;
+
+fn consume_options() -> lapin::options::BasicConsumeOptions {
+    // Manual acks, so the prefetch window actually applies
+    lapin::options::BasicConsumeOptions { no_ack: false, ..Default::default() }
+}
+
+// ============================================================================
+// Streaming consumer
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct StreamConsumeQuery {
+    prefetch: Option<u16>,
+    // Stop after this many messages
+    limit: Option<usize>,
+    // Stop when no message arrives for this long
+    idle_ms: Option<u64>,
+}
+
+// NDJSON: one line per acknowledged message, then a summary line
+pub async fn stream_consume(path: web::Path<String>, query: web::Query<StreamConsumeQuery>) -> impl Responder {
+    let queue = path.into_inner();
+    // Deliveries are acked only once written, so the prefetch window is this client's buffer
+    let max_prefetch = stream_buffer::BufferConfig::from_env().capacity.min(u16::MAX as usize) as u16;
+    let prefetch = query.prefetch.unwrap_or(DEFAULT_PREFETCH).clamp(1, max_prefetch);
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let idle = Duration::from_millis(query.idle_ms.unwrap_or(DEFAULT_IDLE_MS));
+
+    let conn = match amqp_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return HttpResponse::ServiceUnavailable().json(serde_json::json!({ "status": "error", "error": e })),
+    };
+    let setup = async {
+        let channel = conn.create_channel().await.map_err(|e| format!("Channel creation failed: {}", e))?;
+        channel
+            .basic_qos(prefetch, lapin::options::BasicQosOptions::default())
+            .await
+            .map_err(|e| format!("basic.qos failed: {}", e))?;
+        let consumer = channel
+            .basic_consume(&queue, "", consume_options(), lapin::types::FieldTable::default())
+            .await
+            .map_err(|e| format!("basic.consume failed: {}", e))?;
+        Ok::<_, String>((channel, consumer))
+    }
+    .await;
+    let (channel, consumer) = match setup {
+        Ok(setup) => setup,
+        Err(e) => {
+            let _ = conn.close(0, "Error").await;
+            return HttpResponse::InternalServerError().json(serde_json::json!({ "status": "error", "queue": queue, "error": e }));
+        }
+    };
+
+    // The connection and channel travel with the stream so the consumer lives as long as the response
+    struct ConsumeState {
+        conn: lapin::Connection,
+        _channel: lapin::Channel,
+        consumer: lapin::Consumer,
+        received: usize,
+        started: Instant,
+        done: bool,
+    }
+
+    let state = ConsumeState { conn, _channel: channel, consumer, received: 0, started: Instant::now(), done: false };
+    let body = futures_util::stream::unfold(state, move |mut state| async move {
+        if state.done {
+            return None;
+        }
+        let next = if state.received < limit {
+            tokio::time::timeout(idle, state.consumer.next()).await.ok().flatten()
+        } else {
+            None
+        };
+        let line = match next {
+            Some(Ok(delivery)) => {
+                state.received += 1;
+                let content_type = delivery.properties.content_type().as_ref().map(|ct| ct.as_str().to_string());
+                let decoded = message_codec::decode(content_type.as_deref(), &delivery.data);
+                // Undecodable messages go to the dead-letter exchange rather than being acked away
+                let settled = match &decoded {
+                    Ok(_) => delivery.ack(lapin::options::BasicAckOptions::default()).await,
+                    Err(_) => {
+                        let options = lapin::options::BasicNackOptions { multiple: false, requeue: false };
+                        delivery.nack(options).await
+                    }
+                };
+                let acked = decoded.is_ok() && settled.is_ok();
+                serde_json::json!({
+                    "delivery_tag": delivery.delivery_tag,
+                    "redelivered": delivery.redelivered,
+                    "content_type": content_type,
+                    "message": decoded.as_ref().ok(),
+                    "error": decoded.as_ref().err(),
+                    "acked": acked,
+                    "dead_lettered": decoded.is_err() && settled.is_ok()
+                })
+            }
+            other => {
+                state.done = true;
+                let elapsed = state.started.elapsed().as_secs_f64();
+                let _ = state.conn.close(0, "Done").await;
+                serde_json::json!({
+                    "summary": true,
+                    "received": state.received,
+                    "prefetch": prefetch,
+                    "stopped": match other {
+                        Some(Err(_)) => "error",
+                        _ if state.received >= limit => "limit",
+                        _ => "idle",
+                    },
+                    "elapsed_ms": elapsed * 1000.0
+                })
+            }
+        };
+        Some((Ok::<_, std::io::Error>(web::Bytes::from(format!("{}\n", line))), state))
+    });
+
+    HttpResponse::Ok().content_type("application/x-ndjson").streaming(body)
+}
+
+// ============================================================================
+// Prefetch comparison
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct PrefetchDemoQuery {
+    messages: Option<usize>,
+    // Comma-separated prefetch counts to compare
+    prefetch: Option<String>,
+}
+
+pub fn parse_prefetch_list(value: Option<&str>) -> Result<Vec<u16>, String> {
+    let list: Vec<u16> = value
+        .unwrap_or("1,100")
+        .split(',')
+        .map(|p| p.trim().parse::<u16>().ok().filter(|p| *p > 0))
+        .collect::<Option<_>>()
+        .ok_or_else(|| "prefetch must be a comma-separated list of counts between 1 and 65535".to_string())?;
+    if list.is_empty() || list.len() > 5 {
+        return Err("Compare between 1 and 5 prefetch values".to_string());
+    }
+    Ok(list)
+}
+
+// Seeds a fresh exclusive queue with `messages` messages and drains it with manual acks
+async fn measure(conn: &lapin::Connection, messages: usize, prefetch: u16) -> Result<serde_json::Value, String> {
+    let channel = conn.create_channel().await.map_err(|e| format!("Channel creation failed: {}", e))?;
+    let queue = format!("prefetch-demo-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+    let declare = lapin::options::QueueDeclareOptions { exclusive: true, ..Default::default() };
+    channel
+        .queue_declare(&queue, declare, lapin::types::FieldTable::default())
+        .await
+        .map_err(|e| format!("Queue declare failed: {}", e))?;
+
+    channel
+        .confirm_select(lapin::options::ConfirmSelectOptions::default())
+        .await
+        .map_err(|e| format!("confirm.select failed: {}", e))?;
+    let mut confirms = Vec::with_capacity(messages);
+    for i in 0..messages {
+        let confirm = channel
+            .basic_publish(
+                "",
+                &queue,
+                lapin::options::BasicPublishOptions::default(),
+                i.to_string().as_bytes(),
+                lapin::BasicProperties::default(),
+            )
+            .await
+            .map_err(|e| format!("Publish failed: {}", e))?;
+        confirms.push(confirm);
+    }
+    // Wait until the whole queue is on the broker before timing consumption
+    for confirm in confirms {
+        confirm.await.map_err(|e| format!("Publish confirm failed: {}", e))?;
+    }
+
+    channel
+        .basic_qos(prefetch, lapin::options::BasicQosOptions::default())
+        .await
+        .map_err(|e| format!("basic.qos failed: {}", e))?;
+    let mut consumer = channel
+        .basic_consume(&queue, "", consume_options(), lapin::types::FieldTable::default())
+        .await
+        .map_err(|e| format!("basic.consume failed: {}", e))?;
+
+    let started = Instant::now();
+    let mut received = 0;
+    while received < messages {
+        match tokio::time::timeout(Duration::from_secs(10), consumer.next()).await {
+            Ok(Some(Ok(delivery))) => {
+                delivery
+                    .ack(lapin::options::BasicAckOptions::default())
+                    .await
+                    .map_err(|e| format!("Ack failed: {}", e))?;
+                received += 1;
+            }
+            Ok(Some(Err(e))) => return Err(format!("Consume failed: {}", e)),
+            Ok(None) | Err(_) => break,
+        }
+    }
+    let elapsed = started.elapsed().as_secs_f64();
+    let _ = channel.close(0, "Done").await;
+
+    Ok(serde_json::json!({
+        "prefetch": prefetch,
+        "received": received,
+        "elapsed_ms": elapsed * 1000.0,
+        "messages_per_second": if elapsed > 0.0 { received as f64 / elapsed } else { 0.0 }
+    }))
+}
+
+pub async fn prefetch_demo(query: web::Query<PrefetchDemoQuery>) -> impl Responder {
+    let messages = query.messages.unwrap_or(2_000).clamp(1, MAX_DEMO_MESSAGES);
+    let prefetch = match parse_prefetch_list(query.prefetch.as_deref()) {
+        Ok(prefetch) => prefetch,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "status": "error", "error": e })),
+    };
+
+    let conn = match amqp_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return HttpResponse::ServiceUnavailable().json(serde_json::json!({ "status": "error", "error": e })),
+    };
+    let mut results = Vec::new();
+    for count in prefetch {
+        match measure(&conn, messages, count).await {
+            Ok(result) => results.push(result),
+            Err(e) => {
+                let _ = conn.close(0, "Error").await;
+                return HttpResponse::InternalServerError().json(serde_json::json!({
+                    "status": "error",
+                    "prefetch": count,
+                    "error": e,
+                    "results": results
+                }));
+            }
+        }
+    }
+    let _ = conn.close(0, "Done").await;
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "messages": messages,
+        "results": results
+    }))
+}
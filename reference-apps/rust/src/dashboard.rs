@@ -0,0 +1,35 @@
+// Status dashboard at /ui
+//
+// A static page compiled into the binary with rust-embed. It polls /health/all,
+// /redis/cluster/nodes and the queue info endpoint from the browser, so it needs nothing
+// beyond the API itself.
+
+use actix_web::http::header;
+use actix_web::{web, HttpResponse, Responder};
+use rust_embed::RustEmbed;
+
+#[derive(RustEmbed)]
+#[folder = "src/ui/"]
+struct Assets;
+
+fn asset_response(path: &str) -> HttpResponse {
+    match Assets::get(path) {
+        Some(file) => HttpResponse::Ok()
+            .content_type(mime_guess::from_path(path).first_or_octet_stream().as_ref())
+            .insert_header((header::CACHE_CONTROL, "no-cache"))
+            .body(file.data.into_owned()),
+        None => HttpResponse::NotFound().json(serde_json::json!({
+            "status": "error",
+            "error": format!("No dashboard asset '{}'", path)
+        })),
+    }
+}
+
+pub async fn index() -> impl Responder {
+    asset_response("index.html")
+}
+
+pub async fn asset(path: web::Path<String>) -> impl Responder {
+    let path = path.into_inner();
+    asset_response(if path.is_empty() { "index.html" } else { &path })
+}
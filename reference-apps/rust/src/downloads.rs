@@ -0,0 +1,232 @@
+// Downloads of pipeline uploads from MinIO, with byte ranges and conditional requests
+//
+// GET /examples/pipeline/uploads/{id} looks the upload up in MongoDB (test.uploads) and streams
+// the object from MinIO without buffering it. A single `Range: bytes=...` is answered with 206
+// and only that slice is fetched from MinIO; a range past the end gets 416. If-None-Match and
+// If-Modified-Since answer 304, and If-Range lets a client resume an interrupted download only
+// while the object is unchanged, getting the whole object again otherwise. HEAD returns the
+// same headers so a client can learn the size before fetching pieces.
+//
+// Object keys embed a fresh UUID and are never rewritten, so the object can't change between
+// the HEAD that validates the request and the GET that serves it.
+
+use actix_web::body::SizedStream;
+use actix_web::http::header::{self, ContentDisposition, DispositionParam, DispositionType};
+use actix_web::http::{Method, StatusCode};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use mongodb::bson::{doc, oid::ObjectId, Document};
+
+use crate::etag::{header_str, none_match_satisfied};
+use crate::mongodb_client;
+use crate::storage::{self, ObjectInfo, ObjectStore};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ByteRange {
+    // No usable Range header: send the whole object
+    Full,
+    // Inclusive offsets, already clamped to the object
+    Partial { start: u64, end: u64 },
+    Unsatisfiable,
+}
+
+impl ByteRange {
+    pub fn length(&self, size: u64) -> u64 {
+        match self {
+            ByteRange::Full => size,
+            ByteRange::Partial { start, end } => end - start + 1,
+            ByteRange::Unsatisfiable => 0,
+        }
+    }
+}
+
+// One range of `bytes=first-last`, `bytes=first-` or `bytes=-suffix`. A header that doesn't
+// parse, uses another unit or asks for several ranges is ignored, which RFC 9110 allows.
+pub fn parse_range(value: &str, size: u64) -> ByteRange {
+    let Some((unit, spec)) = value.trim().split_once('=') else {
+        return ByteRange::Full;
+    };
+    if !unit.trim().eq_ignore_ascii_case("bytes") || spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((first, last)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+    let parse = |n: &str| -> Option<u64> {
+        (!n.is_empty() && n.bytes().all(|b| b.is_ascii_digit())).then(|| n.parse().ok()).flatten()
+    };
+    match (first.trim(), last.trim()) {
+        ("", suffix) => match parse(suffix) {
+            Some(0) => ByteRange::Unsatisfiable,
+            Some(_) if size == 0 => ByteRange::Unsatisfiable,
+            Some(n) => ByteRange::Partial { start: size.saturating_sub(n), end: size - 1 },
+            None => ByteRange::Full,
+        },
+        (first, last) => {
+            let Some(start) = parse(first) else {
+                return ByteRange::Full;
+            };
+            let end = if last.is_empty() {
+                None
+            } else {
+                match parse(last) {
+                    Some(end) if end >= start => Some(end),
+                    _ => return ByteRange::Full,
+                }
+            };
+            if start >= size {
+                ByteRange::Unsatisfiable
+            } else {
+                ByteRange::Partial { start, end: end.map_or(size - 1, |end| end.min(size - 1)) }
+            }
+        }
+    }
+}
+
+pub fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value.trim()).ok().map(|date| date.with_timezone(&Utc))
+}
+
+pub fn http_date(date: &DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+// If-None-Match wins over If-Modified-Since when both are sent
+pub fn is_not_modified(
+    if_none_match: Option<&str>,
+    if_modified_since: Option<&str>,
+    etag: Option<&str>,
+    last_modified: Option<&DateTime<Utc>>,
+) -> bool {
+    if let Some(if_none_match) = if_none_match {
+        return etag.is_some_and(|etag| none_match_satisfied(if_none_match, etag));
+    }
+    match (if_modified_since.and_then(parse_http_date), last_modified) {
+        // HTTP dates have whole-second precision
+        (Some(since), Some(modified)) => modified.timestamp() <= since.timestamp(),
+        _ => false,
+    }
+}
+
+// Whether a Range may be honoured under If-Range: an entity tag must match strongly, a date
+// exactly. Anything else means the client's partial copy is stale.
+pub fn if_range_matches(if_range: &str, etag: Option<&str>, last_modified: Option<&DateTime<Utc>>) -> bool {
+    let if_range = if_range.trim();
+    if if_range.starts_with('"') || if_range.starts_with("W/") {
+        return !if_range.starts_with("W/") && etag.is_some_and(|etag| !etag.starts_with("W/") && etag == if_range);
+    }
+    match (parse_http_date(if_range), last_modified) {
+        (Some(date), Some(modified)) => date.timestamp() == modified.timestamp(),
+        _ => false,
+    }
+}
+
+fn error_response(status: StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+async fn find_upload(id: &str) -> Result<Document, HttpResponse> {
+    let oid = ObjectId::parse_str(id)
+        .map_err(|_| error_response(StatusCode::BAD_REQUEST, format!("Invalid upload id '{}'", id)))?;
+    let client = mongodb_client().await.map_err(|e| error_response(StatusCode::SERVICE_UNAVAILABLE, e))?;
+    client
+        .database("test")
+        .collection::<Document>("uploads")
+        .find_one(doc! { "_id": oid })
+        .await
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, format!("Lookup failed: {}", e)))?
+        .ok_or_else(|| error_response(StatusCode::NOT_FOUND, format!("Upload {} not found", id)))
+}
+
+fn with_validators(builder: &mut actix_web::HttpResponseBuilder, info: &ObjectInfo) {
+    builder.insert_header((header::ACCEPT_RANGES, "bytes"));
+    if let Some(etag) = &info.etag {
+        builder.insert_header((header::ETAG, etag.clone()));
+    }
+    if let Some(modified) = &info.last_modified {
+        builder.insert_header((header::LAST_MODIFIED, http_date(modified)));
+    }
+}
+
+pub async fn download_upload(req: HttpRequest, path: web::Path<String>) -> impl Responder {
+    let id = path.into_inner();
+    let upload = match find_upload(&id).await {
+        Ok(upload) => upload,
+        Err(res) => return res,
+    };
+    let Ok(object_key) = upload.get_str("object_key") else {
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, format!("Upload {} has no object_key", id));
+    };
+    let filename = upload.get_str("filename").unwrap_or("download");
+
+    let store = match ObjectStore::from_vault().await {
+        Ok(store) => store,
+        Err(e) => return error_response(StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+    let info = match store.head_object(object_key).await {
+        Ok(Some(info)) => info,
+        Ok(None) => return error_response(StatusCode::NOT_FOUND, format!("Object for upload {} is gone", id)),
+        Err(e) => return error_response(StatusCode::BAD_GATEWAY, e),
+    };
+
+    let (etag, last_modified) = (info.etag.as_deref(), info.last_modified.as_ref());
+    if is_not_modified(
+        header_str(&req, header::IF_NONE_MATCH),
+        header_str(&req, header::IF_MODIFIED_SINCE),
+        etag,
+        last_modified,
+    ) {
+        let mut builder = HttpResponse::NotModified();
+        with_validators(&mut builder, &info);
+        return builder.finish();
+    }
+
+    let resumable = header_str(&req, header::IF_RANGE).is_none_or(|v| if_range_matches(v, etag, last_modified));
+    let range = match header_str(&req, header::RANGE) {
+        Some(range) if resumable => parse_range(range, info.size),
+        _ => ByteRange::Full,
+    };
+
+    let mut builder = match range {
+        ByteRange::Full => HttpResponse::Ok(),
+        ByteRange::Partial { start, end } => {
+            let mut builder = HttpResponse::PartialContent();
+            builder.insert_header((header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, info.size)));
+            builder
+        }
+        ByteRange::Unsatisfiable => {
+            return HttpResponse::RangeNotSatisfiable()
+                .insert_header((header::ACCEPT_RANGES, "bytes"))
+                .insert_header((header::CONTENT_RANGE, format!("bytes */{}", info.size)))
+                .json(serde_json::json!({
+                    "status": "error",
+                    "error": format!("Range not satisfiable for an object of {} bytes", info.size)
+                }));
+        }
+    };
+    with_validators(&mut builder, &info);
+    builder
+        .insert_header((header::CONTENT_TYPE, info.content_type.as_deref().unwrap_or("application/octet-stream")))
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(filename.to_string())],
+        });
+
+    let length = range.length(info.size);
+    if req.method() == Method::HEAD {
+        return builder.no_chunking(length).finish();
+    }
+
+    let slice = match range {
+        ByteRange::Partial { start, end } => Some((start, end)),
+        _ => None,
+    };
+    let response = match store.get_object(object_key, slice).await {
+        Ok(response) => response,
+        Err(e) => return error_response(StatusCode::BAD_GATEWAY, e),
+    };
+    // A failure mid-stream aborts the connection, which the client sees as a short read it can
+    // resume with a Range request
+    let body = storage::body_stream(response);
+    builder.body(SizedStream::new(length, body))
+}
@@ -0,0 +1,178 @@
+// Publish to RabbitMQ after a Postgres commit, without an outbox
+//
+// The naive dual write: commit the row, then publish, and delete the row again if the publish
+// fails. It works on the happy path, but there is no atomicity between the two systems; the
+// response lists which failures this approach still cannot handle.
+
+use actix_web::{web, HttpResponse, Responder};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{amqp_connection, pool};
+
+const TABLE_DDL: &str = "CREATE TABLE IF NOT EXISTS dual_write_orders (
+    id BIGSERIAL PRIMARY KEY,
+    payload JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+)";
+
+const REMAINING_FAILURE_MODES: &[&str] = &[
+    "Process crash between the Postgres commit and the publish: the row stays committed and no message is ever sent",
+    "Compensating delete fails (database unreachable after the commit): the row stays without its message",
+    "Readers can see the committed row before a failed publish is compensated",
+    "Connection lost after the broker accepted the message but before the confirm arrived: the row is deleted while consumers still receive the message",
+];
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PublishMode {
+    // Publisher confirms; compensate when the broker doesn't confirm
+    #[default]
+    Confirm,
+    // AMQP tx.select / tx.commit around the publish
+    Tx,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SimulatedFailure {
+    // Publish to an exchange that doesn't exist, so the broker closes the channel
+    Publish,
+    // Stop right after the commit, as if the process had died
+    CrashAfterCommit,
+}
+
+#[derive(Deserialize)]
+pub struct TransactionalPublishRequest {
+    queue: String,
+    payload: Value,
+    #[serde(default)]
+    mode: PublishMode,
+    #[serde(default)]
+    simulate_failure: Option<SimulatedFailure>,
+}
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+async fn publish(queue: &str, payload: &[u8], mode: PublishMode, fail: bool) -> Result<(), String> {
+    let conn = amqp_connection().await?;
+    let result = async {
+        let channel = conn.create_channel().await.map_err(|e| format!("Channel creation failed: {}", e))?;
+        channel
+            .queue_declare(queue, lapin::options::QueueDeclareOptions::default(), lapin::types::FieldTable::default())
+            .await
+            .map_err(|e| format!("Queue declare failed: {}", e))?;
+        let (exchange, routing_key) = if fail { ("dual-write-missing-exchange", "") } else { ("", queue) };
+        let properties = lapin::BasicProperties::default()
+            .with_content_type("application/json".into())
+            .with_delivery_mode(2);
+
+        match mode {
+            PublishMode::Confirm => {
+                channel
+                    .confirm_select(lapin::options::ConfirmSelectOptions::default())
+                    .await
+                    .map_err(|e| format!("confirm.select failed: {}", e))?;
+                let confirmation = channel
+                    .basic_publish(exchange, routing_key, lapin::options::BasicPublishOptions::default(), payload, properties)
+                    .await
+                    .map_err(|e| format!("Publish failed: {}", e))?
+                    .await
+                    .map_err(|e| format!("Publish confirm failed: {}", e))?;
+                if confirmation.is_nack() {
+                    return Err("Broker nacked the message".to_string());
+                }
+            }
+            PublishMode::Tx => {
+                channel
+                    .tx_select()
+                    .await
+                    .map_err(|e| format!("tx.select failed: {}", e))?;
+                channel
+                    .basic_publish(exchange, routing_key, lapin::options::BasicPublishOptions::default(), payload, properties)
+                    .await
+                    .map_err(|e| format!("Publish failed: {}", e))?;
+                // A publish to a missing exchange surfaces here, when the broker closes the channel
+                channel.tx_commit().await.map_err(|e| format!("tx.commit failed: {}", e))?;
+            }
+        }
+        Ok::<_, String>(())
+    }
+    .await;
+    let _ = conn.close(0, "Done").await;
+    result
+}
+
+pub async fn transactional_publish(body: web::Json<TransactionalPublishRequest>) -> impl Responder {
+    if body.queue.is_empty() {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, "queue is required".to_string());
+    }
+
+    let mut client = match pool::postgres().await {
+        Ok(client) => client,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+    if let Err(e) = client.batch_execute(TABLE_DDL).await {
+        return error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Table setup failed: {}", e));
+    }
+
+    let mut steps = Vec::new();
+    let committed = async {
+        let transaction = client.transaction().await?;
+        let row = transaction
+            .query_one("INSERT INTO dual_write_orders (payload) VALUES ($1) RETURNING id", &[&body.payload])
+            .await?;
+        transaction.commit().await?;
+        Ok::<i64, tokio_postgres::Error>(row.get(0))
+    }
+    .await;
+    let id = match committed {
+        Ok(id) => id,
+        Err(e) => {
+            // Nothing was published yet, so a failed transaction leaves both systems clean
+            return error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Insert failed: {}", e));
+        }
+    };
+    steps.push(serde_json::json!({ "step": "postgres_commit", "ok": true }));
+
+    let respond = |status: &str, steps: Vec<Value>| {
+        serde_json::json!({
+            "status": status,
+            "id": id,
+            "queue": body.queue,
+            "mode": match body.mode { PublishMode::Confirm => "confirm", PublishMode::Tx => "tx" },
+            "steps": steps,
+            "remaining_failure_modes": REMAINING_FAILURE_MODES,
+            "alternative": "Write the message to an outbox table in the same transaction and relay it separately"
+        })
+    };
+
+    if body.simulate_failure == Some(SimulatedFailure::CrashAfterCommit) {
+        steps.push(serde_json::json!({ "step": "publish", "ok": false, "error": "skipped: simulated crash after commit" }));
+        return HttpResponse::InternalServerError().json(respond("inconsistent", steps));
+    }
+
+    let payload = body.payload.to_string();
+    let failing = body.simulate_failure == Some(SimulatedFailure::Publish);
+    match publish(&body.queue, payload.as_bytes(), body.mode, failing).await {
+        Ok(()) => {
+            steps.push(serde_json::json!({ "step": "publish", "ok": true }));
+            HttpResponse::Ok().json(respond("published", steps))
+        }
+        Err(publish_error) => {
+            steps.push(serde_json::json!({ "step": "publish", "ok": false, "error": publish_error }));
+            match client.execute("DELETE FROM dual_write_orders WHERE id = $1", &[&id]).await {
+                Ok(_) => {
+                    steps.push(serde_json::json!({ "step": "compensating_delete", "ok": true }));
+                    HttpResponse::BadGateway().json(respond("compensated", steps))
+                }
+                Err(e) => {
+                    steps.push(serde_json::json!({ "step": "compensating_delete", "ok": false, "error": e.to_string() }));
+                    HttpResponse::InternalServerError().json(respond("inconsistent", steps))
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,134 @@
+// Envelope encryption for cache values with Vault Transit data keys
+//
+// Each value is encrypted locally with AES-256-GCM under a fresh data key from
+// transit/datakey/plaintext. Only the Transit-wrapped copy of that key is stored next to the
+// ciphertext; the plaintext key lives in memory for one request and reading the value back needs
+// Vault to unwrap it. The Redis key is the AEAD associated data, so an envelope copied under a
+// different key fails to decrypt.
+
+use actix_web::http::StatusCode;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::Engine;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::get_env_or;
+use crate::vault;
+
+// Like the gzip marker, 0xFF keeps string values from ever looking like an envelope
+pub const ENVELOPE_MARKER: &[u8] = b"\xffenvelope\x00";
+
+const ALGORITHM: &str = "AES-256-GCM";
+const NONCE_LEN: usize = 12;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Envelope {
+    pub alg: String,
+    // Transit key that wrapped the data key
+    pub transit_key: String,
+    // vault:vN:... ciphertext of the data key
+    pub wrapped_key: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+pub fn transit_key_name() -> String {
+    get_env_or("CACHE_TRANSIT_KEY", "cache")
+}
+
+pub fn is_envelope(stored: &[u8]) -> bool {
+    stored.starts_with(ENVELOPE_MARKER)
+}
+
+fn b64() -> base64::engine::GeneralPurpose {
+    base64::engine::general_purpose::STANDARD
+}
+
+// Encrypts with an already-issued data key; `aad` is the Redis key
+pub fn seal_with_key(
+    data_key: &[u8],
+    wrapped_key: String,
+    transit_key: String,
+    nonce: [u8; NONCE_LEN],
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new_from_slice(data_key).map_err(|_| "Data key must be 256 bits".to_string())?;
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad })
+        .map_err(|_| "Encryption failed".to_string())?;
+    let envelope = Envelope {
+        alg: ALGORITHM.to_string(),
+        transit_key,
+        wrapped_key,
+        nonce: b64().encode(nonce),
+        ciphertext: b64().encode(ciphertext),
+    };
+    let mut stored = ENVELOPE_MARKER.to_vec();
+    stored.extend(serde_json::to_vec(&envelope).map_err(|e| format!("Envelope encoding failed: {}", e))?);
+    Ok(stored)
+}
+
+pub fn parse(stored: &[u8]) -> Result<Envelope, String> {
+    let body = stored.strip_prefix(ENVELOPE_MARKER).ok_or_else(|| "Value is not an envelope".to_string())?;
+    let envelope: Envelope = serde_json::from_slice(body).map_err(|e| format!("Corrupt envelope: {}", e))?;
+    if envelope.alg != ALGORITHM {
+        return Err(format!("Unsupported envelope algorithm '{}'", envelope.alg));
+    }
+    Ok(envelope)
+}
+
+pub fn open_with_key(data_key: &[u8], envelope: &Envelope, aad: &[u8]) -> Result<Vec<u8>, String> {
+    let nonce = b64().decode(&envelope.nonce).map_err(|e| format!("Corrupt envelope nonce: {}", e))?;
+    if nonce.len() != NONCE_LEN {
+        return Err("Corrupt envelope nonce".to_string());
+    }
+    let ciphertext = b64().decode(&envelope.ciphertext).map_err(|e| format!("Corrupt envelope ciphertext: {}", e))?;
+    let cipher = Aes256Gcm::new_from_slice(data_key).map_err(|_| "Data key must be 256 bits".to_string())?;
+    cipher
+        .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad })
+        .map_err(|_| "Decryption failed: wrong key or tampered envelope".to_string())
+}
+
+#[derive(Debug)]
+pub enum EnvelopeError {
+    // Transit unreachable, not mounted, or the key is missing
+    Vault(String),
+    // Corrupt or tampered envelope
+    Crypto(String),
+}
+
+impl EnvelopeError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            EnvelopeError::Vault(_) => StatusCode::SERVICE_UNAVAILABLE,
+            EnvelopeError::Crypto(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn into_message(self) -> String {
+        match self {
+            EnvelopeError::Vault(e) => format!("Vault Transit: {}", e),
+            EnvelopeError::Crypto(e) => e,
+        }
+    }
+}
+
+// Fetches a data key from Transit and returns the bytes to store
+pub async fn seal(plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, EnvelopeError> {
+    let transit_key = transit_key_name();
+    let (data_key, wrapped_key) = vault::transit_data_key(&transit_key).await.map_err(EnvelopeError::Vault)?;
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    seal_with_key(&data_key, wrapped_key, transit_key, nonce, plaintext, aad).map_err(EnvelopeError::Crypto)
+}
+
+// Unwraps the data key through Transit and decrypts the stored envelope
+pub async fn open(stored: &[u8], aad: &[u8]) -> Result<Vec<u8>, EnvelopeError> {
+    let envelope = parse(stored).map_err(EnvelopeError::Crypto)?;
+    let data_key = vault::transit_decrypt(&envelope.transit_key, &envelope.wrapped_key)
+        .await
+        .map_err(EnvelopeError::Vault)?;
+    open_with_key(&data_key, &envelope, aad).map_err(EnvelopeError::Crypto)
+}
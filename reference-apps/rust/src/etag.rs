@@ -0,0 +1,48 @@
+// Entity tags and conditional request helpers (If-None-Match / If-Match)
+
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse};
+use sha1::{Digest, Sha1};
+
+// SHA-1 so Redis Lua scripts can compute the same tag server-side with redis.sha1hex
+pub fn sha1_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha1::digest(bytes))
+}
+
+pub fn etag_for(bytes: &[u8]) -> String {
+    format!("\"{}\"", sha1_hex(bytes))
+}
+
+pub fn header_str(req: &HttpRequest, name: header::HeaderName) -> Option<&str> {
+    req.headers().get(name).and_then(|v| v.to_str().ok())
+}
+
+// Weak comparison, as RFC 9110 requires for If-None-Match
+pub fn none_match_satisfied(if_none_match: &str, etag: &str) -> bool {
+    let strip_weak = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    if_none_match.trim() == "*" || if_none_match.split(',').any(|tag| strip_weak(tag) == strip_weak(etag))
+}
+
+// True when the client's cached copy is current and a 304 should be sent
+pub fn is_not_modified(req: &HttpRequest, etag: &str) -> bool {
+    header_str(req, header::IF_NONE_MATCH).map(|v| none_match_satisfied(v, etag)).unwrap_or(false)
+}
+
+pub fn not_modified(etag: &str) -> HttpResponse {
+    HttpResponse::NotModified().insert_header((header::ETAG, etag.to_string())).finish()
+}
+
+// Opaque tags from If-Match, unquoted; weak tags are dropped since If-Match uses strong comparison.
+// `*` is passed through as-is.
+pub fn parse_if_match(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty() && !tag.starts_with("W/"))
+        .map(|tag| tag.trim_matches('"').to_string())
+        .collect()
+}
+
+pub fn if_match(req: &HttpRequest) -> Option<Vec<String>> {
+    header_str(req, header::IF_MATCH).map(parse_if_match)
+}
@@ -0,0 +1,412 @@
+// Event sourcing: bank accounts whose state is never stored, only derived
+//
+// Commands (open, deposit, withdraw, close) are checked against the account's current state and,
+// if accepted, recorded as events in the Postgres table es_events. Current state is rebuilt by
+// replaying an account's events in version order. (aggregate_id, version) is the primary key,
+// so two concurrent writers that read the same version can't both append: the loser gets 409 and
+// retries against the new state. Clients can also send expected_version to get the same 409
+// when the account moved since they read it.
+//
+// Replaying a long history on every read gets slow, so the state is snapshotted in Redis
+// (es:snapshot:account:{id}) every ES_SNAPSHOT_EVERY events (default 50) or on demand. A read
+// starts from the snapshot and replays only the newer events. Snapshots are only a cache: if
+// Redis is down or the snapshot is missing, the state is rebuilt from the full history.
+// The global seq column orders events across accounts for projections that follow the log.
+
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio_postgres::error::SqlState;
+
+use crate::{get_env_or, pool, redis_connection};
+
+pub(crate) const TABLE_DDL: &str = "CREATE TABLE IF NOT EXISTS es_events (
+    seq BIGSERIAL UNIQUE,
+    aggregate_id TEXT NOT NULL,
+    version BIGINT NOT NULL,
+    event_type TEXT NOT NULL,
+    data JSONB NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (aggregate_id, version)
+)";
+
+const SNAPSHOT_PREFIX: &str = "es:snapshot:account:";
+const MAX_ID_LEN: usize = 64;
+const DEFAULT_EVENTS_LIMIT: i64 = 100;
+const MAX_EVENTS_LIMIT: i64 = 1_000;
+
+fn snapshot_every() -> i64 {
+    get_env_or("ES_SNAPSHOT_EVERY", "50").parse().unwrap_or(50)
+}
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+// ============================================================================
+// Domain: commands, events, state
+// ============================================================================
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Command {
+    Open { owner: String },
+    Deposit { amount_cents: i64 },
+    Withdraw { amount_cents: i64 },
+    Close,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    Opened { owner: String },
+    Deposited { amount_cents: i64 },
+    Withdrawn { amount_cents: i64 },
+    Closed,
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Opened { .. } => "opened",
+            Event::Deposited { .. } => "deposited",
+            Event::Withdrawn { .. } => "withdrawn",
+            Event::Closed => "closed",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Account {
+    pub owner: Option<String>,
+    pub balance_cents: i64,
+    pub open: bool,
+    pub closed: bool,
+    // Deposits and withdrawals
+    pub transactions: u64,
+}
+
+impl Account {
+    pub fn apply(&mut self, event: &Event) {
+        match event {
+            Event::Opened { owner } => {
+                self.owner = Some(owner.clone());
+                self.open = true;
+            }
+            Event::Deposited { amount_cents } => {
+                self.balance_cents += amount_cents;
+                self.transactions += 1;
+            }
+            Event::Withdrawn { amount_cents } => {
+                self.balance_cents -= amount_cents;
+                self.transactions += 1;
+            }
+            Event::Closed => {
+                self.open = false;
+                self.closed = true;
+            }
+        }
+    }
+
+    // The event a command produces against this state, or why it is refused
+    pub fn decide(&self, command: &Command) -> Result<Event, String> {
+        let require_open = || {
+            if self.open {
+                Ok(())
+            } else if self.closed {
+                Err("Account is closed".to_string())
+            } else {
+                Err("Account is not open".to_string())
+            }
+        };
+        match command {
+            Command::Open { owner } => {
+                if self.open || self.closed {
+                    return Err("Account already exists".to_string());
+                }
+                if owner.trim().is_empty() {
+                    return Err("owner is required".to_string());
+                }
+                Ok(Event::Opened { owner: owner.trim().to_string() })
+            }
+            Command::Deposit { amount_cents } | Command::Withdraw { amount_cents } if *amount_cents <= 0 => {
+                Err("amount_cents must be positive".to_string())
+            }
+            Command::Deposit { amount_cents } => {
+                require_open()?;
+                Ok(Event::Deposited { amount_cents: *amount_cents })
+            }
+            Command::Withdraw { amount_cents } => {
+                require_open()?;
+                if *amount_cents > self.balance_cents {
+                    return Err(format!("Insufficient funds: balance is {} cents", self.balance_cents));
+                }
+                Ok(Event::Withdrawn { amount_cents: *amount_cents })
+            }
+            Command::Close => {
+                require_open()?;
+                if self.balance_cents != 0 {
+                    return Err("Only an account with a zero balance can be closed".to_string());
+                }
+                Ok(Event::Closed)
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub version: i64,
+    pub state: Account,
+}
+
+// ============================================================================
+// Storage
+// ============================================================================
+
+fn valid_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_ID_LEN && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+async fn client() -> Result<pool::Pooled<pool::PostgresManager>, HttpResponse> {
+    let client = pool::postgres()
+        .await
+        .map_err(|e| error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e))?;
+    client.batch_execute(TABLE_DDL).await.map_err(|e| {
+        error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Table setup failed: {}", e))
+    })?;
+    Ok(client)
+}
+
+async fn read_snapshot(id: &str) -> Option<Snapshot> {
+    let mut conn = redis_connection().await.ok()?;
+    let raw: Option<String> =
+        redis::cmd("GET").arg(format!("{}{}", SNAPSHOT_PREFIX, id)).query_async(&mut conn).await.ok()?;
+    serde_json::from_str(&raw?).ok()
+}
+
+async fn write_snapshot(id: &str, snapshot: &Snapshot) -> Result<(), String> {
+    let mut conn = redis_connection().await?;
+    let raw = serde_json::to_string(snapshot).map_err(|e| e.to_string())?;
+    redis::cmd("SET")
+        .arg(format!("{}{}", SNAPSHOT_PREFIX, id))
+        .arg(raw)
+        .query_async::<()>(&mut conn)
+        .await
+        .map_err(|e| format!("SET failed: {}", e))
+}
+
+pub struct Loaded {
+    pub state: Account,
+    pub version: i64,
+    // Version of the snapshot replay started from
+    pub snapshot_version: Option<i64>,
+    pub replayed: usize,
+}
+
+async fn load(client: &tokio_postgres::Client, id: &str) -> Result<Loaded, String> {
+    let snapshot = read_snapshot(id).await;
+    let (mut state, mut version, snapshot_version) = match snapshot {
+        Some(snapshot) => (snapshot.state, snapshot.version, Some(snapshot.version)),
+        None => (Account::default(), 0, None),
+    };
+    let rows = client
+        .query(
+            "SELECT version, data FROM es_events WHERE aggregate_id = $1 AND version > $2 ORDER BY version",
+            &[&id, &version],
+        )
+        .await
+        .map_err(|e| format!("Event query failed: {}", e))?;
+    for row in &rows {
+        let event: Event =
+            serde_json::from_value(row.get(1)).map_err(|e| format!("Unreadable event in {}: {}", id, e))?;
+        state.apply(&event);
+        version = row.get(0);
+    }
+    Ok(Loaded { state, version, snapshot_version, replayed: rows.len() })
+}
+
+fn state_json(id: &str, loaded: &Loaded) -> Value {
+    serde_json::json!({
+        "id": id,
+        "version": loaded.version,
+        "state": loaded.state,
+        "snapshot_version": loaded.snapshot_version,
+        "replayed_events": loaded.replayed
+    })
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct CommandRequest {
+    #[serde(flatten)]
+    command: Command,
+    #[serde(default)]
+    expected_version: Option<i64>,
+}
+
+// POST /examples/patterns/event-sourcing/accounts/{id}/commands
+// {"type": "deposit", "amount_cents": 500, "expected_version": 3}
+pub async fn handle_command(path: web::Path<String>, body: web::Json<CommandRequest>) -> impl Responder {
+    let id = path.into_inner();
+    if !valid_id(&id) {
+        return error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            format!("Account id must be 1-{} letters, digits, '-' or '_'", MAX_ID_LEN),
+        );
+    }
+    let client = match client().await {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
+    let loaded = match load(&client, &id).await {
+        Ok(loaded) => loaded,
+        Err(e) => return error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e),
+    };
+    if let Some(expected) = body.expected_version {
+        if expected != loaded.version {
+            return error_response(
+                actix_web::http::StatusCode::CONFLICT,
+                format!("Expected version {}, account is at {}", expected, loaded.version),
+            );
+        }
+    }
+    let event = match loaded.state.decide(&body.command) {
+        Ok(event) => event,
+        Err(e) => return error_response(actix_web::http::StatusCode::UNPROCESSABLE_ENTITY, e),
+    };
+
+    let version = loaded.version + 1;
+    let data = serde_json::to_value(&event).unwrap_or(Value::Null);
+    let appended = client
+        .query_one(
+            "INSERT INTO es_events (aggregate_id, version, event_type, data) VALUES ($1, $2, $3, $4) RETURNING seq",
+            &[&id, &version, &event.name(), &data],
+        )
+        .await;
+    let seq: i64 = match appended {
+        Ok(row) => row.get(0),
+        Err(e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => {
+            return error_response(
+                actix_web::http::StatusCode::CONFLICT,
+                format!("Version {} was written concurrently; reload and retry", version),
+            )
+        }
+        Err(e) => {
+            return error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Append failed: {}", e))
+        }
+    };
+
+    let mut state = loaded.state;
+    state.apply(&event);
+    let every = snapshot_every();
+    let mut snapshotted = false;
+    if every > 0 && version % every == 0 {
+        match write_snapshot(&id, &Snapshot { version, state: state.clone() }).await {
+            Ok(()) => snapshotted = true,
+            Err(e) => log::warn!("Snapshot of account {} at version {} failed: {}", id, version, e),
+        }
+    }
+    HttpResponse::Created().json(serde_json::json!({
+        "id": id,
+        "version": version,
+        "seq": seq,
+        "event": data,
+        "state": state,
+        "snapshotted": snapshotted
+    }))
+}
+
+// GET /examples/patterns/event-sourcing/accounts/{id}
+pub async fn get_state(path: web::Path<String>) -> impl Responder {
+    let id = path.into_inner();
+    let client = match client().await {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
+    match load(&client, &id).await {
+        Ok(loaded) if loaded.version == 0 => {
+            error_response(actix_web::http::StatusCode::NOT_FOUND, format!("No events for account '{}'", id))
+        }
+        Ok(loaded) => HttpResponse::Ok().json(state_json(&id, &loaded)),
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EventsQuery {
+    after_version: Option<i64>,
+    limit: Option<i64>,
+}
+
+// GET /examples/patterns/event-sourcing/accounts/{id}/events?after_version=0&limit=100
+pub async fn list_events(path: web::Path<String>, query: web::Query<EventsQuery>) -> impl Responder {
+    let id = path.into_inner();
+    let limit = query.limit.unwrap_or(DEFAULT_EVENTS_LIMIT);
+    if !(1..=MAX_EVENTS_LIMIT).contains(&limit) {
+        return error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            format!("limit must be between 1 and {}", MAX_EVENTS_LIMIT),
+        );
+    }
+    let client = match client().await {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
+    let rows = client
+        .query(
+            "SELECT version, seq, event_type, data, recorded_at::text FROM es_events
+             WHERE aggregate_id = $1 AND version > $2 ORDER BY version LIMIT $3",
+            &[&id, &query.after_version.unwrap_or(0), &limit],
+        )
+        .await;
+    match rows {
+        Ok(rows) => {
+            let events: Vec<Value> = rows
+                .iter()
+                .map(|row| {
+                    serde_json::json!({
+                        "version": row.get::<_, i64>(0),
+                        "seq": row.get::<_, i64>(1),
+                        "type": row.get::<_, String>(2),
+                        "data": row.get::<_, Value>(3),
+                        "recorded_at": row.get::<_, String>(4)
+                    })
+                })
+                .collect();
+            HttpResponse::Ok().json(serde_json::json!({ "id": id, "count": events.len(), "events": events }))
+        }
+        Err(e) => {
+            error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Event query failed: {}", e))
+        }
+    }
+}
+
+// POST /examples/patterns/event-sourcing/accounts/{id}/snapshot
+pub async fn take_snapshot(path: web::Path<String>) -> impl Responder {
+    let id = path.into_inner();
+    let client = match client().await {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
+    let loaded = match load(&client, &id).await {
+        Ok(loaded) if loaded.version == 0 => {
+            return error_response(actix_web::http::StatusCode::NOT_FOUND, format!("No events for account '{}'", id))
+        }
+        Ok(loaded) => loaded,
+        Err(e) => return error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e),
+    };
+    let snapshot = Snapshot { version: loaded.version, state: loaded.state.clone() };
+    match write_snapshot(&id, &snapshot).await {
+        Ok(()) => HttpResponse::Ok().json(serde_json::json!({
+            "status": "snapshotted",
+            "id": id,
+            "version": snapshot.version,
+            "state": snapshot.state
+        })),
+        Err(e) => error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    }
+}
@@ -0,0 +1,241 @@
+// Feature flags stored in Redis and evaluated per request
+//
+// Flags live in the `feature_flags` hash as JSON ({enabled, rollout_percent, description}).
+// A flag is on for a caller when it is enabled and the caller's bucket (a stable hash of flag
+// name and user id, 0-99) is below rollout_percent; callers without a user id get a random
+// bucket. Definitions are cached in-process for FEATURE_FLAG_CACHE_MS (default 1000) so a
+// toggle takes effect on every replica within that window.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
+use crate::{get_env_or, redis_connection};
+
+const FLAGS_KEY: &str = "feature_flags";
+const USER_HEADER: &str = "x-user-id";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FeatureFlag {
+    pub enabled: bool,
+    #[serde(default = "full_rollout")]
+    pub rollout_percent: u8,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+fn full_rollout() -> u8 {
+    100
+}
+
+lazy_static! {
+    static ref FLAG_CACHE: Mutex<Option<(Instant, BTreeMap<String, FeatureFlag>)>> = Mutex::new(None);
+}
+
+// Stable 0-99 bucket, so a user keeps the same answer while the rollout percentage stays put
+pub fn bucket(flag: &str, user: &str) -> u8 {
+    let digest = Sha1::digest(format!("{}:{}", flag, user).as_bytes());
+    (u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) % 100) as u8
+}
+
+pub fn is_on(flag: &FeatureFlag, bucket: u8) -> bool {
+    flag.enabled && bucket < flag.rollout_percent.min(100)
+}
+
+fn cache_ttl() -> Duration {
+    Duration::from_millis(get_env_or("FEATURE_FLAG_CACHE_MS", "1000").parse().unwrap_or(1000))
+}
+
+fn invalidate_cache() {
+    if let Ok(mut cache) = FLAG_CACHE.lock() {
+        *cache = None;
+    }
+}
+
+pub async fn load_flags() -> Result<BTreeMap<String, FeatureFlag>, String> {
+    if let Ok(cache) = FLAG_CACHE.lock() {
+        if let Some((loaded_at, flags)) = cache.as_ref() {
+            if loaded_at.elapsed() < cache_ttl() {
+                return Ok(flags.clone());
+            }
+        }
+    }
+
+    let mut conn = redis_connection().await?;
+    let raw: BTreeMap<String, String> = redis::cmd("HGETALL")
+        .arg(FLAGS_KEY)
+        .query_async(&mut conn)
+        .await
+        .map_err(|e| format!("HGETALL failed: {}", e))?;
+    let flags: BTreeMap<String, FeatureFlag> = raw
+        .into_iter()
+        .filter_map(|(name, json)| match serde_json::from_str(&json) {
+            Ok(flag) => Some((name, flag)),
+            Err(e) => {
+                log::warn!("Ignoring malformed feature flag {}: {}", name, e);
+                None
+            }
+        })
+        .collect();
+
+    if let Ok(mut cache) = FLAG_CACHE.lock() {
+        *cache = Some((Instant::now(), flags.clone()));
+    }
+    Ok(flags)
+}
+
+fn user_id(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(USER_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+pub struct Evaluation {
+    pub on: bool,
+    pub bucket: u8,
+    pub flag: Option<FeatureFlag>,
+}
+
+// Unknown flags are off
+pub async fn evaluate(name: &str, user: Option<&str>) -> Result<Evaluation, String> {
+    let flag = load_flags().await?.remove(name);
+    let bucket = match user {
+        Some(user) => bucket(name, user),
+        None => rand::random::<u8>() % 100,
+    };
+    Ok(Evaluation { on: flag.as_ref().is_some_and(|f| is_on(f, bucket)), bucket, flag })
+}
+
+pub fn valid_flag_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= 64 && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+// ============================================================================
+// Admin endpoints
+// ============================================================================
+
+pub async fn list_flags() -> impl Responder {
+    match load_flags().await {
+        Ok(flags) => HttpResponse::Ok().json(serde_json::json!({ "count": flags.len(), "flags": flags })),
+        Err(e) => error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SetFlagRequest {
+    enabled: bool,
+    rollout_percent: Option<u8>,
+    description: Option<String>,
+}
+
+// Under /admin, so admin_auth::require_admin has already checked the token
+pub async fn set_flag(path: web::Path<String>, body: web::Json<SetFlagRequest>) -> impl Responder {
+    let name = path.into_inner();
+    if !valid_flag_name(&name) {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, "Invalid flag name".to_string());
+    }
+    let rollout_percent = body.rollout_percent.unwrap_or(100);
+    if rollout_percent > 100 {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, "rollout_percent must be 0-100".to_string());
+    }
+
+    let flag = FeatureFlag {
+        enabled: body.enabled,
+        rollout_percent,
+        description: body.description.clone(),
+        updated_at: Some(chrono::Utc::now().to_rfc3339()),
+    };
+    let json = match serde_json::to_string(&flag) {
+        Ok(json) => json,
+        Err(e) => return error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let mut conn = match redis_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+    match redis::cmd("HSET").arg(FLAGS_KEY).arg(&name).arg(json).query_async::<i64>(&mut conn).await {
+        Ok(_) => {
+            invalidate_cache();
+            HttpResponse::Ok().json(serde_json::json!({ "status": "saved", "name": name, "flag": flag }))
+        }
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("HSET failed: {}", e)),
+    }
+}
+
+pub async fn delete_flag(path: web::Path<String>) -> impl Responder {
+    let name = path.into_inner();
+    let mut conn = match redis_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+    match redis::cmd("HDEL").arg(FLAGS_KEY).arg(&name).query_async::<i64>(&mut conn).await {
+        Ok(0) => error_response(actix_web::http::StatusCode::NOT_FOUND, format!("Unknown flag '{}'", name)),
+        Ok(_) => {
+            invalidate_cache();
+            HttpResponse::Ok().json(serde_json::json!({ "status": "deleted", "name": name }))
+        }
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("HDEL failed: {}", e)),
+    }
+}
+
+// How a flag evaluates for the caller (X-User-Id) without doing anything else
+pub async fn evaluate_flag(req: HttpRequest, path: web::Path<String>) -> impl Responder {
+    let name = path.into_inner();
+    let user = user_id(&req);
+    match evaluate(&name, user.as_deref()).await {
+        Ok(evaluation) => HttpResponse::Ok().json(serde_json::json!({
+            "name": name,
+            "user": user,
+            "on": evaluation.on,
+            "bucket": evaluation.bucket,
+            "flag": evaluation.flag
+        })),
+        Err(e) => error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    }
+}
+
+// ============================================================================
+// Example: behavior switched by a flag
+// ============================================================================
+
+const GREETING_FLAG: &str = "new_greeting";
+
+// With `new_greeting` on for the caller the response switches to the new format
+pub async fn greeting(req: HttpRequest) -> impl Responder {
+    let user = user_id(&req);
+    let on = match evaluate(GREETING_FLAG, user.as_deref()).await {
+        Ok(evaluation) => evaluation.on,
+        // Fail closed: a Redis outage falls back to the old behavior
+        Err(e) => {
+            log::debug!("Feature flag lookup failed: {}", e);
+            false
+        }
+    };
+    let name = user.as_deref().unwrap_or("stranger");
+    if on {
+        HttpResponse::Ok().json(serde_json::json!({
+            "variant": "new",
+            "flag": GREETING_FLAG,
+            "greeting": { "text": format!("Welcome back, {}!", name), "emoji": "👋" }
+        }))
+    } else {
+        HttpResponse::Ok().json(serde_json::json!({
+            "variant": "classic",
+            "flag": GREETING_FLAG,
+            "greeting": format!("Hello, {}", name)
+        }))
+    }
+}
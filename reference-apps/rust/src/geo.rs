@@ -0,0 +1,459 @@
+// Geospatial example: Redis GEO next to PostGIS
+//
+// Points arrive as a GeoJSON FeatureCollection and are written to a Redis GEO set (properties
+// in a companion hash) and, when the postgis extension is installed, to a geography column with
+// a GiST index. Radius queries run against either backend or both; comparing them shows that
+// Redis measures distance on a sphere while PostGIS uses the WGS84 spheroid, so distances differ
+// by up to ~0.5% and points near the radius edge can be in one result and not the other.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{pool, redis_connection};
+
+// Hash tag keeps both keys in one cluster slot (4509), served by redis-1, which
+// redis_connection() talks to
+pub(crate) const GEO_KEY: &str = "{geo-points}:points";
+pub(crate) const PROPERTIES_KEY: &str = "{geo-points}:properties";
+
+// Redis GEO can only index latitudes inside the Web Mercator range
+const MAX_LATITUDE: f64 = 85.051_128_78;
+const MAX_RESULTS: usize = 1_000;
+const MAX_RADIUS_M: f64 = 1_000_000.0;
+
+const TABLE_DDL: &str = "CREATE TABLE IF NOT EXISTS geo_points (
+    id TEXT PRIMARY KEY,
+    location geography(Point, 4326) NOT NULL,
+    properties JSONB NOT NULL DEFAULT '{}'
+);
+CREATE INDEX IF NOT EXISTS geo_points_location_idx ON geo_points USING GIST (location)";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoPoint {
+    pub id: String,
+    pub lon: f64,
+    pub lat: f64,
+    pub properties: Value,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Nearby {
+    pub id: String,
+    pub lon: f64,
+    pub lat: f64,
+    pub distance_m: f64,
+    pub properties: Value,
+}
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+pub fn valid_coordinates(lon: f64, lat: f64) -> bool {
+    (-180.0..=180.0).contains(&lon) && (-MAX_LATITUDE..=MAX_LATITUDE).contains(&lat)
+}
+
+// Point features of a GeoJSON FeatureCollection; the id is the feature `id` or `properties.id`
+pub fn parse_feature_collection(collection: &Value) -> Result<Vec<GeoPoint>, String> {
+    if collection["type"] != "FeatureCollection" {
+        return Err("Body must be a GeoJSON FeatureCollection".to_string());
+    }
+    let features = collection["features"]
+        .as_array()
+        .ok_or_else(|| "FeatureCollection has no features array".to_string())?;
+
+    features
+        .iter()
+        .enumerate()
+        .map(|(i, feature)| {
+            if feature["geometry"]["type"] != "Point" {
+                return Err(format!("Feature {} is not a Point", i));
+            }
+            let coordinates = feature["geometry"]["coordinates"].as_array().map(|c| {
+                (c.first().and_then(Value::as_f64), c.get(1).and_then(Value::as_f64))
+            });
+            let (lon, lat) = match coordinates {
+                Some((Some(lon), Some(lat))) => (lon, lat),
+                _ => return Err(format!("Feature {} needs [longitude, latitude] coordinates", i)),
+            };
+            if !valid_coordinates(lon, lat) {
+                return Err(format!(
+                    "Feature {} is out of range: longitude must be within ±180 and latitude within ±{}",
+                    i, MAX_LATITUDE
+                ));
+            }
+            let id = match feature.get("id").filter(|id| !id.is_null()).or_else(|| feature["properties"].get("id")) {
+                Some(Value::String(id)) if !id.is_empty() => id.clone(),
+                Some(Value::Number(id)) => id.to_string(),
+                _ => return Err(format!("Feature {} needs an id", i)),
+            };
+            let properties = match &feature["properties"] {
+                Value::Null => serde_json::json!({}),
+                properties => properties.clone(),
+            };
+            Ok(GeoPoint { id, lon, lat, properties })
+        })
+        .collect()
+}
+
+pub fn to_feature_collection(points: &[Nearby]) -> Value {
+    let features: Vec<Value> = points
+        .iter()
+        .map(|p| {
+            let mut properties = match &p.properties {
+                Value::Object(map) => map.clone(),
+                _ => serde_json::Map::new(),
+            };
+            properties.insert("distance_m".to_string(), serde_json::json!(p.distance_m));
+            serde_json::json!({
+                "type": "Feature",
+                "id": p.id,
+                "geometry": { "type": "Point", "coordinates": [p.lon, p.lat] },
+                "properties": properties
+            })
+        })
+        .collect();
+    serde_json::json!({ "type": "FeatureCollection", "features": features })
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Comparison {
+    pub same_ids: bool,
+    pub same_order: bool,
+    pub only_redis: Vec<String>,
+    pub only_postgis: Vec<String>,
+    // Largest distance disagreement for points both backends returned
+    pub max_distance_delta_m: f64,
+}
+
+pub fn compare(redis: &[Nearby], postgis: &[Nearby]) -> Comparison {
+    let redis_by_id: HashMap<&str, f64> = redis.iter().map(|p| (p.id.as_str(), p.distance_m)).collect();
+    let postgis_by_id: HashMap<&str, f64> = postgis.iter().map(|p| (p.id.as_str(), p.distance_m)).collect();
+
+    let only_redis: Vec<String> =
+        redis.iter().filter(|p| !postgis_by_id.contains_key(p.id.as_str())).map(|p| p.id.clone()).collect();
+    let only_postgis: Vec<String> =
+        postgis.iter().filter(|p| !redis_by_id.contains_key(p.id.as_str())).map(|p| p.id.clone()).collect();
+    let max_distance_delta_m = redis
+        .iter()
+        .filter_map(|p| postgis_by_id.get(p.id.as_str()).map(|d| (p.distance_m - d).abs()))
+        .fold(0.0, f64::max);
+
+    Comparison {
+        same_ids: only_redis.is_empty() && only_postgis.is_empty(),
+        same_order: redis.iter().map(|p| &p.id).eq(postgis.iter().map(|p| &p.id)),
+        only_redis,
+        only_postgis,
+        max_distance_delta_m,
+    }
+}
+
+// ============================================================================
+// Backends
+// ============================================================================
+
+async fn redis_store(points: &[GeoPoint]) -> Result<(), String> {
+    let mut conn = redis_connection().await?;
+    let mut geoadd = redis::cmd("GEOADD");
+    geoadd.arg(GEO_KEY);
+    let mut hset = redis::cmd("HSET");
+    hset.arg(PROPERTIES_KEY);
+    for point in points {
+        geoadd.arg(point.lon).arg(point.lat).arg(&point.id);
+        hset.arg(&point.id).arg(point.properties.to_string());
+    }
+    redis::pipe()
+        .add_command(geoadd)
+        .ignore()
+        .add_command(hset)
+        .ignore()
+        .query_async::<()>(&mut conn)
+        .await
+        .map_err(|e| format!("GEOADD failed: {}", e))
+}
+
+async fn redis_nearby(lon: f64, lat: f64, radius_m: f64, limit: usize) -> Result<Vec<Nearby>, String> {
+    let mut conn = redis_connection().await?;
+    let found: Vec<(String, f64, (f64, f64))> = redis::cmd("GEOSEARCH")
+        .arg(GEO_KEY)
+        .arg("FROMLONLAT")
+        .arg(lon)
+        .arg(lat)
+        .arg("BYRADIUS")
+        .arg(radius_m)
+        .arg("m")
+        .arg("ASC")
+        .arg("COUNT")
+        .arg(limit)
+        .arg("WITHDIST")
+        .arg("WITHCOORD")
+        .query_async(&mut conn)
+        .await
+        .map_err(|e| format!("GEOSEARCH failed: {}", e))?;
+    if found.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let ids: Vec<&str> = found.iter().map(|(id, _, _)| id.as_str()).collect();
+    let properties: Vec<Option<String>> = redis::cmd("HMGET")
+        .arg(PROPERTIES_KEY)
+        .arg(&ids)
+        .query_async(&mut conn)
+        .await
+        .map_err(|e| format!("HMGET failed: {}", e))?;
+
+    Ok(found
+        .into_iter()
+        .zip(properties)
+        .map(|((id, distance_m, (lon, lat)), properties)| Nearby {
+            id,
+            lon,
+            lat,
+            distance_m,
+            properties: properties
+                .and_then(|p| serde_json::from_str(&p).ok())
+                .unwrap_or_else(|| serde_json::json!({})),
+        })
+        .collect())
+}
+
+// A client with geo_points ready, or why PostGIS can't be used
+async fn postgis_client() -> Result<pool::Pooled<pool::PostgresManager>, String> {
+    let client = pool::postgres().await?;
+    let installed = client
+        .query_opt("SELECT extversion FROM pg_extension WHERE extname = 'postgis'", &[])
+        .await
+        .map_err(|e| format!("Extension lookup failed: {}", e))?;
+    if installed.is_none() {
+        return Err("postgis extension is not installed (CREATE EXTENSION postgis)".to_string());
+    }
+    client
+        .batch_execute(TABLE_DDL)
+        .await
+        .map_err(|e| format!("geo_points setup failed: {}", e))?;
+    Ok(client)
+}
+
+async fn postgis_store(points: &[GeoPoint]) -> Result<(), String> {
+    let mut client = postgis_client().await?;
+    let tx = client.transaction().await.map_err(|e| format!("Transaction failed: {}", e))?;
+    let upsert = tx
+        .prepare(
+            "INSERT INTO geo_points (id, location, properties)
+             VALUES ($1, ST_SetSRID(ST_MakePoint($2, $3), 4326)::geography, $4)
+             ON CONFLICT (id) DO UPDATE SET location = EXCLUDED.location, properties = EXCLUDED.properties",
+        )
+        .await
+        .map_err(|e| format!("Prepare failed: {}", e))?;
+    for point in points {
+        tx.execute(&upsert, &[&point.id, &point.lon, &point.lat, &point.properties])
+            .await
+            .map_err(|e| format!("Insert failed: {}", e))?;
+    }
+    tx.commit().await.map_err(|e| format!("Commit failed: {}", e))
+}
+
+async fn postgis_nearby(lon: f64, lat: f64, radius_m: f64, limit: usize) -> Result<Vec<Nearby>, String> {
+    let client = postgis_client().await?;
+    // ST_DWithin filters through the GiST index; <-> orders by the same index (KNN)
+    let rows = client
+        .query(
+            "WITH center AS (SELECT ST_SetSRID(ST_MakePoint($1, $2), 4326)::geography AS point)
+             SELECT id, ST_X(location::geometry), ST_Y(location::geometry),
+                    ST_Distance(location, center.point), properties
+             FROM geo_points, center
+             WHERE ST_DWithin(location, center.point, $3)
+             ORDER BY location <-> center.point
+             LIMIT $4",
+            &[&lon, &lat, &radius_m, &(limit as i64)],
+        )
+        .await
+        .map_err(|e| format!("Query failed: {}", e))?;
+    Ok(rows
+        .iter()
+        .map(|row| Nearby {
+            id: row.get(0),
+            lon: row.get(1),
+            lat: row.get(2),
+            distance_m: row.get(3),
+            properties: row.get(4),
+        })
+        .collect())
+}
+
+// ============================================================================
+// Endpoints
+// ============================================================================
+
+// Body: GeoJSON FeatureCollection of Point features
+pub async fn store_points(body: web::Json<Value>) -> impl Responder {
+    let points = match parse_feature_collection(&body) {
+        Ok(points) if points.is_empty() => {
+            return error_response(actix_web::http::StatusCode::BAD_REQUEST, "No features to store".to_string())
+        }
+        Ok(points) => points,
+        Err(e) => return error_response(actix_web::http::StatusCode::BAD_REQUEST, e),
+    };
+
+    let started = Instant::now();
+    if let Err(e) = redis_store(&points).await {
+        return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e);
+    }
+    let redis_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    let started = Instant::now();
+    let postgis = match postgis_store(&points).await {
+        Ok(()) => serde_json::json!({ "stored": true, "elapsed_ms": started.elapsed().as_secs_f64() * 1000.0 }),
+        Err(e) => serde_json::json!({ "stored": false, "reason": e }),
+    };
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "stored",
+        "count": points.len(),
+        "redis": { "stored": true, "elapsed_ms": redis_ms },
+        "postgis": postgis
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct NearbyQuery {
+    lon: f64,
+    lat: f64,
+    radius_m: Option<f64>,
+    limit: Option<usize>,
+    // redis, postgis, or compare (default)
+    backend: Option<String>,
+    // json (default) or geojson
+    format: Option<String>,
+}
+
+pub async fn nearby(query: web::Query<NearbyQuery>) -> impl Responder {
+    let (lon, lat) = (query.lon, query.lat);
+    let radius_m = query.radius_m.unwrap_or(1_000.0);
+    let limit = query.limit.unwrap_or(50).clamp(1, MAX_RESULTS);
+    let backend = query.backend.as_deref().unwrap_or("compare");
+    let geojson = match query.format.as_deref().unwrap_or("json") {
+        "json" => false,
+        "geojson" => true,
+        other => {
+            return error_response(
+                actix_web::http::StatusCode::BAD_REQUEST,
+                format!("Unknown format '{}'. Must be one of: json, geojson", other),
+            )
+        }
+    };
+    if !valid_coordinates(lon, lat) {
+        return error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            format!("lon must be within ±180 and lat within ±{}", MAX_LATITUDE),
+        );
+    }
+    if !(radius_m > 0.0 && radius_m <= MAX_RADIUS_M) {
+        return error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            format!("radius_m must be greater than 0 and at most {}", MAX_RADIUS_M),
+        );
+    }
+
+    let single = match backend {
+        "redis" => Some(redis_nearby(lon, lat, radius_m, limit).await),
+        "postgis" => Some(postgis_nearby(lon, lat, radius_m, limit).await),
+        "compare" if geojson => {
+            return error_response(
+                actix_web::http::StatusCode::BAD_REQUEST,
+                "format=geojson needs backend=redis or backend=postgis".to_string(),
+            )
+        }
+        "compare" => None,
+        other => {
+            return error_response(
+                actix_web::http::StatusCode::BAD_REQUEST,
+                format!("Unknown backend '{}'. Must be one of: redis, postgis, compare", other),
+            )
+        }
+    };
+    if let Some(result) = single {
+        return match result {
+            Ok(points) if geojson => {
+                HttpResponse::Ok().content_type("application/geo+json").json(to_feature_collection(&points))
+            }
+            Ok(points) => HttpResponse::Ok().json(serde_json::json!({
+                "status": "success",
+                "backend": backend,
+                "count": points.len(),
+                "results": points
+            })),
+            Err(e) => error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+        };
+    }
+
+    let started = Instant::now();
+    let redis = match redis_nearby(lon, lat, radius_m, limit).await {
+        Ok(points) => points,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+    let redis_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    let started = Instant::now();
+    let postgis = postgis_nearby(lon, lat, radius_m, limit).await;
+    let postgis_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    let (postgis, comparison) = match postgis {
+        Ok(points) => {
+            let comparison = compare(&redis, &points);
+            (
+                serde_json::json!({
+                    "available": true,
+                    "count": points.len(),
+                    "elapsed_ms": postgis_ms,
+                    "results": points
+                }),
+                Some(comparison),
+            )
+        }
+        Err(e) => (serde_json::json!({ "available": false, "reason": e }), None),
+    };
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "center": { "lon": lon, "lat": lat },
+        "radius_m": radius_m,
+        "redis": { "count": redis.len(), "elapsed_ms": redis_ms, "results": redis },
+        "postgis": postgis,
+        "comparison": comparison
+    }))
+}
+
+pub async fn clear_points() -> impl Responder {
+    let removed = async {
+        let mut conn = redis_connection().await?;
+        redis::cmd("DEL")
+            .arg(GEO_KEY)
+            .arg(PROPERTIES_KEY)
+            .query_async::<i64>(&mut conn)
+            .await
+            .map_err(|e| format!("DEL failed: {}", e))
+    }
+    .await;
+    if let Err(e) = removed {
+        return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e);
+    }
+
+    let postgis = match postgis_client().await {
+        Ok(client) => match client.execute("DELETE FROM geo_points", &[]).await {
+            Ok(deleted) => serde_json::json!({ "cleared": true, "deleted": deleted }),
+            Err(e) => serde_json::json!({ "cleared": false, "reason": format!("Delete failed: {}", e) }),
+        },
+        Err(e) => serde_json::json!({ "cleared": false, "reason": e }),
+    };
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "cleared",
+        "redis": { "cleared": true },
+        "postgis": postgis
+    }))
+}
@@ -0,0 +1,270 @@
+// Grafana dashboard for the app's own metrics at /observability/dashboard.json
+//
+// Panels are declared below against the metric names and labels registered in lib.rs, and the
+// queries are generated from those declarations, so the dashboard can't drift from what /metrics
+// exports (a test checks every exported metric has a panel). Queries filter on $job and $instance;
+// the job defaults to "rust-api", the Prometheus job fed by GET /metrics/targets.
+//
+// curl -s localhost:8004/observability/dashboard.json?import=true |
+//   curl -u admin:admin -H 'Content-Type: application/json' -d @- localhost:3000/api/dashboards/db
+
+use actix_web::{web, HttpResponse, Responder};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const SELECTOR: &str = r#"job="$job",instance=~"$instance""#;
+const GRID_WIDTH: u32 = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Query {
+    // sum by (labels) of the per-second rate of a counter
+    Rate,
+    // sum by (labels) of a gauge
+    Sum,
+    // max by (labels) of a gauge
+    Max,
+    // 95th percentile of a histogram, by labels
+    P95,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Viz {
+    Timeseries,
+    Stat,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PanelSpec {
+    pub title: &'static str,
+    pub metric: &'static str,
+    pub query: Query,
+    pub by: &'static [&'static str],
+    pub unit: &'static str,
+    pub viz: Viz,
+}
+
+const fn panel(
+    title: &'static str,
+    metric: &'static str,
+    query: Query,
+    by: &'static [&'static str],
+    unit: &'static str,
+) -> PanelSpec {
+    PanelSpec { title, metric, query, by, unit, viz: Viz::Timeseries }
+}
+
+const fn stat(title: &'static str, metric: &'static str, query: Query, unit: &'static str) -> PanelSpec {
+    PanelSpec { title, metric, query, by: &[], unit, viz: Viz::Stat }
+}
+
+pub const ROWS: &[(&str, &[PanelSpec])] = &[
+    ("Overview", &[
+        stat("Requests/s", "http_requests_total", Query::Rate, "reqps"),
+        stat("Live replicas", "cluster_instances", Query::Max, "short"),
+        stat("Vault token TTL", "vault_token_ttl_seconds", Query::Max, "s"),
+        stat("Backends in flight", "backend_inflight_requests", Query::Sum, "short"),
+    ]),
+    ("HTTP", &[
+        panel("Requests by status", "http_requests_total", Query::Rate, &["status"], "reqps"),
+        panel("p95 latency by endpoint", "http_request_duration_seconds", Query::P95, &["endpoint"], "s"),
+        panel("Recovered panics", "panics_total", Query::Rate, &["route"], "ops"),
+        panel("Quota decisions", "quota_decisions_total", Query::Rate, &["quota", "result"], "ops"),
+    ]),
+    ("Backends", &[
+        panel("Service up", "stack_service_up", Query::Max, &["service"], "short"),
+        panel("Health check duration", "stack_service_check_duration_seconds", Query::Max, &["service"], "s"),
+        panel("In-flight requests", "backend_inflight_requests", Query::Sum, &["backend"], "short"),
+        panel("Concurrency limit", "backend_concurrency_limit", Query::Max, &["backend"], "short"),
+        panel("Rejected by concurrency limit", "backend_rejected_requests_total", Query::Rate, &["backend"], "reqps"),
+        panel("Hedged reads", "hedged_requests_total", Query::Rate, &["operation", "result"], "ops"),
+    ]),
+    ("SQL", &[
+        panel("p95 query latency", "sql_query_duration_seconds", Query::P95, &["database", "query_name"], "s"),
+        panel("Connection routing", "sql_route_total", Query::Rate, &["backend", "route"], "ops"),
+        panel("Result cache", "sql_cache_requests_total", Query::Rate, &["result"], "ops"),
+        panel("Result cache invalidations", "sql_cache_invalidations_total", Query::Rate, &["table", "source"], "ops"),
+        panel("Prepared statement cache", "prepared_statement_cache_total", Query::Rate, &["backend", "result"], "ops"),
+        panel("Tenant pools", "tenant_pools", Query::Max, &[], "short"),
+        panel("Tenant pool evictions", "tenant_pool_evictions_total", Query::Rate, &["reason"], "ops"),
+    ]),
+    ("Cache", &[
+        panel("Single-flight", "cache_singleflight_requests_total", Query::Rate, &["role"], "ops"),
+        panel("Early refreshes", "cache_early_refresh_total", Query::Rate, &[], "ops"),
+        panel("p95 compression ratio", "cache_compression_ratio", Query::P95, &[], "short"),
+        panel("p95 compression time", "cache_compression_duration_seconds", Query::P95, &["operation"], "s"),
+        panel("Compressed bytes", "cache_compression_bytes_total", Query::Rate, &["kind"], "Bps"),
+        panel("Dropped stream events", "stream_dropped_events_total", Query::Rate, &["endpoint", "policy"], "ops"),
+    ]),
+    ("Vault", &[
+        panel("Requests", "vault_requests_total", Query::Rate, &["operation", "status"], "reqps"),
+        panel("p95 request latency", "vault_request_duration_seconds", Query::P95, &["operation"], "s"),
+        panel("Secret cache", "vault_secret_cache_requests_total", Query::Rate, &["result"], "ops"),
+        panel("Token TTL", "vault_token_ttl_seconds", Query::Max, &[], "s"),
+    ]),
+    ("SLO", &[
+        panel("Error budget burn rate", "slo_error_budget_burn_rate", Query::Max, &["route", "window"], "short"),
+        panel("Error budget remaining", "slo_error_budget_remaining", Query::Max, &["route"], "percentunit"),
+        panel("Requests by result", "slo_requests_total", Query::Rate, &["route", "result"], "reqps"),
+    ]),
+    ("Patterns", &[
+        panel("Read model lag (events)", "es_projection_lag_events", Query::Max, &[], "short"),
+        panel("Read model lag (age)", "es_projection_lag_seconds", Query::Max, &[], "s"),
+    ]),
+];
+
+fn by_clause(labels: &[&str]) -> String {
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!(" by ({})", labels.join(", "))
+    }
+}
+
+pub fn expr(spec: &PanelSpec) -> String {
+    let series = format!("{}{{{}}}", spec.metric, SELECTOR);
+    match spec.query {
+        Query::Rate => format!("sum{}(rate({}[$__rate_interval]))", by_clause(spec.by), series),
+        Query::Sum => format!("sum{}({})", by_clause(spec.by), series),
+        Query::Max => format!("max{}({})", by_clause(spec.by), series),
+        Query::P95 => {
+            let mut by = vec!["le"];
+            by.extend(spec.by);
+            format!(
+                "histogram_quantile(0.95, sum{}(rate({}_bucket{{{}}}[$__rate_interval])))",
+                by_clause(&by),
+                spec.metric,
+                SELECTOR
+            )
+        }
+    }
+}
+
+fn legend(spec: &PanelSpec) -> String {
+    if spec.by.is_empty() {
+        spec.title.to_string()
+    } else {
+        spec.by.iter().map(|label| format!("{{{{{}}}}}", label)).collect::<Vec<_>>().join(" ")
+    }
+}
+
+// (width, height) in grid units
+fn size(viz: Viz) -> (u32, u32) {
+    match viz {
+        Viz::Timeseries => (12, 8),
+        Viz::Stat => (6, 4),
+    }
+}
+
+fn panel_json(spec: &PanelSpec, id: u32, x: u32, y: u32, datasource: &Value) -> Value {
+    let (w, h) = size(spec.viz);
+    json!({
+        "id": id,
+        "type": match spec.viz { Viz::Timeseries => "timeseries", Viz::Stat => "stat" },
+        "title": spec.title,
+        "datasource": datasource,
+        "gridPos": {"h": h, "w": w, "x": x, "y": y},
+        "fieldConfig": {"defaults": {"unit": spec.unit}, "overrides": []},
+        "options": match spec.viz {
+            Viz::Timeseries => json!({"legend": {"displayMode": "list", "placement": "bottom"}}),
+            Viz::Stat => json!({"reduceOptions": {"calcs": ["lastNotNull"], "fields": "", "values": false}}),
+        },
+        "targets": [{
+            "datasource": datasource,
+            "expr": expr(spec),
+            "legendFormat": legend(spec),
+            "refId": "A"
+        }]
+    })
+}
+
+pub fn dashboard(datasource_uid: &str, job: &str) -> Value {
+    let datasource = json!({"type": "prometheus", "uid": datasource_uid});
+    let mut panels = Vec::new();
+    let mut id = 1;
+    let mut y = 0;
+    for (row, specs) in ROWS {
+        panels.push(json!({
+            "id": id,
+            "type": "row",
+            "title": row,
+            "collapsed": false,
+            "gridPos": {"h": 1, "w": GRID_WIDTH, "x": 0, "y": y},
+            "panels": []
+        }));
+        id += 1;
+        y += 1;
+
+        let mut x = 0;
+        let mut row_height = 0;
+        for spec in specs.iter() {
+            let (w, h) = size(spec.viz);
+            if x + w > GRID_WIDTH {
+                x = 0;
+                y += row_height;
+                row_height = 0;
+            }
+            panels.push(panel_json(spec, id, x, y, &datasource));
+            x += w;
+            row_height = row_height.max(h);
+            id += 1;
+        }
+        y += row_height;
+    }
+
+    json!({
+        "uid": "devstack-rust-api",
+        "title": "Rust API Overview",
+        "tags": ["rust", "application", "metrics", "generated"],
+        "editable": true,
+        "schemaVersion": 38,
+        "version": 0,
+        "refresh": "10s",
+        "time": {"from": "now-1h", "to": "now"},
+        "timezone": "",
+        "annotations": {"list": []},
+        "links": [],
+        "templating": {"list": [
+            {
+                "name": "job",
+                "type": "custom",
+                "query": job,
+                "current": {"text": job, "value": job},
+                "hide": 2
+            },
+            {
+                "name": "instance",
+                "type": "query",
+                "datasource": datasource,
+                "query": "label_values(http_requests_total{job=\"$job\"}, instance)",
+                "refresh": 2,
+                "includeAll": true,
+                "multi": true,
+                "allValue": ".*",
+                "current": {"text": "All", "value": "$__all"}
+            }
+        ]},
+        "panels": panels
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DashboardQuery {
+    pub datasource: Option<String>,
+    pub job: Option<String>,
+    // Wrap for POST /api/dashboards/db instead of returning the bare dashboard
+    #[serde(default)]
+    pub import: bool,
+}
+
+// GET /observability/dashboard.json?datasource=Prometheus&job=rust-api&import=false
+pub async fn dashboard_json(query: web::Query<DashboardQuery>) -> impl Responder {
+    let dashboard = dashboard(
+        query.datasource.as_deref().unwrap_or("Prometheus"),
+        query.job.as_deref().unwrap_or("rust-api"),
+    );
+    if query.import {
+        HttpResponse::Ok().json(json!({"dashboard": dashboard, "overwrite": true, "folderId": 0}))
+    } else {
+        HttpResponse::Ok().json(dashboard)
+    }
+}
@@ -0,0 +1,677 @@
+// Backend health checks
+//
+// Every backend implements HealthCheck and is registered in HEALTH_CHECKS. /health/all and
+// /health/{service} iterate the registry, so an extra backend only needs an implementation
+// and a `HEALTH_CHECKS.write().register(...)` call to show up in both. Services left out of
+// ENABLED_SERVICES are skipped, and checks for backends whose cargo feature is off aren't built.
+//
+// HEALTH_CRITICAL_SERVICES (default "vault", "*" for all) decides what /health/all means: a
+// failing critical service makes it "unhealthy" with 503, a failing optional one only
+// "degraded" with 200, so it can back a compose healthcheck without flapping on extras.
+
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use actix_web::{web, HttpResponse, Responder};
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+#[cfg(feature = "mysql")]
+use mysql_async::prelude::Queryable;
+use serde_json::{json, Value};
+
+use crate::redact::Redacted;
+use crate::redis_parse::parse_cluster_nodes;
+use crate::sharding::key_slot;
+use crate::{
+    get_env_or, get_vault_secret, resolve, services, vault, AllHealthResponse, HealthResponse,
+    STACK_SERVICE_CHECK_DURATION, STACK_SERVICE_UP,
+};
+
+impl HealthResponse {
+    pub fn healthy(version: Option<String>) -> Self {
+        HealthResponse {
+            status: "healthy".to_string(),
+            timestamp: Some(chrono::Utc::now().to_rfc3339()),
+            version,
+            error: None,
+            details: None,
+        }
+    }
+
+    pub fn unhealthy(error: String) -> Self {
+        HealthResponse {
+            status: "unhealthy".to_string(),
+            timestamp: Some(chrono::Utc::now().to_rfc3339()),
+            version: None,
+            error: Some(error),
+            details: None,
+        }
+    }
+}
+
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    // Service name used as the /health/{service} path segment and the /health/all key
+    fn name(&self) -> &'static str;
+
+    async fn check(&self) -> Result<HealthResponse, HealthResponse>;
+}
+
+#[derive(Default)]
+pub struct HealthRegistry {
+    checks: Vec<Arc<dyn HealthCheck>>,
+}
+
+impl HealthRegistry {
+    pub fn with_builtin_checks() -> Self {
+        let mut registry = HealthRegistry::default();
+        registry.register(Arc::new(VaultCheck));
+        registry.register(Arc::new(PostgresCheck));
+        #[cfg(feature = "mysql")]
+        registry.register(Arc::new(MysqlCheck));
+        #[cfg(feature = "mongodb")]
+        registry.register(Arc::new(MongodbCheck));
+        registry.register(Arc::new(RedisCheck));
+        #[cfg(feature = "rabbitmq")]
+        registry.register(Arc::new(RabbitmqCheck));
+        registry
+    }
+
+    // Replaces any existing check with the same name
+    pub fn register(&mut self, check: Arc<dyn HealthCheck>) {
+        self.checks.retain(|existing| existing.name() != check.name());
+        self.checks.push(check);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn HealthCheck>> {
+        self.checks.iter().find(|check| check.name() == name).cloned()
+    }
+
+    pub fn checks(&self) -> Vec<Arc<dyn HealthCheck>> {
+        self.checks.clone()
+    }
+}
+
+lazy_static! {
+    pub static ref HEALTH_CHECKS: RwLock<HealthRegistry> = RwLock::new(HealthRegistry::with_builtin_checks());
+}
+
+// Registered checks for services enabled via ENABLED_SERVICES
+fn enabled_checks() -> Vec<Arc<dyn HealthCheck>> {
+    HEALTH_CHECKS
+        .read()
+        .map(|registry| registry.checks())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|check| services::is_enabled(check.name()))
+        .collect()
+}
+
+pub fn critical_services() -> Vec<String> {
+    services::parse_enabled(&get_env_or("HEALTH_CRITICAL_SERVICES", "vault")).unwrap_or_default()
+}
+
+pub fn is_critical(critical: &[String], service: &str) -> bool {
+    critical.iter().any(|c| c == "*" || c == service)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Rollup {
+    pub status: &'static str,
+    pub critical_failures: Vec<String>,
+    pub optional_failures: Vec<String>,
+}
+
+// Overall status from (service, healthy) pairs
+pub fn rollup<'a>(results: impl IntoIterator<Item = (&'a str, bool)>, critical: &[String]) -> Rollup {
+    let (mut critical_failures, mut optional_failures) = (Vec::new(), Vec::new());
+    for (service, healthy) in results {
+        match (healthy, is_critical(critical, service)) {
+            (true, _) => {}
+            (false, true) => critical_failures.push(service.to_string()),
+            (false, false) => optional_failures.push(service.to_string()),
+        }
+    }
+    let status = if !critical_failures.is_empty() {
+        "unhealthy"
+    } else if !optional_failures.is_empty() {
+        "degraded"
+    } else {
+        "healthy"
+    };
+    Rollup { status, critical_failures, optional_failures }
+}
+
+fn check_timeout() -> Duration {
+    Duration::from_secs(get_env_or("HEALTH_CHECK_TIMEOUT_SECONDS", "5").parse().unwrap_or(5).max(1))
+}
+
+fn credentials_error(e: String) -> HealthResponse {
+    HealthResponse::unhealthy(format!("Failed to get credentials: {}", e))
+}
+
+// ============================================================================
+// Check stages
+// ============================================================================
+
+pub const CONNECT: &str = "connect";
+pub const FUNCTIONAL: &str = "functional";
+// Row/document id the write probes upsert, so repeated checks don't grow the probe tables
+const PROBE_ID: &str = "devstack-core-rust-api";
+const POSTGRES_PROBE_DDL: &str =
+    "CREATE TABLE IF NOT EXISTS health_probe (id TEXT PRIMARY KEY, checked_at TIMESTAMPTZ NOT NULL)";
+#[cfg(feature = "mysql")]
+const MYSQL_PROBE_DDL: &str =
+    "CREATE TABLE IF NOT EXISTS health_probe (id VARCHAR(64) PRIMARY KEY, checked_at DATETIME(3) NOT NULL)";
+
+pub fn functional_checks_enabled() -> bool {
+    get_env_or("HEALTH_CHECK_FUNCTIONAL", "true").parse().unwrap_or(true)
+}
+
+// Outcome and duration of each stage a check ran, reported as the response's details
+#[derive(Default)]
+pub struct Stages {
+    details: serde_json::Map<String, Value>,
+}
+
+impl Stages {
+    pub fn new() -> Self {
+        Stages::default()
+    }
+
+    // Runs one stage; a failure ends the check as unhealthy, with every stage so far in details
+    pub async fn run<T>(
+        &mut self,
+        name: &str,
+        stage: impl Future<Output = Result<T, String>>,
+    ) -> Result<T, HealthResponse> {
+        let started = Instant::now();
+        let result = stage.await;
+        let duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+        match result {
+            Ok(value) => {
+                self.details.insert(name.to_string(), json!({ "status": "pass", "duration_ms": duration_ms }));
+                Ok(value)
+            }
+            Err(e) => {
+                self.details.insert(
+                    name.to_string(),
+                    json!({ "status": "fail", "error": e, "duration_ms": duration_ms }),
+                );
+                let mut response = HealthResponse::unhealthy(format!("{} stage failed: {}", name, e));
+                response.details = Some(Value::Object(self.details.clone()));
+                Err(response)
+            }
+        }
+    }
+
+    // The functional stage, unless HEALTH_CHECK_FUNCTIONAL=false
+    pub async fn functional(&mut self, stage: impl Future<Output = Result<(), String>>) -> Result<(), HealthResponse> {
+        if functional_checks_enabled() {
+            self.run(FUNCTIONAL, stage).await
+        } else {
+            self.details.insert(FUNCTIONAL.to_string(), json!({ "status": "skipped" }));
+            Ok(())
+        }
+    }
+
+    pub fn healthy(self, version: Option<String>) -> HealthResponse {
+        let mut response = HealthResponse::healthy(version);
+        response.details = Some(Value::Object(self.details));
+        response
+    }
+}
+
+// ============================================================================
+// Built-in checks
+// ============================================================================
+//
+// connect: the backend is reachable and accepts the credentials. functional: a write and read
+// round trip through a probe key, row, document or message works, which a read-only replica, a
+// full disk or a revoked grant fails even though connecting still succeeds.
+
+pub struct VaultCheck;
+
+#[async_trait]
+impl HealthCheck for VaultCheck {
+    fn name(&self) -> &'static str {
+        "vault"
+    }
+
+    async fn check(&self) -> Result<HealthResponse, HealthResponse> {
+        let vault_addr = resolve::url(&get_env_or("VAULT_ADDR", "http://vault:8200"));
+
+        let mut stages = Stages::new();
+        stages
+            .run(CONNECT, async {
+                let started = Instant::now();
+                let result = reqwest::get(format!("{}/v1/sys/health", vault_addr)).await;
+                let ok = matches!(&result, Ok(resp) if resp.status().is_success());
+                vault::record_vault_request("health", ok, started);
+                if ok {
+                    Ok(())
+                } else {
+                    Err("Vault unavailable".to_string())
+                }
+            })
+            .await?;
+        // Sealed-but-answering is caught above; an expired or revoked token only shows up here
+        stages.functional(async { vault::lookup_token_ttl().await.map(|_| ()) }).await?;
+        Ok(stages.healthy(None))
+    }
+}
+
+pub struct PostgresCheck;
+
+#[async_trait]
+impl HealthCheck for PostgresCheck {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    async fn check(&self) -> Result<HealthResponse, HealthResponse> {
+        let creds = get_vault_secret("postgres").await.map_err(credentials_error)?;
+
+        let port: u16 = get_env_or("POSTGRES_PORT", "5432").parse().unwrap_or(5432);
+        let (host, port) = resolve::connect_target(&get_env_or("POSTGRES_HOST", "postgres"), port).await;
+        // Fallback defaults match Vault bootstrap credentials
+        let user = creds["user"].as_str().unwrap_or("dev_admin");
+        let password = Redacted::new(creds["password"].as_str().unwrap_or("changeme").to_string());
+        let database = creds["database"].as_str().unwrap_or("dev_database");
+
+        let conn_str = Redacted::new(format!(
+            "host={} port={} user={} password={} dbname={}",
+            host,
+            port,
+            user,
+            password.expose(),
+            database
+        ));
+
+        let mut stages = Stages::new();
+        let (client, version) = stages
+            .run(CONNECT, async {
+                let (client, connection) = tokio_postgres::connect(conn_str.expose(), tokio_postgres::NoTls)
+                    .await
+                    .map_err(|e| format!("Connection failed: {}", e))?;
+                tokio::spawn(async move {
+                    if let Err(e) = connection.await {
+                        log::error!("PostgreSQL connection error: {}", e);
+                    }
+                });
+                let row = client.query_one("SELECT version()", &[]).await.map_err(|e| format!("Query failed: {}", e))?;
+                let version: String = row.get(0);
+                Ok((client, version))
+            })
+            .await?;
+        stages
+            .functional(async {
+                client
+                    .batch_execute(POSTGRES_PROBE_DDL)
+                    .await
+                    .map_err(|e| format!("Probe table setup failed: {}", e))?;
+                client
+                    .execute(
+                        "INSERT INTO health_probe (id, checked_at) VALUES ($1, NOW())
+                         ON CONFLICT (id) DO UPDATE SET checked_at = EXCLUDED.checked_at",
+                        &[&PROBE_ID],
+                    )
+                    .await
+                    .map_err(|e| format!("Probe write failed: {}", e))?;
+                Ok(())
+            })
+            .await?;
+        Ok(stages.healthy(Some(
+            version.split(',').next().map(|s| s.to_string()).unwrap_or_else(|| "unknown".to_string()),
+        )))
+    }
+}
+
+#[cfg(feature = "mysql")]
+pub struct MysqlCheck;
+
+#[cfg(feature = "mysql")]
+#[async_trait]
+impl HealthCheck for MysqlCheck {
+    fn name(&self) -> &'static str {
+        "mysql"
+    }
+
+    async fn check(&self) -> Result<HealthResponse, HealthResponse> {
+        let creds = get_vault_secret("mysql").await.map_err(credentials_error)?;
+
+        let port: u16 = get_env_or("MYSQL_PORT", "3306").parse().unwrap_or(3306);
+        let (host, port) = resolve::connect_target(&get_env_or("MYSQL_HOST", "mysql"), port).await;
+        // Fallback defaults match Vault bootstrap credentials
+        let user = creds["user"].as_str().unwrap_or("dev_admin");
+        let password = Redacted::new(creds["password"].as_str().unwrap_or("changeme").to_string());
+        let database = creds["database"].as_str().unwrap_or("dev_database");
+
+        let opts = mysql_async::OptsBuilder::default()
+            .ip_or_hostname(host)
+            .tcp_port(port)
+            .user(Some(user))
+            .pass(Some(password.expose().as_str()))
+            .db_name(Some(database));
+
+        let mut stages = Stages::new();
+        let (mut conn, version) = stages
+            .run(CONNECT, async {
+                let mut conn = mysql_async::Conn::new(opts).await.map_err(|e| format!("Connection failed: {}", e))?;
+                match conn.query_first::<String, _>("SELECT VERSION()").await {
+                    Ok(Some(version)) => Ok((conn, version)),
+                    Ok(None) => Err("No version returned".to_string()),
+                    Err(e) => Err(format!("Query failed: {}", e)),
+                }
+            })
+            .await?;
+        let functional = stages
+            .functional(async {
+                conn.query_drop(MYSQL_PROBE_DDL).await.map_err(|e| format!("Probe table setup failed: {}", e))?;
+                conn.exec_drop("REPLACE INTO health_probe (id, checked_at) VALUES (?, NOW(3))", (PROBE_ID,))
+                    .await
+                    .map_err(|e| format!("Probe write failed: {}", e))
+            })
+            .await;
+        let _ = conn.disconnect().await;
+        functional?;
+        Ok(stages.healthy(Some(version)))
+    }
+}
+
+#[cfg(feature = "mongodb")]
+pub struct MongodbCheck;
+
+#[cfg(feature = "mongodb")]
+#[async_trait]
+impl HealthCheck for MongodbCheck {
+    fn name(&self) -> &'static str {
+        "mongodb"
+    }
+
+    async fn check(&self) -> Result<HealthResponse, HealthResponse> {
+        use mongodb::bson::doc;
+
+        let creds = get_vault_secret("mongodb").await.map_err(credentials_error)?;
+
+        let address = resolve::env_address("MONGODB_HOST", "mongodb", "MONGODB_PORT", "27017");
+        // Fallback defaults match Vault bootstrap credentials
+        let user = creds["user"].as_str().unwrap_or("dev_admin");
+        let password = Redacted::new(creds["password"].as_str().unwrap_or("changeme").to_string());
+
+        let uri = Redacted::new(format!("mongodb://{}:{}@{}/?authSource=admin", user, password.expose(), address));
+
+        let mut stages = Stages::new();
+        let client = stages
+            .run(CONNECT, async {
+                let client =
+                    mongodb::Client::with_uri_str(uri.expose()).await.map_err(|e| format!("Connection failed: {}", e))?;
+                client
+                    .database("admin")
+                    .run_command(doc! { "ping": 1 })
+                    .await
+                    .map_err(|e| format!("Ping failed: {}", e))?;
+                Ok(client)
+            })
+            .await?;
+        stages
+            .functional(async {
+                let probes = client
+                    .database(crate::seed::MONGODB_DATABASE)
+                    .collection::<mongodb::bson::Document>("health_probe");
+                let update = doc! { "$set": { "checked_at": mongodb::bson::DateTime::now() } };
+                probes
+                    .update_one(doc! { "_id": PROBE_ID }, update)
+                    .upsert(true)
+                    .await
+                    .map_err(|e| format!("Probe write failed: {}", e))?;
+                match probes.find_one(doc! { "_id": PROBE_ID }).await {
+                    Ok(Some(_)) => Ok(()),
+                    Ok(None) => Err("Probe document missing after write".to_string()),
+                    Err(e) => Err(format!("Probe read failed: {}", e)),
+                }
+            })
+            .await?;
+        Ok(stages.healthy(Some("MongoDB".to_string())))
+    }
+}
+
+// In cluster mode (CLUSTER NODES answered) the key's hash tag is picked so its slot belongs to
+// the node being checked, which would otherwise answer MOVED for most keys; None when it owns none
+pub fn redis_probe_key(cluster_nodes: Option<&str>, token: &str) -> Option<String> {
+    let Some(raw) = cluster_nodes else {
+        return Some(format!("health:probe:{}", token));
+    };
+    let myself = parse_cluster_nodes(raw).into_iter().find(|node| node.has_flag("myself"))?;
+    (0..10_000)
+        .map(|tag| format!("health:probe:{{{}}}:{}", tag, token))
+        .find(|key| myself.serves(key_slot(key)))
+}
+
+pub struct RedisCheck;
+
+#[async_trait]
+impl HealthCheck for RedisCheck {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    async fn check(&self) -> Result<HealthResponse, HealthResponse> {
+        let creds = get_vault_secret("redis-1").await.map_err(credentials_error)?;
+
+        let address = resolve::env_address("REDIS_HOST", "redis-1", "REDIS_PORT", "6379");
+        let password = Redacted::new(creds["password"].as_str().unwrap_or("").to_string());
+
+        let url = Redacted::new(format!("redis://:{}@{}", password.expose(), address));
+
+        let mut stages = Stages::new();
+        let mut conn = stages
+            .run(CONNECT, async {
+                let client =
+                    redis::Client::open(url.expose().as_str()).map_err(|e| format!("Client creation failed: {}", e))?;
+                let mut conn =
+                    client.get_multiplexed_async_connection().await.map_err(|e| format!("Connection failed: {}", e))?;
+                redis::cmd("PING").query_async::<String>(&mut conn).await.map_err(|e| format!("PING failed: {}", e))?;
+                Ok(conn)
+            })
+            .await?;
+        stages
+            .functional(async {
+                // A key per check, so concurrent checks from several replicas can't read each other's value
+                let token = uuid::Uuid::new_v4().simple().to_string();
+                let nodes = redis::cmd("CLUSTER").arg("NODES").query_async::<String>(&mut conn).await.ok();
+                let key = redis_probe_key(nodes.as_deref(), &token)
+                    .ok_or_else(|| "Node serves no cluster slots, so it can't take writes".to_string())?;
+                redis::cmd("SET")
+                    .arg(&key)
+                    .arg(&token)
+                    .arg("PX")
+                    .arg(30_000)
+                    .query_async::<()>(&mut conn)
+                    .await
+                    .map_err(|e| format!("SET failed: {}", e))?;
+                let read = redis::cmd("GET")
+                    .arg(&key)
+                    .query_async::<Option<String>>(&mut conn)
+                    .await
+                    .map_err(|e| format!("GET failed: {}", e))?;
+                redis::cmd("DEL")
+                    .arg(&key)
+                    .query_async::<()>(&mut conn)
+                    .await
+                    .map_err(|e| format!("DEL failed: {}", e))?;
+                if read.as_deref() == Some(token.as_str()) {
+                    Ok(())
+                } else {
+                    Err(format!("GET returned {:?} instead of the value just SET", read))
+                }
+            })
+            .await?;
+        Ok(stages.healthy(None))
+    }
+}
+
+#[cfg(feature = "rabbitmq")]
+pub struct RabbitmqCheck;
+
+#[cfg(feature = "rabbitmq")]
+#[async_trait]
+impl HealthCheck for RabbitmqCheck {
+    fn name(&self) -> &'static str {
+        "rabbitmq"
+    }
+
+    async fn check(&self) -> Result<HealthResponse, HealthResponse> {
+        let creds = get_vault_secret("rabbitmq").await.map_err(credentials_error)?;
+
+        let address = resolve::env_address("RABBITMQ_HOST", "rabbitmq", "RABBITMQ_PORT", "5672");
+        let user = creds["user"].as_str().unwrap_or("devuser");
+        let password = Redacted::new(creds["password"].as_str().unwrap_or("").to_string());
+        let vhost = creds["vhost"].as_str().unwrap_or("dev_vhost");
+
+        let url = Redacted::new(format!("amqp://{}:{}@{}/{}", user, password.expose(), address, vhost));
+
+        let mut stages = Stages::new();
+        let conn = stages
+            .run(CONNECT, async {
+                lapin::Connection::connect(url.expose(), lapin::ConnectionProperties::default())
+                    .await
+                    .map_err(|e| format!("Connection failed: {}", e))
+            })
+            .await?;
+        // Round trip through a server-named exclusive queue, which disappears with the connection
+        let functional = stages
+            .functional(async {
+                let channel = conn.create_channel().await.map_err(|e| format!("Channel creation failed: {}", e))?;
+                let declare = lapin::options::QueueDeclareOptions { exclusive: true, ..Default::default() };
+                let queue = channel
+                    .queue_declare("", declare, lapin::types::FieldTable::default())
+                    .await
+                    .map_err(|e| format!("Queue declare failed: {}", e))?;
+                channel
+                    .basic_publish(
+                        "",
+                        queue.name().as_str(),
+                        lapin::options::BasicPublishOptions::default(),
+                        PROBE_ID.as_bytes(),
+                        lapin::BasicProperties::default(),
+                    )
+                    .await
+                    .map_err(|e| format!("Publish failed: {}", e))?;
+                // The publish is asynchronous; poll briefly until the broker has routed it
+                for _ in 0..20 {
+                    let got = channel
+                        .basic_get(queue.name().as_str(), lapin::options::BasicGetOptions { no_ack: true })
+                        .await
+                        .map_err(|e| format!("basic.get failed: {}", e))?;
+                    if got.is_some() {
+                        return Ok(());
+                    }
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+                }
+                Err("Probe message was not delivered back".to_string())
+            })
+            .await;
+        let _ = conn.close(0, "Health check complete").await;
+        functional?;
+        Ok(stages.healthy(None))
+    }
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+pub async fn health_service(path: web::Path<String>) -> impl Responder {
+    let name = path.into_inner();
+    if !services::is_enabled(&name) {
+        return services::disabled_response(&name);
+    }
+    let check = match HEALTH_CHECKS.read().ok().and_then(|registry| registry.get(&name)) {
+        Some(check) => check,
+        None => return HttpResponse::NotFound().json(HealthResponse::unhealthy(format!("Unknown service '{}'", name))),
+    };
+
+    match check.check().await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(response) => HttpResponse::ServiceUnavailable().json(response),
+    }
+}
+
+pub async fn health_all() -> impl Responder {
+    let checks = enabled_checks();
+    // A hung backend counts as down instead of holding up the whole response
+    let timeout = check_timeout();
+    let results = futures_util::future::join_all(checks.iter().map(|check| async move {
+        match tokio::time::timeout(timeout, check.check()).await {
+            Ok(result) => result,
+            Err(_) => Err(HealthResponse::unhealthy(format!("Timed out after {}s", timeout.as_secs()))),
+        }
+    }))
+    .await;
+
+    let critical = critical_services();
+    let mut services = serde_json::Map::new();
+    let mut outcomes = Vec::new();
+    for (check, result) in checks.iter().zip(results) {
+        outcomes.push((check.name(), result.is_ok()));
+        let response = match result {
+            Ok(h) | Err(h) => h,
+        };
+        let mut value = serde_json::to_value(response)
+            .unwrap_or_else(|_| serde_json::json!({"status": "error", "error": "Serialization failed"}));
+        value["critical"] = is_critical(&critical, check.name()).into();
+        services.insert(check.name().to_string(), value);
+    }
+
+    let rollup = rollup(outcomes, &critical);
+    let body = AllHealthResponse {
+        status: rollup.status.to_string(),
+        services,
+        critical_failures: rollup.critical_failures,
+        optional_failures: rollup.optional_failures,
+    };
+    if body.critical_failures.is_empty() {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+// ============================================================================
+// Background monitor
+// ============================================================================
+
+// Runs one check, counting a timeout as down; returns (up, seconds taken)
+pub async fn timed_check(check: &dyn HealthCheck, timeout: Duration) -> (bool, f64) {
+    let started = Instant::now();
+    let up = matches!(tokio::time::timeout(timeout, check.check()).await, Ok(Ok(_)));
+    (up, started.elapsed().as_secs_f64())
+}
+
+// Exports stack_service_up / stack_service_check_duration_seconds for every enabled service,
+// so Prometheus can alert per service by scraping only this app
+pub fn spawn_health_monitor() {
+    let interval_secs: u64 = get_env_or("HEALTH_CHECK_INTERVAL_SECONDS", "30").parse().unwrap_or(30);
+    if interval_secs == 0 {
+        return;
+    }
+    let timeout = check_timeout();
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs));
+        loop {
+            ticker.tick().await;
+            let checks = enabled_checks();
+            let results = futures_util::future::join_all(checks.iter().map(|check| timed_check(check.as_ref(), timeout))).await;
+            for (check, (up, seconds)) in checks.iter().zip(results) {
+                STACK_SERVICE_UP.with_label_values(&[check.name()]).set(up as i64);
+                STACK_SERVICE_CHECK_DURATION.with_label_values(&[check.name()]).set(seconds);
+                if !up {
+                    log::debug!("Background health check: {} is down", check.name());
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,252 @@
+// Hedged requests for idempotent reads
+//
+// A hedged read starts the primary attempt and, if it hasn't answered after the hedge delay,
+// starts a second attempt against another copy of the data and takes whichever answers first.
+// The delay is a percentile (HEDGE_PERCENTILE, default 95) of the operation's recent primary
+// latencies, so only the slowest few percent of reads cost a second request. Until
+// HEDGE_MIN_SAMPLES (default 20) latencies have been seen, and never below it, the delay is
+// HEDGE_MIN_DELAY_MS (default 5). A failed attempt doesn't end the race while the other one is
+// still running; a primary that fails before the delay is returned as is, hedging is for latency
+// and not a retry.
+//
+// Off unless HEDGE_ENABLED=true, and only wired into reads that are safe to send twice:
+// - vault_secret: KV v2 secret reads, hedged to VAULT_HEDGE_ADDR (default VAULT_ADDR, which still
+//   helps when a connection rather than the server is slow)
+// - redis_get: cache-aside GETs, hedged to a replica of the master that owns the key's slot. The
+//   compose cluster has no replicas, so there the hedge fails at once and the primary is used.
+//
+// hedged_requests_total{operation,result} counts not_hedged, primary_won, hedge_won and
+// both_failed; GET /hedging shows the current delays.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::{HttpResponse, Responder};
+use lazy_static::lazy_static;
+
+use crate::redis_replication::slot_owner;
+use crate::sharding::key_slot;
+use crate::{get_env_or, redis_connection, redis_node_connection, HEDGED_REQUESTS_TOTAL};
+
+// Recent primary latencies kept per operation
+const SAMPLE_WINDOW: usize = 256;
+// How long CLUSTER NODES output is reused to find replicas
+const TOPOLOGY_TTL: Duration = Duration::from_secs(10);
+
+pub fn hedging_enabled() -> bool {
+    get_env_or("HEDGE_ENABLED", "false").parse().unwrap_or(false)
+}
+
+fn percentile_setting() -> f64 {
+    get_env_or("HEDGE_PERCENTILE", "95").parse::<f64>().unwrap_or(95.0).clamp(1.0, 100.0)
+}
+
+fn min_delay() -> Duration {
+    Duration::from_millis(get_env_or("HEDGE_MIN_DELAY_MS", "5").parse().unwrap_or(5))
+}
+
+fn min_samples() -> usize {
+    get_env_or("HEDGE_MIN_SAMPLES", "20").parse().unwrap_or(20)
+}
+
+// Nearest-rank percentile (0-100] of unsorted samples
+pub fn percentile(samples: &[Duration], p: f64) -> Option<Duration> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort_unstable();
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+// The last `capacity` latencies of one operation
+#[derive(Debug, Clone)]
+pub struct LatencyWindow {
+    samples: VecDeque<Duration>,
+    capacity: usize,
+}
+
+impl LatencyWindow {
+    pub fn new(capacity: usize) -> Self {
+        LatencyWindow { samples: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    pub fn record(&mut self, latency: Duration) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(latency);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    // The percentile of the window, floored at `floor`; just `floor` while there are too few samples
+    pub fn delay(&self, p: f64, floor: Duration, min_samples: usize) -> Duration {
+        if self.samples.len() < min_samples.max(1) {
+            return floor;
+        }
+        let samples: Vec<Duration> = self.samples.iter().copied().collect();
+        percentile(&samples, p).unwrap_or(floor).max(floor)
+    }
+}
+
+lazy_static! {
+    static ref LATENCIES: Mutex<HashMap<String, LatencyWindow>> = Mutex::new(HashMap::new());
+    // (fetched_at, CLUSTER NODES output)
+    static ref TOPOLOGY: Mutex<Option<(Instant, String)>> = Mutex::new(None);
+}
+
+fn record_latency(operation: &str, latency: Duration) {
+    LATENCIES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .entry(operation.to_string())
+        .or_insert_with(|| LatencyWindow::new(SAMPLE_WINDOW))
+        .record(latency);
+}
+
+pub fn hedge_delay(operation: &str) -> Duration {
+    let latencies = LATENCIES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    match latencies.get(operation) {
+        Some(window) => window.delay(percentile_setting(), min_delay(), min_samples()),
+        None => min_delay(),
+    }
+}
+
+enum Finished<T, E> {
+    Primary(Result<T, E>),
+    Hedge(Result<T, E>),
+}
+
+// Races `primary` against `hedge()`, started only once `delay` has passed without an answer.
+// The primary's latency is recorded; when the hedge wins, the time the primary had been waiting
+// stands in for it, so the percentile doesn't forget the slow reads that caused hedging.
+pub async fn race<T, E, P, H, F>(operation: &str, delay: Duration, primary: P, hedge: F) -> Result<T, E>
+where
+    P: Future<Output = Result<T, E>>,
+    F: FnOnce() -> H,
+    H: Future<Output = Result<T, E>>,
+{
+    let started = Instant::now();
+    tokio::pin!(primary);
+    if let Ok(result) = tokio::time::timeout(delay, &mut primary).await {
+        record_latency(operation, started.elapsed());
+        HEDGED_REQUESTS_TOTAL.with_label_values(&[operation, "not_hedged"]).inc();
+        return result;
+    }
+
+    let hedge = hedge();
+    tokio::pin!(hedge);
+    let finished = tokio::select! {
+        result = &mut primary => Finished::Primary(result),
+        result = &mut hedge => Finished::Hedge(result),
+    };
+    record_latency(operation, started.elapsed());
+
+    let (result, winner) = match finished {
+        Finished::Primary(Ok(value)) => (Ok(value), "primary_won"),
+        Finished::Hedge(Ok(value)) => (Ok(value), "hedge_won"),
+        Finished::Primary(Err(e)) => match hedge.await {
+            Ok(value) => (Ok(value), "hedge_won"),
+            Err(_) => (Err(e), "both_failed"),
+        },
+        Finished::Hedge(Err(_)) => match primary.await {
+            Ok(value) => (Ok(value), "primary_won"),
+            Err(e) => (Err(e), "both_failed"),
+        },
+    };
+    HEDGED_REQUESTS_TOTAL.with_label_values(&[operation, winner]).inc();
+    result
+}
+
+// `primary` on its own when hedging is off, raced against `hedge()` after the operation's delay when on
+pub async fn hedged<T, E, P, H, F>(operation: &str, primary: P, hedge: F) -> Result<T, E>
+where
+    P: Future<Output = Result<T, E>>,
+    F: FnOnce() -> H,
+    H: Future<Output = Result<T, E>>,
+{
+    if !hedging_enabled() {
+        return primary.await;
+    }
+    race(operation, hedge_delay(operation), primary, hedge).await
+}
+
+// ============================================================================
+// Redis
+// ============================================================================
+
+async fn cluster_nodes() -> Result<String, String> {
+    {
+        let topology = TOPOLOGY.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some((fetched_at, nodes)) = topology.as_ref() {
+            if fetched_at.elapsed() < TOPOLOGY_TTL {
+                return Ok(nodes.clone());
+            }
+        }
+    }
+    let mut conn = redis_connection().await?;
+    let nodes: String = redis::cmd("CLUSTER")
+        .arg("NODES")
+        .query_async(&mut conn)
+        .await
+        .map_err(|e| format!("CLUSTER NODES failed: {}", e))?;
+    *TOPOLOGY.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some((Instant::now(), nodes.clone()));
+    Ok(nodes)
+}
+
+// GET from a replica of the master that owns the key's slot
+async fn replica_get(key: &str) -> Result<Option<String>, String> {
+    let nodes = cluster_nodes().await?;
+    let slot = key_slot(key);
+    let owner = slot_owner(&nodes, slot).ok_or_else(|| format!("No master serves slot {}", slot))?;
+    let replica = owner.replicas.first().ok_or_else(|| format!("Master {} has no replicas", owner.master))?;
+    let mut conn = redis_node_connection(replica).await?;
+    // Replicas redirect reads to the master unless the connection opts in to stale reads
+    redis::cmd("READONLY").query_async::<()>(&mut conn).await.map_err(|e| format!("READONLY failed: {}", e))?;
+    redis::cmd("GET").arg(key).query_async(&mut conn).await.map_err(|e| format!("Replica GET failed: {}", e))
+}
+
+// GET on `conn`, hedged to a replica of the key's slot master
+pub async fn redis_get(conn: &mut redis::aio::MultiplexedConnection, key: &str) -> Result<Option<String>, String> {
+    let primary = async {
+        redis::cmd("GET").arg(key).query_async(conn).await.map_err(|e| format!("GET failed: {}", e))
+    };
+    hedged("redis_get", primary, || replica_get(key)).await
+}
+
+// ============================================================================
+// Handler
+// ============================================================================
+
+// GET /hedging
+pub async fn hedging_status() -> impl Responder {
+    let operations: serde_json::Map<String, serde_json::Value> = ["vault_secret", "redis_get"]
+        .iter()
+        .map(|operation| {
+            let samples = LATENCIES
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .get(*operation)
+                .map_or(0, LatencyWindow::len);
+            let delay = hedge_delay(operation);
+            let status = serde_json::json!({ "samples": samples, "delay_ms": delay.as_secs_f64() * 1000.0 });
+            (operation.to_string(), status)
+        })
+        .collect();
+    HttpResponse::Ok().json(serde_json::json!({
+        "enabled": hedging_enabled(),
+        "percentile": percentile_setting(),
+        "min_delay_ms": min_delay().as_millis() as u64,
+        "operations": operations
+    }))
+}
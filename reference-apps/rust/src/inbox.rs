@@ -0,0 +1,441 @@
+// Inbox pattern: an exactly-once effect from an at-least-once RabbitMQ consumer
+//
+// RabbitMQ redelivers anything that wasn't acked: after a consumer crash, a lost connection, or a
+// publisher that retried. The consumer here records each message_id in inbox_processed in the same
+// Postgres transaction as the side effect (a row in inbox_ledger). A redelivered message hits the
+// primary key, the transaction applies nothing, and the delivery is simply acked. A crash before
+// the commit leaves neither row, so the redelivery applies it; a crash after the commit but before
+// the ack is the duplicate the inbox absorbs. Because the ack comes after the commit, a message is
+// never lost either. The dedupe is only as good as the message id: publishers must keep it when
+// they retry.
+//
+// The consumer can drop a share of its acks after committing (drop_ack_rate), nacking the message
+// back onto the queue as if it had crashed, and the redeliver endpoint republishes an applied
+// message under its original id. Either way inbox stats show duplicates skipped while the ledger
+// keeps exactly one entry per message.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::{web, HttpResponse, Responder};
+use futures_util::StreamExt;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::watch;
+
+use crate::{amqp_connection, pool};
+
+const TABLE_DDL: &str = "CREATE TABLE IF NOT EXISTS inbox_processed (
+    message_id TEXT PRIMARY KEY,
+    processed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE TABLE IF NOT EXISTS inbox_ledger (
+    id BIGSERIAL PRIMARY KEY,
+    message_id TEXT NOT NULL REFERENCES inbox_processed (message_id),
+    account TEXT NOT NULL,
+    amount_cents BIGINT NOT NULL,
+    applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+)";
+
+const QUEUE: &str = "inbox-demo";
+const PREFETCH: u16 = 10;
+const MAX_ACCOUNT_LEN: usize = 64;
+const MAX_REDELIVERIES: usize = 10;
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Transfer {
+    pub account: String,
+    pub amount_cents: i64,
+}
+
+impl Transfer {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.account.is_empty() || self.account.len() > MAX_ACCOUNT_LEN {
+            return Err(format!("account must be 1-{} characters", MAX_ACCOUNT_LEN));
+        }
+        if self.amount_cents == 0 {
+            return Err("amount_cents must be non-zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+async fn client() -> Result<pool::Pooled<pool::PostgresManager>, HttpResponse> {
+    let client = pool::postgres()
+        .await
+        .map_err(|e| error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e))?;
+    client.batch_execute(TABLE_DDL).await.map_err(|e| {
+        error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Table setup failed: {}", e))
+    })?;
+    Ok(client)
+}
+
+// ============================================================================
+// Processing
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Processed {
+    Applied,
+    Duplicate,
+}
+
+// Records the message id and applies the transfer in one transaction, or neither
+async fn process(message_id: &str, transfer: &Transfer) -> Result<Processed, String> {
+    let mut client = pool::postgres().await?;
+    let tx = client.transaction().await.map_err(|e| format!("Transaction failed: {}", e))?;
+    let recorded = tx
+        .execute(
+            "INSERT INTO inbox_processed (message_id) VALUES ($1) ON CONFLICT (message_id) DO NOTHING",
+            &[&message_id],
+        )
+        .await
+        .map_err(|e| format!("Inbox insert failed: {}", e))?;
+    if recorded == 0 {
+        // Dropping the transaction rolls it back; there is nothing to undo anyway
+        return Ok(Processed::Duplicate);
+    }
+    tx.execute(
+        "INSERT INTO inbox_ledger (message_id, account, amount_cents) VALUES ($1, $2, $3)",
+        &[&message_id, &transfer.account, &transfer.amount_cents],
+    )
+    .await
+    .map_err(|e| format!("Ledger insert failed: {}", e))?;
+    tx.commit().await.map_err(|e| format!("Commit failed: {}", e))?;
+    Ok(Processed::Applied)
+}
+
+async fn publish(message_id: &str, transfer: &Transfer, copies: usize) -> Result<(), String> {
+    let body = serde_json::to_vec(transfer).map_err(|e| e.to_string())?;
+    let conn = amqp_connection().await?;
+    let result = async {
+        let channel = conn.create_channel().await.map_err(|e| format!("Channel creation failed: {}", e))?;
+        declare_queue(&channel).await?;
+        channel
+            .confirm_select(lapin::options::ConfirmSelectOptions::default())
+            .await
+            .map_err(|e| format!("confirm.select failed: {}", e))?;
+        for _ in 0..copies {
+            let properties = lapin::BasicProperties::default()
+                .with_message_id(message_id.into())
+                .with_content_type("application/json".into())
+                .with_delivery_mode(2);
+            channel
+                .basic_publish("", QUEUE, lapin::options::BasicPublishOptions::default(), &body, properties)
+                .await
+                .map_err(|e| format!("Publish failed: {}", e))?
+                .await
+                .map_err(|e| format!("Publish confirm failed: {}", e))?;
+        }
+        Ok::<_, String>(())
+    }
+    .await;
+    let _ = conn.close(0, "Done").await;
+    result
+}
+
+async fn declare_queue(channel: &lapin::Channel) -> Result<(), String> {
+    let options = lapin::options::QueueDeclareOptions { durable: true, ..Default::default() };
+    channel
+        .queue_declare(QUEUE, options, lapin::types::FieldTable::default())
+        .await
+        .map_err(|e| format!("Queue declare failed: {}", e))?;
+    Ok(())
+}
+
+// ============================================================================
+// Consumer
+// ============================================================================
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ConsumerConfig {
+    // Share of applied messages nacked back onto the queue instead of acked, as if the consumer
+    // crashed between the commit and the ack
+    #[serde(default)]
+    pub drop_ack_rate: f64,
+}
+
+#[derive(Default)]
+struct ConsumerStats {
+    applied: AtomicU64,
+    duplicates: AtomicU64,
+    acks_dropped: AtomicU64,
+    rejected: AtomicU64,
+    last_error: Mutex<Option<String>>,
+}
+
+impl ConsumerStats {
+    fn record_error(&self, error: String) {
+        log::debug!("Inbox consumer: {}", error);
+        if let Ok(mut last) = self.last_error.lock() {
+            *last = Some(error);
+        }
+    }
+}
+
+struct Consumer {
+    config: ConsumerConfig,
+    started_at: chrono::DateTime<chrono::Utc>,
+    stats: Arc<ConsumerStats>,
+    stop: watch::Sender<bool>,
+}
+
+impl Consumer {
+    fn status(&self) -> Value {
+        serde_json::json!({
+            "queue": QUEUE,
+            "config": self.config,
+            "started_at": self.started_at.to_rfc3339(),
+            "applied": self.stats.applied.load(Ordering::Relaxed),
+            "duplicates_skipped": self.stats.duplicates.load(Ordering::Relaxed),
+            "acks_dropped": self.stats.acks_dropped.load(Ordering::Relaxed),
+            "rejected": self.stats.rejected.load(Ordering::Relaxed),
+            "last_error": self.stats.last_error.lock().ok().and_then(|e| e.clone())
+        })
+    }
+}
+
+lazy_static! {
+    static ref CONSUMER: Mutex<Option<Consumer>> = Mutex::new(None);
+}
+
+async fn handle(delivery: lapin::message::Delivery, config: &ConsumerConfig, stats: &ConsumerStats) {
+    let message_id = delivery.properties.message_id().as_ref().map(|id| id.as_str().to_string());
+    let transfer = serde_json::from_slice::<Transfer>(&delivery.data).ok().filter(|t| t.validate().is_ok());
+    let (Some(message_id), Some(transfer)) = (message_id, transfer) else {
+        // Without an id there is nothing to dedupe on; park it rather than apply it blindly
+        stats.rejected.fetch_add(1, Ordering::Relaxed);
+        let _ = delivery.reject(lapin::options::BasicRejectOptions { requeue: false }).await;
+        return;
+    };
+    match process(&message_id, &transfer).await {
+        Ok(Processed::Applied) if rand::random::<f64>() < config.drop_ack_rate => {
+            stats.applied.fetch_add(1, Ordering::Relaxed);
+            stats.acks_dropped.fetch_add(1, Ordering::Relaxed);
+            let _ = delivery.nack(lapin::options::BasicNackOptions { requeue: true, ..Default::default() }).await;
+        }
+        Ok(processed) => {
+            let counter = if processed == Processed::Applied { &stats.applied } else { &stats.duplicates };
+            counter.fetch_add(1, Ordering::Relaxed);
+            if let Err(e) = delivery.ack(lapin::options::BasicAckOptions::default()).await {
+                stats.record_error(format!("Ack failed: {}", e));
+            }
+        }
+        Err(e) => {
+            stats.record_error(e);
+            // Not applied, so it is safe to try again; back off so a dead database isn't hammered
+            tokio::time::sleep(RECONNECT_DELAY).await;
+            let _ = delivery.nack(lapin::options::BasicNackOptions { requeue: true, ..Default::default() }).await;
+        }
+    }
+}
+
+async fn consume(
+    config: &ConsumerConfig,
+    stats: &ConsumerStats,
+    stop: &mut watch::Receiver<bool>,
+) -> Result<(), String> {
+    let conn = amqp_connection().await?;
+    let result = async {
+        let channel = conn.create_channel().await.map_err(|e| format!("Channel creation failed: {}", e))?;
+        declare_queue(&channel).await?;
+        channel
+            .basic_qos(PREFETCH, lapin::options::BasicQosOptions::default())
+            .await
+            .map_err(|e| format!("basic.qos failed: {}", e))?;
+        let options = lapin::options::BasicConsumeOptions { no_ack: false, ..Default::default() };
+        let mut consumer = channel
+            .basic_consume(QUEUE, "", options, lapin::types::FieldTable::default())
+            .await
+            .map_err(|e| format!("basic.consume failed: {}", e))?;
+        loop {
+            let next = tokio::select! {
+                next = consumer.next() => next,
+                _ = stop.changed() => return Ok(()),
+            };
+            match next {
+                Some(Ok(delivery)) => handle(delivery, config, stats).await,
+                Some(Err(e)) => return Err(format!("Consume failed: {}", e)),
+                None => return Err("Consumer was cancelled".to_string()),
+            }
+        }
+    }
+    .await;
+    let _ = conn.close(0, "Done").await;
+    result
+}
+
+async fn run_consumer(config: ConsumerConfig, stats: Arc<ConsumerStats>, mut stop: watch::Receiver<bool>) {
+    while !*stop.borrow() {
+        if let Err(e) = consume(&config, &stats, &mut stop).await {
+            stats.record_error(e);
+            tokio::select! {
+                _ = tokio::time::sleep(RECONNECT_DELAY) => {}
+                _ = stop.changed() => {}
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+// POST /examples/patterns/inbox/messages
+// {"account": "acc-1", "amount_cents": 500}; the response carries the generated message_id
+pub async fn send_message(body: web::Json<Transfer>) -> impl Responder {
+    if let Err(e) = body.validate() {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, e);
+    }
+    let message_id = uuid::Uuid::new_v4().to_string();
+    match publish(&message_id, &body, 1).await {
+        Ok(()) => HttpResponse::Accepted().json(serde_json::json!({
+            "status": "published",
+            "queue": QUEUE,
+            "message_id": message_id,
+            "message": body.into_inner()
+        })),
+        Err(e) => error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RedeliverQuery {
+    times: Option<usize>,
+}
+
+// POST /examples/patterns/inbox/messages/{id}/redeliver?times=1
+// Publishes an already-applied message again under its original id, as a retrying publisher would
+pub async fn redeliver(path: web::Path<String>, query: web::Query<RedeliverQuery>) -> impl Responder {
+    let message_id = path.into_inner();
+    let times = query.times.unwrap_or(1);
+    if !(1..=MAX_REDELIVERIES).contains(&times) {
+        return error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            format!("times must be between 1 and {}", MAX_REDELIVERIES),
+        );
+    }
+    let client = match client().await {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
+    let applied = client
+        .query_opt("SELECT account, amount_cents FROM inbox_ledger WHERE message_id = $1", &[&message_id])
+        .await;
+    let transfer = match applied {
+        Ok(Some(row)) => Transfer { account: row.get(0), amount_cents: row.get(1) },
+        Ok(None) => {
+            return error_response(
+                actix_web::http::StatusCode::NOT_FOUND,
+                format!("Message '{}' has not been applied (yet)", message_id),
+            )
+        }
+        Err(e) => {
+            return error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Query failed: {}", e))
+        }
+    };
+    match publish(&message_id, &transfer, times).await {
+        Ok(()) => HttpResponse::Accepted().json(serde_json::json!({
+            "status": "redelivered",
+            "message_id": message_id,
+            "copies": times,
+            "message": transfer
+        })),
+        Err(e) => error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    }
+}
+
+// GET /examples/patterns/inbox/stats
+pub async fn stats() -> impl Responder {
+    let client = match client().await {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
+    let totals = client
+        .query_one(
+            "SELECT (SELECT COUNT(*) FROM inbox_processed), COUNT(*), COALESCE(SUM(amount_cents), 0)::bigint
+             FROM inbox_ledger",
+            &[],
+        )
+        .await;
+    let balances = client
+        .query(
+            "SELECT account, SUM(amount_cents)::bigint, COUNT(*) FROM inbox_ledger
+             GROUP BY account ORDER BY account LIMIT 50",
+            &[],
+        )
+        .await;
+    let consumer = CONSUMER.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).as_ref().map(Consumer::status);
+    match (totals, balances) {
+        (Ok(totals), Ok(balances)) => {
+            let accounts: Vec<Value> = balances
+                .iter()
+                .map(|row| {
+                    serde_json::json!({
+                        "account": row.get::<_, String>(0),
+                        "balance_cents": row.get::<_, i64>(1),
+                        "entries": row.get::<_, i64>(2)
+                    })
+                })
+                .collect();
+            HttpResponse::Ok().json(serde_json::json!({
+                "queue": QUEUE,
+                "processed_messages": totals.get::<_, i64>(0),
+                // Always equal to processed_messages, however often messages were redelivered
+                "ledger_entries": totals.get::<_, i64>(1),
+                "ledger_total_cents": totals.get::<_, i64>(2),
+                "accounts": accounts,
+                "consumer": consumer
+            }))
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Query failed: {}", e))
+        }
+    }
+}
+
+// POST /examples/patterns/inbox/consumer {"drop_ack_rate": 0.3}
+pub async fn start_consumer(body: Option<web::Json<ConsumerConfig>>) -> impl Responder {
+    let config = body.map(|b| b.into_inner()).unwrap_or(ConsumerConfig { drop_ack_rate: 0.0 });
+    if !(0.0..1.0).contains(&config.drop_ack_rate) {
+        return error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            "drop_ack_rate must be at least 0.0 and below 1.0".to_string(),
+        );
+    }
+    if let Err(response) = client().await {
+        return response;
+    }
+    let mut current = CONSUMER.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if current.is_some() {
+        return error_response(
+            actix_web::http::StatusCode::CONFLICT,
+            "The inbox consumer is already running".to_string(),
+        );
+    }
+    let (stop, stop_rx) = watch::channel(false);
+    let consumer = Consumer { config, started_at: chrono::Utc::now(), stats: Arc::new(ConsumerStats::default()), stop };
+    tokio::spawn(run_consumer(consumer.config.clone(), consumer.stats.clone(), stop_rx));
+    let status = consumer.status();
+    *current = Some(consumer);
+    HttpResponse::Created().json(status)
+}
+
+// DELETE /examples/patterns/inbox/consumer - unacked deliveries go back to the queue
+pub async fn stop_consumer() -> impl Responder {
+    let consumer = CONSUMER.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+    match consumer {
+        Some(consumer) => {
+            let _ = consumer.stop.send(true);
+            HttpResponse::Ok().json(consumer.status())
+        }
+        None => error_response(actix_web::http::StatusCode::NOT_FOUND, "The inbox consumer is not running".to_string()),
+    }
+}
@@ -0,0 +1,232 @@
+// Self-registration of app replicas in Redis
+//
+// Every replica writes `{instances}:<id>` with its hostname, version and start time, refreshed
+// every INSTANCE_HEARTBEAT_SECONDS (default 10) with a TTL of INSTANCE_TTL_SECONDS (default 30),
+// and adds its id to the `{instances}` set. A replica that stops heartbeating simply expires, so
+// GET /cluster/instances lists live replicas without any discovery server. The hash tag keeps
+// all keys in one cluster slot.
+//
+// GET /metrics/targets renders the same registry in Prometheus http_sd format, so Prometheus
+// scrapes every live replica without a static target list. Each replica advertises the address
+// of its first plain HTTP listener, with the hostname in place of a wildcard IP, unless
+// METRICS_ADVERTISE_ADDRESS says otherwise.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use actix_web::{HttpResponse, Responder};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::{build_info, get_env_or, listeners, redis_connection, resolve, CLUSTER_INSTANCES};
+
+const INSTANCE_SET: &str = "{instances}";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Instance {
+    pub id: String,
+    pub hostname: String,
+    pub version: String,
+    pub git_commit: String,
+    pub started_at: String,
+    pub last_heartbeat: String,
+    pub listeners: Vec<String>,
+    // Older replicas don't report it; they are left out of /metrics/targets
+    #[serde(default)]
+    pub metrics_address: Option<String>,
+}
+
+lazy_static! {
+    static ref SELF: (String, String, chrono::DateTime<chrono::Utc>) = {
+        let host = resolve::local_hostname();
+        let id = format!("{}-{}", host, &uuid::Uuid::new_v4().simple().to_string()[..8]);
+        (id, host, chrono::Utc::now())
+    };
+}
+
+pub fn self_id() -> &'static str {
+    &SELF.0
+}
+
+pub fn instance_key(id: &str) -> String {
+    format!("{}:{}", INSTANCE_SET, id)
+}
+
+fn current() -> Instance {
+    let (id, hostname, started_at) = &*SELF;
+    let build = build_info::build_info();
+    Instance {
+        id: id.clone(),
+        hostname: hostname.clone(),
+        version: build.version,
+        git_commit: build.git_commit,
+        started_at: started_at.to_rfc3339(),
+        last_heartbeat: chrono::Utc::now().to_rfc3339(),
+        metrics_address: metrics_address(hostname),
+        listeners: listeners::bound(),
+    }
+}
+
+fn metrics_address(hostname: &str) -> Option<String> {
+    match get_env_or("METRICS_ADVERTISE_ADDRESS", "") {
+        address if address.is_empty() => advertise_address(hostname, &listeners::bound()),
+        address => Some(address),
+    }
+}
+
+// host:port Prometheus can reach, from listeners formatted as "http://0.0.0.0:8004"
+pub fn advertise_address(hostname: &str, listeners: &[String]) -> Option<String> {
+    let addr: SocketAddr = listeners.iter().find_map(|l| l.strip_prefix("http://")?.parse().ok())?;
+    if addr.ip().is_unspecified() {
+        Some(resolve::join(hostname, addr.port()))
+    } else {
+        Some(addr.to_string())
+    }
+}
+
+// One http_sd target group per instance that advertises an address
+pub fn http_sd_targets(instances: &[Instance]) -> serde_json::Value {
+    let groups: Vec<serde_json::Value> = instances
+        .iter()
+        .filter_map(|instance| {
+            let address = instance.metrics_address.as_ref()?;
+            Some(serde_json::json!({
+                "targets": [address],
+                "labels": {
+                    "__metrics_path__": "/metrics",
+                    "app": "rust-api",
+                    "instance_id": instance.id,
+                    "hostname": instance.hostname,
+                    "version": instance.version,
+                    "git_commit": instance.git_commit,
+                }
+            }))
+        })
+        .collect();
+    serde_json::Value::Array(groups)
+}
+
+fn ttl_secs() -> u64 {
+    get_env_or("INSTANCE_TTL_SECONDS", "30").parse().unwrap_or(30).max(1)
+}
+
+async fn heartbeat() -> Result<(), String> {
+    let instance = current();
+    let json = serde_json::to_string(&instance).map_err(|e| e.to_string())?;
+    let mut conn = redis_connection().await?;
+    redis::pipe()
+        .cmd("SET")
+        .arg(instance_key(&instance.id))
+        .arg(json)
+        .arg("EX")
+        .arg(ttl_secs())
+        .ignore()
+        .cmd("SADD")
+        .arg(INSTANCE_SET)
+        .arg(&instance.id)
+        .ignore()
+        .query_async::<()>(&mut conn)
+        .await
+        .map_err(|e| format!("Heartbeat failed: {}", e))
+}
+
+// Live instances, pruning set members whose key has expired
+pub async fn live_instances() -> Result<Vec<Instance>, String> {
+    let mut conn = redis_connection().await?;
+    let ids: Vec<String> = redis::cmd("SMEMBERS")
+        .arg(INSTANCE_SET)
+        .query_async(&mut conn)
+        .await
+        .map_err(|e| format!("SMEMBERS failed: {}", e))?;
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let keys: Vec<String> = ids.iter().map(|id| instance_key(id)).collect();
+    let values: Vec<Option<String>> = redis::cmd("MGET")
+        .arg(&keys)
+        .query_async(&mut conn)
+        .await
+        .map_err(|e| format!("MGET failed: {}", e))?;
+
+    let (live, expired) = split_live(&ids, values);
+    if !expired.is_empty() {
+        let _ = redis::cmd("SREM").arg(INSTANCE_SET).arg(&expired).query_async::<i64>(&mut conn).await;
+    }
+    Ok(live)
+}
+
+// (instances with a live key, ids whose key is gone)
+pub fn split_live(ids: &[String], values: Vec<Option<String>>) -> (Vec<Instance>, Vec<String>) {
+    let mut live = Vec::new();
+    let mut expired = Vec::new();
+    for (id, value) in ids.iter().zip(values) {
+        match value.and_then(|json| serde_json::from_str::<Instance>(&json).ok()) {
+            Some(instance) => live.push(instance),
+            None => expired.push(id.clone()),
+        }
+    }
+    live.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+    (live, expired)
+}
+
+pub fn spawn_instance_heartbeat() {
+    let interval_secs: u64 = get_env_or("INSTANCE_HEARTBEAT_SECONDS", "10").parse().unwrap_or(10);
+    if interval_secs == 0 {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs));
+        loop {
+            ticker.tick().await;
+            if let Err(e) = heartbeat().await {
+                log::debug!("Instance registry: {}", e);
+                continue;
+            }
+            if let Ok(live) = live_instances().await {
+                CLUSTER_INSTANCES.set(live.len() as i64);
+            }
+        }
+    });
+}
+
+pub async fn list_instances() -> impl Responder {
+    match live_instances().await {
+        Ok(instances) => {
+            let self_id = &SELF.0;
+            let instances: Vec<serde_json::Value> = instances
+                .into_iter()
+                .map(|instance| {
+                    let is_self = &instance.id == self_id;
+                    let mut value = serde_json::json!(instance);
+                    value["self"] = serde_json::Value::Bool(is_self);
+                    value
+                })
+                .collect();
+            HttpResponse::Ok().json(serde_json::json!({
+                "self": self_id,
+                "count": instances.len(),
+                "ttl_seconds": ttl_secs(),
+                "instances": instances
+            }))
+        }
+        Err(e) => HttpResponse::ServiceUnavailable().json(serde_json::json!({ "status": "error", "error": e })),
+    }
+}
+
+// GET /metrics/targets - Prometheus http_sd; answers with this replica alone when Redis is down
+// so discovery never comes back empty
+pub async fn metrics_targets() -> impl Responder {
+    let instances = match live_instances().await {
+        Ok(instances) if instances.iter().any(|instance| instance.id == SELF.0) => instances,
+        Ok(mut instances) => {
+            instances.push(current());
+            instances
+        }
+        Err(e) => {
+            log::debug!("Instance registry unavailable for http_sd: {}", e);
+            vec![current()]
+        }
+    };
+    HttpResponse::Ok().json(http_sd_targets(&instances))
+}
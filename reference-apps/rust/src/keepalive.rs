@@ -0,0 +1,139 @@
+// Keep-alive and dead-peer detection settings across the stack
+//
+// Every knob defaults to the value the app used before it was configurable (or the backend's own
+// default), so leaving the environment alone changes nothing. GET /admin/keepalive shows the
+// effective values.
+
+use std::time::Duration;
+
+use actix_web::http::KeepAlive;
+use actix_web::{HttpResponse, Responder};
+
+use crate::{get_env_or, redis_master_addresses, redis_node_connection};
+
+fn env_u64(key: &str, default: u64) -> u64 {
+    get_env_or(key, &default.to_string()).parse().unwrap_or(default)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeepaliveConfig {
+    // Idle time before the server closes a keep-alive HTTP connection; 0 disables keep-alive
+    pub http_keep_alive_secs: u64,
+    // How long a client may take to send the request head
+    pub http_client_request_timeout_ms: u64,
+    // How long to wait for a client to acknowledge connection shutdown
+    pub http_client_disconnect_timeout_ms: u64,
+    // Interval of SSE keep-alive comments; a failed write is how a vanished client is noticed
+    pub stream_heartbeat_secs: u64,
+    // Negotiated AMQP heartbeat; 0 disables heartbeats
+    pub amqp_heartbeat_secs: u64,
+    // Redis server `tcp-keepalive` applied to every master at startup; None leaves it alone
+    pub redis_tcp_keepalive_secs: Option<u64>,
+    // Postgres client TCP keepalives; idle 0 disables them
+    pub postgres_keepalives_idle_secs: u64,
+    pub postgres_keepalives_interval_secs: u64,
+    pub postgres_keepalives_retries: u64,
+}
+
+impl KeepaliveConfig {
+    pub fn from_env() -> Self {
+        KeepaliveConfig {
+            http_keep_alive_secs: env_u64("HTTP_KEEP_ALIVE_SECONDS", 5),
+            http_client_request_timeout_ms: env_u64("HTTP_CLIENT_REQUEST_TIMEOUT_MS", 5_000),
+            http_client_disconnect_timeout_ms: env_u64("HTTP_CLIENT_DISCONNECT_TIMEOUT_MS", 1_000),
+            stream_heartbeat_secs: env_u64("STREAM_HEARTBEAT_SECONDS", 15).max(1),
+            amqp_heartbeat_secs: env_u64("AMQP_HEARTBEAT_SECONDS", 60),
+            redis_tcp_keepalive_secs: get_env_or("REDIS_TCP_KEEPALIVE_SECONDS", "").parse().ok(),
+            postgres_keepalives_idle_secs: env_u64("POSTGRES_KEEPALIVES_IDLE_SECONDS", 0),
+            postgres_keepalives_interval_secs: env_u64("POSTGRES_KEEPALIVES_INTERVAL_SECONDS", 10),
+            postgres_keepalives_retries: env_u64("POSTGRES_KEEPALIVES_RETRIES", 3),
+        }
+    }
+
+    pub fn http_keep_alive(&self) -> KeepAlive {
+        match self.http_keep_alive_secs {
+            0 => KeepAlive::Disabled,
+            secs => KeepAlive::Timeout(Duration::from_secs(secs)),
+        }
+    }
+
+    // Idle timeout as served, None when keep-alive is off
+    pub fn http_keep_alive_timeout_secs(&self) -> Option<u64> {
+        match self.http_keep_alive() {
+            KeepAlive::Timeout(timeout) => Some(timeout.as_secs()),
+            _ => None,
+        }
+    }
+
+    pub fn stream_heartbeat(&self) -> Duration {
+        Duration::from_secs(self.stream_heartbeat_secs)
+    }
+
+    // Query string appended to the AMQP URI
+    pub fn amqp_query(&self) -> String {
+        format!("heartbeat={}", self.amqp_heartbeat_secs)
+    }
+
+    // Extra tokio-postgres connection parameters ("" when keepalives are off)
+    pub fn postgres_params(&self) -> String {
+        if self.postgres_keepalives_idle_secs == 0 {
+            return String::new();
+        }
+        format!(
+            " keepalives=1 keepalives_idle={} keepalives_interval={} keepalives_retries={}",
+            self.postgres_keepalives_idle_secs, self.postgres_keepalives_interval_secs, self.postgres_keepalives_retries
+        )
+    }
+}
+
+// Sets `tcp-keepalive` on every master so Redis probes idle client sockets at that interval
+pub fn spawn_redis_tcp_keepalive(seconds: u64) {
+    tokio::spawn(async move {
+        let masters = match redis_master_addresses().await {
+            Ok(masters) => masters,
+            Err(e) => {
+                log::warn!("Could not apply Redis tcp-keepalive: {}", e);
+                return;
+            }
+        };
+        for address in masters {
+            let applied = async {
+                let mut conn = redis_node_connection(&address).await?;
+                redis::cmd("CONFIG")
+                    .arg("SET")
+                    .arg("tcp-keepalive")
+                    .arg(seconds)
+                    .query_async::<()>(&mut conn)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            .await;
+            match applied {
+                Ok(()) => log::info!("Redis {} tcp-keepalive set to {}s", address, seconds),
+                Err(e) => log::warn!("Could not set tcp-keepalive on {}: {}", address, e),
+            }
+        }
+    });
+}
+
+pub async fn keepalive_settings() -> impl Responder {
+    let config = KeepaliveConfig::from_env();
+    HttpResponse::Ok().json(serde_json::json!({
+        "http": {
+            "keep_alive": config.http_keep_alive_timeout_secs().is_some(),
+            // null rather than 0 when disabled: connections close after each response, they don't time out
+            "keep_alive_seconds": config.http_keep_alive_timeout_secs(),
+            "client_request_timeout_ms": config.http_client_request_timeout_ms,
+            "client_disconnect_timeout_ms": config.http_client_disconnect_timeout_ms
+        },
+        "streams": { "heartbeat_seconds": config.stream_heartbeat_secs },
+        "amqp": { "heartbeat_seconds": config.amqp_heartbeat_secs },
+        "redis": { "server_tcp_keepalive_seconds": config.redis_tcp_keepalive_secs },
+        "postgres": {
+            "keepalives": config.postgres_keepalives_idle_secs > 0,
+            "idle_seconds": config.postgres_keepalives_idle_secs,
+            "interval_seconds": config.postgres_keepalives_interval_secs,
+            "retries": config.postgres_keepalives_retries
+        }
+    }))
+}
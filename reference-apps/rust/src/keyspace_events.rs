@@ -0,0 +1,258 @@
+// Redis keyspace notifications streamed to clients as server-sent events
+//
+// Opt-in with KEYSPACE_EVENTS_ENABLED=true. At startup every cluster master gets
+// `notify-keyspace-events` set (KEYSPACE_EVENTS_FLAGS) and one subscriber per master forwards
+// keyevent messages into a broadcast channel. Each SSE client filters that channel by event
+// type and key pattern, so the number of Redis subscriptions doesn't grow with clients.
+
+use std::time::Duration;
+
+use actix_web::{web, HttpResponse, Responder};
+use futures_util::StreamExt;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::keepalive;
+use crate::stream_buffer::{self, BufferConfig, Received};
+use crate::redact::Redacted;
+use crate::{get_env_or, redis_master_addresses, redis_password, resolve};
+
+const DEFAULT_EVENTS: &[&str] = &["expired", "evicted", "set"];
+const CHANNEL_CAPACITY: usize = 1024;
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct KeyEvent {
+    pub event: String,
+    pub key: String,
+    pub db: u32,
+    pub node: String,
+    pub timestamp: String,
+}
+
+lazy_static! {
+    static ref EVENTS: broadcast::Sender<KeyEvent> = broadcast::channel(CHANNEL_CAPACITY).0;
+}
+
+pub fn is_enabled() -> bool {
+    get_env_or("KEYSPACE_EVENTS_ENABLED", "false").parse().unwrap_or(false)
+}
+
+// `__keyevent@0__:expired` -> (0, "expired")
+pub fn parse_keyevent_channel(channel: &str) -> Option<(u32, String)> {
+    let rest = channel.strip_prefix("__keyevent@")?;
+    let (db, event) = rest.split_once("__:")?;
+    Some((db.parse().ok()?, event.to_string()))
+}
+
+// Redis-style glob with `*` and `?`
+pub fn glob_match(pattern: &str, key: &str) -> bool {
+    let (pattern, key): (Vec<char>, Vec<char>) = (pattern.chars().collect(), key.chars().collect());
+    let (mut p, mut k) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while k < key.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == key[k]) {
+            p += 1;
+            k += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, k));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // Let the last `*` absorb one more character and retry
+            p = star + 1;
+            k = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[derive(Debug, PartialEq)]
+pub struct EventFilter {
+    pub events: Vec<String>,
+    pub pattern: String,
+}
+
+impl EventFilter {
+    pub fn new(events: Option<&str>, pattern: Option<&str>) -> Self {
+        let events = match events {
+            Some(list) => list.split(',').map(|e| e.trim().to_ascii_lowercase()).filter(|e| !e.is_empty()).collect(),
+            None => DEFAULT_EVENTS.iter().map(|e| e.to_string()).collect(),
+        };
+        EventFilter { events, pattern: pattern.unwrap_or("*").to_string() }
+    }
+
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        self.events.iter().any(|e| e == "*" || *e == event.event) && glob_match(&self.pattern, &event.key)
+    }
+}
+
+// ============================================================================
+// Subscribers
+// ============================================================================
+
+async fn subscribe_node(address: &str, password: &Redacted<String>, flags: &str) -> Result<(), String> {
+    let client = redis::Client::open(format!("redis://:{}@{}", password.expose(), resolve::address(address)))
+        .map_err(|e| format!("Client creation failed: {}", e))?;
+
+    let mut conn = client
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(|e| format!("Connection failed: {}", e))?;
+    redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("notify-keyspace-events")
+        .arg(flags)
+        .query_async::<()>(&mut conn)
+        .await
+        .map_err(|e| format!("CONFIG SET notify-keyspace-events failed: {}", e))?;
+
+    let mut pubsub = client.get_async_pubsub().await.map_err(|e| format!("Pub/sub connection failed: {}", e))?;
+    pubsub
+        .psubscribe("__keyevent@*__:*")
+        .await
+        .map_err(|e| format!("PSUBSCRIBE failed: {}", e))?;
+    log::info!("Subscribed to keyspace events on {}", address);
+
+    let mut messages = pubsub.on_message();
+    while let Some(msg) = messages.next().await {
+        let (db, event) = match parse_keyevent_channel(msg.get_channel_name()) {
+            Some(parsed) => parsed,
+            None => continue,
+        };
+        let key: String = match msg.get_payload() {
+            Ok(key) => key,
+            Err(_) => continue,
+        };
+        // Sending only fails when nobody is listening, which is fine
+        let _ = EVENTS.send(KeyEvent {
+            event,
+            key,
+            db,
+            node: address.to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        });
+    }
+    Err("Subscription closed".to_string())
+}
+
+// One subscriber task per master, each reconnecting after a delay if its connection drops
+pub fn spawn_keyspace_subscriber() {
+    let flags = get_env_or("KEYSPACE_EVENTS_FLAGS", "E$xe");
+
+    tokio::spawn(async move {
+        let (masters, password) = loop {
+            match tokio::try_join!(redis_master_addresses(), redis_password()) {
+                Ok(found) => break found,
+                Err(e) => {
+                    log::warn!("Keyspace events waiting for Redis: {}", e);
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            }
+        };
+
+        for address in masters {
+            let (password, flags) = (password.clone(), flags.clone());
+            tokio::spawn(async move {
+                loop {
+                    if let Err(e) = subscribe_node(&address, &password, &flags).await {
+                        log::warn!("Keyspace events from {} interrupted: {}", address, e);
+                    }
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            });
+        }
+    });
+}
+
+// ============================================================================
+// SSE endpoint
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct EventsQuery {
+    // Comma-separated event names (e.g. "expired,evicted,set"), or "*" for all
+    events: Option<String>,
+    // Key glob, e.g. "cache:*"
+    pattern: Option<String>,
+    // Per-client buffer size and overflow policy; default to STREAM_BUFFER_CAPACITY / STREAM_OVERFLOW_POLICY
+    buffer: Option<usize>,
+    overflow: Option<String>,
+}
+
+fn sse_frame(event: &str, data: &serde_json::Value) -> web::Bytes {
+    web::Bytes::from(format!("event: {}\ndata: {}\n\n", event, data))
+}
+
+pub async fn stream_events(query: web::Query<EventsQuery>) -> impl Responder {
+    if !is_enabled() {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "status": "disabled",
+            "error": "Keyspace events are disabled; set KEYSPACE_EVENTS_ENABLED=true"
+        }));
+    }
+
+    let config = match BufferConfig::from_env().with_overrides(query.buffer, query.overflow.as_deref()) {
+        Ok(config) => config,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "status": "error", "error": e })),
+    };
+    let heartbeat = keepalive::KeepaliveConfig::from_env().stream_heartbeat();
+    let filter = EventFilter::new(query.events.as_deref(), query.pattern.as_deref());
+    let hello = sse_frame(
+        "subscribed",
+        &serde_json::json!({
+            "events": filter.events,
+            "pattern": filter.pattern,
+            "buffer": config.capacity,
+            "overflow": config.policy.as_str()
+        }),
+    );
+
+    // The forwarder drains the broadcast channel at full speed; only this client's buffer fills up
+    let (sender, receiver) = stream_buffer::channel("keyspace_events", config);
+    let mut events_rx = EVENTS.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let event = match tokio::time::timeout(heartbeat, events_rx.recv()).await {
+                Ok(Ok(event)) => event,
+                Ok(Err(broadcast::error::RecvError::Lagged(skipped))) => {
+                    sender.note_dropped(skipped);
+                    continue;
+                }
+                Ok(Err(broadcast::error::RecvError::Closed)) => return,
+                // Notice a departed client even when no events arrive
+                Err(_) if sender.is_closed() => return,
+                Err(_) => continue,
+            };
+            if filter.matches(&event) && sender.send(event).is_err() {
+                return;
+            }
+        }
+    });
+
+    let events = futures_util::stream::unfold(Some(receiver), move |receiver| async move {
+        let mut receiver = receiver?;
+        let frame = match tokio::time::timeout(heartbeat, receiver.recv()).await {
+            Ok(Some(Received::Item(event))) => sse_frame(&event.event, &serde_json::json!(event)),
+            // A slow client misses events rather than growing its buffer
+            Ok(Some(Received::Dropped(skipped))) => sse_frame("lagged", &serde_json::json!({ "skipped": skipped })),
+            Ok(Some(Received::Disconnected)) => {
+                let frame = sse_frame("overflow", &serde_json::json!({ "error": "Client too slow; buffer overflowed" }));
+                return Some((Ok::<_, std::io::Error>(frame), None));
+            }
+            Ok(None) => return None,
+            // Comment line so proxies don't time out an idle stream
+            Err(_) => web::Bytes::from_static(b": keep-alive\n\n"),
+        };
+        Some((Ok::<_, std::io::Error>(frame), Some(receiver)))
+    });
+    let body = futures_util::stream::once(async move { Ok::<_, std::io::Error>(hello) }).chain(events);
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(body)
+}
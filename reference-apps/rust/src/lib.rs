@@ -0,0 +1,2076 @@
+// devstack-reference: the reusable half of the Rust reference API
+//
+// Other Rust services in the stack depend on this crate for the same Vault-backed clients the
+// API uses (`clients`), the request/response and Redis topology types (`models`) and the
+// service health checks (`health`). The handlers are here too: `routes` mounts all of them, and
+// the devstack-core-rust-api binary (main.rs) only adds startup, middleware and listeners.
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use std::env;
+use lazy_static::lazy_static;
+use prometheus::{Encoder, TextEncoder, HistogramVec, CounterVec, Opts, Registry};
+#[cfg(feature = "mysql")]
+use mysql_async::prelude::Queryable;
+
+pub mod admin_auth;
+pub mod advisory_lock;
+pub mod audit;
+pub mod bench;
+pub mod blobs;
+pub mod bootstrap;
+pub mod build_info;
+pub mod cache;
+pub mod cache_body;
+pub mod cache_strategies;
+pub mod cloudevents;
+pub mod compression;
+pub mod concurrency;
+pub mod console;
+#[cfg(all(feature = "mysql", feature = "mongodb"))]
+pub mod consistency;
+#[cfg(feature = "rabbitmq")]
+pub mod consumers;
+pub mod dashboard;
+#[cfg(feature = "mongodb")]
+pub mod downloads;
+#[cfg(feature = "rabbitmq")]
+pub mod dual_write;
+pub mod envelope;
+pub mod etag;
+pub mod event_sourcing;
+pub mod feature_flags;
+pub mod geo;
+pub mod grafana;
+pub mod health;
+pub mod hedging;
+#[cfg(feature = "rabbitmq")]
+pub mod inbox;
+pub mod instances;
+pub mod keepalive;
+pub mod keyspace_events;
+pub mod listeners;
+pub mod loki;
+pub mod lua_scripts;
+#[cfg(feature = "rabbitmq")]
+pub mod message_archive;
+#[cfg(feature = "rabbitmq")]
+pub mod message_codec;
+#[cfg(feature = "mongodb")]
+pub mod mongodb_examples;
+#[cfg(feature = "mysql")]
+pub mod mysql_examples;
+pub mod pagination;
+pub mod panic_guard;
+pub mod pg_queue;
+pub mod pipeline;
+pub mod pool;
+pub mod probabilistic;
+pub mod postgres_examples;
+pub mod preflight;
+#[cfg(feature = "mongodb")]
+pub mod projector;
+pub mod protocols;
+pub mod query_cache;
+#[cfg(feature = "rabbitmq")]
+pub mod queues;
+pub mod quota;
+pub mod redact;
+pub mod redis_clients;
+pub mod redis_diagnostics;
+pub mod redis_parse;
+pub mod redis_replication;
+#[cfg(feature = "rabbitmq")]
+pub mod relay;
+pub mod request_signing;
+pub mod resharding;
+pub mod resolve;
+pub mod scheduler;
+pub mod schema_registry;
+#[cfg(feature = "mongodb")]
+pub mod search;
+pub mod seed;
+pub mod sentry;
+pub mod services;
+pub mod sharding;
+pub mod slo;
+pub mod sql_router;
+pub mod sql_timing;
+pub mod stmt_cache;
+pub mod storage;
+pub mod stream_buffer;
+#[cfg(feature = "rabbitmq")]
+pub mod streams;
+pub mod tenants;
+pub mod timeseries;
+pub mod timezone;
+pub mod topology;
+pub mod totp;
+pub mod vault;
+pub mod vault_access;
+pub mod wrapping;
+
+use redact::Redacted;
+use vault::get_vault_secret;
+
+// Connections to the stack's services, with credentials from Vault
+pub mod clients {
+    pub use crate::vault::get_vault_secret;
+    pub use crate::{
+        get_env_or, postgres_client, postgres_connect, postgres_connect_at, postgres_replica_client, redis_connection,
+        redis_master_addresses, redis_node_connection, redis_password, PostgresConnection,
+    };
+    #[cfg(feature = "mysql")]
+    pub use crate::{mysql_connection, mysql_replica_connection};
+    #[cfg(feature = "mongodb")]
+    pub use crate::mongodb_client;
+    #[cfg(feature = "rabbitmq")]
+    pub use crate::amqp_connection;
+}
+
+// Wire types shared with the CLI and dashboard
+pub mod models {
+    pub use crate::topology::*;
+    pub use crate::{
+        AllHealthResponse, CacheResponse, CacheSetRequest, ClusterInfoResponse, ClusterNodesResponse,
+        ClusterSlotsResponse, DatabaseQueryResponse, HealthResponse, MessagingResponse, VaultSecret,
+    };
+    #[cfg(feature = "rabbitmq")]
+    pub use crate::PublishMessageRequest;
+}
+
+// Response types
+#[derive(Serialize, Deserialize)]
+pub struct ApiInfo {
+    pub name: String,
+    pub version: String,
+    pub language: String,
+    pub framework: String,
+    pub description: String,
+    pub docs: String,
+    pub health: String,
+    pub metrics: String,
+    pub dashboard: String,
+    pub build: build_info::BuildInfo,
+    pub redis_cluster: RedisClusterEndpoints,
+    pub examples: ExampleEndpoints,
+    pub note: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RedisClusterEndpoints {
+    pub nodes: String,
+    pub slots: String,
+    pub info: String,
+    pub node_info: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExampleEndpoints {
+    pub vault: String,
+    pub databases: String,
+    pub cache: String,
+    pub messaging: String,
+    pub pipeline: String,
+    pub patterns: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HealthResponse {
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AllHealthResponse {
+    pub status: String,
+    pub services: serde_json::Map<String, serde_json::Value>,
+    #[serde(default)]
+    pub critical_failures: Vec<String>,
+    #[serde(default)]
+    pub optional_failures: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct VaultSecret {
+    pub service: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DatabaseQueryResponse {
+    pub status: String,
+    pub database: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CacheResponse {
+    pub status: String,
+    pub key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CacheSetRequest {
+    pub value: String,
+    #[serde(default)]
+    pub ttl: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MessagingResponse {
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[cfg(feature = "rabbitmq")]
+#[derive(Deserialize)]
+pub struct PublishMessageRequest {
+    pub message: String,
+    // text (default), protobuf or avro
+    #[serde(default)]
+    pub encoding: Option<String>,
+    // Registered JSON Schema the message must satisfy
+    #[serde(default)]
+    pub schema: Option<String>,
+    // Wrap the message in a CloudEvents 1.0 JSON envelope
+    #[serde(default)]
+    pub cloudevent: Option<cloudevents::CloudEventOptions>,
+    // Arguments used if this publish creates the queue
+    #[serde(default)]
+    pub queue_options: queues::QueueOptions,
+    #[serde(default)]
+    pub priority: Option<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ClusterNodesResponse {
+    pub status: String,
+    pub total_nodes: usize,
+    pub nodes: Vec<topology::ClusterNode>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ClusterSlotsResponse {
+    pub status: String,
+    pub total_slots: usize,
+    pub max_slots: u16,
+    pub coverage_percentage: f64,
+    pub slot_distribution: Vec<topology::SlotAssignment>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ClusterInfoResponse {
+    pub status: String,
+    pub cluster_info: topology::ClusterInfo,
+}
+
+// Prometheus metrics
+lazy_static! {
+    static ref REGISTRY: Registry = Registry::new();
+
+    static ref HTTP_REQUESTS_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("http_requests_total", "Total HTTP requests"),
+        &["method", "endpoint", "status"]
+    ).expect("Failed to create HTTP_REQUESTS_TOTAL metric");
+
+    static ref HTTP_REQUEST_DURATION: HistogramVec = HistogramVec::new(
+        prometheus::HistogramOpts::new("http_request_duration_seconds", "HTTP request latency"),
+        &["method", "endpoint"]
+    ).expect("Failed to create HTTP_REQUEST_DURATION metric");
+
+    static ref CACHE_SINGLEFLIGHT_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("cache_singleflight_requests_total", "Cache-aside misses by single-flight role (leader loads, coalesced waits)"),
+        &["role"]
+    ).expect("Failed to create CACHE_SINGLEFLIGHT_TOTAL metric");
+
+    static ref CACHE_COMPRESSION_RATIO: prometheus::Histogram = prometheus::Histogram::with_opts(
+        prometheus::HistogramOpts::new("cache_compression_ratio", "Original/stored size of compressed cache values")
+            .buckets(vec![1.0, 1.5, 2.0, 3.0, 5.0, 10.0, 20.0, 50.0])
+    ).expect("Failed to create CACHE_COMPRESSION_RATIO metric");
+
+    static ref CACHE_COMPRESSION_DURATION: HistogramVec = HistogramVec::new(
+        prometheus::HistogramOpts::new("cache_compression_duration_seconds", "CPU time spent compressing and decompressing cache values")
+            .buckets(vec![0.00001, 0.00005, 0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05]),
+        &["operation"]
+    ).expect("Failed to create CACHE_COMPRESSION_DURATION metric");
+
+    static ref CACHE_COMPRESSION_BYTES_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("cache_compression_bytes_total", "Bytes of compressed cache values before (original) and after (stored) compression"),
+        &["kind"]
+    ).expect("Failed to create CACHE_COMPRESSION_BYTES_TOTAL metric");
+
+    static ref STREAM_DROPPED_EVENTS_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("stream_dropped_events_total", "Events dropped from per-client streaming buffers"),
+        &["endpoint", "policy"]
+    ).expect("Failed to create STREAM_DROPPED_EVENTS_TOTAL metric");
+
+    static ref CACHE_EARLY_REFRESH_TOTAL: prometheus::IntCounter = prometheus::IntCounter::new(
+        "cache_early_refresh_total", "Cache-aside entries recomputed early by probabilistic expiration"
+    ).expect("Failed to create CACHE_EARLY_REFRESH_TOTAL metric");
+
+    static ref SQL_CACHE_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("sql_cache_requests_total", "Cached Postgres reads by query and result (hit, miss, bypass)"),
+        &["query", "result"]
+    ).expect("Failed to create SQL_CACHE_TOTAL metric");
+
+    static ref SQL_CACHE_INVALIDATIONS_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("sql_cache_invalidations_total", "SQL result cache generation bumps by table and source"),
+        &["table", "source"]
+    ).expect("Failed to create SQL_CACHE_INVALIDATIONS_TOTAL metric");
+
+    static ref SQL_ROUTE_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("sql_route_total", "SQL connection checkouts by backend and route (primary, replica, fallback)"),
+        &["backend", "route"]
+    ).expect("Failed to create SQL_ROUTE_TOTAL metric");
+
+    static ref VAULT_REQUESTS_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("vault_requests_total", "Total Vault API requests"),
+        &["operation", "status"]
+    ).expect("Failed to create VAULT_REQUESTS_TOTAL metric");
+
+    static ref VAULT_REQUEST_DURATION: HistogramVec = HistogramVec::new(
+        prometheus::HistogramOpts::new("vault_request_duration_seconds", "Vault API request latency"),
+        &["operation"]
+    ).expect("Failed to create VAULT_REQUEST_DURATION metric");
+
+    static ref VAULT_SECRET_CACHE_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("vault_secret_cache_requests_total", "Vault secret cache lookups by result (hit/miss)"),
+        &["result"]
+    ).expect("Failed to create VAULT_SECRET_CACHE_TOTAL metric");
+
+    static ref SQL_QUERY_DURATION: HistogramVec = HistogramVec::new(
+        prometheus::HistogramOpts::new("sql_query_duration_seconds", "SQL statement latency for the database examples"),
+        &["database", "query_name"]
+    ).expect("Failed to create SQL_QUERY_DURATION metric");
+
+    static ref VAULT_TOKEN_TTL: prometheus::IntGauge = prometheus::IntGauge::new(
+        "vault_token_ttl_seconds", "Remaining TTL of the application's Vault token"
+    ).expect("Failed to create VAULT_TOKEN_TTL metric");
+
+    static ref STACK_SERVICE_UP: prometheus::IntGaugeVec = prometheus::IntGaugeVec::new(
+        Opts::new("stack_service_up", "Whether the background health check last found the service healthy (1) or not (0)"),
+        &["service"]
+    ).expect("Failed to create STACK_SERVICE_UP metric");
+
+    static ref STACK_SERVICE_CHECK_DURATION: prometheus::GaugeVec = prometheus::GaugeVec::new(
+        Opts::new("stack_service_check_duration_seconds", "Duration of the last background health check per service"),
+        &["service"]
+    ).expect("Failed to create STACK_SERVICE_CHECK_DURATION metric");
+
+    static ref CLUSTER_INSTANCES: prometheus::IntGauge = prometheus::IntGauge::new(
+        "cluster_instances", "Live app replicas in the Redis instance registry, as seen by this replica"
+    ).expect("Failed to create CLUSTER_INSTANCES metric");
+
+    static ref BACKEND_INFLIGHT: prometheus::IntGaugeVec = prometheus::IntGaugeVec::new(
+        Opts::new("backend_inflight_requests", "Requests currently holding a backend concurrency permit"),
+        &["backend"]
+    ).expect("Failed to create BACKEND_INFLIGHT metric");
+
+    static ref BACKEND_REJECTED_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("backend_rejected_requests_total", "Requests rejected because the backend concurrency limit was reached"),
+        &["backend"]
+    ).expect("Failed to create BACKEND_REJECTED_TOTAL metric");
+
+    static ref BACKEND_CONCURRENCY_LIMIT: prometheus::IntGaugeVec = prometheus::IntGaugeVec::new(
+        Opts::new("backend_concurrency_limit", "Per-backend concurrency limit, adjusted by the adaptive limiter"),
+        &["backend"]
+    ).expect("Failed to create BACKEND_CONCURRENCY_LIMIT metric");
+
+    static ref HEDGED_REQUESTS_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("hedged_requests_total", "Hedgeable reads by result (not_hedged/primary_won/hedge_won/both_failed)"),
+        &["operation", "result"]
+    ).expect("Failed to create HEDGED_REQUESTS_TOTAL metric");
+
+    static ref PANICS_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("panics_total", "Handler panics turned into 500 responses by the catch-panic middleware"),
+        &["route"]
+    ).expect("Failed to create PANICS_TOTAL metric");
+
+    static ref QUOTA_DECISIONS_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("quota_decisions_total", "Token bucket quota checks by quota and result (allowed/denied)"),
+        &["quota", "result"]
+    ).expect("Failed to create QUOTA_DECISIONS_TOTAL metric");
+
+    static ref PREPARED_STATEMENT_CACHE_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("prepared_statement_cache_total", "Prepared statement cache lookups by result (hit/miss/eviction)"),
+        &["backend", "result"]
+    ).expect("Failed to create PREPARED_STATEMENT_CACHE_TOTAL metric");
+
+    static ref TENANT_POOLS: prometheus::IntGauge = prometheus::IntGauge::new(
+        "tenant_pools", "Per-tenant Postgres pools currently open"
+    ).expect("Failed to create TENANT_POOLS metric");
+
+    static ref TENANT_POOL_EVICTIONS_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("tenant_pool_evictions_total", "Per-tenant Postgres pools dropped, by reason (idle/capacity/manual)"),
+        &["reason"]
+    ).expect("Failed to create TENANT_POOL_EVICTIONS_TOTAL metric");
+
+    static ref ES_PROJECTION_LAG_EVENTS: prometheus::IntGauge = prometheus::IntGauge::new(
+        "es_projection_lag_events", "Events in the es_events log not yet projected into the MongoDB read model"
+    ).expect("Failed to create ES_PROJECTION_LAG_EVENTS metric");
+
+    static ref ES_PROJECTION_LAG_SECONDS: prometheus::Gauge = prometheus::Gauge::new(
+        "es_projection_lag_seconds", "Age of the oldest event not yet projected into the MongoDB read model"
+    ).expect("Failed to create ES_PROJECTION_LAG_SECONDS metric");
+
+    static ref SLO_REQUESTS_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("slo_requests_total", "Requests to routes with an SLO, by result (good/error/slow)"),
+        &["route", "result"]
+    ).expect("Failed to create SLO_REQUESTS_TOTAL metric");
+
+    static ref SLO_BURN_RATE: prometheus::GaugeVec = prometheus::GaugeVec::new(
+        Opts::new("slo_error_budget_burn_rate", "Error budget burn rate per SLO route over a trailing window"),
+        &["route", "window"]
+    ).expect("Failed to create SLO_BURN_RATE metric");
+
+    static ref SLO_BUDGET_REMAINING: prometheus::GaugeVec = prometheus::GaugeVec::new(
+        Opts::new("slo_error_budget_remaining", "Fraction of the error budget left since the process started"),
+        &["route"]
+    ).expect("Failed to create SLO_BUDGET_REMAINING metric");
+}
+
+// Every collector the app exports, in registration order
+fn collectors() -> Vec<Box<dyn prometheus::core::Collector>> {
+    vec![
+        Box::new(HTTP_REQUESTS_TOTAL.clone()),
+        Box::new(HTTP_REQUEST_DURATION.clone()),
+        Box::new(CACHE_SINGLEFLIGHT_TOTAL.clone()),
+        Box::new(CACHE_EARLY_REFRESH_TOTAL.clone()),
+        Box::new(SQL_CACHE_TOTAL.clone()),
+        Box::new(SQL_CACHE_INVALIDATIONS_TOTAL.clone()),
+        Box::new(SQL_ROUTE_TOTAL.clone()),
+        Box::new(VAULT_REQUESTS_TOTAL.clone()),
+        Box::new(VAULT_REQUEST_DURATION.clone()),
+        Box::new(VAULT_SECRET_CACHE_TOTAL.clone()),
+        Box::new(VAULT_TOKEN_TTL.clone()),
+        Box::new(SQL_QUERY_DURATION.clone()),
+        Box::new(BACKEND_INFLIGHT.clone()),
+        Box::new(BACKEND_REJECTED_TOTAL.clone()),
+        Box::new(BACKEND_CONCURRENCY_LIMIT.clone()),
+        Box::new(HEDGED_REQUESTS_TOTAL.clone()),
+        Box::new(PREPARED_STATEMENT_CACHE_TOTAL.clone()),
+        Box::new(TENANT_POOLS.clone()),
+        Box::new(TENANT_POOL_EVICTIONS_TOTAL.clone()),
+        Box::new(CACHE_COMPRESSION_RATIO.clone()),
+        Box::new(CACHE_COMPRESSION_DURATION.clone()),
+        Box::new(CACHE_COMPRESSION_BYTES_TOTAL.clone()),
+        Box::new(STREAM_DROPPED_EVENTS_TOTAL.clone()),
+        Box::new(STACK_SERVICE_UP.clone()),
+        Box::new(CLUSTER_INSTANCES.clone()),
+        Box::new(STACK_SERVICE_CHECK_DURATION.clone()),
+        Box::new(PANICS_TOTAL.clone()),
+        Box::new(QUOTA_DECISIONS_TOTAL.clone()),
+        Box::new(ES_PROJECTION_LAG_EVENTS.clone()),
+        Box::new(ES_PROJECTION_LAG_SECONDS.clone()),
+        Box::new(SLO_REQUESTS_TOTAL.clone()),
+        Box::new(SLO_BURN_RATE.clone()),
+        Box::new(SLO_BUDGET_REMAINING.clone()),
+    ]
+}
+
+pub fn register_metrics() {
+    for collector in collectors() {
+        REGISTRY.register(collector).ok();
+    }
+}
+
+// (name, label names) of every exported metric, whether or not it has been observed yet
+pub fn metric_descriptions() -> Vec<(String, Vec<String>)> {
+    collectors()
+        .iter()
+        .flat_map(|collector| {
+            collector
+                .desc()
+                .into_iter()
+                .map(|desc| (desc.fq_name.clone(), desc.variable_labels.clone()))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+// Helper functions
+pub fn get_env_or(key: &str, default: &str) -> String {
+    env::var(key).unwrap_or_else(|_| default.to_string())
+}
+
+pub async fn redis_password() -> Result<Redacted<String>, String> {
+    let creds = get_vault_secret("redis-1").await?;
+    Ok(Redacted::new(creds["password"].as_str().unwrap_or("").to_string()))
+}
+
+// Connect to a specific Redis node ("host:port")
+pub async fn redis_node_connection(address: &str) -> Result<redis::aio::MultiplexedConnection, String> {
+    let password = redis_password().await?;
+    let url = Redacted::new(format!("redis://:{}@{}", password.expose(), resolve::address(address)));
+
+    let client =
+        redis::Client::open(url.expose().as_str()).map_err(|e| format!("Client creation failed: {}", e))?;
+    client
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(|e| format!("Connection failed: {}", e))
+}
+
+pub async fn redis_connection() -> Result<redis::aio::MultiplexedConnection, String> {
+    let host = get_env_or("REDIS_HOST", "redis-1");
+    let port = get_env_or("REDIS_PORT", "6379");
+    redis_node_connection(&format!("{}:{}", host, port)).await
+}
+
+// Addresses ("host:port") of all cluster masters, or the configured node when cluster mode is off
+pub async fn redis_master_addresses() -> Result<Vec<String>, String> {
+    let mut conn = redis_connection().await?;
+    match redis::cmd("CLUSTER").arg("NODES").query_async::<String>(&mut conn).await {
+        Ok(nodes_raw) => Ok(redis_parse::parse_cluster_nodes(&nodes_raw)
+            .into_iter()
+            .filter(|node| node.is_master() && !node.is_failed())
+            .map(|node| node.address())
+            .collect()),
+        Err(_) => Ok(vec![format!(
+            "{}:{}",
+            get_env_or("REDIS_HOST", "redis-1"),
+            get_env_or("REDIS_PORT", "6379")
+        )]),
+    }
+}
+
+pub type PostgresConnection = tokio_postgres::Connection<tokio_postgres::Socket, tokio_postgres::tls::NoTlsStream>;
+
+// Client plus its connection, for callers that drive the connection themselves (LISTEN/NOTIFY)
+pub async fn postgres_connect() -> Result<(tokio_postgres::Client, PostgresConnection), String> {
+    let host = get_env_or("POSTGRES_HOST", "postgres");
+    let port = get_env_or("POSTGRES_PORT", "5432");
+    postgres_connect_at(&host, &port).await
+}
+
+pub async fn postgres_connect_at(
+    host: &str,
+    port: &str,
+) -> Result<(tokio_postgres::Client, PostgresConnection), String> {
+    postgres_connect_as(host, port, "postgres").await
+}
+
+// Connects with the credentials of the Vault secret `secret` (e.g. a tenant's `acme/postgres`)
+pub async fn postgres_connect_as(
+    host: &str,
+    port: &str,
+    secret: &str,
+) -> Result<(tokio_postgres::Client, PostgresConnection), String> {
+    #[cfg(not(unix))]
+    if host.starts_with('/') {
+        return Err(format!("Unix socket host {} is not supported on this platform", host));
+    }
+    let port: u16 = port.parse().map_err(|_| format!("Invalid PostgreSQL port: {}", port))?;
+    let (host, port) = resolve::connect_target(host, port).await;
+    let creds = get_vault_secret(secret).await?;
+
+    let user = creds["user"].as_str().unwrap_or("devuser");
+    let password = Redacted::new(creds["password"].as_str().unwrap_or("").to_string());
+    let database = creds["database"].as_str().unwrap_or("devdb");
+
+    let conn_str = Redacted::new(format!(
+        "host={} port={} user={} password={} dbname={}{}",
+        host,
+        port,
+        user,
+        password.expose(),
+        database,
+        keepalive::KeepaliveConfig::from_env().postgres_params()
+    ));
+
+    tokio_postgres::connect(conn_str.expose(), tokio_postgres::NoTls)
+        .await
+        .map_err(|e| format!("Connection failed: {}", e))
+}
+
+pub fn spawn_postgres_connection(connection: PostgresConnection) {
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            log::error!("PostgreSQL connection error: {}", e);
+        }
+    });
+}
+
+pub async fn postgres_client() -> Result<tokio_postgres::Client, String> {
+    let (client, connection) = postgres_connect().await?;
+    spawn_postgres_connection(connection);
+    Ok(client)
+}
+
+// A read replica at "host[:port]"; replicas share the primary's credentials
+pub async fn postgres_replica_client(address: &str) -> Result<tokio_postgres::Client, String> {
+    let (host, port) = resolve::split_host_port(address);
+    let (client, connection) = postgres_connect_at(host, port.unwrap_or("5432")).await?;
+    spawn_postgres_connection(connection);
+    Ok(client)
+}
+
+#[cfg(feature = "mysql")]
+pub async fn mysql_opts() -> Result<mysql_async::OptsBuilder, String> {
+    let creds = get_vault_secret("mysql").await?;
+
+    let port: u16 = get_env_or("MYSQL_PORT", "3306").parse().unwrap_or(3306);
+    let (host, port) = resolve::connect_target(&get_env_or("MYSQL_HOST", "mysql"), port).await;
+    let user = creds["user"].as_str().unwrap_or("devuser");
+    let password = Redacted::new(creds["password"].as_str().unwrap_or("").to_string());
+    let database = creds["database"].as_str().unwrap_or("devdb");
+
+    Ok(mysql_async::OptsBuilder::default()
+        .ip_or_hostname(host)
+        .tcp_port(port)
+        .user(Some(user))
+        .pass(Some(password.expose().as_str()))
+        .db_name(Some(database)))
+}
+
+#[cfg(feature = "mysql")]
+pub async fn mysql_connection() -> Result<mysql_async::Conn, String> {
+    mysql_async::Conn::new(mysql_opts().await?)
+        .await
+        .map_err(|e| format!("Connection failed: {}", e))
+}
+
+#[cfg(feature = "mysql")]
+pub async fn mysql_replica_connection(address: &str) -> Result<mysql_async::Conn, String> {
+    let (host, port) = match resolve::split_host_port(address) {
+        (host, Some(port)) => (host, port.parse::<u16>().map_err(|_| format!("Invalid replica address: {}", address))?),
+        (host, None) => (host, 3306),
+    };
+    let (host, port) = resolve::connect_target(host, port).await;
+    mysql_async::Conn::new(mysql_opts().await?.ip_or_hostname(host).tcp_port(port))
+        .await
+        .map_err(|e| format!("Connection failed: {}", e))
+}
+
+#[cfg(feature = "mongodb")]
+pub async fn mongodb_client() -> Result<mongodb::Client, String> {
+    let creds = get_vault_secret("mongodb").await?;
+
+    // The driver resolves (and, for replica sets, rediscovers) hosts itself, so only overrides apply
+    let address = resolve::env_address("MONGODB_HOST", "mongodb", "MONGODB_PORT", "27017");
+    let user = creds["user"].as_str().unwrap_or("devuser");
+    let password = Redacted::new(creds["password"].as_str().unwrap_or("").to_string());
+
+    let uri = Redacted::new(format!("mongodb://{}:{}@{}/?authSource=admin", user, password.expose(), address));
+
+    mongodb::Client::with_uri_str(uri.expose())
+        .await
+        .map_err(|e| format!("Connection failed: {}", e))
+}
+
+#[cfg(feature = "rabbitmq")]
+pub async fn amqp_connection() -> Result<lapin::Connection, String> {
+    let creds = get_vault_secret("rabbitmq").await?;
+
+    let address = resolve::env_address("RABBITMQ_HOST", "rabbitmq", "RABBITMQ_PORT", "5672");
+    let user = creds["user"].as_str().unwrap_or("devuser");
+    let password = Redacted::new(creds["password"].as_str().unwrap_or("").to_string());
+    let vhost = creds["vhost"].as_str().unwrap_or("dev_vhost");
+
+    let url = Redacted::new(format!(
+        "amqp://{}:{}@{}/{}?{}",
+        user,
+        password.expose(),
+        address,
+        vhost,
+        keepalive::KeepaliveConfig::from_env().amqp_query()
+    ));
+
+    lapin::Connection::connect(url.expose(), lapin::ConnectionProperties::default())
+        .await
+        .map_err(|e| format!("Connection failed: {}", e))
+}
+
+// Route handlers
+async fn root() -> impl Responder {
+    let info = ApiInfo {
+        name: "DevStack Core Reference API".to_string(),
+        version: "1.1.0".to_string(),
+        language: "Rust".to_string(),
+        framework: "Actix-web".to_string(),
+        description: "Rust reference implementation for infrastructure integration".to_string(),
+        docs: "/docs".to_string(),
+        health: "/health/all".to_string(),
+        metrics: "/metrics".to_string(),
+        dashboard: "/ui".to_string(),
+        build: build_info::build_info(),
+        redis_cluster: RedisClusterEndpoints {
+            nodes: "/redis/cluster/nodes".to_string(),
+            slots: "/redis/cluster/slots".to_string(),
+            info: "/redis/cluster/info".to_string(),
+            node_info: "/redis/nodes/{node_name}/info".to_string(),
+        },
+        examples: ExampleEndpoints {
+            vault: "/examples/vault".to_string(),
+            databases: "/examples/database".to_string(),
+            cache: "/examples/cache".to_string(),
+            messaging: "/examples/messaging".to_string(),
+            pipeline: "/examples/pipeline".to_string(),
+            patterns: "/examples/patterns".to_string(),
+        },
+        note: "This is a reference implementation, not production code".to_string(),
+    };
+    HttpResponse::Ok().json(info)
+}
+
+// Runtime details: compiled-in backends, connection pool settings, usage, and startup warm-up results
+async fn info() -> impl Responder {
+    let compiled: Vec<&str> = services::BACKENDS.iter().copied().filter(|s| services::is_compiled(s)).collect();
+    #[allow(unused_mut)]
+    let mut pools = serde_json::json!({ "postgres": pool::POSTGRES_POOL.info() });
+    #[cfg(feature = "mysql")]
+    {
+        pools["mysql"] = pool::MYSQL_POOL.info();
+    }
+    HttpResponse::Ok().json(serde_json::json!({
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "listeners": listeners::bound(),
+        "config_bootstrap": bootstrap::report(),
+        "tokio_console": console::status(),
+        "loki": loki::status(),
+        "error_reporting": sentry::status(),
+        "vault_degradation": vault::degradation_status(),
+        "compiled_backends": compiled,
+        "pools": pools
+    }))
+}
+
+// Health check handlers
+async fn health_simple() -> impl Responder {
+    let response = HealthResponse {
+        status: "healthy".to_string(),
+        timestamp: Some(chrono::Utc::now().to_rfc3339()),
+        version: None,
+        error: None,
+        details: None,
+    };
+    HttpResponse::Ok().json(response)
+}
+
+// Vault example handlers
+async fn get_secret(path: web::Path<String>) -> impl Responder {
+    let service_name = path.into_inner();
+
+    match get_vault_secret(&service_name).await {
+        Ok(data) => HttpResponse::Ok().json(VaultSecret {
+            service: service_name,
+            key: None,
+            value: Some(data),
+            error: None,
+        }),
+        Err(e) => HttpResponse::ServiceUnavailable().json(VaultSecret {
+            service: service_name,
+            key: None,
+            value: None,
+            error: Some(e),
+        }),
+    }
+}
+
+async fn get_secret_key(path: web::Path<(String, String)>) -> impl Responder {
+    let (service_name, key) = path.into_inner();
+
+    match get_vault_secret(&service_name).await {
+        Ok(data) => {
+            if let Some(value) = data.get(&key) {
+                HttpResponse::Ok().json(VaultSecret {
+                    service: service_name,
+                    key: Some(key),
+                    value: Some(value.clone()),
+                    error: None,
+                })
+            } else {
+                HttpResponse::NotFound().json(VaultSecret {
+                    service: service_name,
+                    key: Some(key),
+                    value: None,
+                    error: Some("Key not found".to_string()),
+                })
+            }
+        }
+        Err(e) => HttpResponse::ServiceUnavailable().json(VaultSecret {
+            service: service_name,
+            key: Some(key),
+            value: None,
+            error: Some(e),
+        }),
+    }
+}
+
+// Database example handlers
+async fn postgres_query() -> impl Responder {
+    let client = match pool::postgres().await {
+        Ok(client) => client,
+        Err(e) => {
+            return HttpResponse::ServiceUnavailable().json(DatabaseQueryResponse {
+                status: "error".to_string(),
+                database: "PostgreSQL".to_string(),
+                result: None,
+                error: Some(e),
+            })
+        }
+    };
+
+    let sql = "SELECT NOW()::text, 'Hello from PostgreSQL!' as message";
+    match sql_timing::timed_query("postgres", "hello_query", sql, 0, client.query_one(sql, &[])).await {
+        Ok(row) => {
+            let timestamp: String = row.get(0);
+            let message: String = row.get(1);
+
+            HttpResponse::Ok().json(DatabaseQueryResponse {
+                status: "success".to_string(),
+                database: "PostgreSQL".to_string(),
+                result: Some(serde_json::json!({
+                    "timestamp": timestamp,
+                    "message": message
+                })),
+                error: None,
+            })
+        }
+        Err(e) => {
+            client.discard();
+            HttpResponse::InternalServerError().json(DatabaseQueryResponse {
+                status: "error".to_string(),
+                database: "PostgreSQL".to_string(),
+                result: None,
+                error: Some(format!("Query failed: {}", e)),
+            })
+        }
+    }
+}
+
+#[cfg(feature = "mysql")]
+async fn mysql_query() -> impl Responder {
+    let mut conn = match pool::mysql().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            return HttpResponse::ServiceUnavailable().json(DatabaseQueryResponse {
+                status: "error".to_string(),
+                database: "MySQL".to_string(),
+                result: None,
+                error: Some(e),
+            })
+        }
+    };
+
+    let sql = "SELECT NOW(), 'Hello from MySQL!' as message";
+    match sql_timing::timed_query("mysql", "hello_query", sql, 0, conn.query_first::<(String, String), _>(sql)).await {
+        Ok(Some((timestamp, message))) => HttpResponse::Ok().json(DatabaseQueryResponse {
+            status: "success".to_string(),
+            database: "MySQL".to_string(),
+            result: Some(serde_json::json!({
+                "timestamp": timestamp,
+                "message": message
+            })),
+            error: None,
+        }),
+        Ok(None) => HttpResponse::InternalServerError().json(DatabaseQueryResponse {
+            status: "error".to_string(),
+            database: "MySQL".to_string(),
+            result: None,
+            error: Some("No result returned".to_string()),
+        }),
+        Err(e) => {
+            conn.discard();
+            HttpResponse::InternalServerError().json(DatabaseQueryResponse {
+                status: "error".to_string(),
+                database: "MySQL".to_string(),
+                result: None,
+                error: Some(format!("Query failed: {}", e)),
+            })
+        }
+    }
+}
+
+#[cfg(feature = "mongodb")]
+async fn mongodb_query() -> impl Responder {
+    match get_vault_secret("mongodb").await {
+        Ok(creds) => {
+            let address = resolve::env_address("MONGODB_HOST", "mongodb", "MONGODB_PORT", "27017");
+            let user = creds["user"].as_str().unwrap_or("devuser");
+            let password = Redacted::new(creds["password"].as_str().unwrap_or("").to_string());
+
+            let uri =
+                Redacted::new(format!("mongodb://{}:{}@{}/?authSource=admin", user, password.expose(), address));
+
+            match mongodb::Client::with_uri_str(uri.expose()).await {
+                Ok(client) => {
+                    let db = client.database("test");
+                    let collection = db.collection::<mongodb::bson::Document>("test");
+
+                    let doc = mongodb::bson::doc! {
+                        "message": "Hello from MongoDB!",
+                        "timestamp": chrono::Utc::now().to_rfc3339()
+                    };
+
+                    match collection.insert_one(doc.clone()).await {
+                        Ok(_) => {
+                            HttpResponse::Ok().json(DatabaseQueryResponse {
+                                status: "success".to_string(),
+                                database: "MongoDB".to_string(),
+                                result: Some(serde_json::json!({
+                                    "message": doc.get_str("message").unwrap_or("Unknown message"),
+                                    "timestamp": doc.get_str("timestamp").unwrap_or("Unknown timestamp")
+                                })),
+                                error: None,
+                            })
+                        }
+                        Err(e) => HttpResponse::InternalServerError().json(DatabaseQueryResponse {
+                            status: "error".to_string(),
+                            database: "MongoDB".to_string(),
+                            result: None,
+                            error: Some(format!("Insert failed: {}", e)),
+                        }),
+                    }
+                }
+                Err(e) => HttpResponse::InternalServerError().json(DatabaseQueryResponse {
+                    status: "error".to_string(),
+                    database: "MongoDB".to_string(),
+                    result: None,
+                    error: Some(format!("Connection failed: {}", e)),
+                }),
+            }
+        }
+        Err(e) => HttpResponse::ServiceUnavailable().json(DatabaseQueryResponse {
+            status: "error".to_string(),
+            database: "MongoDB".to_string(),
+            result: None,
+            error: Some(e),
+        }),
+    }
+}
+
+// Cache example handlers
+// X-Cache-Encryption header value
+fn encryption_label(encrypted: bool) -> &'static str {
+    if encrypted {
+        "transit-envelope"
+    } else {
+        "none"
+    }
+}
+
+async fn get_cache(req: HttpRequest, path: web::Path<String>, query: web::Query<cache_body::CacheGetQuery>) -> impl Responder {
+    let key = path.into_inner();
+    let format = match cache_body::CacheFormat::parse(query.format.as_deref()) {
+        Ok(format) => format,
+        Err(e) => return HttpResponse::BadRequest().json(CacheResponse {
+            status: "error".to_string(),
+            key,
+            value: None,
+            error: Some(e),
+        }),
+    };
+
+    match get_vault_secret("redis-1").await {
+        Ok(creds) => {
+            let address = resolve::env_address("REDIS_HOST", "redis-1", "REDIS_PORT", "6379");
+            let password = Redacted::new(creds["password"].as_str().unwrap_or("").to_string());
+
+            let url = Redacted::new(format!("redis://:{}@{}", password.expose(), address));
+
+            match redis::Client::open(url.expose().as_str()) {
+                Ok(client) => {
+                    match client.get_multiplexed_async_connection().await {
+                        Ok(mut conn) => {
+                            match redis::cmd("GET").arg(&key).query_async::<Option<Vec<u8>>>(&mut conn).await {
+                                Ok(Some(stored)) => {
+                                    // Tagged on the stored bytes so the If-Match script can compare with redis.sha1hex
+                                    let tag = etag::etag_for(&stored);
+                                    if etag::is_not_modified(&req, &tag) {
+                                        return etag::not_modified(&tag);
+                                    }
+                                    let encrypted = query.encrypted.unwrap_or(false);
+                                    if encrypted != envelope::is_envelope(&stored) {
+                                        let reason = if encrypted {
+                                            "Value is not encrypted; read it without ?encrypted=true"
+                                        } else {
+                                            "Value is envelope-encrypted; read it with ?encrypted=true"
+                                        };
+                                        return HttpResponse::Conflict().json(CacheResponse {
+                                            status: "error".to_string(),
+                                            key,
+                                            value: None,
+                                            error: Some(reason.to_string()),
+                                        });
+                                    }
+                                    let stored = if encrypted {
+                                        match envelope::open(&stored, key.as_bytes()).await {
+                                            Ok(opened) => opened,
+                                            Err(e) => return HttpResponse::build(e.status()).json(CacheResponse {
+                                                status: "error".to_string(),
+                                                key,
+                                                value: None,
+                                                error: Some(e.into_message()),
+                                            }),
+                                        }
+                                    } else {
+                                        stored
+                                    };
+                                    let (bytes, encoding) = match compression::decode(&stored) {
+                                        Ok(decoded) => decoded,
+                                        Err(e) => return HttpResponse::InternalServerError().json(CacheResponse {
+                                            status: "error".to_string(),
+                                            key,
+                                            value: None,
+                                            error: Some(e),
+                                        }),
+                                    };
+                                    let mut response = HttpResponse::Ok();
+                                    response
+                                        .insert_header((actix_web::http::header::ETAG, tag))
+                                        .insert_header(("X-Cache-Encoding", encoding.as_str()))
+                                        .insert_header(("X-Cache-Encryption", encryption_label(encrypted)));
+                                    let value = match format {
+                                        cache_body::CacheFormat::Raw => {
+                                            return response.content_type("application/octet-stream").body(bytes)
+                                        }
+                                        cache_body::CacheFormat::Base64 => cache_body::to_base64(&bytes),
+                                        cache_body::CacheFormat::Json => match String::from_utf8(bytes) {
+                                            Ok(value) => value,
+                                            Err(_) => return HttpResponse::NotAcceptable().json(CacheResponse {
+                                                status: "error".to_string(),
+                                                key,
+                                                value: None,
+                                                error: Some("Value is not valid UTF-8; use ?format=base64 or ?format=raw".to_string()),
+                                            }),
+                                        },
+                                    };
+                                    response.json(CacheResponse {
+                                        status: "found".to_string(),
+                                        key,
+                                        value: Some(value),
+                                        error: None,
+                                    })
+                                }
+                                Ok(None) => HttpResponse::NotFound().json(CacheResponse {
+                                    status: "not_found".to_string(),
+                                    key,
+                                    value: None,
+                                    error: None,
+                                }),
+                                Err(e) => HttpResponse::InternalServerError().json(CacheResponse {
+                                    status: "error".to_string(),
+                                    key,
+                                    value: None,
+                                    error: Some(format!("GET failed: {}", e)),
+                                }),
+                            }
+                        }
+                        Err(e) => HttpResponse::InternalServerError().json(CacheResponse {
+                            status: "error".to_string(),
+                            key,
+                            value: None,
+                            error: Some(format!("Connection failed: {}", e)),
+                        }),
+                    }
+                }
+                Err(e) => HttpResponse::InternalServerError().json(CacheResponse {
+                    status: "error".to_string(),
+                    key,
+                    value: None,
+                    error: Some(format!("Client creation failed: {}", e)),
+                }),
+            }
+        }
+        Err(e) => HttpResponse::ServiceUnavailable().json(CacheResponse {
+            status: "error".to_string(),
+            key,
+            value: None,
+            error: Some(e),
+        }),
+    }
+}
+
+// Compare-and-set for If-Match: the tag check and the write happen atomically inside Redis.
+// Returns 1 when stored, 0 when no tag matches, -1 when the key does not exist.
+const CACHE_CAS_SCRIPT: &str = r#"
+local current = redis.call('GET', KEYS[1])
+if not current then return -1 end
+local tag = redis.sha1hex(current)
+local matched = false
+for i = 3, #ARGV do
+    if ARGV[i] == '*' or ARGV[i] == tag then matched = true end
+end
+if not matched then return 0 end
+local ttl = tonumber(ARGV[2])
+if ttl > 0 then
+    redis.call('SET', KEYS[1], ARGV[1], 'EX', ttl)
+else
+    redis.call('SET', KEYS[1], ARGV[1])
+end
+return 1
+"#;
+
+async fn cache_compare_and_set(
+    conn: &mut redis::aio::MultiplexedConnection,
+    key: &str,
+    value: &[u8],
+    ttl: Option<u64>,
+    tags: &[String],
+) -> redis::RedisResult<i64> {
+    let script = redis::Script::new(CACHE_CAS_SCRIPT);
+    let mut invocation = script.key(key);
+    invocation.arg(value).arg(ttl.unwrap_or(0));
+    for tag in tags {
+        invocation.arg(tag);
+    }
+    invocation.invoke_async(conn).await
+}
+
+async fn set_cache(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<cache_body::CacheSetQuery>,
+    body: web::Bytes,
+) -> impl Responder {
+    let key = path.into_inner();
+    let binary = cache_body::is_octet_stream(&req);
+    let cache_body::CacheSetBody { value, ttl } = match cache_body::parse_set_body(binary, &body, query.ttl) {
+        Ok(parsed) => parsed,
+        Err(e) => return HttpResponse::BadRequest().json(CacheResponse {
+            status: "error".to_string(),
+            key,
+            value: None,
+            error: Some(e),
+        }),
+    };
+    let (stored, encoding) = compression::encode(&value, &compression::CompressionConfig::from_env());
+    let encrypted = query.encrypted.unwrap_or(false);
+    // Compress first: ciphertext doesn't compress
+    let stored = if encrypted {
+        match envelope::seal(&stored, key.as_bytes()).await {
+            Ok(sealed) => sealed,
+            Err(e) => return HttpResponse::build(e.status()).json(CacheResponse {
+                status: "error".to_string(),
+                key,
+                value: None,
+                error: Some(e.into_message()),
+            }),
+        }
+    } else {
+        stored
+    };
+
+    match get_vault_secret("redis-1").await {
+        Ok(creds) => {
+            let address = resolve::env_address("REDIS_HOST", "redis-1", "REDIS_PORT", "6379");
+            let password = Redacted::new(creds["password"].as_str().unwrap_or("").to_string());
+
+            let url = Redacted::new(format!("redis://:{}@{}", password.expose(), address));
+
+            match redis::Client::open(url.expose().as_str()) {
+                Ok(client) => {
+                    match client.get_multiplexed_async_connection().await {
+                        Ok(mut conn) => {
+                            let result = match etag::if_match(&req) {
+                                Some(tags) => match cache_compare_and_set(&mut conn, &key, &stored, ttl, &tags).await {
+                                    Ok(1) => Ok("OK".to_string()),
+                                    Ok(outcome) => {
+                                        let reason = if outcome == -1 {
+                                            "Key does not exist"
+                                        } else {
+                                            "If-Match does not match the current value"
+                                        };
+                                        return HttpResponse::PreconditionFailed().json(CacheResponse {
+                                            status: "precondition_failed".to_string(),
+                                            key,
+                                            value: None,
+                                            error: Some(reason.to_string()),
+                                        });
+                                    }
+                                    Err(e) => Err(e),
+                                },
+                                None => if let Some(ttl_seconds) = ttl {
+                                    redis::cmd("SETEX").arg(&key).arg(ttl_seconds).arg(&stored).query_async::<String>(&mut conn).await
+                                } else {
+                                    redis::cmd("SET").arg(&key).arg(&stored).query_async::<String>(&mut conn).await
+                                },
+                            };
+
+                            match result {
+                                Ok(_) => HttpResponse::Ok()
+                                    .insert_header((actix_web::http::header::ETAG, etag::etag_for(&stored)))
+                                    .insert_header(("X-Cache-Encoding", encoding.as_str()))
+                                    .insert_header(("X-Cache-Encryption", encryption_label(encrypted)))
+                                    .json(CacheResponse {
+                                        status: "stored".to_string(),
+                                        key,
+                                        // Binary uploads aren't echoed back
+                                        value: if binary { None } else { String::from_utf8(value).ok() },
+                                        error: None,
+                                    }),
+                                Err(e) => HttpResponse::InternalServerError().json(CacheResponse {
+                                    status: "error".to_string(),
+                                    key,
+                                    value: None,
+                                    error: Some(format!("SET failed: {}", e)),
+                                }),
+                            }
+                        }
+                        Err(e) => HttpResponse::InternalServerError().json(CacheResponse {
+                            status: "error".to_string(),
+                            key,
+                            value: None,
+                            error: Some(format!("Connection failed: {}", e)),
+                        }),
+                    }
+                }
+                Err(e) => HttpResponse::InternalServerError().json(CacheResponse {
+                    status: "error".to_string(),
+                    key,
+                    value: None,
+                    error: Some(format!("Client creation failed: {}", e)),
+                }),
+            }
+        }
+        Err(e) => HttpResponse::ServiceUnavailable().json(CacheResponse {
+            status: "error".to_string(),
+            key,
+            value: None,
+            error: Some(e),
+        }),
+    }
+}
+
+async fn delete_cache(path: web::Path<String>) -> impl Responder {
+    let key = path.into_inner();
+
+    match get_vault_secret("redis-1").await {
+        Ok(creds) => {
+            let address = resolve::env_address("REDIS_HOST", "redis-1", "REDIS_PORT", "6379");
+            let password = Redacted::new(creds["password"].as_str().unwrap_or("").to_string());
+
+            let url = Redacted::new(format!("redis://:{}@{}", password.expose(), address));
+
+            match redis::Client::open(url.expose().as_str()) {
+                Ok(client) => {
+                    match client.get_multiplexed_async_connection().await {
+                        Ok(mut conn) => {
+                            match redis::cmd("DEL").arg(&key).query_async::<i32>(&mut conn).await {
+                                Ok(count) => HttpResponse::Ok().json(CacheResponse {
+                                    status: if count > 0 { "deleted" } else { "not_found" }.to_string(),
+                                    key,
+                                    value: None,
+                                    error: None,
+                                }),
+                                Err(e) => HttpResponse::InternalServerError().json(CacheResponse {
+                                    status: "error".to_string(),
+                                    key,
+                                    value: None,
+                                    error: Some(format!("DEL failed: {}", e)),
+                                }),
+                            }
+                        }
+                        Err(e) => HttpResponse::InternalServerError().json(CacheResponse {
+                            status: "error".to_string(),
+                            key,
+                            value: None,
+                            error: Some(format!("Connection failed: {}", e)),
+                        }),
+                    }
+                }
+                Err(e) => HttpResponse::InternalServerError().json(CacheResponse {
+                    status: "error".to_string(),
+                    key,
+                    value: None,
+                    error: Some(format!("Client creation failed: {}", e)),
+                }),
+            }
+        }
+        Err(e) => HttpResponse::ServiceUnavailable().json(CacheResponse {
+            status: "error".to_string(),
+            key,
+            value: None,
+            error: Some(e),
+        }),
+    }
+}
+
+// Messaging example handlers
+#[cfg(feature = "rabbitmq")]
+async fn publish_message(
+    path: web::Path<String>,
+    req_body: web::Json<PublishMessageRequest>,
+) -> impl Responder {
+    let queue = path.into_inner();
+    let message = &req_body.message;
+
+    let encoding = match message_codec::PayloadEncoding::parse(req_body.encoding.as_deref()) {
+        Ok(encoding) => encoding,
+        Err(e) => return HttpResponse::BadRequest().json(MessagingResponse {
+            status: "error".to_string(),
+            message: None,
+            queue: Some(queue),
+            error: Some(e),
+        }),
+    };
+
+    if let Err(e) = req_body.queue_options.validate() {
+        return HttpResponse::BadRequest().json(MessagingResponse {
+            status: "error".to_string(),
+            message: None,
+            queue: Some(queue),
+            error: Some(e),
+        });
+    }
+
+    if let Some(schema) = &req_body.schema {
+        if let Err(response) = schema_registry::check_message(schema, message).await {
+            return response;
+        }
+    }
+
+    // Text keeps the message as the raw body; protobuf/avro wrap it in the bundled DemoMessage record
+    let (payload, mut properties) = match (encoding, &req_body.cloudevent) {
+        (message_codec::PayloadEncoding::Text, Some(options)) => {
+            let event = cloudevents::CloudEvent::wrap(message, options);
+            (
+                serde_json::to_vec(&event).unwrap_or_default(),
+                lapin::BasicProperties::default()
+                    .with_content_type(cloudevents::CONTENT_TYPE.into())
+                    .with_message_id(event.id.as_str().into()),
+            )
+        }
+        (_, Some(_)) => return HttpResponse::BadRequest().json(MessagingResponse {
+            status: "error".to_string(),
+            message: None,
+            queue: Some(queue),
+            error: Some("CloudEvents envelopes are JSON; they can't be combined with protobuf/avro encoding".to_string()),
+        }),
+        (message_codec::PayloadEncoding::Text, None) => (message.clone().into_bytes(), lapin::BasicProperties::default()),
+        (binary, None) => match message_codec::encode(&message_codec::DemoMessage::wrap(message), binary) {
+            Ok(payload) => (payload, lapin::BasicProperties::default().with_content_type(binary.content_type().into())),
+            Err(e) => return HttpResponse::InternalServerError().json(MessagingResponse {
+                status: "error".to_string(),
+                message: None,
+                queue: Some(queue),
+                error: Some(e),
+            }),
+        },
+    };
+
+    if let Some(priority) = req_body.priority {
+        properties = properties.with_priority(priority);
+    }
+
+    match get_vault_secret("rabbitmq").await {
+        Ok(creds) => {
+            let address = resolve::env_address("RABBITMQ_HOST", "rabbitmq", "RABBITMQ_PORT", "5672");
+            let user = creds["user"].as_str().unwrap_or("devuser");
+            let password = Redacted::new(creds["password"].as_str().unwrap_or("").to_string());
+            let vhost = creds["vhost"].as_str().unwrap_or("dev_vhost");
+
+            let url = Redacted::new(format!("amqp://{}:{}@{}/{}", user, password.expose(), address, vhost));
+
+            match lapin::Connection::connect(url.expose(), lapin::ConnectionProperties::default()).await {
+                Ok(conn) => {
+                    match conn.create_channel().await {
+                        Ok(channel) => {
+                            // Declare queue
+                            match channel.queue_declare(
+                                &queue,
+                                req_body.queue_options.declare_options(),
+                                req_body.queue_options.arguments(),
+                            ).await {
+                                Ok(_) => {
+                                    // Publish message
+                                    match channel.basic_publish(
+                                        "",
+                                        &queue,
+                                        lapin::options::BasicPublishOptions::default(),
+                                        &payload,
+                                        properties.clone(),
+                                    ).await {
+                                        Ok(_) => {
+                                            let _ = conn.close(0, "Done").await;
+                                            message_archive::archive(&queue, &payload, &properties).await;
+                                            HttpResponse::Ok().json(MessagingResponse {
+                                                status: "published".to_string(),
+                                                message: Some(message.clone()),
+                                                queue: Some(queue),
+                                                error: None,
+                                            })
+                                        }
+                                        Err(e) => {
+                                            let _ = conn.close(0, "Error").await;
+                                            HttpResponse::InternalServerError().json(MessagingResponse {
+                                                status: "error".to_string(),
+                                                message: None,
+                                                queue: Some(queue),
+                                                error: Some(format!("Publish failed: {}", e)),
+                                            })
+                                        }
+                                    }
+                                }
+                                Err(e) => {
+                                    let _ = conn.close(0, "Error").await;
+                                    // The queue already exists with different arguments
+                                    let mut response = if queues::is_precondition_failed(&e) {
+                                        HttpResponse::Conflict()
+                                    } else {
+                                        HttpResponse::InternalServerError()
+                                    };
+                                    response.json(MessagingResponse {
+                                        status: "error".to_string(),
+                                        message: None,
+                                        queue: Some(queue),
+                                        error: Some(format!("Queue declare failed: {}", e)),
+                                    })
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            let _ = conn.close(0, "Error").await;
+                            HttpResponse::InternalServerError().json(MessagingResponse {
+                                status: "error".to_string(),
+                                message: None,
+                                queue: Some(queue),
+                                error: Some(format!("Channel creation failed: {}", e)),
+                            })
+                        }
+                    }
+                }
+                Err(e) => HttpResponse::InternalServerError().json(MessagingResponse {
+                    status: "error".to_string(),
+                    message: None,
+                    queue: Some(queue),
+                    error: Some(format!("Connection failed: {}", e)),
+                }),
+            }
+        }
+        Err(e) => HttpResponse::ServiceUnavailable().json(MessagingResponse {
+            status: "error".to_string(),
+            message: None,
+            queue: Some(queue),
+            error: Some(e),
+        }),
+    }
+}
+
+#[cfg(feature = "rabbitmq")]
+async fn queue_info(path: web::Path<String>) -> impl Responder {
+    let queue_name = path.into_inner();
+
+    match get_vault_secret("rabbitmq").await {
+        Ok(creds) => {
+            let address = resolve::env_address("RABBITMQ_HOST", "rabbitmq", "RABBITMQ_PORT", "5672");
+            let user = creds["user"].as_str().unwrap_or("devuser");
+            let password = Redacted::new(creds["password"].as_str().unwrap_or("").to_string());
+            let vhost = creds["vhost"].as_str().unwrap_or("dev_vhost");
+
+            let url = Redacted::new(format!("amqp://{}:{}@{}/{}", user, password.expose(), address, vhost));
+
+            match lapin::Connection::connect(url.expose(), lapin::ConnectionProperties::default()).await {
+                Ok(conn) => {
+                    match conn.create_channel().await {
+                        Ok(channel) => {
+                            // Use passive=true to check if queue exists without creating it
+                            let mut options = lapin::options::QueueDeclareOptions::default();
+                            options.passive = true;
+
+                            match channel.queue_declare(
+                                &queue_name,
+                                options,
+                                lapin::types::FieldTable::default(),
+                            ).await {
+                                Ok(queue) => {
+                                    let message_count = queue.message_count();
+                                    let consumer_count = queue.consumer_count();
+                                    let _ = conn.close(0, "Done").await;
+                                    HttpResponse::Ok().json(serde_json::json!({
+                                        "queue": queue_name,
+                                        "exists": true,
+                                        "message_count": message_count,
+                                        "consumer_count": consumer_count
+                                    }))
+                                }
+                                Err(_) => {
+                                    // Queue doesn't exist (passive declare failed)
+                                    let _ = conn.close(0, "Done").await;
+                                    HttpResponse::Ok().json(serde_json::json!({
+                                        "queue": queue_name,
+                                        "exists": false,
+                                        "message_count": null,
+                                        "consumer_count": null
+                                    }))
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            let _ = conn.close(0, "Error").await;
+                            HttpResponse::InternalServerError().json(serde_json::json!({
+                                "error": format!("Channel creation failed: {}", e)
+                            }))
+                        }
+                    }
+                }
+                Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+                    "error": format!("Connection failed: {}", e)
+                })),
+            }
+        }
+        Err(e) => HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": e
+        })),
+    }
+}
+
+// Redis cluster handlers
+async fn redis_cluster_nodes() -> impl Responder {
+    match get_vault_secret("redis-1").await {
+        Ok(creds) => {
+            let address = resolve::env_address("REDIS_HOST", "redis-1", "REDIS_PORT", "6379");
+            let password = Redacted::new(creds["password"].as_str().unwrap_or("").to_string());
+
+            let url = Redacted::new(format!("redis://:{}@{}", password.expose(), address));
+
+            match redis::Client::open(url.expose().as_str()) {
+                Ok(client) => {
+                    match client.get_multiplexed_async_connection().await {
+                        Ok(mut conn) => {
+                            match redis::cmd("CLUSTER").arg("NODES").query_async::<String>(&mut conn).await {
+                                Ok(nodes_raw) => {
+                                    let nodes = redis_parse::parse_cluster_nodes(&nodes_raw);
+                                    HttpResponse::Ok().json(ClusterNodesResponse {
+                                        status: "success".to_string(),
+                                        total_nodes: nodes.len(),
+                                        nodes,
+                                    })
+                                }
+                                Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+                                    "status": "error",
+                                    "error": format!("CLUSTER NODES failed: {}", e)
+                                })),
+                            }
+                        }
+                        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+                            "status": "error",
+                            "error": format!("Connection failed: {}", e)
+                        })),
+                    }
+                }
+                Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+                    "status": "error",
+                    "error": format!("Client creation failed: {}", e)
+                })),
+            }
+        }
+        Err(e) => HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "status": "error",
+            "error": e
+        })),
+    }
+}
+
+async fn redis_cluster_slots() -> impl Responder {
+    match get_vault_secret("redis-1").await {
+        Ok(creds) => {
+            let address = resolve::env_address("REDIS_HOST", "redis-1", "REDIS_PORT", "6379");
+            let password = Redacted::new(creds["password"].as_str().unwrap_or("").to_string());
+
+            let url = Redacted::new(format!("redis://:{}@{}", password.expose(), address));
+
+            match redis::Client::open(url.expose().as_str()) {
+                Ok(client) => {
+                    match client.get_multiplexed_async_connection().await {
+                        Ok(mut conn) => {
+                            match redis::cmd("CLUSTER").arg("SLOTS").query_async::<redis::Value>(&mut conn).await {
+                                Ok(slots) => {
+                                    let slot_distribution = redis_parse::parse_cluster_slots(&slots);
+                                    HttpResponse::Ok().json(ClusterSlotsResponse {
+                                        status: "success".to_string(),
+                                        total_slots: slot_distribution.iter().map(|a| a.slots_count).sum(),
+                                        max_slots: topology::CLUSTER_SLOTS,
+                                        coverage_percentage: topology::coverage_percentage(&slot_distribution),
+                                        slot_distribution,
+                                    })
+                                }
+                                Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+                                    "status": "error",
+                                    "error": format!("CLUSTER SLOTS failed: {}", e)
+                                })),
+                            }
+                        }
+                        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+                            "status": "error",
+                            "error": format!("Connection failed: {}", e)
+                        })),
+                    }
+                }
+                Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+                    "status": "error",
+                    "error": format!("Client creation failed: {}", e)
+                })),
+            }
+        }
+        Err(e) => HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "status": "error",
+            "error": e
+        })),
+    }
+}
+
+async fn redis_cluster_info() -> impl Responder {
+    match get_vault_secret("redis-1").await {
+        Ok(creds) => {
+            let address = resolve::env_address("REDIS_HOST", "redis-1", "REDIS_PORT", "6379");
+            let password = Redacted::new(creds["password"].as_str().unwrap_or("").to_string());
+
+            let url = Redacted::new(format!("redis://:{}@{}", password.expose(), address));
+
+            match redis::Client::open(url.expose().as_str()) {
+                Ok(client) => {
+                    match client.get_multiplexed_async_connection().await {
+                        Ok(mut conn) => {
+                            match redis::cmd("CLUSTER").arg("INFO").query_async::<String>(&mut conn).await {
+                                Ok(info_raw) => {
+                                    HttpResponse::Ok().json(ClusterInfoResponse {
+                                        status: "success".to_string(),
+                                        cluster_info: redis_parse::parse_cluster_info(&info_raw),
+                                    })
+                                }
+                                Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+                                    "status": "error",
+                                    "error": format!("CLUSTER INFO failed: {}", e)
+                                })),
+                            }
+                        }
+                        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+                            "status": "error",
+                            "error": format!("Connection failed: {}", e)
+                        })),
+                    }
+                }
+                Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+                    "status": "error",
+                    "error": format!("Client creation failed: {}", e)
+                })),
+            }
+        }
+        Err(e) => HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "status": "error",
+            "error": e
+        })),
+    }
+}
+
+// Cluster nodes addressable by name under /redis/nodes/{node_name}
+const REDIS_NODES: &[&str] = &["redis-1", "redis-2", "redis-3"];
+
+async fn redis_node_info(path: web::Path<String>) -> impl Responder {
+    let node_name = path.into_inner();
+
+    // Validate node name
+    if !REDIS_NODES.contains(&node_name.as_str()) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "status": "error",
+            "error": format!("Invalid node name. Must be one of: {}", REDIS_NODES.join(", "))
+        }));
+    }
+
+    match get_vault_secret("redis-1").await {
+        Ok(creds) => {
+            let password = Redacted::new(creds["password"].as_str().unwrap_or("").to_string());
+            let address = resolve::address(&format!("{}:6379", node_name));
+            let url = Redacted::new(format!("redis://:{}@{}", password.expose(), address));
+
+            match redis::Client::open(url.expose().as_str()) {
+                Ok(client) => {
+                    match client.get_multiplexed_async_connection().await {
+                        Ok(mut conn) => {
+                            match redis::cmd("INFO").query_async::<String>(&mut conn).await {
+                                Ok(info_raw) => {
+                                    let info = redis_parse::parse_info(&info_raw);
+
+                                    HttpResponse::Ok().json(serde_json::json!({
+                                        "status": "success",
+                                        "node": node_name,
+                                        "info": info
+                                    }))
+                                }
+                                Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+                                    "status": "error",
+                                    "error": format!("INFO failed: {}", e)
+                                })),
+                            }
+                        }
+                        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+                            "status": "error",
+                            "error": format!("Connection failed: {}", e)
+                        })),
+                    }
+                }
+                Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+                    "status": "error",
+                    "error": format!("Client creation failed: {}", e)
+                })),
+            }
+        }
+        Err(e) => HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "status": "error",
+            "error": e
+        })),
+    }
+}
+
+// Metrics handler
+async fn metrics() -> impl Responder {
+    let encoder = TextEncoder::new();
+    let metric_families = REGISTRY.gather();
+    let mut buffer = vec![];
+
+    match encoder.encode(&metric_families, &mut buffer) {
+        Ok(_) => HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4")
+            .body(buffer),
+        Err(e) => HttpResponse::InternalServerError()
+            .body(format!("Failed to encode metrics: {}", e))
+    }
+}
+
+// Every route the API serves; the binary adds the middleware stack around it
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/", web::get().to(root))
+        .route("/info", web::get().to(info))
+        .route("/info/build", web::get().to(build_info::build_info_handler))
+        .route("/metrics", web::get().to(metrics))
+        .route("/metrics/targets", web::get().to(instances::metrics_targets))
+        .route("/preflight", web::get().to(preflight::preflight))
+        .route("/slo", web::get().to(slo::slo_summary))
+        .route("/hedging", web::get().to(hedging::hedging_status))
+        .route("/observability/dashboard.json", web::get().to(grafana::dashboard_json))
+        .route("/ui", web::get().to(dashboard::index))
+        .route("/ui/{path:.*}", web::get().to(dashboard::asset))
+        // Admin routes, all behind the admin token
+        .service(
+            web::scope("/admin")
+                .wrap(actix_web::middleware::from_fn(admin_auth::require_admin))
+                .route("/requests", web::get().to(audit::list_requests))
+                .route("/keepalive", web::get().to(keepalive::keepalive_settings))
+                .route("/schedules", web::get().to(scheduler::list_schedules))
+                .route("/flags", web::get().to(feature_flags::list_flags))
+                .route("/flags/{name}", web::put().to(feature_flags::set_flag))
+                .route("/flags/{name}", web::delete().to(feature_flags::delete_flag))
+                .route("/flags/{name}/evaluate", web::get().to(feature_flags::evaluate_flag))
+                .route("/redis/migrations", web::get().to(resharding::migration_status))
+                .route("/redis/reshard", web::post().to(resharding::reshard))
+                .route("/vault-access-log", web::get().to(vault_access::vault_access_log))
+                .route("/sql-cache", web::get().to(query_cache::sql_cache_stats))
+                .route("/sql-routing", web::get().to(sql_router::sql_routing))
+                .route("/resolve", web::get().to(resolve::resolve_host))
+                .route("/concurrency", web::get().to(concurrency::concurrency_limits))
+                .route("/concurrency/reset", web::post().to(concurrency::reset_limits))
+                .route("/tenants/{tenant}/token", web::get().to(tenants::issue_token))
+        )
+        // Replica registry
+        .service(
+            web::scope("/cluster")
+                .route("/instances", web::get().to(instances::list_instances))
+        )
+        // Health check routes
+        .service(
+            web::scope("/health")
+                .route("/", web::get().to(health_simple))
+                .route("/all", web::get().to(health::health_all))
+                .route("/{service}", web::get().to(health::health_service))
+        )
+        // Vault example routes
+        .service(
+            web::scope("/examples/vault")
+                .route("/secret/{service_name}", web::get().to(get_secret))
+                .route("/secret/{service_name}/{key}", web::get().to(get_secret_key))
+                .route("/wrap", web::post().to(wrapping::wrap_secret))
+                .route("/unwrap", web::post().to(wrapping::unwrap_secret))
+                .route("/totp/{key}", web::post().to(totp::create_key))
+                .route("/totp/{key}", web::get().to(totp::get_key))
+                .route("/totp/{key}", web::delete().to(totp::delete_key))
+                .route("/totp/{key}/code", web::get().to(totp::generate_code))
+                .route("/totp/{key}/validate", web::post().to(totp::validate_code))
+        )
+        // Database example routes
+        .service(database_scope())
+        // Cache example routes
+        .service(
+            web::scope("/examples/cache")
+                .route("", web::get().to(cache::list_keys))
+                .route("", web::delete().to(cache::bulk_delete_cache))
+                .route("/aside/{key}", web::get().to(cache::cache_aside_get))
+                .route("/replication-lag/{key}", web::post().to(redis_replication::replication_lag))
+                .route("/events", web::get().to(keyspace_events::stream_events))
+                .route("/sharding-demo", web::get().to(sharding::sharding_demo))
+                .route("/scripts", web::get().to(lua_scripts::list_scripts))
+                .route("/scripts/{name}", web::put().to(lua_scripts::upload_script))
+                .route("/scripts/{name}/run", web::post().to(lua_scripts::run_script))
+                .route("/strategies", web::get().to(cache_strategies::list_strategies))
+                .route("/strategies/flush", web::post().to(cache_strategies::strategy_flush))
+                .route("/strategies/{strategy}/{key}", web::get().to(cache_strategies::strategy_get))
+                .route("/strategies/{strategy}/{key}", web::put().to(cache_strategies::strategy_put))
+                .route("/{key}", web::get().to(get_cache))
+                .route("/{key}", web::post().to(set_cache))
+                .route("/{key}", web::delete().to(delete_cache))
+        )
+        // Driver configuration benchmarks
+        .service(
+            web::scope("/examples/bench")
+                .route("/matrix", web::post().to(bench::bench_matrix))
+        )
+        // Content-addressable blob routes (Redis for small blobs, MinIO for large)
+        .service(
+            web::scope("/examples/blobs")
+                .route("", web::put().to(blobs::put_blob))
+                .route("", web::get().to(blobs::blob_stats))
+                .route("/{hash}", web::get().to(blobs::get_blob))
+        )
+        // Webhook receiver (HMAC-verified when REQUEST_SIGNING_ENABLED=true)
+        .service(
+            web::scope("/webhooks")
+                .route("/receive", web::post().to(request_signing::receive_webhook))
+        )
+        .service(
+            web::scope("/examples/signing")
+                .route("/example", web::get().to(request_signing::signed_example))
+        )
+        // Tenant-scoped secrets and connection pools
+        .service(
+            web::scope("/examples/tenants")
+                .route("/secret/{service}", web::get().to(tenants::get_tenant_secret))
+                .route("/postgres", web::get().to(tenants::tenant_postgres_identity))
+                .route("/pools", web::get().to(tenants::list_pools))
+                .route("/pools/{tenant}", web::delete().to(tenants::evict_pool))
+        )
+        // Token bucket quota routes
+        .service(
+            web::scope("/examples/quota")
+                .route("/quotas", web::get().to(quota::list_quotas))
+                .route("/quotas/{name}", web::put().to(quota::set_quota))
+                .route("/quotas/{name}", web::delete().to(quota::delete_quota))
+                .route("/quotas/{name}/consume", web::post().to(quota::consume))
+                .route("/quotas/{name}/subjects/{subject}", web::get().to(quota::bucket_state))
+                .route("/demo", web::get().to(quota::demo))
+        )
+        // Feature flag example routes
+        .service(
+            web::scope("/examples/flags")
+                .route("/greeting", web::get().to(feature_flags::greeting))
+        )
+        // Geospatial example routes (Redis GEO, optional PostGIS)
+        .service(
+            web::scope("/examples/geo")
+                .route("/points", web::post().to(geo::store_points))
+                .route("/points", web::delete().to(geo::clear_points))
+                .route("/nearby", web::get().to(geo::nearby))
+        )
+        // Probabilistic data structure routes (HyperLogLog, Bloom filters)
+        .service(
+            web::scope("/examples/probabilistic")
+                .route("/hll/{key}", web::post().to(probabilistic::hll_add))
+                .route("/hll/{key}", web::get().to(probabilistic::hll_count))
+                .route("/hll-demo", web::post().to(probabilistic::hll_demo))
+                .route("/bloom/{key}", web::post().to(probabilistic::bloom_add))
+                .route("/bloom/{key}", web::get().to(probabilistic::bloom_exists))
+                .route("/bloom-demo", web::post().to(probabilistic::bloom_demo))
+        )
+        // Time-series pipeline routes (Redis stream -> PostgreSQL rollups)
+        .service(
+            web::scope("/examples/timeseries")
+                .route("/points", web::post().to(timeseries::ingest))
+                .route("/downsample", web::post().to(timeseries::run_downsample))
+                .route("/series/{metric}", web::get().to(timeseries::series))
+        )
+        // Redis cluster routes
+        .service(
+            web::scope("/redis")
+                .route("/cluster/nodes", web::get().to(redis_cluster_nodes))
+                .route("/cluster/slots", web::get().to(redis_cluster_slots))
+                .route("/cluster/info", web::get().to(redis_cluster_info))
+                .route("/cluster/memory/top-keys", web::get().to(redis_diagnostics::memory_top_keys))
+                .route("/nodes/{node_name}/info", web::get().to(redis_node_info))
+                .route("/nodes/{node_name}/slowlog", web::get().to(redis_diagnostics::node_slowlog))
+                .route("/nodes/{node_name}/commandstats", web::get().to(redis_diagnostics::node_commandstats))
+                .route("/nodes/{node_name}/clients", web::get().to(redis_clients::list_clients))
+                .route("/nodes/{node_name}/clients/kill", web::post().to(redis_clients::kill_clients))
+        );
+    // Messaging example routes
+    #[cfg(feature = "rabbitmq")]
+    cfg.service(
+        web::scope("/examples/messaging")
+            .route("/publish/{queue}", web::post().to(publish_message))
+            .route("/queue/{queue_name}/info", web::get().to(queue_info))
+            .route("/consume/{queue}", web::get().to(message_codec::consume_messages))
+            .route("/replay", web::post().to(message_archive::replay))
+            .route("/consume/{queue}/stream", web::get().to(consumers::stream_consume))
+            .route("/prefetch-demo", web::post().to(consumers::prefetch_demo))
+            .route("/priority-demo", web::post().to(queues::priority_demo))
+            .route("/queue-types-demo", web::post().to(queues::queue_types_demo))
+            .route("/transactional-publish", web::post().to(dual_write::transactional_publish))
+            .route("/relay", web::post().to(relay::start_relay))
+            .route("/relay", web::get().to(relay::list_relays))
+            .route("/relay/{name}", web::get().to(relay::relay_status))
+            .route("/relay/{name}/stop", web::post().to(relay::stop_relay))
+            .route("/streams/{stream}", web::post().to(streams::append))
+            .route("/streams/{stream}", web::get().to(streams::read))
+            .route("/schemas", web::get().to(schema_registry::list_schemas))
+            .route("/schemas/{name}", web::get().to(schema_registry::get_schema))
+            .route("/schemas/{name}", web::put().to(schema_registry::put_schema)),
+    );
+    // Multi-service pipeline routes
+    #[cfg(feature = "mongodb")]
+    cfg.service(pipeline_scope());
+    // Application patterns built on the stack's backends
+    cfg.service(patterns_scope());
+    // Full-text search comparison routes (Postgres tsvector, MongoDB $text, RediSearch)
+    #[cfg(feature = "mongodb")]
+    cfg.service(
+        web::scope("/examples/search")
+            .route("/index", web::post().to(search::index_corpus))
+            .route("/compare", web::get().to(search::compare_search)),
+    );
+}
+
+// Background tasks only start when every backend they touch is enabled
+pub fn spawn_background_tasks() {
+    if services::is_enabled("redis") && services::is_enabled("postgres") {
+        cache_strategies::spawn_write_behind_flusher();
+    }
+    if services::is_enabled("vault") {
+        vault::spawn_token_ttl_monitor();
+    }
+    health::spawn_health_monitor();
+    scheduler::spawn_scheduler();
+    slo::spawn_slo_refresher();
+    if services::is_enabled("postgres") && services::is_enabled("vault") {
+        tenants::spawn_tenant_pool_sweeper();
+    }
+    if services::is_enabled("redis") {
+        instances::spawn_instance_heartbeat();
+    }
+    if services::is_enabled("redis") && services::is_enabled("postgres") && query_cache::listen_enabled() {
+        query_cache::spawn_notify_listener();
+    }
+    if services::is_enabled("redis") && keyspace_events::is_enabled() {
+        keyspace_events::spawn_keyspace_subscriber();
+    }
+    let keepalive = keepalive::KeepaliveConfig::from_env();
+    if let (true, Some(seconds)) = (services::is_enabled("redis"), keepalive.redis_tcp_keepalive_secs) {
+        keepalive::spawn_redis_tcp_keepalive(seconds);
+    }
+    #[cfg(feature = "mongodb")]
+    if services::is_enabled("postgres") && services::is_enabled("mongodb") && projector::is_enabled() {
+        projector::spawn_projector();
+    }
+}
+
+// Backends left out of the build (see the Cargo.toml features) add no routes to these scopes
+
+fn database_scope() -> actix_web::Scope {
+    let scope = web::scope("/examples/database");
+    #[cfg(feature = "mysql")]
+    let scope = scope
+        .route("/mysql/query", web::get().to(mysql_query))
+        .route("/mysql/users", web::get().to(mysql_examples::list_users))
+        .route("/mysql/users/{id}", web::get().to(mysql_examples::get_user))
+        .route("/mysql/users/{id}", web::put().to(mysql_examples::update_user))
+        .route("/mysql/bulk", web::post().to(mysql_examples::bulk_insert))
+        .route("/mysql/prepared", web::get().to(stmt_cache::mysql_prepared));
+    #[cfg(feature = "mongodb")]
+    let scope = scope
+        .route("/mongodb/query", web::get().to(mongodb_query))
+        .route("/mongodb/users", web::get().to(mongodb_examples::list_users))
+        .route("/mongodb/users/find", web::get().to(mongodb_examples::find_users))
+        .route("/mongodb/indexes", web::get().to(mongodb_examples::list_indexes))
+        .route("/mongodb/indexes", web::post().to(mongodb_examples::create_index))
+        .route("/mongodb/indexes/{name}", web::delete().to(mongodb_examples::drop_index))
+        .route("/mongodb/bulk", web::post().to(mongodb_examples::bulk_write));
+    #[cfg(all(feature = "mysql", feature = "mongodb"))]
+    let scope = scope.route("/consistency", web::get().to(consistency::check_consistency));
+    scope
+        .route("/postgres/query", web::get().to(postgres_query))
+        .route("/slow-queries", web::get().to(sql_timing::slow_queries))
+        .route("/seed", web::post().to(seed::seed_databases))
+        .route("/postgres/explain", web::get().to(postgres_examples::list_explainable_queries))
+        .route("/postgres/explain/{query_name}", web::get().to(postgres_examples::explain_query))
+        .route("/postgres/items", web::get().to(postgres_examples::list_items))
+        .route("/postgres/items", web::post().to(postgres_examples::create_item))
+        .route("/postgres/items/export", web::get().to(postgres_examples::export_items))
+        .route("/postgres/items/import", web::post().to(postgres_examples::import_items))
+        .route("/postgres/items/stream", web::get().to(postgres_examples::stream_items))
+        .route("/postgres/items/{id}", web::get().to(postgres_examples::get_item))
+        .route("/postgres/items/{id}", web::put().to(postgres_examples::update_item))
+        .route("/postgres/items/{id}", web::delete().to(postgres_examples::delete_item))
+        .route("/postgres/items/{id}/restore", web::post().to(postgres_examples::restore_item))
+        .route("/postgres/items/{id}/history", web::get().to(postgres_examples::item_history))
+        .route("/postgres/prepared", web::get().to(stmt_cache::postgres_prepared))
+        .route("/postgres/advisory-lock", web::get().to(advisory_lock::list_locks))
+        .route("/postgres/advisory-lock/{key}/acquire", web::post().to(advisory_lock::acquire_lock))
+        .route("/postgres/advisory-lock/{key}/try", web::post().to(advisory_lock::try_lock))
+        .route("/postgres/advisory-lock/{key}/release", web::post().to(advisory_lock::release_lock))
+}
+
+// Uploads record their metadata in MongoDB and announce themselves on RabbitMQ
+#[cfg(feature = "mongodb")]
+fn pipeline_scope() -> actix_web::Scope {
+    let scope = web::scope("/examples/pipeline");
+    #[cfg(feature = "rabbitmq")]
+    let scope = scope.route("/upload", web::post().to(pipeline::pipeline_upload));
+    scope
+        .route("/uploads/{id}", web::get().to(downloads::download_upload))
+        .route("/uploads/{id}", web::head().to(downloads::download_upload))
+}
+
+// Application patterns (work queues and the like) demonstrated on the stack's backends
+fn patterns_scope() -> actix_web::Scope {
+    let scope = web::scope("/examples/patterns")
+        .route("/pg-queue/tasks/{id}/complete", web::post().to(pg_queue::complete))
+        .route("/pg-queue/{queue}/tasks", web::post().to(pg_queue::enqueue))
+        .route("/pg-queue/{queue}/claim", web::post().to(pg_queue::claim))
+        .route("/pg-queue/{queue}/stats", web::get().to(pg_queue::stats))
+        .route("/pg-queue/{queue}/worker", web::post().to(pg_queue::start_worker))
+        .route("/pg-queue/{queue}/worker", web::delete().to(pg_queue::stop_worker))
+        .route("/event-sourcing/accounts/{id}", web::get().to(event_sourcing::get_state))
+        .route("/event-sourcing/accounts/{id}/commands", web::post().to(event_sourcing::handle_command))
+        .route("/event-sourcing/accounts/{id}/events", web::get().to(event_sourcing::list_events))
+        .route("/event-sourcing/accounts/{id}/snapshot", web::post().to(event_sourcing::take_snapshot));
+    #[cfg(feature = "mongodb")]
+    let scope = scope
+        .route("/event-sourcing/read-model/accounts", web::get().to(projector::list_accounts))
+        .route("/event-sourcing/read-model/accounts/{id}", web::get().to(projector::get_account))
+        .route("/event-sourcing/projector", web::get().to(projector::projector_status))
+        .route("/event-sourcing/projector/rebuild", web::post().to(projector::rebuild));
+    #[cfg(feature = "rabbitmq")]
+    let scope = scope
+        .route("/inbox/messages", web::post().to(inbox::send_message))
+        .route("/inbox/messages/{id}/redeliver", web::post().to(inbox::redeliver))
+        .route("/inbox/stats", web::get().to(inbox::stats))
+        .route("/inbox/consumer", web::post().to(inbox::start_consumer))
+        .route("/inbox/consumer", web::delete().to(inbox::stop_consumer));
+    scope
+}
+
+#[cfg(test)]
+mod tests;  // Comprehensive test suite in tests.rs
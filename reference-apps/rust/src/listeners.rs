@@ -0,0 +1,175 @@
+// Listen addresses for the HTTP server
+//
+// BIND_ADDRESSES is a comma-separated list of IPv4/IPv6 socket addresses and Unix socket paths,
+// e.g. `0.0.0.0:8004,[::]:8004,unix:/run/devstack/api.sock`. An address without a port uses
+// HTTP_PORT. Unset, the server binds 0.0.0.0:HTTP_PORT as before.
+//
+// Under systemd socket activation (LISTEN_PID/LISTEN_FDS) the passed sockets are used instead.
+
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::get_env_or;
+
+const LISTEN_BACKLOG: i32 = 1024;
+// First descriptor passed by systemd (SD_LISTEN_FDS_START)
+#[cfg(unix)]
+const LISTEN_FDS_START: i32 = 3;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Listener {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+lazy_static! {
+    static ref BOUND: RwLock<Vec<String>> = RwLock::new(Vec::new());
+}
+
+fn parse_one(entry: &str, default_port: u16) -> Result<Listener, String> {
+    if let Some(path) = entry.strip_prefix("unix:") {
+        if path.is_empty() {
+            return Err("unix: needs a socket path".to_string());
+        }
+        return Ok(Listener::Unix(PathBuf::from(path)));
+    }
+    if let Ok(addr) = entry.parse::<SocketAddr>() {
+        return Ok(Listener::Tcp(addr));
+    }
+    let host = entry.trim_start_matches('[').trim_end_matches(']');
+    host.parse::<IpAddr>()
+        .map(|ip| Listener::Tcp(SocketAddr::new(ip, default_port)))
+        .map_err(|_| format!("'{}' is not an IP address, IP:port, or unix:/path", entry))
+}
+
+pub fn parse_bind_addresses(value: &str, default_port: u16) -> Result<Vec<Listener>, String> {
+    let listeners = value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| parse_one(entry, default_port))
+        .collect::<Result<Vec<_>, _>>()?;
+    if listeners.is_empty() {
+        return Ok(vec![Listener::Tcp(SocketAddr::from(([0, 0, 0, 0], default_port)))]);
+    }
+    Ok(listeners)
+}
+
+pub fn from_env(default_port: u16) -> Result<Vec<Listener>, String> {
+    parse_bind_addresses(&get_env_or("BIND_ADDRESSES", ""), default_port)
+}
+
+// With an IPv4 listener alongside, IPv6 sockets are made v6-only so both can share the port;
+// a lone `[::]` stays dual-stack
+pub fn tcp_listener(addr: SocketAddr, listeners: &[Listener]) -> io::Result<std::net::TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    // On Windows SO_REUSEADDR lets another process bind the same port, so only set it on unix
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    if addr.is_ipv6() {
+        let has_ipv4 = listeners.iter().any(|l| matches!(l, Listener::Tcp(other) if other.is_ipv4()));
+        socket.set_only_v6(has_ipv4)?;
+    }
+    socket.bind(&addr.into())?;
+    socket.listen(LISTEN_BACKLOG)?;
+    Ok(socket.into())
+}
+
+// A socket file left behind by a previous run would make the bind fail
+#[cfg(unix)]
+pub fn remove_stale_socket(path: &std::path::Path) -> io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    match std::fs::metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(path),
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        )),
+        Err(_) => Ok(()),
+    }
+}
+
+// ============================================================================
+// Socket activation
+// ============================================================================
+
+pub enum Inherited {
+    Tcp(std::net::TcpListener),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener),
+}
+
+// Number of sockets passed to this process; 0 unless LISTEN_PID names us
+pub fn activated_fd_count(listen_pid: Option<&str>, listen_fds: Option<&str>, pid: u32) -> usize {
+    let listen_pid = listen_pid.and_then(|p| p.trim().parse::<u32>().ok());
+    let listen_fds = listen_fds.and_then(|n| n.trim().parse::<usize>().ok());
+    match (listen_pid, listen_fds) {
+        (Some(listen_pid), Some(count)) if listen_pid == pid => count,
+        _ => 0,
+    }
+}
+
+#[cfg(unix)]
+pub fn inherited_listeners() -> io::Result<Vec<Inherited>> {
+    use std::os::unix::io::FromRawFd;
+
+    let count = activated_fd_count(
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::process::id(),
+    );
+    // Child processes must not mistake these descriptors for their own
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+
+    (0..count as i32)
+        .map(|offset| {
+            // SAFETY: systemd hands over ownership of descriptors 3..3+LISTEN_FDS
+            let socket = unsafe { Socket::from_raw_fd(LISTEN_FDS_START + offset) };
+            // A datagram socket (ListenDatagram=) has an address too but can't accept connections
+            if socket.r#type()? != Type::STREAM {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Descriptor {} is not a stream socket", LISTEN_FDS_START + offset),
+                ));
+            }
+            socket.set_cloexec(true)?;
+            socket.set_nonblocking(true)?;
+            match socket.local_addr()?.domain() {
+                Domain::UNIX => Ok(Inherited::Unix(socket.into())),
+                Domain::IPV4 | Domain::IPV6 => Ok(Inherited::Tcp(socket.into())),
+                _ => Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Descriptor {} is not a TCP or Unix socket", LISTEN_FDS_START + offset),
+                )),
+            }
+        })
+        .collect()
+}
+
+#[cfg(not(unix))]
+pub fn inherited_listeners() -> io::Result<Vec<Inherited>> {
+    Ok(Vec::new())
+}
+
+// TCP entries are the listeners' local addresses (so port 0 shows the assigned port)
+pub fn record_bound(tcp: &[(SocketAddr, &str)], unix: &[PathBuf]) {
+    let mut bound: Vec<String> = tcp.iter().map(|(addr, scheme)| format!("{}://{}", scheme, addr)).collect();
+    bound.extend(unix.iter().map(|path| format!("unix:{}", path.display())));
+    for address in &bound {
+        log::info!("Listening on {}", address);
+    }
+    if let Ok(mut recorded) = BOUND.write() {
+        *recorded = bound;
+    }
+}
+
+pub fn bound() -> Vec<String> {
+    BOUND.read().map(|bound| bound.clone()).unwrap_or_default()
+}
@@ -0,0 +1,261 @@
+// Log shipping straight to Loki, without a promtail or vector sidecar
+//
+// With LOKI_URL set (e.g. http://loki:3100), every log record at LOKI_LEVEL (default info) or
+// above is also queued for Loki's push API, next to the usual stderr output. Records are scrubbed
+// like stderr lines and shipped as JSON lines in streams labelled service (LOKI_SERVICE, default
+// rust-api), instance (the replica id from the instance registry), level and, for records logged
+// while serving a request, route (the matched pattern, e.g. /examples/cache/aside/{key}, never the
+// raw path). LOKI_LABELS adds static labels ("env=dev,team=core") and LOKI_TENANT sets
+// X-Scope-OrgID. A background task pushes every LOKI_FLUSH_MS (default 1000) or LOKI_BATCH_SIZE
+// (default 500) records; the queue holds LOKI_BUFFER (default 10000) records and drops new ones
+// when Loki can't keep up, so logging never blocks a request.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use lazy_static::lazy_static;
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::mpsc;
+
+use crate::{get_env_or, instances, redact, resolve};
+
+// The shipper's own HTTP stack must not feed back into the queue
+const SKIPPED_TARGETS: &[&str] = &["devstack_reference::loki", "reqwest", "hyper", "hyper_util", "h2", "rustls"];
+
+static SHIPPED: AtomicU64 = AtomicU64::new(0);
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+static FAILED_PUSHES: AtomicU64 = AtomicU64::new(0);
+
+tokio::task_local! {
+    static ROUTE: String;
+}
+
+lazy_static! {
+    static ref CONFIG: Option<LokiConfig> = LokiConfig::from_env();
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LokiConfig {
+    pub push_url: String,
+    pub tenant: Option<String>,
+    pub level: LevelFilter,
+    pub labels: BTreeMap<String, String>,
+    pub batch_size: usize,
+    pub flush: Duration,
+    pub buffer: usize,
+}
+
+impl LokiConfig {
+    // None when LOKI_URL is unset
+    pub fn from_env() -> Option<Self> {
+        let url = get_env_or("LOKI_URL", "");
+        if url.is_empty() {
+            return None;
+        }
+        let mut labels = parse_labels(&get_env_or("LOKI_LABELS", ""));
+        labels.insert("service".to_string(), get_env_or("LOKI_SERVICE", "rust-api"));
+        labels.insert("instance".to_string(), instances::self_id().to_string());
+        Some(LokiConfig {
+            push_url: push_url(&resolve::url(&url)),
+            tenant: Some(get_env_or("LOKI_TENANT", "")).filter(|tenant| !tenant.is_empty()),
+            level: get_env_or("LOKI_LEVEL", "info").parse().unwrap_or(LevelFilter::Info),
+            labels,
+            batch_size: get_env_or("LOKI_BATCH_SIZE", "500").parse().unwrap_or(500).max(1),
+            flush: Duration::from_millis(get_env_or("LOKI_FLUSH_MS", "1000").parse().unwrap_or(1000)),
+            buffer: get_env_or("LOKI_BUFFER", "10000").parse().unwrap_or(10000).max(1),
+        })
+    }
+}
+
+// LOKI_URL may be the server root or the full push endpoint
+pub fn push_url(url: &str) -> String {
+    let url = url.trim_end_matches('/');
+    if url.ends_with("/loki/api/v1/push") {
+        url.to_string()
+    } else {
+        format!("{}/loki/api/v1/push", url)
+    }
+}
+
+// "key=value" pairs separated by commas; names that aren't valid Loki labels are skipped
+pub fn parse_labels(value: &str) -> BTreeMap<String, String> {
+    value
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .filter(|(key, _)| valid_label_name(key))
+        .collect()
+}
+
+pub fn valid_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub timestamp_ns: u128,
+    pub level: log::Level,
+    pub target: String,
+    pub message: String,
+    pub route: Option<String>,
+}
+
+impl Entry {
+    fn line(&self) -> String {
+        json!({"level": self.level.as_str(), "target": self.target, "message": self.message}).to_string()
+    }
+}
+
+// Loki push body: one stream per distinct label set, entries in arrival order
+pub fn push_body(entries: &[Entry], labels: &BTreeMap<String, String>) -> serde_json::Value {
+    let mut streams: BTreeMap<(String, Option<&str>), Vec<[String; 2]>> = BTreeMap::new();
+    for entry in entries {
+        let level = entry.level.as_str().to_ascii_lowercase();
+        streams
+            .entry((level, entry.route.as_deref()))
+            .or_default()
+            .push([entry.timestamp_ns.to_string(), entry.line()]);
+    }
+    let streams: Vec<serde_json::Value> = streams
+        .into_iter()
+        .map(|((level, route), values)| {
+            let mut stream = labels.clone();
+            stream.insert("level".to_string(), level);
+            if let Some(route) = route {
+                stream.insert("route".to_string(), route.to_string());
+            }
+            json!({"stream": stream, "values": values})
+        })
+        .collect();
+    json!({ "streams": streams })
+}
+
+// env_logger (or any logger) plus a queue to Loki
+pub struct LokiLogger {
+    inner: Box<dyn Log>,
+    queue: Option<(mpsc::Sender<Entry>, LevelFilter)>,
+}
+
+impl LokiLogger {
+    // Wraps `inner` and starts the shipper when LOKI_URL is set; returns the max level to install.
+    // Has to run inside the tokio runtime.
+    pub fn wrap(inner: Box<dyn Log>, inner_level: LevelFilter) -> (Self, LevelFilter) {
+        let Some(config) = CONFIG.clone() else {
+            return (LokiLogger { inner, queue: None }, inner_level);
+        };
+        let (tx, rx) = mpsc::channel(config.buffer);
+        let level = config.level;
+        tokio::spawn(ship(rx, config));
+        (LokiLogger { inner, queue: Some((tx, level)) }, inner_level.max(level))
+    }
+}
+
+pub fn shipped_target(target: &str) -> bool {
+    !SKIPPED_TARGETS
+        .iter()
+        .any(|skipped| target.strip_prefix(skipped).is_some_and(|rest| rest.is_empty() || rest.starts_with("::")))
+}
+
+impl Log for LokiLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata) || self.queue.as_ref().is_some_and(|(_, level)| metadata.level() <= *level)
+    }
+
+    fn log(&self, record: &Record) {
+        self.inner.log(record);
+        let Some((tx, level)) = &self.queue else {
+            return;
+        };
+        if record.level() > *level || !shipped_target(record.target()) {
+            return;
+        }
+        let entry = Entry {
+            timestamp_ns: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0),
+            level: record.level(),
+            target: record.target().to_string(),
+            message: redact::scrub(&record.args().to_string()),
+            route: ROUTE.try_with(|route| route.clone()).ok(),
+        };
+        if tx.try_send(entry).is_err() {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+async fn ship(mut rx: mpsc::Receiver<Entry>, config: LokiConfig) {
+    let client = reqwest::Client::new();
+    loop {
+        let mut batch = Vec::with_capacity(config.batch_size);
+        if rx.recv_many(&mut batch, config.batch_size).await == 0 {
+            return;
+        }
+        let deadline = tokio::time::sleep(config.flush);
+        tokio::pin!(deadline);
+        while batch.len() < config.batch_size {
+            let room = config.batch_size - batch.len();
+            tokio::select! {
+                _ = &mut deadline => break,
+                received = rx.recv_many(&mut batch, room) => if received == 0 { break },
+            }
+        }
+
+        let mut request = client.post(&config.push_url).json(&push_body(&batch, &config.labels));
+        if let Some(tenant) = &config.tenant {
+            request = request.header("X-Scope-OrgID", tenant);
+        }
+        match request.send().await.and_then(|res| res.error_for_status()) {
+            Ok(_) => {
+                SHIPPED.fetch_add(batch.len() as u64, Ordering::Relaxed);
+            }
+            Err(e) => {
+                FAILED_PUSHES.fetch_add(1, Ordering::Relaxed);
+                DROPPED.fetch_add(batch.len() as u64, Ordering::Relaxed);
+                log::debug!("Loki push failed: {}", e);
+            }
+        }
+    }
+}
+
+// Tags everything logged while handling a request with the matched route pattern
+pub async fn route_label_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    if CONFIG.is_none() {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+    let route = req.match_pattern().unwrap_or_else(|| "unmatched".to_string());
+    Ok(ROUTE.scope(route, next.call(req)).await?.map_into_boxed_body())
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct LokiStatus {
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub push_url: Option<String>,
+    pub shipped: u64,
+    pub dropped: u64,
+    pub failed_pushes: u64,
+}
+
+pub fn status() -> LokiStatus {
+    LokiStatus {
+        enabled: CONFIG.is_some(),
+        push_url: CONFIG.as_ref().map(|config| config.push_url.clone()),
+        shipped: SHIPPED.load(Ordering::Relaxed),
+        dropped: DROPPED.load(Ordering::Relaxed),
+        failed_pushes: FAILED_PUSHES.load(Ordering::Relaxed),
+    }
+}
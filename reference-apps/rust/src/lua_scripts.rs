@@ -0,0 +1,308 @@
+// Named Lua scripts executed with EVALSHA across the cluster
+//
+// Scripts live in an in-memory registry (built-ins plus uploads, lost on restart). Redis caches
+// scripts per node, so each run goes to the master owning the keys' slot and falls back to
+// SCRIPT LOAD + EVALSHA when that node answers NOSCRIPT (e.g. after a restart or failover).
+// Uploading runs arbitrary code on Redis, so it needs the admin token; running registered
+// scripts doesn't.
+
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use std::time::Instant;
+
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::admin_auth;
+use crate::etag::sha1_hex;
+use crate::redis_replication::slot_owner;
+use crate::{get_env_or, redis_connection, redis_master_addresses, redis_node_connection};
+
+const MAX_SCRIPT_BYTES: usize = 64 * 1024;
+
+// GET the key and refresh its TTL in one step: KEYS[1], ARGV[1] = seconds
+const GET_AND_EXPIRE: &str = r#"
+local value = redis.call('GET', KEYS[1])
+if value then
+    redis.call('EXPIRE', KEYS[1], ARGV[1])
+end
+return value
+"#;
+
+// SET only if the current value equals ARGV[1] ('' means "key must not exist"): returns 1 or 0
+const SET_IF_EQUALS: &str = r#"
+local current = redis.call('GET', KEYS[1])
+if (ARGV[1] == '' and not current) or current == ARGV[1] then
+    redis.call('SET', KEYS[1], ARGV[2])
+    return 1
+end
+return 0
+"#;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StoredScript {
+    pub name: String,
+    pub sha: String,
+    pub builtin: bool,
+    #[serde(skip_serializing)]
+    pub source: String,
+}
+
+impl StoredScript {
+    pub fn new(name: &str, source: &str, builtin: bool) -> Self {
+        StoredScript { name: name.to_string(), sha: sha1_hex(source.as_bytes()), builtin, source: source.to_string() }
+    }
+}
+
+lazy_static! {
+    static ref SCRIPTS: RwLock<BTreeMap<String, StoredScript>> = RwLock::new(
+        [("get_and_expire", GET_AND_EXPIRE), ("set_if_equals", SET_IF_EQUALS)]
+            .into_iter()
+            .map(|(name, source)| (name.to_string(), StoredScript::new(name, source, true)))
+            .collect()
+    );
+}
+
+pub fn valid_script_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= 64 && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn find_script(name: &str) -> Option<StoredScript> {
+    SCRIPTS.read().ok().and_then(|scripts| scripts.get(name).cloned())
+}
+
+fn error_response(status: StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+// Script replies as JSON: bulk strings as text (or byte arrays when not UTF-8), nil as null
+pub fn value_to_json(value: &redis::Value) -> serde_json::Value {
+    match value {
+        redis::Value::Nil => serde_json::Value::Null,
+        redis::Value::Int(n) => serde_json::json!(n),
+        redis::Value::BulkString(bytes) => match std::str::from_utf8(bytes) {
+            Ok(text) => serde_json::json!(text),
+            Err(_) => serde_json::json!(bytes),
+        },
+        redis::Value::SimpleString(text) => serde_json::json!(text),
+        redis::Value::Okay => serde_json::json!("OK"),
+        redis::Value::Array(items) => serde_json::Value::Array(items.iter().map(value_to_json).collect()),
+        redis::Value::Boolean(b) => serde_json::json!(b),
+        redis::Value::Double(d) => serde_json::json!(d),
+        other => serde_json::json!(format!("{:?}", other)),
+    }
+}
+
+// ============================================================================
+// List and upload
+// ============================================================================
+
+pub async fn list_scripts() -> impl Responder {
+    let scripts: Vec<StoredScript> = match SCRIPTS.read() {
+        Ok(scripts) => scripts.values().cloned().collect(),
+        Err(_) => Vec::new(),
+    };
+    let shas: Vec<&str> = scripts.iter().map(|s| s.sha.as_str()).collect();
+
+    // SCRIPT EXISTS on every master shows where each script is already cached
+    let mut cached_on: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut errors = Vec::new();
+    match redis_master_addresses().await {
+        Ok(masters) => {
+            for address in masters {
+                let exists = async {
+                    let mut conn = redis_node_connection(&address).await?;
+                    redis::cmd("SCRIPT")
+                        .arg("EXISTS")
+                        .arg(&shas)
+                        .query_async::<Vec<bool>>(&mut conn)
+                        .await
+                        .map_err(|e| format!("SCRIPT EXISTS failed: {}", e))
+                }
+                .await;
+                match exists {
+                    Ok(flags) => {
+                        for (script, cached) in scripts.iter().zip(flags) {
+                            if cached {
+                                cached_on.entry(script.name.clone()).or_default().push(address.clone());
+                            }
+                        }
+                    }
+                    Err(e) => errors.push(serde_json::json!({ "node": address, "error": e })),
+                }
+            }
+        }
+        Err(e) => errors.push(serde_json::json!({ "error": e })),
+    }
+
+    let scripts: Vec<serde_json::Value> = scripts
+        .iter()
+        .map(|s| {
+            serde_json::json!({
+                "name": s.name,
+                "sha": s.sha,
+                "builtin": s.builtin,
+                "cached_on": cached_on.get(&s.name).cloned().unwrap_or_default()
+            })
+        })
+        .collect();
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "count": scripts.len(),
+        "scripts": scripts,
+        "errors": errors
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct UploadRequest {
+    source: String,
+}
+
+pub async fn upload_script(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<UploadRequest>,
+) -> impl Responder {
+    if let Err(response) = admin_auth::authorize(&req) {
+        return *response;
+    }
+    let name = path.into_inner();
+    if !valid_script_name(&name) {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "Script names are 1-64 characters of letters, digits, '_' or '-'".to_string(),
+        );
+    }
+    if body.source.trim().is_empty() || body.source.len() > MAX_SCRIPT_BYTES {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            format!("source must be non-empty and at most {} bytes", MAX_SCRIPT_BYTES),
+        );
+    }
+    if find_script(&name).is_some_and(|s| s.builtin) {
+        return error_response(StatusCode::CONFLICT, format!("'{}' is a built-in script", name));
+    }
+
+    // Loading also compiles the script, so syntax errors are reported here rather than at run time
+    let script = StoredScript::new(&name, &body.source, false);
+    let mut conn = match redis_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return error_response(StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+    match redis::cmd("SCRIPT").arg("LOAD").arg(&script.source).query_async::<String>(&mut conn).await {
+        Ok(_) => {}
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, format!("SCRIPT LOAD failed: {}", e)),
+    }
+
+    let replaced = match SCRIPTS.write() {
+        Ok(mut scripts) => scripts.insert(name.clone(), script.clone()).is_some(),
+        Err(_) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Script registry unavailable".to_string()),
+    };
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "name": name,
+        "sha": script.sha,
+        "replaced": replaced
+    }))
+}
+
+// ============================================================================
+// Run
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct RunRequest {
+    #[serde(default)]
+    keys: Vec<String>,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+fn is_noscript(e: &redis::RedisError) -> bool {
+    e.code() == Some("NOSCRIPT")
+}
+
+// Master owning the keys' slot; every key must hash to the same slot, as in any cluster script.
+// Falls back to the configured node when there are no keys or cluster mode is off.
+async fn target_node(keys: &[String]) -> Result<String, (StatusCode, String)> {
+    let unavailable = |e: String| (StatusCode::SERVICE_UNAVAILABLE, e);
+    let fallback = || format!("{}:{}", get_env_or("REDIS_HOST", "redis-1"), get_env_or("REDIS_PORT", "6379"));
+    if keys.is_empty() {
+        return Ok(fallback());
+    }
+
+    let mut conn = redis_connection().await.map_err(unavailable)?;
+    let mut pipe = redis::pipe();
+    for key in keys {
+        pipe.cmd("CLUSTER").arg("KEYSLOT").arg(key);
+    }
+    let slots: Vec<u16> = match pipe.query_async(&mut conn).await {
+        Ok(slots) => slots,
+        Err(_) => return Ok(fallback()),
+    };
+    if slots.iter().any(|slot| *slot != slots[0]) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "All keys must hash to the same slot (use a {hash tag})".to_string(),
+        ));
+    }
+
+    let nodes: String = redis::cmd("CLUSTER")
+        .arg("NODES")
+        .query_async(&mut conn)
+        .await
+        .map_err(|e| unavailable(format!("CLUSTER NODES failed: {}", e)))?;
+    slot_owner(&nodes, slots[0])
+        .map(|owner| owner.master)
+        .ok_or_else(|| unavailable(format!("No master serves slot {}", slots[0])))
+}
+
+pub async fn run_script(path: web::Path<String>, body: web::Json<RunRequest>) -> impl Responder {
+    let name = path.into_inner();
+    let script = match find_script(&name) {
+        Some(script) => script,
+        None => return error_response(StatusCode::NOT_FOUND, format!("Unknown script '{}'", name)),
+    };
+
+    let node = match target_node(&body.keys).await {
+        Ok(node) => node,
+        Err((status, e)) => return error_response(status, e),
+    };
+    let mut conn = match redis_node_connection(&node).await {
+        Ok(conn) => conn,
+        Err(e) => return error_response(StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+
+    let evalsha = |sha: &str| {
+        let mut cmd = redis::cmd("EVALSHA");
+        cmd.arg(sha).arg(body.keys.len()).arg(&body.keys).arg(&body.args);
+        cmd
+    };
+
+    let started = Instant::now();
+    let mut reloaded = false;
+    let mut result = evalsha(&script.sha).query_async::<redis::Value>(&mut conn).await;
+    if matches!(&result, Err(e) if is_noscript(e)) {
+        reloaded = true;
+        result = match redis::cmd("SCRIPT").arg("LOAD").arg(&script.source).query_async::<String>(&mut conn).await {
+            Ok(sha) => evalsha(&sha).query_async(&mut conn).await,
+            Err(e) => Err(e),
+        };
+    }
+
+    match result {
+        Ok(value) => HttpResponse::Ok().json(serde_json::json!({
+            "status": "success",
+            "script": name,
+            "sha": script.sha,
+            "node": node,
+            "reloaded": reloaded,
+            "result": value_to_json(&value),
+            "elapsed_ms": started.elapsed().as_secs_f64() * 1000.0
+        })),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, format!("EVALSHA failed: {}", e)),
+    }
+}
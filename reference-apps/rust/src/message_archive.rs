@@ -0,0 +1,489 @@
+// Durable archive of published messages, and replay from it
+//
+// With MESSAGE_ARCHIVE=mongodb or minio (default off), every message sent through
+// POST /examples/messaging/publish/{queue} is also archived once the broker has taken it: as a
+// document in the message_archive collection of the seed database, or as one JSON object per
+// message under message-archive/ in MINIO_BUCKET, keyed by timestamp so a time range is a key
+// range. The archive keeps the exact payload bytes, content type and priority. Archiving
+// failures are logged and don't fail the publish; the queue is the source of truth.
+//
+// POST /examples/messaging/replay republishes archived messages from a time range, optionally
+// only those first sent to a given queue or whose payload contains a string, to a target queue
+// that must already exist. Replayed copies keep their properties and carry x-replay-of (the
+// archive id), x-original-queue and x-archived-at headers so consumers can tell them apart.
+// Publishes are confirmed before the response; dry_run lists what would be sent.
+
+use actix_web::{web, HttpResponse, Responder};
+use base64::Engine;
+use chrono::{DateTime, SecondsFormat, Utc};
+use lapin::types::{AMQPValue, FieldTable};
+use serde::{Deserialize, Serialize};
+
+use crate::storage::ObjectStore;
+use crate::{amqp_connection, get_env_or};
+
+pub const MINIO_PREFIX: &str = "message-archive/";
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1_000;
+// Archived messages read per replay, matching or not, so a narrow filter can't scan forever
+const MAX_SCANNED: usize = 10_000;
+const LIST_PAGE: u32 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    MongoDb,
+    Minio,
+}
+
+impl Backend {
+    pub fn parse(value: &str) -> Result<Option<Self>, String> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "off" | "false" => Ok(None),
+            #[cfg(feature = "mongodb")]
+            "mongodb" => Ok(Some(Backend::MongoDb)),
+            #[cfg(not(feature = "mongodb"))]
+            "mongodb" => Err("MESSAGE_ARCHIVE=mongodb needs the mongodb feature".to_string()),
+            "minio" => Ok(Some(Backend::Minio)),
+            other => Err(format!("Unknown MESSAGE_ARCHIVE '{}'; use mongodb, minio or off", other)),
+        }
+    }
+
+    pub fn from_env() -> Result<Option<Self>, String> {
+        Backend::parse(&get_env_or("MESSAGE_ARCHIVE", "off"))
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Backend::MongoDb => "mongodb",
+            Backend::Minio => "minio",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ArchivedMessage {
+    pub id: String,
+    pub queue: String,
+    #[serde(with = "rfc3339_millis")]
+    pub archived_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u8>,
+    // Base64 of the exact bytes published
+    pub payload: String,
+}
+
+// chrono is built without its serde feature
+mod rfc3339_millis {
+    use chrono::{DateTime, SecondsFormat, Utc};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(at: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&at.to_rfc3339_opts(SecondsFormat::Millis, true))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        DateTime::parse_from_rfc3339(&value).map(|at| at.with_timezone(&Utc)).map_err(serde::de::Error::custom)
+    }
+}
+
+impl ArchivedMessage {
+    pub fn new(queue: &str, payload: &[u8], properties: &lapin::BasicProperties) -> Self {
+        ArchivedMessage {
+            id: uuid::Uuid::new_v4().to_string(),
+            queue: queue.to_string(),
+            archived_at: Utc::now(),
+            content_type: properties.content_type().as_ref().map(|c| c.as_str().to_string()),
+            priority: *properties.priority(),
+            payload: base64::engine::general_purpose::STANDARD.encode(payload),
+        }
+    }
+
+    pub fn payload_bytes(&self) -> Result<Vec<u8>, String> {
+        base64::engine::general_purpose::STANDARD
+            .decode(&self.payload)
+            .map_err(|e| format!("Archived message {} has a corrupt payload: {}", self.id, e))
+    }
+
+    // Sorts by time: message-archive/20260102T030405.678Z-<id>.json
+    pub fn object_key(&self) -> String {
+        format!("{}{}-{}.json", MINIO_PREFIX, key_timestamp(&self.archived_at), self.id)
+    }
+
+    fn replay_properties(&self) -> lapin::BasicProperties {
+        let mut headers = FieldTable::default();
+        headers.insert("x-replay-of".into(), AMQPValue::LongString(self.id.as_str().into()));
+        headers.insert("x-original-queue".into(), AMQPValue::LongString(self.queue.as_str().into()));
+        headers.insert(
+            "x-archived-at".into(),
+            AMQPValue::LongString(self.archived_at.to_rfc3339_opts(SecondsFormat::Millis, true).as_str().into()),
+        );
+        let mut properties = lapin::BasicProperties::default().with_headers(headers);
+        if let Some(content_type) = &self.content_type {
+            properties = properties.with_content_type(content_type.as_str().into());
+        }
+        if let Some(priority) = self.priority {
+            properties = properties.with_priority(priority);
+        }
+        properties
+    }
+}
+
+pub fn key_timestamp(at: &DateTime<Utc>) -> String {
+    at.format("%Y%m%dT%H%M%S%.3fZ").to_string()
+}
+
+// Archive a message the broker accepted; a no-op unless MESSAGE_ARCHIVE is set
+pub async fn archive(queue: &str, payload: &[u8], properties: &lapin::BasicProperties) {
+    let backend = match Backend::from_env() {
+        Ok(Some(backend)) => backend,
+        Ok(None) => return,
+        Err(e) => {
+            log::warn!("Message not archived: {}", e);
+            return;
+        }
+    };
+    let message = ArchivedMessage::new(queue, payload, properties);
+    let result = match backend {
+        #[cfg(feature = "mongodb")]
+        Backend::MongoDb => mongo::insert(&message).await,
+        #[cfg(not(feature = "mongodb"))]
+        Backend::MongoDb => Err("mongodb feature not compiled".to_string()),
+        Backend::Minio => minio_insert(&message).await,
+    };
+    if let Err(e) = result {
+        log::warn!("Archiving message {} for {} to {} failed: {}", message.id, queue, backend.name(), e);
+    }
+}
+
+async fn minio_insert(message: &ArchivedMessage) -> Result<(), String> {
+    let store = ObjectStore::from_vault().await?;
+    let body = serde_json::to_vec(message).map_err(|e| e.to_string())?;
+    match store.put_object(&message.object_key(), body.clone(), "application/json").await {
+        Ok(_) => Ok(()),
+        // First write to a fresh MinIO
+        Err(_) => {
+            store.ensure_bucket().await?;
+            store.put_object(&message.object_key(), body, "application/json").await.map(|_| ())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReplayFilter {
+    // Inclusive
+    pub from: Option<DateTime<Utc>>,
+    // Exclusive
+    pub to: Option<DateTime<Utc>>,
+    pub queue: Option<String>,
+    pub contains: Option<String>,
+}
+
+impl ReplayFilter {
+    pub fn in_range(&self, at: &DateTime<Utc>) -> bool {
+        self.from.is_none_or(|from| *at >= from) && self.to.is_none_or(|to| *at < to)
+    }
+
+    pub fn matches(&self, message: &ArchivedMessage, payload: &[u8]) -> bool {
+        self.in_range(&message.archived_at)
+            && self.queue.as_ref().is_none_or(|queue| *queue == message.queue)
+            && self
+                .contains
+                .as_ref()
+                .is_none_or(|needle| String::from_utf8_lossy(payload).contains(needle.as_str()))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ReplayRequest {
+    pub target_queue: String,
+    // RFC 3339 timestamps
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub to: Option<String>,
+    // Only messages originally published to this queue
+    #[serde(default)]
+    pub queue: Option<String>,
+    // Only messages whose payload contains this text
+    #[serde(default)]
+    pub contains: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+fn parse_time(field: &str, value: Option<&str>) -> Result<Option<DateTime<Utc>>, String> {
+    value
+        .map(|value| {
+            DateTime::parse_from_rfc3339(value)
+                .map(|at| at.with_timezone(&Utc))
+                .map_err(|e| format!("{} must be an RFC 3339 timestamp: {}", field, e))
+        })
+        .transpose()
+}
+
+impl ReplayRequest {
+    pub fn validate(&self) -> Result<(ReplayFilter, usize), String> {
+        if self.target_queue.trim().is_empty() {
+            return Err("target_queue is required".to_string());
+        }
+        let filter = ReplayFilter {
+            from: parse_time("from", self.from.as_deref())?,
+            to: parse_time("to", self.to.as_deref())?,
+            queue: self.queue.clone().filter(|queue| !queue.is_empty()),
+            contains: self.contains.clone().filter(|needle| !needle.is_empty()),
+        };
+        if let (Some(from), Some(to)) = (filter.from, filter.to) {
+            if from >= to {
+                return Err("from must be before to".to_string());
+            }
+        }
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT);
+        if limit == 0 || limit > MAX_LIMIT {
+            return Err(format!("limit must be between 1 and {}", MAX_LIMIT));
+        }
+        Ok((filter, limit))
+    }
+}
+
+// Matching messages in archive order, and how many were read to find them
+async fn find(backend: Backend, filter: &ReplayFilter, limit: usize) -> Result<(Vec<ArchivedMessage>, usize), String> {
+    match backend {
+        #[cfg(feature = "mongodb")]
+        Backend::MongoDb => mongo::find(filter, limit).await,
+        #[cfg(not(feature = "mongodb"))]
+        Backend::MongoDb => Err("mongodb feature not compiled".to_string()),
+        Backend::Minio => minio_find(filter, limit).await,
+    }
+}
+
+async fn minio_find(filter: &ReplayFilter, limit: usize) -> Result<(Vec<ArchivedMessage>, usize), String> {
+    let store = ObjectStore::from_vault().await?;
+    let mut start_after = filter.from.map(|from| format!("{}{}", MINIO_PREFIX, key_timestamp(&from)));
+    let end = filter.to.map(|to| format!("{}{}", MINIO_PREFIX, key_timestamp(&to)));
+    let mut found = Vec::new();
+    let mut scanned = 0;
+    loop {
+        let (keys, truncated) = store.list_objects(MINIO_PREFIX, start_after.as_deref(), LIST_PAGE).await?;
+        for key in &keys {
+            if end.as_ref().is_some_and(|end| key >= end) || found.len() >= limit || scanned >= MAX_SCANNED {
+                return Ok((found, scanned));
+            }
+            scanned += 1;
+            let bytes = store
+                .get_object(key, None)
+                .await?
+                .bytes()
+                .await
+                .map_err(|e| format!("Reading {} failed: {}", key, e))?;
+            let message: ArchivedMessage =
+                serde_json::from_slice(&bytes).map_err(|e| format!("{} is not an archived message: {}", key, e))?;
+            if filter.matches(&message, &message.payload_bytes()?) {
+                found.push(message);
+            }
+        }
+        match keys.last() {
+            Some(last) if truncated => start_after = Some(last.clone()),
+            _ => return Ok((found, scanned)),
+        }
+    }
+}
+
+#[cfg(feature = "mongodb")]
+mod mongo {
+    use futures_util::TryStreamExt;
+    use mongodb::bson::{doc, Document};
+
+    use super::{ArchivedMessage, ReplayFilter, MAX_SCANNED};
+    use crate::mongodb_client;
+    use crate::seed::MONGODB_DATABASE;
+
+    const COLLECTION: &str = "message_archive";
+
+    fn bson_time(at: &chrono::DateTime<chrono::Utc>) -> mongodb::bson::DateTime {
+        mongodb::bson::DateTime::from_millis(at.timestamp_millis())
+    }
+
+    async fn collection() -> Result<mongodb::Collection<Document>, String> {
+        Ok(mongodb_client().await?.database(MONGODB_DATABASE).collection(COLLECTION))
+    }
+
+    pub async fn insert(message: &ArchivedMessage) -> Result<(), String> {
+        let mut document = doc! {
+            "_id": &message.id,
+            "queue": &message.queue,
+            // A BSON date so time ranges use the index
+            "archived_at": bson_time(&message.archived_at),
+            "payload": &message.payload,
+        };
+        if let Some(content_type) = &message.content_type {
+            document.insert("content_type", content_type);
+        }
+        if let Some(priority) = message.priority {
+            document.insert("priority", priority as i32);
+        }
+        let collection = collection().await?;
+        collection
+            .create_index(mongodb::IndexModel::builder().keys(doc! { "archived_at": 1 }).build())
+            .await
+            .map_err(|e| format!("Creating archive index failed: {}", e))?;
+        collection.insert_one(document).await.map_err(|e| format!("Insert failed: {}", e))?;
+        Ok(())
+    }
+
+    fn from_document(document: &Document) -> Option<ArchivedMessage> {
+        Some(ArchivedMessage {
+            id: document.get_str("_id").ok()?.to_string(),
+            queue: document.get_str("queue").ok()?.to_string(),
+            archived_at: chrono::DateTime::from_timestamp_millis(
+                document.get_datetime("archived_at").ok()?.timestamp_millis(),
+            )?,
+            content_type: document.get_str("content_type").ok().map(str::to_string),
+            priority: document.get_i32("priority").ok().and_then(|p| u8::try_from(p).ok()),
+            payload: document.get_str("payload").ok()?.to_string(),
+        })
+    }
+
+    pub async fn find(filter: &ReplayFilter, limit: usize) -> Result<(Vec<ArchivedMessage>, usize), String> {
+        let mut query = Document::new();
+        let mut range = Document::new();
+        if let Some(from) = &filter.from {
+            range.insert("$gte", bson_time(from));
+        }
+        if let Some(to) = &filter.to {
+            range.insert("$lt", bson_time(to));
+        }
+        if !range.is_empty() {
+            query.insert("archived_at", range);
+        }
+        if let Some(queue) = &filter.queue {
+            query.insert("queue", queue);
+        }
+        let mut cursor = collection()
+            .await?
+            .find(query)
+            .sort(doc! { "archived_at": 1, "_id": 1 })
+            .limit(MAX_SCANNED as i64)
+            .await
+            .map_err(|e| format!("Query failed: {}", e))?;
+
+        let mut found = Vec::new();
+        let mut scanned = 0;
+        while found.len() < limit {
+            let Some(document) = cursor.try_next().await.map_err(|e| format!("Query failed: {}", e))? else {
+                break;
+            };
+            scanned += 1;
+            let Some(message) = from_document(&document) else {
+                continue;
+            };
+            if filter.matches(&message, &message.payload_bytes()?) {
+                found.push(message);
+            }
+        }
+        Ok((found, scanned))
+    }
+}
+
+#[derive(Serialize)]
+struct ReplayedMessage {
+    id: String,
+    queue: String,
+    archived_at: String,
+}
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+async fn republish(target: &str, messages: &[ArchivedMessage]) -> Result<(), (actix_web::http::StatusCode, String)> {
+    let unavailable = |e: String| (actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e);
+    let failed = |e: String| (actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e);
+    let conn = amqp_connection().await.map_err(unavailable)?;
+    let result = async {
+        let channel = conn.create_channel().await.map_err(|e| failed(format!("Channel creation failed: {}", e)))?;
+        // Passive, so a typo doesn't create a queue nobody consumes
+        let passive = lapin::options::QueueDeclareOptions { passive: true, ..Default::default() };
+        channel.queue_declare(target, passive, FieldTable::default()).await.map_err(|e| {
+            (actix_web::http::StatusCode::NOT_FOUND, format!("Target queue not found: {}", e))
+        })?;
+        channel
+            .confirm_select(lapin::options::ConfirmSelectOptions::default())
+            .await
+            .map_err(|e| failed(format!("confirm.select failed: {}", e)))?;
+        let mut confirms = Vec::with_capacity(messages.len());
+        for message in messages {
+            let payload = message.payload_bytes().map_err(failed)?;
+            let confirm = channel
+                .basic_publish(
+                    "",
+                    target,
+                    lapin::options::BasicPublishOptions::default(),
+                    &payload,
+                    message.replay_properties(),
+                )
+                .await
+                .map_err(|e| failed(format!("Publish failed: {}", e)))?;
+            confirms.push(confirm);
+        }
+        for confirm in confirms {
+            confirm.await.map_err(|e| failed(format!("Publish confirm failed: {}", e)))?;
+        }
+        Ok(())
+    }
+    .await;
+    let _ = conn.close(0, "Done").await;
+    result
+}
+
+// POST /examples/messaging/replay
+// {"target_queue": "orders-replay", "from": "2026-01-01T00:00:00Z", "to": "...", "queue": "orders",
+//  "contains": "\"status\":\"failed\"", "limit": 100, "dry_run": false}
+pub async fn replay(body: web::Json<ReplayRequest>) -> impl Responder {
+    let (filter, limit) = match body.validate() {
+        Ok(parsed) => parsed,
+        Err(e) => return error_response(actix_web::http::StatusCode::BAD_REQUEST, e),
+    };
+    let backend = match Backend::from_env() {
+        Ok(Some(backend)) => backend,
+        Ok(None) => {
+            return error_response(
+                actix_web::http::StatusCode::CONFLICT,
+                "The message archive is off; set MESSAGE_ARCHIVE=mongodb or minio".to_string(),
+            )
+        }
+        Err(e) => return error_response(actix_web::http::StatusCode::BAD_REQUEST, e),
+    };
+    let (messages, scanned) = match find(backend, &filter, limit).await {
+        Ok(found) => found,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+    if !body.dry_run && !messages.is_empty() {
+        if let Err((status, e)) = republish(&body.target_queue, &messages).await {
+            return error_response(status, e);
+        }
+    }
+
+    let replayed: Vec<ReplayedMessage> = messages
+        .iter()
+        .map(|message| ReplayedMessage {
+            id: message.id.clone(),
+            queue: message.queue.clone(),
+            archived_at: message.archived_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+        })
+        .collect();
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": if body.dry_run { "dry_run" } else { "replayed" },
+        "archive": backend.name(),
+        "target_queue": body.target_queue,
+        "count": replayed.len(),
+        "scanned": scanned,
+        // More may match beyond the limit or the scan cap
+        "truncated": replayed.len() >= limit || scanned >= MAX_SCANNED,
+        "messages": replayed
+    }))
+}
@@ -0,0 +1,198 @@
+// Binary payload encodings for the messaging examples
+//
+// Publishing with `"encoding": "protobuf"|"avro"` wraps the message in a `DemoMessage` record
+// (schemas in src/schemas/) and tags the AMQP content type; the consume endpoint decodes by
+// that content type, so both ends agree without sharing anything but the bundled schemas.
+
+use actix_web::{web, HttpResponse, Responder};
+use lazy_static::lazy_static;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+
+use crate::{amqp_connection, cloudevents};
+
+pub const PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+pub const AVRO_CONTENT_TYPE: &str = "avro/binary";
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+pub const TEXT_CONTENT_TYPE: &str = "text/plain";
+
+const AVRO_SCHEMA: &str = include_str!("schemas/demo_message.avsc");
+const MAX_CONSUME: usize = 100;
+
+lazy_static! {
+    static ref DEMO_MESSAGE_AVRO: apache_avro::Schema =
+        apache_avro::Schema::parse_str(AVRO_SCHEMA).expect("Bundled Avro schema must parse");
+}
+
+// Mirrors src/schemas/demo_message.proto and demo_message.avsc
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+pub struct DemoMessage {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(string, tag = "2")]
+    pub body: String,
+    #[prost(int64, tag = "3")]
+    pub created_at_ms: i64,
+}
+
+impl DemoMessage {
+    pub fn wrap(body: &str) -> Self {
+        DemoMessage {
+            id: uuid::Uuid::new_v4().to_string(),
+            body: body.to_string(),
+            created_at_ms: chrono::Utc::now().timestamp_millis(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PayloadEncoding {
+    // The message string as-is (the original behaviour)
+    Text,
+    Protobuf,
+    Avro,
+}
+
+impl PayloadEncoding {
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value.unwrap_or("text") {
+            "text" => Ok(PayloadEncoding::Text),
+            "protobuf" | "proto" => Ok(PayloadEncoding::Protobuf),
+            "avro" => Ok(PayloadEncoding::Avro),
+            other => Err(format!("Unknown encoding '{}'. Must be one of: text, protobuf, avro", other)),
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            PayloadEncoding::Text => TEXT_CONTENT_TYPE,
+            PayloadEncoding::Protobuf => PROTOBUF_CONTENT_TYPE,
+            PayloadEncoding::Avro => AVRO_CONTENT_TYPE,
+        }
+    }
+}
+
+pub fn encode(message: &DemoMessage, encoding: PayloadEncoding) -> Result<Vec<u8>, String> {
+    match encoding {
+        PayloadEncoding::Text => Ok(message.body.clone().into_bytes()),
+        PayloadEncoding::Protobuf => Ok(message.encode_to_vec()),
+        PayloadEncoding::Avro => {
+            let value = apache_avro::to_value(message).map_err(|e| format!("Avro encoding failed: {}", e))?;
+            apache_avro::to_avro_datum(&DEMO_MESSAGE_AVRO, value).map_err(|e| format!("Avro encoding failed: {}", e))
+        }
+    }
+}
+
+// Decodes a consumed payload according to its AMQP content type
+pub fn decode(content_type: Option<&str>, payload: &[u8]) -> Result<serde_json::Value, String> {
+    match content_type.map(|ct| ct.split(';').next().unwrap_or(ct).trim()) {
+        Some(PROTOBUF_CONTENT_TYPE) => DemoMessage::decode(payload)
+            .map(|m| serde_json::json!(m))
+            .map_err(|e| format!("Protobuf decoding failed: {}", e)),
+        Some(AVRO_CONTENT_TYPE) => {
+            let value = apache_avro::from_avro_datum(&DEMO_MESSAGE_AVRO, &mut &payload[..], None)
+                .map_err(|e| format!("Avro decoding failed: {}", e))?;
+            apache_avro::from_value::<DemoMessage>(&value)
+                .map(|m| serde_json::json!(m))
+                .map_err(|e| format!("Avro decoding failed: {}", e))
+        }
+        Some(JSON_CONTENT_TYPE) | Some(cloudevents::CONTENT_TYPE) => {
+            serde_json::from_slice(payload).map_err(|e| format!("JSON decoding failed: {}", e))
+        }
+        // Untyped and text payloads come back as text when they are UTF-8
+        _ => match std::str::from_utf8(payload) {
+            Ok(text) => Ok(serde_json::json!(text)),
+            Err(_) => Err("Payload is binary with no known content type".to_string()),
+        },
+    }
+}
+
+// ============================================================================
+// Consume
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct ConsumeQuery {
+    count: Option<usize>,
+}
+
+// Pulls up to `count` messages with basic.get and decodes each one. Decoded messages are acked;
+// a message that fails to decode is nacked without requeue so it reaches the queue's dead-letter
+// exchange instead of being lost silently or redelivered forever.
+pub async fn consume_messages(path: web::Path<String>, query: web::Query<ConsumeQuery>) -> impl Responder {
+    let queue = path.into_inner();
+    let count = query.count.unwrap_or(1).clamp(1, MAX_CONSUME);
+
+    let conn = match amqp_connection().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            return HttpResponse::ServiceUnavailable().json(serde_json::json!({ "status": "error", "queue": queue, "error": e }))
+        }
+    };
+    let channel = match conn.create_channel().await {
+        Ok(channel) => channel,
+        Err(e) => {
+            let _ = conn.close(0, "Error").await;
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "status": "error",
+                "queue": queue,
+                "error": format!("Channel creation failed: {}", e)
+            }));
+        }
+    };
+
+    let mut messages = Vec::new();
+    while messages.len() < count {
+        let message = match channel
+            .basic_get(queue.as_str(), lapin::options::BasicGetOptions { no_ack: false })
+            .await
+        {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(e) => {
+                let _ = conn.close(0, "Error").await;
+                return HttpResponse::InternalServerError().json(serde_json::json!({
+                    "status": "error",
+                    "queue": queue,
+                    "error": format!("basic.get failed: {}", e)
+                }));
+            }
+        };
+        let delivery = &message.delivery;
+        let content_type = delivery.properties.content_type().as_ref().map(|ct| ct.as_str().to_string());
+        let decoded = decode(content_type.as_deref(), &delivery.data);
+        // CloudEvents are unwrapped: attributes on their own, `data` as the message
+        let event = decoded.as_ref().ok().and_then(cloudevents::CloudEvent::parse);
+        let settled = match &decoded {
+            Ok(_) => delivery.ack(lapin::options::BasicAckOptions::default()).await,
+            Err(_) => delivery.nack(lapin::options::BasicNackOptions { multiple: false, requeue: false }).await,
+        };
+        if let Err(e) = settled {
+            let _ = conn.close(0, "Error").await;
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "status": "error",
+                "queue": queue,
+                "error": format!("Acknowledging delivery failed: {}", e)
+            }));
+        }
+        messages.push(serde_json::json!({
+            "content_type": content_type,
+            "bytes": delivery.data.len(),
+            "cloudevent": event.as_ref().map(|e| e.attributes()),
+            "message": match &event {
+                Some(event) => Some(&event.data),
+                None => decoded.as_ref().ok(),
+            },
+            "error": decoded.as_ref().err(),
+            "dead_lettered": decoded.is_err()
+        }));
+    }
+    let _ = conn.close(0, "Done").await;
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "queue": queue,
+        "count": messages.len(),
+        "messages": messages
+    }))
+}
@@ -0,0 +1,572 @@
+// MongoDB example handlers beyond the basic query endpoint
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use actix_web::{web, HttpResponse, Responder};
+use futures_util::TryStreamExt;
+use mongodb::bson::{doc, Bson, Document};
+use mongodb::error::{ErrorKind, PartialBulkWriteResult};
+use mongodb::options::{
+    DeleteManyModel, DeleteOneModel, IndexOptions, InsertOneModel, ReplaceOneModel, UpdateManyModel, UpdateOneModel,
+    WriteModel,
+};
+use mongodb::results::VerboseBulkWriteResult;
+use mongodb::{IndexModel, Namespace};
+use serde::Deserialize;
+
+use crate::mongodb_client;
+use crate::pagination::{AfterId, Page, PageQuery, PageRequest};
+use crate::seed::{UserRecord, MONGODB_DATABASE, USERS_TABLE};
+
+// Server error codes
+const INDEX_NOT_FOUND: i32 = 27;
+const NAMESPACE_NOT_FOUND: i32 = 26;
+const INDEX_OPTIONS_CONFLICT: i32 = 85;
+const INDEX_KEY_SPECS_CONFLICT: i32 = 86;
+
+fn user_from_document(doc: &Document) -> Option<UserRecord> {
+    Some(UserRecord {
+        id: doc.get_i64("_id").ok()?,
+        name: doc.get_str("name").ok()?.to_string(),
+        email: doc.get_str("email").ok()?.to_string(),
+        created_at: doc.get_datetime("created_at").ok()?.try_to_rfc3339_string().ok()?,
+    })
+}
+
+async fn fetch_users_page(request: &PageRequest<AfterId>) -> Result<(Vec<UserRecord>, u64), String> {
+    let client = mongodb_client().await?;
+    let collection = client.database(MONGODB_DATABASE).collection::<Document>(USERS_TABLE);
+
+    let total = collection
+        .count_documents(doc! {})
+        .await
+        .map_err(|e| format!("Count failed: {}", e))?;
+
+    // Fetch one extra document so the page knows whether another one follows
+    let fetch = (request.limit() + 1) as i64;
+    let find = match request {
+        PageRequest::Offset { offset, .. } => collection.find(doc! {}).skip(*offset),
+        PageRequest::Cursor { after, .. } => {
+            let after_id = after.as_ref().map(|p| p.id).unwrap_or(0);
+            collection.find(doc! { "_id": { "$gt": after_id } })
+        }
+    };
+    let docs: Vec<Document> = find
+        .sort(doc! { "_id": 1 })
+        .limit(fetch)
+        .await
+        .map_err(|e| format!("Query failed: {}", e))?
+        .try_collect()
+        .await
+        .map_err(|e| format!("Query failed: {}", e))?;
+
+    let users = docs
+        .iter()
+        .map(|d| user_from_document(d).ok_or_else(|| format!("Malformed document in {}", USERS_TABLE)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((users, total))
+}
+
+pub async fn list_users(query: web::Query<PageQuery>) -> impl Responder {
+    let request = match query.resolve::<AfterId>() {
+        Ok(request) => request,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "status": "error", "error": e })),
+    };
+
+    match fetch_users_page(&request).await {
+        Ok((users, total)) => {
+            HttpResponse::Ok().json(Page::from_rows(&request, users, Some(total), |user| AfterId { id: user.id }))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({ "status": "error", "error": e })),
+    }
+}
+
+// ============================================================================
+// Index management
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct CreateIndexRequest {
+    // Field -> 1, -1, or "text", e.g. {"email": 1} or {"name": "text"}. Deserialized straight into
+    // BSON because a compound index depends on key order, which serde_json's map would sort away
+    keys: Document,
+    name: Option<String>,
+    unique: Option<bool>,
+    // Makes this a TTL index; the single key must be a date field such as created_at
+    ttl_seconds: Option<u64>,
+}
+
+fn users_collection(client: &mongodb::Client) -> mongodb::Collection<Document> {
+    client.database(MONGODB_DATABASE).collection::<Document>(USERS_TABLE)
+}
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+fn command_error_code(e: &mongodb::error::Error) -> Option<i32> {
+    match e.kind.as_ref() {
+        ErrorKind::Command(command_error) => Some(command_error.code),
+        _ => None,
+    }
+}
+
+// Validates an index key specification, keeping its field order, and normalizes directions to Int32
+pub fn index_keys(keys: &Document) -> Result<Document, String> {
+    if keys.is_empty() {
+        return Err("keys must be a non-empty object".to_string());
+    }
+
+    let mut document = Document::new();
+    for (field, direction) in keys {
+        let value = match direction {
+            Bson::Int32(n @ (1 | -1)) => Bson::Int32(*n),
+            Bson::Int64(n @ (1 | -1)) => Bson::Int32(*n as i32),
+            Bson::Double(n) if *n == 1.0 || *n == -1.0 => Bson::Int32(*n as i32),
+            Bson::String(s) if s == "text" => Bson::String(s.clone()),
+            _ => return Err(format!("Invalid direction for '{}': expected 1, -1, or \"text\"", field)),
+        };
+        document.insert(field.clone(), value);
+    }
+    Ok(document)
+}
+
+fn index_json(model: &IndexModel) -> serde_json::Value {
+    let options = model.options.as_ref();
+    serde_json::json!({
+        "name": options.and_then(|o| o.name.clone()),
+        "keys": Bson::Document(model.keys.clone()).into_relaxed_extjson(),
+        "unique": options.and_then(|o| o.unique).unwrap_or(false),
+        "ttl_seconds": options.and_then(|o| o.expire_after).map(|d| d.as_secs()),
+        "text": options.and_then(|o| o.weights.as_ref()).is_some(),
+    })
+}
+
+pub async fn list_indexes() -> impl Responder {
+    let client = match mongodb_client().await {
+        Ok(client) => client,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+
+    let indexes: Result<Vec<IndexModel>, _> = match users_collection(&client).list_indexes().await {
+        Ok(cursor) => cursor.try_collect().await,
+        Err(e) => Err(e),
+    };
+
+    match indexes {
+        Ok(indexes) => HttpResponse::Ok().json(serde_json::json!({
+            "collection": USERS_TABLE,
+            "indexes": indexes.iter().map(index_json).collect::<Vec<_>>()
+        })),
+        // The collection doesn't exist until /examples/database/seed has run
+        Err(e) if command_error_code(&e) == Some(NAMESPACE_NOT_FOUND) => HttpResponse::Ok().json(serde_json::json!({
+            "collection": USERS_TABLE,
+            "indexes": []
+        })),
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("List indexes failed: {}", e)),
+    }
+}
+
+pub async fn create_index(body: web::Json<CreateIndexRequest>) -> impl Responder {
+    let keys = match index_keys(&body.keys) {
+        Ok(keys) => keys,
+        Err(e) => return error_response(actix_web::http::StatusCode::BAD_REQUEST, e),
+    };
+    if body.ttl_seconds.is_some() && (keys.len() != 1 || keys.values().any(|v| v.as_str() == Some("text"))) {
+        return error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            "TTL indexes must have exactly one non-text key".to_string(),
+        );
+    }
+
+    let client = match mongodb_client().await {
+        Ok(client) => client,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+
+    let options = IndexOptions::builder()
+        .name(body.name.clone())
+        .unique(body.unique)
+        .expire_after(body.ttl_seconds.map(Duration::from_secs))
+        .build();
+    let model = IndexModel::builder().keys(keys).options(options).build();
+
+    match users_collection(&client).create_index(model).await {
+        Ok(result) => HttpResponse::Created().json(serde_json::json!({
+            "status": "created",
+            "collection": USERS_TABLE,
+            "name": result.index_name
+        })),
+        Err(e) if matches!(command_error_code(&e), Some(INDEX_OPTIONS_CONFLICT) | Some(INDEX_KEY_SPECS_CONFLICT)) => {
+            error_response(actix_web::http::StatusCode::CONFLICT, format!("Create index failed: {}", e))
+        }
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Create index failed: {}", e)),
+    }
+}
+
+pub async fn drop_index(path: web::Path<String>) -> impl Responder {
+    let name = path.into_inner();
+    if name == "_id_" {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, "The _id_ index cannot be dropped".to_string());
+    }
+
+    let client = match mongodb_client().await {
+        Ok(client) => client,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+
+    match users_collection(&client).drop_index(name.as_str()).await {
+        Ok(()) => HttpResponse::Ok().json(serde_json::json!({ "status": "dropped", "name": name })),
+        Err(e) if matches!(command_error_code(&e), Some(INDEX_NOT_FOUND) | Some(NAMESPACE_NOT_FOUND)) => {
+            error_response(actix_web::http::StatusCode::NOT_FOUND, format!("Index '{}' not found", name))
+        }
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Drop index failed: {}", e)),
+    }
+}
+
+// ============================================================================
+// Find with explain
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct FindUsersQuery {
+    name: Option<String>,
+    email: Option<String>,
+    // Full-text search; needs a text index, e.g. {"keys": {"name": "text"}}
+    search: Option<String>,
+    limit: Option<i64>,
+}
+
+pub fn find_filter(query: &FindUsersQuery) -> Document {
+    let mut filter = Document::new();
+    if let Some(name) = &query.name {
+        filter.insert("name", name.as_str());
+    }
+    if let Some(email) = &query.email {
+        filter.insert("email", email.as_str());
+    }
+    if let Some(search) = &query.search {
+        filter.insert("$text", doc! { "$search": search.as_str() });
+    }
+    filter
+}
+
+// Stage names and index names from a winning plan, outermost stage first. Newer servers nest the
+// classic plan under `queryPlan`.
+pub fn plan_stages(plan: &Document) -> (Vec<String>, Vec<String>) {
+    let mut stages = Vec::new();
+    let mut indexes = Vec::new();
+    let mut pending = vec![plan];
+    while let Some(stage) = pending.pop() {
+        if let Ok(name) = stage.get_str("stage") {
+            stages.push(name.to_string());
+        }
+        if let Ok(index) = stage.get_str("indexName") {
+            indexes.push(index.to_string());
+        }
+        for key in ["queryPlan", "inputStage"] {
+            if let Ok(child) = stage.get_document(key) {
+                pending.push(child);
+            }
+        }
+        if let Ok(children) = stage.get_array("inputStages") {
+            pending.extend(children.iter().filter_map(|c| c.as_document()));
+        }
+    }
+    (stages, indexes)
+}
+
+fn explain_summary(explain: &Document) -> serde_json::Value {
+    let winning_plan = explain.get_document("queryPlanner").and_then(|q| q.get_document("winningPlan"));
+    let (stages, indexes) = winning_plan.map(plan_stages).unwrap_or_default();
+    let stats = explain.get_document("executionStats").ok();
+    let stat = |key: &str| {
+        stats
+            .and_then(|s| s.get(key))
+            .and_then(|v| v.as_i64().or_else(|| v.as_i32().map(i64::from)))
+    };
+
+    serde_json::json!({
+        "used_index": !indexes.is_empty() || stages.iter().any(|s| s == "TEXT_MATCH" || s == "IDHACK"),
+        "indexes": indexes,
+        "stages": stages,
+        "collection_scan": stages.iter().any(|s| s == "COLLSCAN"),
+        "keys_examined": stat("totalKeysExamined"),
+        "docs_examined": stat("totalDocsExamined"),
+        "returned": stat("nReturned"),
+        "execution_time_ms": stat("executionTimeMillis"),
+    })
+}
+
+pub async fn find_users(query: web::Query<FindUsersQuery>) -> impl Responder {
+    let limit = query.limit.unwrap_or(20).clamp(1, 1000);
+    let filter = find_filter(&query);
+
+    let client = match mongodb_client().await {
+        Ok(client) => client,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+
+    let docs: Result<Vec<Document>, _> = match users_collection(&client).find(filter.clone()).limit(limit).await {
+        Ok(cursor) => cursor.try_collect().await,
+        Err(e) => Err(e),
+    };
+    let docs = match docs {
+        Ok(docs) => docs,
+        Err(e) if command_error_code(&e) == Some(INDEX_NOT_FOUND) => {
+            return error_response(
+                actix_web::http::StatusCode::BAD_REQUEST,
+                "search requires a text index; create one with POST /examples/database/mongodb/indexes".to_string(),
+            )
+        }
+        Err(e) => return error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Query failed: {}", e)),
+    };
+
+    let explain = client
+        .database(MONGODB_DATABASE)
+        .run_command(doc! {
+            "explain": { "find": USERS_TABLE, "filter": filter.clone(), "limit": limit },
+            "verbosity": "executionStats"
+        })
+        .await;
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "filter": Bson::Document(filter).into_relaxed_extjson(),
+        "count": docs.len(),
+        "users": docs.iter().filter_map(user_from_document).collect::<Vec<_>>(),
+        "explain": match explain {
+            Ok(explain) => explain_summary(&explain),
+            Err(e) => serde_json::json!({ "error": format!("Explain failed: {}", e) }),
+        }
+    }))
+}
+
+// ============================================================================
+// Bulk write
+// ============================================================================
+
+pub const BULK_COLLECTION: &str = "bulk_demo";
+const BULK_MAX_OPERATIONS: usize = 1000;
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BulkOperation {
+    InsertOne { document: serde_json::Value },
+    UpdateOne { filter: serde_json::Value, update: serde_json::Value, #[serde(default)] upsert: bool },
+    UpdateMany { filter: serde_json::Value, update: serde_json::Value, #[serde(default)] upsert: bool },
+    ReplaceOne { filter: serde_json::Value, replacement: serde_json::Value, #[serde(default)] upsert: bool },
+    DeleteOne { filter: serde_json::Value },
+    DeleteMany { filter: serde_json::Value },
+}
+
+#[derive(Deserialize)]
+pub struct BulkRequest {
+    operations: Vec<BulkOperation>,
+    // Ordered (the default) stops at the first failed operation; unordered attempts all of them
+    #[serde(default = "default_ordered")]
+    ordered: bool,
+}
+
+fn default_ordered() -> bool {
+    true
+}
+
+fn to_document(value: &serde_json::Value, field: &str) -> Result<Document, String> {
+    if !value.is_object() {
+        return Err(format!("{} must be an object", field));
+    }
+    mongodb::bson::to_document(value).map_err(|e| format!("Invalid {}: {}", field, e))
+}
+
+fn has_operator_keys(document: &Document) -> bool {
+    !document.is_empty() && document.keys().all(|k| k.starts_with('$'))
+}
+
+impl BulkOperation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BulkOperation::InsertOne { .. } => "insert_one",
+            BulkOperation::UpdateOne { .. } => "update_one",
+            BulkOperation::UpdateMany { .. } => "update_many",
+            BulkOperation::ReplaceOne { .. } => "replace_one",
+            BulkOperation::DeleteOne { .. } => "delete_one",
+            BulkOperation::DeleteMany { .. } => "delete_many",
+        }
+    }
+
+    // Updates must use operators ($set, $inc, ...) and replacements must not
+    pub fn to_write_model(&self, namespace: &Namespace) -> Result<WriteModel, String> {
+        let namespace = namespace.clone();
+        let model = match self {
+            BulkOperation::InsertOne { document } => WriteModel::InsertOne(
+                InsertOneModel::builder().namespace(namespace).document(to_document(document, "document")?).build(),
+            ),
+            BulkOperation::UpdateOne { filter, update, upsert } | BulkOperation::UpdateMany { filter, update, upsert } => {
+                let update = to_document(update, "update")?;
+                if !has_operator_keys(&update) {
+                    return Err("update must only contain update operators such as $set".to_string());
+                }
+                let filter = to_document(filter, "filter")?;
+                if matches!(self, BulkOperation::UpdateOne { .. }) {
+                    WriteModel::UpdateOne(
+                        UpdateOneModel::builder()
+                            .namespace(namespace)
+                            .filter(filter)
+                            .update(update)
+                            .upsert(*upsert)
+                            .build(),
+                    )
+                } else {
+                    WriteModel::UpdateMany(
+                        UpdateManyModel::builder()
+                            .namespace(namespace)
+                            .filter(filter)
+                            .update(update)
+                            .upsert(*upsert)
+                            .build(),
+                    )
+                }
+            }
+            BulkOperation::ReplaceOne { filter, replacement, upsert } => {
+                let replacement = to_document(replacement, "replacement")?;
+                if replacement.keys().any(|k| k.starts_with('$')) {
+                    return Err("replacement must not contain update operators".to_string());
+                }
+                WriteModel::ReplaceOne(
+                    ReplaceOneModel::builder()
+                        .namespace(namespace)
+                        .filter(to_document(filter, "filter")?)
+                        .replacement(replacement)
+                        .upsert(*upsert)
+                        .build(),
+                )
+            }
+            BulkOperation::DeleteOne { filter } => WriteModel::DeleteOne(
+                DeleteOneModel::builder().namespace(namespace).filter(to_document(filter, "filter")?).build(),
+            ),
+            BulkOperation::DeleteMany { filter } => WriteModel::DeleteMany(
+                DeleteManyModel::builder().namespace(namespace).filter(to_document(filter, "filter")?).build(),
+            ),
+        };
+        Ok(model)
+    }
+}
+
+// Per-operation results keyed by operation index
+fn verbose_results(result: &VerboseBulkWriteResult) -> HashMap<usize, serde_json::Value> {
+    let mut results = HashMap::new();
+    for (index, insert) in &result.insert_results {
+        results.insert(*index, serde_json::json!({ "inserted_id": insert.inserted_id.clone().into_relaxed_extjson() }));
+    }
+    for (index, update) in &result.update_results {
+        results.insert(
+            *index,
+            serde_json::json!({
+                "matched": update.matched_count,
+                "modified": update.modified_count,
+                "upserted_id": update.upserted_id.clone().map(Bson::into_relaxed_extjson)
+            }),
+        );
+    }
+    for (index, delete) in &result.delete_results {
+        results.insert(*index, serde_json::json!({ "deleted": delete.deleted_count }));
+    }
+    results
+}
+
+// One entry per submitted operation: "ok" with its result, "error" with the write error, or
+// "not_executed" for operations after the first failure of an ordered bulk write
+pub fn operation_report(
+    operations: &[BulkOperation],
+    results: &HashMap<usize, serde_json::Value>,
+    errors: &HashMap<usize, serde_json::Value>,
+) -> Vec<serde_json::Value> {
+    operations
+        .iter()
+        .enumerate()
+        .map(|(index, operation)| {
+            let (status, detail) = match (results.get(&index), errors.get(&index)) {
+                (_, Some(error)) => ("error", serde_json::json!({ "error": error })),
+                (Some(result), None) => ("ok", serde_json::json!({ "result": result })),
+                (None, None) => ("not_executed", serde_json::json!({})),
+            };
+            let mut entry = serde_json::json!({ "index": index, "op": operation.name(), "status": status });
+            if let (Some(entry), Some(detail)) = (entry.as_object_mut(), detail.as_object()) {
+                entry.extend(detail.clone());
+            }
+            entry
+        })
+        .collect()
+}
+
+pub async fn bulk_write(body: web::Json<BulkRequest>) -> impl Responder {
+    let request = body.into_inner();
+    if request.operations.is_empty() || request.operations.len() > BULK_MAX_OPERATIONS {
+        return error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            format!("operations must contain between 1 and {} entries", BULK_MAX_OPERATIONS),
+        );
+    }
+
+    let namespace = Namespace::new(MONGODB_DATABASE, BULK_COLLECTION);
+    let models = match request
+        .operations
+        .iter()
+        .enumerate()
+        .map(|(index, op)| op.to_write_model(&namespace).map_err(|e| format!("operations[{}]: {}", index, e)))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(models) => models,
+        Err(e) => return error_response(actix_web::http::StatusCode::BAD_REQUEST, e),
+    };
+
+    let client = match mongodb_client().await {
+        Ok(client) => client,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+
+    let started = std::time::Instant::now();
+    let outcome = client.bulk_write(models).ordered(request.ordered).verbose_results().await;
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+
+    let (results, errors) = match outcome {
+        Ok(result) => (verbose_results(&result), HashMap::new()),
+        Err(e) => match e.kind.as_ref() {
+            ErrorKind::BulkWrite(bulk_error) => {
+                let results = match &bulk_error.partial_result {
+                    Some(PartialBulkWriteResult::Verbose(result)) => verbose_results(result),
+                    _ => HashMap::new(),
+                };
+                let errors = bulk_error
+                    .write_errors
+                    .iter()
+                    .map(|(index, error)| {
+                        (*index, serde_json::json!({ "code": error.code, "code_name": error.code_name, "message": error.message }))
+                    })
+                    .collect();
+                (results, errors)
+            }
+            _ => {
+                return error_response(
+                    actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Bulk write failed: {}", e),
+                )
+            }
+        },
+    };
+
+    let report = operation_report(&request.operations, &results, &errors);
+    let count = |status: &str| report.iter().filter(|r| r["status"] == status).count();
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": if errors.is_empty() { "success" } else { "partial" },
+        "collection": BULK_COLLECTION,
+        "ordered": request.ordered,
+        "succeeded": count("ok"),
+        "failed": count("error"),
+        "not_executed": count("not_executed"),
+        "elapsed_ms": elapsed_ms,
+        "operations": report
+    }))
+}
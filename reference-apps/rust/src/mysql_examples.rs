@@ -0,0 +1,408 @@
+// MySQL example handlers beyond the basic query endpoint
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use futures_util::StreamExt;
+use mysql_async::prelude::Queryable;
+use serde::{Deserialize, Serialize};
+
+use crate::pagination::{AfterId, Page, PageQuery, PageRequest};
+use crate::pool;
+use crate::seed::{mysql_insert_sql, UserRecord, USERS_TABLE};
+use crate::sql_router::SqlRouter;
+use crate::{get_env_or, mysql_connection};
+use crate::sql_timing::timed_query;
+
+const USER_COLUMNS: &str = "id, name, email, DATE_FORMAT(created_at, '%Y-%m-%dT%H:%i:%sZ')";
+
+type UserRow = (i64, String, String, String);
+
+async fn fetch_users_page(request: &PageRequest<AfterId>) -> Result<(Vec<UserRecord>, u64), String> {
+    let mut conn = mysql_connection().await?;
+
+    let count_sql = format!("SELECT COUNT(*) FROM {}", USERS_TABLE);
+    let total: Option<i64> = timed_query("mysql", "count_users", &count_sql, 0, conn.query_first(count_sql.as_str()))
+        .await
+        .map_err(|e| format!("Query failed: {}", e))?;
+
+    // Fetch one extra row so the page knows whether another one follows
+    let fetch = request.limit() + 1;
+    let rows: Vec<UserRow> = match request {
+        PageRequest::Offset { offset, .. } => {
+            let sql = format!("SELECT {} FROM {} ORDER BY id LIMIT ? OFFSET ?", USER_COLUMNS, USERS_TABLE);
+            timed_query("mysql", "list_users", &sql, 2, conn.exec(sql.as_str(), (fetch, *offset))).await
+        }
+        PageRequest::Cursor { after, .. } => {
+            let sql = format!("SELECT {} FROM {} WHERE id > ? ORDER BY id LIMIT ?", USER_COLUMNS, USERS_TABLE);
+            let after_id = after.as_ref().map(|p| p.id).unwrap_or(0);
+            timed_query("mysql", "list_users", &sql, 2, conn.exec(sql.as_str(), (after_id, fetch))).await
+        }
+    }
+    .map_err(|e| format!("Query failed: {}", e))?;
+    let _ = conn.disconnect().await;
+
+    let users = rows
+        .into_iter()
+        .map(|(id, name, email, created_at)| UserRecord { id, name, email, created_at })
+        .collect();
+    Ok((users, total.unwrap_or(0) as u64))
+}
+
+pub async fn list_users(query: web::Query<PageQuery>) -> impl Responder {
+    let request = match query.resolve::<AfterId>() {
+        Ok(request) => request,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "status": "error", "error": e })),
+    };
+
+    match fetch_users_page(&request).await {
+        Ok((users, total)) => {
+            HttpResponse::Ok().json(Page::from_rows(&request, users, Some(total), |user| AfterId { id: user.id }))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({ "status": "error", "error": e })),
+    }
+}
+
+// ============================================================================
+// Optimistic locking on seeded users
+// ============================================================================
+
+type VersionedUserRow = (i64, String, String, String, i64);
+
+#[derive(Deserialize)]
+pub struct UserUpdate {
+    name: Option<String>,
+    email: Option<String>,
+    // Version from the client's last read; required
+    version: Option<i64>,
+}
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+fn versioned_user_json((id, name, email, created_at, version): VersionedUserRow) -> serde_json::Value {
+    serde_json::json!({ "id": id, "name": name, "email": email, "created_at": created_at, "version": version })
+}
+
+pub fn validate_user_update(name: Option<&str>, email: Option<&str>) -> Result<(), String> {
+    if name.is_some_and(|n| n.trim().is_empty() || n.len() > 255) {
+        return Err("name must be 1-255 characters".to_string());
+    }
+    if email.is_some_and(|e| e.len() > 255 || !e.contains('@')) {
+        return Err("email must be an address of at most 255 characters".to_string());
+    }
+    Ok(())
+}
+
+// Tables seeded before the column existed get it added with every row at version 1.
+// MySQL has no ADD COLUMN IF NOT EXISTS, so the catalog is checked first.
+async fn ensure_version_column(conn: &mut mysql_async::Conn) -> Result<(), String> {
+    let exists: Option<i64> = conn
+        .exec_first(
+            "SELECT COUNT(*) FROM information_schema.COLUMNS
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? AND COLUMN_NAME = 'version'",
+            (USERS_TABLE,),
+        )
+        .await
+        .map_err(|e| format!("Query failed: {}", e))?;
+    if exists.unwrap_or(0) == 0 {
+        conn.query_drop(format!("ALTER TABLE {} ADD COLUMN version BIGINT NOT NULL DEFAULT 1", USERS_TABLE))
+            .await
+            .map_err(|e| format!("Adding version column failed (seed the databases first?): {}", e))?;
+    }
+    Ok(())
+}
+
+static VERSION_COLUMN_READY: AtomicBool = AtomicBool::new(false);
+
+// Runs the schema check on the primary once per process; replicas get the column by replication
+async fn prepare_version_column() -> Result<(), HttpResponse> {
+    if VERSION_COLUMN_READY.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let mut conn = pool::mysql()
+        .await
+        .map_err(|e| error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e))?;
+    ensure_version_column(&mut conn)
+        .await
+        .map_err(|e| error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    VERSION_COLUMN_READY.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+fn versioned_user_sql() -> String {
+    format!("SELECT {}, version FROM {} WHERE id = ?", USER_COLUMNS, USERS_TABLE)
+}
+
+// Reads through the router: a replica normally, the primary once this request has written
+async fn find_versioned_user(router: &SqlRouter, id: i64) -> Result<(Option<VersionedUserRow>, &'static str), String> {
+    let sql = versioned_user_sql();
+    let (mut conn, route) = router.mysql(&sql).await?;
+    match timed_query("mysql", "get_user", &sql, 1, conn.exec_first(sql.as_str(), (id,))).await {
+        Ok(row) => Ok((row, route.as_str())),
+        Err(e) => {
+            conn.discard();
+            Err(format!("Query failed: {}", e))
+        }
+    }
+}
+
+pub async fn get_user(req: HttpRequest, path: web::Path<i64>) -> impl Responder {
+    let id = path.into_inner();
+    if let Err(resp) = prepare_version_column().await {
+        return resp;
+    }
+    match find_versioned_user(&SqlRouter::for_request(&req), id).await {
+        Ok((Some(row), route)) => {
+            HttpResponse::Ok().insert_header(("X-SQL-Route", route)).json(versioned_user_json(row))
+        }
+        Ok((None, _)) => HttpResponse::NotFound().json(serde_json::json!({ "status": "not_found", "id": id })),
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+// Compare-and-set in one statement: the UPDATE only matches while the row still has the
+// version the client read. Zero affected rows means the id is unknown or someone else won.
+pub async fn update_user(req: HttpRequest, path: web::Path<i64>, body: web::Json<UserUpdate>) -> impl Responder {
+    let id = path.into_inner();
+    if let Err(e) = validate_user_update(body.name.as_deref(), body.email.as_deref()) {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, e);
+    }
+    let Some(expected) = body.version else {
+        return error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            "version is required: send the version from your last read of the user".to_string(),
+        );
+    };
+    if let Err(resp) = prepare_version_column().await {
+        return resp;
+    }
+
+    let router = SqlRouter::for_request(&req);
+    let sql = format!(
+        "UPDATE {} SET name = COALESCE(?, name), email = COALESCE(?, email), version = version + 1
+         WHERE id = ? AND version = ?",
+        USERS_TABLE
+    );
+    let params = (body.name.clone(), body.email.clone(), id, expected);
+    let result = async {
+        let (mut conn, _) = router.mysql(&sql).await?;
+        let updated = match timed_query("mysql", "update_user", &sql, 4, conn.exec_drop(sql.as_str(), params)).await {
+            Ok(()) => conn.affected_rows() == 1,
+            Err(e) => {
+                conn.discard();
+                return Err(format!("Update failed: {}", e));
+            }
+        };
+        drop(conn);
+        // Sticky primary: this read follows a write in the same request, so it skips the replicas
+        let (row, _) = find_versioned_user(&router, id).await?;
+        Ok::<_, String>((updated, row))
+    }
+    .await;
+
+    match result {
+        Ok((true, Some(row))) => HttpResponse::Ok().json(versioned_user_json(row)),
+        Ok((false, Some(current))) => HttpResponse::Conflict().json(serde_json::json!({
+            "status": "conflict",
+            "error": format!(
+                "User {} has changed since it was read: expected version {}, current version is {}",
+                id, expected, current.4
+            ),
+            "expected_version": expected,
+            "current": versioned_user_json(current)
+        })),
+        Ok((_, None)) => HttpResponse::NotFound().json(serde_json::json!({ "status": "not_found", "id": id })),
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+// ============================================================================
+// Bulk insert: multi-row INSERT vs LOAD DATA LOCAL INFILE
+// ============================================================================
+
+pub const BULK_TABLE: &str = "bulk_demo";
+const DEFAULT_BULK_ROWS: usize = 10_000;
+const DEFAULT_BATCH_SIZE: usize = 1_000;
+// Keeps a single multi-row INSERT well under the 65535 placeholder limit
+const MAX_BATCH_SIZE: usize = 10_000;
+
+#[derive(Deserialize)]
+pub struct BulkQuery {
+    rows: Option<usize>,
+    batch_size: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct BulkMethodResult {
+    status: String,
+    rows: u64,
+    statements: u64,
+    elapsed_ms: u64,
+    rows_per_sec: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl BulkMethodResult {
+    fn from_outcome(rows: usize, outcome: Result<u64, String>, started: Instant) -> Self {
+        let elapsed = started.elapsed();
+        match outcome {
+            Ok(statements) => BulkMethodResult {
+                status: "success".to_string(),
+                rows: rows as u64,
+                statements,
+                elapsed_ms: elapsed.as_millis() as u64,
+                rows_per_sec: if elapsed.as_secs_f64() > 0.0 { rows as f64 / elapsed.as_secs_f64() } else { rows as f64 },
+                error: None,
+            },
+            Err(e) => BulkMethodResult {
+                status: "error".to_string(),
+                rows: 0,
+                statements: 0,
+                elapsed_ms: elapsed.as_millis() as u64,
+                rows_per_sec: 0.0,
+                error: Some(e),
+            },
+        }
+    }
+}
+
+pub fn bulk_row(i: usize) -> (String, i64) {
+    (format!("row-{}", i), (i % 1000) as i64)
+}
+
+// Tab-separated rows for LOAD DATA; generated names never contain tabs or newlines
+pub fn bulk_tsv(rows: usize) -> String {
+    let mut tsv = String::with_capacity(rows * 16);
+    for i in 0..rows {
+        let (name, value) = bulk_row(i);
+        tsv.push_str(&format!("{}\t{}\n", name, value));
+    }
+    tsv
+}
+
+async fn reset_bulk_table(conn: &mut mysql_async::Conn) -> Result<(), String> {
+    let setup = [
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                id BIGINT AUTO_INCREMENT PRIMARY KEY,
+                name VARCHAR(64) NOT NULL,
+                value INT NOT NULL
+            )",
+            BULK_TABLE
+        ),
+        format!("TRUNCATE TABLE {}", BULK_TABLE),
+    ];
+    for statement in setup.iter() {
+        conn.query_drop(statement.as_str())
+            .await
+            .map_err(|e| format!("Schema setup failed: {}", e))?;
+    }
+    Ok(())
+}
+
+// Returns the number of INSERT statements sent
+async fn multi_row_insert(conn: &mut mysql_async::Conn, rows: usize, batch_size: usize) -> Result<u64, String> {
+    let mut statements = 0;
+    let mut start = 0;
+    while start < rows {
+        let end = (start + batch_size).min(rows);
+        let sql = mysql_insert_sql(BULK_TABLE, "name, value", "(?, ?)", end - start);
+        let mut params: Vec<mysql_async::Value> = Vec::with_capacity((end - start) * 2);
+        for i in start..end {
+            let (name, value) = bulk_row(i);
+            params.push(name.into());
+            params.push(value.into());
+        }
+        conn.exec_drop(sql, mysql_async::Params::Positional(params))
+            .await
+            .map_err(|e| format!("Insert into {} failed: {}", BULK_TABLE, e))?;
+        statements += 1;
+        start = end;
+    }
+    Ok(statements)
+}
+
+// Streams the whole payload in one statement; requires local_infile=ON on the server
+async fn load_data_infile(conn: &mut mysql_async::Conn, rows: usize) -> Result<u64, String> {
+    let payload = web::Bytes::from(bulk_tsv(rows));
+    conn.set_infile_handler(async move { Ok(futures_util::stream::once(async move { Ok(payload) }).boxed()) });
+
+    let sql = format!(
+        "LOAD DATA LOCAL INFILE 'bulk_demo.tsv' INTO TABLE {} \
+         FIELDS TERMINATED BY '\\t' LINES TERMINATED BY '\\n' (name, value)",
+        BULK_TABLE
+    );
+    conn.query_drop(sql)
+        .await
+        .map_err(|e| format!("LOAD DATA failed (is local_infile enabled on the server?): {}", e))?;
+    Ok(1)
+}
+
+async fn timed_method<F>(rows: usize, fut: F) -> BulkMethodResult
+where
+    F: std::future::Future<Output = Result<u64, String>>,
+{
+    let started = Instant::now();
+    let outcome = fut.await;
+    BulkMethodResult::from_outcome(rows, outcome, started)
+}
+
+pub async fn bulk_insert(query: web::Query<BulkQuery>) -> impl Responder {
+    let rows = query.rows.unwrap_or(DEFAULT_BULK_ROWS);
+    let batch_size = query.batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
+    let max_rows: usize = get_env_or("SEED_MAX_ROWS", "100000").parse().unwrap_or(100_000);
+
+    if rows == 0 || rows > max_rows {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "status": "error",
+            "error": format!("rows must be between 1 and {}", max_rows)
+        }));
+    }
+    if batch_size == 0 || batch_size > MAX_BATCH_SIZE {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "status": "error",
+            "error": format!("batch_size must be between 1 and {}", MAX_BATCH_SIZE)
+        }));
+    }
+
+    let mut conn = match mysql_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return HttpResponse::ServiceUnavailable().json(serde_json::json!({ "status": "error", "error": e })),
+    };
+
+    // Both methods run sequentially against an emptied table so neither competes for locks
+    let mut results = Vec::new();
+    for method in ["multi_row_insert", "load_data"] {
+        if let Err(e) = reset_bulk_table(&mut conn).await {
+            return HttpResponse::InternalServerError().json(serde_json::json!({ "status": "error", "error": e }));
+        }
+        let result = match method {
+            "multi_row_insert" => timed_method(rows, multi_row_insert(&mut conn, rows, batch_size)).await,
+            _ => timed_method(rows, load_data_infile(&mut conn, rows)).await,
+        };
+        results.push((method, result));
+    }
+    let _ = conn.disconnect().await;
+
+    let fastest = results
+        .iter()
+        .filter(|(_, r)| r.status == "success")
+        .max_by(|(_, a), (_, b)| a.rows_per_sec.total_cmp(&b.rows_per_sec))
+        .map(|(method, _)| *method);
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": if results.iter().all(|(_, r)| r.status == "success") { "success" } else { "partial" },
+        "table": BULK_TABLE,
+        "rows": rows,
+        "batch_size": batch_size,
+        "fastest": fastest,
+        "methods": results
+            .iter()
+            .map(|(method, r)| (method.to_string(), serde_json::to_value(r).unwrap_or_default()))
+            .collect::<serde_json::Map<_, _>>()
+    }))
+}
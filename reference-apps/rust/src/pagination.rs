@@ -0,0 +1,126 @@
+// Shared pagination for list endpoints
+//
+// Two modes are supported: `?limit=&offset=` for page-number style navigation, and
+// `?limit=&cursor=` for keyset navigation where the cursor is an opaque token returned
+// as `next_cursor` by the previous page. Cursors are hex-encoded JSON positions, so each
+// endpoint decides what a position means (last id, SCAN cursor, ...).
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_LIMIT: u64 = 50;
+pub const MAX_LIMIT: u64 = 1000;
+
+#[derive(Deserialize, Default)]
+pub struct PageQuery {
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+    pub cursor: Option<String>,
+}
+
+// Keyset position for tables paged by ascending integer id
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct AfterId {
+    pub id: i64,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PageRequest<P> {
+    Offset { limit: u64, offset: u64 },
+    Cursor { limit: u64, after: Option<P> },
+}
+
+impl<P> PageRequest<P> {
+    pub fn limit(&self) -> u64 {
+        match self {
+            PageRequest::Offset { limit, .. } | PageRequest::Cursor { limit, .. } => *limit,
+        }
+    }
+}
+
+impl PageQuery {
+    // Limits above MAX_LIMIT are clamped; zero limits, offsets past i64::MAX (what the databases
+    // accept) and mixing offset with cursor are rejected
+    pub fn resolve<P: DeserializeOwned>(&self) -> Result<PageRequest<P>, String> {
+        let limit = match self.limit {
+            Some(0) => return Err("limit must be greater than 0".to_string()),
+            Some(limit) => limit.min(MAX_LIMIT),
+            None => DEFAULT_LIMIT,
+        };
+
+        match (&self.offset, &self.cursor) {
+            (Some(_), Some(_)) => Err("offset and cursor cannot be combined".to_string()),
+            (Some(offset), None) if *offset > i64::MAX as u64 => Err(format!("offset must be at most {}", i64::MAX)),
+            (Some(offset), None) => Ok(PageRequest::Offset { limit, offset: *offset }),
+            (None, Some(cursor)) => Ok(PageRequest::Cursor { limit, after: Some(decode_cursor(cursor)?) }),
+            (None, None) => Ok(PageRequest::Cursor { limit, after: None }),
+        }
+    }
+}
+
+pub fn encode_cursor<P: Serialize>(position: &P) -> String {
+    hex::encode(serde_json::to_vec(position).unwrap_or_default())
+}
+
+pub fn decode_cursor<P: DeserializeOwned>(cursor: &str) -> Result<P, String> {
+    let bytes = hex::decode(cursor).map_err(|_| "Invalid cursor".to_string())?;
+    serde_json::from_slice(&bytes).map_err(|_| "Invalid cursor".to_string())
+}
+
+#[derive(Serialize)]
+pub struct Page<T: Serialize> {
+    pub items: Vec<T>,
+    pub limit: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+}
+
+impl<T: Serialize> Page<T> {
+    // `items` should hold up to limit + 1 rows; the extra row only signals that more exist
+    pub fn from_rows<P, F>(request: &PageRequest<P>, mut items: Vec<T>, total: Option<u64>, position_of: F) -> Self
+    where
+        P: Serialize,
+        F: Fn(&T) -> P,
+    {
+        let limit = request.limit();
+        let has_more = items.len() as u64 > limit;
+        items.truncate(limit as usize);
+
+        match request {
+            PageRequest::Offset { offset, .. } => Page {
+                next_offset: if has_more { offset.checked_add(items.len() as u64) } else { None },
+                offset: Some(*offset),
+                next_cursor: None,
+                items,
+                limit,
+                total,
+            },
+            PageRequest::Cursor { .. } => Page {
+                next_cursor: if has_more { items.last().map(|last| encode_cursor(&position_of(last))) } else { None },
+                offset: None,
+                next_offset: None,
+                items,
+                limit,
+                total,
+            },
+        }
+    }
+
+    // For sources such as SCAN that hand back their own continuation position
+    pub fn with_cursor<P: Serialize>(items: Vec<T>, limit: u64, next: Option<P>) -> Self {
+        Page {
+            items,
+            limit,
+            offset: None,
+            next_offset: None,
+            next_cursor: next.map(|position| encode_cursor(&position)),
+            total: None,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+// Turns a panicking handler into a JSON 500 instead of a dropped connection
+//
+// The middleware runs the rest of the request under catch_unwind. On a panic it counts it in
+// panics_total{route}, logs the location and backtrace with the request id, and answers with the
+// usual error body plus `request_id` (the caller's X-Request-Id, or a fresh one) so the log line
+// can be found from the response. The panic message itself stays in the log; it can contain
+// anything. The backtrace is captured by a panic hook on the panicking thread and picked up by
+// the middleware on the same thread once unwinding reaches it. The 500 goes out as an
+// InternalError so no HttpRequest clone is held across routing, which needs the only reference.
+
+use std::cell::RefCell;
+use std::panic::AssertUnwindSafe;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::InternalError;
+use actix_web::middleware::Next;
+use actix_web::HttpResponse;
+use futures_util::FutureExt;
+use serde_json::json;
+
+use crate::PANICS_TOTAL;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+thread_local! {
+    // (location, backtrace) of the last panic on this thread
+    static LAST_PANIC: RefCell<Option<(String, String)>> = const { RefCell::new(None) };
+}
+
+// Chains onto the current hook; install before anything else wraps it
+pub fn install_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()))
+            .unwrap_or_else(|| "unknown location".to_string());
+        let backtrace = std::backtrace::Backtrace::force_capture().to_string();
+        LAST_PANIC.with(|last| *last.borrow_mut() = Some((location, backtrace)));
+        previous(info);
+    }));
+}
+
+fn take_last_panic() -> (String, String) {
+    LAST_PANIC
+        .with(|last| last.borrow_mut().take())
+        .unwrap_or_else(|| ("unknown location".to_string(), "no backtrace captured".to_string()))
+}
+
+pub fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panic with a non-string payload".to_string())
+}
+
+// The caller's id when it looks sane, otherwise a new one
+pub fn request_id(header: Option<&str>) -> String {
+    header
+        .filter(|id| !id.is_empty() && id.len() <= 128 && id.chars().all(|c| c.is_ascii_graphic()))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+pub fn panic_body(request_id: &str) -> serde_json::Value {
+    json!({
+        "status": "error",
+        "error": "Internal server error",
+        "request_id": request_id
+    })
+}
+
+pub async fn catch_panic_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let request_id = request_id(req.headers().get(REQUEST_ID_HEADER).and_then(|v| v.to_str().ok()));
+    let route = req.match_pattern().unwrap_or_else(|| "unmatched".to_string());
+    let request_line = format!("{} {}", req.method(), req.path());
+
+    // Routing runs inside call(), so it goes under catch_unwind together with the handler
+    match AssertUnwindSafe(async move { next.call(req).await }).catch_unwind().await {
+        Ok(res) => Ok(res?.map_into_boxed_body()),
+        Err(payload) => {
+            PANICS_TOTAL.with_label_values(&[route.as_str()]).inc();
+            let (location, backtrace) = take_last_panic();
+            log::error!(
+                "Panic in {} (request {}) at {}: {}\n{}",
+                request_line,
+                request_id,
+                location,
+                panic_message(payload.as_ref()),
+                backtrace
+            );
+            let res = HttpResponse::InternalServerError()
+                .insert_header((REQUEST_ID_HEADER, request_id.clone()))
+                .json(panic_body(&request_id));
+            Err(InternalError::from_response("handler panicked", res).into())
+        }
+    }
+}
@@ -0,0 +1,484 @@
+// Postgres as a work queue: SELECT ... FOR UPDATE SKIP LOCKED
+//
+// Tasks are rows in pg_queue_tasks. A claim picks the oldest ready rows that no other
+// transaction has locked, marks them running with a lease (locked_by, locked_until) and commits,
+// so any number of workers can claim concurrently without blocking each other or getting the
+// same task. Completing a task needs the worker's own lease: a worker that outlived its lease
+// gets 409, because the task has become claimable again and may already be running elsewhere.
+// Failed tasks go back to pending with exponential backoff until max_attempts, then stay failed.
+// This is the whole pattern: no broker, and the task commits or rolls back with the rest of your
+// data. The trade-off against RabbitMQ is polling and write load on the database.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::{web, HttpResponse, Responder};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::watch;
+
+use crate::pool;
+
+const TABLE_DDL: &str = "CREATE TABLE IF NOT EXISTS pg_queue_tasks (
+    id BIGSERIAL PRIMARY KEY,
+    queue TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INT NOT NULL DEFAULT 0,
+    max_attempts INT NOT NULL DEFAULT 3,
+    run_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    locked_by TEXT,
+    locked_until TIMESTAMPTZ,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS pg_queue_tasks_ready
+    ON pg_queue_tasks (queue, run_at) WHERE status IN ('pending', 'running')";
+
+// Ready = pending and due, or running with an expired lease (its worker died or stalled)
+const CLAIM_SQL: &str = "UPDATE pg_queue_tasks t
+    SET status = 'running', attempts = t.attempts + 1, locked_by = $3,
+        locked_until = NOW() + make_interval(secs => $4), updated_at = NOW()
+    WHERE t.id IN (
+        SELECT id FROM pg_queue_tasks
+        WHERE queue = $1
+          AND ((status = 'pending' AND run_at <= NOW()) OR (status = 'running' AND locked_until < NOW()))
+        ORDER BY run_at, id
+        LIMIT $2
+        FOR UPDATE SKIP LOCKED
+    )
+    RETURNING t.id, t.payload, t.attempts, t.max_attempts, t.locked_until::text";
+
+const DEFAULT_LEASE_SECONDS: u32 = 30;
+const MAX_CLAIM: i64 = 100;
+const MAX_BACKOFF_SECONDS: u32 = 300;
+const WORKER_BATCH: i64 = 10;
+const WORKER_POLL: Duration = Duration::from_secs(1);
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+async fn client() -> Result<pool::Pooled<pool::PostgresManager>, HttpResponse> {
+    let client = pool::postgres()
+        .await
+        .map_err(|e| error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e))?;
+    client.batch_execute(TABLE_DDL).await.map_err(|e| {
+        error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Table setup failed: {}", e))
+    })?;
+    Ok(client)
+}
+
+// ============================================================================
+// Enqueue
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct EnqueueRequest {
+    payload: Value,
+    #[serde(default)]
+    delay_seconds: Option<u32>,
+    #[serde(default)]
+    max_attempts: Option<u32>,
+}
+
+// POST /examples/patterns/pg-queue/{queue}/tasks
+pub async fn enqueue(path: web::Path<String>, body: web::Json<EnqueueRequest>) -> impl Responder {
+    let queue = path.into_inner();
+    let max_attempts = body.max_attempts.unwrap_or(3);
+    if !(1..=100).contains(&max_attempts) {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, "max_attempts must be 1-100".to_string());
+    }
+    let client = match client().await {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
+    let delay = f64::from(body.delay_seconds.unwrap_or(0));
+    let result = client
+        .query_one(
+            "INSERT INTO pg_queue_tasks (queue, payload, max_attempts, run_at)
+             VALUES ($1, $2, $3, NOW() + make_interval(secs => $4))
+             RETURNING id, run_at::text",
+            &[&queue, &body.payload, &(max_attempts as i32), &delay],
+        )
+        .await;
+    match result {
+        Ok(row) => HttpResponse::Created().json(serde_json::json!({
+            "status": "enqueued",
+            "queue": queue,
+            "id": row.get::<_, i64>(0),
+            "run_at": row.get::<_, String>(1)
+        })),
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Enqueue failed: {}", e)),
+    }
+}
+
+// ============================================================================
+// Claim and complete
+// ============================================================================
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ClaimedTask {
+    pub id: i64,
+    pub payload: Value,
+    pub attempt: i32,
+    pub max_attempts: i32,
+    pub locked_until: String,
+}
+
+async fn claim_tasks(
+    client: &tokio_postgres::Client,
+    queue: &str,
+    limit: i64,
+    worker: &str,
+    lease_seconds: u32,
+) -> Result<Vec<ClaimedTask>, String> {
+    let rows = client
+        .query(CLAIM_SQL, &[&queue, &limit, &worker, &f64::from(lease_seconds)])
+        .await
+        .map_err(|e| format!("Claim failed: {}", e))?;
+    let mut tasks: Vec<ClaimedTask> = rows
+        .iter()
+        .map(|row| ClaimedTask {
+            id: row.get(0),
+            payload: row.get(1),
+            attempt: row.get(2),
+            max_attempts: row.get(3),
+            locked_until: row.get(4),
+        })
+        .collect();
+    // RETURNING doesn't keep the subquery's order
+    tasks.sort_by_key(|task| task.id);
+    Ok(tasks)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Completion {
+    Done,
+    Retry,
+    Failed,
+    // The caller doesn't hold the lease any more
+    LeaseLost,
+}
+
+async fn complete_task(
+    client: &tokio_postgres::Client,
+    id: i64,
+    worker: &str,
+    error: Option<&str>,
+) -> Result<Completion, String> {
+    let row = match error {
+        None => client
+            .query_opt(
+                "UPDATE pg_queue_tasks
+                 SET status = 'done', locked_by = NULL, locked_until = NULL, last_error = NULL, updated_at = NOW()
+                 WHERE id = $1 AND status = 'running' AND locked_by = $2 AND locked_until >= NOW()
+                 RETURNING status",
+                &[&id, &worker],
+            )
+            .await,
+        Some(error) => {
+            // attempts already counts the attempt that just failed, so the retry waits 2, 4, 8... seconds
+            client
+                .query_opt(
+                    "UPDATE pg_queue_tasks
+                     SET status = CASE WHEN attempts >= max_attempts THEN 'failed' ELSE 'pending' END,
+                         run_at = NOW() + make_interval(secs => LEAST(power(2, attempts), $4)),
+                         locked_by = NULL, locked_until = NULL, last_error = $3, updated_at = NOW()
+                     WHERE id = $1 AND status = 'running' AND locked_by = $2 AND locked_until >= NOW()
+                     RETURNING status",
+                    &[&id, &worker, &error, &f64::from(MAX_BACKOFF_SECONDS)],
+                )
+                .await
+        }
+    }
+    .map_err(|e| format!("Complete failed: {}", e))?;
+    Ok(match row.map(|row| row.get::<_, String>(0)).as_deref() {
+        Some("done") => Completion::Done,
+        Some("pending") => Completion::Retry,
+        Some(_) => Completion::Failed,
+        None => Completion::LeaseLost,
+    })
+}
+
+#[derive(Deserialize)]
+pub struct ClaimQuery {
+    limit: Option<i64>,
+    lease_seconds: Option<u32>,
+    // Defaults to a random id, returned so the caller can complete with it
+    worker: Option<String>,
+}
+
+// POST /examples/patterns/pg-queue/{queue}/claim?limit=10&lease_seconds=30&worker=w1
+pub async fn claim(path: web::Path<String>, query: web::Query<ClaimQuery>) -> impl Responder {
+    let queue = path.into_inner();
+    let limit = query.limit.unwrap_or(1);
+    if !(1..=MAX_CLAIM).contains(&limit) {
+        return error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            format!("limit must be between 1 and {}", MAX_CLAIM),
+        );
+    }
+    let lease_seconds = query.lease_seconds.unwrap_or(DEFAULT_LEASE_SECONDS).clamp(1, 3600);
+    let worker = query.worker.clone().unwrap_or_else(|| format!("http-{}", uuid::Uuid::new_v4().simple()));
+    let client = match client().await {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
+    match claim_tasks(&client, &queue, limit, &worker, lease_seconds).await {
+        Ok(tasks) => HttpResponse::Ok().json(serde_json::json!({
+            "queue": queue,
+            "worker": worker,
+            "lease_seconds": lease_seconds,
+            "count": tasks.len(),
+            "tasks": tasks
+        })),
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CompleteRequest {
+    worker: String,
+    // Set to fail the attempt; the task is retried with backoff until max_attempts
+    #[serde(default)]
+    error: Option<String>,
+}
+
+// POST /examples/patterns/pg-queue/tasks/{id}/complete
+pub async fn complete(path: web::Path<i64>, body: web::Json<CompleteRequest>) -> impl Responder {
+    let id = path.into_inner();
+    let client = match client().await {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
+    match complete_task(&client, id, &body.worker, body.error.as_deref()).await {
+        Ok(Completion::LeaseLost) => error_response(
+            actix_web::http::StatusCode::CONFLICT,
+            format!("Task {} is not running under a live lease held by '{}'", id, body.worker),
+        ),
+        Ok(outcome) => HttpResponse::Ok().json(serde_json::json!({
+            "id": id,
+            "status": match outcome {
+                Completion::Done => "done",
+                Completion::Retry => "pending",
+                _ => "failed",
+            }
+        })),
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+// ============================================================================
+// In-process worker
+// ============================================================================
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct WorkerConfig {
+    // Simulated work per task
+    #[serde(default = "default_work_ms")]
+    work_ms: u64,
+    // Share of tasks that fail, 0.0-1.0, to show retries and backoff
+    #[serde(default)]
+    fail_rate: f64,
+    #[serde(default = "default_lease_seconds")]
+    lease_seconds: u32,
+}
+
+fn default_work_ms() -> u64 {
+    50
+}
+
+fn default_lease_seconds() -> u32 {
+    DEFAULT_LEASE_SECONDS
+}
+
+#[derive(Default)]
+struct WorkerStats {
+    completed: AtomicU64,
+    failed: AtomicU64,
+    lost_leases: AtomicU64,
+    last_error: Mutex<Option<String>>,
+}
+
+struct Worker {
+    id: String,
+    config: WorkerConfig,
+    started_at: chrono::DateTime<chrono::Utc>,
+    stats: Arc<WorkerStats>,
+    stop: watch::Sender<bool>,
+}
+
+impl Worker {
+    fn status(&self) -> Value {
+        serde_json::json!({
+            "worker": self.id,
+            "config": self.config,
+            "started_at": self.started_at.to_rfc3339(),
+            "completed": self.stats.completed.load(Ordering::Relaxed),
+            "failed": self.stats.failed.load(Ordering::Relaxed),
+            "lost_leases": self.stats.lost_leases.load(Ordering::Relaxed),
+            "last_error": self.stats.last_error.lock().ok().and_then(|e| e.clone())
+        })
+    }
+}
+
+lazy_static! {
+    // queue -> the worker draining it
+    static ref WORKERS: Mutex<HashMap<String, Worker>> = Mutex::new(HashMap::new());
+}
+
+async fn run_worker(
+    queue: String,
+    id: String,
+    config: WorkerConfig,
+    stats: Arc<WorkerStats>,
+    mut stop: watch::Receiver<bool>,
+) {
+    while !*stop.borrow() {
+        let claimed = match pool::postgres().await {
+            Ok(client) => claim_tasks(&client, &queue, WORKER_BATCH, &id, config.lease_seconds).await,
+            Err(e) => Err(e),
+        };
+        let tasks = match claimed {
+            Ok(tasks) => tasks,
+            Err(e) => {
+                if let Ok(mut last) = stats.last_error.lock() {
+                    *last = Some(e);
+                }
+                Vec::new()
+            }
+        };
+        if tasks.is_empty() {
+            tokio::select! {
+                _ = tokio::time::sleep(WORKER_POLL) => {}
+                _ = stop.changed() => {}
+            }
+            continue;
+        }
+        for task in tasks {
+            tokio::time::sleep(Duration::from_millis(config.work_ms)).await;
+            let error = (rand::random::<f64>() < config.fail_rate).then(|| "simulated failure".to_string());
+            let completed = match pool::postgres().await {
+                Ok(client) => complete_task(&client, task.id, &id, error.as_deref()).await,
+                Err(e) => Err(e),
+            };
+            let counter = match completed {
+                Ok(Completion::Done) => &stats.completed,
+                Ok(Completion::Retry | Completion::Failed) => &stats.failed,
+                Ok(Completion::LeaseLost) => &stats.lost_leases,
+                Err(e) => {
+                    if let Ok(mut last) = stats.last_error.lock() {
+                        *last = Some(e);
+                    }
+                    continue;
+                }
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+// POST /examples/patterns/pg-queue/{queue}/worker
+pub async fn start_worker(path: web::Path<String>, body: Option<web::Json<WorkerConfig>>) -> impl Responder {
+    let queue = path.into_inner();
+    let config = body.map(|b| b.into_inner()).unwrap_or(WorkerConfig {
+        work_ms: default_work_ms(),
+        fail_rate: 0.0,
+        lease_seconds: DEFAULT_LEASE_SECONDS,
+    });
+    if !(0.0..=1.0).contains(&config.fail_rate) || config.lease_seconds == 0 {
+        return error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            "fail_rate must be 0.0-1.0 and lease_seconds positive".to_string(),
+        );
+    }
+    if let Err(response) = client().await {
+        return response;
+    }
+    let mut workers = WORKERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if workers.contains_key(&queue) {
+        return error_response(
+            actix_web::http::StatusCode::CONFLICT,
+            format!("A worker is already running for '{}'", queue),
+        );
+    }
+    let (stop, stop_rx) = watch::channel(false);
+    let worker = Worker {
+        id: format!("worker-{}", uuid::Uuid::new_v4().simple()),
+        config,
+        started_at: chrono::Utc::now(),
+        stats: Arc::new(WorkerStats::default()),
+        stop,
+    };
+    tokio::spawn(run_worker(queue.clone(), worker.id.clone(), worker.config.clone(), worker.stats.clone(), stop_rx));
+    let status = worker.status();
+    workers.insert(queue, worker);
+    HttpResponse::Created().json(status)
+}
+
+// DELETE /examples/patterns/pg-queue/{queue}/worker - stops after the task in hand
+pub async fn stop_worker(path: web::Path<String>) -> impl Responder {
+    let queue = path.into_inner();
+    let worker = WORKERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(&queue);
+    match worker {
+        Some(worker) => {
+            let _ = worker.stop.send(true);
+            HttpResponse::Ok().json(worker.status())
+        }
+        None => error_response(actix_web::http::StatusCode::NOT_FOUND, format!("No worker running for '{}'", queue)),
+    }
+}
+
+// ============================================================================
+// Stats
+// ============================================================================
+
+// GET /examples/patterns/pg-queue/{queue}/stats
+pub async fn stats(path: web::Path<String>) -> impl Responder {
+    let queue = path.into_inner();
+    let client = match client().await {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
+    let result = client
+        .query_one(
+            "SELECT
+                COUNT(*) FILTER (WHERE status = 'pending' AND run_at <= NOW()),
+                COUNT(*) FILTER (WHERE status = 'pending' AND run_at > NOW()),
+                COUNT(*) FILTER (WHERE status = 'running' AND locked_until >= NOW()),
+                COUNT(*) FILTER (WHERE status = 'running' AND locked_until < NOW()),
+                COUNT(*) FILTER (WHERE status = 'done'),
+                COUNT(*) FILTER (WHERE status = 'failed'),
+                COALESCE(EXTRACT(EPOCH FROM NOW() - MIN(run_at)
+                    FILTER (WHERE status = 'pending' AND run_at <= NOW())), 0)::float8
+             FROM pg_queue_tasks WHERE queue = $1",
+            &[&queue],
+        )
+        .await;
+    let worker = WORKERS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(&queue)
+        .map(Worker::status);
+    match result {
+        Ok(row) => HttpResponse::Ok().json(serde_json::json!({
+            "queue": queue,
+            "ready": row.get::<_, i64>(0),
+            "scheduled": row.get::<_, i64>(1),
+            "running": row.get::<_, i64>(2),
+            "expired_leases": row.get::<_, i64>(3),
+            "done": row.get::<_, i64>(4),
+            "failed": row.get::<_, i64>(5),
+            "oldest_ready_seconds": row.get::<_, f64>(6),
+            "worker": worker
+        })),
+        Err(e) => {
+            error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Stats query failed: {}", e))
+        }
+    }
+}
@@ -0,0 +1,315 @@
+// Multi-service pipeline example: multipart upload -> MinIO -> MongoDB metadata -> RabbitMQ event
+//
+// The file is streamed into MinIO as an S3 multipart upload, so only one part is held in memory
+// however large the file. Its SHA-256 is computed on the way; when the client sends
+// X-Content-SHA256 and the digest differs, the upload is aborted before the object becomes
+// visible and the request fails with 422.
+//
+// The upload route needs both the `mongodb` and `rabbitmq` features; the multipart helpers are
+// always built, since other modules read uploads with them.
+
+use actix_multipart::{Field, Multipart};
+#[cfg(all(feature = "mongodb", feature = "rabbitmq"))]
+use actix_web::http::StatusCode;
+#[cfg(all(feature = "mongodb", feature = "rabbitmq"))]
+use actix_web::{HttpRequest, HttpResponse, Responder};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+
+#[cfg(all(feature = "mongodb", feature = "rabbitmq"))]
+use crate::storage::{MultipartUpload, ObjectStore};
+#[cfg(all(feature = "mongodb", feature = "rabbitmq"))]
+use crate::{amqp_connection, get_env_or, mongodb_client};
+
+#[derive(Serialize, Deserialize)]
+pub struct PipelineUploadResponse {
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub object_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_queue: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stage: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[cfg(all(feature = "mongodb", feature = "rabbitmq"))]
+impl PipelineUploadResponse {
+    fn failed(stage: &str, error: String) -> Self {
+        PipelineUploadResponse {
+            status: "error".to_string(),
+            object_url: None,
+            document_id: None,
+            event_queue: None,
+            size: None,
+            sha256: None,
+            stage: Some(stage.to_string()),
+            error: Some(error),
+        }
+    }
+}
+
+pub struct UploadedFile {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+pub const CHECKSUM_HEADER: &str = "X-Content-SHA256";
+
+// The client's hex SHA-256, lower-cased; Err when the header is present but malformed
+pub fn expected_checksum(value: Option<&str>) -> Result<Option<String>, String> {
+    match value.map(str::trim) {
+        None => Ok(None),
+        Some(v) if v.len() == 64 && v.bytes().all(|b| b.is_ascii_hexdigit()) => Ok(Some(v.to_ascii_lowercase())),
+        Some(v) => Err(format!("{} must be 64 hex characters, got '{}'", CHECKSUM_HEADER, v)),
+    }
+}
+
+pub struct FileField {
+    pub filename: String,
+    pub content_type: String,
+    pub field: Field,
+}
+
+// Advance to the first file field in the multipart body, leaving its content unread
+pub async fn next_file_field(payload: &mut Multipart) -> Result<Option<FileField>, (u16, String)> {
+    while let Some(item) = payload.next().await {
+        let field = item.map_err(|e| (400, format!("Invalid multipart body: {}", e)))?;
+
+        let filename = match field.content_disposition().and_then(|cd| cd.get_filename()) {
+            Some(name) => name.to_string(),
+            None => continue, // Not a file field
+        };
+        let content_type = field
+            .content_type()
+            .map(|m| m.to_string())
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+        return Ok(Some(FileField { filename, content_type, field }));
+    }
+    Ok(None)
+}
+
+// Read the first file field from the multipart body, enforcing the size limit
+pub async fn read_file_field(payload: &mut Multipart, max_bytes: usize) -> Result<Option<UploadedFile>, (u16, String)> {
+    let Some(FileField { filename, content_type, mut field }) = next_file_field(payload).await? else {
+        return Ok(None);
+    };
+    let mut data = Vec::new();
+    while let Some(chunk) = field.next().await {
+        let chunk = chunk.map_err(|e| (400, format!("Failed to read upload: {}", e)))?;
+        if data.len() + chunk.len() > max_bytes {
+            return Err((413, format!("File exceeds maximum size of {} bytes", max_bytes)));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(Some(UploadedFile { filename, content_type, data }))
+}
+
+// Copy a file field into an upload as it arrives; the error carries the status and stage to report
+#[cfg(all(feature = "mongodb", feature = "rabbitmq"))]
+async fn stream_field(
+    field: &mut Field,
+    upload: &mut MultipartUpload<'_>,
+    max_bytes: usize,
+) -> Result<(), (StatusCode, &'static str, String)> {
+    while let Some(chunk) = field.next().await {
+        let chunk = chunk.map_err(|e| (StatusCode::BAD_REQUEST, "upload", format!("Failed to read upload: {}", e)))?;
+        if upload.size() + chunk.len() as u64 > max_bytes as u64 {
+            let error = format!("File exceeds maximum size of {} bytes", max_bytes);
+            return Err((StatusCode::PAYLOAD_TOO_LARGE, "upload", error));
+        }
+        upload.write(&chunk).await.map_err(|e| (StatusCode::BAD_GATEWAY, "storage", e))?;
+    }
+    Ok(())
+}
+
+// Keep object keys URL- and filesystem-friendly
+#[cfg(all(feature = "mongodb", feature = "rabbitmq"))]
+fn sanitize_filename(name: &str) -> String {
+    let cleaned: String = name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(name)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    if cleaned.is_empty() { "upload".to_string() } else { cleaned }
+}
+
+// Compensates for a stored object that nothing will refer to
+#[cfg(all(feature = "mongodb", feature = "rabbitmq"))]
+async fn discard_object(store: &ObjectStore, key: &str) {
+    if let Err(e) = store.delete_object(key).await {
+        log::warn!("Deleting orphaned upload {} failed: {}", key, e);
+    }
+}
+
+// Removes the metadata document and then the object it describes
+#[cfg(all(feature = "mongodb", feature = "rabbitmq"))]
+async fn discard_upload(
+    collection: &mongodb::Collection<mongodb::bson::Document>,
+    document_id: &mongodb::bson::Bson,
+    store: &ObjectStore,
+    key: &str,
+) {
+    if let Err(e) = collection.delete_one(mongodb::bson::doc! { "_id": document_id.clone() }).await {
+        log::warn!("Deleting upload document {} failed: {}", document_id, e);
+    }
+    discard_object(store, key).await;
+}
+
+#[cfg(all(feature = "mongodb", feature = "rabbitmq"))]
+pub async fn pipeline_upload(req: HttpRequest, mut payload: Multipart) -> impl Responder {
+    let max_bytes: usize = get_env_or("PIPELINE_MAX_UPLOAD_BYTES", "10485760").parse().unwrap_or(10_485_760);
+    let queue = get_env_or("PIPELINE_EVENT_QUEUE", "upload-events");
+    let expected_sha256 = match expected_checksum(req.headers().get(CHECKSUM_HEADER).and_then(|v| v.to_str().ok())) {
+        Ok(expected) => expected,
+        Err(e) => return HttpResponse::BadRequest().json(PipelineUploadResponse::failed("upload", e)),
+    };
+
+    // Stage 1: find the uploaded file
+    let mut file = match next_file_field(&mut payload).await {
+        Ok(Some(file)) => file,
+        Ok(None) => {
+            return HttpResponse::BadRequest()
+                .json(PipelineUploadResponse::failed("upload", "No file field in multipart body".to_string()))
+        }
+        Err((_, e)) => return HttpResponse::BadRequest().json(PipelineUploadResponse::failed("upload", e)),
+    };
+    let object_key = format!(
+        "{}/{}-{}",
+        chrono::Utc::now().format("%Y/%m/%d"),
+        uuid::Uuid::new_v4(),
+        sanitize_filename(&file.filename)
+    );
+
+    // Stage 2: stream the object into MinIO, verifying the checksum before it is committed
+    let store = match ObjectStore::from_vault().await {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::ServiceUnavailable().json(PipelineUploadResponse::failed("storage", e)),
+    };
+    if let Err(e) = store.ensure_bucket().await {
+        return HttpResponse::BadGateway().json(PipelineUploadResponse::failed("storage", e));
+    }
+    let mut upload = match store.start_upload(&object_key, &file.content_type).await {
+        Ok(upload) => upload,
+        Err(e) => return HttpResponse::BadGateway().json(PipelineUploadResponse::failed("storage", e)),
+    };
+    if let Err((status, stage, e)) = stream_field(&mut file.field, &mut upload, max_bytes).await {
+        upload.abort().await;
+        return HttpResponse::build(status).json(PipelineUploadResponse::failed(stage, e));
+    }
+    let (size, sha256) = (upload.size() as usize, upload.sha256());
+    if let Some(expected) = expected_sha256.filter(|expected| *expected != sha256) {
+        upload.abort().await;
+        let error =
+            format!("SHA-256 mismatch: {} is {} but the received file hashes to {}", CHECKSUM_HEADER, expected, sha256);
+        return HttpResponse::UnprocessableEntity()
+            .json(PipelineUploadResponse { sha256: Some(sha256), ..PipelineUploadResponse::failed("checksum", error) });
+    }
+    let object_url = match upload.complete().await {
+        Ok(url) => url,
+        Err(e) => return HttpResponse::BadGateway().json(PipelineUploadResponse::failed("storage", e)),
+    };
+
+    // Stage 3: record metadata in MongoDB. Without a document nothing refers to the object, so a
+    // failure here deletes it again rather than leaving an orphan in the bucket.
+    let uploaded_at = chrono::Utc::now().to_rfc3339();
+    let (collection, inserted_id) = match mongodb_client().await {
+        Ok(client) => {
+            let collection = client.database("test").collection::<mongodb::bson::Document>("uploads");
+            let doc = mongodb::bson::doc! {
+                "filename": file.filename.as_str(),
+                "content_type": file.content_type.as_str(),
+                "size": size as i64,
+                "sha256": sha256.as_str(),
+                "bucket": store.bucket(),
+                "object_key": object_key.as_str(),
+                "object_url": object_url.as_str(),
+                "uploaded_at": uploaded_at.as_str(),
+            };
+            match collection.insert_one(doc).await {
+                Ok(result) => (collection, result.inserted_id),
+                Err(e) => {
+                    discard_object(&store, &object_key).await;
+                    return HttpResponse::BadGateway()
+                        .json(PipelineUploadResponse::failed("metadata", format!("Insert failed: {}", e)));
+                }
+            }
+        }
+        Err(e) => {
+            discard_object(&store, &object_key).await;
+            return HttpResponse::ServiceUnavailable().json(PipelineUploadResponse::failed("metadata", e));
+        }
+    };
+    let document_id = inserted_id.as_object_id().map(|id| id.to_hex()).unwrap_or_else(|| inserted_id.to_string());
+
+    // Stage 4: emit an upload event to RabbitMQ. No event means nothing downstream learns of the
+    // upload, and a client retrying on the error would store it twice, so a failure here removes
+    // the document and the object as stage 3 does.
+    let event = serde_json::json!({
+        "event": "file.uploaded",
+        "document_id": document_id,
+        "object_url": object_url,
+        "filename": file.filename,
+        "content_type": file.content_type,
+        "size": size,
+        "sha256": sha256,
+        "uploaded_at": uploaded_at,
+    });
+    let conn = match amqp_connection().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            discard_upload(&collection, &inserted_id, &store, &object_key).await;
+            return HttpResponse::ServiceUnavailable().json(PipelineUploadResponse::failed("event", e));
+        }
+    };
+    let publish_result = async {
+        let channel = conn.create_channel().await.map_err(|e| format!("Channel creation failed: {}", e))?;
+        channel
+            .queue_declare(
+                &queue,
+                lapin::options::QueueDeclareOptions::default(),
+                lapin::types::FieldTable::default(),
+            )
+            .await
+            .map_err(|e| format!("Queue declare failed: {}", e))?;
+        channel
+            .basic_publish(
+                "",
+                &queue,
+                lapin::options::BasicPublishOptions::default(),
+                event.to_string().as_bytes(),
+                lapin::BasicProperties::default().with_content_type("application/json".into()),
+            )
+            .await
+            .map_err(|e| format!("Publish failed: {}", e))?;
+        Ok::<(), String>(())
+    }
+    .await;
+    let _ = conn.close(0, "Done").await;
+
+    if let Err(e) = publish_result {
+        discard_upload(&collection, &inserted_id, &store, &object_key).await;
+        return HttpResponse::BadGateway().json(PipelineUploadResponse::failed("event", e));
+    }
+
+    HttpResponse::Created().json(PipelineUploadResponse {
+        status: "uploaded".to_string(),
+        object_url: Some(object_url),
+        document_id: Some(document_id),
+        event_queue: Some(queue),
+        size: Some(size),
+        sha256: Some(sha256),
+        stage: None,
+        error: None,
+    })
+}
@@ -0,0 +1,337 @@
+// Idle connection pools for the SQL backends, with warm-up and pre-ping
+//
+// Connections are kept after use (up to POOL_MAX_IDLE) instead of being closed. At startup
+// warm_up() opens POOL_MIN_IDLE connections per backend so the first requests skip connection
+// setup, and connections that sat idle longer than POOL_PRE_PING_IDLE_SECONDS are pinged on
+// checkout so a stale one is replaced instead of failing the request.
+
+use std::collections::VecDeque;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+#[cfg(feature = "mysql")]
+use mysql_async::prelude::Queryable;
+use serde::Serialize;
+
+use crate::{get_env_or, postgres_client, postgres_replica_client, services};
+#[cfg(feature = "mysql")]
+use crate::{mysql_connection, mysql_replica_connection};
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct PoolConfig {
+    pub min_idle: usize,
+    pub max_idle: usize,
+    pub pre_ping: bool,
+    pub pre_ping_idle_seconds: u64,
+}
+
+impl PoolConfig {
+    pub fn from_env() -> Self {
+        let max_idle = get_env_or("POOL_MAX_IDLE", "10").parse().unwrap_or(10);
+        PoolConfig {
+            min_idle: get_env_or("POOL_MIN_IDLE", "2").parse::<usize>().unwrap_or(2).min(max_idle),
+            max_idle,
+            pre_ping: get_env_or("POOL_PRE_PING", "true").parse().unwrap_or(true),
+            pre_ping_idle_seconds: get_env_or("POOL_PRE_PING_IDLE_SECONDS", "30").parse().unwrap_or(30),
+        }
+    }
+
+    // Connections idle at least this long are pinged before being handed out
+    pub fn needs_ping(&self, idle_for: Duration) -> bool {
+        self.pre_ping && idle_for >= Duration::from_secs(self.pre_ping_idle_seconds)
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct WarmupReport {
+    pub opened: usize,
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[async_trait]
+pub trait Manager: Send + Sync + 'static {
+    type Connection: Send + 'static;
+
+    async fn connect(&self) -> Result<Self::Connection, String>;
+
+    async fn ping(&self, conn: &mut Self::Connection) -> bool;
+}
+
+// `address` ("host[:port]") selects a read replica; None is the primary from POSTGRES_HOST
+pub struct PostgresManager {
+    pub address: Option<String>,
+}
+
+#[async_trait]
+impl Manager for PostgresManager {
+    type Connection = tokio_postgres::Client;
+
+    async fn connect(&self) -> Result<Self::Connection, String> {
+        match &self.address {
+            Some(address) => postgres_replica_client(address).await,
+            None => postgres_client().await,
+        }
+    }
+
+    async fn ping(&self, conn: &mut Self::Connection) -> bool {
+        !conn.is_closed() && conn.batch_execute("SELECT 1").await.is_ok()
+    }
+}
+
+// Same addressing as PostgresManager; None is the primary from MYSQL_HOST
+#[cfg(feature = "mysql")]
+pub struct MysqlManager {
+    pub address: Option<String>,
+}
+
+#[cfg(feature = "mysql")]
+#[async_trait]
+impl Manager for MysqlManager {
+    type Connection = mysql_async::Conn;
+
+    async fn connect(&self) -> Result<Self::Connection, String> {
+        match &self.address {
+            Some(address) => mysql_replica_connection(address).await,
+            None => mysql_connection().await,
+        }
+    }
+
+    async fn ping(&self, conn: &mut Self::Connection) -> bool {
+        conn.ping().await.is_ok()
+    }
+}
+
+struct IdleConnection<C> {
+    conn: C,
+    idle_since: Instant,
+}
+
+pub struct Pool<M: Manager> {
+    manager: M,
+    backend: &'static str,
+    config: PoolConfig,
+    idle: Mutex<VecDeque<IdleConnection<M::Connection>>>,
+    opened: AtomicU64,
+    reused: AtomicU64,
+    ping_failures: AtomicU64,
+    warmup: Mutex<Option<WarmupReport>>,
+}
+
+// A process-wide pool, or one that can be dropped once nothing uses it (e.g. a tenant's)
+enum PoolRef<M: Manager> {
+    Static(&'static Pool<M>),
+    Shared(Arc<Pool<M>>),
+}
+
+impl<M: Manager> Deref for PoolRef<M> {
+    type Target = Pool<M>;
+
+    fn deref(&self) -> &Pool<M> {
+        match self {
+            PoolRef::Static(pool) => pool,
+            PoolRef::Shared(pool) => pool,
+        }
+    }
+}
+
+// Checked-out connection; goes back to the idle list when dropped
+pub struct Pooled<M: Manager> {
+    conn: Option<M::Connection>,
+    pool: PoolRef<M>,
+}
+
+impl<M: Manager> Deref for Pooled<M> {
+    type Target = M::Connection;
+
+    fn deref(&self) -> &Self::Target {
+        self.conn.as_ref().expect("pooled connection already returned")
+    }
+}
+
+impl<M: Manager> DerefMut for Pooled<M> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.conn.as_mut().expect("pooled connection already returned")
+    }
+}
+
+impl<M: Manager> Pooled<M> {
+    // Closes the connection instead of returning it, e.g. after an error left it in a bad state
+    pub fn discard(mut self) {
+        self.conn.take();
+    }
+}
+
+impl<M: Manager> Drop for Pooled<M> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool.check_in(conn);
+        }
+    }
+}
+
+impl<M: Manager> Pool<M> {
+    pub fn new(manager: M, backend: &'static str, config: PoolConfig) -> Self {
+        Pool {
+            manager,
+            backend,
+            config,
+            idle: Mutex::new(VecDeque::new()),
+            opened: AtomicU64::new(0),
+            reused: AtomicU64::new(0),
+            ping_failures: AtomicU64::new(0),
+            warmup: Mutex::new(None),
+        }
+    }
+
+    fn take_idle(&self) -> Option<IdleConnection<M::Connection>> {
+        self.idle.lock().ok().and_then(|mut idle| idle.pop_back())
+    }
+
+    fn check_in(&self, conn: M::Connection) {
+        if let Ok(mut idle) = self.idle.lock() {
+            if idle.len() < self.config.max_idle {
+                idle.push_back(IdleConnection { conn, idle_since: Instant::now() });
+            }
+        }
+    }
+
+    async fn open(&self) -> Result<M::Connection, String> {
+        let conn = self.manager.connect().await?;
+        self.opened.fetch_add(1, Ordering::Relaxed);
+        Ok(conn)
+    }
+
+    async fn checkout(&self) -> Result<M::Connection, String> {
+        // Most recently used first, so rarely needed extras are the ones that go stale
+        while let Some(mut idle) = self.take_idle() {
+            if self.config.needs_ping(idle.idle_since.elapsed()) && !self.manager.ping(&mut idle.conn).await {
+                self.ping_failures.fetch_add(1, Ordering::Relaxed);
+                log::info!("Discarding stale {} connection after failed pre-ping", self.backend);
+                continue;
+            }
+            self.reused.fetch_add(1, Ordering::Relaxed);
+            return Ok(idle.conn);
+        }
+        self.open().await
+    }
+
+    pub async fn get(&'static self) -> Result<Pooled<M>, String> {
+        let conn = self.checkout().await?;
+        Ok(Pooled { conn: Some(conn), pool: PoolRef::Static(self) })
+    }
+
+    // For pools that aren't statics; the connection keeps the pool alive until it is returned
+    pub async fn get_shared(self: &Arc<Self>) -> Result<Pooled<M>, String> {
+        let conn = self.checkout().await?;
+        Ok(Pooled { conn: Some(conn), pool: PoolRef::Shared(self.clone()) })
+    }
+
+    pub async fn warm_up(&self) -> WarmupReport {
+        let started = Instant::now();
+        let timeout = Duration::from_secs(get_env_or("POOL_WARMUP_TIMEOUT_SECONDS", "10").parse().unwrap_or(10));
+        let mut opened = 0;
+        let mut error = None;
+
+        for _ in 0..self.config.min_idle {
+            match tokio::time::timeout(timeout, self.open()).await {
+                Ok(Ok(conn)) => {
+                    self.check_in(conn);
+                    opened += 1;
+                }
+                Ok(Err(e)) => {
+                    error = Some(e);
+                    break;
+                }
+                Err(_) => {
+                    error = Some(format!("Timed out after {}s", timeout.as_secs()));
+                    break;
+                }
+            }
+        }
+
+        let report = WarmupReport { opened, duration_ms: started.elapsed().as_millis() as u64, error };
+        if let Ok(mut warmup) = self.warmup.lock() {
+            *warmup = Some(report.clone());
+        }
+        report
+    }
+
+    pub fn info(&self) -> serde_json::Value {
+        serde_json::json!({
+            "config": self.config,
+            "idle": self.idle.lock().map(|idle| idle.len()).unwrap_or(0),
+            "opened": self.opened.load(Ordering::Relaxed),
+            "reused": self.reused.load(Ordering::Relaxed),
+            "ping_failures": self.ping_failures.load(Ordering::Relaxed),
+            "warmup": self.warmup.lock().ok().and_then(|w| w.clone())
+        })
+    }
+}
+
+lazy_static! {
+    pub static ref POSTGRES_POOL: Pool<PostgresManager> =
+        Pool::new(PostgresManager { address: None }, "postgres", PoolConfig::from_env());
+}
+
+#[cfg(feature = "mysql")]
+lazy_static! {
+    pub static ref MYSQL_POOL: Pool<MysqlManager> =
+        Pool::new(MysqlManager { address: None }, "mysql", PoolConfig::from_env());
+}
+
+pub async fn postgres() -> Result<Pooled<PostgresManager>, String> {
+    POSTGRES_POOL.get().await
+}
+
+#[cfg(feature = "mysql")]
+pub async fn mysql() -> Result<Pooled<MysqlManager>, String> {
+    MYSQL_POOL.get().await
+}
+
+#[cfg(feature = "mysql")]
+async fn warm_up_mysql() -> Option<WarmupReport> {
+    if services::is_enabled("mysql") {
+        Some(MYSQL_POOL.warm_up().await)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(feature = "mysql"))]
+async fn warm_up_mysql() -> Option<WarmupReport> {
+    None
+}
+
+// Opens POOL_MIN_IDLE connections to each enabled SQL backend; failures are logged, not fatal
+pub async fn warm_up() {
+    let (postgres, mysql) = tokio::join!(
+        async {
+            if services::is_enabled("postgres") {
+                Some(POSTGRES_POOL.warm_up().await)
+            } else {
+                None
+            }
+        },
+        warm_up_mysql(),
+    );
+    for (backend, report) in [("postgres", postgres), ("mysql", mysql)] {
+        match report {
+            Some(WarmupReport { error: Some(e), opened, .. }) => {
+                log::warn!("{} pool warm-up opened {} connection(s) before failing: {}", backend, opened, e)
+            }
+            Some(report) => log::info!(
+                "{} pool warmed up with {} connection(s) in {}ms",
+                backend,
+                report.opened,
+                report.duration_ms
+            ),
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,1442 @@
+// PostgreSQL example handlers beyond the basic query endpoint
+
+use std::collections::HashMap;
+#[cfg(feature = "parquet")]
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "parquet")]
+use std::sync::{Arc, Mutex};
+
+use actix_multipart::Multipart;
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+#[cfg(feature = "parquet")]
+use arrow_array::builder::{Int64Builder, StringBuilder, TimestampMicrosecondBuilder};
+#[cfg(feature = "parquet")]
+use arrow_array::{ArrayRef, RecordBatch};
+#[cfg(feature = "parquet")]
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use futures_util::StreamExt;
+#[cfg(feature = "parquet")]
+use parquet::arrow::ArrowWriter;
+#[cfg(feature = "parquet")]
+use parquet::basic::Compression;
+#[cfg(feature = "parquet")]
+use parquet::file::properties::WriterProperties;
+use serde::{Deserialize, Serialize};
+use tokio_postgres::types::ToSql;
+
+use crate::etag;
+use crate::pagination::{AfterId, Page, PageQuery, PageRequest};
+use crate::pipeline::read_file_field;
+use crate::pool::{self, Pooled, PostgresManager};
+use crate::query_cache;
+use crate::sql_router::SqlRouter;
+use crate::{get_env_or, postgres_client};
+use crate::sql_timing::timed_query;
+
+// Queries that may be run under EXPLAIN ANALYZE (ANALYZE executes them, so only read-only SQL belongs here)
+const EXPLAINABLE_QUERIES: &[(&str, &str, &str)] = &[
+    (
+        "hello_query",
+        "Constant projection used by /examples/database/postgres/query",
+        "SELECT NOW()::text, 'Hello from PostgreSQL!' as message",
+    ),
+    (
+        "series_filter",
+        "Sequential scan with a filter over a generated series",
+        "SELECT g FROM generate_series(1, 100000) AS g WHERE g % 997 = 0",
+    ),
+    (
+        "series_aggregate",
+        "Hash aggregate with sort over a generated series",
+        "SELECT g % 10 AS bucket, COUNT(*) FROM generate_series(1, 100000) AS g GROUP BY 1 ORDER BY 1",
+    ),
+    (
+        "table_stats",
+        "Catalog view join used to inspect table sizes",
+        "SELECT relname, n_live_tup FROM pg_stat_user_tables ORDER BY n_live_tup DESC LIMIT 10",
+    ),
+];
+
+fn find_explainable(name: &str) -> Option<&'static (&'static str, &'static str, &'static str)> {
+    EXPLAINABLE_QUERIES.iter().find(|(query_name, _, _)| *query_name == name)
+}
+
+pub async fn list_explainable_queries() -> impl Responder {
+    let queries: Vec<serde_json::Value> = EXPLAINABLE_QUERIES
+        .iter()
+        .map(|(name, description, sql)| serde_json::json!({
+            "name": name,
+            "description": description,
+            "sql": sql
+        }))
+        .collect();
+
+    HttpResponse::Ok().json(serde_json::json!({ "queries": queries }))
+}
+
+pub async fn explain_query(path: web::Path<String>) -> impl Responder {
+    let query_name = path.into_inner();
+
+    let (name, _, sql) = match find_explainable(&query_name) {
+        Some(query) => *query,
+        None => {
+            let valid: Vec<&str> = EXPLAINABLE_QUERIES.iter().map(|(n, _, _)| *n).collect();
+            return HttpResponse::NotFound().json(serde_json::json!({
+                "status": "error",
+                "query_name": query_name,
+                "error": format!("Unknown query. Must be one of: {}", valid.join(", "))
+            }));
+        }
+    };
+
+    let client = match postgres_client().await {
+        Ok(client) => client,
+        Err(e) => {
+            return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                "status": "error",
+                "query_name": name,
+                "error": e
+            }))
+        }
+    };
+
+    let explain_sql = format!("EXPLAIN (ANALYZE, BUFFERS, FORMAT JSON) {}", sql);
+    match timed_query("postgres", "explain", &explain_sql, 0, client.query_one(explain_sql.as_str(), &[])).await {
+        Ok(row) => {
+            let output: serde_json::Value = row.get(0);
+            // FORMAT JSON returns a one-element array holding the plan and timings
+            let report = output.get(0).cloned().unwrap_or(serde_json::Value::Null);
+            HttpResponse::Ok().json(serde_json::json!({
+                "status": "success",
+                "query_name": name,
+                "sql": sql,
+                "planning_time_ms": report["Planning Time"],
+                "execution_time_ms": report["Execution Time"],
+                "plan": report["Plan"]
+            }))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "status": "error",
+            "query_name": name,
+            "error": format!("EXPLAIN failed: {}", e)
+        })),
+    }
+}
+
+pub const ITEMS_TABLE: &str = "items";
+pub const ITEMS_AUDIT_TABLE: &str = "items_audit";
+
+// Flush streamed export output once a chunk reaches this size
+const EXPORT_CHUNK_BYTES: usize = 64 * 1024;
+
+// Creates the example items table and fills it with sample rows the first time it is used
+pub async fn ensure_items_table(client: &tokio_postgres::Client) -> Result<(), String> {
+    client
+        .batch_execute(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                id BIGSERIAL PRIMARY KEY,
+                name TEXT NOT NULL,
+                category TEXT NOT NULL,
+                price_cents BIGINT NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                deleted_at TIMESTAMPTZ,
+                version BIGINT NOT NULL DEFAULT 1
+            );
+            ALTER TABLE {table} ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+            ALTER TABLE {table} ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
+            CREATE TABLE IF NOT EXISTS {audit} (
+                id BIGSERIAL PRIMARY KEY,
+                item_id BIGINT NOT NULL,
+                action TEXT NOT NULL,
+                before JSONB,
+                after JSONB,
+                changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+            );
+            CREATE INDEX IF NOT EXISTS {audit}_item_idx ON {audit} (item_id, id)",
+            table = ITEMS_TABLE,
+            audit = ITEMS_AUDIT_TABLE
+        ))
+        .await
+        .map_err(|e| format!("Schema setup failed: {}", e))?;
+
+    let sample_rows: i64 = get_env_or("ITEMS_SAMPLE_ROWS", "10000").parse().unwrap_or(10_000);
+    let populate = format!(
+        "INSERT INTO {table} (name, category, price_cents)
+         SELECT 'Item ' || g, (ARRAY['books', 'games', 'tools', 'garden'])[1 + g % 4], 100 + (g * 7919) % 100000
+         FROM generate_series(1, $1::bigint) AS g
+         WHERE NOT EXISTS (SELECT 1 FROM {table})",
+        table = ITEMS_TABLE
+    );
+    client
+        .execute(populate.as_str(), &[&sample_rows])
+        .await
+        .map_err(|e| format!("Sample data setup failed: {}", e))?;
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    format: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ExportFormat {
+    Ndjson,
+    Csv,
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl ExportFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Ndjson => "application/x-ndjson",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Csv => "csv",
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+
+    // Parquet stores created_at as a timestamp column, so it is read as epoch microseconds
+    fn created_at_column(self) -> &'static str {
+        match self {
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => "(EXTRACT(EPOCH FROM created_at) * 1000000)::bigint",
+            _ => "created_at::text",
+        }
+    }
+}
+
+// ============================================================================
+// Parquet encoding (cargo feature `parquet`)
+// ============================================================================
+
+// Rows per Parquet row group; each group is encoded and sent as soon as it fills
+#[cfg(feature = "parquet")]
+const PARQUET_ROW_GROUP_ROWS: usize = 10_000;
+
+// Write target whose bytes can be drained while the ArrowWriter still owns it
+#[cfg(feature = "parquet")]
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+#[cfg(feature = "parquet")]
+impl SharedBuffer {
+    fn take(&self) -> Vec<u8> {
+        self.0.lock().map(|mut buf| std::mem::take(&mut *buf)).unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl Write for SharedBuffer {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        let mut buf = self.0.lock().map_err(|_| std::io::Error::other("Parquet buffer poisoned"))?;
+        buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "parquet")]
+pub fn items_arrow_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("category", DataType::Utf8, false),
+        Field::new("price_cents", DataType::Int64, false),
+        Field::new("created_at", DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())), false),
+    ]))
+}
+
+// Buffers items into Arrow columns and writes one Snappy-compressed row group per batch.
+// The footer only exists once finish() runs, so a truncated download is not a valid file.
+#[cfg(feature = "parquet")]
+pub struct ParquetEncoder {
+    schema: SchemaRef,
+    writer: ArrowWriter<SharedBuffer>,
+    output: SharedBuffer,
+    ids: Int64Builder,
+    names: StringBuilder,
+    categories: StringBuilder,
+    prices: Int64Builder,
+    created_at: TimestampMicrosecondBuilder,
+    rows: usize,
+}
+
+#[cfg(feature = "parquet")]
+impl ParquetEncoder {
+    pub fn new() -> Result<Self, String> {
+        let schema = items_arrow_schema();
+        let output = SharedBuffer::default();
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_max_row_group_size(PARQUET_ROW_GROUP_ROWS)
+            .build();
+        let writer = ArrowWriter::try_new(output.clone(), schema.clone(), Some(props))
+            .map_err(|e| format!("Parquet writer setup failed: {}", e))?;
+        Ok(ParquetEncoder {
+            schema,
+            writer,
+            output,
+            ids: Int64Builder::new(),
+            names: StringBuilder::new(),
+            categories: StringBuilder::new(),
+            prices: Int64Builder::new(),
+            created_at: TimestampMicrosecondBuilder::new().with_timezone("UTC"),
+            rows: 0,
+        })
+    }
+
+    // Adds a row, encoding a row group when the batch is full
+    pub fn push(
+        &mut self,
+        id: i64,
+        name: &str,
+        category: &str,
+        price_cents: i64,
+        created_at_micros: i64,
+    ) -> Result<(), String> {
+        self.ids.append_value(id);
+        self.names.append_value(name);
+        self.categories.append_value(category);
+        self.prices.append_value(price_cents);
+        self.created_at.append_value(created_at_micros);
+        self.rows += 1;
+        if self.rows >= PARQUET_ROW_GROUP_ROWS {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    fn write_batch(&mut self) -> Result<(), String> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.ids.finish()),
+            Arc::new(self.names.finish()),
+            Arc::new(self.categories.finish()),
+            Arc::new(self.prices.finish()),
+            Arc::new(self.created_at.finish()),
+        ];
+        let batch = RecordBatch::try_new(self.schema.clone(), columns)
+            .map_err(|e| format!("Arrow batch failed: {}", e))?;
+        self.rows = 0;
+        self.writer.write(&batch).map_err(|e| format!("Parquet write failed: {}", e))?;
+        self.writer.flush().map_err(|e| format!("Parquet write failed: {}", e))
+    }
+
+    // Encoded bytes not yet handed out
+    pub fn take_output(&self) -> Vec<u8> {
+        self.output.take()
+    }
+
+    // Writes the last row group and the footer, returning the remaining bytes
+    pub fn finish(mut self) -> Result<Vec<u8>, String> {
+        self.write_batch()?;
+        self.writer.close().map_err(|e| format!("Parquet close failed: {}", e))?;
+        Ok(self.output.take())
+    }
+}
+
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn render_row(row: &tokio_postgres::Row, format: ExportFormat, out: &mut String) {
+    let id: i64 = row.get(0);
+    let name: &str = row.get(1);
+    let category: &str = row.get(2);
+    let price_cents: i64 = row.get(3);
+    let created_at: &str = row.get(4);
+
+    match format {
+        ExportFormat::Ndjson => {
+            out.push_str(
+                &serde_json::json!({
+                    "id": id,
+                    "name": name,
+                    "category": category,
+                    "price_cents": price_cents,
+                    "created_at": created_at
+                })
+                .to_string(),
+            );
+        }
+        ExportFormat::Csv => {
+            out.push_str(&format!(
+                "{},{},{},{},{}",
+                id,
+                csv_field(name),
+                csv_field(category),
+                price_cents,
+                csv_field(created_at)
+            ));
+        }
+        // Parquet rows go through ParquetEncoder instead
+        #[cfg(feature = "parquet")]
+        ExportFormat::Parquet => return,
+    }
+    out.push('\n');
+}
+
+pub async fn export_items(req: HttpRequest, query: web::Query<ExportQuery>) -> impl Responder {
+    let format = match query.format.as_deref().unwrap_or("ndjson") {
+        "ndjson" => ExportFormat::Ndjson,
+        "csv" => ExportFormat::Csv,
+        #[cfg(feature = "parquet")]
+        "parquet" => ExportFormat::Parquet,
+        #[cfg(not(feature = "parquet"))]
+        "parquet" => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "status": "error",
+                "error": "Parquet export is not compiled into this build (cargo feature 'parquet')"
+            }))
+        }
+        other => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "status": "error",
+                "error": format!("Unsupported format '{}'. Must be one of: ndjson, csv, parquet", other)
+            }))
+        }
+    };
+
+    let sql = format!(
+        "SELECT id, name, category, price_cents, {} FROM {} WHERE deleted_at IS NULL ORDER BY id",
+        format.created_at_column(),
+        ITEMS_TABLE
+    );
+    let client = match items_connection(&SqlRouter::for_request(&req), &sql).await {
+        Ok(client) => client,
+        Err(resp) => return resp,
+    };
+    // query_raw yields rows as they arrive instead of collecting the whole result set
+    let rows = match client.query_raw(sql.as_str(), std::iter::empty::<&dyn ToSql>()).await {
+        Ok(rows) => Box::pin(rows),
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "status": "error",
+                "error": format!("Query failed: {}", e)
+            }))
+        }
+    };
+
+    #[cfg(feature = "parquet")]
+    let parquet = match format {
+        ExportFormat::Parquet => match ParquetEncoder::new() {
+            Ok(encoder) => Some(encoder),
+            Err(e) => {
+                return HttpResponse::InternalServerError().json(serde_json::json!({ "status": "error", "error": e }))
+            }
+        },
+        _ => None,
+    };
+    let header = match format {
+        ExportFormat::Csv => b"id,name,category,price_cents,created_at\n".to_vec(),
+        _ => Vec::new(),
+    };
+
+    // The body is pulled only as fast as the client reads it, and the next rows are
+    // only read from PostgreSQL when the body asks for more, so memory stays bounded.
+    // The client travels with the stream to keep the connection open until the end.
+    struct ExportState {
+        _client: Pooled<PostgresManager>,
+        rows: Pin<Box<tokio_postgres::RowStream>>,
+        #[cfg(feature = "parquet")]
+        parquet: Option<ParquetEncoder>,
+        buffer: Vec<u8>,
+        done: bool,
+    }
+
+    impl ExportState {
+        fn encode(&mut self, row: &tokio_postgres::Row, format: ExportFormat) -> Result<Vec<u8>, String> {
+            #[cfg(feature = "parquet")]
+            if let Some(encoder) = self.parquet.as_mut() {
+                return encoder
+                    .push(row.get(0), row.get(1), row.get(2), row.get(3), row.get(4))
+                    .map(|()| encoder.take_output());
+            }
+            let mut line = String::new();
+            render_row(row, format, &mut line);
+            Ok(line.into_bytes())
+        }
+
+        // Bytes still owed once the rows run out: the Parquet footer, nothing for text formats
+        fn finish(&mut self) -> Result<Vec<u8>, String> {
+            #[cfg(feature = "parquet")]
+            if let Some(encoder) = self.parquet.take() {
+                return encoder.finish();
+            }
+            Ok(Vec::new())
+        }
+    }
+
+    let state = ExportState {
+        _client: client,
+        rows,
+        #[cfg(feature = "parquet")]
+        parquet,
+        buffer: header,
+        done: false,
+    };
+    let body = futures_util::stream::unfold(state, move |mut state| async move {
+        if state.done {
+            return None;
+        }
+        while state.buffer.len() < EXPORT_CHUNK_BYTES {
+            let encoded = match state.rows.next().await {
+                Some(Ok(row)) => state.encode(&row, format),
+                Some(Err(e)) => Err(e.to_string()),
+                None => {
+                    state.done = true;
+                    state.finish()
+                }
+            };
+            match encoded {
+                Ok(bytes) => state.buffer.extend_from_slice(&bytes),
+                Err(e) => {
+                    log::error!("Items export aborted: {}", e);
+                    state.done = true;
+                    return Some((Err(std::io::Error::other(e)), state));
+                }
+            }
+            if state.done {
+                break;
+            }
+        }
+        if state.buffer.is_empty() {
+            return None;
+        }
+        let chunk = web::Bytes::from(std::mem::take(&mut state.buffer));
+        Some((Ok::<_, std::io::Error>(chunk), state))
+    });
+
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"items.{}\"", format.extension()),
+        ))
+        .streaming(body)
+}
+
+// ============================================================================
+// Cursor streaming (DECLARE CURSOR / FETCH)
+// ============================================================================
+
+const DEFAULT_FETCH_SIZE: usize = 1_000;
+const MAX_FETCH_SIZE: usize = 10_000;
+const STREAM_CURSOR: &str = "items_stream";
+
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    fetch_size: Option<usize>,
+}
+
+// Per-stream fetch statistics, sent as the last NDJSON line
+#[derive(Serialize, Default)]
+pub struct FetchStats {
+    pub fetch_size: usize,
+    pub rows: u64,
+    pub batches: u64,
+    pub elapsed_ms: u64,
+    pub max_batch_ms: u64,
+    pub max_batch_bytes: usize,
+}
+
+impl FetchStats {
+    pub fn record_batch(&mut self, rows: usize, bytes: usize, elapsed: std::time::Duration) {
+        self.rows += rows as u64;
+        self.batches += 1;
+        self.max_batch_ms = self.max_batch_ms.max(elapsed.as_millis() as u64);
+        self.max_batch_bytes = self.max_batch_bytes.max(bytes);
+    }
+}
+
+// Streams items as NDJSON through a server-side cursor (a named portal), fetching fetch_size rows
+// per round trip. Small fetch sizes keep memory flat at the cost of more round trips; large ones
+// do the opposite. The final line is {"summary": {...}} with the observed batch timings.
+pub async fn stream_items(req: HttpRequest, query: web::Query<StreamQuery>) -> impl Responder {
+    let fetch_size = query.fetch_size.unwrap_or(DEFAULT_FETCH_SIZE);
+    if fetch_size == 0 || fetch_size > MAX_FETCH_SIZE {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "status": "error",
+            "error": format!("fetch_size must be between 1 and {}", MAX_FETCH_SIZE)
+        }));
+    }
+
+    // Routed by the cursor's query, a plain read
+    let select = format!("SELECT {} FROM {} WHERE deleted_at IS NULL ORDER BY id", ITEM_COLUMNS, ITEMS_TABLE);
+    let client = match items_connection(&SqlRouter::for_request(&req), &select).await {
+        Ok(client) => client,
+        Err(resp) => return resp,
+    };
+
+    // Cursors only live inside a transaction; it stays open until the last batch is sent
+    let declare = format!("BEGIN READ ONLY; DECLARE {} NO SCROLL CURSOR FOR {}", STREAM_CURSOR, select);
+    if let Err(e) = client.batch_execute(&declare).await {
+        client.discard();
+        return HttpResponse::InternalServerError().json(serde_json::json!({
+            "status": "error",
+            "error": format!("Declare cursor failed: {}", e)
+        }));
+    }
+
+    struct CursorState {
+        client: Option<Pooled<PostgresManager>>,
+        fetch_sql: String,
+        stats: FetchStats,
+        started: std::time::Instant,
+        done: bool,
+        // Set once the cursor's transaction is committed and the connection can be reused
+        closed: bool,
+    }
+
+    // A stream cut short (client gone, fetch failed) leaves the transaction open, so that
+    // connection is closed rather than handed back to the pool
+    impl Drop for CursorState {
+        fn drop(&mut self) {
+            if let Some(client) = self.client.take().filter(|_| !self.closed) {
+                client.discard();
+            }
+        }
+    }
+
+    let state = CursorState {
+        client: Some(client),
+        fetch_sql: format!("FETCH FORWARD {} FROM {}", fetch_size, STREAM_CURSOR),
+        stats: FetchStats { fetch_size, ..FetchStats::default() },
+        started: std::time::Instant::now(),
+        done: false,
+        closed: false,
+    };
+    let body = futures_util::stream::unfold(state, |mut state| async move {
+        if state.done {
+            return None;
+        }
+
+        let batch_started = std::time::Instant::now();
+        let client = state.client.as_ref()?;
+        let rows = match client.query(state.fetch_sql.as_str(), &[]).await {
+            Ok(rows) => rows,
+            Err(e) => {
+                log::error!("Items stream aborted: {}", e);
+                state.done = true;
+                return Some((Err(std::io::Error::other(e.to_string())), state));
+            }
+        };
+
+        let mut chunk = String::new();
+        for row in &rows {
+            render_row(row, ExportFormat::Ndjson, &mut chunk);
+        }
+        if rows.is_empty() {
+            // Cursor exhausted: close the transaction and finish with the summary line
+            match client.batch_execute(&format!("CLOSE {}; COMMIT", STREAM_CURSOR)).await {
+                Ok(()) => state.closed = true,
+                Err(e) => log::warn!("Closing items cursor failed: {}", e),
+            }
+            state.stats.elapsed_ms = state.started.elapsed().as_millis() as u64;
+            chunk = format!("{}\n", serde_json::json!({ "summary": state.stats }));
+            state.done = true;
+        } else {
+            state.stats.record_batch(rows.len(), chunk.len(), batch_started.elapsed());
+        }
+        Some((Ok::<_, std::io::Error>(web::Bytes::from(chunk)), state))
+    });
+
+    HttpResponse::Ok()
+        .content_type(ExportFormat::Ndjson.content_type())
+        .insert_header(("X-Fetch-Size", fetch_size.to_string()))
+        .streaming(body)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Item {
+    pub id: i64,
+    pub name: String,
+    pub category: String,
+    pub price_cents: i64,
+    pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
+    // Bumped by every write; clients send it back to prove they saw the latest row
+    pub version: i64,
+}
+
+pub const ITEM_COLUMNS: &str = "id, name, category, price_cents, created_at::text, deleted_at::text, version";
+
+impl Item {
+    pub fn from_row(row: &tokio_postgres::Row) -> Self {
+        Item {
+            id: row.get(0),
+            name: row.get(1),
+            category: row.get(2),
+            price_cents: row.get(3),
+            created_at: row.get(4),
+            deleted_at: row.get(5),
+            version: row.get(6),
+        }
+    }
+}
+
+// SQL and parameters for one page of live items
+fn items_page_query(request: &PageRequest<AfterId>) -> (String, [i64; 2]) {
+    // Fetch one extra row so the page knows whether another one follows
+    let fetch = (request.limit() + 1) as i64;
+    match request {
+        PageRequest::Offset { offset, .. } => (
+            format!(
+                "SELECT {} FROM {} WHERE deleted_at IS NULL ORDER BY id LIMIT $1 OFFSET $2",
+                ITEM_COLUMNS, ITEMS_TABLE
+            ),
+            [fetch, *offset as i64],
+        ),
+        PageRequest::Cursor { after, .. } => (
+            format!(
+                "SELECT {} FROM {} WHERE deleted_at IS NULL AND id > $1 ORDER BY id LIMIT $2",
+                ITEM_COLUMNS, ITEMS_TABLE
+            ),
+            [after.as_ref().map(|p| p.id).unwrap_or(0), fetch],
+        ),
+    }
+}
+
+async fn fetch_items_page(
+    client: &tokio_postgres::Client,
+    request: &PageRequest<AfterId>,
+) -> Result<(Vec<Item>, u64), String> {
+    let count_sql = format!("SELECT COUNT(*) FROM {} WHERE deleted_at IS NULL", ITEMS_TABLE);
+    let total: i64 = timed_query("postgres", "count_items", &count_sql, 0, client.query_one(count_sql.as_str(), &[]))
+        .await
+        .map_err(|e| format!("Query failed: {}", e))?
+        .get(0);
+
+    let (sql, [first, second]) = items_page_query(request);
+    let rows = timed_query("postgres", "list_items", &sql, 2, client.query(sql.as_str(), &[&first, &second]))
+        .await
+        .map_err(|e| format!("Query failed: {}", e))?;
+
+    Ok((rows.iter().map(Item::from_row).collect(), total as u64))
+}
+
+pub async fn list_items(req: HttpRequest, query: web::Query<PageQuery>) -> impl Responder {
+    let request = match query.resolve::<AfterId>() {
+        Ok(request) => request,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "status": "error", "error": e })),
+    };
+
+    // Cached as rendered JSON; Page itself is write-only
+    let (sql, params) = items_page_query(&request);
+    let params: Vec<String> = params.iter().map(i64::to_string).collect();
+    let (load_sql, router) = (sql.clone(), SqlRouter::for_request(&req));
+    let page = query_cache::cached("list_items", ITEMS_TABLE, &sql, &params, || async move {
+        let client = items_connection(&router, &load_sql).await?;
+        let (items, total) = fetch_items_page(&client, &request)
+            .await
+            .map_err(|e| item_error(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        let page = Page::from_rows(&request, items, Some(total), |item| AfterId { id: item.id });
+        serde_json::to_value(page).map_err(|e| {
+            item_error(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Serialization failed: {}", e))
+        })
+    })
+    .await;
+
+    match page {
+        Ok((page, cache)) => HttpResponse::Ok().insert_header(("X-SQL-Cache", cache.as_str())).json(page),
+        Err(resp) => resp,
+    }
+}
+
+pub async fn get_item(req: HttpRequest, path: web::Path<i64>) -> impl Responder {
+    let id = path.into_inner();
+
+    // Misses are cached too; a create bumps the generation like any other write
+    let sql = format!("SELECT {} FROM {} WHERE id = $1 AND deleted_at IS NULL", ITEM_COLUMNS, ITEMS_TABLE);
+    let load_sql = sql.clone();
+    let router = SqlRouter::for_request(&req);
+    let item = query_cache::cached("get_item", ITEMS_TABLE, &sql, &[id.to_string()], || async move {
+        let client = items_connection(&router, &load_sql).await?;
+        timed_query("postgres", "get_item", &load_sql, 1, client.query_opt(load_sql.as_str(), &[&id]))
+            .await
+            .map(|row| row.as_ref().map(Item::from_row))
+            .map_err(|e| item_error(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Query failed: {}", e)))
+    })
+    .await;
+
+    match item {
+        Ok((Some(item), cache)) => {
+            let body = match serde_json::to_vec(&item) {
+                Ok(body) => body,
+                Err(e) => {
+                    return HttpResponse::InternalServerError().json(serde_json::json!({
+                        "status": "error",
+                        "error": format!("Serialization failed: {}", e)
+                    }))
+                }
+            };
+            let tag = etag::etag_for(&body);
+            if etag::is_not_modified(&req, &tag) {
+                return etag::not_modified(&tag);
+            }
+            HttpResponse::Ok()
+                .content_type("application/json")
+                .insert_header((header::ETAG, tag))
+                .insert_header(("X-SQL-Cache", cache.as_str()))
+                .body(body)
+        }
+        Ok((None, _)) => HttpResponse::NotFound().json(serde_json::json!({
+            "status": "not_found",
+            "id": id
+        })),
+        Err(resp) => resp,
+    }
+}
+
+// ============================================================================
+// Writes with soft delete and an audit trail
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct ItemInput {
+    name: Option<String>,
+    category: Option<String>,
+    price_cents: Option<i64>,
+    // Expected current version; required for updates
+    version: Option<i64>,
+}
+
+// Checks the fields that are present; `require_all` is set for creates
+pub fn validate_item_input(
+    name: Option<&str>,
+    category: Option<&str>,
+    price_cents: Option<i64>,
+    require_all: bool,
+) -> Result<(), String> {
+    if require_all && (name.is_none() || category.is_none() || price_cents.is_none()) {
+        return Err("name, category, and price_cents are required".to_string());
+    }
+    if let Some(name) = name {
+        if name.trim().is_empty() || name.chars().count() > MAX_NAME_LEN {
+            return Err(format!("name must be 1-{} characters", MAX_NAME_LEN));
+        }
+    }
+    if category.is_some_and(|c| c.trim().is_empty()) {
+        return Err("category must not be empty".to_string());
+    }
+    if price_cents.is_some_and(|p| p < 0) {
+        return Err("price_cents must not be negative".to_string());
+    }
+    Ok(())
+}
+
+// Top-level fields whose values differ between two audit images
+pub fn changed_fields(before: Option<&serde_json::Value>, after: Option<&serde_json::Value>) -> Vec<String> {
+    let empty = serde_json::Map::new();
+    let before = before.and_then(|v| v.as_object()).unwrap_or(&empty);
+    let after = after.and_then(|v| v.as_object()).unwrap_or(&empty);
+    let mut fields: Vec<String> = before
+        .keys()
+        .chain(after.keys())
+        .filter(|key| before.get(*key) != after.get(*key))
+        .cloned()
+        .collect();
+    fields.sort();
+    fields.dedup();
+    fields
+}
+
+fn item_image(item: &Item) -> serde_json::Value {
+    serde_json::to_value(item).unwrap_or(serde_json::Value::Null)
+}
+
+// Written in the same transaction as the change, so history and data never disagree
+async fn record_audit(
+    tx: &tokio_postgres::Transaction<'_>,
+    item_id: i64,
+    action: &str,
+    before: Option<&Item>,
+    after: Option<&Item>,
+) -> Result<(), String> {
+    let sql = format!("INSERT INTO {} (item_id, action, before, after) VALUES ($1, $2, $3, $4)", ITEMS_AUDIT_TABLE);
+    tx.execute(sql.as_str(), &[&item_id, &action, &before.map(item_image), &after.map(item_image)])
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("Audit insert failed: {}", e))
+}
+
+fn item_error(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+static ITEMS_TABLE_READY: AtomicBool = AtomicBool::new(false);
+
+// Schema setup and sample rows are writes, so they run on the primary, once per process;
+// replicas get the tables by replication
+async fn prepare_items_table() -> Result<(), HttpResponse> {
+    if ITEMS_TABLE_READY.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let conn = pool::postgres()
+        .await
+        .map_err(|e| item_error(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e))?;
+    ensure_items_table(&conn)
+        .await
+        .map_err(|e| item_error(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    ITEMS_TABLE_READY.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+// A connection for `sql` through the request's router: a replica for reads, the primary for
+// writes and for everything after the request's first write
+async fn items_connection(router: &SqlRouter, sql: &str) -> Result<Pooled<PostgresManager>, HttpResponse> {
+    prepare_items_table().await?;
+    router
+        .postgres(sql)
+        .await
+        .map(|(conn, _)| conn)
+        .map_err(|e| item_error(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e))
+}
+
+pub async fn create_item(req: HttpRequest, body: web::Json<ItemInput>) -> impl Responder {
+    if let Err(e) = validate_item_input(body.name.as_deref(), body.category.as_deref(), body.price_cents, true) {
+        return item_error(actix_web::http::StatusCode::BAD_REQUEST, e);
+    }
+    let sql = format!(
+        "INSERT INTO {} (name, category, price_cents) VALUES ($1, $2, $3) RETURNING {}",
+        ITEMS_TABLE, ITEM_COLUMNS
+    );
+    let mut client = match items_connection(&SqlRouter::for_request(&req), &sql).await {
+        Ok(client) => client,
+        Err(resp) => return resp,
+    };
+
+    let result = async {
+        let tx = client.transaction().await.map_err(|e| format!("Transaction failed: {}", e))?;
+        let row = tx
+            .query_one(sql.as_str(), &[&body.name, &body.category, &body.price_cents])
+            .await
+            .map_err(|e| format!("Insert failed: {}", e))?;
+        let item = Item::from_row(&row);
+        record_audit(&tx, item.id, "create", None, Some(&item)).await?;
+        tx.commit().await.map_err(|e| format!("Commit failed: {}", e))?;
+        Ok::<_, String>(item)
+    }
+    .await;
+
+    if result.is_ok() {
+        query_cache::invalidate(ITEMS_TABLE, "write").await;
+    }
+    match result {
+        Ok(item) => HttpResponse::Created().json(item),
+        Err(e) => item_error(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+// Outcome of a write guarded by the version column
+enum VersionedWrite {
+    Written(Item),
+    NotFound,
+    // The caller's version is stale; carries the row as it is now
+    Conflict(Item),
+}
+
+#[derive(Deserialize)]
+pub struct VersionQuery {
+    version: Option<i64>,
+}
+
+fn conflict_response(expected: i64, current: Item) -> HttpResponse {
+    HttpResponse::Conflict().json(serde_json::json!({
+        "status": "conflict",
+        "error": format!(
+            "Item {} has changed since it was read: expected version {}, current version is {}",
+            current.id, expected, current.version
+        ),
+        "expected_version": expected,
+        "current": current
+    }))
+}
+
+async fn find_item(tx: &tokio_postgres::Transaction<'_>, id: i64) -> Result<Option<Item>, String> {
+    let sql = format!("SELECT {} FROM {} WHERE id = $1", ITEM_COLUMNS, ITEMS_TABLE);
+    tx.query_opt(sql.as_str(), &[&id])
+        .await
+        .map(|row| row.as_ref().map(Item::from_row))
+        .map_err(|e| format!("Query failed: {}", e))
+}
+
+// Optimistic locking: no row lock is held between the read and the write. The UPDATE only
+// matches while the version is still the one the client read, so a concurrent writer makes it
+// match nothing and the client gets 409 with the current row to merge against.
+pub async fn update_item(req: HttpRequest, path: web::Path<i64>, body: web::Json<ItemInput>) -> impl Responder {
+    let id = path.into_inner();
+    if let Err(e) = validate_item_input(body.name.as_deref(), body.category.as_deref(), body.price_cents, false) {
+        return item_error(actix_web::http::StatusCode::BAD_REQUEST, e);
+    }
+    let Some(expected) = body.version else {
+        return item_error(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            "version is required: send the version from your last read of the item".to_string(),
+        );
+    };
+    let sql = format!(
+        "UPDATE {} SET name = COALESCE($2, name), category = COALESCE($3, category),
+             price_cents = COALESCE($4, price_cents), version = version + 1
+         WHERE id = $1 AND version = $5 AND deleted_at IS NULL RETURNING {}",
+        ITEMS_TABLE, ITEM_COLUMNS
+    );
+    let mut client = match items_connection(&SqlRouter::for_request(&req), &sql).await {
+        Ok(client) => client,
+        Err(resp) => return resp,
+    };
+
+    let result = async {
+        let tx = client.transaction().await.map_err(|e| format!("Transaction failed: {}", e))?;
+        let before = match find_item(&tx, id).await? {
+            Some(item) if item.deleted_at.is_none() => item,
+            _ => return Ok(VersionedWrite::NotFound),
+        };
+        if before.version != expected {
+            return Ok(VersionedWrite::Conflict(before));
+        }
+        let row = tx
+            .query_opt(sql.as_str(), &[&id, &body.name, &body.category, &body.price_cents, &expected])
+            .await
+            .map_err(|e| format!("Update failed: {}", e))?;
+        let Some(row) = row else {
+            // Another writer committed between the read and the update
+            return match find_item(&tx, id).await? {
+                Some(current) if current.deleted_at.is_none() => Ok(VersionedWrite::Conflict(current)),
+                _ => Ok(VersionedWrite::NotFound),
+            };
+        };
+        let after = Item::from_row(&row);
+        record_audit(&tx, id, "update", Some(&before), Some(&after)).await?;
+        tx.commit().await.map_err(|e| format!("Commit failed: {}", e))?;
+        Ok::<_, String>(VersionedWrite::Written(after))
+    }
+    .await;
+
+    if let Ok(VersionedWrite::Written(_)) = result {
+        query_cache::invalidate(ITEMS_TABLE, "write").await;
+    }
+    match result {
+        Ok(VersionedWrite::Written(item)) => HttpResponse::Ok().json(item),
+        Ok(VersionedWrite::NotFound) => {
+            HttpResponse::NotFound().json(serde_json::json!({ "status": "not_found", "id": id }))
+        }
+        Ok(VersionedWrite::Conflict(current)) => conflict_response(expected, current),
+        Err(e) => item_error(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+// Sets or clears deleted_at and bumps the version; the row and its history are kept either way.
+// `expected` is optional here: without it the change applies to whatever version is current.
+async fn set_deleted(router: &SqlRouter, id: i64, deleted: bool, expected: Option<i64>) -> HttpResponse {
+    let (set, current, action) = if deleted {
+        ("NOW()", "deleted_at IS NULL", "delete")
+    } else {
+        ("NULL", "deleted_at IS NOT NULL", "restore")
+    };
+    let update = format!(
+        "UPDATE {} SET deleted_at = {}, version = version + 1 WHERE id = $1 RETURNING {}",
+        ITEMS_TABLE, set, ITEM_COLUMNS
+    );
+    let mut client = match items_connection(router, &update).await {
+        Ok(client) => client,
+        Err(resp) => return resp,
+    };
+
+    let result = async {
+        let tx = client.transaction().await.map_err(|e| format!("Transaction failed: {}", e))?;
+        let select = format!("SELECT {} FROM {} WHERE id = $1 AND {} FOR UPDATE", ITEM_COLUMNS, ITEMS_TABLE, current);
+        let Some(row) = tx.query_opt(select.as_str(), &[&id]).await.map_err(|e| format!("Query failed: {}", e))? else {
+            return Ok(VersionedWrite::NotFound);
+        };
+        let before = Item::from_row(&row);
+        if expected.is_some_and(|v| v != before.version) {
+            return Ok(VersionedWrite::Conflict(before));
+        }
+        let row = tx
+            .query_one(update.as_str(), &[&id])
+            .await
+            .map_err(|e| format!("Update failed: {}", e))?;
+        let after = Item::from_row(&row);
+        record_audit(&tx, id, action, Some(&before), Some(&after)).await?;
+        tx.commit().await.map_err(|e| format!("Commit failed: {}", e))?;
+        Ok::<_, String>(VersionedWrite::Written(after))
+    }
+    .await;
+
+    if let Ok(VersionedWrite::Written(_)) = result {
+        query_cache::invalidate(ITEMS_TABLE, "write").await;
+    }
+    match result {
+        Ok(VersionedWrite::Written(item)) => HttpResponse::Ok().json(serde_json::json!({
+            "status": if deleted { "deleted" } else { "restored" },
+            "item": item
+        })),
+        Ok(VersionedWrite::NotFound) => HttpResponse::NotFound().json(serde_json::json!({
+            "status": "not_found",
+            "id": id,
+            "error": if deleted { "No live item with this id" } else { "No deleted item with this id" }
+        })),
+        Ok(VersionedWrite::Conflict(current)) => conflict_response(expected.unwrap_or_default(), current),
+        Err(e) => item_error(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+pub async fn delete_item(req: HttpRequest, path: web::Path<i64>, query: web::Query<VersionQuery>) -> impl Responder {
+    set_deleted(&SqlRouter::for_request(&req), path.into_inner(), true, query.version).await
+}
+
+pub async fn restore_item(req: HttpRequest, path: web::Path<i64>, query: web::Query<VersionQuery>) -> impl Responder {
+    set_deleted(&SqlRouter::for_request(&req), path.into_inner(), false, query.version).await
+}
+
+// Change history for an item, oldest first; deleted items keep theirs
+pub async fn item_history(req: HttpRequest, path: web::Path<i64>) -> impl Responder {
+    let id = path.into_inner();
+    let exists_sql = format!("SELECT deleted_at IS NOT NULL FROM {} WHERE id = $1", ITEMS_TABLE);
+    let client = match items_connection(&SqlRouter::for_request(&req), &exists_sql).await {
+        Ok(client) => client,
+        Err(resp) => return resp,
+    };
+
+    let deleted: bool = match client.query_opt(exists_sql.as_str(), &[&id]).await {
+        Ok(Some(row)) => row.get(0),
+        Ok(None) => return HttpResponse::NotFound().json(serde_json::json!({ "status": "not_found", "id": id })),
+        Err(e) => return item_error(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Query failed: {}", e)),
+    };
+    let sql = format!(
+        "SELECT id, action, before, after, changed_at::text FROM {} WHERE item_id = $1 ORDER BY id",
+        ITEMS_AUDIT_TABLE
+    );
+    let rows = match timed_query("postgres", "item_history", &sql, 1, client.query(sql.as_str(), &[&id])).await {
+        Ok(rows) => rows,
+        Err(e) => return item_error(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Query failed: {}", e)),
+    };
+
+    let history: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|row| {
+            let before: Option<serde_json::Value> = row.get(2);
+            let after: Option<serde_json::Value> = row.get(3);
+            serde_json::json!({
+                "audit_id": row.get::<_, i64>(0),
+                "action": row.get::<_, String>(1),
+                "changed_at": row.get::<_, String>(4),
+                "changed_fields": changed_fields(before.as_ref(), after.as_ref()),
+                "before": before,
+                "after": after
+            })
+        })
+        .collect();
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "item_id": id,
+        "deleted": deleted,
+        "count": history.len(),
+        "history": history
+    }))
+}
+
+// ============================================================================
+// CSV import
+// ============================================================================
+
+const IMPORT_BATCH_ROWS: usize = 500;
+// Rejected rows listed in the report; `rejected` still counts all of them
+const MAX_REPORTED_REJECTS: usize = 1_000;
+const MAX_NAME_LEN: usize = 200;
+const IMPORT_COLUMNS: &[&str] = &["name", "category", "price_cents", "created_at"];
+const REQUIRED_IMPORT_COLUMNS: &[&str] = &["name", "category", "price_cents"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportRow {
+    pub name: String,
+    pub category: String,
+    pub price_cents: i64,
+    // RFC 3339; None takes the column default
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct RowReject {
+    // Line in the file, counting the header as line 1
+    pub line: u64,
+    pub errors: Vec<FieldError>,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportPlan {
+    pub valid: Vec<ImportRow>,
+    pub rejects: Vec<RowReject>,
+}
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    dry_run: Option<bool>,
+}
+
+fn field_error(field: &str, message: &str) -> FieldError {
+    FieldError { field: field.to_string(), message: message.to_string() }
+}
+
+pub fn validate_import_row(
+    name: &str,
+    category: &str,
+    price_cents: &str,
+    created_at: &str,
+) -> Result<ImportRow, Vec<FieldError>> {
+    let mut errors = Vec::new();
+    if name.is_empty() {
+        errors.push(field_error("name", "is required"));
+    } else if name.chars().count() > MAX_NAME_LEN {
+        errors.push(field_error("name", &format!("must be at most {} characters", MAX_NAME_LEN)));
+    }
+    if category.is_empty() {
+        errors.push(field_error("category", "is required"));
+    }
+    let price = match price_cents.parse::<i64>() {
+        Err(_) if price_cents.is_empty() => {
+            errors.push(field_error("price_cents", "is required"));
+            None
+        }
+        Ok(price) if price < 0 => {
+            errors.push(field_error("price_cents", "must not be negative"));
+            None
+        }
+        Ok(price) => Some(price),
+        Err(_) => {
+            errors.push(field_error("price_cents", "must be an integer"));
+            None
+        }
+    };
+    let created_at = match created_at {
+        "" => None,
+        value => match chrono::DateTime::parse_from_rfc3339(value) {
+            Ok(ts) => Some(ts.to_rfc3339()),
+            Err(_) => {
+                errors.push(field_error("created_at", "must be an RFC 3339 timestamp"));
+                None
+            }
+        },
+    };
+
+    match price {
+        Some(price_cents) if errors.is_empty() => Ok(ImportRow {
+            name: name.to_string(),
+            category: category.to_string(),
+            price_cents,
+            created_at,
+        }),
+        _ => Err(errors),
+    }
+}
+
+// The csv reader positions a record where the previous one ended, so blank lines in between
+// are counted against it; skip them to get the line the row is actually on
+fn record_line(data: &[u8], position: &csv::Position) -> u64 {
+    let gap = data.get(position.byte() as usize..).unwrap_or_default();
+    let blank_lines = gap.iter().take_while(|&&b| b == b'\n' || b == b'\r').filter(|&&b| b == b'\n').count();
+    position.line() + blank_lines as u64
+}
+
+// Validates every row; Err only when the file itself is unusable (header problems)
+pub fn plan_import(data: &[u8]) -> Result<ImportPlan, String> {
+    // Flexible and byte records, so short rows and bad UTF-8 come back as records carrying their
+    // own position; both are checked per row below
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).flexible(true).from_reader(data);
+    let headers = reader.headers().map_err(|e| format!("Invalid CSV header: {}", e))?.clone();
+    let mut columns = HashMap::new();
+    for (i, header) in headers.iter().enumerate() {
+        let column = header.to_ascii_lowercase();
+        if !IMPORT_COLUMNS.contains(&column.as_str()) {
+            return Err(format!("Unknown column '{}'. Allowed: {}", header, IMPORT_COLUMNS.join(", ")));
+        }
+        if columns.insert(column, i).is_some() {
+            return Err(format!("Duplicate column '{}'", header));
+        }
+    }
+    let missing: Vec<&str> = REQUIRED_IMPORT_COLUMNS.iter().filter(|c| !columns.contains_key(**c)).copied().collect();
+    if !missing.is_empty() {
+        return Err(format!("Missing required column(s): {}", missing.join(", ")));
+    }
+
+    let mut plan = ImportPlan::default();
+    for record in reader.byte_records() {
+        // Only I/O errors are left here, which a byte slice doesn't produce
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map(|p| p.line()).unwrap_or(0);
+                plan.rejects.push(RowReject { line, errors: vec![field_error("row", &e.to_string())] });
+                continue;
+            }
+        };
+        // A wrong field count or invalid UTF-8 rejects just that row, at the record's own line
+        let line = record.position().map(|p| record_line(data, p)).unwrap_or(0);
+        if record.len() != headers.len() {
+            let message = format!("Expected {} fields, found {}", headers.len(), record.len());
+            plan.rejects.push(RowReject { line, errors: vec![field_error("row", &message)] });
+            continue;
+        }
+        let record = match csv::StringRecord::from_byte_record(record) {
+            Ok(record) => record,
+            Err(e) => {
+                plan.rejects.push(RowReject { line, errors: vec![field_error("row", &e.to_string())] });
+                continue;
+            }
+        };
+        let field = |column: &str| columns.get(column).and_then(|&i| record.get(i)).unwrap_or("");
+        match validate_import_row(field("name"), field("category"), field("price_cents"), field("created_at")) {
+            Ok(row) => plan.valid.push(row),
+            Err(errors) => plan.rejects.push(RowReject { line, errors }),
+        }
+    }
+    Ok(plan)
+}
+
+// Inserts a batch and its "create" audit rows in one transaction, like every other item write
+async fn insert_import_batch(client: &mut tokio_postgres::Client, rows: &[ImportRow]) -> Result<u64, String> {
+    let names: Vec<&str> = rows.iter().map(|r| r.name.as_str()).collect();
+    let categories: Vec<&str> = rows.iter().map(|r| r.category.as_str()).collect();
+    let prices: Vec<i64> = rows.iter().map(|r| r.price_cents).collect();
+    let created_at: Vec<Option<&str>> = rows.iter().map(|r| r.created_at.as_deref()).collect();
+    // One statement per batch: UNNEST turns the parameter arrays back into rows
+    let sql = format!(
+        "INSERT INTO {} (name, category, price_cents, created_at)
+         SELECT name, category, price_cents, COALESCE(created_at::timestamptz, NOW())
+         FROM UNNEST($1::text[], $2::text[], $3::bigint[], $4::text[]) AS t(name, category, price_cents, created_at)
+         RETURNING {}",
+        ITEMS_TABLE, ITEM_COLUMNS
+    );
+    let tx = client.transaction().await.map_err(|e| format!("Transaction failed: {}", e))?;
+    let inserted = timed_query(
+        "postgres",
+        "import_items",
+        &sql,
+        4,
+        tx.query(sql.as_str(), &[&names, &categories, &prices, &created_at]),
+    )
+    .await
+    .map_err(|e| format!("Insert failed: {}", e))?;
+    for row in &inserted {
+        let item = Item::from_row(row);
+        record_audit(&tx, item.id, "create", None, Some(&item)).await?;
+    }
+    tx.commit().await.map_err(|e| format!("Commit failed: {}", e))?;
+    Ok(inserted.len() as u64)
+}
+
+// Imports items from the first file field of a multipart body. Valid rows are inserted in
+// batches of IMPORT_BATCH_ROWS, each committed on its own; rejected rows are reported by line.
+pub async fn import_items(req: HttpRequest, query: web::Query<ImportQuery>, mut payload: Multipart) -> impl Responder {
+    let max_bytes: usize = get_env_or("ITEMS_IMPORT_MAX_BYTES", "10485760").parse().unwrap_or(10_485_760);
+    let file = match read_file_field(&mut payload, max_bytes).await {
+        Ok(Some(file)) => file,
+        Ok(None) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "status": "error",
+                "error": "No file field in multipart body"
+            }))
+        }
+        Err((413, e)) => {
+            return HttpResponse::PayloadTooLarge().json(serde_json::json!({ "status": "error", "error": e }))
+        }
+        Err((_, e)) => return HttpResponse::BadRequest().json(serde_json::json!({ "status": "error", "error": e })),
+    };
+    let plan = match plan_import(&file.data) {
+        Ok(plan) => plan,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "status": "error", "error": e })),
+    };
+    let total_rows = plan.valid.len() + plan.rejects.len();
+    let rejected = plan.rejects.len();
+    let mut rejects = plan.rejects;
+    let rejects_truncated = rejects.len() > MAX_REPORTED_REJECTS;
+    rejects.truncate(MAX_REPORTED_REJECTS);
+
+    let mut inserted = 0u64;
+    let mut batches = 0usize;
+    if !query.dry_run.unwrap_or(false) && !plan.valid.is_empty() {
+        // Every batch is an INSERT, so this is the primary
+        let insert = format!("INSERT INTO {}", ITEMS_TABLE);
+        let mut client = match items_connection(&SqlRouter::for_request(&req), &insert).await {
+            Ok(client) => client,
+            Err(resp) => return resp,
+        };
+        for batch in plan.valid.chunks(IMPORT_BATCH_ROWS) {
+            match insert_import_batch(&mut client, batch).await {
+                Ok(count) => {
+                    inserted += count;
+                    batches += 1;
+                }
+                Err(e) => {
+                    // Earlier batches stay committed; report how far the import got
+                    if inserted > 0 {
+                        query_cache::invalidate(ITEMS_TABLE, "write").await;
+                    }
+                    return HttpResponse::InternalServerError().json(serde_json::json!({
+                        "status": "error",
+                        "error": e,
+                        "filename": file.filename,
+                        "total_rows": total_rows,
+                        "inserted": inserted,
+                        "batches": batches,
+                        "rejected": rejected,
+                        "rejects": rejects
+                    }));
+                }
+            }
+        }
+    }
+
+    if inserted > 0 {
+        query_cache::invalidate(ITEMS_TABLE, "write").await;
+    }
+
+    let status = match (query.dry_run.unwrap_or(false), plan.valid.is_empty(), rejected) {
+        (true, _, _) => "validated",
+        (false, true, _) => "rejected",
+        (false, false, 0) => "imported",
+        (false, false, _) => "partial",
+    };
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": status,
+        "filename": file.filename,
+        "total_rows": total_rows,
+        "valid": plan.valid.len(),
+        "inserted": inserted,
+        "batches": batches,
+        "rejected": rejected,
+        "rejects": rejects,
+        "rejects_truncated": rejects_truncated
+    }))
+}
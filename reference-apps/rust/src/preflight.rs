@@ -0,0 +1,220 @@
+// Startup preflight: is the configuration good enough to serve traffic?
+//
+// GET /preflight, or `devstack-core-rust-api preflight` from a compose healthcheck or CI job,
+// checks what startup and the first requests depend on without opening any backend connection:
+// - the HTTP port, the listener, protocol and SLO_ROUTES settings, and every enabled backend's
+//   *_PORT parse;
+// - Vault answers a token lookup with VAULT_TOKEN;
+// - each enabled backend's Vault secret exists and has the keys the clients read;
+// - each enabled backend's host resolves, after HOST_OVERRIDES.
+// Every check is pass, warn (works, but on a default or with something missing that has a
+// fallback) or fail, and the report takes the worst of them. HTTP answers 503 and the command
+// exits 1 when anything fails; warnings alone still pass.
+
+use std::time::Instant;
+
+use actix_web::{HttpResponse, Responder};
+use serde::Serialize;
+
+use crate::{get_env_or, get_vault_secret, listeners, protocols, resolve, services, slo, vault};
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Check {
+    pub name: String,
+    pub status: Outcome,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: Outcome, detail: impl Into<String>) -> Self {
+        Check { name: name.into(), status, detail: detail.into() }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Report {
+    pub status: Outcome,
+    pub checks: Vec<Check>,
+    pub duration_ms: u64,
+}
+
+impl Report {
+    pub fn exit_code(&self) -> i32 {
+        if self.status == Outcome::Fail {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+// Where a backend lives and what its Vault secret has to contain
+pub struct Endpoint {
+    pub backend: &'static str,
+    pub host_key: &'static str,
+    pub host_default: &'static str,
+    pub port_key: &'static str,
+    pub port_default: &'static str,
+    pub secret: &'static str,
+    pub keys: &'static [&'static str],
+}
+
+pub const ENDPOINTS: &[Endpoint] = &[
+    Endpoint {
+        backend: "postgres",
+        host_key: "POSTGRES_HOST",
+        host_default: "postgres",
+        port_key: "POSTGRES_PORT",
+        port_default: "5432",
+        secret: "postgres",
+        keys: &["user", "password", "database"],
+    },
+    Endpoint {
+        backend: "mysql",
+        host_key: "MYSQL_HOST",
+        host_default: "mysql",
+        port_key: "MYSQL_PORT",
+        port_default: "3306",
+        secret: "mysql",
+        keys: &["user", "password", "database"],
+    },
+    Endpoint {
+        backend: "mongodb",
+        host_key: "MONGODB_HOST",
+        host_default: "mongodb",
+        port_key: "MONGODB_PORT",
+        port_default: "27017",
+        secret: "mongodb",
+        keys: &["user", "password"],
+    },
+    Endpoint {
+        backend: "redis",
+        host_key: "REDIS_HOST",
+        host_default: "redis-1",
+        port_key: "REDIS_PORT",
+        port_default: "6379",
+        secret: "redis-1",
+        keys: &["password"],
+    },
+    Endpoint {
+        backend: "rabbitmq",
+        host_key: "RABBITMQ_HOST",
+        host_default: "rabbitmq",
+        port_key: "RABBITMQ_PORT",
+        port_default: "5672",
+        secret: "rabbitmq",
+        keys: &["user", "password", "vhost"],
+    },
+];
+
+pub fn check_port(key: &str, value: &str) -> Check {
+    match value.trim().parse::<u16>() {
+        Ok(0) => Check::new(format!("port:{}", key), Outcome::Fail, "0 is not a usable port"),
+        Ok(port) => Check::new(format!("port:{}", key), Outcome::Pass, port.to_string()),
+        Err(_) => Check::new(format!("port:{}", key), Outcome::Fail, format!("{:?} is not a port number", value)),
+    }
+}
+
+pub fn check_secret(path: &str, result: &Result<serde_json::Value, String>, keys: &[&str]) -> Check {
+    let name = format!("secret:{}", path);
+    let secret = match result {
+        Ok(secret) => secret,
+        Err(e) => return Check::new(name, Outcome::Fail, e.clone()),
+    };
+    let missing: Vec<&str> = keys
+        .iter()
+        .copied()
+        .filter(|key| secret[*key].as_str().is_none_or(str::is_empty))
+        .collect();
+    if missing.is_empty() {
+        Check::new(name, Outcome::Pass, format!("has {}", keys.join(", ")))
+    } else {
+        Check::new(name, Outcome::Warn, format!("missing {}; built-in defaults will be used", missing.join(", ")))
+    }
+}
+
+pub fn overall(checks: &[Check]) -> Outcome {
+    checks.iter().map(|check| check.status).max().unwrap_or(Outcome::Pass)
+}
+
+fn config_checks() -> Vec<Check> {
+    let mut checks = Vec::new();
+    let http_port = get_env_or("HTTP_PORT", "8004");
+    checks.push(check_port("HTTP_PORT", &http_port));
+    let default_port = http_port.trim().parse().unwrap_or(8004);
+    checks.push(match listeners::from_env(default_port) {
+        Ok(bound) => Check::new("listeners", Outcome::Pass, format!("{} listener(s)", bound.len())),
+        Err(e) => Check::new("listeners", Outcome::Fail, e),
+    });
+    checks.push(match protocols::ProtocolConfig::from_env() {
+        Ok(config) if config.tls.is_some() => Check::new("protocols", Outcome::Pass, "TLS enabled"),
+        Ok(_) => Check::new("protocols", Outcome::Pass, "plain HTTP"),
+        Err(e) => Check::new("protocols", Outcome::Fail, e),
+    });
+    checks.push(match slo::objectives_from_env() {
+        Ok(objectives) => Check::new("slo", Outcome::Pass, format!("{} objective(s)", objectives.len())),
+        Err(e) => Check::new("slo", Outcome::Fail, e),
+    });
+    for endpoint in ENDPOINTS.iter().filter(|endpoint| services::is_enabled(endpoint.backend)) {
+        checks.push(check_port(endpoint.port_key, &get_env_or(endpoint.port_key, endpoint.port_default)));
+    }
+    checks
+}
+
+async fn vault_check() -> Check {
+    if !services::is_enabled("vault") {
+        return Check::new("vault", Outcome::Warn, "disabled by ENABLED_SERVICES; secret reads will fail");
+    }
+    if get_env_or("VAULT_TOKEN", "").is_empty() {
+        return Check::new("vault", Outcome::Fail, "VAULT_TOKEN is not set");
+    }
+    match vault::lookup_token_ttl().await {
+        Ok(0) => Check::new("vault", Outcome::Pass, "reachable; token does not expire"),
+        Ok(ttl) => Check::new("vault", Outcome::Pass, format!("reachable; token expires in {}s", ttl)),
+        Err(e) => Check::new("vault", Outcome::Fail, e),
+    }
+}
+
+async fn endpoint_checks(endpoint: &Endpoint) -> Vec<Check> {
+    let host = get_env_or(endpoint.host_key, endpoint.host_default);
+    let port = get_env_or(endpoint.port_key, endpoint.port_default).trim().parse::<u16>().ok();
+    let dns = if host.starts_with('/') {
+        Check::new(format!("dns:{}", host), Outcome::Pass, "unix socket")
+    } else {
+        match resolve::lookup(&host, port.unwrap_or(0)).await {
+            Ok(addresses) => Check::new(format!("dns:{}", host), Outcome::Pass, addresses[0].to_string()),
+            Err(e) => Check::new(format!("dns:{}", host), Outcome::Fail, e),
+        }
+    };
+    let secret = check_secret(endpoint.secret, &get_vault_secret(endpoint.secret).await, endpoint.keys);
+    vec![dns, secret]
+}
+
+pub async fn run() -> Report {
+    let started = Instant::now();
+    let mut checks = config_checks();
+    checks.push(vault_check().await);
+    let enabled = ENDPOINTS.iter().filter(|endpoint| services::is_enabled(endpoint.backend));
+    for endpoint_checks in futures_util::future::join_all(enabled.map(endpoint_checks)).await {
+        checks.extend(endpoint_checks);
+    }
+    Report { status: overall(&checks), checks, duration_ms: started.elapsed().as_millis() as u64 }
+}
+
+// GET /preflight
+pub async fn preflight() -> impl Responder {
+    let report = run().await;
+    if report.status == Outcome::Fail {
+        HttpResponse::ServiceUnavailable().json(report)
+    } else {
+        HttpResponse::Ok().json(report)
+    }
+}
@@ -0,0 +1,451 @@
+// Probabilistic data structures: HyperLogLog and Bloom filters
+//
+// HyperLogLog (PFADD/PFCOUNT) is built into Redis and counts distinct items in ~12 KB with a
+// standard error of 0.81%. Bloom filters need the RedisBloom module (BF.*); when it isn't loaded
+// the same endpoints use an in-process filter, and the demo runs both so their false-positive
+// rates and sizes can be compared against the configured target.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::f64::consts::LN_2;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use actix_web::{web, HttpResponse, Responder};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+
+use crate::redis_connection;
+
+const KEY_PREFIX: &str = "probabilistic";
+const MAX_ITEMS_PER_REQUEST: usize = 10_000;
+const MAX_DEMO_ITEMS: usize = 200_000;
+const BATCH_SIZE: usize = 1_000;
+const DEFAULT_CAPACITY: u64 = 10_000;
+const DEFAULT_ERROR_RATE: f64 = 0.01;
+
+lazy_static! {
+    // Fallback filters by key, used when RedisBloom isn't loaded
+    static ref LOCAL_FILTERS: Mutex<HashMap<String, BloomFilter>> = Mutex::new(HashMap::new());
+}
+
+// ============================================================================
+// In-process Bloom filter
+// ============================================================================
+
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    bit_count: u64,
+    hashes: u32,
+}
+
+impl BloomFilter {
+    // Sized for `capacity` items at `error_rate`: m = -n ln p / (ln 2)^2 bits, k = m/n ln 2 hashes
+    pub fn new(capacity: u64, error_rate: f64) -> Self {
+        let n = capacity.max(1) as f64;
+        let bit_count = (-(n * error_rate.ln()) / (LN_2 * LN_2)).ceil().max(64.0) as u64;
+        let hashes = ((bit_count as f64 / n) * LN_2).round().clamp(1.0, 32.0) as u32;
+        BloomFilter { bits: vec![0; bit_count.div_ceil(64) as usize], bit_count, hashes }
+    }
+
+    // Double hashing: position i is h1 + i * h2
+    fn positions(&self, item: &str) -> impl Iterator<Item = u64> {
+        let hash = |seed: u64| {
+            let mut hasher = DefaultHasher::new();
+            seed.hash(&mut hasher);
+            item.hash(&mut hasher);
+            hasher.finish()
+        };
+        let (h1, h2, m) = (hash(0), hash(1) | 1, self.bit_count);
+        (0..self.hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % m)
+    }
+
+    // True when the item was definitely not present before
+    pub fn insert(&mut self, item: &str) -> bool {
+        let mut added = false;
+        for bit in self.positions(item).collect::<Vec<_>>() {
+            let (word, mask) = ((bit / 64) as usize, 1u64 << (bit % 64));
+            added |= self.bits[word] & mask == 0;
+            self.bits[word] |= mask;
+        }
+        added
+    }
+
+    pub fn contains(&self, item: &str) -> bool {
+        self.positions(item).all(|bit| self.bits[(bit / 64) as usize] & (1u64 << (bit % 64)) != 0)
+    }
+
+    pub fn hashes(&self) -> u32 {
+        self.hashes
+    }
+
+    pub fn size_bytes(&self) -> usize {
+        self.bits.len() * 8
+    }
+}
+
+// ============================================================================
+// Helpers
+// ============================================================================
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+fn hll_key(name: &str) -> String {
+    format!("{}:hll:{}", KEY_PREFIX, name)
+}
+
+fn bloom_key(name: &str) -> String {
+    format!("{}:bloom:{}", KEY_PREFIX, name)
+}
+
+pub fn validate_bloom_params(capacity: u64, error_rate: f64) -> Result<(), String> {
+    if capacity == 0 || capacity > 100_000_000 {
+        return Err("capacity must be between 1 and 100000000".to_string());
+    }
+    if !(error_rate > 0.0 && error_rate < 1.0) {
+        return Err("error_rate must be between 0 and 1 (exclusive)".to_string());
+    }
+    Ok(())
+}
+
+fn is_unknown_command(e: &redis::RedisError) -> bool {
+    e.to_string().to_ascii_lowercase().contains("unknown command")
+}
+
+// Whether the BF.* commands exist on the node
+async fn redisbloom_available(conn: &mut redis::aio::MultiplexedConnection) -> Result<bool, String> {
+    match redis::cmd("BF.EXISTS")
+        .arg(format!("{}:bloom-probe", KEY_PREFIX))
+        .arg("x")
+        .query_async::<i64>(conn)
+        .await
+    {
+        Ok(_) => Ok(true),
+        Err(e) if is_unknown_command(&e) => Ok(false),
+        Err(e) => Err(format!("BF.EXISTS failed: {}", e)),
+    }
+}
+
+async fn connection() -> Result<redis::aio::MultiplexedConnection, HttpResponse> {
+    redis_connection()
+        .await
+        .map_err(|e| error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e))
+}
+
+// Key sizes from MEMORY USAGE; None when the command is unavailable
+async fn memory_usage(conn: &mut redis::aio::MultiplexedConnection, key: &str) -> Option<i64> {
+    redis::cmd("MEMORY").arg("USAGE").arg(key).query_async::<Option<i64>>(conn).await.ok().flatten()
+}
+
+fn demo_items(prefix: &str, count: usize) -> Vec<String> {
+    (0..count).map(|i| format!("{}-{}", prefix, i)).collect()
+}
+
+#[derive(Deserialize)]
+pub struct ItemsBody {
+    items: Vec<String>,
+    capacity: Option<u64>,
+    error_rate: Option<f64>,
+}
+
+fn check_items(items: &[String]) -> Result<(), Box<HttpResponse>> {
+    if items.is_empty() || items.len() > MAX_ITEMS_PER_REQUEST {
+        return Err(Box::new(error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            format!("items must hold between 1 and {} entries", MAX_ITEMS_PER_REQUEST),
+        )));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// HyperLogLog
+// ============================================================================
+
+pub async fn hll_add(path: web::Path<String>, body: web::Json<ItemsBody>) -> impl Responder {
+    let name = path.into_inner();
+    if let Err(response) = check_items(&body.items) {
+        return *response;
+    }
+    let mut conn = match connection().await {
+        Ok(conn) => conn,
+        Err(response) => return response,
+    };
+    let key = hll_key(&name);
+    let result = async {
+        let changed: i64 = redis::cmd("PFADD").arg(&key).arg(&body.items).query_async(&mut conn).await?;
+        let count: u64 = redis::cmd("PFCOUNT").arg(&key).query_async(&mut conn).await?;
+        Ok::<_, redis::RedisError>((changed == 1, count))
+    }
+    .await;
+    match result {
+        Ok((changed, count)) => HttpResponse::Ok().json(serde_json::json!({
+            "status": "success",
+            "key": key,
+            "added": body.items.len(),
+            "changed": changed,
+            "estimated_count": count
+        })),
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("PFADD failed: {}", e)),
+    }
+}
+
+pub async fn hll_count(path: web::Path<String>) -> impl Responder {
+    let key = hll_key(&path.into_inner());
+    let mut conn = match connection().await {
+        Ok(conn) => conn,
+        Err(response) => return response,
+    };
+    match redis::cmd("PFCOUNT").arg(&key).query_async::<u64>(&mut conn).await {
+        Ok(count) => HttpResponse::Ok().json(serde_json::json!({
+            "status": "success",
+            "key": key,
+            "estimated_count": count
+        })),
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("PFCOUNT failed: {}", e)),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct HllDemoQuery {
+    items: Option<usize>,
+}
+
+// Counts N distinct items with both a HyperLogLog and a SET, comparing accuracy and size
+pub async fn hll_demo(query: web::Query<HllDemoQuery>) -> impl Responder {
+    let count = query.items.unwrap_or(100_000).clamp(1, MAX_DEMO_ITEMS);
+    let mut conn = match connection().await {
+        Ok(conn) => conn,
+        Err(response) => return response,
+    };
+    let run = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+    // Shared hash tag so the demo keys live on one node
+    let hll = format!("{}:{{demo-{}}}:hll", KEY_PREFIX, run);
+    let set = format!("{}:{{demo-{}}}:set", KEY_PREFIX, run);
+    let items = demo_items(&run, count);
+
+    let result = async {
+        let started = Instant::now();
+        for batch in items.chunks(BATCH_SIZE) {
+            redis::cmd("PFADD").arg(&hll).arg(batch).query_async::<i64>(&mut conn).await?;
+        }
+        let hll_ms = started.elapsed().as_secs_f64() * 1000.0;
+        let started = Instant::now();
+        for batch in items.chunks(BATCH_SIZE) {
+            redis::cmd("SADD").arg(&set).arg(batch).query_async::<i64>(&mut conn).await?;
+        }
+        let set_ms = started.elapsed().as_secs_f64() * 1000.0;
+        let estimated: u64 = redis::cmd("PFCOUNT").arg(&hll).query_async(&mut conn).await?;
+        let exact: u64 = redis::cmd("SCARD").arg(&set).query_async(&mut conn).await?;
+        Ok::<_, redis::RedisError>((estimated, exact, hll_ms, set_ms))
+    }
+    .await;
+    let (hll_bytes, set_bytes) = (memory_usage(&mut conn, &hll).await, memory_usage(&mut conn, &set).await);
+    let _ = redis::cmd("DEL").arg(&hll).arg(&set).query_async::<i64>(&mut conn).await;
+
+    match result {
+        Ok((estimated, exact, hll_ms, set_ms)) => HttpResponse::Ok().json(serde_json::json!({
+            "status": "success",
+            "items": count,
+            "hyperloglog": { "estimated_count": estimated, "memory_bytes": hll_bytes, "elapsed_ms": hll_ms },
+            "set": { "exact_count": exact, "memory_bytes": set_bytes, "elapsed_ms": set_ms },
+            "error_percent": (estimated as f64 - exact as f64).abs() / exact.max(1) as f64 * 100.0
+        })),
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("HLL demo failed: {}", e)),
+    }
+}
+
+// ============================================================================
+// Bloom filters
+// ============================================================================
+
+fn local_add(key: &str, items: &[String], capacity: u64, error_rate: f64) -> Vec<bool> {
+    let mut filters = LOCAL_FILTERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let filter = filters.entry(key.to_string()).or_insert_with(|| BloomFilter::new(capacity, error_rate));
+    items.iter().map(|item| filter.insert(item)).collect()
+}
+
+fn local_exists(key: &str, item: &str) -> Option<bool> {
+    let filters = LOCAL_FILTERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    filters.get(key).map(|filter| filter.contains(item))
+}
+
+pub async fn bloom_add(path: web::Path<String>, body: web::Json<ItemsBody>) -> impl Responder {
+    let key = bloom_key(&path.into_inner());
+    let capacity = body.capacity.unwrap_or(DEFAULT_CAPACITY);
+    let error_rate = body.error_rate.unwrap_or(DEFAULT_ERROR_RATE);
+    if let Err(response) = check_items(&body.items) {
+        return *response;
+    }
+    if let Err(e) = validate_bloom_params(capacity, error_rate) {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, e);
+    }
+    let mut conn = match connection().await {
+        Ok(conn) => conn,
+        Err(response) => return response,
+    };
+
+    let (backend, added) = match redisbloom_available(&mut conn).await {
+        Ok(true) => {
+            // BF.INSERT creates the filter with these parameters only if it doesn't exist yet
+            let result = redis::cmd("BF.INSERT")
+                .arg(&key)
+                .arg("CAPACITY")
+                .arg(capacity)
+                .arg("ERROR")
+                .arg(error_rate)
+                .arg("ITEMS")
+                .arg(&body.items)
+                .query_async::<Vec<bool>>(&mut conn)
+                .await;
+            match result {
+                Ok(added) => ("redisbloom", added),
+                Err(e) => {
+                    return error_response(
+                        actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("BF.INSERT failed: {}", e),
+                    )
+                }
+            }
+        }
+        Ok(false) => ("in-process", local_add(&key, &body.items, capacity, error_rate)),
+        Err(e) => return error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e),
+    };
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "key": key,
+        "backend": backend,
+        "newly_added": added.iter().filter(|added| **added).count(),
+        "already_present": added.iter().filter(|added| !**added).count()
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct BloomExistsQuery {
+    item: String,
+}
+
+pub async fn bloom_exists(path: web::Path<String>, query: web::Query<BloomExistsQuery>) -> impl Responder {
+    let key = bloom_key(&path.into_inner());
+    let mut conn = match connection().await {
+        Ok(conn) => conn,
+        Err(response) => return response,
+    };
+    let (backend, exists) = match redisbloom_available(&mut conn).await {
+        Ok(true) => match redis::cmd("BF.EXISTS").arg(&key).arg(&query.item).query_async::<bool>(&mut conn).await {
+            Ok(exists) => ("redisbloom", exists),
+            Err(e) => {
+                return error_response(
+                    actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("BF.EXISTS failed: {}", e),
+                )
+            }
+        },
+        Ok(false) => match local_exists(&key, &query.item) {
+            Some(exists) => ("in-process", exists),
+            None => {
+                return error_response(actix_web::http::StatusCode::NOT_FOUND, format!("Unknown bloom filter '{}'", key))
+            }
+        },
+        Err(e) => return error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e),
+    };
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "key": key,
+        "backend": backend,
+        "item": query.item,
+        // A Bloom filter never gives false negatives, only false positives
+        "result": if exists { "possibly_present" } else { "definitely_absent" }
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct BloomDemoQuery {
+    items: Option<usize>,
+    probes: Option<usize>,
+    error_rate: Option<f64>,
+}
+
+pub fn false_positive_percent(false_positives: usize, probes: usize) -> f64 {
+    false_positives as f64 / probes.max(1) as f64 * 100.0
+}
+
+// Inserts N items, then probes M items that were never inserted and counts false positives
+pub async fn bloom_demo(query: web::Query<BloomDemoQuery>) -> impl Responder {
+    let count = query.items.unwrap_or(10_000).clamp(1, MAX_DEMO_ITEMS);
+    let probes = query.probes.unwrap_or(10_000).clamp(1, MAX_DEMO_ITEMS);
+    let error_rate = query.error_rate.unwrap_or(DEFAULT_ERROR_RATE);
+    if let Err(e) = validate_bloom_params(count as u64, error_rate) {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, e);
+    }
+    let run = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+    let items = demo_items(&format!("{}-in", run), count);
+    let absent = demo_items(&format!("{}-out", run), probes);
+
+    let started = Instant::now();
+    let mut local = BloomFilter::new(count as u64, error_rate);
+    for item in &items {
+        local.insert(item);
+    }
+    let local_false_positives = absent.iter().filter(|item| local.contains(item)).count();
+    let in_process = serde_json::json!({
+        "false_positives": local_false_positives,
+        "false_positive_percent": false_positive_percent(local_false_positives, probes),
+        "size_bytes": local.size_bytes(),
+        "hashes": local.hashes(),
+        "elapsed_ms": started.elapsed().as_secs_f64() * 1000.0
+    });
+
+    let mut conn = match connection().await {
+        Ok(conn) => conn,
+        Err(response) => return response,
+    };
+    let redisbloom = match redisbloom_available(&mut conn).await {
+        Ok(true) => {
+            let key = format!("{}:bloom-demo:{}", KEY_PREFIX, run);
+            let started = Instant::now();
+            let result = async {
+                redis::cmd("BF.RESERVE").arg(&key).arg(error_rate).arg(count).query_async::<()>(&mut conn).await?;
+                for batch in items.chunks(BATCH_SIZE) {
+                    redis::cmd("BF.MADD").arg(&key).arg(batch).query_async::<Vec<bool>>(&mut conn).await?;
+                }
+                let mut false_positives = 0;
+                for batch in absent.chunks(BATCH_SIZE) {
+                    let found: Vec<bool> = redis::cmd("BF.MEXISTS").arg(&key).arg(batch).query_async(&mut conn).await?;
+                    false_positives += found.iter().filter(|found| **found).count();
+                }
+                Ok::<_, redis::RedisError>(false_positives)
+            }
+            .await;
+            let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+            let size = memory_usage(&mut conn, &key).await;
+            let _ = redis::cmd("DEL").arg(&key).query_async::<i64>(&mut conn).await;
+            match result {
+                Ok(false_positives) => serde_json::json!({
+                    "available": true,
+                    "false_positives": false_positives,
+                    "false_positive_percent": false_positive_percent(false_positives, probes),
+                    "memory_bytes": size,
+                    "elapsed_ms": elapsed_ms
+                }),
+                Err(e) => serde_json::json!({ "available": true, "error": e.to_string() }),
+            }
+        }
+        Ok(false) => serde_json::json!({ "available": false, "reason": "RedisBloom module is not loaded" }),
+        Err(e) => serde_json::json!({ "available": false, "reason": e }),
+    };
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "items": count,
+        "probes": probes,
+        "target_false_positive_percent": error_rate * 100.0,
+        "in_process": in_process,
+        "redisbloom": redisbloom
+    }))
+}
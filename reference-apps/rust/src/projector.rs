@@ -0,0 +1,432 @@
+// CQRS read model: the event-sourced accounts, projected into MongoDB for querying
+//
+// event_sourcing is the write model: an append-only es_events log in Postgres from which an
+// account's state is derived on demand. That answers "what is account X's balance" but not "which
+// open accounts hold the most money". The projector follows the log in global seq order and keeps
+// one denormalized document per account in the MongoDB collection es_account_read_model (status,
+// balance, running deposit and withdrawal totals), which the read endpoints query directly.
+// Reads are eventually consistent: es_projection_lag_events and es_projection_lag_seconds say how
+// far behind the read side is.
+//
+// The position in the log is checkpointed in es_projection_checkpoints after each batch. Each
+// document records the account version it reflects, and an account is always caught up from that
+// version rather than from the batch, so projecting a batch twice after a crash is harmless and an
+// event whose seq was allocated before, but committed after, a later one is picked up with that
+// account's next event. Rebuild drops the read model and the checkpoint and replays the whole log,
+// which is also how a changed projection gets backfilled.
+//
+// The projector polls in the background every ES_PROJECTOR_INTERVAL_MS (default 1000) when
+// ES_PROJECTOR_ENABLED=true; otherwise the rebuild endpoint brings the read model up to date.
+
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use actix_web::{web, HttpResponse, Responder};
+use futures_util::TryStreamExt;
+use lazy_static::lazy_static;
+use mongodb::bson::{doc, Document};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use crate::event_sourcing::{self, Account, Event};
+use crate::seed::MONGODB_DATABASE;
+use crate::{get_env_or, mongodb_client, pool, ES_PROJECTION_LAG_EVENTS, ES_PROJECTION_LAG_SECONDS};
+
+const READ_MODEL: &str = "es_account_read_model";
+const CHECKPOINTS: &str = "es_projection_checkpoints";
+const PROJECTION: &str = "accounts";
+const BATCH_SIZE: i64 = 500;
+const DEFAULT_LIST_LIMIT: i64 = 50;
+const MAX_LIST_LIMIT: i64 = 500;
+
+lazy_static! {
+    // One batch or reset at a time, whether from the background loop or an endpoint
+    static ref PROJECTING: Mutex<()> = Mutex::new(());
+    static ref STATS: Stats = Stats::default();
+}
+
+#[derive(Default)]
+struct Stats {
+    running: AtomicBool,
+    events_projected: AtomicU64,
+    batches: AtomicU64,
+    last_error: std::sync::Mutex<Option<String>>,
+}
+
+pub fn is_enabled() -> bool {
+    get_env_or("ES_PROJECTOR_ENABLED", "false").parse().unwrap_or(false)
+}
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+// ============================================================================
+// The projection
+// ============================================================================
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReadModel {
+    pub id: String,
+    pub account: Account,
+    pub deposited_cents: i64,
+    pub withdrawn_cents: i64,
+    // Last account version and global seq folded in
+    pub version: i64,
+    pub last_seq: i64,
+}
+
+impl ReadModel {
+    pub fn new(id: &str) -> Self {
+        ReadModel { id: id.to_string(), ..Default::default() }
+    }
+
+    // Events at or below the version already projected are skipped, which makes replays harmless
+    pub fn project(&mut self, version: i64, seq: i64, event: &Event) {
+        if version <= self.version {
+            return;
+        }
+        match event {
+            Event::Deposited { amount_cents } => self.deposited_cents += amount_cents,
+            Event::Withdrawn { amount_cents } => self.withdrawn_cents += amount_cents,
+            Event::Opened { .. } | Event::Closed => {}
+        }
+        self.account.apply(event);
+        self.version = version;
+        self.last_seq = self.last_seq.max(seq);
+    }
+
+    pub fn status(&self) -> &'static str {
+        if self.account.closed {
+            "closed"
+        } else if self.account.open {
+            "open"
+        } else {
+            "pending"
+        }
+    }
+
+    fn to_document(&self) -> Document {
+        doc! {
+            "_id": &self.id,
+            "owner": self.account.owner.clone(),
+            "status": self.status(),
+            "balance_cents": self.account.balance_cents,
+            "deposited_cents": self.deposited_cents,
+            "withdrawn_cents": self.withdrawn_cents,
+            "transactions": self.account.transactions as i64,
+            "version": self.version,
+            "last_seq": self.last_seq,
+            "projected_at": mongodb::bson::DateTime::now(),
+        }
+    }
+
+    fn from_document(document: &Document) -> Option<Self> {
+        let status = document.get_str("status").ok()?;
+        Some(ReadModel {
+            id: document.get_str("_id").ok()?.to_string(),
+            account: Account {
+                owner: document.get_str("owner").ok().map(str::to_string),
+                balance_cents: document.get_i64("balance_cents").ok()?,
+                open: status == "open",
+                closed: status == "closed",
+                transactions: document.get_i64("transactions").ok()?.max(0) as u64,
+            },
+            deposited_cents: document.get_i64("deposited_cents").ok()?,
+            withdrawn_cents: document.get_i64("withdrawn_cents").ok()?,
+            version: document.get_i64("version").ok()?,
+            last_seq: document.get_i64("last_seq").ok()?,
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::json!({
+            "id": self.id,
+            "owner": self.account.owner,
+            "status": self.status(),
+            "balance_cents": self.account.balance_cents,
+            "deposited_cents": self.deposited_cents,
+            "withdrawn_cents": self.withdrawn_cents,
+            "transactions": self.account.transactions,
+            "version": self.version,
+            "last_seq": self.last_seq
+        })
+    }
+}
+
+// ============================================================================
+// Projector
+// ============================================================================
+
+async fn collections() -> Result<(mongodb::Collection<Document>, mongodb::Collection<Document>), String> {
+    let database = mongodb_client().await?.database(MONGODB_DATABASE);
+    Ok((database.collection(READ_MODEL), database.collection(CHECKPOINTS)))
+}
+
+async fn read_checkpoint(checkpoints: &mongodb::Collection<Document>) -> Result<i64, String> {
+    let document = checkpoints
+        .find_one(doc! { "_id": PROJECTION })
+        .await
+        .map_err(|e| format!("Checkpoint read failed: {}", e))?;
+    Ok(document.and_then(|document| document.get_i64("seq").ok()).unwrap_or(0))
+}
+
+// (events past the checkpoint, seconds since the oldest of them was recorded)
+async fn lag(client: &tokio_postgres::Client, checkpoint: i64) -> Result<(i64, f64), String> {
+    let row = client
+        .query_one(
+            "SELECT COUNT(*), COALESCE(EXTRACT(EPOCH FROM NOW() - MIN(recorded_at)), 0)::float8
+             FROM es_events WHERE seq > $1",
+            &[&checkpoint],
+        )
+        .await
+        .map_err(|e| format!("Lag query failed: {}", e))?;
+    let (events, seconds): (i64, f64) = (row.get(0), row.get(1));
+    ES_PROJECTION_LAG_EVENTS.set(events);
+    ES_PROJECTION_LAG_SECONDS.set(seconds);
+    Ok((events, seconds))
+}
+
+#[derive(Debug, Default)]
+pub struct Batch {
+    // Log entries read past the checkpoint, and events folded into documents
+    pub read: usize,
+    pub projected: usize,
+    pub accounts: usize,
+    pub checkpoint: i64,
+}
+
+async fn project_batch() -> Result<Batch, String> {
+    let _guard = PROJECTING.lock().await;
+    let client = pool::postgres().await?;
+    client
+        .batch_execute(event_sourcing::TABLE_DDL)
+        .await
+        .map_err(|e| format!("Table setup failed: {}", e))?;
+    let (read_model, checkpoints) = collections().await?;
+    let checkpoint = read_checkpoint(&checkpoints).await?;
+
+    let rows = client
+        .query(
+            "SELECT seq, aggregate_id FROM es_events WHERE seq > $1 ORDER BY seq LIMIT $2",
+            &[&checkpoint, &BATCH_SIZE],
+        )
+        .await
+        .map_err(|e| format!("Event query failed: {}", e))?;
+    let Some(last) = rows.last() else {
+        lag(&client, checkpoint).await?;
+        return Ok(Batch { checkpoint, ..Default::default() });
+    };
+    let next_checkpoint: i64 = last.get(0);
+    let accounts: BTreeSet<String> = rows.iter().map(|row| row.get(1)).collect();
+
+    let mut projected = 0;
+    for id in &accounts {
+        let existing = read_model
+            .find_one(doc! { "_id": id })
+            .await
+            .map_err(|e| format!("Read model lookup failed: {}", e))?;
+        let mut model = match existing {
+            Some(document) => {
+                ReadModel::from_document(&document).ok_or_else(|| format!("Unreadable read model for {}", id))?
+            }
+            None => ReadModel::new(id),
+        };
+        let events = client
+            .query(
+                "SELECT version, seq, data FROM es_events WHERE aggregate_id = $1 AND version > $2 ORDER BY version",
+                &[id, &model.version],
+            )
+            .await
+            .map_err(|e| format!("Event query failed: {}", e))?;
+        for row in &events {
+            let event: Event =
+                serde_json::from_value(row.get(2)).map_err(|e| format!("Unreadable event in {}: {}", id, e))?;
+            model.project(row.get(0), row.get(1), &event);
+        }
+        projected += events.len();
+        read_model
+            .replace_one(doc! { "_id": id }, model.to_document())
+            .upsert(true)
+            .await
+            .map_err(|e| format!("Read model write failed: {}", e))?;
+    }
+
+    checkpoints
+        .update_one(doc! { "_id": PROJECTION }, doc! { "$set": { "seq": next_checkpoint } })
+        .upsert(true)
+        .await
+        .map_err(|e| format!("Checkpoint write failed: {}", e))?;
+    lag(&client, next_checkpoint).await?;
+    STATS.batches.fetch_add(1, Ordering::Relaxed);
+    STATS.events_projected.fetch_add(projected as u64, Ordering::Relaxed);
+    Ok(Batch { read: rows.len(), projected, accounts: accounts.len(), checkpoint: next_checkpoint })
+}
+
+// Batches back to back until one comes up short of BATCH_SIZE
+async fn catch_up() -> Result<Batch, String> {
+    let mut total = Batch::default();
+    loop {
+        let batch = project_batch().await?;
+        total.read += batch.read;
+        total.projected += batch.projected;
+        total.accounts += batch.accounts;
+        total.checkpoint = batch.checkpoint;
+        if (batch.read as i64) < BATCH_SIZE {
+            return Ok(total);
+        }
+    }
+}
+
+pub fn spawn_projector() {
+    let interval_ms: u64 = get_env_or("ES_PROJECTOR_INTERVAL_MS", "1000").parse().unwrap_or(1000);
+    STATS.running.store(true, Ordering::Relaxed);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_millis(interval_ms.max(50)));
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let result = catch_up().await;
+            if let Err(e) = &result {
+                log::debug!("Read model projector: {}", e);
+            }
+            *STATS.last_error.lock().unwrap() = result.err();
+        }
+    });
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+// GET /examples/patterns/event-sourcing/projector
+pub async fn projector_status() -> impl Responder {
+    let progress = async {
+        let (_, checkpoints) = collections().await?;
+        let checkpoint = read_checkpoint(&checkpoints).await?;
+        let client = pool::postgres().await?;
+        client
+            .batch_execute(event_sourcing::TABLE_DDL)
+            .await
+            .map_err(|e| format!("Table setup failed: {}", e))?;
+        let (events, seconds) = lag(&client, checkpoint).await?;
+        Ok::<_, String>((checkpoint, events, seconds))
+    };
+    match progress.await {
+        Ok((checkpoint, lag_events, lag_seconds)) => HttpResponse::Ok().json(serde_json::json!({
+            "running": STATS.running.load(Ordering::Relaxed),
+            "checkpoint": checkpoint,
+            "lag_events": lag_events,
+            "lag_seconds": lag_seconds,
+            "batches": STATS.batches.load(Ordering::Relaxed),
+            "events_projected": STATS.events_projected.load(Ordering::Relaxed),
+            "last_error": STATS.last_error.lock().unwrap().clone()
+        })),
+        Err(e) => error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    }
+}
+
+// POST /examples/patterns/event-sourcing/projector/rebuild
+pub async fn rebuild() -> impl Responder {
+    let started = Instant::now();
+    let reset = async {
+        let _guard = PROJECTING.lock().await;
+        let (read_model, checkpoints) = collections().await?;
+        read_model.delete_many(doc! {}).await.map_err(|e| format!("Clearing read model failed: {}", e))?;
+        checkpoints
+            .delete_one(doc! { "_id": PROJECTION })
+            .await
+            .map_err(|e| format!("Resetting checkpoint failed: {}", e))?;
+        Ok::<_, String>(())
+    };
+    if let Err(e) = reset.await {
+        return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e);
+    }
+    match catch_up().await {
+        Ok(batch) => HttpResponse::Ok().json(serde_json::json!({
+            "status": "rebuilt",
+            "events_projected": batch.projected,
+            "checkpoint": batch.checkpoint,
+            "duration_ms": started.elapsed().as_millis() as u64
+        })),
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Rebuild failed: {}", e)),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ListQuery {
+    status: Option<String>,
+    min_balance_cents: Option<i64>,
+    limit: Option<i64>,
+}
+
+// GET /examples/patterns/event-sourcing/read-model/accounts?status=open&min_balance_cents=0&limit=50
+// Richest first: a query the event log can't answer without replaying every account
+pub async fn list_accounts(query: web::Query<ListQuery>) -> impl Responder {
+    let limit = query.limit.unwrap_or(DEFAULT_LIST_LIMIT);
+    if !(1..=MAX_LIST_LIMIT).contains(&limit) {
+        return error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            format!("limit must be between 1 and {}", MAX_LIST_LIMIT),
+        );
+    }
+    let mut filter = Document::new();
+    if let Some(status) = &query.status {
+        if !["pending", "open", "closed"].contains(&status.as_str()) {
+            return error_response(
+                actix_web::http::StatusCode::BAD_REQUEST,
+                "status must be pending, open or closed".to_string(),
+            );
+        }
+        filter.insert("status", status);
+    }
+    if let Some(min) = query.min_balance_cents {
+        filter.insert("balance_cents", doc! { "$gte": min });
+    }
+
+    let found = async {
+        let (read_model, _) = collections().await?;
+        read_model
+            .find(filter)
+            .sort(doc! { "balance_cents": -1, "_id": 1 })
+            .limit(limit)
+            .await
+            .map_err(|e| format!("Find failed: {}", e))?
+            .try_collect::<Vec<Document>>()
+            .await
+            .map_err(|e| format!("Cursor failed: {}", e))
+    };
+    match found.await {
+        Ok(documents) => {
+            let accounts: Vec<Value> =
+                documents.iter().filter_map(ReadModel::from_document).map(|model| model.to_json()).collect();
+            HttpResponse::Ok().json(serde_json::json!({ "count": accounts.len(), "accounts": accounts }))
+        }
+        Err(e) => error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    }
+}
+
+// GET /examples/patterns/event-sourcing/read-model/accounts/{id}
+pub async fn get_account(path: web::Path<String>) -> impl Responder {
+    let id = path.into_inner();
+    let found = async {
+        let (read_model, _) = collections().await?;
+        read_model.find_one(doc! { "_id": &id }).await.map_err(|e| format!("Find failed: {}", e))
+    };
+    match found.await {
+        Ok(Some(document)) => match ReadModel::from_document(&document) {
+            Some(model) => HttpResponse::Ok().json(model.to_json()),
+            None => error_response(
+                actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unreadable read model for {}", id),
+            ),
+        },
+        Ok(None) => error_response(
+            actix_web::http::StatusCode::NOT_FOUND,
+            format!("Account '{}' is not in the read model (yet)", id),
+        ),
+        Err(e) => error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    }
+}
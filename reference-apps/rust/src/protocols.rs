@@ -0,0 +1,104 @@
+// HTTP protocol versions: HTTP/2 over TLS and h2c on the plaintext listeners
+//
+// HTTP_PROTOCOLS lists what the plaintext listeners speak: `http1` (default) or `http1,h2c`,
+// which also accepts HTTP/2 with prior knowledge. With TLS_ENABLED=true the server additionally
+// listens on HTTPS_BIND_ADDRESSES (default 0.0.0.0:8447) and offers h2 and http/1.1 via ALPN.
+// Every response carries the protocol that served it in X-Protocol.
+
+use std::io;
+use std::sync::Arc;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::http::Version;
+use actix_web::middleware::Next;
+
+use crate::get_env_or;
+use crate::listeners::{self, Listener};
+
+#[derive(Debug, PartialEq)]
+pub struct TlsSettings {
+    pub cert_path: String,
+    pub key_path: String,
+    pub listeners: Vec<Listener>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ProtocolConfig {
+    pub h2c: bool,
+    pub tls: Option<TlsSettings>,
+}
+
+// true when h2c is enabled
+pub fn parse_protocols(value: &str) -> Result<bool, String> {
+    let mut h2c = false;
+    for protocol in value.split(',').map(|p| p.trim().to_ascii_lowercase()).filter(|p| !p.is_empty()) {
+        match protocol.as_str() {
+            "http1" => {}
+            "h2c" => h2c = true,
+            other => return Err(format!("Unknown protocol '{}' in HTTP_PROTOCOLS; use http1 or h2c", other)),
+        }
+    }
+    Ok(h2c)
+}
+
+impl ProtocolConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let h2c = parse_protocols(&get_env_or("HTTP_PROTOCOLS", "http1"))?;
+        let tls = if get_env_or("TLS_ENABLED", "false").parse().unwrap_or(false) {
+            let https_port = get_env_or("HTTPS_PORT", "8447").parse().unwrap_or(8447);
+            let listeners = listeners::parse_bind_addresses(&get_env_or("HTTPS_BIND_ADDRESSES", ""), https_port)?;
+            if listeners.iter().any(|l| matches!(l, Listener::Unix(_))) {
+                return Err("HTTPS_BIND_ADDRESSES only accepts TCP addresses".to_string());
+            }
+            Some(TlsSettings {
+                cert_path: get_env_or("TLS_CERT_PATH", "/certs/cert.pem"),
+                key_path: get_env_or("TLS_KEY_PATH", "/certs/key.pem"),
+                listeners,
+            })
+        } else {
+            None
+        };
+        Ok(ProtocolConfig { h2c, tls })
+    }
+}
+
+pub fn load_rustls(settings: &TlsSettings) -> io::Result<rustls::ServerConfig> {
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+
+    let mut cert_reader = io::BufReader::new(std::fs::File::open(&settings.cert_path)?);
+    let certs = rustls_pemfile::certs(&mut cert_reader).collect::<Result<Vec<_>, _>>()?;
+    let mut key_reader = io::BufReader::new(std::fs::File::open(&settings.key_path)?);
+    let key = rustls_pemfile::private_key(&mut key_reader)?
+        .ok_or_else(|| invalid(format!("No private key in {}", settings.key_path)))?;
+
+    rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| invalid(e.to_string()))?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| invalid(format!("Invalid certificate or key: {}", e)))
+}
+
+pub fn protocol_name(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_11 => "HTTP/1.1",
+        Version::HTTP_2 => "HTTP/2",
+        Version::HTTP_3 => "HTTP/3",
+        _ => "unknown",
+    }
+}
+
+pub async fn protocol_header_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let protocol = protocol_name(req.version());
+    let mut res = next.call(req).await?.map_into_boxed_body();
+    res.headers_mut()
+        .insert(HeaderName::from_static("x-protocol"), HeaderValue::from_static(protocol));
+    Ok(res)
+}
@@ -0,0 +1,325 @@
+// Redis-backed result cache for Postgres reads, invalidated on write
+//
+// Entries are keyed by table, the table's generation and a hash of the normalized SQL plus its
+// parameters. A write bumps the generation (INCR sqlcache:gen:<table>), which retires every
+// cached result for that table at once without scanning for keys; the orphans age out by TTL.
+// The CRUD handlers bump it after they commit. With SQL_CACHE_LISTEN=true a statement trigger
+// also NOTIFYs on any change and a background listener bumps it, which covers writes made from
+// psql or another service.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use actix_web::{HttpResponse, Responder};
+use futures_util::StreamExt;
+use lazy_static::lazy_static;
+use redis::AsyncCommands;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio_postgres::AsyncMessage;
+
+use crate::postgres_examples::{ensure_items_table, ITEMS_TABLE};
+use crate::{get_env_or, postgres_connect, redis_connection, services, SQL_CACHE_INVALIDATIONS_TOTAL, SQL_CACHE_TOTAL};
+
+const GENERATION_PREFIX: &str = "sqlcache:gen:";
+const ENTRY_PREFIX: &str = "sqlcache:entry:";
+pub const NOTIFY_CHANNEL: &str = "sql_cache_invalidate";
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheStatus {
+    Hit,
+    Miss,
+    // Cache off or Redis unreachable; the query went straight to Postgres
+    Bypass,
+}
+
+impl CacheStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheStatus::Hit => "HIT",
+            CacheStatus::Miss => "MISS",
+            CacheStatus::Bypass => "BYPASS",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct QueryStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub bypassed: u64,
+}
+
+impl QueryStats {
+    pub fn hit_rate(&self) -> Option<f64> {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            None
+        } else {
+            Some(self.hits as f64 / lookups as f64)
+        }
+    }
+}
+
+lazy_static! {
+    static ref STATS: Mutex<HashMap<String, QueryStats>> = Mutex::new(HashMap::new());
+    // (table, source) -> generation bumps made by this replica
+    static ref INVALIDATIONS: Mutex<BTreeMap<(String, String), u64>> = Mutex::new(BTreeMap::new());
+}
+
+pub fn is_enabled() -> bool {
+    services::is_enabled("redis") && get_env_or("SQL_CACHE_ENABLED", "true").parse().unwrap_or(true)
+}
+
+pub fn listen_enabled() -> bool {
+    get_env_or("SQL_CACHE_LISTEN", "false").parse().unwrap_or(false)
+}
+
+fn ttl_seconds() -> u64 {
+    get_env_or("SQL_CACHE_TTL_SECONDS", "60").parse::<u64>().unwrap_or(60).max(1)
+}
+
+// Whitespace differences alone shouldn't split one query across several entries
+pub fn normalize_sql(sql: &str) -> String {
+    sql.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+pub fn cache_key(table: &str, generation: u64, sql: &str, params: &[String]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(normalize_sql(sql).as_bytes());
+    for param in params {
+        // Length-prefixed so ["a", "bc"] and ["ab", "c"] hash differently
+        hasher.update((param.len() as u64).to_le_bytes());
+        hasher.update(param.as_bytes());
+    }
+    format!("{}{}:{}:{}", ENTRY_PREFIX, table, generation, hex::encode(hasher.finalize()))
+}
+
+fn record_lookup(query: &str, status: CacheStatus) {
+    let result = match status {
+        CacheStatus::Hit => "hit",
+        CacheStatus::Miss => "miss",
+        CacheStatus::Bypass => "bypass",
+    };
+    SQL_CACHE_TOTAL.with_label_values(&[query, result]).inc();
+    let mut stats = STATS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let entry = stats.entry(query.to_string()).or_default();
+    match status {
+        CacheStatus::Hit => entry.hits += 1,
+        CacheStatus::Miss => entry.misses += 1,
+        CacheStatus::Bypass => entry.bypassed += 1,
+    }
+}
+
+async fn bypass<T, E, Fut>(query: &str, load: Fut) -> Result<(T, CacheStatus), E>
+where
+    Fut: Future<Output = Result<T, E>>,
+{
+    record_lookup(query, CacheStatus::Bypass);
+    load.await.map(|value| (value, CacheStatus::Bypass))
+}
+
+// Serve `load` through the cache. Redis trouble never fails the read: the query runs against
+// Postgres as if the cache were off. Errors from `load` are passed through and never cached.
+//
+// The generation is read before loading, so a write that commits while the load runs retires
+// the key this result is stored under; a stale result can be written but never read.
+pub async fn cached<T, E, F, Fut>(
+    query: &str,
+    table: &str,
+    sql: &str,
+    params: &[String],
+    load: F,
+) -> Result<(T, CacheStatus), E>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    if !is_enabled() {
+        return bypass(query, load()).await;
+    }
+    let mut conn = match redis_connection().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            log::debug!("SQL cache bypassed for {}: {}", query, e);
+            return bypass(query, load()).await;
+        }
+    };
+    let generation: u64 = match conn.get::<_, Option<u64>>(format!("{}{}", GENERATION_PREFIX, table)).await {
+        Ok(generation) => generation.unwrap_or(0),
+        Err(e) => {
+            log::debug!("SQL cache bypassed for {}: {}", query, e);
+            return bypass(query, load()).await;
+        }
+    };
+    let key = cache_key(table, generation, sql, params);
+
+    match conn.get::<_, Option<String>>(&key).await {
+        Ok(Some(raw)) => match serde_json::from_str(&raw) {
+            Ok(value) => {
+                record_lookup(query, CacheStatus::Hit);
+                return Ok((value, CacheStatus::Hit));
+            }
+            // Written by an older build with a different shape; reload and overwrite
+            Err(e) => log::debug!("Discarding unreadable SQL cache entry {}: {}", key, e),
+        },
+        Ok(None) => {}
+        Err(e) => {
+            log::debug!("SQL cache bypassed for {}: {}", query, e);
+            return bypass(query, load()).await;
+        }
+    }
+
+    record_lookup(query, CacheStatus::Miss);
+    let value = load().await?;
+    match serde_json::to_string(&value) {
+        Ok(raw) => {
+            if let Err(e) = conn.set_ex::<_, _, ()>(&key, raw, ttl_seconds()).await {
+                log::debug!("SQL cache store failed for {}: {}", key, e);
+            }
+        }
+        Err(e) => log::debug!("SQL cache serialization failed for {}: {}", query, e),
+    }
+    Ok((value, CacheStatus::Miss))
+}
+
+// Retire every cached result for a table. Always attempted, even with the cache off here,
+// since other replicas may have it on. If Redis is unreachable the old entries stay readable
+// until their TTL runs out.
+pub async fn invalidate(table: &str, source: &str) {
+    SQL_CACHE_INVALIDATIONS_TOTAL.with_label_values(&[table, source]).inc();
+    *INVALIDATIONS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .entry((table.to_string(), source.to_string()))
+        .or_default() += 1;
+    if !services::is_enabled("redis") {
+        return;
+    }
+    let result = match redis_connection().await {
+        Ok(mut conn) => conn
+            .incr::<_, _, u64>(format!("{}{}", GENERATION_PREFIX, table), 1)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        log::warn!("SQL cache invalidation of {} failed: {}", table, e);
+    }
+}
+
+// ============================================================================
+// LISTEN/NOTIFY invalidation
+// ============================================================================
+
+async fn install_notify_trigger(client: &tokio_postgres::Client, table: &str) -> Result<(), String> {
+    let sql = format!(
+        "CREATE OR REPLACE FUNCTION sql_cache_notify() RETURNS trigger AS $$
+         BEGIN
+             PERFORM pg_notify('{channel}', TG_TABLE_NAME);
+             RETURN NULL;
+         END $$ LANGUAGE plpgsql;
+         CREATE OR REPLACE TRIGGER {table}_sql_cache_notify
+             AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON {table}
+             FOR EACH STATEMENT EXECUTE FUNCTION sql_cache_notify();",
+        channel = NOTIFY_CHANNEL,
+        table = table
+    );
+    client
+        .batch_execute(&sql)
+        .await
+        .map_err(|e| format!("Installing notify trigger on {} failed: {}", table, e))
+}
+
+async fn listen_for_changes() -> Result<(), String> {
+    let (client, mut connection) = postgres_connect().await?;
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+
+    // Notifications only arrive through the connection's message stream, so drive it here
+    // rather than with the usual spawned `connection.await`
+    let driver = tokio::spawn(async move {
+        let mut messages = futures_util::stream::poll_fn(move |cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            match message {
+                Ok(AsyncMessage::Notification(notification)) => {
+                    if tx.send(notification.payload().to_string()).is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => return Err(format!("Connection error: {}", e)),
+            }
+        }
+        Ok(())
+    });
+
+    ensure_items_table(&client).await?;
+    install_notify_trigger(&client, ITEMS_TABLE).await?;
+    client
+        .batch_execute(&format!("LISTEN {}", NOTIFY_CHANNEL))
+        .await
+        .map_err(|e| format!("LISTEN failed: {}", e))?;
+    log::info!("SQL cache listening for changes on channel {}", NOTIFY_CHANNEL);
+
+    while let Some(table) = rx.recv().await {
+        invalidate(&table, "notify").await;
+    }
+    driver.await.map_err(|e| format!("Listener task failed: {}", e))??;
+    Err("Listener connection closed".to_string())
+}
+
+pub fn spawn_notify_listener() {
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = listen_for_changes().await {
+                log::warn!("SQL cache change listener interrupted: {}", e);
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    });
+}
+
+// ============================================================================
+// Stats endpoint
+// ============================================================================
+
+pub fn stats_snapshot() -> serde_json::Value {
+    let stats = STATS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+    let mut total = QueryStats::default();
+    let mut queries = serde_json::Map::new();
+    for (query, entry) in stats.iter().collect::<BTreeMap<_, _>>() {
+        total.hits += entry.hits;
+        total.misses += entry.misses;
+        total.bypassed += entry.bypassed;
+        let mut value = serde_json::to_value(entry).unwrap_or_default();
+        value["hit_rate"] = serde_json::json!(entry.hit_rate());
+        queries.insert(query.clone(), value);
+    }
+    let invalidations: Vec<serde_json::Value> = INVALIDATIONS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .iter()
+        .map(|((table, source), count)| serde_json::json!({ "table": table, "source": source, "count": count }))
+        .collect();
+    serde_json::json!({
+        "enabled": is_enabled(),
+        "listen": listen_enabled(),
+        "ttl_seconds": ttl_seconds(),
+        "hits": total.hits,
+        "misses": total.misses,
+        "bypassed": total.bypassed,
+        "hit_rate": total.hit_rate(),
+        "queries": queries,
+        "invalidations": invalidations
+    })
+}
+
+pub async fn sql_cache_stats() -> impl Responder {
+    HttpResponse::Ok().json(stats_snapshot())
+}
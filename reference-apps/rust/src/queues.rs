@@ -0,0 +1,373 @@
+// RabbitMQ queue declaration options, a priority / max-length demo, and a queue type comparison
+//
+// Queue arguments are fixed when a queue is first declared; redeclaring with different ones
+// fails with PRECONDITION_FAILED, so demos use fresh queues that are removed afterwards.
+
+use std::time::{Duration, Instant};
+
+use actix_web::{web, HttpResponse, Responder};
+use futures_util::StreamExt;
+use lapin::types::{AMQPValue, FieldTable};
+use serde::Deserialize;
+
+use crate::amqp_connection;
+
+const OVERFLOW_MODES: &[&str] = &["drop-head", "reject-publish", "reject-publish-dlx"];
+pub const QUEUE_TYPES: &[&str] = &["classic", "quorum", "stream"];
+
+#[derive(Deserialize, Default, Clone, Debug)]
+pub struct QueueOptions {
+    // Enables priorities 0..=max_priority (RabbitMQ recommends at most 10)
+    pub max_priority: Option<u8>,
+    pub max_length: Option<u32>,
+    // What happens at max_length: drop-head (default), reject-publish, reject-publish-dlx
+    pub overflow: Option<String>,
+    // classic (default), quorum, or stream
+    pub queue_type: Option<String>,
+}
+
+impl QueueOptions {
+    fn queue_type(&self) -> &str {
+        self.queue_type.as_deref().unwrap_or("classic")
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_priority == Some(0) {
+            return Err("max_priority must be between 1 and 255".to_string());
+        }
+        let queue_type = self.queue_type();
+        if !QUEUE_TYPES.contains(&queue_type) {
+            return Err(format!("queue_type must be one of: {}", QUEUE_TYPES.join(", ")));
+        }
+        if queue_type != "classic" && self.max_priority.is_some() {
+            return Err(format!("{} queues don't support max_priority", queue_type));
+        }
+        if queue_type == "stream" && self.overflow.is_some() {
+            return Err("stream queues don't support overflow; use retention limits instead".to_string());
+        }
+        if queue_type == "quorum" && self.overflow.as_deref() == Some("reject-publish-dlx") {
+            return Err("quorum queues don't support reject-publish-dlx; use reject-publish or drop-head".to_string());
+        }
+        if let Some(overflow) = &self.overflow {
+            if !OVERFLOW_MODES.contains(&overflow.as_str()) {
+                return Err(format!("overflow must be one of: {}", OVERFLOW_MODES.join(", ")));
+            }
+            if self.max_length.is_none() {
+                return Err("overflow requires max_length".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn arguments(&self) -> FieldTable {
+        let mut args = FieldTable::default();
+        if let Some(priority) = self.max_priority {
+            args.insert("x-max-priority".into(), AMQPValue::ShortShortUInt(priority));
+        }
+        if let Some(length) = self.max_length {
+            args.insert("x-max-length".into(), AMQPValue::LongUInt(length));
+        }
+        if let Some(overflow) = &self.overflow {
+            args.insert("x-overflow".into(), AMQPValue::LongString(overflow.as_str().into()));
+        }
+        if let Some(queue_type) = &self.queue_type {
+            args.insert("x-queue-type".into(), AMQPValue::LongString(queue_type.as_str().into()));
+        }
+        args
+    }
+
+    // Quorum queues and streams are replicated and must be durable and non-exclusive
+    pub fn declare_options(&self) -> lapin::options::QueueDeclareOptions {
+        lapin::options::QueueDeclareOptions {
+            durable: self.queue_type() != "classic",
+            ..Default::default()
+        }
+    }
+}
+
+// AMQP reply codes of the channel exceptions the examples map to HTTP statuses
+pub const REPLY_NOT_FOUND: u16 = 404;
+pub const REPLY_PRECONDITION_FAILED: u16 = 406;
+
+// Reply code of the channel or connection exception behind `error`, if the broker raised one
+pub fn reply_code(error: &lapin::Error) -> Option<u16> {
+    match error.kind() {
+        lapin::ErrorKind::ProtocolError(amqp) => Some(amqp.get_id()),
+        _ => None,
+    }
+}
+
+// A publish that conflicts with how the queue was first declared
+pub fn is_precondition_failed(error: &lapin::Error) -> bool {
+    reply_code(error) == Some(REPLY_PRECONDITION_FAILED)
+}
+
+// ============================================================================
+// Priority demo
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct PriorityDemoRequest {
+    // Published in this order, one message each
+    #[serde(default = "default_priorities")]
+    priorities: Vec<u8>,
+    #[serde(default)]
+    max_length: Option<u32>,
+    #[serde(default)]
+    overflow: Option<String>,
+}
+
+fn default_priorities() -> Vec<u8> {
+    vec![1, 5, 9, 3, 9, 0, 7]
+}
+
+pub async fn priority_demo(body: web::Json<PriorityDemoRequest>) -> impl Responder {
+    let max_priority = body.priorities.iter().copied().max().unwrap_or(0).max(1);
+    let options = QueueOptions {
+        max_priority: Some(max_priority),
+        max_length: body.max_length,
+        overflow: body.overflow.clone(),
+        queue_type: None,
+    };
+    if let Err(e) = options.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "status": "error", "error": e }));
+    }
+    if body.priorities.is_empty() || body.priorities.len() > 100 {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "status": "error",
+            "error": "priorities must list 1 to 100 values"
+        }));
+    }
+
+    let conn = match amqp_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return HttpResponse::ServiceUnavailable().json(serde_json::json!({ "status": "error", "error": e })),
+    };
+    let queue = format!("priority-demo-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+
+    let result = async {
+        let channel = conn.create_channel().await.map_err(|e| format!("Channel creation failed: {}", e))?;
+        channel
+            .confirm_select(lapin::options::ConfirmSelectOptions::default())
+            .await
+            .map_err(|e| format!("confirm.select failed: {}", e))?;
+        let declare = lapin::options::QueueDeclareOptions { exclusive: true, ..Default::default() };
+        channel
+            .queue_declare(&queue, declare, options.arguments())
+            .await
+            .map_err(|e| format!("Queue declare failed: {}", e))?;
+
+        // Everything is published before the first get, so the broker can reorder by priority
+        let mut published = Vec::new();
+        for (seq, priority) in body.priorities.iter().enumerate() {
+            let payload = serde_json::json!({ "seq": seq, "priority": priority }).to_string();
+            let confirmation = channel
+                .basic_publish(
+                    "",
+                    &queue,
+                    lapin::options::BasicPublishOptions::default(),
+                    payload.as_bytes(),
+                    lapin::BasicProperties::default().with_priority(*priority),
+                )
+                .await
+                .map_err(|e| format!("Publish failed: {}", e))?
+                .await
+                .map_err(|e| format!("Publish confirm failed: {}", e))?;
+            published.push(serde_json::json!({
+                "seq": seq,
+                "priority": priority,
+                // reject-publish nacks messages that don't fit
+                "accepted": confirmation.is_ack()
+            }));
+        }
+
+        let mut consumed = Vec::new();
+        while let Some(message) = channel
+            .basic_get(queue.as_str(), lapin::options::BasicGetOptions { no_ack: true })
+            .await
+            .map_err(|e| format!("basic.get failed: {}", e))?
+        {
+            consumed.push(serde_json::from_slice::<serde_json::Value>(&message.delivery.data).unwrap_or_default());
+        }
+        Ok::<_, String>((published, consumed))
+    }
+    .await;
+    let _ = conn.close(0, "Done").await;
+
+    match result {
+        Ok((published, consumed)) => {
+            let dropped = published.iter().filter(|p| p["accepted"] == true).count().saturating_sub(consumed.len());
+            HttpResponse::Ok().json(serde_json::json!({
+                "status": "success",
+                "queue": queue,
+                "arguments": {
+                    "x-max-priority": options.max_priority,
+                    "x-max-length": options.max_length,
+                    "x-overflow": options.overflow
+                },
+                "published": published,
+                "consumed": consumed,
+                "dropped": dropped
+            }))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({ "status": "error", "queue": queue, "error": e })),
+    }
+}
+
+// ============================================================================
+// Queue type comparison
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct QueueTypesQuery {
+    messages: Option<usize>,
+}
+
+fn durability(queue_type: &str) -> &'static str {
+    match queue_type {
+        "quorum" => "Replicated with Raft; always durable and persistent; survives the loss of a minority of nodes",
+        "stream" => "Replicated append-only log; always durable; reads are non-destructive and replayable by offset",
+        _ => "Single leader node; durable only if declared so and messages are persistent; lost with its node",
+    }
+}
+
+fn percentile(sorted_ms: &[f64], p: f64) -> f64 {
+    if sorted_ms.is_empty() {
+        return 0.0;
+    }
+    let index = ((sorted_ms.len() - 1) as f64 * p).round() as usize;
+    sorted_ms[index]
+}
+
+// Publishes `messages` persistent messages one confirm at a time, consumes them back, and checks
+// what is left in the queue afterwards
+async fn compare_type(conn: &lapin::Connection, queue_type: &str, messages: usize) -> Result<serde_json::Value, String> {
+    let channel = conn.create_channel().await.map_err(|e| format!("Channel creation failed: {}", e))?;
+    let queue = format!("queue-type-demo-{}-{}", queue_type, &uuid::Uuid::new_v4().simple().to_string()[..8]);
+    let options = QueueOptions { queue_type: Some(queue_type.to_string()), ..Default::default() };
+    channel
+        .queue_declare(&queue, options.declare_options(), options.arguments())
+        .await
+        .map_err(|e| format!("Queue declare failed: {}", e))?;
+
+    let outcome = async {
+        channel
+            .confirm_select(lapin::options::ConfirmSelectOptions::default())
+            .await
+            .map_err(|e| format!("confirm.select failed: {}", e))?;
+
+        let publish_started = Instant::now();
+        let mut latencies = Vec::with_capacity(messages);
+        for i in 0..messages {
+            let sent = Instant::now();
+            channel
+                .basic_publish(
+                    "",
+                    &queue,
+                    lapin::options::BasicPublishOptions::default(),
+                    i.to_string().as_bytes(),
+                    lapin::BasicProperties::default().with_delivery_mode(2),
+                )
+                .await
+                .map_err(|e| format!("Publish failed: {}", e))?
+                .await
+                .map_err(|e| format!("Publish confirm failed: {}", e))?;
+            latencies.push(sent.elapsed().as_secs_f64() * 1000.0);
+        }
+        let publish_ms = publish_started.elapsed().as_secs_f64() * 1000.0;
+        latencies.sort_by(|a, b| a.total_cmp(b));
+
+        // Streams need a prefetch and a starting offset; the same consumer setup works for all types
+        channel
+            .basic_qos(100, lapin::options::BasicQosOptions::default())
+            .await
+            .map_err(|e| format!("basic.qos failed: {}", e))?;
+        let mut args = FieldTable::default();
+        if queue_type == "stream" {
+            args.insert("x-stream-offset".into(), AMQPValue::LongString("first".into()));
+        }
+        let mut consumer = channel
+            .basic_consume(&queue, "", lapin::options::BasicConsumeOptions::default(), args)
+            .await
+            .map_err(|e| format!("basic.consume failed: {}", e))?;
+        let consume_started = Instant::now();
+        let mut received = 0;
+        while received < messages {
+            match tokio::time::timeout(Duration::from_secs(10), consumer.next()).await {
+                Ok(Some(Ok(delivery))) => {
+                    delivery
+                        .ack(lapin::options::BasicAckOptions::default())
+                        .await
+                        .map_err(|e| format!("Ack failed: {}", e))?;
+                    received += 1;
+                }
+                Ok(Some(Err(e))) => return Err(format!("Consume failed: {}", e)),
+                Ok(None) | Err(_) => break,
+            }
+        }
+        let consume_ms = consume_started.elapsed().as_secs_f64() * 1000.0;
+
+        // Consumed messages leave classic and quorum queues but stay in a stream
+        let passive = lapin::options::QueueDeclareOptions { passive: true, ..Default::default() };
+        let remaining = channel
+            .queue_declare(&queue, passive, FieldTable::default())
+            .await
+            .map(|q| q.message_count())
+            .ok();
+
+        Ok::<_, String>(serde_json::json!({
+            "queue_type": queue_type,
+            "published": messages,
+            "publish_ms": publish_ms,
+            "confirm_latency_ms": {
+                "p50": percentile(&latencies, 0.50),
+                "p99": percentile(&latencies, 0.99),
+                "max": latencies.last().copied().unwrap_or(0.0)
+            },
+            "consumed": received,
+            "consume_ms": consume_ms,
+            "messages_left_after_consume": remaining,
+            "durability": durability(queue_type)
+        }))
+    }
+    .await;
+
+    // A failed step may have closed the channel, so the queue is deleted on a fresh one
+    let _ = channel.close(0, "Done").await;
+    let deleted = match conn.create_channel().await {
+        Ok(cleanup) => {
+            let deleted = cleanup.queue_delete(&queue, lapin::options::QueueDeleteOptions::default()).await;
+            let _ = cleanup.close(0, "Done").await;
+            deleted.map(|_| ()).map_err(|e| e.to_string())
+        }
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = deleted {
+        log::warn!("Failed to delete demo queue {}: {}", queue, e);
+    }
+    outcome
+}
+
+pub async fn queue_types_demo(query: web::Query<QueueTypesQuery>) -> impl Responder {
+    let messages = query.messages.unwrap_or(500).clamp(1, 10_000);
+    let conn = match amqp_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return HttpResponse::ServiceUnavailable().json(serde_json::json!({ "status": "error", "error": e })),
+    };
+
+    // A type the broker doesn't support (e.g. streams without the plugin) is reported, not fatal
+    let mut results = Vec::new();
+    for queue_type in QUEUE_TYPES {
+        results.push(match compare_type(&conn, queue_type, messages).await {
+            Ok(result) => result,
+            Err(e) => serde_json::json!({ "queue_type": queue_type, "error": e, "durability": durability(queue_type) }),
+        });
+    }
+    let _ = conn.close(0, "Done").await;
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "messages": messages,
+        "results": results
+    }))
+}
@@ -0,0 +1,455 @@
+// Named quotas enforced with Redis token buckets
+//
+// A quota allows `limit` operations per `window_seconds`. Definitions live in the `quotas` hash
+// as JSON and are cached in-process for QUOTA_CACHE_MS (default 1000), like feature flags. Each
+// subject (the caller's IP address; a client-chosen header would let it pick a fresh bucket) gets a bucket at `quota:{<name>}:<subject>`
+// holding `limit` tokens that refill continuously at limit/window. A Lua script refills, takes
+// and stores in one step, on the Redis server's clock, so replicas sharing a bucket can't both
+// spend its last token and don't need their clocks in sync. A bucket left alone for a window is
+// full again, so it expires then.
+//
+// QUOTA_ROUTES applies quotas to routes: `<path prefix>=<quota name>`, comma-separated (default
+// `/examples/quota/demo=demo`). Responses on those routes carry X-RateLimit-Limit,
+// X-RateLimit-Remaining and X-RateLimit-Reset (seconds until the bucket is full again); a request
+// over the quota gets 429 with Retry-After. Routes whose quota isn't defined, and every route
+// while Redis is unreachable, are let through.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::admin_auth;
+use crate::{get_env_or, redis_connection, QUOTA_DECISIONS_TOTAL};
+
+const QUOTAS_KEY: &str = "quotas";
+const DEFAULT_ROUTES: &str = "/examples/quota/demo=demo";
+const MAX_LIMIT: u64 = 1_000_000;
+const MAX_WINDOW_SECONDS: u64 = 86_400;
+
+// Refill and take `cost` tokens atomically. KEYS[1] = bucket, ARGV = limit, window ms, cost.
+// Returns {allowed (0/1), whole tokens left, ms until full, ms until `cost` tokens are available}.
+// Tokens are stored as strings: Lua numbers returned to Redis would be truncated to integers.
+const TAKE_TOKENS: &str = r#"
+local limit = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local cost = tonumber(ARGV[3])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+local state = redis.call('HMGET', KEYS[1], 'tokens', 'updated_ms')
+local tokens = tonumber(state[1]) or limit
+local updated = tonumber(state[2]) or now
+tokens = math.min(limit, tokens + math.max(0, now - updated) * limit / window)
+
+local allowed = 0
+if tokens >= cost then
+    tokens = tokens - cost
+    allowed = 1
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'updated_ms', tostring(now))
+redis.call('PEXPIRE', KEYS[1], window)
+
+local reset_ms = math.ceil((limit - tokens) * window / limit)
+local retry_ms = 0
+if allowed == 0 then
+    retry_ms = math.ceil((cost - tokens) * window / limit)
+end
+return {allowed, math.floor(tokens), reset_ms, retry_ms}
+"#;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Quota {
+    pub limit: u64,
+    pub window_seconds: u64,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+impl Quota {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.limit == 0 || self.limit > MAX_LIMIT {
+            return Err(format!("limit must be 1-{}", MAX_LIMIT));
+        }
+        if self.window_seconds == 0 || self.window_seconds > MAX_WINDOW_SECONDS {
+            return Err(format!("window_seconds must be 1-{}", MAX_WINDOW_SECONDS));
+        }
+        Ok(())
+    }
+}
+
+// The outcome of taking tokens from a bucket
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u64,
+    pub remaining: u64,
+    // Until the bucket is full again
+    pub reset_ms: u64,
+    // Until the denied request would fit; 0 when allowed
+    pub retry_after_ms: u64,
+}
+
+impl Decision {
+    pub fn from_reply(limit: u64, reply: &[i64]) -> Option<Self> {
+        let [allowed, remaining, reset_ms, retry_ms] = reply else {
+            return None;
+        };
+        Some(Decision {
+            allowed: *allowed == 1,
+            limit,
+            remaining: (*remaining).max(0) as u64,
+            reset_ms: (*reset_ms).max(0) as u64,
+            retry_after_ms: (*retry_ms).max(0) as u64,
+        })
+    }
+
+    // X-RateLimit-* headers, plus Retry-After when denied; times round up to whole seconds
+    pub fn headers(&self) -> Vec<(HeaderName, String)> {
+        let seconds = |ms: u64| ms.div_ceil(1000).to_string();
+        let mut headers = vec![
+            (HeaderName::from_static("x-ratelimit-limit"), self.limit.to_string()),
+            (HeaderName::from_static("x-ratelimit-remaining"), self.remaining.to_string()),
+            (HeaderName::from_static("x-ratelimit-reset"), seconds(self.reset_ms)),
+        ];
+        if !self.allowed {
+            headers.push((header::RETRY_AFTER, seconds(self.retry_after_ms.max(1))));
+        }
+        headers
+    }
+
+    fn apply(&self, headers: &mut HeaderMap) {
+        for (name, value) in self.headers() {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(name, value);
+            }
+        }
+    }
+}
+
+pub fn valid_quota_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+// The hash tag keeps every bucket of a quota in one slot
+pub fn bucket_key(name: &str, subject: &str) -> String {
+    format!("quota:{{{}}}:{}", name, subject)
+}
+
+// (path prefix, quota name) pairs from `<prefix>=<name>,...`
+pub fn parse_routes(value: &str) -> Result<Vec<(String, String)>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let invalid = || format!("Invalid quota route {:?}: expected <path prefix>=<quota name>", entry);
+            let (prefix, name) = entry.split_once('=').ok_or_else(invalid)?;
+            let (prefix, name) = (prefix.trim(), name.trim());
+            if !prefix.starts_with('/') || !valid_quota_name(name) {
+                return Err(invalid());
+            }
+            Ok((prefix.to_string(), name.to_string()))
+        })
+        .collect()
+}
+
+// The quota of the longest matching prefix
+pub fn quota_for_path<'a>(routes: &'a [(String, String)], path: &str) -> Option<&'a str> {
+    routes
+        .iter()
+        .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, name)| name.as_str())
+}
+
+lazy_static! {
+    static ref QUOTA_CACHE: Mutex<Option<(Instant, BTreeMap<String, Quota>)>> = Mutex::new(None);
+    static ref ROUTES: Vec<(String, String)> = parse_routes(&get_env_or("QUOTA_ROUTES", DEFAULT_ROUTES))
+        .unwrap_or_else(|e| {
+            log::warn!("{}; no routes are rate limited", e);
+            Vec::new()
+        });
+}
+
+fn cache_ttl() -> Duration {
+    Duration::from_millis(get_env_or("QUOTA_CACHE_MS", "1000").parse().unwrap_or(1000))
+}
+
+fn invalidate_cache() {
+    if let Ok(mut cache) = QUOTA_CACHE.lock() {
+        *cache = None;
+    }
+}
+
+pub async fn load_quotas() -> Result<BTreeMap<String, Quota>, String> {
+    if let Ok(cache) = QUOTA_CACHE.lock() {
+        if let Some((loaded_at, quotas)) = cache.as_ref() {
+            if loaded_at.elapsed() < cache_ttl() {
+                return Ok(quotas.clone());
+            }
+        }
+    }
+
+    let mut conn = redis_connection().await?;
+    let raw: BTreeMap<String, String> = redis::cmd("HGETALL")
+        .arg(QUOTAS_KEY)
+        .query_async(&mut conn)
+        .await
+        .map_err(|e| format!("HGETALL failed: {}", e))?;
+    let quotas: BTreeMap<String, Quota> = raw
+        .into_iter()
+        .filter_map(|(name, json)| match serde_json::from_str(&json) {
+            Ok(quota) => Some((name, quota)),
+            Err(e) => {
+                log::warn!("Ignoring malformed quota {}: {}", name, e);
+                None
+            }
+        })
+        .collect();
+
+    if let Ok(mut cache) = QUOTA_CACHE.lock() {
+        *cache = Some((Instant::now(), quotas.clone()));
+    }
+    Ok(quotas)
+}
+
+// Takes `cost` tokens (0 just looks) from the subject's bucket
+pub async fn take(name: &str, quota: &Quota, subject: &str, cost: u64) -> Result<Decision, String> {
+    let mut conn = redis_connection().await?;
+    let reply: Vec<i64> = redis::Script::new(TAKE_TOKENS)
+        .key(bucket_key(name, subject))
+        .arg(quota.limit)
+        .arg(quota.window_seconds * 1000)
+        .arg(cost)
+        .invoke_async(&mut conn)
+        .await
+        .map_err(|e| format!("Quota script failed: {}", e))?;
+    let decision =
+        Decision::from_reply(quota.limit, &reply).ok_or_else(|| format!("Unexpected quota reply {:?}", reply))?;
+    if cost > 0 {
+        let result = if decision.allowed { "allowed" } else { "denied" };
+        QUOTA_DECISIONS_TOTAL.with_label_values(&[name, result]).inc();
+    }
+    Ok(decision)
+}
+
+fn subject(peer: Option<std::net::SocketAddr>) -> String {
+    peer.map(|addr| format!("ip:{}", addr.ip())).unwrap_or_else(|| "anonymous".to_string())
+}
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+fn quota_exceeded(name: &str, decision: &Decision) -> HttpResponse {
+    let mut response = HttpResponse::TooManyRequests().json(serde_json::json!({
+        "status": "error",
+        "quota": name,
+        "error": format!("Quota '{}' exceeded, retry later", name),
+        "retry_after_ms": decision.retry_after_ms
+    }));
+    decision.apply(response.headers_mut());
+    response
+}
+
+// ============================================================================
+// Middleware
+// ============================================================================
+
+// Enforces the QUOTA_ROUTES quota of the request's path
+pub async fn quota_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let Some(name) = quota_for_path(&ROUTES, req.path()) else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    let subject = subject(req.peer_addr());
+    let decision = match load_quotas().await.map(|mut quotas| quotas.remove(name)) {
+        Ok(Some(quota)) => take(name, &quota, &subject, 1).await,
+        Ok(None) => return Ok(next.call(req).await?.map_into_boxed_body()),
+        Err(e) => Err(e),
+    };
+
+    match decision {
+        Ok(decision) if !decision.allowed => Ok(req.into_response(quota_exceeded(name, &decision))),
+        Ok(decision) => {
+            let mut res = next.call(req).await?;
+            decision.apply(res.headers_mut());
+            Ok(res.map_into_boxed_body())
+        }
+        // Fail open: an outage of the limiter shouldn't become an outage of the routes
+        Err(e) => {
+            log::debug!("Quota '{}' not enforced: {}", name, e);
+            Ok(next.call(req).await?.map_into_boxed_body())
+        }
+    }
+}
+
+// ============================================================================
+// Quota management
+// ============================================================================
+
+pub async fn list_quotas() -> impl Responder {
+    let routes: Vec<serde_json::Value> =
+        ROUTES.iter().map(|(prefix, name)| serde_json::json!({ "prefix": prefix, "quota": name })).collect();
+    match load_quotas().await {
+        Ok(quotas) => HttpResponse::Ok().json(serde_json::json!({
+            "count": quotas.len(),
+            "quotas": quotas,
+            "routes": routes
+        })),
+        Err(e) => error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SetQuotaRequest {
+    limit: u64,
+    window_seconds: u64,
+    description: Option<String>,
+}
+
+// Quota definitions are what the quota middleware enforces, so changing them needs the admin token
+pub async fn set_quota(req: HttpRequest, path: web::Path<String>, body: web::Json<SetQuotaRequest>) -> impl Responder {
+    if let Err(response) = admin_auth::authorize(&req) {
+        return *response;
+    }
+    let name = path.into_inner();
+    if !valid_quota_name(&name) {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, "Invalid quota name".to_string());
+    }
+    let quota = Quota {
+        limit: body.limit,
+        window_seconds: body.window_seconds,
+        description: body.description.clone(),
+        updated_at: Some(chrono::Utc::now().to_rfc3339()),
+    };
+    if let Err(e) = quota.validate() {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, e);
+    }
+    let json = match serde_json::to_string(&quota) {
+        Ok(json) => json,
+        Err(e) => return error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let mut conn = match redis_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+    match redis::cmd("HSET").arg(QUOTAS_KEY).arg(&name).arg(json).query_async::<i64>(&mut conn).await {
+        Ok(_) => {
+            invalidate_cache();
+            HttpResponse::Ok().json(serde_json::json!({ "status": "saved", "name": name, "quota": quota }))
+        }
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("HSET failed: {}", e)),
+    }
+}
+
+// Buckets of a deleted quota are left to expire
+pub async fn delete_quota(req: HttpRequest, path: web::Path<String>) -> impl Responder {
+    if let Err(response) = admin_auth::authorize(&req) {
+        return *response;
+    }
+    let name = path.into_inner();
+    let mut conn = match redis_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+    match redis::cmd("HDEL").arg(QUOTAS_KEY).arg(&name).query_async::<i64>(&mut conn).await {
+        Ok(0) => error_response(actix_web::http::StatusCode::NOT_FOUND, format!("Unknown quota '{}'", name)),
+        Ok(_) => {
+            invalidate_cache();
+            HttpResponse::Ok().json(serde_json::json!({ "status": "deleted", "name": name }))
+        }
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("HDEL failed: {}", e)),
+    }
+}
+
+async fn find_quota(name: &str) -> Result<Quota, HttpResponse> {
+    match load_quotas().await {
+        Ok(mut quotas) => quotas
+            .remove(name)
+            .ok_or_else(|| error_response(actix_web::http::StatusCode::NOT_FOUND, format!("Unknown quota '{}'", name))),
+        Err(e) => Err(error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e)),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ConsumeRequest {
+    // Defaults to the caller, as the middleware sees it
+    subject: Option<String>,
+    cost: Option<u64>,
+}
+
+// POST /examples/quota/quotas/{name}/consume
+pub async fn consume(req: HttpRequest, path: web::Path<String>, body: web::Json<ConsumeRequest>) -> impl Responder {
+    // Spending from someone else's bucket could lock them out, so naming a subject is an admin operation
+    if body.subject.is_some() {
+        if let Err(response) = admin_auth::authorize(&req) {
+            return *response;
+        }
+    }
+    let name = path.into_inner();
+    let quota = match find_quota(&name).await {
+        Ok(quota) => quota,
+        Err(response) => return response,
+    };
+    let cost = body.cost.unwrap_or(1);
+    if cost == 0 || cost > quota.limit {
+        return error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            format!("cost must be 1-{} (the quota's limit)", quota.limit),
+        );
+    }
+    let subject = body.subject.clone().unwrap_or_else(|| subject(req.peer_addr()));
+    match take(&name, &quota, &subject, cost).await {
+        Ok(decision) if !decision.allowed => quota_exceeded(&name, &decision),
+        Ok(decision) => {
+            let mut response = HttpResponse::Ok().json(serde_json::json!({
+                "status": "allowed",
+                "quota": name,
+                "subject": subject,
+                "decision": decision
+            }));
+            decision.apply(response.headers_mut());
+            response
+        }
+        Err(e) => error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    }
+}
+
+// GET /examples/quota/quotas/{name}/subjects/{subject} - the bucket, without taking from it
+pub async fn bucket_state(path: web::Path<(String, String)>) -> impl Responder {
+    let (name, subject) = path.into_inner();
+    let quota = match find_quota(&name).await {
+        Ok(quota) => quota,
+        Err(response) => return response,
+    };
+    match take(&name, &quota, &subject, 0).await {
+        Ok(decision) => HttpResponse::Ok().json(serde_json::json!({
+            "quota": name,
+            "subject": subject,
+            "limit": decision.limit,
+            "remaining": decision.remaining,
+            "reset_ms": decision.reset_ms
+        })),
+        Err(e) => error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    }
+}
+
+// GET /examples/quota/demo - does nothing; the middleware enforces the `demo` quota on it
+pub async fn demo() -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({ "status": "ok", "quota": "demo" }))
+}
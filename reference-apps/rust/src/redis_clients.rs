@@ -0,0 +1,151 @@
+// Redis client inspection and connection kill, for tracking down connection leaks
+//
+// CLIENT LIST is parsed into JSON. Kills select clients from that list by id, address, and
+// idle time, then issue CLIENT KILL ID for each, so the same filters work on every Redis version.
+
+use std::collections::BTreeMap;
+
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::admin_auth;
+use crate::redis_diagnostics::{error_response, named_node_connection};
+
+// Fields that CLIENT LIST reports as integers
+const NUMERIC_FIELDS: &[&str] = &[
+    "id", "fd", "age", "idle", "db", "sub", "psub", "ssub", "multi", "watch", "qbuf", "qbuf-free",
+    "argv-mem", "multi-mem", "obl", "oll", "omem", "tot-mem", "rbs", "rbp", "redir", "tot-net-in",
+    "tot-net-out", "tot-cmds",
+];
+
+// One line per client: `id=5 addr=172.20.0.1:40122 laddr=... name= age=12 idle=3 flags=N ...`
+pub fn parse_client_list(raw: &str) -> Vec<BTreeMap<String, Value>> {
+    raw.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            line.split_whitespace()
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(key, value)| {
+                    let value = match value.parse::<i64>() {
+                        Ok(n) if NUMERIC_FIELDS.contains(&key) => Value::from(n),
+                        _ => Value::from(value),
+                    };
+                    (key.to_string(), value)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[derive(Deserialize, Default)]
+pub struct KillRequest {
+    pub id: Option<i64>,
+    pub addr: Option<String>,
+    // Only clients idle at least this many seconds
+    pub idle: Option<i64>,
+}
+
+impl KillRequest {
+    fn is_empty(&self) -> bool {
+        self.id.is_none() && self.addr.is_none() && self.idle.is_none()
+    }
+}
+
+// IDs of clients matching every given filter, never including the caller's own connection
+pub fn matching_clients(clients: &[BTreeMap<String, Value>], filter: &KillRequest, own_id: i64) -> Vec<i64> {
+    clients
+        .iter()
+        .filter_map(|client| {
+            let id = client.get("id")?.as_i64()?;
+            let addr = client.get("addr").and_then(Value::as_str);
+            let idle = client.get("idle").and_then(Value::as_i64);
+            let matches = id != own_id
+                && filter.id.is_none_or(|wanted| wanted == id)
+                && filter.addr.as_deref().is_none_or(|wanted| addr == Some(wanted))
+                && filter.idle.is_none_or(|min| idle.is_some_and(|idle| idle >= min));
+            matches.then_some(id)
+        })
+        .collect()
+}
+
+async fn client_list(conn: &mut redis::aio::MultiplexedConnection) -> Result<Vec<BTreeMap<String, Value>>, String> {
+    redis::cmd("CLIENT")
+        .arg("LIST")
+        .query_async::<String>(conn)
+        .await
+        .map(|raw| parse_client_list(&raw))
+        .map_err(|e| format!("CLIENT LIST failed: {}", e))
+}
+
+pub async fn list_clients(path: web::Path<String>) -> impl Responder {
+    let node = path.into_inner();
+    let mut conn = match named_node_connection(&node).await {
+        Ok(conn) => conn,
+        Err(response) => return response,
+    };
+
+    match client_list(&mut conn).await {
+        Ok(clients) => HttpResponse::Ok().json(serde_json::json!({
+            "status": "success",
+            "node": node,
+            "count": clients.len(),
+            "clients": clients
+        })),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+pub async fn kill_clients(req: HttpRequest, path: web::Path<String>, body: web::Json<KillRequest>) -> impl Responder {
+    if let Err(response) = admin_auth::authorize(&req) {
+        return *response;
+    }
+    if body.is_empty() {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "At least one filter (id, addr, idle) is required".to_string(),
+        );
+    }
+
+    let node = path.into_inner();
+    let mut conn = match named_node_connection(&node).await {
+        Ok(conn) => conn,
+        Err(response) => return response,
+    };
+
+    let listing = async {
+        let own_id: i64 = redis::cmd("CLIENT")
+            .arg("ID")
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| format!("CLIENT ID failed: {}", e))?;
+        let clients = client_list(&mut conn).await?;
+        Ok::<_, String>(matching_clients(&clients, &body, own_id))
+    }
+    .await;
+    let targets = match listing {
+        Ok(targets) => targets,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+    };
+
+    let mut killed = Vec::new();
+    let mut errors = Vec::new();
+    for id in targets {
+        // Replies with the number of clients killed; 0 if it disconnected in the meantime
+        match redis::cmd("CLIENT").arg("KILL").arg("ID").arg(id).query_async::<i64>(&mut conn).await {
+            Ok(0) => {}
+            Ok(_) => killed.push(id),
+            Err(e) => errors.push(serde_json::json!({ "id": id, "error": e.to_string() })),
+        }
+    }
+
+    log::info!("Killed {} client connection(s) on {}", killed.len(), node);
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": if errors.is_empty() { "success" } else { "partial" },
+        "node": node,
+        "killed": killed.len(),
+        "killed_ids": killed,
+        "errors": errors
+    }))
+}
@@ -0,0 +1,367 @@
+// Redis diagnostics: memory usage by key, slow log, and per-command statistics
+//
+// Keys are sampled with SCAN and sized with MEMORY USAGE, like `redis-cli --bigkeys --memkeys`.
+// Only the largest keys are kept while scanning, so memory use is bounded by `count`.
+
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+
+use crate::{redis_master_addresses, redis_node_connection, REDIS_NODES};
+
+const DEFAULT_TOP_COUNT: usize = 20;
+const MAX_TOP_COUNT: usize = 1_000;
+const DEFAULT_SAMPLE: u64 = 10_000;
+const MAX_SAMPLE: u64 = 1_000_000;
+const SCAN_BATCH: u64 = 1_000;
+
+#[derive(Deserialize)]
+pub struct TopKeysQuery {
+    count: Option<usize>,
+    // Maximum keys sampled per master
+    sample: Option<u64>,
+    pattern: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct KeyMemory {
+    pub key: String,
+    pub bytes: u64,
+    #[serde(rename = "type")]
+    pub key_type: String,
+    // None when the key has no expiry
+    pub ttl_seconds: Option<i64>,
+}
+
+// Largest first, ties broken by key name so results are stable
+pub fn keep_largest(keys: &mut Vec<(String, u64)>, count: usize) {
+    keys.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    keys.truncate(count);
+}
+
+async fn node_top_keys(address: &str, pattern: &str, count: usize, sample: u64) -> Result<(u64, Vec<KeyMemory>), String> {
+    let mut conn = redis_node_connection(address).await?;
+    let mut largest: Vec<(String, u64)> = Vec::new();
+    let mut scanned = 0u64;
+    let mut cursor = 0u64;
+
+    loop {
+        let (next_cursor, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(pattern)
+            .arg("COUNT")
+            .arg(SCAN_BATCH)
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| format!("SCAN failed on {}: {}", address, e))?;
+
+        if !batch.is_empty() {
+            let mut pipe = redis::pipe();
+            for key in &batch {
+                pipe.cmd("MEMORY").arg("USAGE").arg(key);
+            }
+            // Nil when a key expired between SCAN and MEMORY USAGE
+            let sizes: Vec<Option<u64>> = pipe
+                .query_async(&mut conn)
+                .await
+                .map_err(|e| format!("MEMORY USAGE failed on {}: {}", address, e))?;
+            scanned += batch.len() as u64;
+            largest.extend(batch.into_iter().zip(sizes).filter_map(|(key, size)| size.map(|s| (key, s))));
+            keep_largest(&mut largest, count);
+        }
+
+        cursor = next_cursor;
+        if cursor == 0 || scanned >= sample {
+            break;
+        }
+    }
+
+    if largest.is_empty() {
+        return Ok((scanned, Vec::new()));
+    }
+
+    // Type and TTL are only needed for the keys that made the cut
+    let mut pipe = redis::pipe();
+    for (key, _) in &largest {
+        pipe.cmd("TYPE").arg(key).cmd("TTL").arg(key);
+    }
+    let details: Vec<(String, i64)> = pipe
+        .query_async::<Vec<redis::Value>>(&mut conn)
+        .await
+        .map_err(|e| format!("TYPE/TTL failed on {}: {}", address, e))
+        .and_then(|values| {
+            values
+                .chunks(2)
+                .map(|pair| match pair {
+                    [key_type, ttl] => Ok((value_string(key_type), value_int(ttl))),
+                    _ => Err("Unexpected pipeline reply".to_string()),
+                })
+                .collect()
+        })?;
+
+    let keys = largest
+        .into_iter()
+        .zip(details)
+        .map(|((key, bytes), (key_type, ttl))| KeyMemory {
+            key,
+            bytes,
+            key_type,
+            ttl_seconds: (ttl >= 0).then_some(ttl),
+        })
+        .collect();
+    Ok((scanned, keys))
+}
+
+fn value_string(value: &redis::Value) -> String {
+    match value {
+        redis::Value::SimpleString(s) => s.clone(),
+        redis::Value::BulkString(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+        _ => "unknown".to_string(),
+    }
+}
+
+fn value_int(value: &redis::Value) -> i64 {
+    match value {
+        redis::Value::Int(i) => *i,
+        _ => -1,
+    }
+}
+
+pub async fn memory_top_keys(query: web::Query<TopKeysQuery>) -> impl Responder {
+    let count = query.count.unwrap_or(DEFAULT_TOP_COUNT);
+    let sample = query.sample.unwrap_or(DEFAULT_SAMPLE);
+    let pattern = query.pattern.clone().unwrap_or_else(|| "*".to_string());
+
+    if count == 0 || count > MAX_TOP_COUNT {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "status": "error",
+            "error": format!("count must be between 1 and {}", MAX_TOP_COUNT)
+        }));
+    }
+    if sample == 0 || sample > MAX_SAMPLE {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "status": "error",
+            "error": format!("sample must be between 1 and {}", MAX_SAMPLE)
+        }));
+    }
+
+    let masters = match redis_master_addresses().await {
+        Ok(masters) => masters,
+        Err(e) => return HttpResponse::ServiceUnavailable().json(serde_json::json!({ "status": "error", "error": e })),
+    };
+
+    let results = futures_util::future::join_all(
+        masters.iter().map(|address| node_top_keys(address, &pattern, count, sample)),
+    )
+    .await;
+
+    let mut nodes = Vec::new();
+    let mut overall: Vec<(String, u64)> = Vec::new();
+    let mut overall_details = std::collections::HashMap::new();
+    for (address, result) in masters.iter().zip(results) {
+        match result {
+            Ok((scanned, keys)) => {
+                for key in &keys {
+                    overall.push((key.key.clone(), key.bytes));
+                    overall_details.insert(key.key.clone(), (address.clone(), key.clone()));
+                }
+                nodes.push(serde_json::json!({ "node": address, "scanned": scanned, "top_keys": keys }));
+            }
+            Err(e) => nodes.push(serde_json::json!({ "node": address, "error": e })),
+        }
+    }
+    keep_largest(&mut overall, count);
+    let overall: Vec<serde_json::Value> = overall
+        .iter()
+        .filter_map(|(key, _)| overall_details.get(key))
+        .map(|(node, key)| {
+            serde_json::json!({
+                "node": node,
+                "key": key.key,
+                "bytes": key.bytes,
+                "type": key.key_type,
+                "ttl_seconds": key.ttl_seconds
+            })
+        })
+        .collect();
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "pattern": pattern,
+        "count": count,
+        "sample_per_node": sample,
+        "top_keys": overall,
+        "nodes": nodes
+    }))
+}
+
+// ============================================================================
+// Per-node slow log and command statistics
+// ============================================================================
+
+pub fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+// Connects to a named node from REDIS_NODES
+pub async fn named_node_connection(node: &str) -> Result<redis::aio::MultiplexedConnection, HttpResponse> {
+    if !REDIS_NODES.contains(&node) {
+        return Err(error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            format!("Invalid node name. Must be one of: {}", REDIS_NODES.join(", ")),
+        ));
+    }
+    redis_node_connection(&format!("{}:6379", node))
+        .await
+        .map_err(|e| error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e))
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct SlowlogEntry {
+    pub id: i64,
+    pub timestamp: i64,
+    pub duration_us: i64,
+    pub command: Vec<String>,
+    pub client_addr: Option<String>,
+    pub client_name: Option<String>,
+}
+
+// SLOWLOG GET replies with [id, unix time, microseconds, [args...], client addr, client name]
+pub fn parse_slowlog(reply: &redis::Value) -> Vec<SlowlogEntry> {
+    let entries = match reply {
+        redis::Value::Array(entries) => entries,
+        _ => return Vec::new(),
+    };
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let fields = match entry {
+                redis::Value::Array(fields) if fields.len() >= 4 => fields,
+                _ => return None,
+            };
+            let command = match &fields[3] {
+                redis::Value::Array(args) => args.iter().map(value_string).collect(),
+                _ => Vec::new(),
+            };
+            let optional = |index: usize| fields.get(index).map(value_string).filter(|s| !s.is_empty());
+            Some(SlowlogEntry {
+                id: value_int(&fields[0]),
+                timestamp: value_int(&fields[1]),
+                duration_us: value_int(&fields[2]),
+                command,
+                client_addr: optional(4),
+                client_name: optional(5),
+            })
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+pub struct SlowlogQuery {
+    count: Option<u32>,
+    // Clear the slow log after reading it
+    #[serde(default)]
+    reset: bool,
+}
+
+pub async fn node_slowlog(path: web::Path<String>, query: web::Query<SlowlogQuery>) -> impl Responder {
+    let node = path.into_inner();
+    let count = query.count.unwrap_or(128).clamp(1, 10_000);
+    let mut conn = match named_node_connection(&node).await {
+        Ok(conn) => conn,
+        Err(response) => return response,
+    };
+
+    let (reply, length) = match redis::pipe()
+        .cmd("SLOWLOG")
+        .arg("GET")
+        .arg(count)
+        .cmd("SLOWLOG")
+        .arg("LEN")
+        .query_async::<(redis::Value, i64)>(&mut conn)
+        .await
+    {
+        Ok(result) => result,
+        Err(e) => {
+            return error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("SLOWLOG failed: {}", e))
+        }
+    };
+
+    if query.reset {
+        if let Err(e) = redis::cmd("SLOWLOG").arg("RESET").query_async::<()>(&mut conn).await {
+            return error_response(
+                actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+                format!("SLOWLOG RESET failed: {}", e),
+            );
+        }
+    }
+
+    let entries = parse_slowlog(&reply);
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "node": node,
+        "length": length,
+        "returned": entries.len(),
+        "reset": query.reset,
+        "entries": entries
+    }))
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct CommandStat {
+    pub command: String,
+    pub calls: u64,
+    pub usec: u64,
+    pub usec_per_call: f64,
+    pub rejected_calls: u64,
+    pub failed_calls: u64,
+}
+
+// Parses `cmdstat_<name>:calls=..,usec=..,usec_per_call=..` lines, most total time first
+pub fn parse_commandstats(info: &str) -> Vec<CommandStat> {
+    let mut stats: Vec<CommandStat> = info
+        .lines()
+        .filter_map(|line| {
+            let (name, values) = line.trim().strip_prefix("cmdstat_")?.split_once(':')?;
+            let field = |key: &str| {
+                values
+                    .split(',')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, v)| v)
+            };
+            Some(CommandStat {
+                command: name.to_string(),
+                calls: field("calls")?.parse().ok()?,
+                usec: field("usec")?.parse().ok()?,
+                usec_per_call: field("usec_per_call").and_then(|v| v.parse().ok()).unwrap_or(0.0),
+                rejected_calls: field("rejected_calls").and_then(|v| v.parse().ok()).unwrap_or(0),
+                failed_calls: field("failed_calls").and_then(|v| v.parse().ok()).unwrap_or(0),
+            })
+        })
+        .collect();
+    stats.sort_by(|a, b| b.usec.cmp(&a.usec).then_with(|| a.command.cmp(&b.command)));
+    stats
+}
+
+pub async fn node_commandstats(path: web::Path<String>) -> impl Responder {
+    let node = path.into_inner();
+    let mut conn = match named_node_connection(&node).await {
+        Ok(conn) => conn,
+        Err(response) => return response,
+    };
+
+    match redis::cmd("INFO").arg("commandstats").query_async::<String>(&mut conn).await {
+        Ok(info) => {
+            let commands = parse_commandstats(&info);
+            HttpResponse::Ok().json(serde_json::json!({
+                "status": "success",
+                "node": node,
+                "total_calls": commands.iter().map(|c| c.calls).sum::<u64>(),
+                "total_usec": commands.iter().map(|c| c.usec).sum::<u64>(),
+                "commands": commands
+            }))
+        }
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("INFO failed: {}", e)),
+    }
+}
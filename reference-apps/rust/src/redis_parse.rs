@@ -0,0 +1,159 @@
+// Parsers for Redis's diagnostic replies: CLUSTER NODES, CLUSTER SLOTS, CLUSTER INFO and INFO
+//
+// These replies come from whatever server is on the other end, so the parsers treat them as
+// untrusted: malformed lines and fields are skipped, never indexed blindly or trusted for
+// arithmetic. Property tests live in tests.rs; fuzz/ feeds the same functions arbitrary bytes.
+// The output is the typed model in topology.rs; the two modules depend only on redis, serde and
+// serde_json so the fuzz crate can include them as they are.
+
+pub use crate::topology::{ClusterInfo, ClusterNode, NodeRole, SlotAssignment, SlotNode, SlotRange, CLUSTER_SLOTS};
+
+// A slot field from CLUSTER NODES: "5-10" or "7". Migration markers ("[7->-id]") aren't ranges.
+pub fn parse_slot_range(field: &str) -> Option<SlotRange> {
+    let (start, end) = field.split_once('-').unwrap_or((field, field));
+    SlotRange::new(start.parse().ok()?, end.parse().ok()?)
+}
+
+// "host:port@bus,hostname" -> ("host", port); IPv6 hosts keep their colons
+pub fn split_address(field: &str) -> (&str, Option<u16>) {
+    let host_port = field.split('@').next().unwrap_or(field);
+    match host_port.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().ok()),
+        None => (host_port, None),
+    }
+}
+
+// One node per line; lines with fewer than the eight fixed fields are skipped
+pub fn parse_cluster_nodes(raw: &str) -> Vec<ClusterNode> {
+    raw.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [id, address, flags, master_id, ping_sent, pong_recv, config_epoch, link_state, slots @ ..] =
+                fields.as_slice()
+            else {
+                return None;
+            };
+            let (migrations, ranges): (Vec<&str>, Vec<&str>) = slots.iter().copied().partition(|f| f.starts_with('['));
+            let (host, port) = split_address(address);
+            let flags: Vec<String> = flags.split(',').filter(|f| !f.is_empty()).map(str::to_string).collect();
+            let slot_ranges: Vec<SlotRange> = ranges.into_iter().filter_map(parse_slot_range).collect();
+            Some(ClusterNode {
+                node_id: id.to_string(),
+                host: host.to_string(),
+                port: port.unwrap_or(0),
+                role: NodeRole::from_flags(&flags),
+                flags,
+                master_id: (*master_id != "-").then(|| master_id.to_string()),
+                ping_sent: ping_sent.to_string(),
+                pong_recv: pong_recv.to_string(),
+                config_epoch: config_epoch.parse().unwrap_or(0),
+                link_state: link_state.to_string(),
+                slots_count: slot_ranges.iter().map(SlotRange::count).sum(),
+                slot_ranges,
+                migrations: migrations.into_iter().map(str::to_string).collect(),
+            })
+        })
+        .collect()
+}
+
+fn value_string(value: &redis::Value) -> String {
+    match value {
+        redis::Value::BulkString(bytes) => String::from_utf8_lossy(bytes).to_string(),
+        redis::Value::SimpleString(s) => s.clone(),
+        _ => String::new(),
+    }
+}
+
+// [host, port, id, ...]; older servers without node IDs don't qualify
+fn slot_node(value: &redis::Value) -> Option<SlotNode> {
+    match value {
+        redis::Value::Array(fields) if fields.len() >= 3 => Some(SlotNode {
+            host: value_string(&fields[0]),
+            port: match &fields[1] {
+                redis::Value::Int(port) => *port,
+                _ => 0,
+            },
+            node_id: value_string(&fields[2]),
+        }),
+        _ => None,
+    }
+}
+
+// [[start, end, master, replica...], ...]; entries with an invalid slot range are dropped
+pub fn parse_cluster_slots(reply: &redis::Value) -> Vec<SlotAssignment> {
+    let redis::Value::Array(entries) = reply else {
+        return Vec::new();
+    };
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let redis::Value::Array(parts) = entry else {
+                return None;
+            };
+            let [redis::Value::Int(start), redis::Value::Int(end), master, replicas @ ..] = parts.as_slice() else {
+                return None;
+            };
+            Some(SlotAssignment::new(
+                SlotRange::new(*start, *end)?,
+                slot_node(master),
+                replicas.iter().filter_map(slot_node).collect(),
+            ))
+        })
+        .collect()
+}
+
+// Integers, then finite floats, otherwise the raw string
+pub fn info_value(value: &str) -> serde_json::Value {
+    if let Ok(int) = value.parse::<i64>() {
+        serde_json::json!(int)
+    } else if let Some(float) = value.parse::<f64>().ok().filter(|f| f.is_finite()) {
+        serde_json::json!(float)
+    } else {
+        serde_json::json!(value)
+    }
+}
+
+// CLUSTER INFO: flat key:value lines. A known field that isn't a number reads as 0.
+pub fn parse_cluster_info(raw: &str) -> ClusterInfo {
+    let mut info = ClusterInfo::default();
+    for (key, value) in raw.lines().filter_map(|line| line.trim().split_once(':')) {
+        let number = || value.parse().unwrap_or(0);
+        match key {
+            "cluster_state" => info.cluster_state = value.to_string(),
+            "cluster_slots_assigned" => info.cluster_slots_assigned = number(),
+            "cluster_slots_ok" => info.cluster_slots_ok = number(),
+            "cluster_slots_pfail" => info.cluster_slots_pfail = number(),
+            "cluster_slots_fail" => info.cluster_slots_fail = number(),
+            "cluster_known_nodes" => info.cluster_known_nodes = number(),
+            "cluster_size" => info.cluster_size = number(),
+            "cluster_current_epoch" => info.cluster_current_epoch = number(),
+            "cluster_my_epoch" => info.cluster_my_epoch = number(),
+            _ => {
+                info.other.insert(key.to_string(), info_value(value));
+            }
+        }
+    }
+    info
+}
+
+// INFO: key:value lines grouped under "# Section" headers, keyed by lower-cased section name.
+// Lines before the first header and empty sections are dropped.
+pub fn parse_info(raw: &str) -> serde_json::Map<String, serde_json::Value> {
+    let mut info = serde_json::Map::new();
+    let mut section: Option<(String, serde_json::Map<String, serde_json::Value>)> = None;
+    let mut finish = |section: Option<(String, serde_json::Map<String, serde_json::Value>)>| {
+        if let Some((name, fields)) = section.filter(|(name, fields)| !name.is_empty() && !fields.is_empty()) {
+            info.insert(name, serde_json::Value::Object(fields));
+        }
+    };
+    for line in raw.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if let Some(header) = line.strip_prefix('#') {
+            finish(section.take());
+            section = Some((header.trim().to_lowercase(), serde_json::Map::new()));
+        } else if let (Some((_, fields)), Some((key, value))) = (section.as_mut(), line.split_once(':')) {
+            fields.insert(key.to_string(), info_value(value));
+        }
+    }
+    finish(section);
+    info
+}
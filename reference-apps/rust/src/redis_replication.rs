@@ -0,0 +1,196 @@
+// Read-your-writes across Redis Cluster replicas
+//
+// Replication is asynchronous: a write acknowledged by a master may not be on its replicas yet.
+// The demo writes through the master that owns the key's slot, reads the key back from one of
+// that master's replicas in READONLY mode, and measures how long the value took to appear.
+//
+// The compose cluster is three masters without replicas, so out of the box there is nothing to
+// measure: the endpoint says so (status no_replicas) instead of failing. Replicas can be added
+// with `redis-cli --cluster add-node <new> <existing> --cluster-slave`.
+
+use std::time::{Duration, Instant};
+
+use actix_web::{web, HttpResponse, Responder};
+use serde::Deserialize;
+
+use crate::redis_parse::{parse_cluster_nodes, ClusterNode};
+use crate::{redis_connection, redis_node_connection};
+
+const DEFAULT_MAX_WAIT_MS: u64 = 1_000;
+const MAX_WAIT_MS: u64 = 10_000;
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+// Demo keys expire on their own
+const KEY_TTL_SECONDS: u64 = 60;
+
+#[derive(Debug, PartialEq)]
+pub struct SlotOwner {
+    pub master: String,
+    pub replicas: Vec<String>,
+}
+
+// Finds the master serving `slot` and its healthy replicas in CLUSTER NODES output
+pub fn slot_owner(nodes_raw: &str, slot: u16) -> Option<SlotOwner> {
+    let nodes = parse_cluster_nodes(nodes_raw);
+    let master = nodes.iter().find(|node| node.is_master() && !node.is_failed() && node.serves(slot))?;
+    let replicas = nodes
+        .iter()
+        .filter(|node| node.master_id.as_ref() == Some(&master.node_id) && node.is_replica() && !node.is_failed())
+        .map(ClusterNode::address)
+        .collect();
+
+    Some(SlotOwner { master: master.address(), replicas })
+}
+
+#[derive(Deserialize)]
+pub struct LagQuery {
+    // How long to keep polling the replica for the new value
+    max_wait_ms: Option<u64>,
+    // Run WAIT 1 <ms> after the write so the master blocks until a replica has acknowledged it
+    #[serde(default)]
+    wait: bool,
+}
+
+fn error_response(status: actix_web::http::StatusCode, key: &str, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "key": key, "error": error }))
+}
+
+pub async fn replication_lag(path: web::Path<String>, query: web::Query<LagQuery>) -> impl Responder {
+    let key = path.into_inner();
+    let max_wait_ms = query.max_wait_ms.unwrap_or(DEFAULT_MAX_WAIT_MS).min(MAX_WAIT_MS);
+    let redis_key = format!("replication-lag:{}", key);
+
+    let mut conn = match redis_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, &key, e),
+    };
+    let topology = async {
+        let slot: u16 = redis::cmd("CLUSTER").arg("KEYSLOT").arg(&redis_key).query_async(&mut conn).await?;
+        let nodes: String = redis::cmd("CLUSTER").arg("NODES").query_async(&mut conn).await?;
+        Ok::<_, redis::RedisError>((slot, nodes))
+    }
+    .await;
+    let (slot, nodes_raw) = match topology {
+        Ok(topology) => topology,
+        Err(e) => {
+            return error_response(
+                actix_web::http::StatusCode::SERVICE_UNAVAILABLE,
+                &key,
+                format!("Cluster topology unavailable (is cluster mode enabled?): {}", e),
+            )
+        }
+    };
+
+    let owner = match slot_owner(&nodes_raw, slot) {
+        Some(owner) => owner,
+        None => {
+            return error_response(
+                actix_web::http::StatusCode::SERVICE_UNAVAILABLE,
+                &key,
+                format!("No master serves slot {}", slot),
+            )
+        }
+    };
+    let replica = match owner.replicas.first() {
+        Some(replica) => replica.clone(),
+        None => {
+            return HttpResponse::Ok().json(serde_json::json!({
+                "status": "no_replicas",
+                "key": redis_key,
+                "slot": slot,
+                "master": owner.master,
+                "replicas": owner.replicas,
+                "message": format!(
+                    "Master {} for slot {} has no replicas, so reads are never stale and there is no lag to measure",
+                    owner.master, slot
+                )
+            }))
+        }
+    };
+
+    let (master_conn, replica_conn) =
+        tokio::join!(redis_node_connection(&owner.master), redis_node_connection(&replica));
+    let (mut master_conn, mut replica_conn) = match (master_conn, replica_conn) {
+        (Ok(master), Ok(replica)) => (master, replica),
+        (Err(e), _) | (_, Err(e)) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, &key, e),
+    };
+
+    // Replicas redirect reads to the master unless the connection opts in to stale reads
+    if let Err(e) = redis::cmd("READONLY").query_async::<()>(&mut replica_conn).await {
+        return error_response(
+            actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+            &key,
+            format!("READONLY failed: {}", e),
+        );
+    }
+
+    let value = uuid::Uuid::new_v4().to_string();
+    let written = Instant::now();
+    let set = redis::cmd("SET")
+        .arg(&redis_key)
+        .arg(&value)
+        .arg("EX")
+        .arg(KEY_TTL_SECONDS)
+        .query_async::<()>(&mut master_conn)
+        .await;
+    if let Err(e) = set {
+        return error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, &key, format!("SET failed: {}", e));
+    }
+
+    let acknowledged_replicas = if query.wait {
+        // WAIT with a timeout of 0 blocks until a replica acknowledges, which may be never
+        match redis::cmd("WAIT").arg(1).arg(max_wait_ms.max(1)).query_async::<i64>(&mut master_conn).await {
+            Ok(count) => Some(count),
+            Err(e) => {
+                return error_response(
+                    actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+                    &key,
+                    format!("WAIT failed: {}", e),
+                )
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut reads = 0u64;
+    let mut visible_immediately = false;
+    let mut lag = None;
+    let deadline = Duration::from_millis(max_wait_ms);
+    loop {
+        reads += 1;
+        let seen: Option<String> = match redis::cmd("GET").arg(&redis_key).query_async(&mut replica_conn).await {
+            Ok(seen) => seen,
+            Err(e) => {
+                return error_response(
+                    actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+                    &key,
+                    format!("Replica GET failed: {}", e),
+                )
+            }
+        };
+        if seen.as_deref() == Some(value.as_str()) {
+            visible_immediately = reads == 1;
+            lag = Some(written.elapsed());
+            break;
+        }
+        if written.elapsed() >= deadline {
+            break;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "key": redis_key,
+        "slot": slot,
+        "master": owner.master,
+        "replica": replica,
+        "wait": query.wait,
+        "acknowledged_replicas": acknowledged_replicas,
+        "visible_immediately": visible_immediately,
+        "visible": lag.is_some(),
+        "lag_ms": lag.map(|l| l.as_secs_f64() * 1000.0),
+        "replica_reads": reads,
+        "max_wait_ms": max_wait_ms
+    }))
+}
@@ -0,0 +1,320 @@
+// Shovel-style relay: a background task that moves messages from a source queue to a
+// destination queue or exchange, optionally reshaping JSON bodies on the way
+//
+// Each message is acked on the source only after the broker confirms it on the destination, so
+// a crash mid-relay redelivers rather than loses (at-least-once). Messages that can't be
+// transformed are rejected without requeue, which dead-letters them if the source has a DLX.
+// A message the destination nacks is requeued once after a backoff; nacked again on its
+// redelivery, it is rejected the same way instead of cycling through the relay forever.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::{web, HttpResponse, Responder};
+use futures_util::StreamExt;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::watch;
+
+use crate::amqp_connection;
+use crate::message_codec::JSON_CONTENT_TYPE;
+
+const RELAY_PREFETCH: u16 = 50;
+// Wait before requeueing a nacked message, doubling per consecutive nack up to the max
+const NACK_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_NACK_BACKOFF: Duration = Duration::from_secs(5);
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct RelayConfig {
+    #[serde(default)]
+    name: Option<String>,
+    source_queue: String,
+    #[serde(default)]
+    destination_queue: Option<String>,
+    #[serde(default)]
+    destination_exchange: Option<String>,
+    #[serde(default)]
+    routing_key: Option<String>,
+    // Output field -> dotted path into the source JSON, e.g. {"user_id": "user.id"}
+    #[serde(default)]
+    mapping: Option<BTreeMap<String, String>>,
+}
+
+impl RelayConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.source_queue.is_empty() {
+            return Err("source_queue is required".to_string());
+        }
+        match (&self.destination_queue, &self.destination_exchange) {
+            (Some(_), Some(_)) | (None, None) => {
+                Err("Set exactly one of destination_queue or destination_exchange".to_string())
+            }
+            (Some(queue), None) if *queue == self.source_queue => {
+                Err("destination_queue must differ from source_queue".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // (exchange, routing key) to publish to
+    fn destination(&self) -> (String, String) {
+        match &self.destination_queue {
+            Some(queue) => (String::new(), queue.clone()),
+            None => (
+                self.destination_exchange.clone().unwrap_or_default(),
+                self.routing_key.clone().unwrap_or_default(),
+            ),
+        }
+    }
+}
+
+fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(value, |current, segment| current.get(segment))
+}
+
+// Builds the output object from the mapping; missing source fields become null
+pub fn apply_mapping(mapping: &BTreeMap<String, String>, body: &[u8]) -> Result<Vec<u8>, String> {
+    let source: Value = serde_json::from_slice(body).map_err(|e| format!("Body is not JSON: {}", e))?;
+    let output: serde_json::Map<String, Value> = mapping
+        .iter()
+        .map(|(field, path)| (field.clone(), lookup(&source, path).cloned().unwrap_or(Value::Null)))
+        .collect();
+    serde_json::to_vec(&output).map_err(|e| e.to_string())
+}
+
+// Properties for a mapped body: always JSON, so the source's content type and encoding don't
+// carry over, but delivery and correlation properties do
+pub fn mapped_properties(source: &lapin::BasicProperties) -> lapin::BasicProperties {
+    let mut properties = lapin::BasicProperties::default().with_content_type(JSON_CONTENT_TYPE.into());
+    if let Some(mode) = source.delivery_mode() {
+        properties = properties.with_delivery_mode(*mode);
+    }
+    if let Some(priority) = source.priority() {
+        properties = properties.with_priority(*priority);
+    }
+    if let Some(headers) = source.headers() {
+        properties = properties.with_headers(headers.clone());
+    }
+    if let Some(message_id) = source.message_id() {
+        properties = properties.with_message_id(message_id.clone());
+    }
+    if let Some(correlation_id) = source.correlation_id() {
+        properties = properties.with_correlation_id(correlation_id.clone());
+    }
+    if let Some(timestamp) = source.timestamp() {
+        properties = properties.with_timestamp(*timestamp);
+    }
+    properties
+}
+
+pub fn nack_backoff(consecutive_nacks: u32) -> Duration {
+    NACK_BACKOFF.saturating_mul(2u32.saturating_pow(consecutive_nacks.saturating_sub(1))).min(MAX_NACK_BACKOFF)
+}
+
+#[derive(Default)]
+struct RelayStats {
+    moved: AtomicU64,
+    failed: AtomicU64,
+    last_error: Mutex<Option<String>>,
+    // Set when the relay task exits on its own
+    stopped_reason: Mutex<Option<String>>,
+}
+
+struct Relay {
+    config: RelayConfig,
+    started_at: chrono::DateTime<chrono::Utc>,
+    stats: Arc<RelayStats>,
+    stop: watch::Sender<bool>,
+}
+
+impl Relay {
+    fn status(&self, name: &str) -> Value {
+        let stopped_reason = self.stats.stopped_reason.lock().ok().and_then(|r| r.clone());
+        serde_json::json!({
+            "name": name,
+            "state": if stopped_reason.is_some() { "stopped" } else { "running" },
+            "stopped_reason": stopped_reason,
+            "config": self.config,
+            "started_at": self.started_at.to_rfc3339(),
+            "moved": self.stats.moved.load(Ordering::Relaxed),
+            "failed": self.stats.failed.load(Ordering::Relaxed),
+            "last_error": self.stats.last_error.lock().ok().and_then(|e| e.clone())
+        })
+    }
+}
+
+lazy_static! {
+    static ref RELAYS: Mutex<HashMap<String, Relay>> = Mutex::new(HashMap::new());
+}
+
+fn record_failure(stats: &RelayStats, error: String) {
+    stats.failed.fetch_add(1, Ordering::Relaxed);
+    if let Ok(mut last) = stats.last_error.lock() {
+        *last = Some(error);
+    }
+}
+
+async fn run_relay(config: RelayConfig, stats: Arc<RelayStats>, mut stop: watch::Receiver<bool>) -> Result<(), String> {
+    let conn = amqp_connection().await?;
+    let result = async {
+        let source = conn.create_channel().await.map_err(|e| format!("Channel creation failed: {}", e))?;
+        let destination = conn.create_channel().await.map_err(|e| format!("Channel creation failed: {}", e))?;
+        destination
+            .confirm_select(lapin::options::ConfirmSelectOptions::default())
+            .await
+            .map_err(|e| format!("confirm.select failed: {}", e))?;
+        source
+            .basic_qos(RELAY_PREFETCH, lapin::options::BasicQosOptions::default())
+            .await
+            .map_err(|e| format!("basic.qos failed: {}", e))?;
+        let mut consumer = source
+            .basic_consume(
+                &config.source_queue,
+                "",
+                lapin::options::BasicConsumeOptions::default(),
+                lapin::types::FieldTable::default(),
+            )
+            .await
+            .map_err(|e| format!("basic.consume failed: {}", e))?;
+        let (exchange, routing_key) = config.destination();
+        let mut consecutive_nacks = 0u32;
+
+        loop {
+            let delivery = tokio::select! {
+                _ = stop.changed() => return Ok(()),
+                next = consumer.next() => match next {
+                    Some(Ok(delivery)) => delivery,
+                    Some(Err(e)) => return Err(format!("Consume failed: {}", e)),
+                    None => return Err("Source consumer was cancelled".to_string()),
+                },
+            };
+
+            let (payload, properties) = match &config.mapping {
+                Some(mapping) => match apply_mapping(mapping, &delivery.data) {
+                    Ok(payload) => (payload, mapped_properties(&delivery.properties)),
+                    Err(e) => {
+                        record_failure(&stats, e);
+                        let _ = delivery.reject(lapin::options::BasicRejectOptions { requeue: false }).await;
+                        continue;
+                    }
+                },
+                None => (delivery.data.clone(), delivery.properties.clone()),
+            };
+
+            let published = async {
+                destination
+                    .basic_publish(
+                        &exchange,
+                        &routing_key,
+                        lapin::options::BasicPublishOptions::default(),
+                        &payload,
+                        properties,
+                    )
+                    .await?
+                    .await
+            }
+            .await;
+            match published {
+                Ok(lapin::publisher_confirm::Confirmation::Nack(_)) => {
+                    record_failure(&stats, "Destination nacked the message".to_string());
+                    if delivery.redelivered {
+                        let _ = delivery.reject(lapin::options::BasicRejectOptions { requeue: false }).await;
+                        continue;
+                    }
+                    consecutive_nacks += 1;
+                    tokio::select! {
+                        _ = stop.changed() => return Ok(()),
+                        _ = tokio::time::sleep(nack_backoff(consecutive_nacks)) => {}
+                    }
+                    let _ = delivery.nack(lapin::options::BasicNackOptions { requeue: true, ..Default::default() }).await;
+                }
+                Ok(_) => {
+                    consecutive_nacks = 0;
+                    stats.moved.fetch_add(1, Ordering::Relaxed);
+                    let _ = delivery.ack(lapin::options::BasicAckOptions::default()).await;
+                }
+                Err(e) => return Err(format!("Publish failed: {}", e)),
+            }
+        }
+    }
+    .await;
+    let _ = conn.close(0, "Relay stopped").await;
+    result
+}
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+pub async fn start_relay(body: web::Json<RelayConfig>) -> impl Responder {
+    let mut config = body.into_inner();
+    if let Err(e) = config.validate() {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, e);
+    }
+    let name = config
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("relay-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]));
+    config.name = Some(name.clone());
+
+    let mut relays = match RELAYS.lock() {
+        Ok(relays) => relays,
+        Err(e) => return error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    if relays.get(&name).is_some_and(|r| r.stats.stopped_reason.lock().map(|s| s.is_none()).unwrap_or(false)) {
+        return error_response(actix_web::http::StatusCode::CONFLICT, format!("Relay '{}' is already running", name));
+    }
+
+    let stats = Arc::new(RelayStats::default());
+    let (stop_tx, stop_rx) = watch::channel(false);
+    let task_stats = stats.clone();
+    let task_config = config.clone();
+    tokio::spawn(async move {
+        let reason = match run_relay(task_config, task_stats.clone(), stop_rx).await {
+            Ok(()) => "stopped by request".to_string(),
+            Err(e) => e,
+        };
+        if let Ok(mut stopped) = task_stats.stopped_reason.lock() {
+            *stopped = Some(reason);
+        }
+    });
+
+    let relay = Relay { config, started_at: chrono::Utc::now(), stats, stop: stop_tx };
+    let status = relay.status(&name);
+    relays.insert(name, relay);
+    HttpResponse::Created().json(status)
+}
+
+pub async fn list_relays() -> impl Responder {
+    let relays: Vec<Value> = RELAYS
+        .lock()
+        .map(|relays| relays.iter().map(|(name, relay)| relay.status(name)).collect())
+        .unwrap_or_default();
+    HttpResponse::Ok().json(serde_json::json!({ "count": relays.len(), "relays": relays }))
+}
+
+pub async fn relay_status(path: web::Path<String>) -> impl Responder {
+    let name = path.into_inner();
+    match RELAYS.lock().ok().and_then(|relays| relays.get(&name).map(|r| r.status(&name))) {
+        Some(status) => HttpResponse::Ok().json(status),
+        None => error_response(actix_web::http::StatusCode::NOT_FOUND, format!("Unknown relay '{}'", name)),
+    }
+}
+
+// Signals the task and forgets the relay; in-flight unacked messages go back to the source
+pub async fn stop_relay(path: web::Path<String>) -> impl Responder {
+    let name = path.into_inner();
+    let relay = RELAYS.lock().ok().and_then(|mut relays| relays.remove(&name));
+    match relay {
+        Some(relay) => {
+            let _ = relay.stop.send(true);
+            let mut status = relay.status(&name);
+            status["state"] = Value::from("stopped");
+            HttpResponse::Ok().json(status)
+        }
+        None => error_response(actix_web::http::StatusCode::NOT_FOUND, format!("Unknown relay '{}'", name)),
+    }
+}
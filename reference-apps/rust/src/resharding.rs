@@ -0,0 +1,393 @@
+// Guided Redis Cluster resharding
+//
+// Moves a small slot range between two masters with the same steps `redis-cli --cluster reshard`
+// performs: mark each slot IMPORTING on the target and MIGRATING on the source, MIGRATE its keys
+// in batches, then assign the slot to the target with SETSLOT NODE. A slot whose migration fails
+// before that last step is rolled back: moved keys go back to the source and both nodes are set
+// STABLE again, so the cluster isn't left half-way.
+
+use std::time::Instant;
+
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+
+use crate::redis_parse::parse_cluster_nodes;
+use crate::{get_env_or, redis_connection, redis_master_addresses, redis_node_connection, redis_password};
+
+const CLUSTER_SLOTS: u16 = 16384;
+const DEFAULT_BATCH: usize = 100;
+const DEFAULT_TIMEOUT_MS: u64 = 5_000;
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct SlotMigration {
+    pub slot: u16,
+    // "migrating" on the source node, "importing" on the target node
+    pub state: String,
+    pub peer_id: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ClusterMaster {
+    pub id: String,
+    pub address: String,
+}
+
+// In-flight migrations from a node's own line in its CLUSTER NODES output:
+// `[slot->-peer]` while migrating away, `[slot-<-peer]` while importing
+pub fn parse_migrations(nodes_raw: &str) -> Vec<SlotMigration> {
+    let Some(myself) = parse_cluster_nodes(nodes_raw).into_iter().find(|node| node.has_flag("myself")) else {
+        return Vec::new();
+    };
+
+    myself
+        .migrations
+        .iter()
+        .filter_map(|field| {
+            let inner = field.strip_prefix('[')?.strip_suffix(']')?;
+            let (slot, state, peer) = if let Some((slot, peer)) = inner.split_once("->-") {
+                (slot, "migrating", peer)
+            } else {
+                let (slot, peer) = inner.split_once("-<-")?;
+                (slot, "importing", peer)
+            };
+            Some(SlotMigration { slot: slot.parse().ok()?, state: state.to_string(), peer_id: peer.to_string() })
+        })
+        .collect()
+}
+
+// Accepts a node ID or a "host:port" address
+pub fn resolve_master(nodes_raw: &str, node: &str) -> Option<ClusterMaster> {
+    parse_cluster_nodes(nodes_raw)
+        .into_iter()
+        .filter(|candidate| candidate.is_master() && !candidate.is_failed())
+        .find(|candidate| candidate.node_id == node || candidate.address() == node)
+        .map(|master| ClusterMaster { address: master.address(), id: master.node_id })
+}
+
+// ============================================================================
+// Migration status
+// ============================================================================
+
+pub async fn migration_status() -> impl Responder {
+    let masters = match redis_master_addresses().await {
+        Ok(masters) => masters,
+        Err(e) => return HttpResponse::ServiceUnavailable().json(serde_json::json!({ "status": "error", "error": e })),
+    };
+
+    let mut nodes = Vec::new();
+    for address in masters {
+        let migrations = async {
+            let mut conn = redis_node_connection(&address).await?;
+            redis::cmd("CLUSTER")
+                .arg("NODES")
+                .query_async::<String>(&mut conn)
+                .await
+                .map(|raw| parse_migrations(&raw))
+                .map_err(|e| format!("CLUSTER NODES failed: {}", e))
+        }
+        .await;
+        nodes.push(match migrations {
+            Ok(migrations) => serde_json::json!({ "node": address, "migrations": migrations }),
+            Err(e) => serde_json::json!({ "node": address, "error": e }),
+        });
+    }
+
+    let in_progress = nodes
+        .iter()
+        .any(|n| n["migrations"].as_array().is_some_and(|m| !m.is_empty()));
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": if in_progress { "migrating" } else { "stable" },
+        "nodes": nodes
+    }))
+}
+
+// ============================================================================
+// Reshard
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct ReshardRequest {
+    // Node IDs or "host:port" addresses of two masters
+    source: String,
+    target: String,
+    start_slot: u16,
+    end_slot: u16,
+    #[serde(default)]
+    confirm: bool,
+    batch: Option<usize>,
+    timeout_ms: Option<u64>,
+}
+
+// Slots in the range that `source` doesn't currently serve
+pub fn unowned_slots(nodes_raw: &str, source: &ClusterMaster, slots: std::ops::RangeInclusive<u16>) -> Vec<u16> {
+    let nodes = parse_cluster_nodes(nodes_raw);
+    let Some(node) = nodes.iter().find(|node| node.node_id == source.id) else {
+        return slots.collect();
+    };
+    slots.filter(|slot| !node.serves(*slot)).collect()
+}
+
+// Commands run for each slot, for the dry-run preview and the response
+pub fn plan_commands(slot: u16, source: &ClusterMaster, target: &ClusterMaster, batch: usize) -> Vec<String> {
+    vec![
+        format!("{}: CLUSTER SETSLOT {} IMPORTING {}", target.address, slot, source.id),
+        format!("{}: CLUSTER SETSLOT {} MIGRATING {}", source.address, slot, target.id),
+        format!("{}: CLUSTER GETKEYSINSLOT {} {} + MIGRATE ... KEYS (repeat until empty)", source.address, slot, batch),
+        format!("{}: CLUSTER SETSLOT {} NODE {}", target.address, slot, target.id),
+        format!("{}: CLUSTER SETSLOT {} NODE {}", source.address, slot, target.id),
+    ]
+}
+
+fn split_address(address: &str) -> Option<(&str, u16)> {
+    let (host, port) = address.rsplit_once(':')?;
+    Some((host, port.parse().ok()?))
+}
+
+struct SlotMove<'a> {
+    source: &'a ClusterMaster,
+    target: &'a ClusterMaster,
+    batch: usize,
+    timeout_ms: u64,
+    password: &'a str,
+}
+
+// MIGRATE host port "" 0 timeout [AUTH password] KEYS k1 k2 ...
+fn migrate_command(to: &ClusterMaster, keys: &[String], params: &SlotMove<'_>) -> Result<redis::Cmd, String> {
+    let (host, port) = split_address(&to.address).ok_or_else(|| format!("Invalid address {}", to.address))?;
+    let mut migrate = redis::cmd("MIGRATE");
+    migrate.arg(host).arg(port).arg("").arg(0).arg(params.timeout_ms);
+    if !params.password.is_empty() {
+        migrate.arg("AUTH").arg(params.password);
+    }
+    migrate.arg("KEYS").arg(keys);
+    Ok(migrate)
+}
+
+// Moves every key of `slot` held by `from_conn` to `to`, returning how many moved. On error the
+// count moved so far comes with the message.
+async fn move_keys(
+    from_conn: &mut redis::aio::MultiplexedConnection,
+    to: &ClusterMaster,
+    slot: u16,
+    params: &SlotMove<'_>,
+) -> Result<u64, (u64, String)> {
+    let mut moved = 0u64;
+    loop {
+        let keys: Vec<String> = redis::cmd("CLUSTER")
+            .arg("GETKEYSINSLOT")
+            .arg(slot)
+            .arg(params.batch)
+            .query_async(from_conn)
+            .await
+            .map_err(|e| (moved, format!("GETKEYSINSLOT failed: {}", e)))?;
+        if keys.is_empty() {
+            return Ok(moved);
+        }
+        migrate_command(to, &keys, params)
+            .map_err(|e| (moved, e))?
+            .query_async::<()>(from_conn)
+            .await
+            .map_err(|e| (moved, format!("MIGRATE failed after {} keys: {}", moved, e)))?;
+        moved += keys.len() as u64;
+    }
+}
+
+// Undoes a slot migration that failed before SETSLOT NODE: keys that reached the target go back,
+// then both nodes forget the IMPORTING/MIGRATING state
+async fn roll_back_slot(
+    source_conn: &mut redis::aio::MultiplexedConnection,
+    target_conn: &mut redis::aio::MultiplexedConnection,
+    slot: u16,
+    params: &SlotMove<'_>,
+) -> Result<u64, String> {
+    let returned = move_keys(target_conn, params.source, slot, params).await.map_err(|(_, e)| e)?;
+    for conn in [target_conn, source_conn] {
+        redis::cmd("CLUSTER")
+            .arg("SETSLOT")
+            .arg(slot)
+            .arg("STABLE")
+            .query_async::<()>(conn)
+            .await
+            .map_err(|e| format!("SETSLOT STABLE failed: {}", e))?;
+    }
+    Ok(returned)
+}
+
+#[derive(Debug)]
+pub struct SlotFailure {
+    pub error: String,
+    // None when the rollback itself failed, leaving the slot for `redis-cli --cluster fix`
+    pub rolled_back: Option<bool>,
+    pub rollback_error: Option<String>,
+}
+
+async fn migrate_slot(
+    source_conn: &mut redis::aio::MultiplexedConnection,
+    target_conn: &mut redis::aio::MultiplexedConnection,
+    slot: u16,
+    params: &SlotMove<'_>,
+) -> Result<u64, SlotFailure> {
+    let failed = |error: String| SlotFailure { error, rolled_back: Some(false), rollback_error: None };
+    let SlotMove { source, target, .. } = *params;
+
+    redis::cmd("CLUSTER")
+        .arg("SETSLOT")
+        .arg(slot)
+        .arg("IMPORTING")
+        .arg(&source.id)
+        .query_async::<()>(target_conn)
+        .await
+        .map_err(|e| failed(format!("SETSLOT IMPORTING failed: {}", e)))?;
+
+    let migrated = async {
+        redis::cmd("CLUSTER")
+            .arg("SETSLOT")
+            .arg(slot)
+            .arg("MIGRATING")
+            .arg(&target.id)
+            .query_async::<()>(&mut *source_conn)
+            .await
+            .map_err(|e| format!("SETSLOT MIGRATING failed: {}", e))?;
+        move_keys(&mut *source_conn, target, slot, params).await.map_err(|(_, e)| e)
+    }
+    .await;
+    let moved = match migrated {
+        Ok(moved) => moved,
+        Err(error) => {
+            return Err(match roll_back_slot(source_conn, target_conn, slot, params).await {
+                Ok(_) => SlotFailure { error, rolled_back: Some(true), rollback_error: None },
+                Err(e) => SlotFailure { error, rolled_back: None, rollback_error: Some(e) },
+            })
+        }
+    };
+
+    // Target first, so the source never points clients at a node that doesn't own the slot yet
+    for conn in [target_conn, source_conn] {
+        redis::cmd("CLUSTER")
+            .arg("SETSLOT")
+            .arg(slot)
+            .arg("NODE")
+            .arg(&target.id)
+            .query_async::<()>(conn)
+            .await
+            .map_err(|e| failed(format!("SETSLOT NODE failed: {}", e)))?;
+    }
+    Ok(moved)
+}
+
+pub async fn reshard(body: web::Json<ReshardRequest>) -> impl Responder {
+    let max_slots: u16 = get_env_or("RESHARD_MAX_SLOTS", "16").parse().unwrap_or(16);
+    let batch = body.batch.unwrap_or(DEFAULT_BATCH).max(1);
+    let timeout_ms = body.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS);
+
+    if body.start_slot > body.end_slot || body.end_slot >= CLUSTER_SLOTS {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "status": "error",
+            "error": format!("Slot range must satisfy start_slot <= end_slot < {}", CLUSTER_SLOTS)
+        }));
+    }
+    if body.end_slot - body.start_slot + 1 > max_slots {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "status": "error",
+            "error": format!("At most {} slots can be moved per request (RESHARD_MAX_SLOTS)", max_slots)
+        }));
+    }
+
+    let nodes_raw = match redis_connection().await {
+        Ok(mut conn) => match redis::cmd("CLUSTER").arg("NODES").query_async::<String>(&mut conn).await {
+            Ok(raw) => raw,
+            Err(e) => {
+                return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                    "status": "error",
+                    "error": format!("CLUSTER NODES failed: {}", e)
+                }))
+            }
+        },
+        Err(e) => return HttpResponse::ServiceUnavailable().json(serde_json::json!({ "status": "error", "error": e })),
+    };
+
+    let (source, target) = match (resolve_master(&nodes_raw, &body.source), resolve_master(&nodes_raw, &body.target)) {
+        (Some(source), Some(target)) if source.id != target.id => (source, target),
+        (Some(_), Some(_)) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "status": "error",
+                "error": "source and target must be different masters"
+            }))
+        }
+        _ => {
+            return HttpResponse::NotFound().json(serde_json::json!({
+                "status": "error",
+                "error": "source and target must be healthy masters (node ID or host:port)"
+            }))
+        }
+    };
+
+    let slots = body.start_slot..=body.end_slot;
+    let unowned = unowned_slots(&nodes_raw, &source, slots.clone());
+    if !unowned.is_empty() {
+        return HttpResponse::Conflict().json(serde_json::json!({
+            "status": "error",
+            "error": format!("{} doesn't serve every slot in the range", source.address),
+            "unowned_slots": unowned
+        }));
+    }
+    let plan: Vec<String> = slots.clone().flat_map(|slot| plan_commands(slot, &source, &target, batch)).collect();
+
+    // Safety guard: moving slots changes cluster topology, so without confirmation this is a dry run
+    if !body.confirm {
+        return HttpResponse::Ok().json(serde_json::json!({
+            "status": "dry_run",
+            "message": "Resharding moves keys between nodes; set \"confirm\": true to run this plan",
+            "source": source.address,
+            "target": target.address,
+            "plan": plan
+        }));
+    }
+
+    let connections = async {
+        let password = redis_password().await?;
+        let source_conn = redis_node_connection(&source.address).await?;
+        let target_conn = redis_node_connection(&target.address).await?;
+        Ok::<_, String>((password, source_conn, target_conn))
+    }
+    .await;
+    let (password, mut source_conn, mut target_conn) = match connections {
+        Ok(connections) => connections,
+        Err(e) => return HttpResponse::ServiceUnavailable().json(serde_json::json!({ "status": "error", "error": e })),
+    };
+
+    let params = SlotMove { source: &source, target: &target, batch, timeout_ms, password: password.expose() };
+    let started = Instant::now();
+    let mut results = Vec::new();
+    for slot in slots {
+        match migrate_slot(&mut source_conn, &mut target_conn, slot, &params).await {
+            Ok(keys) => results.push(serde_json::json!({ "slot": slot, "status": "moved", "keys": keys })),
+            Err(failure) => {
+                // Stop at the first failure; only a failed rollback leaves the slot MIGRATING/IMPORTING
+                results.push(serde_json::json!({
+                    "slot": slot,
+                    "status": "error",
+                    "error": failure.error,
+                    "rolled_back": failure.rolled_back,
+                    "rollback_error": failure.rollback_error
+                }));
+                return HttpResponse::InternalServerError().json(serde_json::json!({
+                    "status": "error",
+                    "source": source.address,
+                    "target": target.address,
+                    "elapsed_ms": started.elapsed().as_millis() as u64,
+                    "slots": results
+                }));
+            }
+        }
+    }
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "source": source.address,
+        "target": target.address,
+        "keys_moved": results.iter().filter_map(|r| r["keys"].as_u64()).sum::<u64>(),
+        "elapsed_ms": started.elapsed().as_millis() as u64,
+        "slots": results
+    }))
+}
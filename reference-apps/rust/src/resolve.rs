@@ -0,0 +1,239 @@
+// Host name resolution for backends, inside and outside the compose network
+//
+// Inside docker compose every backend is reachable by its service name ("redis-1", "postgres").
+// Running the binary natively (a static musl build, or Windows) breaks that in a few ways:
+// - compose names don't resolve, and Redis cluster nodes announce addresses that only exist on
+//   the compose network;
+// - "localhost" resolves to ::1 first on Windows while the published ports listen on IPv4, and
+//   static musl images usually ship without /etc/hosts;
+// - the musl resolver gives up on the first EAI_AGAIN instead of retrying.
+//
+// Every backend address goes through this module so those quirks are handled in one place:
+// HOST_OVERRIDES=redis-1=127.0.0.1:6379,redis-2=127.0.0.1:6380,postgres=127.0.0.1 rewrites a host
+// (or one host:port) to another, "localhost" maps to DNS_LOCALHOST (127.0.0.1 unless set), and
+// lookups retry DNS_LOOKUP_ATTEMPTS times and put IPv4 addresses first unless DNS_PREFER_IPV4=false.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use actix_web::{web, HttpResponse, Responder};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::get_env_or;
+
+lazy_static! {
+    static ref OVERRIDES: HashMap<String, String> = {
+        let mut overrides = parse_overrides(&get_env_or("HOST_OVERRIDES", ""));
+        let localhost = get_env_or("DNS_LOCALHOST", "127.0.0.1");
+        if !localhost.is_empty() && localhost != "off" {
+            overrides.entry("localhost".to_string()).or_insert(localhost);
+        }
+        overrides
+    };
+}
+
+// "from=to" pairs separated by commas; either side may be "host" or "host:port"
+pub fn parse_overrides(value: &str) -> HashMap<String, String> {
+    value
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(from, to)| (from.trim().to_ascii_lowercase(), to.trim().to_string()))
+        .filter(|(from, to)| !from.is_empty() && !to.is_empty())
+        .collect()
+}
+
+// Split "host:port", "[v6]:port", a bare host or a bare IPv6 literal; brackets are removed
+pub fn split_host_port(address: &str) -> (&str, Option<&str>) {
+    if let Some(rest) = address.strip_prefix('[') {
+        if let Some((host, tail)) = rest.split_once(']') {
+            return (host, tail.strip_prefix(':').filter(|port| !port.is_empty()));
+        }
+    }
+    match address.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => (host, Some(port)),
+        _ => (address, None),
+    }
+}
+
+// "host:port", bracketing IPv6 literals so the result can go into a URL
+pub fn join(host: &str, port: impl std::fmt::Display) -> String {
+    if host.parse::<std::net::Ipv6Addr>().is_ok() {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+pub fn apply_host(overrides: &HashMap<String, String>, name: &str) -> String {
+    match overrides.get(&name.to_ascii_lowercase()) {
+        // A port in the override is meaningless where only a host is wanted
+        Some(to) => split_host_port(to).0.to_string(),
+        None => name.to_string(),
+    }
+}
+
+pub fn apply_address(overrides: &HashMap<String, String>, address: &str) -> String {
+    if let Some(to) = overrides.get(&address.to_ascii_lowercase()) {
+        return to.clone();
+    }
+    let (host, port) = split_host_port(address);
+    let Some(port) = port else {
+        return apply_host(overrides, host);
+    };
+    match overrides.get(&host.to_ascii_lowercase()) {
+        Some(to) if split_host_port(to).1.is_some() => to.clone(),
+        Some(to) => join(to, port),
+        None => join(host, port),
+    }
+}
+
+// Rewrite the authority of "scheme://host[:port]/..." (VAULT_ADDR, MINIO_ENDPOINT)
+pub fn apply_url(overrides: &HashMap<String, String>, url: &str) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return apply_address(overrides, url);
+    };
+    let end = rest.find(['/', '?']).unwrap_or(rest.len());
+    let (authority, path) = rest.split_at(end);
+    format!("{}://{}{}", scheme, apply_address(overrides, authority), path)
+}
+
+// Stable sort that keeps the resolver's order within each address family
+pub fn sort_preferred(addresses: &mut [SocketAddr], prefer_ipv4: bool) {
+    addresses.sort_by_key(|address| address.is_ipv4() != prefer_ipv4);
+}
+
+pub fn host(name: &str) -> String {
+    apply_host(&OVERRIDES, name)
+}
+
+pub fn address(address: &str) -> String {
+    apply_address(&OVERRIDES, address)
+}
+
+pub fn url(url: &str) -> String {
+    apply_url(&OVERRIDES, url)
+}
+
+// A *_HOST setting with overrides applied
+pub fn env_host(key: &str, default: &str) -> String {
+    host(&get_env_or(key, default))
+}
+
+// A *_HOST / *_PORT pair as "host:port", with overrides applied, ready for a connection URL
+pub fn env_address(host_key: &str, host_default: &str, port_key: &str, port_default: &str) -> String {
+    address(&join(&get_env_or(host_key, host_default), get_env_or(port_key, port_default)))
+}
+
+fn prefer_ipv4() -> bool {
+    get_env_or("DNS_PREFER_IPV4", "true") != "false"
+}
+
+// Resolve host:port to socket addresses, after overrides, with retries and family preference
+pub async fn lookup(host: &str, port: u16) -> Result<Vec<SocketAddr>, String> {
+    let target = address(&join(host, port));
+    let (name, target_port) = split_host_port(&target);
+    let port = target_port.and_then(|p| p.parse().ok()).unwrap_or(port);
+    if let Ok(ip) = name.parse::<IpAddr>() {
+        return Ok(vec![SocketAddr::new(ip, port)]);
+    }
+
+    let attempts: u32 = get_env_or("DNS_LOOKUP_ATTEMPTS", "3").parse().unwrap_or(3).max(1);
+    let mut last_error = String::new();
+    for attempt in 0..attempts {
+        match tokio::net::lookup_host((name, port)).await {
+            Ok(found) => {
+                let mut addresses: Vec<SocketAddr> = found.collect();
+                if !addresses.is_empty() {
+                    sort_preferred(&mut addresses, prefer_ipv4());
+                    return Ok(addresses);
+                }
+                last_error = "no addresses returned".to_string();
+            }
+            Err(e) => last_error = e.to_string(),
+        }
+        if attempt + 1 < attempts {
+            tokio::time::sleep(Duration::from_millis(100 << attempt.min(5))).await;
+        }
+    }
+    Err(format!("Could not resolve {}: {}", name, last_error))
+}
+
+// The (ip, port) a driver should dial; falls back to the overridden name when lookup fails so
+// the driver reports its own error. Unix socket paths are passed through untouched.
+pub async fn connect_target(host: &str, port: u16) -> (String, u16) {
+    if host.starts_with('/') {
+        return (host.to_string(), port);
+    }
+    match lookup(host, port).await {
+        Ok(addresses) => (addresses[0].ip().to_string(), addresses[0].port()),
+        Err(e) => {
+            log::debug!("{}", e);
+            let target = address(&join(host, port));
+            let (name, target_port) = split_host_port(&target);
+            (name.to_string(), target_port.and_then(|p| p.parse().ok()).unwrap_or(port))
+        }
+    }
+}
+
+// This machine's name: HOSTNAME (containers, most shells), COMPUTERNAME (Windows), /etc/hostname
+pub fn local_hostname() -> String {
+    let from_env = |key: &str| {
+        let name = std::env::var(key).unwrap_or_default();
+        Some(name.trim().to_string()).filter(|name| !name.is_empty())
+    };
+    if let Some(name) = from_env("HOSTNAME") {
+        return name;
+    }
+    #[cfg(windows)]
+    if let Some(name) = from_env("COMPUTERNAME") {
+        return name;
+    }
+    #[cfg(unix)]
+    if let Ok(name) = std::fs::read_to_string("/etc/hostname") {
+        let name = name.trim();
+        if !name.is_empty() {
+            return name.to_string();
+        }
+    }
+    "unknown".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResolveQuery {
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+// GET /admin/resolve?host=redis-1&port=6379 - what this process would dial for a backend host
+pub async fn resolve_host(query: web::Query<ResolveQuery>) -> impl Responder {
+    let port = query.port.unwrap_or(0);
+    let target = address(&join(&query.host, port));
+    let started = Instant::now();
+    let result = lookup(&query.host, port).await;
+    let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    let mut overrides: Vec<_> = OVERRIDES.iter().map(|(from, to)| format!("{}={}", from, to)).collect();
+    overrides.sort();
+    let mut body = json!({
+        "host": query.host,
+        "port": port,
+        "target": target,
+        "prefer_ipv4": prefer_ipv4(),
+        "overrides": overrides,
+        "platform": {"os": std::env::consts::OS, "target": crate::build_info::build_info().target},
+        "elapsed_ms": elapsed_ms,
+    });
+    match result {
+        Ok(addresses) => {
+            body["addresses"] = json!(addresses.iter().map(|a| a.to_string()).collect::<Vec<_>>());
+            HttpResponse::Ok().json(body)
+        }
+        Err(e) => {
+            body["error"] = json!(e);
+            HttpResponse::BadGateway().json(body)
+        }
+    }
+}
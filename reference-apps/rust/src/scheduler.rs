@@ -0,0 +1,278 @@
+// Periodic jobs driven by cron expressions
+//
+// Each job has a default schedule that SCHEDULE_<JOB> (e.g. SCHEDULE_RABBITMQ_HEARTBEAT) can
+// replace with another six-field cron expression (seconds first) or turn off with "off".
+// SCHEDULER_ENABLED=false disables them all. Jobs whose backend is not in ENABLED_SERVICES
+// are skipped. GET /admin/schedules lists every job with its last and next run.
+
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use actix_web::{HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use lazy_static::lazy_static;
+
+use crate::{get_env_or, pool, services, timeseries, vault};
+#[cfg(feature = "rabbitmq")]
+use crate::amqp_connection;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JobKind {
+    VaultTokenRenew,
+    PruneDemoData,
+    RabbitmqHeartbeat,
+    DownsampleTimeseries,
+}
+
+impl JobKind {
+    const ALL: [JobKind; 4] = [
+        JobKind::VaultTokenRenew,
+        JobKind::PruneDemoData,
+        JobKind::RabbitmqHeartbeat,
+        JobKind::DownsampleTimeseries,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobKind::VaultTokenRenew => "vault_token_renew",
+            JobKind::PruneDemoData => "prune_demo_data",
+            JobKind::RabbitmqHeartbeat => "rabbitmq_heartbeat",
+            JobKind::DownsampleTimeseries => "downsample_timeseries",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            JobKind::VaultTokenRenew => "Renew the app's Vault token and drop cached secrets",
+            JobKind::PruneDemoData => "Delete demo rows older than DEMO_DATA_RETENTION_HOURS",
+            JobKind::RabbitmqHeartbeat => "Publish a heartbeat message to SCHEDULER_HEARTBEAT_QUEUE",
+            JobKind::DownsampleTimeseries => "Roll up time-series points from the Redis stream into per-minute rows",
+        }
+    }
+
+    fn default_schedule(&self) -> &'static str {
+        match self {
+            JobKind::VaultTokenRenew => "0 */15 * * * *",
+            JobKind::PruneDemoData => "0 0 * * * *",
+            JobKind::RabbitmqHeartbeat => "0 * * * * *",
+            // A few seconds past the minute so the previous minute is complete
+            JobKind::DownsampleTimeseries => "5 * * * * *",
+        }
+    }
+
+    fn service(&self) -> &'static str {
+        match self {
+            JobKind::VaultTokenRenew => "vault",
+            JobKind::PruneDemoData => "postgres",
+            JobKind::RabbitmqHeartbeat => "rabbitmq",
+            JobKind::DownsampleTimeseries => "postgres",
+        }
+    }
+
+    async fn run(&self) -> Result<String, String> {
+        match self {
+            JobKind::VaultTokenRenew => vault::renew_token().await.map(|ttl| format!("token ttl {}s", ttl)),
+            JobKind::PruneDemoData => prune_demo_data().await,
+            JobKind::RabbitmqHeartbeat => publish_heartbeat().await,
+            JobKind::DownsampleTimeseries => timeseries::downsample().await,
+        }
+    }
+}
+
+// None for "off"
+pub fn parse_schedule(expression: &str) -> Result<Option<Schedule>, String> {
+    let expression = expression.trim();
+    if expression.eq_ignore_ascii_case("off") {
+        return Ok(None);
+    }
+    Schedule::from_str(expression)
+        .map(Some)
+        .map_err(|e| format!("Invalid cron expression '{}': {}", expression, e))
+}
+
+#[derive(Default)]
+struct JobState {
+    last_run: Option<DateTime<Utc>>,
+    last_duration_ms: Option<f64>,
+    last_result: Option<Result<String, String>>,
+    runs: u64,
+    failures: u64,
+}
+
+struct Job {
+    kind: JobKind,
+    expression: String,
+    // Err holds the parse error so a bad expression shows up in the listing
+    schedule: Result<Option<Schedule>, String>,
+    state: Mutex<JobState>,
+}
+
+impl Job {
+    fn from_env(kind: JobKind) -> Self {
+        let key = format!("SCHEDULE_{}", kind.name().to_ascii_uppercase());
+        let expression = get_env_or(&key, kind.default_schedule());
+        let schedule = parse_schedule(&expression);
+        Job { kind, expression, schedule, state: Mutex::new(JobState::default()) }
+    }
+
+    fn next_run(&self) -> Option<DateTime<Utc>> {
+        match &self.schedule {
+            Ok(Some(schedule)) if scheduler_enabled() && services::is_enabled(self.kind.service()) => {
+                schedule.upcoming(Utc).next()
+            }
+            _ => None,
+        }
+    }
+
+    fn status(&self) -> serde_json::Value {
+        let state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (last_status, last_message) = match &state.last_result {
+            Some(Ok(message)) => (Some("success"), Some(message.clone())),
+            Some(Err(error)) => (Some("error"), Some(error.clone())),
+            None => (None, None),
+        };
+        serde_json::json!({
+            "name": self.kind.name(),
+            "description": self.kind.description(),
+            "schedule": self.expression,
+            "service": self.kind.service(),
+            "enabled": self.next_run().is_some(),
+            "error": self.schedule.as_ref().err(),
+            "next_run": self.next_run().map(|t| t.to_rfc3339()),
+            "last_run": state.last_run.map(|t| t.to_rfc3339()),
+            "last_duration_ms": state.last_duration_ms,
+            "last_status": last_status,
+            "last_message": last_message,
+            "runs": state.runs,
+            "failures": state.failures
+        })
+    }
+
+    async fn execute(&self) {
+        let started_at = Utc::now();
+        let started = Instant::now();
+        let result = self.kind.run().await;
+        if let Err(e) = &result {
+            log::warn!("Scheduled job {} failed: {}", self.kind.name(), e);
+        }
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.last_run = Some(started_at);
+        state.last_duration_ms = Some(started.elapsed().as_secs_f64() * 1000.0);
+        state.runs += 1;
+        if result.is_err() {
+            state.failures += 1;
+        }
+        state.last_result = Some(result);
+    }
+}
+
+lazy_static! {
+    static ref JOBS: Vec<Arc<Job>> = JobKind::ALL.iter().map(|kind| Arc::new(Job::from_env(*kind))).collect();
+}
+
+fn scheduler_enabled() -> bool {
+    get_env_or("SCHEDULER_ENABLED", "true").parse().unwrap_or(true)
+}
+
+// ============================================================================
+// Jobs
+// ============================================================================
+
+async fn prune_demo_data() -> Result<String, String> {
+    let hours: i32 = get_env_or("DEMO_DATA_RETENTION_HOURS", "24").parse().unwrap_or(24);
+    let client = pool::postgres().await?;
+    // Only tables that exist; demo endpoints create them on first use
+    let mut deleted = 0;
+    for table in ["dual_write_orders"] {
+        let exists: bool = client
+            .query_one("SELECT to_regclass($1) IS NOT NULL", &[&table])
+            .await
+            .map_err(|e| e.to_string())?
+            .get(0);
+        if exists {
+            deleted += client
+                .execute(
+                    format!("DELETE FROM {} WHERE created_at < NOW() - make_interval(hours => $1)", table).as_str(),
+                    &[&hours],
+                )
+                .await
+                .map_err(|e| format!("Pruning {} failed: {}", table, e))?;
+        }
+    }
+    Ok(format!("deleted {} rows older than {}h", deleted, hours))
+}
+
+#[cfg(feature = "rabbitmq")]
+async fn publish_heartbeat() -> Result<String, String> {
+    let queue = get_env_or("SCHEDULER_HEARTBEAT_QUEUE", "devstack.heartbeat");
+    let conn = amqp_connection().await?;
+    let result = async {
+        let channel = conn.create_channel().await.map_err(|e| format!("Channel creation failed: {}", e))?;
+        channel
+            .queue_declare(&queue, lapin::options::QueueDeclareOptions::default(), lapin::types::FieldTable::default())
+            .await
+            .map_err(|e| format!("Queue declare failed: {}", e))?;
+        let payload = serde_json::json!({
+            "type": "heartbeat",
+            "source": "devstack-core-rust-api",
+            "timestamp": Utc::now().to_rfc3339()
+        })
+        .to_string();
+        channel
+            .basic_publish(
+                "",
+                &queue,
+                lapin::options::BasicPublishOptions::default(),
+                payload.as_bytes(),
+                lapin::BasicProperties::default().with_content_type("application/json".into()),
+            )
+            .await
+            .map_err(|e| format!("Publish failed: {}", e))?;
+        Ok::<_, String>(format!("published to {}", queue))
+    }
+    .await;
+    let _ = conn.close(0, "Done").await;
+    result
+}
+
+// The job is never scheduled when RabbitMQ is not compiled in, since services::is_enabled is false
+#[cfg(not(feature = "rabbitmq"))]
+async fn publish_heartbeat() -> Result<String, String> {
+    Err("RabbitMQ support is not compiled in".to_string())
+}
+
+// ============================================================================
+// Runner and endpoint
+// ============================================================================
+
+// One task per job, sleeping until the next matching time
+pub fn spawn_scheduler() {
+    if !scheduler_enabled() {
+        return;
+    }
+    for job in JOBS.iter() {
+        if let Err(e) = &job.schedule {
+            log::warn!("Scheduled job {} disabled: {}", job.kind.name(), e);
+            continue;
+        }
+        let job = job.clone();
+        tokio::spawn(async move {
+            while let Some(next) = job.next_run() {
+                let wait = (next - Utc::now()).to_std().unwrap_or_default();
+                tokio::time::sleep(wait).await;
+                job.execute().await;
+            }
+        });
+    }
+}
+
+pub async fn list_schedules() -> impl Responder {
+    let jobs: Vec<serde_json::Value> = JOBS.iter().map(|job| job.status()).collect();
+    HttpResponse::Ok().json(serde_json::json!({
+        "enabled": scheduler_enabled(),
+        "count": jobs.len(),
+        "jobs": jobs
+    }))
+}
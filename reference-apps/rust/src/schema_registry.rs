@@ -0,0 +1,193 @@
+// JSON Schema registry for RabbitMQ message bodies
+//
+// Schemas are stored in PostgreSQL (message_schemas) and versioned on every update. A publish
+// request that names a schema has its message parsed as JSON and validated before it reaches
+// the broker; invalid payloads are rejected with one error per violation.
+
+use actix_web::{web, HttpResponse, Responder};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::pool;
+
+const TABLE_DDL: &str = "CREATE TABLE IF NOT EXISTS message_schemas (
+    name TEXT PRIMARY KEY,
+    schema JSONB NOT NULL,
+    version INTEGER NOT NULL DEFAULT 1,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+)";
+
+// Enough to explain a bad payload without echoing thousands of errors
+const MAX_ERRORS: usize = 20;
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct SchemaError {
+    // JSON pointer to the offending value ("" for the document root)
+    pub path: String,
+    pub message: String,
+}
+
+// Violations of `schema` by `instance`; Err when the schema itself is invalid
+pub fn validate(schema: &Value, instance: &Value) -> Result<Vec<SchemaError>, String> {
+    let validator = jsonschema::validator_for(schema).map_err(|e| format!("Invalid JSON Schema: {}", e))?;
+    Ok(validator
+        .iter_errors(instance)
+        .take(MAX_ERRORS)
+        .map(|e| SchemaError { path: e.instance_path.to_string(), message: e.to_string() })
+        .collect())
+}
+
+pub fn valid_schema_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 128
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+async fn connection() -> Result<pool::Pooled<pool::PostgresManager>, HttpResponse> {
+    let client = pool::postgres()
+        .await
+        .map_err(|e| error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e))?;
+    client.batch_execute(TABLE_DDL).await.map_err(|e| {
+        error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Schema table setup failed: {}", e))
+    })?;
+    Ok(client)
+}
+
+// (schema, version) or None when no schema has that name
+async fn fetch_schema(
+    client: &tokio_postgres::Client,
+    name: &str,
+) -> Result<Option<(Value, i32)>, tokio_postgres::Error> {
+    let row = client
+        .query_opt("SELECT schema, version FROM message_schemas WHERE name = $1", &[&name])
+        .await?;
+    Ok(row.map(|row| (row.get(0), row.get(1))))
+}
+
+// ============================================================================
+// Registry endpoints
+// ============================================================================
+
+pub async fn list_schemas() -> impl Responder {
+    let client = match connection().await {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
+    match client
+        .query("SELECT name, version, updated_at::text FROM message_schemas ORDER BY name", &[])
+        .await
+    {
+        Ok(rows) => {
+            let schemas: Vec<Value> = rows
+                .iter()
+                .map(|row| {
+                    serde_json::json!({
+                        "name": row.get::<_, String>(0),
+                        "version": row.get::<_, i32>(1),
+                        "updated_at": row.get::<_, String>(2)
+                    })
+                })
+                .collect();
+            HttpResponse::Ok().json(serde_json::json!({ "status": "success", "count": schemas.len(), "schemas": schemas }))
+        }
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Query failed: {}", e)),
+    }
+}
+
+pub async fn get_schema(path: web::Path<String>) -> impl Responder {
+    let name = path.into_inner();
+    let client = match connection().await {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
+    match fetch_schema(&client, &name).await {
+        Ok(Some((schema, version))) => HttpResponse::Ok().json(serde_json::json!({
+            "status": "success",
+            "name": name,
+            "version": version,
+            "schema": schema
+        })),
+        Ok(None) => error_response(actix_web::http::StatusCode::NOT_FOUND, format!("Unknown schema '{}'", name)),
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Query failed: {}", e)),
+    }
+}
+
+// The request body is the JSON Schema itself
+pub async fn put_schema(path: web::Path<String>, body: web::Json<Value>) -> impl Responder {
+    let name = path.into_inner();
+    let schema = body.into_inner();
+    if !valid_schema_name(&name) {
+        return error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            "Schema names are 1-128 characters of letters, digits, '_', '-' or '.'".to_string(),
+        );
+    }
+    // Compile before storing so only usable schemas reach the registry
+    if let Err(e) = validate(&schema, &Value::Null) {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, e);
+    }
+
+    let client = match connection().await {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
+    let upsert = "INSERT INTO message_schemas (name, schema) VALUES ($1, $2)
+        ON CONFLICT (name) DO UPDATE
+            SET schema = EXCLUDED.schema, version = message_schemas.version + 1, updated_at = NOW()
+        RETURNING version";
+    match client.query_one(upsert, &[&name, &schema]).await {
+        Ok(row) => HttpResponse::Ok().json(serde_json::json!({
+            "status": "stored",
+            "name": name,
+            "version": row.get::<_, i32>(0)
+        })),
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Store failed: {}", e)),
+    }
+}
+
+// ============================================================================
+// Publish-time validation
+// ============================================================================
+
+// Ok when `message` is JSON that satisfies the named schema; otherwise the response to return
+pub async fn check_message(schema_name: &str, message: &str) -> Result<(), HttpResponse> {
+    let instance: Value = serde_json::from_str(message).map_err(|e| {
+        error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            format!("Message must be JSON to validate against schema '{}': {}", schema_name, e),
+        )
+    })?;
+
+    let client = connection().await?;
+    let (schema, version) = match fetch_schema(&client, schema_name).await {
+        Ok(Some(found)) => found,
+        Ok(None) => {
+            return Err(error_response(
+                actix_web::http::StatusCode::NOT_FOUND,
+                format!("Unknown schema '{}'", schema_name),
+            ))
+        }
+        Err(e) => {
+            return Err(error_response(
+                actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Query failed: {}", e),
+            ))
+        }
+    };
+
+    match validate(&schema, &instance) {
+        Ok(errors) if errors.is_empty() => Ok(()),
+        Ok(errors) => Err(HttpResponse::UnprocessableEntity().json(serde_json::json!({
+            "status": "invalid",
+            "schema": schema_name,
+            "version": version,
+            "errors": errors
+        }))),
+        Err(e) => Err(error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
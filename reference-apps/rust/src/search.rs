@@ -0,0 +1,494 @@
+// Full-text search across PostgreSQL, MongoDB and RediSearch
+//
+// The same small corpus is indexed into a tsvector column with a GIN index, a MongoDB text
+// index and (when the RediSearch module is loaded) an FT index over Redis hashes, then one query
+// runs against all three. The engines disagree in the details: websearch_to_tsquery
+// ANDs terms, MongoDB's $text ORs them, RediSearch ANDs them but stems differently, and each
+// ranks with its own formula, so the comparison reports which documents every engine found.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Instant;
+
+use actix_web::{web, HttpResponse, Responder};
+use futures_util::TryStreamExt;
+use mongodb::bson::{doc, Document};
+use mongodb::options::IndexOptions;
+use mongodb::IndexModel;
+use serde::{Deserialize, Serialize};
+
+use crate::seed::MONGODB_DATABASE;
+use crate::{mongodb_client, pool, redis_connection};
+
+const COLLECTION: &str = "search_documents";
+const REDISEARCH_INDEX: &str = "search-idx";
+const REDISEARCH_PREFIX: &str = "search:doc:";
+const MAX_QUERY_LEN: usize = 200;
+const MAX_RESULTS: usize = 50;
+
+const TABLE_DDL: &str = "CREATE TABLE IF NOT EXISTS search_documents (
+    id TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    body TEXT NOT NULL,
+    tsv tsvector GENERATED ALWAYS AS (
+        setweight(to_tsvector('english', title), 'A') || setweight(to_tsvector('english', body), 'B')
+    ) STORED
+);
+CREATE INDEX IF NOT EXISTS search_documents_tsv_idx ON search_documents USING GIN (tsv)";
+
+// (id, title, body)
+pub const CORPUS: &[(&str, &str, &str)] = &[
+    (
+        "postgres-mvcc",
+        "PostgreSQL MVCC and vacuum",
+        "PostgreSQL keeps old row versions for concurrent readers; autovacuum reclaims dead tuples \
+         and prevents transaction ID wraparound.",
+    ),
+    (
+        "postgres-indexes",
+        "Choosing PostgreSQL index types",
+        "B-tree indexes serve equality and range queries, GIN indexes serve full-text search and \
+         JSONB containment, and GiST indexes serve geometric data.",
+    ),
+    (
+        "postgres-replication",
+        "Streaming replication in PostgreSQL",
+        "A standby replays the primary's write-ahead log; synchronous replication waits for the \
+         standby to confirm before a commit returns.",
+    ),
+    (
+        "mysql-innodb",
+        "InnoDB buffer pool tuning",
+        "MySQL caches table and index pages in the InnoDB buffer pool; size it to hold the working \
+         set so queries avoid disk reads.",
+    ),
+    (
+        "mysql-replication",
+        "MySQL binlog replication",
+        "Replicas read the binary log from the source; GTID based replication makes failover and \
+         replica promotion simpler.",
+    ),
+    (
+        "mongodb-aggregation",
+        "MongoDB aggregation pipelines",
+        "Aggregation stages such as match, group and lookup transform documents; put match first \
+         so the pipeline can use an index.",
+    ),
+    (
+        "mongodb-sharding",
+        "Sharding a MongoDB collection",
+        "A shard key distributes documents across shards; a monotonically increasing key sends \
+         every insert to the same chunk.",
+    ),
+    (
+        "redis-persistence",
+        "Redis persistence: RDB and AOF",
+        "RDB snapshots are compact and fast to load, while the append-only file logs every write \
+         and loses less data after a crash.",
+    ),
+    (
+        "redis-cluster",
+        "Redis Cluster hash slots",
+        "Keys map to one of 16384 hash slots; hash tags keep related keys in the same slot so \
+         multi-key commands still work.",
+    ),
+    (
+        "redis-streams",
+        "Consumer groups with Redis Streams",
+        "Consumer groups split a stream between workers; pending entries are acknowledged with \
+         XACK or claimed by another consumer after a timeout.",
+    ),
+    (
+        "rabbitmq-queues",
+        "RabbitMQ quorum queues",
+        "Quorum queues replicate messages with Raft and replace classic mirrored queues for data \
+         safety during node failures.",
+    ),
+    (
+        "rabbitmq-dead-letter",
+        "Dead letter exchanges in RabbitMQ",
+        "Rejected or expired messages are routed to a dead letter exchange where they can be \
+         inspected or retried later.",
+    ),
+    (
+        "vault-dynamic-secrets",
+        "Vault dynamic database credentials",
+        "Vault issues short-lived database users on demand and revokes them when the lease expires, \
+         so no long-lived password is shared.",
+    ),
+    (
+        "vault-transit",
+        "Encryption as a service with Vault Transit",
+        "Transit encrypts and decrypts data without storing it; applications never see the key and \
+         rotation only requires rewrapping ciphertext.",
+    ),
+    (
+        "caching-patterns",
+        "Cache-aside versus write-through caching",
+        "Cache-aside loads from the database on a miss, while write-through updates the cache on \
+         every write; both need an expiry to bound staleness.",
+    ),
+    (
+        "connection-pooling",
+        "Connection pooling for databases",
+        "A pool reuses open connections to PostgreSQL, MySQL or MongoDB, capping concurrency and \
+         avoiding the cost of a handshake per query.",
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchHit {
+    pub id: String,
+    pub title: String,
+    pub score: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Comparison {
+    // Backends that answered, in name order
+    pub compared: Vec<String>,
+    pub found_by_all: Vec<String>,
+    // Hits that only one backend returned, keyed by backend
+    pub only_in: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize)]
+pub struct CompareQuery {
+    pub q: Option<String>,
+    pub limit: Option<usize>,
+}
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+pub fn validate_query(q: Option<&str>) -> Result<String, String> {
+    let q = q.map(str::trim).unwrap_or_default();
+    if q.is_empty() {
+        return Err("Query parameter 'q' is required".to_string());
+    }
+    if q.len() > MAX_QUERY_LEN {
+        return Err(format!("Query must be at most {} bytes", MAX_QUERY_LEN));
+    }
+    Ok(q.to_string())
+}
+
+// RediSearch treats punctuation as query syntax; keep words, quoted phrases and leading-dash
+// negation (the subset websearch_to_tsquery also understands) and escape everything else
+pub fn redisearch_query(q: &str) -> String {
+    let mut out = String::with_capacity(q.len());
+    let mut prev = ' ';
+    for c in q.chars() {
+        let negation = c == '-' && prev.is_whitespace();
+        if c.is_alphanumeric() || c.is_whitespace() || c == '"' || negation {
+            out.push(c);
+        } else {
+            out.push('\\');
+            out.push(c);
+        }
+        prev = c;
+    }
+    out
+}
+
+fn value_string(value: &redis::Value) -> Option<String> {
+    match value {
+        redis::Value::BulkString(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+        redis::Value::SimpleString(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+// FT.SEARCH ... WITHSCORES RETURN 1 title replies [total, key, score, [field, value], key, ...]
+pub fn parse_ft_search(reply: &redis::Value) -> Result<Vec<SearchHit>, String> {
+    let items = match reply {
+        redis::Value::Array(items) => items,
+        other => return Err(format!("Unexpected FT.SEARCH reply: {:?}", other)),
+    };
+    let mut hits = Vec::new();
+    for chunk in items.get(1..).unwrap_or_default().chunks(3) {
+        let [key, score, fields] = chunk else {
+            return Err("Truncated FT.SEARCH reply".to_string());
+        };
+        let key = value_string(key).ok_or_else(|| "FT.SEARCH key is not a string".to_string())?;
+        let score = match score {
+            redis::Value::Double(score) => *score,
+            other => value_string(other)
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| "FT.SEARCH score is not a number".to_string())?,
+        };
+        let title = match fields {
+            redis::Value::Array(fields) => fields
+                .chunks(2)
+                .find(|pair| pair.first().and_then(value_string).as_deref() == Some("title"))
+                .and_then(|pair| pair.get(1).and_then(value_string))
+                .unwrap_or_default(),
+            _ => String::new(),
+        };
+        let id = key.strip_prefix(REDISEARCH_PREFIX).unwrap_or(&key).to_string();
+        hits.push(SearchHit { id, title, score });
+    }
+    Ok(hits)
+}
+
+pub fn compare(results: &BTreeMap<String, Vec<SearchHit>>) -> Comparison {
+    let id_sets: BTreeMap<&String, BTreeSet<&str>> = results
+        .iter()
+        .map(|(backend, hits)| (backend, hits.iter().map(|h| h.id.as_str()).collect()))
+        .collect();
+    let all_ids: BTreeSet<&str> = id_sets.values().flatten().copied().collect();
+    let found_by = |id: &str| id_sets.values().filter(|ids| ids.contains(id)).count();
+
+    let found_by_all = all_ids
+        .iter()
+        .filter(|id| found_by(id) == id_sets.len())
+        .map(|id| id.to_string())
+        .collect();
+    let only_in = id_sets
+        .iter()
+        .map(|(backend, ids)| {
+            let unique = ids.iter().filter(|id| found_by(id) == 1).map(|id| id.to_string()).collect();
+            (backend.to_string(), unique)
+        })
+        .collect();
+    Comparison { compared: results.keys().cloned().collect(), found_by_all, only_in }
+}
+
+// ============================================================================
+// Backends
+// ============================================================================
+
+fn is_unknown_command(e: &redis::RedisError) -> bool {
+    e.to_string().to_ascii_lowercase().contains("unknown command")
+}
+
+// None when the RediSearch module isn't loaded
+async fn redisearch_connection() -> Result<Option<redis::aio::MultiplexedConnection>, String> {
+    let mut conn = redis_connection().await?;
+    match redis::cmd("FT._LIST").query_async::<Vec<String>>(&mut conn).await {
+        Ok(_) => Ok(Some(conn)),
+        Err(e) if is_unknown_command(&e) => Ok(None),
+        Err(e) => Err(format!("FT._LIST failed: {}", e)),
+    }
+}
+
+async fn index_postgres() -> Result<u64, String> {
+    let client = pool::postgres().await?;
+    client
+        .batch_execute(TABLE_DDL)
+        .await
+        .map_err(|e| format!("search_documents setup failed: {}", e))?;
+    let mut indexed = 0;
+    for (id, title, body) in CORPUS {
+        indexed += client
+            .execute(
+                "INSERT INTO search_documents (id, title, body) VALUES ($1, $2, $3)
+                 ON CONFLICT (id) DO UPDATE SET title = EXCLUDED.title, body = EXCLUDED.body",
+                &[id, title, body],
+            )
+            .await
+            .map_err(|e| format!("Insert failed: {}", e))?;
+    }
+    Ok(indexed)
+}
+
+async fn index_mongodb() -> Result<u64, String> {
+    let client = mongodb_client().await?;
+    let collection = client.database(MONGODB_DATABASE).collection::<Document>(COLLECTION);
+    let options = IndexOptions::builder()
+        .name("search_text".to_string())
+        .weights(doc! { "title": 2, "body": 1 })
+        .build();
+    let model = IndexModel::builder().keys(doc! { "title": "text", "body": "text" }).options(options).build();
+    collection.create_index(model).await.map_err(|e| format!("Create index failed: {}", e))?;
+    let mut indexed = 0;
+    for (id, title, body) in CORPUS {
+        collection
+            .replace_one(doc! { "_id": *id }, doc! { "_id": *id, "title": *title, "body": *body })
+            .upsert(true)
+            .await
+            .map_err(|e| format!("Replace failed: {}", e))?;
+        indexed += 1;
+    }
+    Ok(indexed)
+}
+
+async fn index_redisearch(conn: &mut redis::aio::MultiplexedConnection) -> Result<u64, String> {
+    let created = redis::cmd("FT.CREATE")
+        .arg(REDISEARCH_INDEX)
+        .arg(&["ON", "HASH", "PREFIX", "1", REDISEARCH_PREFIX, "SCHEMA"])
+        .arg(&["title", "TEXT", "WEIGHT", "2.0", "body", "TEXT"])
+        .query_async::<()>(conn)
+        .await;
+    match created {
+        Ok(()) => {}
+        Err(e) if e.to_string().to_ascii_lowercase().contains("index already exists") => {}
+        Err(e) => return Err(format!("FT.CREATE failed: {}", e)),
+    }
+    let mut pipe = redis::pipe();
+    for (id, title, body) in CORPUS {
+        pipe.hset_multiple(format!("{}{}", REDISEARCH_PREFIX, id), &[("title", *title), ("body", *body)])
+            .ignore();
+    }
+    pipe.query_async::<()>(conn).await.map_err(|e| format!("HSET failed: {}", e))?;
+    Ok(CORPUS.len() as u64)
+}
+
+async fn search_postgres(q: &str, limit: usize) -> Result<Vec<SearchHit>, String> {
+    let client = pool::postgres().await?;
+    let rows = client
+        .query(
+            "SELECT id, title, ts_rank(tsv, query)::float8 AS score
+             FROM search_documents, websearch_to_tsquery('english', $1) AS query
+             WHERE tsv @@ query
+             ORDER BY score DESC, id
+             LIMIT $2",
+            &[&q, &(limit as i64)],
+        )
+        .await
+        .map_err(|e| format!("Search failed: {}", e))?;
+    Ok(rows
+        .iter()
+        .map(|row| SearchHit { id: row.get("id"), title: row.get("title"), score: row.get("score") })
+        .collect())
+}
+
+async fn search_mongodb(q: &str, limit: usize) -> Result<Vec<SearchHit>, String> {
+    let client = mongodb_client().await?;
+    let collection = client.database(MONGODB_DATABASE).collection::<Document>(COLLECTION);
+    let docs: Vec<Document> = collection
+        .find(doc! { "$text": { "$search": q } })
+        .projection(doc! { "title": 1, "score": { "$meta": "textScore" } })
+        .sort(doc! { "score": { "$meta": "textScore" } })
+        .limit(limit as i64)
+        .await
+        .map_err(|e| format!("Search failed: {}", e))?
+        .try_collect()
+        .await
+        .map_err(|e| format!("Search failed: {}", e))?;
+    Ok(docs
+        .iter()
+        .map(|d| SearchHit {
+            id: d.get_str("_id").unwrap_or_default().to_string(),
+            title: d.get_str("title").unwrap_or_default().to_string(),
+            score: d.get_f64("score").unwrap_or_default(),
+        })
+        .collect())
+}
+
+async fn search_redisearch(
+    conn: &mut redis::aio::MultiplexedConnection,
+    q: &str,
+    limit: usize,
+) -> Result<Vec<SearchHit>, String> {
+    let reply = redis::cmd("FT.SEARCH")
+        .arg(REDISEARCH_INDEX)
+        .arg(redisearch_query(q))
+        .arg("WITHSCORES")
+        .arg(&["RETURN", "1", "title", "LIMIT", "0"])
+        .arg(limit)
+        .query_async::<redis::Value>(conn)
+        .await
+        .map_err(|e| format!("FT.SEARCH failed: {}", e))?;
+    parse_ft_search(&reply)
+}
+
+fn index_json(result: &Result<u64, String>, elapsed_ms: f64) -> serde_json::Value {
+    match result {
+        Ok(indexed) => serde_json::json!({ "available": true, "elapsed_ms": elapsed_ms, "indexed": indexed }),
+        Err(e) => serde_json::json!({ "available": false, "reason": e }),
+    }
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+// Loads the corpus into every backend that is reachable
+pub async fn index_corpus() -> impl Responder {
+    let started = Instant::now();
+    let postgres = index_postgres().await;
+    let postgres = index_json(&postgres, started.elapsed().as_secs_f64() * 1000.0);
+
+    let started = Instant::now();
+    let mongodb = index_mongodb().await;
+    let mongodb = index_json(&mongodb, started.elapsed().as_secs_f64() * 1000.0);
+
+    let started = Instant::now();
+    let redisearch = match redisearch_connection().await {
+        Ok(Some(mut conn)) => index_redisearch(&mut conn).await,
+        Ok(None) => Err("RediSearch module is not loaded".to_string()),
+        Err(e) => Err(e),
+    };
+    let redisearch = index_json(&redisearch, started.elapsed().as_secs_f64() * 1000.0);
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "indexed",
+        "documents": CORPUS.len(),
+        "postgres": postgres,
+        "mongodb": mongodb,
+        "redisearch": redisearch
+    }))
+}
+
+pub async fn compare_search(query: web::Query<CompareQuery>) -> impl Responder {
+    let q = match validate_query(query.q.as_deref()) {
+        Ok(q) => q,
+        Err(e) => return error_response(actix_web::http::StatusCode::BAD_REQUEST, e),
+    };
+    let limit = query.limit.unwrap_or(10).clamp(1, MAX_RESULTS);
+
+    let mut backends = serde_json::Map::new();
+    let mut answered = BTreeMap::new();
+
+    let started = Instant::now();
+    let postgres = search_postgres(&q, limit).await;
+    let postgres_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    let started = Instant::now();
+    let mongodb = search_mongodb(&q, limit).await;
+    let mongodb_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    let started = Instant::now();
+    let redisearch = match redisearch_connection().await {
+        Ok(Some(mut conn)) => search_redisearch(&mut conn, &q, limit).await,
+        Ok(None) => Err("RediSearch module is not loaded".to_string()),
+        Err(e) => Err(e),
+    };
+    let redisearch_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    for (name, result, elapsed_ms) in [
+        ("postgres", postgres, postgres_ms),
+        ("mongodb", mongodb, mongodb_ms),
+        ("redisearch", redisearch, redisearch_ms),
+    ] {
+        let entry = match result {
+            Ok(hits) => {
+                let entry = serde_json::json!({
+                    "available": true,
+                    "elapsed_ms": elapsed_ms,
+                    "count": hits.len(),
+                    "results": hits
+                });
+                answered.insert(name.to_string(), hits);
+                entry
+            }
+            Err(e) => serde_json::json!({ "available": false, "reason": e }),
+        };
+        backends.insert(name.to_string(), entry);
+    }
+
+    if answered.is_empty() {
+        return error_response(
+            actix_web::http::StatusCode::SERVICE_UNAVAILABLE,
+            "No search backend is available; POST /examples/search/index first".to_string(),
+        );
+    }
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "query": q,
+        "limit": limit,
+        "backends": backends,
+        "comparison": compare(&answered)
+    }))
+}
@@ -0,0 +1,425 @@
+// Fake-data seeding for PostgreSQL, MySQL, and MongoDB
+//
+// The same seeded RNG produces the same users and orders on every run, so the three
+// databases hold identical datasets that other examples can query and compare.
+
+use std::time::Instant;
+
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use fake::faker::internet::en::SafeEmail;
+use fake::faker::name::en::Name;
+use fake::Fake;
+#[cfg(feature = "mongodb")]
+use mongodb::bson::{doc, Document};
+#[cfg(feature = "mysql")]
+use mysql_async::prelude::Queryable;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::services;
+use crate::{get_env_or, postgres_client};
+#[cfg(feature = "mongodb")]
+use crate::mongodb_client;
+#[cfg(feature = "mysql")]
+use crate::mysql_connection;
+
+pub const USERS_TABLE: &str = "seed_users";
+pub const ORDERS_TABLE: &str = "seed_orders";
+pub const MONGODB_DATABASE: &str = "test";
+
+const DEFAULT_ROWS: usize = 10_000;
+const DEFAULT_SEED: u64 = 42;
+const INSERT_BATCH: usize = 1_000;
+const ORDER_STATUSES: &[&str] = &["pending", "paid", "shipped", "refunded"];
+
+#[derive(Deserialize)]
+pub struct SeedQuery {
+    rows: Option<usize>,
+    seed: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct SeedResult {
+    status: String,
+    users: u64,
+    orders: u64,
+    elapsed_ms: u64,
+    rows_per_sec: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl SeedResult {
+    fn success(rows: usize, started: Instant) -> Self {
+        let elapsed = started.elapsed();
+        let total = (rows * 2) as f64;
+        SeedResult {
+            status: "success".to_string(),
+            users: rows as u64,
+            orders: rows as u64,
+            elapsed_ms: elapsed.as_millis() as u64,
+            rows_per_sec: if elapsed.as_secs_f64() > 0.0 { total / elapsed.as_secs_f64() } else { total },
+            error: None,
+        }
+    }
+
+    fn failed(error: String, started: Instant) -> Self {
+        SeedResult {
+            status: "error".to_string(),
+            users: 0,
+            orders: 0,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+            rows_per_sec: 0.0,
+            error: Some(error),
+        }
+    }
+
+    fn disabled() -> Self {
+        SeedResult {
+            status: "disabled".to_string(),
+            users: 0,
+            orders: 0,
+            elapsed_ms: 0,
+            rows_per_sec: 0.0,
+            error: None,
+        }
+    }
+}
+
+// Seeded user as returned by the list endpoints
+#[derive(Serialize)]
+pub struct UserRecord {
+    pub id: i64,
+    pub name: String,
+    pub email: String,
+    pub created_at: String,
+}
+
+pub struct SeedUser {
+    pub id: i64,
+    pub name: String,
+    pub email: String,
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct SeedOrder {
+    pub id: i64,
+    pub user_id: i64,
+    pub amount_cents: i64,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct SeedData {
+    pub users: Vec<SeedUser>,
+    pub orders: Vec<SeedOrder>,
+}
+
+// Deterministic for a given (rows, seed) pair
+pub fn generate(rows: usize, seed: u64) -> SeedData {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let epoch = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).single().unwrap_or_else(Utc::now);
+    let year_secs = 365 * 24 * 3600;
+
+    let users = (1..=rows as i64)
+        .map(|id| SeedUser {
+            id,
+            name: Name().fake_with_rng(&mut rng),
+            email: SafeEmail().fake_with_rng(&mut rng),
+            created_at: epoch + Duration::seconds(rng.gen_range(0..year_secs)),
+        })
+        .collect();
+
+    let orders = (1..=rows as i64)
+        .map(|id| SeedOrder {
+            id,
+            user_id: rng.gen_range(1..=rows as i64),
+            amount_cents: rng.gen_range(100..50_000),
+            status: ORDER_STATUSES[rng.gen_range(0..ORDER_STATUSES.len())].to_string(),
+            created_at: epoch + Duration::seconds(rng.gen_range(0..year_secs)),
+        })
+        .collect();
+
+    SeedData { users, orders }
+}
+
+async fn seed_postgres(data: &SeedData) -> Result<(), String> {
+    let client = postgres_client().await?;
+
+    client
+        .batch_execute(&format!(
+            "CREATE TABLE IF NOT EXISTS {users} (
+                id BIGINT PRIMARY KEY,
+                name TEXT NOT NULL,
+                email TEXT NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS {orders} (
+                id BIGINT PRIMARY KEY,
+                user_id BIGINT NOT NULL,
+                amount_cents BIGINT NOT NULL,
+                status TEXT NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL
+            );
+            TRUNCATE {orders}, {users};",
+            users = USERS_TABLE,
+            orders = ORDERS_TABLE
+        ))
+        .await
+        .map_err(|e| format!("Schema setup failed: {}", e))?;
+
+    // UNNEST turns one set of array parameters into many rows per round trip
+    let insert_users = format!(
+        "INSERT INTO {} (id, name, email, created_at)
+         SELECT id, name, email, created_at::timestamptz
+         FROM UNNEST($1::bigint[], $2::text[], $3::text[], $4::text[]) AS t(id, name, email, created_at)",
+        USERS_TABLE
+    );
+    for batch in data.users.chunks(INSERT_BATCH) {
+        let ids: Vec<i64> = batch.iter().map(|u| u.id).collect();
+        let names: Vec<&str> = batch.iter().map(|u| u.name.as_str()).collect();
+        let emails: Vec<&str> = batch.iter().map(|u| u.email.as_str()).collect();
+        let created: Vec<String> = batch.iter().map(|u| u.created_at.to_rfc3339()).collect();
+        client
+            .execute(insert_users.as_str(), &[&ids, &names, &emails, &created])
+            .await
+            .map_err(|e| format!("Insert into {} failed: {}", USERS_TABLE, e))?;
+    }
+
+    let insert_orders = format!(
+        "INSERT INTO {} (id, user_id, amount_cents, status, created_at)
+         SELECT id, user_id, amount_cents, status, created_at::timestamptz
+         FROM UNNEST($1::bigint[], $2::bigint[], $3::bigint[], $4::text[], $5::text[])
+           AS t(id, user_id, amount_cents, status, created_at)",
+        ORDERS_TABLE
+    );
+    for batch in data.orders.chunks(INSERT_BATCH) {
+        let ids: Vec<i64> = batch.iter().map(|o| o.id).collect();
+        let user_ids: Vec<i64> = batch.iter().map(|o| o.user_id).collect();
+        let amounts: Vec<i64> = batch.iter().map(|o| o.amount_cents).collect();
+        let statuses: Vec<&str> = batch.iter().map(|o| o.status.as_str()).collect();
+        let created: Vec<String> = batch.iter().map(|o| o.created_at.to_rfc3339()).collect();
+        client
+            .execute(insert_orders.as_str(), &[&ids, &user_ids, &amounts, &statuses, &created])
+            .await
+            .map_err(|e| format!("Insert into {} failed: {}", ORDERS_TABLE, e))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "mysql")]
+fn mysql_datetime(ts: &DateTime<Utc>) -> String {
+    ts.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+// Multi-row INSERT with `row_placeholders` repeated once per row
+pub fn mysql_insert_sql(table: &str, columns: &str, row_placeholders: &str, rows: usize) -> String {
+    let values = vec![row_placeholders; rows].join(", ");
+    format!("INSERT INTO {} ({}) VALUES {}", table, columns, values)
+}
+
+#[cfg(feature = "mysql")]
+async fn seed_mysql(data: &SeedData) -> Result<(), String> {
+    let mut conn = mysql_connection().await?;
+
+    let setup = [
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                id BIGINT PRIMARY KEY,
+                name VARCHAR(255) NOT NULL,
+                email VARCHAR(255) NOT NULL,
+                created_at DATETIME NOT NULL,
+                version BIGINT NOT NULL DEFAULT 1
+            )",
+            USERS_TABLE
+        ),
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                id BIGINT PRIMARY KEY,
+                user_id BIGINT NOT NULL,
+                amount_cents BIGINT NOT NULL,
+                status VARCHAR(32) NOT NULL,
+                created_at DATETIME NOT NULL
+            )",
+            ORDERS_TABLE
+        ),
+        format!("TRUNCATE TABLE {}", ORDERS_TABLE),
+        format!("TRUNCATE TABLE {}", USERS_TABLE),
+    ];
+    for statement in setup.iter() {
+        conn.query_drop(statement.as_str())
+            .await
+            .map_err(|e| format!("Schema setup failed: {}", e))?;
+    }
+
+    for batch in data.users.chunks(INSERT_BATCH) {
+        let sql = mysql_insert_sql(USERS_TABLE, "id, name, email, created_at", "(?, ?, ?, ?)", batch.len());
+        let mut params: Vec<mysql_async::Value> = Vec::with_capacity(batch.len() * 4);
+        for u in batch {
+            params.push(u.id.into());
+            params.push(u.name.as_str().into());
+            params.push(u.email.as_str().into());
+            params.push(mysql_datetime(&u.created_at).into());
+        }
+        conn.exec_drop(sql, mysql_async::Params::Positional(params))
+            .await
+            .map_err(|e| format!("Insert into {} failed: {}", USERS_TABLE, e))?;
+    }
+
+    for batch in data.orders.chunks(INSERT_BATCH) {
+        let sql = mysql_insert_sql(
+            ORDERS_TABLE,
+            "id, user_id, amount_cents, status, created_at",
+            "(?, ?, ?, ?, ?)",
+            batch.len(),
+        );
+        let mut params: Vec<mysql_async::Value> = Vec::with_capacity(batch.len() * 5);
+        for o in batch {
+            params.push(o.id.into());
+            params.push(o.user_id.into());
+            params.push(o.amount_cents.into());
+            params.push(o.status.as_str().into());
+            params.push(mysql_datetime(&o.created_at).into());
+        }
+        conn.exec_drop(sql, mysql_async::Params::Positional(params))
+            .await
+            .map_err(|e| format!("Insert into {} failed: {}", ORDERS_TABLE, e))?;
+    }
+
+    let _ = conn.disconnect().await;
+    Ok(())
+}
+
+#[cfg(feature = "mongodb")]
+async fn seed_mongodb(data: &SeedData) -> Result<(), String> {
+    let client = mongodb_client().await?;
+    let db = client.database(MONGODB_DATABASE);
+    let users = db.collection::<Document>(USERS_TABLE);
+    let orders = db.collection::<Document>(ORDERS_TABLE);
+
+    users.drop().await.map_err(|e| format!("Drop {} failed: {}", USERS_TABLE, e))?;
+    orders.drop().await.map_err(|e| format!("Drop {} failed: {}", ORDERS_TABLE, e))?;
+
+    for batch in data.users.chunks(INSERT_BATCH) {
+        let docs: Vec<Document> = batch
+            .iter()
+            .map(|u| doc! {
+                "_id": u.id,
+                "name": u.name.as_str(),
+                "email": u.email.as_str(),
+                "created_at": mongodb::bson::DateTime::from_millis(u.created_at.timestamp_millis()),
+            })
+            .collect();
+        users
+            .insert_many(docs)
+            .await
+            .map_err(|e| format!("Insert into {} failed: {}", USERS_TABLE, e))?;
+    }
+
+    for batch in data.orders.chunks(INSERT_BATCH) {
+        let docs: Vec<Document> = batch
+            .iter()
+            .map(|o| doc! {
+                "_id": o.id,
+                "user_id": o.user_id,
+                "amount_cents": o.amount_cents,
+                "status": o.status.as_str(),
+                "created_at": mongodb::bson::DateTime::from_millis(o.created_at.timestamp_millis()),
+            })
+            .collect();
+        orders
+            .insert_many(docs)
+            .await
+            .map_err(|e| format!("Insert into {} failed: {}", ORDERS_TABLE, e))?;
+    }
+
+    Ok(())
+}
+
+// Stand-ins for backends left out of the build; timed_seed reports them disabled without polling these
+#[cfg(not(feature = "mysql"))]
+async fn seed_mysql(_data: &SeedData) -> Result<(), String> {
+    Err("MySQL support is not compiled in".to_string())
+}
+
+#[cfg(not(feature = "mongodb"))]
+async fn seed_mongodb(_data: &SeedData) -> Result<(), String> {
+    Err("MongoDB support is not compiled in".to_string())
+}
+
+async fn timed_seed<F>(database: &str, rows: usize, fut: F) -> SeedResult
+where
+    F: std::future::Future<Output = Result<(), String>>,
+{
+    if !services::is_enabled(database) {
+        return SeedResult::disabled();
+    }
+    let started = Instant::now();
+    match fut.await {
+        Ok(()) => SeedResult::success(rows, started),
+        Err(e) => SeedResult::failed(e, started),
+    }
+}
+
+pub async fn seed_databases(query: web::Query<SeedQuery>) -> impl Responder {
+    let rows = query.rows.unwrap_or(DEFAULT_ROWS);
+    let seed = query.seed.unwrap_or(DEFAULT_SEED);
+    let max_rows: usize = get_env_or("SEED_MAX_ROWS", "100000").parse().unwrap_or(100_000);
+
+    if rows == 0 || rows > max_rows {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "status": "error",
+            "error": format!("rows must be between 1 and {}", max_rows)
+        }));
+    }
+
+    // Fake data generation is CPU-bound, keep it off the async workers
+    let data = match tokio::task::spawn_blocking(move || generate(rows, seed)).await {
+        Ok(data) => data,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "status": "error",
+                "error": format!("Data generation failed: {}", e)
+            }))
+        }
+    };
+
+    let started = Instant::now();
+    let (postgres, mysql, mongodb) = tokio::join!(
+        timed_seed("postgres", rows, seed_postgres(&data)),
+        timed_seed("mysql", rows, seed_mysql(&data)),
+        timed_seed("mongodb", rows, seed_mongodb(&data)),
+    );
+
+    let results = [&postgres, &mysql, &mongodb];
+    let attempted = results.iter().filter(|r| r.status != "disabled").count();
+    let succeeded = results.iter().filter(|r| r.status == "success").count();
+    let status = if succeeded == 0 {
+        "error"
+    } else if succeeded == attempted {
+        "success"
+    } else {
+        "partial"
+    };
+
+    let body = serde_json::json!({
+        "status": status,
+        "rows": rows,
+        "seed": seed,
+        "elapsed_ms": started.elapsed().as_millis() as u64,
+        "databases": {
+            "postgres": postgres,
+            "mysql": mysql,
+            "mongodb": mongodb
+        }
+    });
+
+    if succeeded == 0 {
+        HttpResponse::InternalServerError().json(body)
+    } else {
+        HttpResponse::Ok().json(body)
+    }
+}
@@ -0,0 +1,315 @@
+// Error reporting to a Sentry-compatible server (Sentry, or GlitchTip running in the stack)
+//
+// With SENTRY_DSN set, panics and requests that end in a 5xx are sent as events to the DSN's
+// store endpoint. Each event carries the request (method, URL, matched route, headers), the
+// backend the route talks to, and this replica's id and release. Panics also carry the location
+// and a backtrace. Secrets never leave the process: Authorization, cookies, admin and signing
+// headers are dropped, and every other header, the query string and the message go through the
+// same scrub() as log lines. SENTRY_SAMPLE_RATE (default 1.0) samples 5xx events; panics are
+// always sent. SENTRY_ENVIRONMENT (default development) tags every event. Events are posted
+// from a background task, and dropped when more than EVENT_QUEUE are waiting.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+use crate::{audit, build_info, get_env_or, instances, panic_guard, redact, resolve, services};
+
+const EVENT_QUEUE: usize = 100;
+const MAX_MESSAGE_LEN: usize = 1024;
+const DROPPED_HEADERS: &[&str] =
+    &["authorization", "proxy-authorization", "cookie", "set-cookie", "x-admin-token", "x-signature"];
+
+static SENT: AtomicU64 = AtomicU64::new(0);
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+static FAILED: AtomicU64 = AtomicU64::new(0);
+static SENDER: OnceLock<mpsc::Sender<Value>> = OnceLock::new();
+
+tokio::task_local! {
+    static REQUEST: RequestContext;
+}
+
+lazy_static! {
+    static ref CONFIG: Option<SentryConfig> = match SentryConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            log::warn!("Error reporting disabled: {}", e);
+            None
+        }
+    };
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dsn {
+    pub store_url: String,
+    pub public_key: String,
+}
+
+// https://<public_key>[:<secret>]@host[:port][/path]/<project_id>
+pub fn parse_dsn(dsn: &str) -> Result<Dsn, String> {
+    let invalid = || format!("Invalid SENTRY_DSN: {}", redact::scrub(dsn));
+    let (scheme, rest) = dsn.split_once("://").ok_or_else(invalid)?;
+    let (credentials, location) = rest.split_once('@').ok_or_else(invalid)?;
+    let public_key = credentials.split(':').next().unwrap_or("");
+    let (path, project) = location.trim_end_matches('/').rsplit_once('/').ok_or_else(invalid)?;
+    if public_key.is_empty() || project.is_empty() || !project.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    Ok(Dsn {
+        store_url: format!("{}://{}/api/{}/store/", scheme, path, project),
+        public_key: public_key.to_string(),
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct SentryConfig {
+    pub dsn: Dsn,
+    pub environment: String,
+    pub sample_rate: f64,
+}
+
+impl SentryConfig {
+    // Ok(None) when SENTRY_DSN is unset
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let dsn = get_env_or("SENTRY_DSN", "");
+        if dsn.is_empty() {
+            return Ok(None);
+        }
+        let mut dsn = parse_dsn(&dsn)?;
+        dsn.store_url = resolve::url(&dsn.store_url);
+        Ok(Some(SentryConfig {
+            dsn,
+            environment: get_env_or("SENTRY_ENVIRONMENT", "development"),
+            sample_rate: get_env_or("SENTRY_SAMPLE_RATE", "1.0").parse::<f64>().unwrap_or(1.0).clamp(0.0, 1.0),
+        }))
+    }
+}
+
+// What an event knows about the request that failed
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestContext {
+    pub method: String,
+    pub url: String,
+    pub route: Option<String>,
+    pub query: String,
+    pub headers: Vec<(String, String)>,
+    pub backend: Option<&'static str>,
+}
+
+impl RequestContext {
+    pub fn from_request(req: &ServiceRequest) -> Self {
+        let connection = req.connection_info();
+        RequestContext {
+            method: req.method().to_string(),
+            url: format!("{}://{}{}", connection.scheme(), connection.host(), req.path()),
+            route: req.match_pattern(),
+            query: redact::scrub(req.query_string()),
+            headers: safe_headers(
+                req.headers().iter().map(|(name, value)| (name.as_str(), value.to_str().unwrap_or(""))),
+            ),
+            backend: services::backend_for_path(req.path()),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let headers: serde_json::Map<String, Value> =
+            self.headers.iter().map(|(name, value)| (name.clone(), json!(value))).collect();
+        json!({
+            "method": self.method,
+            "url": self.url,
+            "query_string": self.query,
+            "headers": headers,
+        })
+    }
+}
+
+// Headers safe to send: credentials dropped, everything else scrubbed
+pub fn safe_headers<'a>(headers: impl Iterator<Item = (&'a str, &'a str)>) -> Vec<(String, String)> {
+    headers
+        .filter(|(name, _)| {
+            let name = name.to_ascii_lowercase();
+            !DROPPED_HEADERS.contains(&name.as_str()) && !audit::is_sensitive_key(&name)
+        })
+        .map(|(name, value)| (name.to_string(), redact::scrub(value)))
+        .collect()
+}
+
+fn truncate(message: &str) -> String {
+    match message.char_indices().nth(MAX_MESSAGE_LEN) {
+        Some((end, _)) => format!("{}...", &message[..end]),
+        None => message.to_string(),
+    }
+}
+
+// A store-API event; `kind` becomes the exception type (Panic, HttpError)
+pub fn build_event(
+    level: &str,
+    kind: &str,
+    message: &str,
+    request: Option<&RequestContext>,
+    extra: Value,
+    environment: &str,
+) -> Value {
+    let build = build_info::build_info();
+    let mut tags = json!({ "instance": instances::self_id() });
+    let mut event = json!({
+        "event_id": uuid::Uuid::new_v4().simple().to_string(),
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "platform": "native",
+        "level": level,
+        "logger": "devstack_reference",
+        "server_name": instances::self_id(),
+        "release": format!("devstack-reference@{}+{}", build.version, build_info::short_commit(&build.git_commit)),
+        "environment": environment,
+        "exception": {"values": [{"type": kind, "value": truncate(&redact::scrub(message))}]},
+        "extra": extra,
+    });
+    if let Some(request) = request {
+        event["request"] = request.to_json();
+        if let Some(route) = &request.route {
+            tags["route"] = json!(route);
+            event["transaction"] = json!(format!("{} {}", request.method, route));
+        }
+        if let Some(backend) = request.backend {
+            tags["backend"] = json!(backend);
+        }
+    }
+    event["tags"] = tags;
+    event
+}
+
+pub fn capture(event: Value) {
+    match SENDER.get() {
+        Some(sender) => {
+            if sender.try_send(event).is_err() {
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        None => {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+// Installs the panic hook and starts the sender; a no-op without SENTRY_DSN. Needs the runtime.
+pub fn init() {
+    let Some(config) = CONFIG.as_ref() else {
+        return;
+    };
+    let (tx, rx) = mpsc::channel(EVENT_QUEUE);
+    if SENDER.set(tx).is_err() {
+        return;
+    }
+    tokio::spawn(send_events(rx, config.clone()));
+
+    let environment = config.environment.clone();
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let location = info.location().map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()));
+        let extra = json!({
+            "location": location,
+            "thread": std::thread::current().name().unwrap_or("unnamed"),
+            "backtrace": std::backtrace::Backtrace::force_capture().to_string(),
+        });
+        let request = REQUEST.try_with(|request| request.clone()).ok();
+        let message = panic_guard::panic_message(info.payload());
+        capture(build_event("fatal", "Panic", &message, request.as_ref(), extra, &environment));
+        previous(info);
+    }));
+    log::info!("Error reporting to {}", config.dsn.store_url);
+}
+
+async fn send_events(mut rx: mpsc::Receiver<Value>, config: SentryConfig) {
+    let client = reqwest::Client::new();
+    let auth = format!(
+        "Sentry sentry_version=7, sentry_client=devstack-reference/{}, sentry_key={}",
+        env!("CARGO_PKG_VERSION"),
+        config.dsn.public_key
+    );
+    while let Some(event) = rx.recv().await {
+        let result = client
+            .post(&config.dsn.store_url)
+            .header("X-Sentry-Auth", &auth)
+            .json(&event)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status());
+        match result {
+            Ok(_) => SENT.fetch_add(1, Ordering::Relaxed),
+            Err(e) => {
+                log::debug!("Error report failed: {}", e);
+                FAILED.fetch_add(1, Ordering::Relaxed)
+            }
+        };
+    }
+}
+
+// The "error" field of a JSON error body, or the body itself
+pub fn error_message(body: &[u8]) -> String {
+    let text = String::from_utf8_lossy(body);
+    serde_json::from_str::<Value>(&text)
+        .ok()
+        .and_then(|json| json["error"].as_str().or(json["detail"].as_str()).map(str::to_string))
+        .unwrap_or_else(|| text.into_owned())
+}
+
+// Reports 5xx responses (and errors that become one) with the request's context, and scopes
+// the context so the panic hook can attach it
+pub async fn capture_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let Some(config) = CONFIG.as_ref() else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    let context = RequestContext::from_request(&req);
+    let res = match REQUEST.scope(context.clone(), next.call(req)).await {
+        Ok(res) => res,
+        Err(e) => {
+            let status = e.as_response_error().status_code();
+            if status.is_server_error() && rand::random::<f64>() < config.sample_rate {
+                let extra = json!({ "status": status.as_u16() });
+                capture(build_event("error", "HttpError", &e.to_string(), Some(&context), extra, &config.environment));
+            }
+            return Err(e);
+        }
+    };
+    if !res.status().is_server_error() || rand::random::<f64>() >= config.sample_rate {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let status = res.status();
+    let (http_req, res) = res.into_parts();
+    let (res, res_body) = res.into_parts();
+    let bytes = body::to_bytes(res_body).await.map_err(|e| {
+        let e: Box<dyn std::error::Error> = e.into();
+        actix_web::error::ErrorInternalServerError(e.to_string())
+    })?;
+    let extra = json!({ "status": status.as_u16() });
+    capture(build_event("error", "HttpError", &error_message(&bytes), Some(&context), extra, &config.environment));
+    Ok(ServiceResponse::new(http_req, res.set_body(BoxBody::new(bytes))))
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct SentryStatus {
+    pub enabled: bool,
+    pub sent: u64,
+    pub dropped: u64,
+    pub failed: u64,
+}
+
+pub fn status() -> SentryStatus {
+    SentryStatus {
+        enabled: CONFIG.is_some(),
+        sent: SENT.load(Ordering::Relaxed),
+        dropped: DROPPED.load(Ordering::Relaxed),
+        failed: FAILED.load(Ordering::Relaxed),
+    }
+}
@@ -0,0 +1,96 @@
+// Backend services known to the API and which of them are enabled
+//
+// ENABLED_SERVICES=postgres,redis,vault limits the app to the listed backends; when it is
+// unset or empty every backend is enabled. Routes of disabled backends answer 404.
+//
+// MySQL, MongoDB and RabbitMQ are also cargo features (`mysql`, `mongodb`, `rabbitmq`, all on by
+// default). A backend left out of the build is never enabled and its routes aren't registered.
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::HttpResponse;
+
+use crate::get_env_or;
+
+pub const BACKENDS: &[&str] = &["postgres", "mysql", "mongodb", "redis", "rabbitmq", "vault"];
+
+// Route prefixes that talk to a single backend; most specific first
+const ROUTE_BACKENDS: &[(&str, &str)] = &[
+    ("/examples/database/postgres", "postgres"),
+    ("/examples/database/mysql", "mysql"),
+    ("/examples/database/mongodb", "mongodb"),
+    ("/examples/cache", "redis"),
+    ("/examples/flags", "redis"),
+    ("/examples/quota", "redis"),
+    ("/examples/geo", "redis"),
+    ("/examples/probabilistic", "redis"),
+    ("/examples/timeseries", "redis"),
+    ("/redis", "redis"),
+    ("/cluster", "redis"),
+    ("/examples/messaging", "rabbitmq"),
+    ("/examples/vault", "vault"),
+];
+
+pub fn backend_for_path(path: &str) -> Option<&'static str> {
+    ROUTE_BACKENDS
+        .iter()
+        .find(|(prefix, _)| path.starts_with(prefix))
+        .map(|(_, backend)| *backend)
+}
+
+pub fn parse_enabled(value: &str) -> Option<Vec<String>> {
+    let services: Vec<String> = value
+        .split(',')
+        .map(|s| s.trim().to_ascii_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect();
+    if services.is_empty() {
+        None
+    } else {
+        Some(services)
+    }
+}
+
+// Whether the backend's client was compiled into this build
+pub fn is_compiled(service: &str) -> bool {
+    match service {
+        "mysql" => cfg!(feature = "mysql"),
+        "mongodb" => cfg!(feature = "mongodb"),
+        "rabbitmq" => cfg!(feature = "rabbitmq"),
+        _ => true,
+    }
+}
+
+pub fn is_enabled(service: &str) -> bool {
+    if !is_compiled(service) {
+        return false;
+    }
+    match parse_enabled(&get_env_or("ENABLED_SERVICES", "")) {
+        Some(enabled) => enabled.iter().any(|s| s == service),
+        None => true,
+    }
+}
+
+pub fn disabled_response(service: &str) -> HttpResponse {
+    let error = if is_compiled(service) {
+        format!("Service '{}' is disabled (not listed in ENABLED_SERVICES)", service)
+    } else {
+        format!("Service '{}' is not compiled into this build (cargo feature '{}')", service, service)
+    };
+    HttpResponse::NotFound().json(serde_json::json!({
+        "status": "disabled",
+        "service": service,
+        "error": error
+    }))
+}
+
+pub async fn enabled_services_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    match backend_for_path(req.path()) {
+        Some(backend) if !is_enabled(backend) => Ok(req.into_response(disabled_response(backend))),
+        _ => Ok(next.call(req).await?.map_into_boxed_body()),
+    }
+}
@@ -0,0 +1,231 @@
+// Cluster slots vs client-side consistent hashing
+//
+// Redis Cluster maps every key to one of 16384 slots (CRC16 of the key, or of its {hash tag})
+// and each master owns slot ranges. Before cluster mode, clients sharded keys themselves with a
+// consistent-hash ring of virtual nodes. The demo places the same keys with both schemes and
+// reports how evenly each spreads them, and how many keys a ring moves when a master is lost.
+
+use std::collections::BTreeMap;
+
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
+use crate::redis_parse::{parse_cluster_nodes, ClusterNode};
+use crate::{redis_connection, redis_node_connection};
+
+pub const CLUSTER_SLOTS: usize = 16384;
+const DEFAULT_KEYS: usize = 1_000;
+const MAX_KEYS: usize = 50_000;
+const DEFAULT_VNODES: usize = 160;
+const MAX_VNODES: usize = 1_000;
+const DEMO_TTL_SECONDS: u64 = 60;
+
+// CRC16-CCITT (XMODEM), the checksum Redis Cluster uses for key slots
+pub fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0u16, |crc, byte| {
+        (0..8).fold(crc ^ ((*byte as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+// Only the part inside the first non-empty {...} is hashed, so related keys can share a slot
+pub fn key_slot(key: &str) -> u16 {
+    let hashed = key
+        .find('{')
+        .and_then(|open| {
+            let rest = &key[open + 1..];
+            rest.find('}').filter(|close| *close > 0).map(|close| &rest[..close])
+        })
+        .unwrap_or(key);
+    crc16(hashed.as_bytes()) % CLUSTER_SLOTS as u16
+}
+
+// Masters ("host:port") and the index of the master owning each slot, from CLUSTER NODES
+pub fn slot_table(nodes_raw: &str) -> (Vec<String>, Vec<Option<usize>>) {
+    let masters: Vec<ClusterNode> = parse_cluster_nodes(nodes_raw)
+        .into_iter()
+        .filter(|node| node.is_master() && !node.is_failed())
+        .collect();
+    let mut owners = vec![None; CLUSTER_SLOTS];
+    for (index, master) in masters.iter().enumerate() {
+        for range in &master.slot_ranges {
+            owners[range.start as usize..=range.end as usize].fill(Some(index));
+        }
+    }
+    (masters.iter().map(ClusterNode::address).collect(), owners)
+}
+
+fn ring_hash(data: &str) -> u64 {
+    let digest = Sha1::digest(data.as_bytes());
+    u64::from_be_bytes(digest[..8].try_into().expect("SHA-1 digest is 20 bytes"))
+}
+
+// Consistent-hash ring: each node is placed at `vnodes` points, a key belongs to the first
+// point clockwise from its hash
+pub struct HashRing {
+    points: Vec<(u64, usize)>,
+}
+
+impl HashRing {
+    pub fn new(nodes: &[String], vnodes: usize) -> Self {
+        let mut points: Vec<(u64, usize)> = nodes
+            .iter()
+            .enumerate()
+            .flat_map(|(index, node)| (0..vnodes).map(move |v| (ring_hash(&format!("{}#{}", node, v)), index)))
+            .collect();
+        points.sort_unstable();
+        HashRing { points }
+    }
+
+    pub fn node_for(&self, key: &str) -> Option<usize> {
+        if self.points.is_empty() {
+            return None;
+        }
+        let hash = ring_hash(key);
+        let position = self.points.partition_point(|(point, _)| *point < hash);
+        Some(self.points[position % self.points.len()].1)
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Distribution {
+    pub counts: BTreeMap<String, usize>,
+    // Largest count relative to a perfectly even split (1.0 = perfectly even)
+    pub max_over_mean: f64,
+    pub stddev_percent: f64,
+}
+
+pub fn distribution(nodes: &[String], assignments: &[usize]) -> Distribution {
+    let mut counts = vec![0usize; nodes.len()];
+    for index in assignments {
+        counts[*index] += 1;
+    }
+    let mean = assignments.len() as f64 / nodes.len().max(1) as f64;
+    let variance = counts.iter().map(|c| (*c as f64 - mean).powi(2)).sum::<f64>() / nodes.len().max(1) as f64;
+    let max = counts.iter().copied().max().unwrap_or(0) as f64;
+    Distribution {
+        counts: nodes.iter().cloned().zip(counts).collect(),
+        max_over_mean: if mean > 0.0 { max / mean } else { 0.0 },
+        stddev_percent: if mean > 0.0 { variance.sqrt() / mean * 100.0 } else { 0.0 },
+    }
+}
+
+// Keys placed on `node`; `assignments` holds one entry per key, None where no node owns it
+pub fn keys_for_node<'a>(keys: &'a [String], assignments: &[Option<usize>], node: usize) -> Vec<&'a str> {
+    keys.iter().zip(assignments).filter(|(_, owner)| **owner == Some(node)).map(|(key, _)| key.as_str()).collect()
+}
+
+#[derive(Deserialize)]
+pub struct ShardingQuery {
+    keys: Option<usize>,
+    vnodes: Option<usize>,
+    // Also SET each key (60s TTL) on the master its slot maps to; off unless asked for, this is a GET
+    write: Option<bool>,
+}
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+pub async fn sharding_demo(query: web::Query<ShardingQuery>) -> impl Responder {
+    let key_count = query.keys.unwrap_or(DEFAULT_KEYS).clamp(1, MAX_KEYS);
+    let vnodes = query.vnodes.unwrap_or(DEFAULT_VNODES).clamp(1, MAX_VNODES);
+    let write = query.write.unwrap_or(false);
+
+    let mut conn = match redis_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+    let nodes_raw: String = match redis::cmd("CLUSTER").arg("NODES").query_async(&mut conn).await {
+        Ok(raw) => raw,
+        Err(e) => {
+            return error_response(
+                actix_web::http::StatusCode::SERVICE_UNAVAILABLE,
+                format!("Cluster topology unavailable (is cluster mode enabled?): {}", e),
+            )
+        }
+    };
+    let (masters, owners) = slot_table(&nodes_raw);
+    if masters.is_empty() {
+        return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, "No healthy masters".to_string());
+    }
+
+    let run = uuid::Uuid::new_v4().simple().to_string();
+    let keys: Vec<String> = (0..key_count).map(|i| format!("sharding-demo:{}:{}", &run[..8], i)).collect();
+
+    // One entry per key, so they stay aligned with `keys` when a slot has no owner
+    let cluster_assignments: Vec<Option<usize>> = keys.iter().map(|key| owners[key_slot(key) as usize]).collect();
+    let unassigned = cluster_assignments.iter().filter(|owner| owner.is_none()).count();
+
+    let ring = HashRing::new(&masters, vnodes);
+    let ring_assignments: Vec<Option<usize>> = keys.iter().map(|key| ring.node_for(key)).collect();
+
+    // Losing a master only moves that master's keys on a ring; naive `hash % n` moves most keys
+    let survivors = &masters[..masters.len() - 1];
+    let smaller_ring = HashRing::new(survivors, vnodes);
+    let ring_moved = keys
+        .iter()
+        .zip(&ring_assignments)
+        .filter(|(key, before)| smaller_ring.node_for(key) != **before)
+        .count();
+    let modulo_moved = keys
+        .iter()
+        .filter(|key| {
+            let hash = ring_hash(key);
+            hash % masters.len() as u64 != hash % survivors.len().max(1) as u64
+        })
+        .count();
+
+    let mut written = 0usize;
+    let mut write_errors = Vec::new();
+    if write {
+        for (index, master) in masters.iter().enumerate() {
+            let owned = keys_for_node(&keys, &cluster_assignments, index);
+            if owned.is_empty() {
+                continue;
+            }
+            let batch = owned.len();
+            let mut pipe = redis::pipe();
+            for key in owned {
+                pipe.cmd("SET").arg(key).arg(index).arg("EX").arg(DEMO_TTL_SECONDS).ignore();
+            }
+            let result = match redis_node_connection(master).await {
+                Ok(mut node) => pipe.query_async::<()>(&mut node).await.map_err(|e| e.to_string()),
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => written += batch,
+                Err(e) => write_errors.push(serde_json::json!({ "node": master, "error": e })),
+            }
+        }
+    }
+
+    let percent = |moved: usize| moved as f64 / key_count as f64 * 100.0;
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": if write_errors.is_empty() { "success" } else { "partial" },
+        "keys": key_count,
+        "key_prefix": format!("sharding-demo:{}:", &run[..8]),
+        "masters": masters,
+        "cluster_slots": {
+            "distribution": distribution(&masters, &cluster_assignments.iter().flatten().copied().collect::<Vec<_>>()),
+            "unassigned_keys": unassigned
+        },
+        "consistent_hash_ring": {
+            "vnodes_per_master": vnodes,
+            "distribution": distribution(&masters, &ring_assignments.iter().flatten().copied().collect::<Vec<_>>())
+        },
+        "if_master_removed": {
+            "removed": masters.last(),
+            "ring_keys_moved_percent": percent(ring_moved),
+            "modulo_keys_moved_percent": percent(modulo_moved)
+        },
+        "written": written,
+        "write_errors": write_errors
+    }))
+}
@@ -0,0 +1,318 @@
+// Latency SLOs and error-budget burn rates, tracked in process
+//
+// SLO_ROUTES lists objectives by matched route pattern, `<route>=<target %>@<threshold ms>`,
+// comma-separated (default `/health/all=99.5@1000`). A request to such a route is good when it
+// answers below 500 within the threshold; the target is the share of requests that have to be
+// good. The middleware that records http_requests_total and http_request_duration_seconds for
+// every route also sorts SLO routes' requests into good, error or slow, counted in per-minute
+// slots covering the last six hours.
+//
+// The burn rate over a window is its bad share divided by the share the target allows (1 - target):
+// 1 spends the error budget exactly over the SLO period, 14.4 spends 2% of a 30-day budget in an
+// hour. slo_error_budget_burn_rate{route,window} is exported for 5m, 30m, 1h and 6h, and GET /slo
+// applies the usual multiwindow alerts: page when both 1h and 5m burn at 14.4 or more, ticket when
+// both 6h and 30m burn at 6 or more. Budget remaining counts since the process started, which
+// is as long as an in-process tracker can remember; Prometheus can compute longer windows from
+// slo_requests_total{route,result}.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{HttpResponse, Responder};
+use lazy_static::lazy_static;
+use serde::Serialize;
+
+use crate::{
+    get_env_or, HTTP_REQUESTS_TOTAL, HTTP_REQUEST_DURATION, SLO_BUDGET_REMAINING, SLO_BURN_RATE, SLO_REQUESTS_TOTAL,
+};
+
+const DEFAULT_ROUTES: &str = "/health/all=99.5@1000";
+const SLOT_SECONDS: u64 = 60;
+// Six hours of minutes, the longest window
+const SLOTS: usize = 360;
+const REFRESH_INTERVAL: Duration = Duration::from_secs(15);
+
+// (label, length in minutes)
+pub const WINDOWS: &[(&str, u64)] = &[("5m", 5), ("30m", 30), ("1h", 60), ("6h", 360)];
+// (alert, long window, short window, burn rate both must reach)
+const ALERTS: &[(&str, &str, &str, f64)] = &[("page", "1h", "5m", 14.4), ("ticket", "6h", "30m", 6.0)];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Objective {
+    pub route: String,
+    // Fraction of requests that have to be good, e.g. 0.995
+    pub target: f64,
+    pub threshold_ms: u64,
+}
+
+pub fn parse_objectives(value: &str) -> Result<Vec<Objective>, String> {
+    let mut objectives: Vec<Objective> = Vec::new();
+    for entry in value.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let invalid = || format!("Invalid SLO {:?}: expected <route>=<target %>@<threshold ms>", entry);
+        let (route, spec) = entry.rsplit_once('=').ok_or_else(invalid)?;
+        let (target, threshold) = spec.split_once('@').ok_or_else(invalid)?;
+        let target: f64 = target.trim().trim_end_matches('%').parse().map_err(|_| invalid())?;
+        let threshold_ms: u64 = threshold.trim().trim_end_matches("ms").parse().map_err(|_| invalid())?;
+        let route = route.trim();
+        if !route.starts_with('/') {
+            return Err(format!("Invalid SLO {:?}: the route must be a path pattern", entry));
+        }
+        if !(target > 0.0 && target < 100.0) || threshold_ms == 0 {
+            return Err(format!("Invalid SLO {:?}: target must be between 0 and 100, threshold positive", entry));
+        }
+        if objectives.iter().any(|objective| objective.route == route) {
+            return Err(format!("Duplicate SLO for {}", route));
+        }
+        objectives.push(Objective { route: route.to_string(), target: target / 100.0, threshold_ms });
+    }
+    Ok(objectives)
+}
+
+pub fn objectives_from_env() -> Result<Vec<Objective>, String> {
+    parse_objectives(&get_env_or("SLO_ROUTES", DEFAULT_ROUTES))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Good,
+    Error,
+    Slow,
+}
+
+impl Outcome {
+    pub fn classify(status: u16, elapsed: Duration, threshold_ms: u64) -> Self {
+        if status >= 500 {
+            Outcome::Error
+        } else if elapsed > Duration::from_millis(threshold_ms) {
+            Outcome::Slow
+        } else {
+            Outcome::Good
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Outcome::Good => "good",
+            Outcome::Error => "error",
+            Outcome::Slow => "slow",
+        }
+    }
+}
+
+// ============================================================================
+// Tracker
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Slot {
+    minute: u64,
+    total: u64,
+    bad: u64,
+}
+
+// Per-minute (total, bad) counts in a ring, plus totals since start
+#[derive(Debug, Clone)]
+pub struct Tracker {
+    slots: Vec<Slot>,
+    total: u64,
+    bad: u64,
+}
+
+impl Default for Tracker {
+    fn default() -> Self {
+        Tracker { slots: vec![Slot::default(); SLOTS], total: 0, bad: 0 }
+    }
+}
+
+impl Tracker {
+    pub fn record(&mut self, minute: u64, bad: bool) {
+        let slot = &mut self.slots[(minute % SLOTS as u64) as usize];
+        if slot.minute != minute {
+            *slot = Slot { minute, total: 0, bad: 0 };
+        }
+        slot.total += 1;
+        self.total += 1;
+        if bad {
+            slot.bad += 1;
+            self.bad += 1;
+        }
+    }
+
+    // (total, bad) over the last `minutes` minutes, the current one included
+    pub fn counts(&self, now_minute: u64, minutes: u64) -> (u64, u64) {
+        self.slots
+            .iter()
+            .filter(|slot| slot.total > 0 && slot.minute <= now_minute && now_minute - slot.minute < minutes)
+            .fold((0, 0), |(total, bad), slot| (total + slot.total, bad + slot.bad))
+    }
+
+    pub fn since_start(&self) -> (u64, u64) {
+        (self.total, self.bad)
+    }
+}
+
+// Bad share over the share the target allows; 0 without traffic
+pub fn burn_rate(total: u64, bad: u64, target: f64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        (bad as f64 / total as f64) / (1.0 - target)
+    }
+}
+
+// Fraction of the error budget left; negative once it is overspent
+pub fn budget_remaining(total: u64, bad: u64, target: f64) -> f64 {
+    1.0 - burn_rate(total, bad, target)
+}
+
+lazy_static! {
+    static ref OBJECTIVES: Vec<Objective> = objectives_from_env().unwrap_or_else(|e| {
+        log::warn!("{}; SLO tracking is off", e);
+        Vec::new()
+    });
+    static ref TRACKERS: Mutex<HashMap<String, Tracker>> = Mutex::new(HashMap::new());
+}
+
+fn current_minute() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / SLOT_SECONDS
+}
+
+#[derive(Debug, Serialize)]
+pub struct WindowSummary {
+    pub window: &'static str,
+    pub requests: u64,
+    pub bad: u64,
+    // Good share, None without traffic
+    pub sli: Option<f64>,
+    pub burn_rate: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    #[serde(flatten)]
+    pub objective: Objective,
+    pub windows: Vec<WindowSummary>,
+    pub requests_since_start: u64,
+    pub bad_since_start: u64,
+    pub budget_remaining: f64,
+    // "page", "ticket" or "ok"
+    pub alert: &'static str,
+}
+
+pub fn summarize(objective: &Objective, tracker: &Tracker, now_minute: u64) -> Summary {
+    let windows: Vec<WindowSummary> = WINDOWS
+        .iter()
+        .map(|(window, minutes)| {
+            let (requests, bad) = tracker.counts(now_minute, *minutes);
+            WindowSummary {
+                window,
+                requests,
+                bad,
+                sli: (requests > 0).then(|| 1.0 - bad as f64 / requests as f64),
+                burn_rate: burn_rate(requests, bad, objective.target),
+            }
+        })
+        .collect();
+    let burn = |label: &str| windows.iter().find(|w| w.window == label).map_or(0.0, |w| w.burn_rate);
+    let alert = ALERTS
+        .iter()
+        .find(|(_, long, short, threshold)| burn(long) >= *threshold && burn(short) >= *threshold)
+        .map_or("ok", |(alert, ..)| *alert);
+    let (total, bad) = tracker.since_start();
+    Summary {
+        objective: objective.clone(),
+        windows,
+        requests_since_start: total,
+        bad_since_start: bad,
+        budget_remaining: budget_remaining(total, bad, objective.target),
+        alert,
+    }
+}
+
+// Summaries for every objective, refreshing the exported gauges on the way
+pub fn summaries() -> Vec<Summary> {
+    let now = current_minute();
+    let trackers = TRACKERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let empty = Tracker::default();
+    OBJECTIVES
+        .iter()
+        .map(|objective| {
+            let summary = summarize(objective, trackers.get(&objective.route).unwrap_or(&empty), now);
+            for window in &summary.windows {
+                SLO_BURN_RATE.with_label_values(&[objective.route.as_str(), window.window]).set(window.burn_rate);
+            }
+            SLO_BUDGET_REMAINING.with_label_values(&[objective.route.as_str()]).set(summary.budget_remaining);
+            summary
+        })
+        .collect()
+}
+
+// Burn rates decay as minutes pass without traffic, so the gauges are refreshed on a timer too
+pub fn spawn_slo_refresher() {
+    if OBJECTIVES.is_empty() {
+        return;
+    }
+    tokio::spawn(async {
+        let mut ticker = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            ticker.tick().await;
+            summaries();
+        }
+    });
+}
+
+// ============================================================================
+// Middleware and handler
+// ============================================================================
+
+fn record(route: &str, method: &str, status: u16, elapsed: Duration) {
+    let status_label = status.to_string();
+    HTTP_REQUESTS_TOTAL.with_label_values(&[method, route, status_label.as_str()]).inc();
+    HTTP_REQUEST_DURATION.with_label_values(&[method, route]).observe(elapsed.as_secs_f64());
+
+    let Some(objective) = OBJECTIVES.iter().find(|objective| objective.route == route) else {
+        return;
+    };
+    let outcome = Outcome::classify(status, elapsed, objective.threshold_ms);
+    SLO_REQUESTS_TOTAL.with_label_values(&[route, outcome.label()]).inc();
+    TRACKERS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .entry(route.to_string())
+        .or_default()
+        .record(current_minute(), outcome != Outcome::Good);
+}
+
+// Records every request in the HTTP metrics, and SLO routes' requests in their trackers; the
+// duration runs until the response head, not until a streamed body finishes
+pub async fn metrics_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let route = req.match_pattern().unwrap_or_else(|| "unmatched".to_string());
+    let method = req.method().to_string();
+    let started = Instant::now();
+    let result = next.call(req).await;
+    let status = match &result {
+        Ok(res) => res.status().as_u16(),
+        Err(e) => e.as_response_error().status_code().as_u16(),
+    };
+    record(&route, &method, status, started.elapsed());
+    Ok(result?.map_into_boxed_body())
+}
+
+// GET /slo
+pub async fn slo_summary() -> impl Responder {
+    let summaries = summaries();
+    let alerting: Vec<&str> =
+        summaries.iter().filter(|s| s.alert != "ok").map(|s| s.objective.route.as_str()).collect();
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": if alerting.is_empty() { "ok" } else { "burning" },
+        "alerting": alerting,
+        "objectives": summaries
+    }))
+}
@@ -0,0 +1,298 @@
+// Read/write splitting for the SQL pools
+//
+// Each backend may list read replicas in <DB>_REPLICA_HOSTS (comma-separated host[:port],
+// sharing the primary's credentials). A SqlRouter, one per request, sends statements it can
+// prove read-only to a replica (round robin) and everything else to the primary. After the
+// first write in a request the rest of that request stays on the primary, so it reads its own
+// writes despite replication lag. <DB>_READ_SPLITTING=false sends everything to the primary;
+// <DB>_STICKY_PRIMARY=false drops the stickiness for callers that can live with lag.
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, Responder};
+use lazy_static::lazy_static;
+use serde::Serialize;
+
+#[cfg(feature = "mysql")]
+use crate::pool::MysqlManager;
+use crate::pool::{self, Manager, Pool, PoolConfig, Pooled, PostgresManager};
+use crate::{get_env_or, SQL_ROUTE_TOTAL};
+
+// Statements that only read, judged by their first word
+const READ_STATEMENTS: &[&str] = &["SELECT", "WITH", "SHOW", "VALUES", "TABLE", "EXPLAIN", "DESCRIBE", "DESC"];
+// Words that turn a read-looking statement into a write or a lock: data-modifying CTEs,
+// SELECT ... INTO, FOR UPDATE / FOR SHARE, EXPLAIN ANALYZE (which runs the statement) and
+// functions with side effects. A false positive only costs a trip to the primary.
+const WRITE_WORDS: &[&str] = &[
+    "INSERT",
+    "UPDATE",
+    "DELETE",
+    "MERGE",
+    "INTO",
+    "SHARE",
+    "LOCK",
+    "ANALYZE",
+    "NEXTVAL",
+    "SETVAL",
+    "PG_ADVISORY_LOCK",
+    "PG_ADVISORY_XACT_LOCK",
+    "PG_NOTIFY",
+    "GET_LOCK",
+    "RELEASE_LOCK",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Route {
+    Primary,
+    // Index into the backend's replica list
+    Replica(usize),
+}
+
+impl Route {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Route::Primary => "primary",
+            Route::Replica(_) => "replica",
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct RoutingConfig {
+    pub replicas: Vec<String>,
+    pub read_splitting: bool,
+    pub sticky_primary: bool,
+}
+
+impl RoutingConfig {
+    // `prefix` is the backend's env prefix: POSTGRES or MYSQL
+    pub fn from_env(prefix: &str) -> Self {
+        RoutingConfig {
+            replicas: parse_replica_hosts(&get_env_or(&format!("{}_REPLICA_HOSTS", prefix), "")),
+            read_splitting: get_env_or(&format!("{}_READ_SPLITTING", prefix), "true").parse().unwrap_or(true),
+            sticky_primary: get_env_or(&format!("{}_STICKY_PRIMARY", prefix), "true").parse().unwrap_or(true),
+        }
+    }
+}
+
+pub fn parse_replica_hosts(value: &str) -> Vec<String> {
+    value.split(',').map(str::trim).filter(|host| !host.is_empty()).map(str::to_string).collect()
+}
+
+// Upper-cased words of a statement, skipping string literals, quoted identifiers and comments
+pub fn sql_words(sql: &str) -> Vec<String> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_ascii_alphanumeric() || c == '_' {
+            word.push(c.to_ascii_uppercase());
+            i += 1;
+            continue;
+        }
+        if !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        match c {
+            '\'' | '"' | '`' => {
+                i += 1;
+                while i < chars.len() && chars[i] != c {
+                    i += 1;
+                }
+            }
+            '-' if chars.get(i + 1) == Some(&'-') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i + 1 < chars.len() && !(chars[i] == '*' && chars[i + 1] == '/') {
+                    i += 1;
+                }
+                i += 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+// Conservative: anything not provably read-only goes to the primary
+pub fn is_read_only(sql: &str) -> bool {
+    let words = sql_words(sql);
+    match words.first() {
+        Some(first) if READ_STATEMENTS.contains(&first.as_str()) => {
+            !words.iter().any(|word| WRITE_WORDS.contains(&word.as_str()))
+        }
+        _ => false,
+    }
+}
+
+// `wrote` is whether this request already wrote to the backend; `counter` picks the replica
+pub fn choose(config: &RoutingConfig, sql: &str, wrote: bool, counter: usize) -> Route {
+    if !config.read_splitting || config.replicas.is_empty() || (wrote && config.sticky_primary) || !is_read_only(sql) {
+        Route::Primary
+    } else {
+        Route::Replica(counter % config.replicas.len())
+    }
+}
+
+// Picks the route for `sql` and remembers a write, so the rest of the request stays on the primary
+fn route(config: &RoutingConfig, sql: &str, wrote: &Cell<bool>, counter: usize) -> Route {
+    let route = choose(config, sql, wrote.get(), counter);
+    if !is_read_only(sql) {
+        wrote.set(true);
+    }
+    route
+}
+
+fn replica_pools<M: Manager>(config: &RoutingConfig, backend: &'static str, manager: fn(String) -> M) -> Vec<Pool<M>> {
+    config
+        .replicas
+        .iter()
+        .map(|address| Pool::new(manager(address.clone()), backend, PoolConfig::from_env()))
+        .collect()
+}
+
+lazy_static! {
+    static ref POSTGRES_ROUTING: RoutingConfig = RoutingConfig::from_env("POSTGRES");
+    static ref POSTGRES_REPLICAS: Vec<Pool<PostgresManager>> =
+        replica_pools(&POSTGRES_ROUTING, "postgres-replica", |address| PostgresManager { address: Some(address) });
+}
+
+#[cfg(feature = "mysql")]
+lazy_static! {
+    static ref MYSQL_ROUTING: RoutingConfig = RoutingConfig::from_env("MYSQL");
+    static ref MYSQL_REPLICAS: Vec<Pool<MysqlManager>> =
+        replica_pools(&MYSQL_ROUTING, "mysql-replica", |address| MysqlManager { address: Some(address) });
+}
+
+static POSTGRES_NEXT_REPLICA: AtomicUsize = AtomicUsize::new(0);
+#[cfg(feature = "mysql")]
+static MYSQL_NEXT_REPLICA: AtomicUsize = AtomicUsize::new(0);
+
+struct Backend<M: Manager + 'static> {
+    name: &'static str,
+    config: &'static RoutingConfig,
+    primary: &'static Pool<M>,
+    replicas: &'static [Pool<M>],
+    next_replica: &'static AtomicUsize,
+}
+
+impl<M: Manager> Backend<M> {
+    // An unreachable replica falls back to the primary rather than failing the read
+    async fn checkout(&self, sql: &str, wrote: &Cell<bool>) -> Result<(Pooled<M>, Route), String> {
+        let route = route(self.config, sql, wrote, self.next_replica.fetch_add(1, Ordering::Relaxed));
+        if let Route::Replica(index) = route {
+            match self.replicas[index].get().await {
+                Ok(conn) => {
+                    SQL_ROUTE_TOTAL.with_label_values(&[self.name, "replica"]).inc();
+                    return Ok((conn, route));
+                }
+                Err(e) => {
+                    SQL_ROUTE_TOTAL.with_label_values(&[self.name, "fallback"]).inc();
+                    log::warn!(
+                        "{} replica {} unavailable, reading from the primary: {}",
+                        self.name,
+                        self.config.replicas[index],
+                        e
+                    );
+                }
+            }
+        } else {
+            SQL_ROUTE_TOTAL.with_label_values(&[self.name, "primary"]).inc();
+        }
+        Ok((self.primary.get().await?, Route::Primary))
+    }
+
+    fn info(&self) -> serde_json::Value {
+        let replicas: Vec<serde_json::Value> = self
+            .config
+            .replicas
+            .iter()
+            .zip(self.replicas)
+            .map(|(address, pool)| serde_json::json!({ "address": address, "pool": pool.info() }))
+            .collect();
+        serde_json::json!({ "config": self.config, "replicas": replicas })
+    }
+}
+
+fn postgres_backend() -> Backend<PostgresManager> {
+    Backend {
+        name: "postgres",
+        config: &POSTGRES_ROUTING,
+        primary: &pool::POSTGRES_POOL,
+        replicas: &POSTGRES_REPLICAS,
+        next_replica: &POSTGRES_NEXT_REPLICA,
+    }
+}
+
+#[cfg(feature = "mysql")]
+fn mysql_backend() -> Backend<MysqlManager> {
+    Backend {
+        name: "mysql",
+        config: &MYSQL_ROUTING,
+        primary: &pool::MYSQL_POOL,
+        replicas: &MYSQL_REPLICAS,
+        next_replica: &MYSQL_NEXT_REPLICA,
+    }
+}
+
+#[derive(Default)]
+struct RequestWrites {
+    postgres: Cell<bool>,
+    #[cfg(feature = "mysql")]
+    mysql: Cell<bool>,
+}
+
+// Per-request routing state; clones share it
+#[derive(Clone, Default)]
+pub struct SqlRouter {
+    writes: Rc<RequestWrites>,
+}
+
+impl SqlRouter {
+    // The router for this request, created on first use and stored in the request extensions
+    // so every handler and helper working on the request sees the same write history
+    pub fn for_request(req: &HttpRequest) -> Self {
+        if let Some(router) = req.extensions().get::<SqlRouter>() {
+            return router.clone();
+        }
+        let router = SqlRouter::default();
+        req.extensions_mut().insert(router.clone());
+        router
+    }
+
+    pub async fn postgres(&self, sql: &str) -> Result<(Pooled<PostgresManager>, Route), String> {
+        postgres_backend().checkout(sql, &self.writes.postgres).await
+    }
+
+    // The route postgres() would take for `sql` under `config`, recorded the same way
+    pub fn route_postgres(&self, config: &RoutingConfig, sql: &str, counter: usize) -> Route {
+        route(config, sql, &self.writes.postgres, counter)
+    }
+
+    #[cfg(feature = "mysql")]
+    pub async fn mysql(&self, sql: &str) -> Result<(Pooled<MysqlManager>, Route), String> {
+        mysql_backend().checkout(sql, &self.writes.mysql).await
+    }
+}
+
+pub async fn sql_routing() -> impl Responder {
+    #[allow(unused_mut)]
+    let mut routing = serde_json::json!({ "postgres": postgres_backend().info() });
+    #[cfg(feature = "mysql")]
+    {
+        routing["mysql"] = mysql_backend().info();
+    }
+    HttpResponse::Ok().json(routing)
+}
@@ -0,0 +1,146 @@
+// Statement timing for the SQL examples: latency histogram, slow-query log, and a recent slow-query buffer
+
+use actix_web::{HttpResponse, Responder};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::{get_env_or, SQL_QUERY_DURATION};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SlowQuery {
+    pub database: String,
+    pub query_name: String,
+    pub sql: String,
+    pub param_count: usize,
+    pub duration_ms: f64,
+    pub recorded_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+lazy_static! {
+    static ref SLOW_QUERIES: Mutex<VecDeque<SlowQuery>> = Mutex::new(VecDeque::new());
+}
+
+fn slow_threshold_ms() -> f64 {
+    get_env_or("SQL_SLOW_QUERY_MS", "100").parse().unwrap_or(100.0)
+}
+
+fn buffer_capacity() -> usize {
+    get_env_or("SQL_SLOW_QUERY_BUFFER", "100").parse().unwrap_or(100)
+}
+
+// Replace string and numeric literals so logged SQL never carries data values
+pub fn redact_sql(sql: &str) -> String {
+    let mut out = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    let mut prev: Option<char> = None;
+
+    while let Some(c) = chars.next() {
+        if c == '\'' {
+            // Skip to the closing quote, honoring '' escapes
+            loop {
+                match chars.next() {
+                    Some('\'') if chars.peek() == Some(&'\'') => {
+                        chars.next();
+                    }
+                    Some('\'') | None => break,
+                    Some(_) => {}
+                }
+            }
+            out.push_str("'?'");
+            prev = Some('\'');
+        } else if c.is_ascii_digit() && !prev.is_some_and(|p| p.is_alphanumeric() || p == '_' || p == '$') {
+            while chars.peek().is_some_and(|n| n.is_ascii_digit() || *n == '.') {
+                chars.next();
+            }
+            out.push('?');
+            prev = Some('?');
+        } else {
+            out.push(c);
+            prev = Some(c);
+        }
+    }
+    out
+}
+
+fn record_slow_query(entry: SlowQuery) {
+    log::warn!(
+        "Slow query on {} ({}): {:.2}ms params={} sql={}",
+        entry.database,
+        entry.query_name,
+        entry.duration_ms,
+        entry.param_count,
+        entry.sql
+    );
+
+    let capacity = buffer_capacity();
+    let mut buffer = SLOW_QUERIES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    buffer.push_back(entry);
+    while buffer.len() > capacity {
+        buffer.pop_front();
+    }
+}
+
+// Time a query future, feed the histogram, and log it when it exceeds SQL_SLOW_QUERY_MS.
+// Bind parameters are never logged, only their count.
+pub async fn timed_query<T, E, F>(database: &str, query_name: &str, sql: &str, param_count: usize, query: F) -> Result<T, E>
+where
+    E: std::fmt::Display,
+    F: Future<Output = Result<T, E>>,
+{
+    timed_query_with_threshold(slow_threshold_ms(), database, query_name, sql, param_count, query).await
+}
+
+// timed_query with the slow-query threshold given instead of read from SQL_SLOW_QUERY_MS
+pub async fn timed_query_with_threshold<T, E, F>(
+    threshold_ms: f64,
+    database: &str,
+    query_name: &str,
+    sql: &str,
+    param_count: usize,
+    query: F,
+) -> Result<T, E>
+where
+    E: std::fmt::Display,
+    F: Future<Output = Result<T, E>>,
+{
+    let started = Instant::now();
+    let result = query.await;
+    let elapsed = started.elapsed();
+
+    SQL_QUERY_DURATION
+        .with_label_values(&[database, query_name])
+        .observe(elapsed.as_secs_f64());
+
+    let duration_ms = elapsed.as_secs_f64() * 1000.0;
+    if duration_ms >= threshold_ms {
+        record_slow_query(SlowQuery {
+            database: database.to_string(),
+            query_name: query_name.to_string(),
+            sql: redact_sql(sql),
+            param_count,
+            duration_ms: (duration_ms * 100.0).round() / 100.0,
+            recorded_at: chrono::Utc::now().to_rfc3339(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        });
+    }
+
+    result
+}
+
+pub async fn slow_queries() -> impl Responder {
+    let buffer = SLOW_QUERIES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let queries: Vec<SlowQuery> = buffer.iter().rev().cloned().collect();
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "threshold_ms": slow_threshold_ms(),
+        "capacity": buffer_capacity(),
+        "count": queries.len(),
+        "queries": queries
+    }))
+}
@@ -0,0 +1,304 @@
+// LRU prepared-statement cache for the SQL backends
+//
+// Statements belong to the connection that prepared them, so each backend keeps one dedicated
+// connection alongside its cache. The /prepared endpoints run a parameterized query repeatedly,
+// either through the cache or re-preparing every time, to show what preparing costs.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use actix_web::{web, HttpResponse, Responder};
+use lazy_static::lazy_static;
+#[cfg(feature = "mysql")]
+use mysql_async::prelude::Queryable;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{get_env_or, postgres_client, PREPARED_STATEMENT_CACHE_TOTAL};
+#[cfg(feature = "mysql")]
+use crate::mysql_opts;
+
+const DEFAULT_ITERATIONS: usize = 100;
+const MAX_ITERATIONS: usize = 10_000;
+const DEFAULT_DISTINCT: usize = 5;
+const MAX_DISTINCT: usize = 1_000;
+
+#[derive(Serialize, Clone, Default, Debug, PartialEq)]
+pub struct CacheStats {
+    pub capacity: usize,
+    pub size: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+// Least-recently-used map from SQL text to a prepared statement
+pub struct StatementCache<S> {
+    capacity: usize,
+    entries: HashMap<String, (S, u64)>,
+    tick: u64,
+    stats: CacheStats,
+}
+
+impl<S: Clone> StatementCache<S> {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        StatementCache {
+            capacity,
+            entries: HashMap::new(),
+            tick: 0,
+            stats: CacheStats { capacity, ..CacheStats::default() },
+        }
+    }
+
+    pub fn get(&mut self, sql: &str) -> Option<S> {
+        self.tick += 1;
+        match self.entries.get_mut(sql) {
+            Some((statement, last_used)) => {
+                *last_used = self.tick;
+                self.stats.hits += 1;
+                Some(statement.clone())
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    // Returns the statement evicted to make room, if any
+    pub fn insert(&mut self, sql: &str, statement: S) -> Option<S> {
+        self.tick += 1;
+        let mut evicted = None;
+        if !self.entries.contains_key(sql) && self.entries.len() >= self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                evicted = self.entries.remove(&oldest).map(|(statement, _)| statement);
+                self.stats.evictions += 1;
+            }
+        }
+        self.entries.insert(sql.to_string(), (statement, self.tick));
+        evicted
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats { size: self.entries.len(), ..self.stats.clone() }
+    }
+}
+
+fn cache_capacity() -> usize {
+    get_env_or("PREPARED_CACHE_CAPACITY", "100").parse().unwrap_or(100)
+}
+
+fn record(backend: &str, result: &str) {
+    PREPARED_STATEMENT_CACHE_TOTAL.with_label_values(&[backend, result]).inc();
+}
+
+struct PostgresCached {
+    client: tokio_postgres::Client,
+    cache: StatementCache<tokio_postgres::Statement>,
+}
+
+#[cfg(feature = "mysql")]
+struct MysqlCached {
+    conn: mysql_async::Conn,
+    cache: StatementCache<mysql_async::Statement>,
+}
+
+lazy_static! {
+    static ref POSTGRES: Mutex<Option<PostgresCached>> = Mutex::new(None);
+}
+
+#[cfg(feature = "mysql")]
+lazy_static! {
+    static ref MYSQL: Mutex<Option<MysqlCached>> = Mutex::new(None);
+}
+
+#[derive(Deserialize)]
+pub struct PreparedQuery {
+    // "cached" (default) reuses statements from the LRU; "uncached" prepares on every call
+    mode: Option<String>,
+    iterations: Option<usize>,
+    // Number of distinct SQL texts cycled through; above PREPARED_CACHE_CAPACITY causes evictions
+    distinct: Option<usize>,
+}
+
+struct BenchParams {
+    cached: bool,
+    iterations: usize,
+    distinct: usize,
+}
+
+impl PreparedQuery {
+    fn resolve(&self) -> Result<BenchParams, String> {
+        let cached = match self.mode.as_deref().unwrap_or("cached") {
+            "cached" => true,
+            "uncached" => false,
+            other => return Err(format!("Unsupported mode '{}'. Must be one of: cached, uncached", other)),
+        };
+        let iterations = self.iterations.unwrap_or(DEFAULT_ITERATIONS);
+        if iterations == 0 || iterations > MAX_ITERATIONS {
+            return Err(format!("iterations must be between 1 and {}", MAX_ITERATIONS));
+        }
+        let distinct = self.distinct.unwrap_or(DEFAULT_DISTINCT);
+        if distinct == 0 || distinct > MAX_DISTINCT {
+            return Err(format!("distinct must be between 1 and {}", MAX_DISTINCT));
+        }
+        Ok(BenchParams { cached, iterations, distinct })
+    }
+}
+
+#[derive(Serialize)]
+struct BenchResult {
+    backend: &'static str,
+    mode: &'static str,
+    iterations: usize,
+    distinct: usize,
+    total_ms: f64,
+    avg_us: f64,
+    prepare_us: f64,
+    cache: CacheStats,
+}
+
+impl BenchResult {
+    fn new(backend: &'static str, params: &BenchParams, total: Duration, prepare: Duration, cache: CacheStats) -> Self {
+        BenchResult {
+            backend,
+            mode: if params.cached { "cached" } else { "uncached" },
+            iterations: params.iterations,
+            distinct: params.distinct,
+            total_ms: total.as_secs_f64() * 1000.0,
+            avg_us: total.as_secs_f64() * 1_000_000.0 / params.iterations as f64,
+            prepare_us: prepare.as_secs_f64() * 1_000_000.0 / params.iterations as f64,
+            cache,
+        }
+    }
+}
+
+async fn postgres_bench(params: &BenchParams) -> Result<BenchResult, String> {
+    let mut guard = POSTGRES.lock().await;
+    if guard.as_ref().is_none_or(|cached| cached.client.is_closed()) {
+        let client = postgres_client().await?;
+        *guard = Some(PostgresCached { client, cache: StatementCache::new(cache_capacity()) });
+    }
+    let state = guard.as_mut().ok_or("Connection unavailable")?;
+
+    let (mut total, mut prepare) = (Duration::ZERO, Duration::ZERO);
+    for i in 0..params.iterations {
+        let sql = format!("SELECT $1::int + {} AS value", i % params.distinct);
+        let started = Instant::now();
+        let statement = match params.cached.then(|| state.cache.get(&sql)).flatten() {
+            Some(statement) => {
+                record("postgres", "hit");
+                statement
+            }
+            None => {
+                let statement = state.client.prepare(&sql).await.map_err(|e| format!("Prepare failed: {}", e))?;
+                if params.cached {
+                    record("postgres", "miss");
+                    if state.cache.insert(&sql, statement.clone()).is_some() {
+                        record("postgres", "eviction");
+                    }
+                }
+                statement
+            }
+        };
+        prepare += started.elapsed();
+        state
+            .client
+            .query_one(&statement, &[&(i as i32)])
+            .await
+            .map_err(|e| format!("Query failed: {}", e))?;
+        total += started.elapsed();
+    }
+
+    Ok(BenchResult::new("postgres", params, total, prepare, state.cache.stats()))
+}
+
+#[cfg(feature = "mysql")]
+async fn mysql_bench(params: &BenchParams) -> Result<BenchResult, String> {
+    let mut guard = MYSQL.lock().await;
+    if guard.is_none() {
+        // The driver's own statement cache is disabled so only the LRU here caches statements
+        let opts = mysql_opts().await?.stmt_cache_size(0);
+        let conn = mysql_async::Conn::new(opts).await.map_err(|e| format!("Connection failed: {}", e))?;
+        *guard = Some(MysqlCached { conn, cache: StatementCache::new(cache_capacity()) });
+    }
+    let state = guard.as_mut().ok_or("Connection unavailable")?;
+
+    let result = mysql_bench_on(state, params).await;
+    if result.is_err() {
+        // Drop the connection so the next request reconnects instead of reusing a broken one
+        *guard = None;
+    }
+    result
+}
+
+#[cfg(feature = "mysql")]
+async fn mysql_bench_on(state: &mut MysqlCached, params: &BenchParams) -> Result<BenchResult, String> {
+    let (mut total, mut prepare) = (Duration::ZERO, Duration::ZERO);
+    for i in 0..params.iterations {
+        let sql = format!("SELECT CAST(? AS SIGNED) + {} AS value", i % params.distinct);
+        let started = Instant::now();
+        let cached = params.cached.then(|| state.cache.get(&sql)).flatten();
+        let statement = match cached {
+            Some(statement) => {
+                record("mysql", "hit");
+                statement
+            }
+            None => {
+                let statement = state.conn.prep(&sql).await.map_err(|e| format!("Prepare failed: {}", e))?;
+                if params.cached {
+                    record("mysql", "miss");
+                    if let Some(evicted) = state.cache.insert(&sql, statement.clone()) {
+                        record("mysql", "eviction");
+                        // MySQL keeps statements until they are closed explicitly
+                        state.conn.close(evicted).await.map_err(|e| format!("Close failed: {}", e))?;
+                    }
+                }
+                statement
+            }
+        };
+        prepare += started.elapsed();
+        let _: Option<i64> = state
+            .conn
+            .exec_first(&statement, (i as i64,))
+            .await
+            .map_err(|e| format!("Query failed: {}", e))?;
+        if !params.cached {
+            state.conn.close(statement).await.map_err(|e| format!("Close failed: {}", e))?;
+        }
+        total += started.elapsed();
+    }
+
+    Ok(BenchResult::new("mysql", params, total, prepare, state.cache.stats()))
+}
+
+fn bench_response(result: Result<BenchResult, String>) -> HttpResponse {
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(e) if e.starts_with("Connection failed") || e.contains("Vault") => {
+            HttpResponse::ServiceUnavailable().json(serde_json::json!({ "status": "error", "error": e }))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({ "status": "error", "error": e })),
+    }
+}
+
+pub async fn postgres_prepared(query: web::Query<PreparedQuery>) -> impl Responder {
+    match query.resolve() {
+        Ok(params) => bench_response(postgres_bench(&params).await),
+        Err(e) => HttpResponse::BadRequest().json(serde_json::json!({ "status": "error", "error": e })),
+    }
+}
+
+#[cfg(feature = "mysql")]
+pub async fn mysql_prepared(query: web::Query<PreparedQuery>) -> impl Responder {
+    match query.resolve() {
+        Ok(params) => bench_response(mysql_bench(&params).await),
+        Err(e) => HttpResponse::BadRequest().json(serde_json::json!({ "status": "error", "error": e })),
+    }
+}
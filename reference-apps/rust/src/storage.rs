@@ -0,0 +1,438 @@
+// Minimal S3-compatible object storage client (MinIO)
+// Requests are signed with AWS Signature Version 4 using reqwest, so no SDK is required
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::{get_env_or, get_vault_secret, resolve};
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectInfo {
+    pub size: u64,
+    pub last_modified: Option<chrono::DateTime<chrono::Utc>>,
+    // As MinIO sends it, quotes included
+    pub etag: Option<String>,
+    pub content_type: Option<String>,
+}
+
+pub struct ObjectStore {
+    endpoint: String,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+    client: reqwest::Client,
+}
+
+impl ObjectStore {
+    // Build a client from Vault credentials (secret/minio), falling back to MinIO defaults
+    pub async fn from_vault() -> Result<Self, String> {
+        let creds = get_vault_secret("minio").await.unwrap_or_else(|e| {
+            log::warn!("MinIO credentials unavailable from Vault, using defaults: {}", e);
+            serde_json::json!({})
+        });
+
+        Ok(ObjectStore {
+            endpoint: resolve::url(get_env_or("MINIO_ENDPOINT", "http://minio:9000").trim_end_matches('/')),
+            bucket: get_env_or("MINIO_BUCKET", "devstack-uploads"),
+            region: get_env_or("MINIO_REGION", "us-east-1"),
+            access_key: creds["access_key"].as_str().map(|s| s.to_string())
+                .unwrap_or_else(|| get_env_or("MINIO_ACCESS_KEY", "minioadmin")),
+            secret_key: creds["secret_key"].as_str().map(|s| s.to_string())
+                .unwrap_or_else(|| get_env_or("MINIO_SECRET_KEY", "minioadmin")),
+            client: reqwest::Client::new(),
+        })
+    }
+
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    pub fn object_url(&self, key: &str) -> String {
+        format!("{}/{}/{}", self.endpoint, self.bucket, uri_encode(key, false))
+    }
+
+    // Create the bucket if needed; "already owned" responses are treated as success
+    pub async fn ensure_bucket(&self) -> Result<(), String> {
+        let url = format!("{}/{}", self.endpoint, self.bucket);
+        let response = self.signed_request(reqwest::Method::PUT, &url, Vec::new(), &[]).await?;
+        let status = response.status();
+        if status.is_success() || status == reqwest::StatusCode::CONFLICT {
+            Ok(())
+        } else {
+            Err(format!("Bucket creation returned status: {}", status))
+        }
+    }
+
+    // Upload an object and return its URL
+    pub async fn put_object(&self, key: &str, body: Vec<u8>, content_type: &str) -> Result<String, String> {
+        let url = self.object_url(key);
+        let response = self
+            .signed_request(reqwest::Method::PUT, &url, body, &[("Content-Type", content_type)])
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(format!("Object storage returned status: {}", response.status()));
+        }
+        Ok(url)
+    }
+
+    // Remove an object; deleting one that doesn't exist succeeds
+    pub async fn delete_object(&self, key: &str) -> Result<(), String> {
+        let response = self.signed_request(reqwest::Method::DELETE, &self.object_url(key), Vec::new(), &[]).await?;
+        let status = response.status();
+        if status.is_success() || status == reqwest::StatusCode::NOT_FOUND {
+            Ok(())
+        } else {
+            Err(format!("Object storage returned status: {}", status))
+        }
+    }
+
+    // Size, validators and type of an object; None when it doesn't exist
+    pub async fn head_object(&self, key: &str) -> Result<Option<ObjectInfo>, String> {
+        let response = self.signed_request(reqwest::Method::HEAD, &self.object_url(key), Vec::new(), &[]).await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(format!("Object storage returned status: {}", response.status()));
+        }
+        let header = |name: &str| response.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+        Ok(Some(ObjectInfo {
+            // From the header: for a HEAD response the body (and so content_length()) is empty
+            size: header("Content-Length").and_then(|v| v.parse().ok()).unwrap_or(0),
+            last_modified: header("Last-Modified")
+                .and_then(|v| chrono::DateTime::parse_from_rfc2822(&v).ok())
+                .map(|date| date.with_timezone(&chrono::Utc)),
+            etag: header("ETag"),
+            content_type: header("Content-Type"),
+        }))
+    }
+
+    // Fetch an object, or the inclusive byte range `start..=end` of it. The response is returned
+    // unread so the caller can stream the body.
+    pub async fn get_object(&self, key: &str, range: Option<(u64, u64)>) -> Result<reqwest::Response, String> {
+        let range = range.map(|(start, end)| format!("bytes={}-{}", start, end));
+        let headers: Vec<(&str, &str)> = range.iter().map(|value| ("Range", value.as_str())).collect();
+        let response = self.signed_request(reqwest::Method::GET, &self.object_url(key), Vec::new(), &headers).await?;
+        if !response.status().is_success() {
+            return Err(format!("Object storage returned status: {}", response.status()));
+        }
+        Ok(response)
+    }
+
+    // One page (up to `max_keys`) of keys under `prefix`, in key order, after `start_after`;
+    // the flag says whether more follow
+    pub async fn list_objects(
+        &self,
+        prefix: &str,
+        start_after: Option<&str>,
+        max_keys: u32,
+    ) -> Result<(Vec<String>, bool), String> {
+        let mut url = format!(
+            "{}/{}?list-type=2&max-keys={}&prefix={}",
+            self.endpoint,
+            self.bucket,
+            max_keys,
+            uri_encode(prefix, true)
+        );
+        if let Some(start_after) = start_after {
+            url.push_str(&format!("&start-after={}", uri_encode(start_after, true)));
+        }
+        let response = self.signed_request(reqwest::Method::GET, &url, Vec::new(), &[]).await?;
+        let status = response.status();
+        let body = response.text().await.map_err(|e| format!("Reading list response failed: {}", e))?;
+        if !status.is_success() {
+            return Err(format!("Object storage returned status: {}", status));
+        }
+        let keys = xml_elements(&body, "Key").into_iter().map(str::to_string).collect();
+        Ok((keys, xml_element(&body, "IsTruncated") == Some("true")))
+    }
+
+    // Start an S3 multipart upload; parts are then streamed through the returned writer
+    pub async fn start_upload(&self, key: &str, content_type: &str) -> Result<MultipartUpload<'_>, String> {
+        let url = format!("{}?uploads", self.object_url(key));
+        let response = self
+            .signed_request(reqwest::Method::POST, &url, Vec::new(), &[("Content-Type", content_type)])
+            .await?;
+        let status = response.status();
+        let body = response.text().await.map_err(|e| format!("Reading upload response failed: {}", e))?;
+        let upload_id = match xml_element(&body, "UploadId") {
+            Some(id) if status.is_success() => id.to_string(),
+            _ => return Err(format!("Starting multipart upload returned status: {}", status)),
+        };
+        Ok(MultipartUpload {
+            store: self,
+            key: key.to_string(),
+            upload_id,
+            part_size: part_size(),
+            buffer: Vec::new(),
+            parts: Vec::new(),
+            hasher: Sha256::new(),
+            size: 0,
+        })
+    }
+
+    async fn signed_request(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        body: Vec<u8>,
+        headers: &[(&str, &str)],
+    ) -> Result<reqwest::Response, String> {
+        let parsed = reqwest::Url::parse(url).map_err(|e| format!("Invalid object storage URL: {}", e))?;
+        let host = match (parsed.host_str(), parsed.port()) {
+            (Some(h), Some(p)) => format!("{}:{}", h, p),
+            (Some(h), None) => h.to_string(),
+            (None, _) => return Err("Object storage URL has no host".to_string()),
+        };
+
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex::encode(Sha256::digest(&body));
+
+        let authorization = sign_v4(&SigningInput {
+            method: method.as_str(),
+            canonical_uri: parsed.path(),
+            canonical_query: &canonical_query(parsed.query().unwrap_or("")),
+            host: &host,
+            amz_date: &amz_date,
+            date: &date,
+            payload_hash: &payload_hash,
+            region: &self.region,
+            access_key: &self.access_key,
+            secret_key: &self.secret_key,
+        })?;
+
+        let mut request = self
+            .client
+            .request(method, parsed)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("Authorization", authorization);
+        // Not signed, so they may be anything the caller needs (Content-Type, Range)
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+
+        request
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| format!("Object storage request failed: {}", e))
+    }
+}
+
+// Relay an object's body chunk by chunk instead of buffering it; a read error ends the stream
+pub fn body_stream(
+    response: reqwest::Response,
+) -> impl futures_util::Stream<Item = Result<actix_web::web::Bytes, std::io::Error>> {
+    futures_util::stream::unfold(Some(response), |response| async move {
+        let mut response = response?;
+        match response.chunk().await {
+            Ok(Some(chunk)) => Some((Ok(chunk), Some(response))),
+            Ok(None) => None,
+            Err(e) => Some((Err(std::io::Error::other(format!("Object storage read failed: {}", e))), None)),
+        }
+    })
+}
+
+// S3 allows at most 10,000 parts of at least 5 MiB each (the last may be smaller)
+const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+
+fn part_size() -> usize {
+    get_env_or("MINIO_UPLOAD_PART_BYTES", "5242880").parse::<usize>().unwrap_or(MIN_PART_SIZE).max(MIN_PART_SIZE)
+}
+
+// Streams an object into MinIO one part at a time, so memory use is bounded by the part size
+// whatever the object size. The SHA-256 of everything written is computed on the way through.
+// Nothing is visible in the bucket until `complete`; `abort` discards the parts.
+pub struct MultipartUpload<'a> {
+    store: &'a ObjectStore,
+    key: String,
+    upload_id: String,
+    part_size: usize,
+    buffer: Vec<u8>,
+    // (part number, ETag) in upload order
+    parts: Vec<(usize, String)>,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl MultipartUpload<'_> {
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    // Hex SHA-256 of the bytes written so far
+    pub fn sha256(&self) -> String {
+        hex::encode(self.hasher.clone().finalize())
+    }
+
+    pub async fn write(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.hasher.update(bytes);
+        self.size += bytes.len() as u64;
+        self.buffer.extend_from_slice(bytes);
+        while self.buffer.len() >= self.part_size {
+            let rest = self.buffer.split_off(self.part_size);
+            let part = std::mem::replace(&mut self.buffer, rest);
+            self.upload_part(part).await?;
+        }
+        Ok(())
+    }
+
+    fn part_url(&self, query: &str) -> String {
+        format!("{}?{}uploadId={}", self.store.object_url(&self.key), query, uri_encode(&self.upload_id, true))
+    }
+
+    // Each part carries its own x-amz-content-sha256, so MinIO rejects a part damaged in transit
+    async fn upload_part(&mut self, part: Vec<u8>) -> Result<(), String> {
+        let number = self.parts.len() + 1;
+        let url = self.part_url(&format!("partNumber={}&", number));
+        let response = self.store.signed_request(reqwest::Method::PUT, &url, part, &[]).await?;
+        if !response.status().is_success() {
+            return Err(format!("Uploading part {} returned status: {}", number, response.status()));
+        }
+        let etag = response
+            .headers()
+            .get("ETag")
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| format!("Part {} response has no ETag", number))?;
+        self.parts.push((number, etag.to_string()));
+        Ok(())
+    }
+
+    // Upload what's buffered as the last part and assemble the object; returns its URL
+    pub async fn complete(mut self) -> Result<String, String> {
+        // An empty object still needs one (empty) part
+        if !self.buffer.is_empty() || self.parts.is_empty() {
+            let part = std::mem::take(&mut self.buffer);
+            self.upload_part(part).await?;
+        }
+        let parts: String = self
+            .parts
+            .iter()
+            .map(|(number, etag)| format!("<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>", number, etag))
+            .collect();
+        let body = format!("<CompleteMultipartUpload>{}</CompleteMultipartUpload>", parts).into_bytes();
+        let response = self
+            .store
+            .signed_request(reqwest::Method::POST, &self.part_url(""), body, &[("Content-Type", "application/xml")])
+            .await?;
+        let status = response.status();
+        let text = response.text().await.map_err(|e| format!("Reading completion response failed: {}", e))?;
+        // S3 may report a failed completion in the body of a 200
+        if !status.is_success() || xml_element(&text, "Error").is_some() {
+            let code = xml_element(&text, "Code").unwrap_or("unknown error");
+            return Err(format!("Completing multipart upload returned status {}: {}", status, code));
+        }
+        Ok(self.store.object_url(&self.key))
+    }
+
+    pub async fn abort(self) {
+        let result = self.store.signed_request(reqwest::Method::DELETE, &self.part_url(""), Vec::new(), &[]).await;
+        match result {
+            Ok(response) if response.status().is_success() => {}
+            Ok(response) => log::warn!("Aborting upload of {} returned status: {}", self.key, response.status()),
+            Err(e) => log::warn!("Aborting upload of {} failed: {}", self.key, e),
+        }
+    }
+}
+
+// Text of the first <name>...</name> element; the S3 responses used here are flat enough
+// that this beats pulling in an XML parser
+pub fn xml_element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let open = format!("<{}>", name);
+    let start = xml.find(&open)? + open.len();
+    let end = xml[start..].find(&format!("</{}>", name))? + start;
+    Some(&xml[start..end])
+}
+
+// Text of every <name>...</name> element, in document order
+pub fn xml_elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let (open, close) = (format!("<{}>", name), format!("</{}>", name));
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let start = start + open.len();
+        let Some(len) = rest[start..].find(&close) else {
+            break;
+        };
+        found.push(&rest[start..start + len]);
+        rest = &rest[start + len + close.len()..];
+    }
+    found
+}
+
+struct SigningInput<'a> {
+    method: &'a str,
+    canonical_uri: &'a str,
+    canonical_query: &'a str,
+    host: &'a str,
+    amz_date: &'a str,
+    date: &'a str,
+    payload_hash: &'a str,
+    region: &'a str,
+    access_key: &'a str,
+    secret_key: &'a str,
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut mac = HmacSha256::new_from_slice(key).map_err(|e| format!("HMAC init failed: {}", e))?;
+    mac.update(data);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+// Build the SigV4 Authorization header value (host, x-amz-content-sha256 and x-amz-date are signed)
+fn sign_v4(input: &SigningInput) -> Result<String, String> {
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+    let canonical_request = format!(
+        "{}\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        input.method, input.canonical_uri, input.canonical_query, input.host, input.payload_hash, input.amz_date,
+        signed_headers, input.payload_hash
+    );
+
+    let scope = format!("{}/{}/s3/aws4_request", input.date, input.region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        input.amz_date,
+        scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let k_date = hmac_sha256(format!("AWS4{}", input.secret_key).as_bytes(), input.date.as_bytes())?;
+    let k_region = hmac_sha256(&k_date, input.region.as_bytes())?;
+    let k_service = hmac_sha256(&k_region, b"s3")?;
+    let k_signing = hmac_sha256(&k_service, b"aws4_request")?;
+    let signature = hex::encode(hmac_sha256(&k_signing, string_to_sign.as_bytes())?);
+
+    Ok(format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        input.access_key, scope, signed_headers, signature
+    ))
+}
+
+// Query parameters sorted and re-encoded as SigV4 expects: `uploads` becomes `uploads=`
+pub fn canonical_query(query: &str) -> String {
+    let mut pairs: Vec<(String, String)> = reqwest::Url::parse(&format!("http://localhost/?{}", query))
+        .map(|url| url.query_pairs().map(|(k, v)| (uri_encode(&k, true), uri_encode(&v, true))).collect())
+        .unwrap_or_default();
+    pairs.sort();
+    pairs.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("&")
+}
+
+// Percent-encode per the SigV4 rules (unreserved characters pass through, '/' optionally kept)
+pub fn uri_encode(input: &str, encode_slash: bool) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
@@ -0,0 +1,255 @@
+// Bounded per-client buffers for streaming responses
+//
+// A forwarding task pushes events into the buffer and the response body drains it, so a client
+// that reads slowly only ever holds STREAM_BUFFER_CAPACITY events. When the buffer is full the
+// overflow policy decides: drop-oldest keeps the stream open and tells the client how many
+// events it missed, disconnect ends the stream.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::Notify;
+
+use crate::{get_env_or, STREAM_DROPPED_EVENTS_TOTAL};
+
+const DEFAULT_CAPACITY: usize = 256;
+const MAX_CAPACITY: usize = 65_536;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OverflowPolicy {
+    DropOldest,
+    Disconnect,
+}
+
+impl OverflowPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "drop-oldest" => Some(OverflowPolicy::DropOldest),
+            "disconnect" => Some(OverflowPolicy::Disconnect),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OverflowPolicy::DropOldest => "drop-oldest",
+            OverflowPolicy::Disconnect => "disconnect",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BufferConfig {
+    pub capacity: usize,
+    pub policy: OverflowPolicy,
+}
+
+impl BufferConfig {
+    pub fn from_env() -> Self {
+        BufferConfig {
+            capacity: get_env_or("STREAM_BUFFER_CAPACITY", "256")
+                .parse()
+                .unwrap_or(DEFAULT_CAPACITY)
+                .clamp(1, MAX_CAPACITY),
+            policy: OverflowPolicy::parse(&get_env_or("STREAM_OVERFLOW_POLICY", "drop-oldest"))
+                .unwrap_or(OverflowPolicy::DropOldest),
+        }
+    }
+
+    // Per-request overrides on top of the environment defaults
+    pub fn with_overrides(self, capacity: Option<usize>, policy: Option<&str>) -> Result<Self, String> {
+        let capacity = match capacity {
+            Some(capacity) if capacity == 0 || capacity > MAX_CAPACITY => {
+                return Err(format!("buffer must be between 1 and {}", MAX_CAPACITY))
+            }
+            Some(capacity) => capacity,
+            None => self.capacity,
+        };
+        let policy = match policy {
+            Some(policy) => OverflowPolicy::parse(policy)
+                .ok_or_else(|| format!("Unknown overflow policy '{}'; use drop-oldest or disconnect", policy))?,
+            None => self.policy,
+        };
+        Ok(BufferConfig { capacity, policy })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PushOutcome {
+    Queued,
+    // The oldest buffered event was discarded to make room
+    DroppedOldest,
+    // Disconnect policy tripped; the buffer was cleared and accepts nothing more
+    Overflowed { discarded: usize },
+}
+
+pub struct BoundedBuffer<T> {
+    queue: VecDeque<T>,
+    config: BufferConfig,
+    // Dropped since the receiver last heard about it
+    dropped: u64,
+    overflowed: bool,
+}
+
+impl<T> BoundedBuffer<T> {
+    pub fn new(config: BufferConfig) -> Self {
+        BoundedBuffer { queue: VecDeque::with_capacity(config.capacity.min(1024)), config, dropped: 0, overflowed: false }
+    }
+
+    pub fn push(&mut self, item: T) -> PushOutcome {
+        if self.overflowed {
+            return PushOutcome::Overflowed { discarded: 1 };
+        }
+        if self.queue.len() < self.config.capacity {
+            self.queue.push_back(item);
+            return PushOutcome::Queued;
+        }
+        match self.config.policy {
+            OverflowPolicy::DropOldest => {
+                self.queue.pop_front();
+                self.queue.push_back(item);
+                self.dropped += 1;
+                PushOutcome::DroppedOldest
+            }
+            OverflowPolicy::Disconnect => {
+                let discarded = self.queue.len() + 1;
+                self.queue.clear();
+                self.overflowed = true;
+                PushOutcome::Overflowed { discarded }
+            }
+        }
+    }
+
+    // Events lost upstream (e.g. a lagging broadcast receiver) count as dropped too
+    pub fn note_dropped(&mut self, count: u64) {
+        self.dropped += count;
+    }
+
+    pub fn pop(&mut self) -> Option<Received<T>> {
+        if self.overflowed {
+            return Some(Received::Disconnected);
+        }
+        // Report a gap before the events that follow it
+        if self.dropped > 0 {
+            return Some(Received::Dropped(std::mem::take(&mut self.dropped)));
+        }
+        self.queue.pop_front().map(Received::Item)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Received<T> {
+    Item(T),
+    Dropped(u64),
+    Disconnected,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    notify: Notify,
+    endpoint: &'static str,
+}
+
+struct State<T> {
+    buffer: BoundedBuffer<T>,
+    sender_closed: bool,
+    receiver_closed: bool,
+}
+
+// The client went away or the stream was ended by the overflow policy
+#[derive(Debug, PartialEq)]
+pub struct Closed;
+
+pub struct BufferSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+pub struct BufferReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+// `endpoint` labels stream_dropped_events_total
+pub fn channel<T>(endpoint: &'static str, config: BufferConfig) -> (BufferSender<T>, BufferReceiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State { buffer: BoundedBuffer::new(config), sender_closed: false, receiver_closed: false }),
+        notify: Notify::new(),
+        endpoint,
+    });
+    (BufferSender { shared: shared.clone() }, BufferReceiver { shared })
+}
+
+impl<T> BufferSender<T> {
+    // Err once the client is gone or the disconnect policy ended the stream
+    pub fn send(&self, item: T) -> Result<(), Closed> {
+        let mut state = self.shared.state.lock().map_err(|_| Closed)?;
+        if state.receiver_closed {
+            return Err(Closed);
+        }
+        let policy = state.buffer.config.policy.as_str();
+        let outcome = state.buffer.push(item);
+        drop(state);
+        self.shared.notify.notify_one();
+        match outcome {
+            PushOutcome::Queued => Ok(()),
+            PushOutcome::DroppedOldest => {
+                STREAM_DROPPED_EVENTS_TOTAL.with_label_values(&[self.shared.endpoint, policy]).inc();
+                Ok(())
+            }
+            PushOutcome::Overflowed { discarded } => {
+                STREAM_DROPPED_EVENTS_TOTAL
+                    .with_label_values(&[self.shared.endpoint, policy])
+                    .inc_by(discarded as f64);
+                Err(Closed)
+            }
+        }
+    }
+
+    pub fn note_dropped(&self, count: u64) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.buffer.note_dropped(count);
+            STREAM_DROPPED_EVENTS_TOTAL
+                .with_label_values(&[self.shared.endpoint, "upstream"])
+                .inc_by(count as f64);
+        }
+        self.shared.notify.notify_one();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.shared.state.lock().map(|state| state.receiver_closed).unwrap_or(true)
+    }
+}
+
+impl<T> Drop for BufferSender<T> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.sender_closed = true;
+        }
+        self.shared.notify.notify_one();
+    }
+}
+
+impl<T> BufferReceiver<T> {
+    // None once the sender is gone and everything buffered has been read
+    pub async fn recv(&mut self) -> Option<Received<T>> {
+        loop {
+            {
+                let mut state = self.shared.state.lock().ok()?;
+                if let Some(received) = state.buffer.pop() {
+                    return Some(received);
+                }
+                if state.sender_closed {
+                    return None;
+                }
+            }
+            self.shared.notify.notified().await;
+        }
+    }
+}
+
+impl<T> Drop for BufferReceiver<T> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.receiver_closed = true;
+        }
+    }
+}
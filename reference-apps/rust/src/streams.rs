@@ -0,0 +1,248 @@
+// RabbitMQ streams over AMQP 0.9.1: an append-only, replayable log
+//
+// Unlike classic and quorum queues, reading a stream doesn't remove anything. Each consumer
+// chooses where to start with `x-stream-offset` (first, last, next, a numeric offset, or a
+// timestamp), so the same messages can be read again, Kafka-style.
+
+use std::time::Duration;
+
+use actix_web::{web, HttpResponse, Responder};
+use futures_util::StreamExt;
+use lapin::types::{AMQPValue, FieldTable};
+use serde::Deserialize;
+
+use crate::amqp_connection;
+use crate::queues::{reply_code, REPLY_NOT_FOUND};
+
+const DEFAULT_LIMIT: u16 = 100;
+const MAX_LIMIT: u16 = 1_000;
+const READ_IDLE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, PartialEq)]
+pub enum StreamOffset {
+    First,
+    Last,
+    Next,
+    Offset(i64),
+    // Seconds since the epoch; starts at the first chunk written at or after it
+    Timestamp(u64),
+}
+
+impl StreamOffset {
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        let value = value.unwrap_or("first");
+        match value {
+            "first" => Ok(StreamOffset::First),
+            "last" => Ok(StreamOffset::Last),
+            "next" => Ok(StreamOffset::Next),
+            _ => {
+                if let Ok(offset) = value.parse::<i64>() {
+                    return if offset >= 0 {
+                        Ok(StreamOffset::Offset(offset))
+                    } else {
+                        Err("offset must not be negative".to_string())
+                    };
+                }
+                chrono::DateTime::parse_from_rfc3339(value)
+                    .ok()
+                    .and_then(|t| u64::try_from(t.timestamp()).ok())
+                    .map(StreamOffset::Timestamp)
+                    .ok_or_else(|| "offset must be first, last, next, a number, or an RFC 3339 timestamp".to_string())
+            }
+        }
+    }
+
+    pub fn to_amqp(&self) -> AMQPValue {
+        match self {
+            StreamOffset::First => AMQPValue::LongString("first".into()),
+            StreamOffset::Last => AMQPValue::LongString("last".into()),
+            StreamOffset::Next => AMQPValue::LongString("next".into()),
+            StreamOffset::Offset(offset) => AMQPValue::LongLongInt(*offset),
+            StreamOffset::Timestamp(seconds) => AMQPValue::Timestamp(*seconds),
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct RetentionOptions {
+    // e.g. "7D", "12h"
+    max_age: Option<String>,
+    max_length_bytes: Option<i64>,
+}
+
+fn stream_arguments(retention: &RetentionOptions) -> FieldTable {
+    let mut args = FieldTable::default();
+    args.insert("x-queue-type".into(), AMQPValue::LongString("stream".into()));
+    if let Some(max_age) = &retention.max_age {
+        args.insert("x-max-age".into(), AMQPValue::LongString(max_age.as_str().into()));
+    }
+    if let Some(bytes) = retention.max_length_bytes {
+        args.insert("x-max-length-bytes".into(), AMQPValue::LongLongInt(bytes));
+    }
+    args
+}
+
+async fn declare_stream(channel: &lapin::Channel, stream: &str, retention: &RetentionOptions) -> Result<(), String> {
+    let options = lapin::options::QueueDeclareOptions { durable: true, ..Default::default() };
+    channel
+        .queue_declare(stream, options, stream_arguments(retention))
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("Stream declare failed (streams need RabbitMQ 3.9+): {}", e))
+}
+
+fn stream_offset(properties: &lapin::BasicProperties) -> Option<i64> {
+    let headers = properties.headers().as_ref()?;
+    match headers.inner().iter().find(|(key, _)| key.as_str() == "x-stream-offset")?.1 {
+        AMQPValue::LongLongInt(offset) => Some(*offset),
+        AMQPValue::LongInt(offset) => Some(*offset as i64),
+        _ => None,
+    }
+}
+
+fn error_response(status: actix_web::http::StatusCode, stream: &str, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "stream": stream, "error": error }))
+}
+
+// ============================================================================
+// Append
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct AppendRequest {
+    messages: Vec<String>,
+    // Only applied when this request creates the stream
+    #[serde(default)]
+    retention: RetentionOptions,
+}
+
+pub async fn append(path: web::Path<String>, body: web::Json<AppendRequest>) -> impl Responder {
+    let stream = path.into_inner();
+    if body.messages.is_empty() || body.messages.len() > 1_000 {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, &stream, "messages must hold 1 to 1000 entries".to_string());
+    }
+
+    let conn = match amqp_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, &stream, e),
+    };
+    let result = async {
+        let channel = conn.create_channel().await.map_err(|e| format!("Channel creation failed: {}", e))?;
+        declare_stream(&channel, &stream, &body.retention).await?;
+        channel
+            .confirm_select(lapin::options::ConfirmSelectOptions::default())
+            .await
+            .map_err(|e| format!("confirm.select failed: {}", e))?;
+        let mut confirms = Vec::with_capacity(body.messages.len());
+        for message in &body.messages {
+            let confirm = channel
+                .basic_publish(
+                    "",
+                    &stream,
+                    lapin::options::BasicPublishOptions::default(),
+                    message.as_bytes(),
+                    lapin::BasicProperties::default().with_delivery_mode(2),
+                )
+                .await
+                .map_err(|e| format!("Publish failed: {}", e))?;
+            confirms.push(confirm);
+        }
+        for confirm in confirms {
+            confirm.await.map_err(|e| format!("Publish confirm failed: {}", e))?;
+        }
+        Ok::<_, String>(())
+    }
+    .await;
+    let _ = conn.close(0, "Done").await;
+
+    match result {
+        Ok(()) => HttpResponse::Ok().json(serde_json::json!({
+            "status": "appended",
+            "stream": stream,
+            "count": body.messages.len()
+        })),
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, &stream, e),
+    }
+}
+
+// ============================================================================
+// Read from an offset
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct ReadQuery {
+    offset: Option<String>,
+    limit: Option<u16>,
+}
+
+pub async fn read(path: web::Path<String>, query: web::Query<ReadQuery>) -> impl Responder {
+    let stream = path.into_inner();
+    let offset = match StreamOffset::parse(query.offset.as_deref()) {
+        Ok(offset) => offset,
+        Err(e) => return error_response(actix_web::http::StatusCode::BAD_REQUEST, &stream, e),
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let conn = match amqp_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, &stream, e),
+    };
+    let internal = |e: String| (actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e);
+    let result = async {
+        let channel = conn.create_channel().await.map_err(|e| internal(format!("Channel creation failed: {}", e)))?;
+        // A passive declare so reading a missing stream doesn't create it; only a 404 reply means it's missing
+        let passive = lapin::options::QueueDeclareOptions { passive: true, ..Default::default() };
+        channel.queue_declare(&stream, passive, FieldTable::default()).await.map_err(|e| {
+            let status = match reply_code(&e) {
+                Some(REPLY_NOT_FOUND) => actix_web::http::StatusCode::NOT_FOUND,
+                _ => actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, format!("Stream declare failed: {}", e))
+        })?;
+
+        // Stream consumers must set a prefetch and ack manually
+        channel
+            .basic_qos(limit, lapin::options::BasicQosOptions::default())
+            .await
+            .map_err(|e| internal(format!("basic.qos failed: {}", e)))?;
+        let mut args = FieldTable::default();
+        args.insert("x-stream-offset".into(), offset.to_amqp());
+        let mut consumer = channel
+            .basic_consume(&stream, "", lapin::options::BasicConsumeOptions::default(), args)
+            .await
+            .map_err(|e| internal(format!("basic.consume failed: {}", e)))?;
+
+        let mut messages = Vec::new();
+        while messages.len() < limit as usize {
+            match tokio::time::timeout(READ_IDLE, consumer.next()).await {
+                Ok(Some(Ok(delivery))) => {
+                    let _ = delivery.ack(lapin::options::BasicAckOptions::default()).await;
+                    messages.push(serde_json::json!({
+                        "offset": stream_offset(&delivery.properties),
+                        "message": String::from_utf8_lossy(&delivery.data)
+                    }));
+                }
+                Ok(Some(Err(e))) => return Err(internal(format!("Consume failed: {}", e))),
+                // Caught up with the end of the stream
+                Ok(None) | Err(_) => break,
+            }
+        }
+        Ok::<_, (actix_web::http::StatusCode, String)>(messages)
+    }
+    .await;
+    let _ = conn.close(0, "Done").await;
+
+    match result {
+        Ok(messages) => {
+            let next_offset = messages.last().and_then(|m| m["offset"].as_i64()).map(|o| o + 1);
+            HttpResponse::Ok().json(serde_json::json!({
+                "status": "success",
+                "stream": stream,
+                "count": messages.len(),
+                "next_offset": next_offset,
+                "messages": messages
+            }))
+        }
+        Err((status, e)) => error_response(status, &stream, e),
+    }
+}
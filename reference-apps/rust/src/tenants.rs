@@ -0,0 +1,412 @@
+// Tenant-scoped Vault secrets and per-tenant connection pools
+//
+// A request names its tenant in X-Tenant-Id: lowercase letters, digits, '-' and '_', up to 63
+// characters, so a tenant id can't step out of its own part of the Vault tree. It proves the
+// tenant with X-Tenant-Token, HMAC-SHA256 of the tenant id under TENANT_TOKEN_SECRET, issued by
+// GET /admin/tenants/{tenant}/token; with no secret configured, tenant routes are refused. A tenant's
+// credentials live at secret/data/<tenant>/<service> and are read through the app's secret
+// cache and last-known-good fallback. There is no fallback to the app's own secret: a tenant
+// without credentials gets an error, never another tenant's connection.
+//
+// Each tenant gets its own Postgres pool, created on first use with the credentials from
+// <tenant>/postgres (which may also carry `host` and `port`, for tenants on their own server).
+// A pool unused for TENANT_POOL_IDLE_SECONDS (default 300) is evicted by a background sweep,
+// and the least recently used one makes room when TENANT_POOL_MAX (default 50) are open.
+// Connections checked out of an evicted pool finish their work and are closed when returned.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::admin_auth;
+use crate::pool::{Manager, Pool, PoolConfig, Pooled};
+use crate::vault::get_vault_secret;
+use crate::{get_env_or, postgres_connect_as, spawn_postgres_connection, TENANT_POOLS, TENANT_POOL_EVICTIONS_TOTAL};
+
+pub const TENANT_HEADER: &str = "x-tenant-id";
+pub const TENANT_TOKEN_HEADER: &str = "x-tenant-token";
+
+// Overrides TENANT_TOKEN_SECRET when registered as app data, like admin_auth::AdminToken
+#[derive(Clone)]
+pub struct TenantTokenSecret(pub String);
+
+pub fn valid_tenant_id(tenant: &str) -> bool {
+    !tenant.is_empty()
+        && tenant.len() <= 63
+        && tenant.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_'))
+}
+
+// Vault secret name (`<tenant>/<service>`, under secret/data/) of a tenant's credentials
+pub fn secret_name(tenant: &str, service: &str) -> String {
+    format!("{}/{}", tenant, service)
+}
+
+pub fn tenant_id(req: &HttpRequest) -> Result<String, String> {
+    let tenant = req
+        .headers()
+        .get(TENANT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .ok_or_else(|| "Missing X-Tenant-Id header".to_string())?;
+    if !valid_tenant_id(tenant) {
+        return Err(format!("Invalid tenant id {:?}: use a-z, 0-9, '-' and '_', up to 63 characters", tenant));
+    }
+    Ok(tenant.to_string())
+}
+
+// The token a caller presents to act as `tenant`
+pub fn tenant_token(secret: &str, tenant: &str) -> String {
+    // HMAC accepts keys of any length, so this cannot fail
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(tenant.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn token_secret(req: &HttpRequest) -> String {
+    match req.app_data::<TenantTokenSecret>() {
+        Some(TenantTokenSecret(secret)) => secret.clone(),
+        None => get_env_or("TENANT_TOKEN_SECRET", ""),
+    }
+}
+
+// The tenant named in X-Tenant-Id, once X-Tenant-Token proves the caller may act as it
+pub fn authenticated_tenant(req: &HttpRequest) -> Result<String, Box<HttpResponse>> {
+    let tenant = tenant_id(req).map_err(|e| Box::new(error_response(actix_web::http::StatusCode::BAD_REQUEST, e)))?;
+    let secret = token_secret(req);
+    if secret.is_empty() {
+        return Err(Box::new(error_response(
+            actix_web::http::StatusCode::FORBIDDEN,
+            "Tenant routes are disabled; set TENANT_TOKEN_SECRET to enable them".to_string(),
+        )));
+    }
+    let provided = req.headers().get(TENANT_TOKEN_HEADER).and_then(|v| v.to_str().ok()).map(str::trim);
+    match provided {
+        Some(token) if admin_auth::tokens_match(&tenant_token(&secret, &tenant), token) => Ok(tenant),
+        _ => Err(Box::new(error_response(
+            actix_web::http::StatusCode::UNAUTHORIZED,
+            format!("Missing or invalid X-Tenant-Token for tenant '{}'", tenant),
+        ))),
+    }
+}
+
+pub async fn tenant_secret(tenant: &str, service: &str) -> Result<serde_json::Value, String> {
+    get_vault_secret(&secret_name(tenant, service)).await
+}
+
+fn idle_limit() -> Duration {
+    Duration::from_secs(get_env_or("TENANT_POOL_IDLE_SECONDS", "300").parse().unwrap_or(300))
+}
+
+fn max_pools() -> usize {
+    get_env_or("TENANT_POOL_MAX", "50").parse::<usize>().unwrap_or(50).max(1)
+}
+
+// ============================================================================
+// Registry
+// ============================================================================
+
+struct Entry<T> {
+    value: Arc<T>,
+    created: Instant,
+    last_used: Instant,
+}
+
+// Per-tenant values created on first use and dropped when idle or to stay under capacity
+pub struct TenantRegistry<T> {
+    entries: HashMap<String, Entry<T>>,
+    capacity: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntryInfo {
+    pub tenant: String,
+    pub age_seconds: u64,
+    pub idle_seconds: u64,
+    // Whether something outside the registry still holds the value, e.g. a checked-out connection
+    pub in_use: bool,
+}
+
+impl<T> TenantRegistry<T> {
+    pub fn new(capacity: usize) -> Self {
+        TenantRegistry { entries: HashMap::new(), capacity: capacity.max(1) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // The tenant's value, created with `make` if there is none; returns the tenant it evicted to make room
+    pub fn get_or_insert_with(
+        &mut self,
+        tenant: &str,
+        now: Instant,
+        make: impl FnOnce() -> T,
+    ) -> (Arc<T>, Option<String>) {
+        if let Some(entry) = self.entries.get_mut(tenant) {
+            entry.last_used = now;
+            return (entry.value.clone(), None);
+        }
+        let mut evicted = None;
+        if self.entries.len() >= self.capacity {
+            evicted = self.entries.iter().min_by_key(|(_, entry)| entry.last_used).map(|(t, _)| t.clone());
+            if let Some(oldest) = &evicted {
+                self.entries.remove(oldest);
+            }
+        }
+        let value = Arc::new(make());
+        self.entries.insert(tenant.to_string(), Entry { value: value.clone(), created: now, last_used: now });
+        (value, evicted)
+    }
+
+    pub fn remove(&mut self, tenant: &str) -> bool {
+        self.entries.remove(tenant).is_some()
+    }
+
+    // Drops entries unused for `idle` that nothing else holds; returns their tenants
+    pub fn evict_idle(&mut self, now: Instant, idle: Duration) -> Vec<String> {
+        let expired: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| {
+                now.saturating_duration_since(entry.last_used) >= idle && Arc::strong_count(&entry.value) == 1
+            })
+            .map(|(tenant, _)| tenant.clone())
+            .collect();
+        for tenant in &expired {
+            self.entries.remove(tenant);
+        }
+        expired
+    }
+
+    pub fn info(&self, now: Instant) -> Vec<EntryInfo> {
+        let mut info: Vec<EntryInfo> = self
+            .entries
+            .iter()
+            .map(|(tenant, entry)| EntryInfo {
+                tenant: tenant.clone(),
+                age_seconds: now.saturating_duration_since(entry.created).as_secs(),
+                idle_seconds: now.saturating_duration_since(entry.last_used).as_secs(),
+                in_use: Arc::strong_count(&entry.value) > 1,
+            })
+            .collect();
+        info.sort_by(|a, b| a.tenant.cmp(&b.tenant));
+        info
+    }
+
+    pub fn get(&self, tenant: &str) -> Option<&Arc<T>> {
+        self.entries.get(tenant).map(|entry| &entry.value)
+    }
+}
+
+// ============================================================================
+// Postgres pools
+// ============================================================================
+
+// Connects with the tenant's own credentials, to the host in its secret or POSTGRES_HOST
+pub struct TenantPostgresManager {
+    pub tenant: String,
+}
+
+#[async_trait]
+impl Manager for TenantPostgresManager {
+    type Connection = tokio_postgres::Client;
+
+    async fn connect(&self) -> Result<Self::Connection, String> {
+        let creds = tenant_secret(&self.tenant, "postgres").await?;
+        let host = match creds["host"].as_str() {
+            Some(host) => host.to_string(),
+            None => get_env_or("POSTGRES_HOST", "postgres"),
+        };
+        let port = match &creds["port"] {
+            serde_json::Value::Number(port) => port.to_string(),
+            serde_json::Value::String(port) => port.clone(),
+            _ => get_env_or("POSTGRES_PORT", "5432"),
+        };
+        let (client, connection) = postgres_connect_as(&host, &port, &secret_name(&self.tenant, "postgres")).await?;
+        spawn_postgres_connection(connection);
+        Ok(client)
+    }
+
+    async fn ping(&self, conn: &mut Self::Connection) -> bool {
+        !conn.is_closed() && conn.batch_execute("SELECT 1").await.is_ok()
+    }
+}
+
+type TenantPool = Pool<TenantPostgresManager>;
+
+lazy_static! {
+    static ref POOLS: Mutex<TenantRegistry<TenantPool>> = Mutex::new(TenantRegistry::new(max_pools()));
+}
+
+fn pools() -> std::sync::MutexGuard<'static, TenantRegistry<TenantPool>> {
+    POOLS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn new_pool(tenant: &str) -> TenantPool {
+    let config = PoolConfig {
+        min_idle: 0,
+        max_idle: get_env_or("TENANT_POOL_MAX_IDLE", "2").parse().unwrap_or(2),
+        ..PoolConfig::from_env()
+    };
+    Pool::new(TenantPostgresManager { tenant: tenant.to_string() }, "postgres", config)
+}
+
+fn record_evictions(reason: &str, count: usize, live: usize) {
+    if count > 0 {
+        TENANT_POOL_EVICTIONS_TOTAL.with_label_values(&[reason]).inc_by(count as f64);
+    }
+    TENANT_POOLS.set(live as i64);
+}
+
+pub async fn tenant_postgres(tenant: &str) -> Result<Pooled<TenantPostgresManager>, String> {
+    let pool = {
+        let mut pools = pools();
+        let (pool, evicted) = pools.get_or_insert_with(tenant, Instant::now(), || new_pool(tenant));
+        if let Some(evicted) = &evicted {
+            log::info!("Evicted the Postgres pool of tenant {} to make room for {}", evicted, tenant);
+        }
+        record_evictions("capacity", usize::from(evicted.is_some()), pools.len());
+        pool
+    };
+    pool.get_shared().await
+}
+
+// Sweeps idle tenant pools out every quarter of the idle limit
+pub fn spawn_tenant_pool_sweeper() {
+    let idle = idle_limit();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval((idle / 4).max(Duration::from_secs(1)));
+        loop {
+            ticker.tick().await;
+            let mut pools = pools();
+            let evicted = pools.evict_idle(Instant::now(), idle);
+            for tenant in &evicted {
+                log::info!("Evicted the idle Postgres pool of tenant {}", tenant);
+            }
+            record_evictions("idle", evicted.len(), pools.len());
+        }
+    });
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+// GET /examples/tenants/secret/{service}
+// Reports where the tenant's secret lives and which keys it has, never the values
+pub async fn get_tenant_secret(req: HttpRequest, path: web::Path<String>) -> impl Responder {
+    let tenant = match authenticated_tenant(&req) {
+        Ok(tenant) => tenant,
+        Err(response) => return *response,
+    };
+    let service = path.into_inner();
+    // The service is part of the Vault path too, so it gets the tenant id's character rules
+    if !valid_tenant_id(&service) {
+        return error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            format!("Invalid service {:?}: use a-z, 0-9, '-' and '_', up to 63 characters", service),
+        );
+    }
+    match tenant_secret(&tenant, &service).await {
+        Ok(value) => {
+            let keys: Vec<&String> = value.as_object().map(|fields| fields.keys().collect()).unwrap_or_default();
+            HttpResponse::Ok().json(serde_json::json!({
+                "tenant": tenant,
+                "service": service,
+                "path": format!("secret/data/{}", secret_name(&tenant, &service)),
+                "keys": keys
+            }))
+        }
+        Err(e) => error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    }
+}
+
+// GET /examples/tenants/postgres - who the tenant's pool connects as
+pub async fn tenant_postgres_identity(req: HttpRequest) -> impl Responder {
+    let tenant = match authenticated_tenant(&req) {
+        Ok(tenant) => tenant,
+        Err(response) => return *response,
+    };
+    let client = match tenant_postgres(&tenant).await {
+        Ok(client) => client,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+    match client.query_one("SELECT current_user::text, current_database()::text", &[]).await {
+        Ok(row) => HttpResponse::Ok().json(serde_json::json!({
+            "tenant": tenant,
+            "user": row.get::<_, String>(0),
+            "database": row.get::<_, String>(1)
+        })),
+        Err(e) => {
+            client.discard();
+            error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Query failed: {}", e))
+        }
+    }
+}
+
+// GET /admin/tenants/{tenant}/token - the X-Tenant-Token to hand to that tenant
+pub async fn issue_token(req: HttpRequest, path: web::Path<String>) -> impl Responder {
+    let tenant = path.into_inner();
+    if !valid_tenant_id(&tenant) {
+        return error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            format!("Invalid tenant id {:?}: use a-z, 0-9, '-' and '_', up to 63 characters", tenant),
+        );
+    }
+    let secret = token_secret(&req);
+    if secret.is_empty() {
+        return error_response(
+            actix_web::http::StatusCode::SERVICE_UNAVAILABLE,
+            "TENANT_TOKEN_SECRET is not set".to_string(),
+        );
+    }
+    HttpResponse::Ok().json(serde_json::json!({ "tenant": tenant, "token": tenant_token(&secret, &tenant) }))
+}
+
+// GET /examples/tenants/pools
+pub async fn list_pools() -> impl Responder {
+    let pools = pools();
+    let entries: Vec<serde_json::Value> = pools
+        .info(Instant::now())
+        .into_iter()
+        .map(|info| {
+            let pool = pools.get(&info.tenant).map(|pool| pool.info());
+            serde_json::json!({ "entry": info, "pool": pool })
+        })
+        .collect();
+    HttpResponse::Ok().json(serde_json::json!({
+        "count": entries.len(),
+        "max_pools": max_pools(),
+        "idle_seconds": idle_limit().as_secs(),
+        "pools": entries
+    }))
+}
+
+// DELETE /examples/tenants/pools/{tenant}; tearing down a tenant's pool is an operator action
+pub async fn evict_pool(req: HttpRequest, path: web::Path<String>) -> impl Responder {
+    if let Err(response) = admin_auth::authorize(&req) {
+        return *response;
+    }
+    let tenant = path.into_inner();
+    let mut pools = pools();
+    if !pools.remove(&tenant) {
+        return error_response(actix_web::http::StatusCode::NOT_FOUND, format!("No pool for tenant '{}'", tenant));
+    }
+    record_evictions("manual", 1, pools.len());
+    HttpResponse::Ok().json(serde_json::json!({ "status": "evicted", "tenant": tenant }))
+}
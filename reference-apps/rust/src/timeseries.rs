@@ -0,0 +1,349 @@
+// Mini telemetry pipeline: raw points in a Redis stream, per-minute rollups in PostgreSQL
+//
+// POSTed measurements are appended to one Redis stream (trimmed to about
+// TIMESERIES_STREAM_MAXLEN entries). The downsample_timeseries scheduler job reads entries added
+// before the current minute, aggregates them per metric and minute of the point's own timestamp,
+// and upserts count/sum/min/max into timeseries_minutely. The last processed stream ID is saved
+// in the same transaction, so a crash never counts an entry twice, and late points simply merge
+// into their minute's existing row.
+
+use std::collections::BTreeMap;
+
+use actix_web::{web, HttpResponse, Responder};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{get_env_or, pool, redis_connection};
+
+const STREAM_KEY: &str = "timeseries:points";
+const READ_BATCH: usize = 1_000;
+const MAX_POINTS_PER_REQUEST: usize = 5_000;
+const MAX_RAW_POINTS: usize = 10_000;
+const MINUTE_MS: i64 = 60_000;
+
+const TABLE_DDL: &str = "CREATE TABLE IF NOT EXISTS timeseries_minutely (
+    metric TEXT NOT NULL,
+    bucket TIMESTAMPTZ NOT NULL,
+    count BIGINT NOT NULL,
+    sum DOUBLE PRECISION NOT NULL,
+    min DOUBLE PRECISION NOT NULL,
+    max DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (metric, bucket)
+);
+CREATE TABLE IF NOT EXISTS timeseries_downsample_state (
+    id INTEGER PRIMARY KEY,
+    last_stream_id TEXT NOT NULL
+);
+INSERT INTO timeseries_downsample_state (id, last_stream_id) VALUES (1, '0-0') ON CONFLICT DO NOTHING";
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Point {
+    pub metric: String,
+    pub value: f64,
+    // Milliseconds since the epoch; defaults to the time of the request
+    pub timestamp: Option<i64>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Aggregate {
+    pub count: i64,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Aggregate {
+    fn new(value: f64) -> Self {
+        Aggregate { count: 1, sum: value, min: value, max: value }
+    }
+
+    fn add(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+}
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+pub fn valid_metric_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 128
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+}
+
+pub fn validate_point(point: &Point) -> Result<(), String> {
+    if !valid_metric_name(&point.metric) {
+        return Err(format!(
+            "Invalid metric '{}': 1-128 characters of letters, digits, '_', '-', '.' or ':'",
+            point.metric
+        ));
+    }
+    if !point.value.is_finite() {
+        return Err(format!("Value for '{}' must be a finite number", point.metric));
+    }
+    Ok(())
+}
+
+// Start of the minute containing `timestamp_ms`
+pub fn minute_bucket(timestamp_ms: i64) -> i64 {
+    timestamp_ms.div_euclid(MINUTE_MS) * MINUTE_MS
+}
+
+// Per (metric, minute) aggregates of (metric, timestamp_ms, value) samples
+pub fn aggregate(samples: &[(String, i64, f64)]) -> BTreeMap<(String, i64), Aggregate> {
+    let mut buckets: BTreeMap<(String, i64), Aggregate> = BTreeMap::new();
+    for (metric, timestamp_ms, value) in samples {
+        buckets
+            .entry((metric.clone(), minute_bucket(*timestamp_ms)))
+            .and_modify(|aggregate| aggregate.add(*value))
+            .or_insert_with(|| Aggregate::new(*value));
+    }
+    buckets
+}
+
+// (stream id, metric, timestamp_ms, value); entries missing a field are skipped
+fn parse_entries(reply: redis::streams::StreamRangeReply) -> Vec<(String, String, i64, f64)> {
+    reply
+        .ids
+        .into_iter()
+        .filter_map(|entry| {
+            let metric: String = entry.get("metric")?;
+            let timestamp: i64 = entry.get("ts")?;
+            let value: f64 = entry.get("value")?;
+            Some((entry.id, metric, timestamp, value))
+        })
+        .collect()
+}
+
+// ============================================================================
+// Write path
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct IngestBody {
+    points: Vec<Point>,
+}
+
+pub async fn ingest(body: web::Json<IngestBody>) -> impl Responder {
+    let points = &body.points;
+    if points.is_empty() || points.len() > MAX_POINTS_PER_REQUEST {
+        return error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            format!("points must hold between 1 and {} entries", MAX_POINTS_PER_REQUEST),
+        );
+    }
+    if let Some(e) = points.iter().find_map(|point| validate_point(point).err()) {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, e);
+    }
+
+    let mut conn = match redis_connection().await {
+        Ok(conn) => conn,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+    let max_len: usize = get_env_or("TIMESERIES_STREAM_MAXLEN", "100000").parse().unwrap_or(100_000);
+    let now = Utc::now().timestamp_millis();
+    let mut pipe = redis::pipe();
+    for point in points {
+        pipe.cmd("XADD")
+            .arg(STREAM_KEY)
+            .arg("MAXLEN")
+            .arg("~")
+            .arg(max_len)
+            .arg("*")
+            .arg("metric")
+            .arg(&point.metric)
+            .arg("ts")
+            .arg(point.timestamp.unwrap_or(now))
+            .arg("value")
+            .arg(point.value);
+    }
+    match pipe.query_async::<Vec<String>>(&mut conn).await {
+        Ok(ids) => HttpResponse::Ok().json(serde_json::json!({
+            "status": "accepted",
+            "stream": STREAM_KEY,
+            "count": ids.len(),
+            "first_id": ids.first(),
+            "last_id": ids.last()
+        })),
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("XADD failed: {}", e)),
+    }
+}
+
+// ============================================================================
+// Downsampling
+// ============================================================================
+
+// Rolls up stream entries added before the current minute; run by the scheduler and on demand
+pub async fn downsample() -> Result<String, String> {
+    let mut client = pool::postgres().await?;
+    client
+        .batch_execute(TABLE_DDL)
+        .await
+        .map_err(|e| format!("Timeseries table setup failed: {}", e))?;
+    let mut conn = redis_connection().await?;
+    // Entries added during the current minute wait for the next run
+    let cutoff_ms = minute_bucket(Utc::now().timestamp_millis());
+
+    let tx = client.transaction().await.map_err(|e| format!("Transaction failed: {}", e))?;
+    // The row lock keeps replicas that run the job at the same time from reading the same entries
+    let start_id: String = tx
+        .query_one("SELECT last_stream_id FROM timeseries_downsample_state WHERE id = 1 FOR UPDATE", &[])
+        .await
+        .map_err(|e| format!("State lookup failed: {}", e))?
+        .get(0);
+    let mut last_id = start_id.clone();
+
+    let mut samples = Vec::new();
+    loop {
+        let reply: redis::streams::StreamRangeReply = redis::cmd("XRANGE")
+            .arg(STREAM_KEY)
+            .arg(format!("({}", last_id))
+            .arg(cutoff_ms - 1)
+            .arg("COUNT")
+            .arg(READ_BATCH)
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| format!("XRANGE failed: {}", e))?;
+        let read = reply.ids.len();
+        if let Some(entry) = reply.ids.last() {
+            last_id = entry.id.clone();
+        }
+        samples.extend(parse_entries(reply).into_iter().map(|(_, metric, ts, value)| (metric, ts, value)));
+        if read < READ_BATCH {
+            break;
+        }
+    }
+    if last_id == start_id {
+        return Ok("no new points".to_string());
+    }
+
+    let buckets = aggregate(&samples);
+    let upsert = tx
+        .prepare(
+            "INSERT INTO timeseries_minutely (metric, bucket, count, sum, min, max)
+             VALUES ($1, to_timestamp($2), $3, $4, $5, $6)
+             ON CONFLICT (metric, bucket) DO UPDATE SET
+                 count = timeseries_minutely.count + EXCLUDED.count,
+                 sum = timeseries_minutely.sum + EXCLUDED.sum,
+                 min = LEAST(timeseries_minutely.min, EXCLUDED.min),
+                 max = GREATEST(timeseries_minutely.max, EXCLUDED.max)",
+        )
+        .await
+        .map_err(|e| format!("Prepare failed: {}", e))?;
+    for ((metric, bucket_ms), aggregate) in &buckets {
+        let bucket_seconds = *bucket_ms as f64 / 1000.0;
+        tx.execute(
+            &upsert,
+            &[metric, &bucket_seconds, &aggregate.count, &aggregate.sum, &aggregate.min, &aggregate.max],
+        )
+        .await
+        .map_err(|e| format!("Upsert failed: {}", e))?;
+    }
+    tx.execute("UPDATE timeseries_downsample_state SET last_stream_id = $1 WHERE id = 1", &[&last_id])
+        .await
+        .map_err(|e| format!("State update failed: {}", e))?;
+    tx.commit().await.map_err(|e| format!("Commit failed: {}", e))?;
+
+    Ok(format!("{} points into {} minute buckets", samples.len(), buckets.len()))
+}
+
+pub async fn run_downsample() -> impl Responder {
+    match downsample().await {
+        Ok(result) => HttpResponse::Ok().json(serde_json::json!({ "status": "success", "result": result })),
+        Err(e) => error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+// ============================================================================
+// Read path
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct SeriesQuery {
+    // Window ending now
+    minutes: Option<i32>,
+}
+
+pub async fn series(path: web::Path<String>, query: web::Query<SeriesQuery>) -> impl Responder {
+    let metric = path.into_inner();
+    if !valid_metric_name(&metric) {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, format!("Invalid metric '{}'", metric));
+    }
+    let minutes = query.minutes.unwrap_or(60).clamp(1, 7 * 24 * 60);
+    let since_ms = Utc::now().timestamp_millis() - i64::from(minutes) * MINUTE_MS;
+
+    // Raw points come from the stream, selected by when they were added
+    let raw = async {
+        let mut conn = redis_connection().await?;
+        let reply: redis::streams::StreamRangeReply = redis::cmd("XRANGE")
+            .arg(STREAM_KEY)
+            .arg(since_ms)
+            .arg("+")
+            .arg("COUNT")
+            .arg(MAX_RAW_POINTS)
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| format!("XRANGE failed: {}", e))?;
+        let scanned = reply.ids.len();
+        let points: Vec<serde_json::Value> = parse_entries(reply)
+            .into_iter()
+            .filter(|(_, m, _, _)| *m == metric)
+            .map(|(id, _, timestamp, value)| serde_json::json!({ "id": id, "timestamp": timestamp, "value": value }))
+            .collect();
+        Ok::<_, String>((points, scanned >= MAX_RAW_POINTS))
+    }
+    .await;
+    let (raw, truncated) = match raw {
+        Ok(raw) => raw,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+
+    let client = match pool::postgres().await {
+        Ok(client) => client,
+        Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+    if let Err(e) = client.batch_execute(TABLE_DDL).await {
+        return error_response(
+            actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Timeseries table setup failed: {}", e),
+        );
+    }
+    let rows = client
+        .query(
+            "SELECT (extract(epoch FROM bucket) * 1000)::bigint, count, sum / count, min, max
+             FROM timeseries_minutely
+             WHERE metric = $1 AND bucket >= NOW() - make_interval(mins => $2)
+             ORDER BY bucket",
+            &[&metric, &minutes],
+        )
+        .await;
+    let aggregated: Vec<serde_json::Value> = match rows {
+        Ok(rows) => rows
+            .iter()
+            .map(|row| {
+                serde_json::json!({
+                    "bucket": row.get::<_, i64>(0),
+                    "count": row.get::<_, i64>(1),
+                    "avg": row.get::<_, f64>(2),
+                    "min": row.get::<_, f64>(3),
+                    "max": row.get::<_, f64>(4)
+                })
+            })
+            .collect(),
+        Err(e) => {
+            return error_response(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Query failed: {}", e))
+        }
+    };
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "metric": metric,
+        "minutes": minutes,
+        "raw": { "count": raw.len(), "truncated": truncated, "points": raw },
+        "minutely": { "count": aggregated.len(), "buckets": aggregated }
+    }))
+}
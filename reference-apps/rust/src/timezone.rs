@@ -0,0 +1,170 @@
+// Localized timestamps for the human-facing endpoints (/health, /info)
+//
+// Timestamps stay RFC3339 UTC. When a request carries X-Timezone (an IANA zone such as
+// Europe/Berlin) or Accept-Language, every `timestamp` / `*_at` field in the JSON response
+// gets a `<field>_local` sibling rendered in that zone with the language's date layout. An
+// unknown zone is rejected with 400 instead of silently falling back to UTC.
+
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::HttpResponse;
+use chrono::DateTime;
+use chrono_tz::Tz;
+
+const LOCALIZED_PREFIXES: &[&str] = &["/health", "/info"];
+pub const TIMEZONE_HEADER: &str = "X-Timezone";
+
+// Language tag (lower case) -> chrono format; a region-specific tag wins over its language
+const LOCALE_FORMATS: &[(&str, &str)] = &[
+    ("en-us", "%m/%d/%Y, %I:%M:%S %p %Z"),
+    ("en", "%d/%m/%Y, %H:%M:%S %Z"),
+    ("de", "%d.%m.%Y, %H:%M:%S %Z"),
+    ("fr", "%d/%m/%Y %H:%M:%S %Z"),
+    ("es", "%d/%m/%Y, %H:%M:%S %Z"),
+    ("it", "%d/%m/%Y, %H:%M:%S %Z"),
+    ("nl", "%d-%m-%Y %H:%M:%S %Z"),
+    ("pt", "%d/%m/%Y, %H:%M:%S %Z"),
+    ("sv", "%Y-%m-%d %H:%M:%S %Z"),
+    ("ja", "%Y/%m/%d %H:%M:%S %Z"),
+    ("zh", "%Y/%m/%d %H:%M:%S %Z"),
+    ("ko", "%Y. %m. %d. %H:%M:%S %Z"),
+];
+const DEFAULT_FORMAT: &str = "%Y-%m-%d %H:%M:%S %Z";
+
+// Case-insensitive IANA name, e.g. "america/new_york"
+pub fn parse_zone(name: &str) -> Result<Tz, String> {
+    Tz::from_str_insensitive(name.trim())
+        .map_err(|_| format!("Unknown time zone '{}': use an IANA name such as Europe/Berlin", name.trim()))
+}
+
+// Best supported language in an Accept-Language header, honouring q-values
+pub fn parse_accept_language(value: &str) -> Option<(&'static str, &'static str)> {
+    let mut ranges: Vec<(String, f32)> = value
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';');
+            let tag = parts.next()?.trim().to_ascii_lowercase();
+            let q = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            (!tag.is_empty() && tag != "*" && q > 0.0).then_some((tag, q))
+        })
+        .collect();
+    // Stable, so equal weights keep the client's order
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranges.iter().find_map(|(tag, _)| {
+        let language = tag.split('-').next().unwrap_or(tag);
+        LOCALE_FORMATS
+            .iter()
+            .find(|(locale, _)| *locale == tag)
+            .or_else(|| LOCALE_FORMATS.iter().find(|(locale, _)| *locale == language))
+            .copied()
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Localizer {
+    pub zone: Tz,
+    pub locale: Option<&'static str>,
+    format: &'static str,
+}
+
+impl Localizer {
+    // None when the request asked for neither a zone nor a language
+    pub fn from_headers(zone: Option<&str>, accept_language: Option<&str>) -> Result<Option<Self>, String> {
+        if zone.is_none() && accept_language.is_none() {
+            return Ok(None);
+        }
+        let zone = zone.map(parse_zone).transpose()?.unwrap_or(Tz::UTC);
+        let locale = accept_language.and_then(parse_accept_language);
+        Ok(Some(Localizer {
+            zone,
+            locale: locale.map(|(tag, _)| tag),
+            format: locale.map_or(DEFAULT_FORMAT, |(_, format)| format),
+        }))
+    }
+
+    pub fn localize(&self, rfc3339: &str) -> Option<serde_json::Value> {
+        let local = DateTime::parse_from_rfc3339(rfc3339).ok()?.with_timezone(&self.zone);
+        Some(serde_json::json!({
+            "timezone": self.zone.name(),
+            "locale": self.locale,
+            "rfc3339": local.to_rfc3339(),
+            "utc_offset": local.format("%:z").to_string(),
+            "display": local.format(self.format).to_string()
+        }))
+    }
+
+    // Adds `<field>_local` next to every timestamp field, at any depth
+    pub fn apply(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(fields) => {
+                let localized: Vec<(String, serde_json::Value)> = fields
+                    .iter()
+                    .filter(|(key, _)| *key == "timestamp" || key.ends_with("_at"))
+                    .filter_map(|(key, v)| Some((format!("{}_local", key), self.localize(v.as_str()?)?)))
+                    .collect();
+                for child in fields.values_mut() {
+                    self.apply(child);
+                }
+                fields.extend(localized);
+            }
+            serde_json::Value::Array(items) => items.iter_mut().for_each(|item| self.apply(item)),
+            _ => {}
+        }
+    }
+}
+
+fn is_localized_path(path: &str) -> bool {
+    LOCALIZED_PREFIXES
+        .iter()
+        .any(|prefix| path.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/')))
+}
+
+pub async fn localize_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    if !is_localized_path(req.path()) {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+    let header_value = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+    let (zone, accept_language) = (header_value(TIMEZONE_HEADER), header_value(header::ACCEPT_LANGUAGE.as_str()));
+    let localizer = match Localizer::from_headers(zone.as_deref(), accept_language.as_deref()) {
+        Ok(Some(localizer)) => localizer,
+        Ok(None) => return Ok(next.call(req).await?.map_into_boxed_body()),
+        Err(e) => {
+            let res = HttpResponse::BadRequest().json(serde_json::json!({ "status": "error", "error": e }));
+            return Ok(req.into_response(res).map_into_boxed_body());
+        }
+    };
+
+    let res = next.call(req).await?;
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if !is_json {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let (http_req, res) = res.into_parts();
+    let (mut res, res_body) = res.into_parts();
+    let bytes = body::to_bytes(res_body).await.map_err(|e| {
+        let e: Box<dyn std::error::Error> = e.into();
+        actix_web::error::ErrorInternalServerError(e.to_string())
+    })?;
+    let bytes = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(mut value) => {
+            localizer.apply(&mut value);
+            serde_json::to_vec(&value).map(Into::into).unwrap_or(bytes)
+        }
+        Err(_) => bytes,
+    };
+    res.headers_mut()
+        .append(header::VARY, HeaderValue::from_static("X-Timezone, Accept-Language"));
+    Ok(ServiceResponse::new(http_req, res.set_body(BoxBody::new(bytes))))
+}
@@ -0,0 +1,150 @@
+// Typed Redis Cluster topology: nodes, slot ranges and CLUSTER INFO
+//
+// These are the shapes the /redis/cluster/* endpoints return, with Serialize and Deserialize,
+// so the CLI and dashboard can read the API responses into the same types instead of picking
+// through JSON. redis_parse builds them from the raw replies. Like redis_parse, the module
+// depends only on serde and serde_json.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+pub const CLUSTER_SLOTS: u16 = 16384;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlotRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl SlotRange {
+    // Only ranges inside the slot space with start <= end exist
+    pub fn new(start: i64, end: i64) -> Option<Self> {
+        let valid = 0 <= start && start <= end && end < CLUSTER_SLOTS as i64;
+        valid.then_some(SlotRange { start: start as u16, end: end as u16 })
+    }
+
+    pub fn count(&self) -> usize {
+        (self.end - self.start) as usize + 1
+    }
+
+    pub fn contains(&self, slot: u16) -> bool {
+        (self.start..=self.end).contains(&slot)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeRole {
+    Master,
+    Replica,
+    Unknown,
+}
+
+impl NodeRole {
+    pub fn from_flags(flags: &[String]) -> Self {
+        if flags.iter().any(|f| f == "master") {
+            NodeRole::Master
+        } else if flags.iter().any(|f| f == "slave") {
+            NodeRole::Replica
+        } else {
+            NodeRole::Unknown
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClusterNode {
+    pub node_id: String,
+    pub host: String,
+    // 0 when the address had no usable port
+    pub port: u16,
+    pub role: NodeRole,
+    pub flags: Vec<String>,
+    pub master_id: Option<String>,
+    pub ping_sent: String,
+    pub pong_recv: String,
+    pub config_epoch: u64,
+    pub link_state: String,
+    pub slots_count: usize,
+    pub slot_ranges: Vec<SlotRange>,
+    // Raw `[slot->-peer]` / `[slot-<-peer]` fields
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub migrations: Vec<String>,
+}
+
+impl ClusterNode {
+    // host:port, as redis_node_connection expects it
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|f| f == flag)
+    }
+
+    pub fn is_master(&self) -> bool {
+        self.role == NodeRole::Master
+    }
+
+    pub fn is_replica(&self) -> bool {
+        self.role == NodeRole::Replica
+    }
+
+    // Confirmed (fail) or suspected (fail?) by the cluster
+    pub fn is_failed(&self) -> bool {
+        self.flags.iter().any(|f| f.starts_with("fail"))
+    }
+
+    pub fn serves(&self, slot: u16) -> bool {
+        self.slot_ranges.iter().any(|range| range.contains(slot))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlotNode {
+    pub host: String,
+    pub port: i64,
+    pub node_id: String,
+}
+
+// One entry of CLUSTER SLOTS
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlotAssignment {
+    pub start_slot: u16,
+    pub end_slot: u16,
+    pub slots_count: usize,
+    // None when the master entry is malformed
+    pub master: Option<SlotNode>,
+    pub replicas: Vec<SlotNode>,
+}
+
+impl SlotAssignment {
+    pub fn new(range: SlotRange, master: Option<SlotNode>, replicas: Vec<SlotNode>) -> Self {
+        SlotAssignment { start_slot: range.start, end_slot: range.end, slots_count: range.count(), master, replicas }
+    }
+}
+
+// Percentage of the slot space covered, to two decimals
+pub fn coverage_percentage(assignments: &[SlotAssignment]) -> f64 {
+    let total: usize = assignments.iter().map(|a| a.slots_count).sum();
+    ((total as f64 / CLUSTER_SLOTS as f64) * 100.0 * 100.0).round() / 100.0
+}
+
+// CLUSTER INFO. Fields a server doesn't report stay at their defaults; everything else
+// (cluster_stats_messages_*, newer fields) is kept in `other`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClusterInfo {
+    pub cluster_state: String,
+    pub cluster_slots_assigned: u64,
+    pub cluster_slots_ok: u64,
+    pub cluster_slots_pfail: u64,
+    pub cluster_slots_fail: u64,
+    pub cluster_known_nodes: u64,
+    pub cluster_size: u64,
+    pub cluster_current_epoch: u64,
+    pub cluster_my_epoch: u64,
+    #[serde(flatten)]
+    pub other: BTreeMap<String, serde_json::Value>,
+}
@@ -0,0 +1,161 @@
+// TOTP codes through Vault's TOTP secrets engine (mounted at totp/)
+//
+// Vault acts as the provider: it generates the shared secret, hands it out once as an otpauth://
+// URL and QR code for the user's authenticator app, and afterwards only answers whether a
+// submitted code is valid. The secret itself never passes through the application again.
+
+use actix_web::{web, HttpResponse, Responder};
+use serde::Deserialize;
+
+use crate::vault::{self, VaultError};
+
+const DEFAULT_ISSUER: &str = "devstack-core";
+const DEFAULT_PERIOD: u32 = 30;
+const DEFAULT_DIGITS: u32 = 6;
+const MAX_KEY_NAME_LEN: usize = 64;
+
+#[derive(Deserialize)]
+pub struct CreateKeyRequest {
+    account_name: String,
+    issuer: Option<String>,
+    period: Option<u32>,
+    digits: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct ValidateRequest {
+    code: String,
+}
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+fn vault_error_response(e: VaultError) -> HttpResponse {
+    error_response(e.status(), e.into_message())
+}
+
+pub fn valid_key_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_KEY_NAME_LEN
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// Vault supports 6 or 8 digits; periods beyond a few minutes defeat the point of TOTP
+pub fn validate_key_params(digits: u32, period: u32) -> Result<(), String> {
+    if digits != 6 && digits != 8 {
+        return Err("digits must be 6 or 8".to_string());
+    }
+    if !(15..=300).contains(&period) {
+        return Err("period must be between 15 and 300 seconds".to_string());
+    }
+    Ok(())
+}
+
+pub fn valid_code(code: &str) -> bool {
+    matches!(code.len(), 6 | 8) && code.chars().all(|c| c.is_ascii_digit())
+}
+
+fn checked_key(path: web::Path<String>) -> Result<String, Box<HttpResponse>> {
+    let key = path.into_inner();
+    if valid_key_name(&key) {
+        Ok(key)
+    } else {
+        Err(Box::new(error_response(
+            actix_web::http::StatusCode::BAD_REQUEST,
+            format!("Key names are 1-{} letters, digits, '-' or '_'", MAX_KEY_NAME_LEN),
+        )))
+    }
+}
+
+// Creates a Vault-generated key; the response is the only time the secret is shown
+pub async fn create_key(path: web::Path<String>, body: web::Json<CreateKeyRequest>) -> impl Responder {
+    let key = match checked_key(path) {
+        Ok(key) => key,
+        Err(resp) => return *resp,
+    };
+    let digits = body.digits.unwrap_or(DEFAULT_DIGITS);
+    let period = body.period.unwrap_or(DEFAULT_PERIOD);
+    if let Err(e) = validate_key_params(digits, period) {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, e);
+    }
+    if body.account_name.trim().is_empty() {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, "account_name is required".to_string());
+    }
+
+    let request = serde_json::json!({
+        "generate": true,
+        "exported": true,
+        "issuer": body.issuer.as_deref().unwrap_or(DEFAULT_ISSUER),
+        "account_name": body.account_name.trim(),
+        "period": period,
+        "digits": digits
+    });
+    match vault::totp_request(reqwest::Method::POST, &format!("keys/{}", key), Some(&request)).await {
+        Ok(data) => HttpResponse::Created().json(serde_json::json!({
+            "status": "created",
+            "key": key,
+            "url": data["url"],
+            // Base64 PNG of the otpauth:// URL, for scanning into an authenticator app
+            "barcode": data["barcode"]
+        })),
+        Err(e) => vault_error_response(e),
+    }
+}
+
+pub async fn get_key(path: web::Path<String>) -> impl Responder {
+    let key = match checked_key(path) {
+        Ok(key) => key,
+        Err(resp) => return *resp,
+    };
+    match vault::totp_request(reqwest::Method::GET, &format!("keys/{}", key), None).await {
+        Ok(data) => HttpResponse::Ok().json(serde_json::json!({ "key": key, "config": data })),
+        Err(e) => vault_error_response(e),
+    }
+}
+
+pub async fn delete_key(path: web::Path<String>) -> impl Responder {
+    let key = match checked_key(path) {
+        Ok(key) => key,
+        Err(resp) => return *resp,
+    };
+    match vault::totp_request(reqwest::Method::DELETE, &format!("keys/{}", key), None).await {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({ "status": "deleted", "key": key })),
+        Err(e) => vault_error_response(e),
+    }
+}
+
+// The current code, as an authenticator app would show it (for testing the validate step)
+pub async fn generate_code(path: web::Path<String>) -> impl Responder {
+    let key = match checked_key(path) {
+        Ok(key) => key,
+        Err(resp) => return *resp,
+    };
+    match vault::totp_request(reqwest::Method::GET, &format!("code/{}", key), None).await {
+        Ok(data) => HttpResponse::Ok().json(serde_json::json!({ "key": key, "code": data["code"] })),
+        Err(e) => vault_error_response(e),
+    }
+}
+
+// Vault rejects a code that was already accepted once within its period, so replays fail
+pub async fn validate_code(path: web::Path<String>, body: web::Json<ValidateRequest>) -> impl Responder {
+    let key = match checked_key(path) {
+        Ok(key) => key,
+        Err(resp) => return *resp,
+    };
+    let code = body.code.trim();
+    if !valid_code(code) {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, "code must be 6 or 8 digits".to_string());
+    }
+    let request = serde_json::json!({ "code": code });
+    match vault::totp_request(reqwest::Method::POST, &format!("code/{}", key), Some(&request)).await {
+        Ok(data) => HttpResponse::Ok().json(serde_json::json!({
+            "key": key,
+            "valid": data["valid"].as_bool().unwrap_or(false)
+        })),
+        Err(VaultError::Rejected(e)) if e.contains("already used") => {
+            HttpResponse::Ok().json(serde_json::json!({ "key": key, "valid": false, "reason": e }))
+        }
+        Err(e) => vault_error_response(e),
+    }
+}
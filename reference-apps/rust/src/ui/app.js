@@ -0,0 +1,125 @@
+// Polls the API's own JSON endpoints; no build step or external dependencies
+const POLL_MS = 5000;
+const QUEUES_KEY = "devstack.ui.queues";
+
+function badge(text, cls) {
+  const span = document.createElement("span");
+  span.className = "badge " + (cls || text);
+  span.textContent = text;
+  return span;
+}
+
+function cell(row, value) {
+  const td = document.createElement("td");
+  if (value instanceof Node) {
+    td.appendChild(value);
+  } else {
+    td.textContent = value === null || value === undefined ? "-" : value;
+  }
+  row.appendChild(td);
+}
+
+async function getJson(path) {
+  const res = await fetch(path, { headers: { Accept: "application/json" } });
+  return res.json();
+}
+
+async function refreshHealth() {
+  const container = document.getElementById("services");
+  try {
+    const health = await getJson("/health/all");
+    document.getElementById("overall").replaceWith(
+      Object.assign(badge(health.status), { id: "overall" })
+    );
+    container.replaceChildren(
+      ...Object.entries(health.services || {}).map(([name, detail]) => {
+        const card = document.createElement("div");
+        card.className = "card";
+        const title = document.createElement("div");
+        title.className = "name";
+        title.textContent = name + " ";
+        title.appendChild(badge(detail.status || "unknown"));
+        card.appendChild(title);
+        if (detail.error) {
+          const error = document.createElement("div");
+          error.className = "muted";
+          error.textContent = detail.error;
+          card.appendChild(error);
+        }
+        return card;
+      })
+    );
+  } catch (e) {
+    container.textContent = "Health check failed: " + e;
+  }
+}
+
+async function refreshRedis() {
+  const body = document.getElementById("redis-nodes");
+  try {
+    const topology = await getJson("/redis/cluster/nodes");
+    if (topology.status !== "success") {
+      body.replaceChildren();
+      const row = body.insertRow();
+      cell(row, topology.error || "unavailable");
+      return;
+    }
+    const byId = Object.fromEntries(topology.nodes.map((n) => [n.node_id, n.host + ":" + n.port]));
+    body.replaceChildren(
+      ...topology.nodes
+        .sort((a, b) => (a.host + a.port).localeCompare(b.host + b.port))
+        .map((node) => {
+          const row = document.createElement("tr");
+          cell(row, node.host + ":" + node.port);
+          cell(row, badge(node.role));
+          cell(row, badge(node.link_state));
+          cell(row, node.slots_count);
+          cell(row, node.master_id ? byId[node.master_id] || node.master_id : null);
+          return row;
+        })
+    );
+  } catch (e) {
+    body.textContent = "Redis topology failed: " + e;
+  }
+}
+
+function watchedQueues() {
+  const fromQuery = new URLSearchParams(location.search).get("queues");
+  const value = fromQuery !== null ? fromQuery : localStorage.getItem(QUEUES_KEY) || "";
+  return value.split(",").map((q) => q.trim()).filter(Boolean);
+}
+
+async function refreshQueues() {
+  const body = document.getElementById("queues");
+  const rows = await Promise.all(
+    watchedQueues().map(async (queue) => {
+      const row = document.createElement("tr");
+      cell(row, queue);
+      try {
+        const info = await getJson("/examples/messaging/queue/" + encodeURIComponent(queue) + "/info");
+        cell(row, info.exists ? info.message_count : badge("missing", "error"));
+        cell(row, info.exists ? info.consumer_count : null);
+      } catch (e) {
+        cell(row, badge("error"));
+        cell(row, null);
+      }
+      return row;
+    })
+  );
+  body.replaceChildren(...rows);
+}
+
+async function refresh() {
+  await Promise.all([refreshHealth(), refreshRedis(), refreshQueues()]);
+  document.getElementById("updated").textContent = "updated " + new Date().toLocaleTimeString();
+}
+
+document.getElementById("queue-names").value = watchedQueues().join(", ");
+document.getElementById("queue-form").addEventListener("submit", (event) => {
+  event.preventDefault();
+  localStorage.setItem(QUEUES_KEY, document.getElementById("queue-names").value);
+  refreshQueues();
+});
+
+refresh();
+setInterval(refresh, POLL_MS);
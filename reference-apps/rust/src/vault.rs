@@ -0,0 +1,480 @@
+// Vault client helpers: KV v2 secret reads with an in-process cache, plus request metrics
+//
+// Degradation mode: every secret that was read successfully is also kept as last-known-good.
+// When Vault can't be reached (connection error, sealed, 5xx), a read falls back to that copy
+// for up to VAULT_STALE_LIMIT seconds (default 3600, 0 turns degradation off) after it was
+// fetched, and only fails once the copy is older than that. Responses to requests that used a
+// stale secret carry `"stale_credentials": true` and an X-Stale-Credentials header. A 403 or 404
+// from Vault is an answer, not an outage, and never falls back.
+
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use actix_web::middleware::Next;
+use base64::Engine;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::hedging;
+use crate::redact;
+use crate::resolve;
+use crate::vault_access::{self, Access};
+use crate::{
+    get_env_or, VAULT_REQUESTS_TOTAL, VAULT_REQUEST_DURATION, VAULT_SECRET_CACHE_TOTAL, VAULT_TOKEN_TTL,
+};
+
+lazy_static! {
+    // service -> (fetched_at, secret data)
+    static ref SECRET_CACHE: Mutex<HashMap<String, (Instant, serde_json::Value)>> = Mutex::new(HashMap::new());
+    // service -> (fetched_at, secret data); survives token renewal, only replaced by a newer read
+    static ref LAST_KNOWN_GOOD: Mutex<HashMap<String, (Instant, serde_json::Value)>> = Mutex::new(HashMap::new());
+}
+
+pub const STALE_CREDENTIALS_HEADER: &str = "x-stale-credentials";
+
+tokio::task_local! {
+    // Set when a secret read during the request was served from last-known-good
+    static SERVED_STALE: Cell<bool>;
+}
+
+fn vault_addr() -> String {
+    resolve::url(&get_env_or("VAULT_ADDR", "http://vault:8200"))
+}
+
+fn vault_token() -> String {
+    get_env_or("VAULT_TOKEN", "")
+}
+
+fn secret_cache_ttl() -> Duration {
+    Duration::from_secs(get_env_or("VAULT_SECRET_CACHE_TTL", "60").parse().unwrap_or(60))
+}
+
+fn stale_limit() -> Duration {
+    Duration::from_secs(get_env_or("VAULT_STALE_LIMIT", "3600").parse().unwrap_or(3600))
+}
+
+// Record one Vault round trip in vault_requests_total and the latency histogram
+pub fn record_vault_request(operation: &str, success: bool, started: Instant) {
+    let status = if success { "success" } else { "error" };
+    VAULT_REQUESTS_TOTAL.with_label_values(&[operation, status]).inc();
+    VAULT_REQUEST_DURATION
+        .with_label_values(&[operation])
+        .observe(started.elapsed().as_secs_f64());
+}
+
+// Metrics plus an entry in the access ledger for one Vault round trip
+fn record_round_trip(operation: &str, path: &str, success: bool, started: Instant) {
+    record_vault_request(operation, success, started);
+    vault_access::record(path, if success { Access::Vault } else { Access::Error });
+}
+
+// Where hedged secret reads go; the primary address unless VAULT_HEDGE_ADDR points elsewhere
+fn vault_hedge_addr() -> String {
+    match get_env_or("VAULT_HEDGE_ADDR", "") {
+        addr if addr.is_empty() => vault_addr(),
+        addr => resolve::url(&addr),
+    }
+}
+
+async fn fetch_vault_secret(service: &str) -> Result<serde_json::Value, VaultError> {
+    let path = format!("secret/data/{}", service);
+    let token = vault_token();
+    let (addr, hedge_addr) = (vault_addr(), vault_hedge_addr());
+    let primary = vault_request_at(&addr, reqwest::Method::GET, &path, &token, None, None);
+    let data = hedging::hedged("vault_secret", primary, || {
+        vault_request_at(&hedge_addr, reqwest::Method::GET, &path, &token, None, None)
+    })
+    .await?;
+    Ok(data["data"]["data"].clone())
+}
+
+// The last-known-good copy of a secret, if it was fetched no longer than `limit` ago
+pub fn stale_fallback(
+    copies: &HashMap<String, (Instant, serde_json::Value)>,
+    service: &str,
+    limit: Duration,
+) -> Option<(Duration, serde_json::Value)> {
+    let (fetched_at, value) = copies.get(service)?;
+    let age = fetched_at.elapsed();
+    (!limit.is_zero() && age <= limit).then(|| (age, value.clone()))
+}
+
+pub async fn get_vault_secret(service: &str) -> Result<serde_json::Value, String> {
+    let ttl = secret_cache_ttl();
+    let path = format!("secret/data/{}", service);
+
+    if !ttl.is_zero() {
+        let cache = SECRET_CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some((fetched_at, value)) = cache.get(service) {
+            if fetched_at.elapsed() < ttl {
+                VAULT_SECRET_CACHE_TOTAL.with_label_values(&["hit"]).inc();
+                vault_access::record(&path, Access::Cache);
+                return Ok(value.clone());
+            }
+        }
+        VAULT_SECRET_CACHE_TOTAL.with_label_values(&["miss"]).inc();
+    }
+
+    let started = Instant::now();
+    let result = fetch_vault_secret(service).await;
+    record_round_trip("read_secret", &path, result.is_ok(), started);
+
+    match result {
+        Ok(value) => {
+            redact::register_vault_secret(&value);
+            let now = Instant::now();
+            if !ttl.is_zero() {
+                let mut cache = SECRET_CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                cache.insert(service.to_string(), (now, value.clone()));
+            }
+            let mut copies = LAST_KNOWN_GOOD.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            copies.insert(service.to_string(), (now, value.clone()));
+            Ok(value)
+        }
+        Err(VaultError::Unavailable(e)) => {
+            let copies = LAST_KNOWN_GOOD.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let Some((age, value)) = stale_fallback(&copies, service, stale_limit()) else {
+                return Err(e);
+            };
+            VAULT_SECRET_CACHE_TOTAL.with_label_values(&["stale"]).inc();
+            log::warn!("{}; serving {} from last-known-good ({}s old)", e, path, age.as_secs());
+            let _ = SERVED_STALE.try_with(|stale| stale.set(true));
+            Ok(value)
+        }
+        Err(e) => Err(e.into_message()),
+    }
+}
+
+// Remaining TTL of the app's Vault token (auth/token/lookup-self); 0 means non-expiring
+pub async fn lookup_token_ttl() -> Result<i64, String> {
+    let started = Instant::now();
+    let result = async {
+        let response = reqwest::Client::new()
+            .get(format!("{}/v1/auth/token/lookup-self", vault_addr()))
+            .header("X-Vault-Token", vault_token())
+            .send()
+            .await
+            .map_err(|e| format!("Vault request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            return Err(format!("Vault returned status: {}", response.status()));
+        }
+
+        let data: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse Vault response: {}", e))?;
+        data["data"]["ttl"]
+            .as_i64()
+            .ok_or_else(|| "Token lookup response has no ttl".to_string())
+    }
+    .await;
+    record_round_trip("token_lookup", "auth/token/lookup-self", result.is_ok(), started);
+    result
+}
+
+// Extends the app's token (auth/token/renew-self) and drops cached secrets so the next read
+// fetches current values; returns the new TTL
+pub async fn renew_token() -> Result<i64, String> {
+    let started = Instant::now();
+    let result = async {
+        let response = reqwest::Client::new()
+            .post(format!("{}/v1/auth/token/renew-self", vault_addr()))
+            .header("X-Vault-Token", vault_token())
+            .send()
+            .await
+            .map_err(|e| format!("Vault request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            return Err(format!("Vault returned status: {}", response.status()));
+        }
+
+        let data: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse Vault response: {}", e))?;
+        data["auth"]["lease_duration"]
+            .as_i64()
+            .ok_or_else(|| "Token renewal response has no lease_duration".to_string())
+    }
+    .await;
+    record_round_trip("token_renew", "auth/token/renew-self", result.is_ok(), started);
+
+    if let Ok(ttl) = result {
+        VAULT_TOKEN_TTL.set(ttl);
+        SECRET_CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+    }
+    result
+}
+
+// POST to the Transit engine and return the response's `data` object
+async fn transit_request(path: &str, body: serde_json::Value) -> Result<serde_json::Value, String> {
+    let response = reqwest::Client::new()
+        .post(format!("{}/v1/transit/{}", vault_addr(), path))
+        .header("X-Vault-Token", vault_token())
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| format!("Vault request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!("Vault returned status: {}", response.status()));
+    }
+
+    let data: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Vault response: {}", e))?;
+    Ok(data["data"].clone())
+}
+
+fn decode_plaintext(data: &serde_json::Value) -> Result<Vec<u8>, String> {
+    let plaintext = data["plaintext"].as_str().ok_or_else(|| "Transit response has no plaintext".to_string())?;
+    base64::engine::general_purpose::STANDARD
+        .decode(plaintext)
+        .map_err(|e| format!("Transit plaintext is not base64: {}", e))
+}
+
+// New data key from transit/datakey/plaintext/<key>: (plaintext key, key wrapped by Transit)
+pub async fn transit_data_key(key_name: &str) -> Result<(Vec<u8>, String), String> {
+    let started = Instant::now();
+    let result = async {
+        let data = transit_request(&format!("datakey/plaintext/{}", key_name), serde_json::json!({ "bits": 256 })).await?;
+        let wrapped = data["ciphertext"]
+            .as_str()
+            .ok_or_else(|| "Transit response has no ciphertext".to_string())?
+            .to_string();
+        Ok((decode_plaintext(&data)?, wrapped))
+    }
+    .await;
+    let path = format!("transit/datakey/plaintext/{}", key_name);
+    record_round_trip("transit_datakey", &path, result.is_ok(), started);
+    result
+}
+
+// Unwraps a data key produced by transit_data_key
+pub async fn transit_decrypt(key_name: &str, ciphertext: &str) -> Result<Vec<u8>, String> {
+    let started = Instant::now();
+    let result = async {
+        let data = transit_request(&format!("decrypt/{}", key_name), serde_json::json!({ "ciphertext": ciphertext })).await?;
+        decode_plaintext(&data)
+    }
+    .await;
+    record_round_trip("transit_decrypt", &format!("transit/decrypt/{}", key_name), result.is_ok(), started);
+    result
+}
+
+// Vault's error messages from an `{"errors": [...]}` body, or the status when there are none
+pub fn vault_error_message(status: reqwest::StatusCode, body: &serde_json::Value) -> String {
+    let errors: Vec<&str> = body["errors"]
+        .as_array()
+        .map(|errors| errors.iter().filter_map(|e| e.as_str()).collect())
+        .unwrap_or_default();
+    if errors.is_empty() {
+        format!("Vault returned status: {}", status)
+    } else {
+        format!("Vault returned status {}: {}", status, errors.join("; "))
+    }
+}
+
+#[derive(Debug)]
+pub enum VaultError {
+    // Vault refused the request: bad input, an unknown key, or a token that is invalid or used up
+    Rejected(String),
+    NotFound(String),
+    Unavailable(String),
+}
+
+impl VaultError {
+    pub fn status(&self) -> actix_web::http::StatusCode {
+        match self {
+            VaultError::Rejected(_) => actix_web::http::StatusCode::BAD_REQUEST,
+            VaultError::NotFound(_) => actix_web::http::StatusCode::NOT_FOUND,
+            VaultError::Unavailable(_) => actix_web::http::StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    pub fn into_message(self) -> String {
+        match self {
+            VaultError::Rejected(e) | VaultError::NotFound(e) | VaultError::Unavailable(e) => e,
+        }
+    }
+}
+
+// Sends a request to /v1/<path> and returns the whole response body (Null for 204s)
+async fn vault_request(
+    method: reqwest::Method,
+    path: &str,
+    token: &str,
+    wrap_ttl: Option<&str>,
+    body: Option<&serde_json::Value>,
+) -> Result<serde_json::Value, VaultError> {
+    vault_request_at(&vault_addr(), method, path, token, wrap_ttl, body).await
+}
+
+async fn vault_request_at(
+    addr: &str,
+    method: reqwest::Method,
+    path: &str,
+    token: &str,
+    wrap_ttl: Option<&str>,
+    body: Option<&serde_json::Value>,
+) -> Result<serde_json::Value, VaultError> {
+    let mut request = reqwest::Client::new()
+        .request(method, format!("{}/v1/{}", addr, path))
+        .header("X-Vault-Token", token);
+    if let Some(ttl) = wrap_ttl {
+        request = request.header("X-Vault-Wrap-TTL", ttl);
+    }
+    if let Some(body) = body {
+        request = request.json(body);
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| VaultError::Unavailable(format!("Vault request failed: {}", e)))?;
+
+    let status = response.status();
+    let data: serde_json::Value = response.json().await.unwrap_or(serde_json::Value::Null);
+    match status {
+        s if s.is_success() => Ok(data),
+        s if s == reqwest::StatusCode::BAD_REQUEST || s == reqwest::StatusCode::FORBIDDEN => {
+            Err(VaultError::Rejected(vault_error_message(s, &data)))
+        }
+        s if s == reqwest::StatusCode::NOT_FOUND => Err(VaultError::NotFound(vault_error_message(s, &data))),
+        s => Err(VaultError::Unavailable(vault_error_message(s, &data))),
+    }
+}
+
+// Stores `data` in a single-use cubbyhole (sys/wrapping/wrap) and returns its wrap_info:
+// token, accessor, ttl, creation_time and creation_path
+pub async fn wrap_data(data: &serde_json::Value, ttl: &str) -> Result<serde_json::Value, VaultError> {
+    let started = Instant::now();
+    let result = vault_request(reqwest::Method::POST, "sys/wrapping/wrap", &vault_token(), Some(ttl), Some(data))
+        .await
+        .map(|response| response["wrap_info"].clone());
+    record_round_trip("wrap", "sys/wrapping/wrap", result.is_ok(), started);
+    result
+}
+
+// Redeems a wrapping token. It authenticates itself, so the recipient needs no Vault token of
+// its own, and Vault deletes the cubbyhole on the first unwrap.
+pub async fn unwrap_token(wrapping_token: &str) -> Result<serde_json::Value, VaultError> {
+    let started = Instant::now();
+    let result = vault_request(reqwest::Method::POST, "sys/wrapping/unwrap", wrapping_token, None, None)
+        .await
+        .map(|response| response["data"].clone());
+    record_round_trip("unwrap", "sys/wrapping/unwrap", result.is_ok(), started);
+    if let Ok(data) = &result {
+        redact::register_vault_secret(data);
+    }
+    result
+}
+
+// Calls the TOTP engine (mounted at totp/) with the app's token; returns the response's `data`
+pub async fn totp_request(
+    method: reqwest::Method,
+    path: &str,
+    body: Option<&serde_json::Value>,
+) -> Result<serde_json::Value, VaultError> {
+    let started = Instant::now();
+    let path = format!("totp/{}", path);
+    let result = vault_request(method, &path, &vault_token(), None, body)
+        .await
+        .map(|response| response["data"].clone());
+    record_round_trip("totp", &path, result.is_ok(), started);
+    result
+}
+
+// Periodically refresh the vault_token_ttl_seconds gauge so alerts can fire before expiry
+pub fn spawn_token_ttl_monitor() {
+    let interval_secs: u64 = get_env_or("VAULT_TOKEN_TTL_CHECK_SECONDS", "30").parse().unwrap_or(30);
+    if interval_secs == 0 {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs));
+        loop {
+            ticker.tick().await;
+            match lookup_token_ttl().await {
+                Ok(ttl) => VAULT_TOKEN_TTL.set(ttl),
+                Err(e) => log::debug!("Vault token TTL lookup failed: {}", e),
+            }
+        }
+    });
+}
+
+// Marks responses built from stale credentials: the header always, plus `stale_credentials`
+// in JSON object bodies
+pub async fn stale_credentials_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let (res, stale) = SERVED_STALE
+        .scope(Cell::new(false), async {
+            let res = next.call(req).await;
+            (res, SERVED_STALE.with(Cell::get))
+        })
+        .await;
+    let res = res?;
+    if !stale {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let is_json = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let (http_req, res) = res.into_parts();
+    let (mut res, res_body) = res.into_parts();
+    res.headers_mut()
+        .insert(HeaderName::from_static(STALE_CREDENTIALS_HEADER), HeaderValue::from_static("true"));
+    if !is_json {
+        return Ok(ServiceResponse::new(http_req, res.set_body(res_body).map_into_boxed_body()));
+    }
+    let bytes = body::to_bytes(res_body).await.map_err(|e| {
+        let e: Box<dyn std::error::Error> = e.into();
+        actix_web::error::ErrorInternalServerError(e.to_string())
+    })?;
+    let bytes = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(serde_json::Value::Object(mut fields)) => {
+            fields.insert("stale_credentials".to_string(), serde_json::Value::Bool(true));
+            serde_json::to_vec(&fields).map(Into::into).unwrap_or(bytes)
+        }
+        _ => bytes,
+    };
+    Ok(ServiceResponse::new(http_req, res.set_body(BoxBody::new(bytes))))
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct StaleSecret {
+    pub service: String,
+    pub age_seconds: u64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DegradationStatus {
+    pub stale_limit_seconds: u64,
+    // Secrets that could be served if Vault went away now
+    pub last_known_good: Vec<StaleSecret>,
+}
+
+pub fn degradation_status() -> DegradationStatus {
+    let limit = stale_limit();
+    let copies = LAST_KNOWN_GOOD.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut last_known_good: Vec<StaleSecret> = copies
+        .keys()
+        .filter_map(|service| {
+            stale_fallback(&copies, service, limit)
+                .map(|(age, _)| StaleSecret { service: service.clone(), age_seconds: age.as_secs() })
+        })
+        .collect();
+    last_known_good.sort_by(|a, b| a.service.cmp(&b.service));
+    DegradationStatus { stale_limit_seconds: limit.as_secs(), last_known_good }
+}
@@ -0,0 +1,192 @@
+// Ledger of the Vault paths this process touches
+//
+// Every secret lookup is recorded by path with where the answer came from: the in-process secret
+// cache, a round trip to Vault, or a failed round trip. The ledger shows which secrets the app
+// depends on, how often it asks for them and whether VAULT_SECRET_CACHE_TTL is doing its job.
+// Counts are kept in memory; with VAULT_ACCESS_LOG_REDIS=true they are also added to Redis so
+// GET /admin/vault-access-log?scope=redis covers every replica.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use actix_web::{web, HttpResponse, Responder};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::{get_env_or, redis_connection};
+
+const REDIS_PATHS_KEY: &str = "vault:access:paths";
+const REDIS_ENTRY_PREFIX: &str = "vault:access:path:";
+
+lazy_static! {
+    static ref LEDGER: Mutex<HashMap<String, AccessEntry>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Cache,
+    Vault,
+    Error,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AccessEntry {
+    pub path: String,
+    pub cache_hits: u64,
+    pub vault_reads: u64,
+    pub errors: u64,
+    pub first_access: String,
+    pub last_access: String,
+}
+
+impl AccessEntry {
+    pub fn total(&self) -> u64 {
+        self.cache_hits + self.vault_reads + self.errors
+    }
+
+    pub fn apply(&mut self, access: Access, now: &str) {
+        match access {
+            Access::Cache => self.cache_hits += 1,
+            Access::Vault => self.vault_reads += 1,
+            Access::Error => self.errors += 1,
+        }
+        if self.first_access.is_empty() {
+            self.first_access = now.to_string();
+        }
+        self.last_access = now.to_string();
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AccessLogQuery {
+    scope: Option<String>,
+}
+
+fn redis_enabled() -> bool {
+    get_env_or("VAULT_ACCESS_LOG_REDIS", "false") == "true"
+}
+
+pub fn record(path: &str, access: Access) {
+    let now = chrono::Utc::now().to_rfc3339();
+    {
+        let mut ledger = LEDGER.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let entry = ledger
+            .entry(path.to_string())
+            .or_insert_with(|| AccessEntry { path: path.to_string(), ..Default::default() });
+        entry.apply(access, &now);
+    }
+    if redis_enabled() {
+        let path = path.to_string();
+        // Fire and forget: a slow or missing Redis must not delay secret reads
+        tokio::spawn(async move {
+            if let Err(e) = mirror_to_redis(&path, access, &now).await {
+                log::debug!("Vault access ledger mirror failed: {}", e);
+            }
+        });
+    }
+}
+
+async fn mirror_to_redis(path: &str, access: Access, now: &str) -> Result<(), String> {
+    let field = match access {
+        Access::Cache => "cache_hits",
+        Access::Vault => "vault_reads",
+        Access::Error => "errors",
+    };
+    let key = format!("{}{}", REDIS_ENTRY_PREFIX, path);
+    let mut conn = redis_connection().await?;
+    redis::pipe()
+        .sadd(REDIS_PATHS_KEY, path)
+        .ignore()
+        .hincr(&key, field, 1)
+        .ignore()
+        .cmd("HSETNX")
+        .arg(&key)
+        .arg("first_access")
+        .arg(now)
+        .ignore()
+        .hset(&key, "last_access", now)
+        .ignore()
+        .query_async::<()>(&mut conn)
+        .await
+        .map_err(|e| format!("Redis write failed: {}", e))
+}
+
+async fn redis_snapshot() -> Result<Vec<AccessEntry>, String> {
+    let mut conn = redis_connection().await?;
+    let paths: Vec<String> = redis::cmd("SMEMBERS")
+        .arg(REDIS_PATHS_KEY)
+        .query_async(&mut conn)
+        .await
+        .map_err(|e| format!("SMEMBERS failed: {}", e))?;
+    let mut entries = Vec::with_capacity(paths.len());
+    for path in paths {
+        let fields: HashMap<String, String> = redis::cmd("HGETALL")
+            .arg(format!("{}{}", REDIS_ENTRY_PREFIX, path))
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| format!("HGETALL failed: {}", e))?;
+        let count = |name: &str| fields.get(name).and_then(|v| v.parse().ok()).unwrap_or(0);
+        entries.push(AccessEntry {
+            cache_hits: count("cache_hits"),
+            vault_reads: count("vault_reads"),
+            errors: count("errors"),
+            first_access: fields.get("first_access").cloned().unwrap_or_default(),
+            last_access: fields.get("last_access").cloned().unwrap_or_default(),
+            path,
+        });
+    }
+    Ok(entries)
+}
+
+pub fn snapshot() -> Vec<AccessEntry> {
+    LEDGER.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).values().cloned().collect()
+}
+
+// Busiest paths first, with the share of lookups the secret cache answered
+pub fn summarize(mut entries: Vec<AccessEntry>) -> serde_json::Value {
+    entries.sort_by(|a, b| b.total().cmp(&a.total()).then_with(|| a.path.cmp(&b.path)));
+    let cache_hits: u64 = entries.iter().map(|e| e.cache_hits).sum();
+    let total: u64 = entries.iter().map(AccessEntry::total).sum();
+    let ratio = |hits: u64, total: u64| if total == 0 { None } else { Some(hits as f64 / total as f64) };
+    let paths: Vec<serde_json::Value> = entries
+        .iter()
+        .map(|e| {
+            let mut value = serde_json::to_value(e).unwrap_or_default();
+            value["total"] = e.total().into();
+            value["cache_hit_ratio"] = serde_json::json!(ratio(e.cache_hits, e.total()));
+            value
+        })
+        .collect();
+    serde_json::json!({
+        "total_accesses": total,
+        "cache_hit_ratio": ratio(cache_hits, total),
+        "paths": paths
+    })
+}
+
+pub async fn vault_access_log(query: web::Query<AccessLogQuery>) -> impl Responder {
+    let (scope, entries) = match query.scope.as_deref().unwrap_or("local") {
+        "local" => ("local", snapshot()),
+        "redis" if !redis_enabled() => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "status": "error",
+                "error": "Redis mirroring is off; set VAULT_ACCESS_LOG_REDIS=true"
+            }))
+        }
+        "redis" => match redis_snapshot().await {
+            Ok(entries) => ("redis", entries),
+            Err(e) => {
+                return HttpResponse::ServiceUnavailable().json(serde_json::json!({ "status": "error", "error": e }))
+            }
+        },
+        other => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "status": "error",
+                "error": format!("Unknown scope '{}'. Must be one of: local, redis", other)
+            }))
+        }
+    };
+    let mut body = summarize(entries);
+    body["scope"] = scope.into();
+    HttpResponse::Ok().json(body)
+}
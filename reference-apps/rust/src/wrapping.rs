@@ -0,0 +1,108 @@
+// Vault response wrapping: hand a secret to another service without either side logging it
+//
+// The sender wraps a secret and passes on only the wrapping token. The recipient unwraps it
+// once; afterwards the token is useless, so an intercepted token that was already redeemed
+// reveals nothing, and one redeemed by an attacker first shows up as a failed unwrap.
+
+use actix_web::{web, HttpResponse, Responder};
+use serde::Deserialize;
+
+use crate::vault::{self, VaultError};
+
+const DEFAULT_WRAP_TTL: &str = "5m";
+const MAX_WRAP_TTL_SECONDS: u64 = 24 * 60 * 60;
+
+#[derive(Deserialize)]
+pub struct WrapRequest {
+    // Wrap this service's KV secret...
+    service: Option<String>,
+    // ...or an arbitrary JSON object
+    data: Option<serde_json::Value>,
+    ttl: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UnwrapRequest {
+    token: String,
+}
+
+fn error_response(status: actix_web::http::StatusCode, error: String) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "status": "error", "error": error }))
+}
+
+fn vault_error_response(e: VaultError) -> HttpResponse {
+    error_response(e.status(), e.into_message())
+}
+
+// Vault duration strings as accepted here: plain seconds or a number with an s, m or h suffix
+pub fn parse_wrap_ttl(ttl: &str) -> Result<u64, String> {
+    let invalid = || format!("Invalid ttl '{}': use seconds or a duration like 30s, 5m, 1h", ttl);
+    let (digits, unit) = match ttl.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&ttl[..i], c),
+        _ => (ttl, 's'),
+    };
+    let value: u64 = digits.parse().map_err(|_| invalid())?;
+    let seconds = match unit {
+        's' => value,
+        'm' => value.saturating_mul(60),
+        'h' => value.saturating_mul(3600),
+        _ => return Err(invalid()),
+    };
+    if seconds == 0 || seconds > MAX_WRAP_TTL_SECONDS {
+        return Err(format!("ttl must be between 1s and {}h", MAX_WRAP_TTL_SECONDS / 3600));
+    }
+    Ok(seconds)
+}
+
+pub async fn wrap_secret(body: web::Json<WrapRequest>) -> impl Responder {
+    let ttl = body.ttl.as_deref().unwrap_or(DEFAULT_WRAP_TTL);
+    let ttl_seconds = match parse_wrap_ttl(ttl) {
+        Ok(seconds) => seconds,
+        Err(e) => return error_response(actix_web::http::StatusCode::BAD_REQUEST, e),
+    };
+    let data = match (&body.service, &body.data) {
+        (Some(service), None) => match vault::get_vault_secret(service).await {
+            Ok(data) if data.is_object() => data,
+            Ok(_) => {
+                return error_response(
+                    actix_web::http::StatusCode::NOT_FOUND,
+                    format!("No secret stored for service '{}'", service),
+                )
+            }
+            Err(e) => return error_response(actix_web::http::StatusCode::SERVICE_UNAVAILABLE, e),
+        },
+        (None, Some(data)) if data.is_object() => data.clone(),
+        (None, Some(_)) => {
+            return error_response(actix_web::http::StatusCode::BAD_REQUEST, "data must be a JSON object".to_string())
+        }
+        _ => {
+            return error_response(
+                actix_web::http::StatusCode::BAD_REQUEST,
+                "Provide exactly one of 'service' or 'data'".to_string(),
+            )
+        }
+    };
+
+    match vault::wrap_data(&data, &format!("{}s", ttl_seconds)).await {
+        Ok(wrap_info) => HttpResponse::Ok().json(serde_json::json!({
+            "status": "wrapped",
+            "token": wrap_info["token"],
+            "accessor": wrap_info["accessor"],
+            "ttl": wrap_info["ttl"],
+            "creation_time": wrap_info["creation_time"],
+            "creation_path": wrap_info["creation_path"]
+        })),
+        Err(e) => vault_error_response(e),
+    }
+}
+
+pub async fn unwrap_secret(body: web::Json<UnwrapRequest>) -> impl Responder {
+    let token = body.token.trim();
+    if token.is_empty() {
+        return error_response(actix_web::http::StatusCode::BAD_REQUEST, "token is required".to_string());
+    }
+    match vault::unwrap_token(token).await {
+        Ok(data) => HttpResponse::Ok().json(serde_json::json!({ "status": "unwrapped", "data": data })),
+        Err(e) => vault_error_response(e),
+    }
+}